@@ -15,7 +15,27 @@ CREATE TABLE issues (
   html_url VARCHAR NOT NULL,
   url VARCHAR NOT NULL,
   repository_full_name VARCHAR NOT NULL,
+  labels TEXT[] NOT NULL DEFAULT '{}',
+  milestone VARCHAR,
+  author VARCHAR,
   embedding halfvec(2560) NOT NULL,
+  excluded BOOLEAN NOT NULL DEFAULT false,
+  opted_out BOOLEAN NOT NULL DEFAULT false,
+  fingerprint VARCHAR,
+  private BOOLEAN NOT NULL DEFAULT false,
+  gone_at timestamp with time zone,
+  closed_at timestamp with time zone,
+  package_version VARCHAR,
+  python_version VARCHAR,
+  torch_version VARCHAR,
+  os VARCHAR,
+  accelerator VARCHAR,
+  gpu VARCHAR,
+  form_description TEXT,
+  form_reproduction TEXT,
+  form_expected_behavior TEXT,
+  form_environment TEXT,
+  code_context TEXT,
   created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC'),
   updated_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
 );
@@ -26,13 +46,129 @@ CREATE TABLE comments (
   issue_id INT NOT NULL REFERENCES issues(id) ON DELETE CASCADE,
   body TEXT NOT NULL,
   url VARCHAR NOT NULL,
+  author VARCHAR,
+  opted_out BOOLEAN NOT NULL DEFAULT false,
+  is_review BOOLEAN NOT NULL DEFAULT false,
   created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC'),
   updated_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
 );
 
 CREATE INDEX issues_source_id_idx ON issues (source_id);
+CREATE INDEX issues_fingerprint_idx ON issues (fingerprint);
 CREATE INDEX comments_source_id_idx ON comments (source_id);
+CREATE INDEX issues_author_idx ON issues (author);
+CREATE INDEX issues_accelerator_idx ON issues (accelerator);
+CREATE INDEX comments_author_idx ON comments (author);
 CREATE INDEX issues_embedding_hnsw_idx ON issues USING hnsw (embedding halfvec_cosine_ops);
+-- the `binary` vector search quantization also needs migrations/binary_quantization.sql
+
+CREATE TABLE archived_issues (
+  id SERIAL PRIMARY KEY,
+  source_id BIGINT NOT NULL,
+  repository_full_name VARCHAR NOT NULL,
+  data JSONB NOT NULL,
+  archived_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX archived_issues_source_id_idx ON archived_issues (source_id);
+
+CREATE TYPE closure_proposal_status AS ENUM ('pending', 'approved', 'expired');
+
+CREATE TABLE closure_proposals (
+  id SERIAL PRIMARY KEY,
+  issue_source_id BIGINT NOT NULL,
+  issue_url VARCHAR NOT NULL,
+  duplicate_of_html_url VARCHAR NOT NULL,
+  comment_url VARCHAR NOT NULL,
+  status closure_proposal_status NOT NULL DEFAULT 'pending',
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC'),
+  updated_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX closure_proposals_status_idx ON closure_proposals (status);
+
+CREATE TABLE feedback (
+  id SERIAL PRIMARY KEY,
+  run_id VARCHAR NOT NULL,
+  vote VARCHAR NOT NULL,
+  prompt_profile VARCHAR,
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX feedback_run_id_idx ON feedback (run_id);
+
+CREATE TABLE comment_runs (
+  run_id VARCHAR PRIMARY KEY,
+  issue_url VARCHAR NOT NULL,
+  prompt_profile VARCHAR,
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE TABLE pending_comments (
+  id SERIAL PRIMARY KEY,
+  source VARCHAR NOT NULL,
+  repository_full_name VARCHAR NOT NULL,
+  issue_url VARCHAR NOT NULL,
+  body TEXT NOT NULL,
+  awaiting_approval BOOLEAN NOT NULL DEFAULT false,
+  -- message the draft was posted in for approval
+  slack_ts VARCHAR,
+  -- the bot's answer to the issue, edited in place when `comment_queue.update_in_place` is set
+  suggestions BOOLEAN NOT NULL DEFAULT false,
+  attempts INT NOT NULL DEFAULT 0,
+  last_error TEXT,
+  next_attempt_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC'),
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE TYPE issue_link_kind AS ENUM ('cross_reference', 'fixed_by', 'duplicate');
+
+CREATE TABLE issue_links (
+  repository_full_name VARCHAR NOT NULL,
+  number INTEGER NOT NULL,
+  linked_number INTEGER NOT NULL,
+  kind issue_link_kind NOT NULL,
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC'),
+  PRIMARY KEY (repository_full_name, number, linked_number, kind)
+);
+
+CREATE TABLE repository_metadata (
+  full_name VARCHAR PRIMARY KEY,
+  description TEXT,
+  stars BIGINT NOT NULL,
+  topics TEXT[] NOT NULL DEFAULT '{}',
+  updated_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE TABLE settings (
+  key VARCHAR PRIMARY KEY,
+  value VARCHAR NOT NULL,
+  updated_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE TABLE knowledge_base (
+  id SERIAL PRIMARY KEY,
+  repository_full_name VARCHAR,
+  question TEXT NOT NULL,
+  answer TEXT NOT NULL,
+  embedding halfvec(2560) NOT NULL,
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE TABLE api_keys (
+  id SERIAL PRIMARY KEY,
+  name VARCHAR NOT NULL,
+  key_hash VARCHAR NOT NULL UNIQUE,
+  scopes VARCHAR NOT NULL,
+  expires_at timestamp with time zone,
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE TABLE locks (
+  name VARCHAR PRIMARY KEY,
+  holder VARCHAR NOT NULL,
+  expires_at timestamp with time zone NOT NULL
+);
 
 CREATE TYPE job_type AS ENUM ('embeddings_regeneration', 'issue_indexation');
 
@@ -41,9 +177,217 @@ CREATE TABLE jobs (
   job_type job_type NOT NULL,
   repository_full_name VARCHAR UNIQUE,
   data JSONB NOT NULL,
+  paused BOOLEAN NOT NULL DEFAULT false,
   created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC'),
   updated_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
 );
 
 CREATE INDEX jobs_repository_full_name_idx ON jobs (repository_full_name);
 CREATE UNIQUE INDEX jobs_type_embeddings_regeneration_idx ON jobs (job_type) WHERE job_type = 'embeddings_regeneration';
+
+CREATE TYPE event_outcome AS ENUM ('commented', 'skipped', 'error', 'handled', 'queued');
+
+CREATE TABLE event_log (
+  id SERIAL PRIMARY KEY,
+  event_type VARCHAR NOT NULL,
+  description VARCHAR NOT NULL,
+  source_id BIGINT,
+  repository_full_name VARCHAR,
+  issue_number INTEGER,
+  request_id VARCHAR NOT NULL,
+  outcome event_outcome NOT NULL,
+  -- run of the queued comment, see `Storage::mark_events_commented`
+  run_id VARCHAR,
+  error TEXT,
+  embed_ms BIGINT,
+  search_ms BIGINT,
+  summarize_ms BIGINT,
+  comment_ms BIGINT,
+  total_ms BIGINT NOT NULL,
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX event_log_repository_issue_idx ON event_log (repository_full_name, issue_number);
+CREATE INDEX event_log_request_id_idx ON event_log (request_id);
+CREATE INDEX event_log_created_at_idx ON event_log (created_at);
+
+CREATE TABLE suggestion_targets (
+  id SERIAL PRIMARY KEY,
+  repository_full_name VARCHAR NOT NULL,
+  number INT NOT NULL,
+  issue_repository_full_name VARCHAR NOT NULL,
+  issue_number INT NOT NULL,
+  issue_html_url VARCHAR NOT NULL,
+  issue_title TEXT NOT NULL,
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX suggestion_targets_target_idx ON suggestion_targets (repository_full_name, number, created_at);
+
+CREATE TABLE escalations (
+  repository_full_name VARCHAR NOT NULL,
+  number INT NOT NULL,
+  escalated_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC'),
+  PRIMARY KEY (repository_full_name, number)
+);
+
+CREATE TABLE suggestions (
+  issue_source_id BIGINT NOT NULL,
+  repository_full_name VARCHAR NOT NULL,
+  number INT NOT NULL,
+  rank INT NOT NULL,
+  cosine_similarity DOUBLE PRECISION NOT NULL,
+  run_id VARCHAR NOT NULL,
+  prompt_profile VARCHAR,
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC'),
+  PRIMARY KEY (issue_source_id, repository_full_name, number)
+);
+
+CREATE TABLE duplicate_resolutions (
+  issue_source_id BIGINT PRIMARY KEY,
+  repository_full_name VARCHAR NOT NULL,
+  number INT NOT NULL,
+  duplicate_of_number INT NOT NULL,
+  had_suggestions BOOLEAN NOT NULL,
+  -- NULL when the duplicate wasn't among the suggestions
+  suggested_rank INT,
+  resolved_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE TABLE link_similarities (
+  model VARCHAR NOT NULL,
+  repository_full_name VARCHAR NOT NULL,
+  number INT NOT NULL,
+  linked_number INT NOT NULL,
+  cosine_similarity DOUBLE PRECISION NOT NULL,
+  sampled_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC'),
+  PRIMARY KEY (model, repository_full_name, number, linked_number)
+);
+
+CREATE TABLE guidance_sections (
+  id SERIAL PRIMARY KEY,
+  repository_full_name VARCHAR NOT NULL,
+  path VARCHAR NOT NULL,
+  heading TEXT NOT NULL,
+  anchor VARCHAR NOT NULL,
+  content TEXT NOT NULL,
+  content_hash VARCHAR,
+  embedding halfvec(2560) NOT NULL,
+  indexed_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX guidance_sections_repository_idx ON guidance_sections (repository_full_name);
+
+CREATE TABLE repository_cursors (
+  repository_full_name VARCHAR PRIMARY KEY,
+  processed_at timestamp with time zone NOT NULL
+);
+
+CREATE TABLE suppressed_issues (
+  source_id BIGINT PRIMARY KEY,
+  suppressed_by VARCHAR NOT NULL,
+  suppressed_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE TABLE opt_out_requests (
+  id SERIAL PRIMARY KEY,
+  login VARCHAR NOT NULL,
+  requested_through VARCHAR NOT NULL,
+  purged BOOLEAN NOT NULL,
+  issues BIGINT NOT NULL,
+  comments BIGINT NOT NULL,
+  requested_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX opt_out_requests_login_idx ON opt_out_requests (login);
+
+CREATE TABLE embedding_metadata (
+  source_id BIGINT PRIMARY KEY REFERENCES issues(source_id) ON DELETE CASCADE,
+  model VARCHAR NOT NULL,
+  endpoint VARCHAR NOT NULL,
+  input_chars INT NOT NULL,
+  input_tokens INT,
+  truncated BOOLEAN NOT NULL,
+  template VARCHAR,
+  embedded_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE TABLE onboarded_repositories (
+  repository_full_name VARCHAR PRIMARY KEY,
+  hook_id BIGINT,
+  estimated_issues BIGINT NOT NULL,
+  onboarded_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE TABLE comment_idempotency_keys (
+  issue_url VARCHAR NOT NULL,
+  fingerprint VARCHAR NOT NULL,
+  posted BOOLEAN NOT NULL,
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC'),
+  updated_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC'),
+  PRIMARY KEY (issue_url, fingerprint)
+);
+
+CREATE TABLE slack_outbox (
+  id SERIAL PRIMARY KEY,
+  repository_full_name VARCHAR NOT NULL,
+  payload JSONB NOT NULL,
+  attempts INT NOT NULL DEFAULT 0,
+  last_error TEXT,
+  next_attempt_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC'),
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE TABLE slack_batches (
+  id SERIAL PRIMARY KEY,
+  repository_full_name VARCHAR NOT NULL,
+  payload JSONB NOT NULL,
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE TABLE github_response_cache (
+  url VARCHAR PRIMARY KEY,
+  etag VARCHAR NOT NULL,
+  link VARCHAR,
+  body BYTEA NOT NULL,
+  cached_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX github_response_cache_cached_at_idx ON github_response_cache (cached_at);
+
+CREATE TABLE bot_comments (
+  issue_url VARCHAR PRIMARY KEY,
+  comment_url VARCHAR NOT NULL,
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC'),
+  updated_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE TABLE bot_comment_revisions (
+  id SERIAL PRIMARY KEY,
+  issue_url VARCHAR NOT NULL,
+  comment_url VARCHAR NOT NULL,
+  body TEXT NOT NULL,
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX bot_comment_revisions_issue_url_idx ON bot_comment_revisions (issue_url);
+
+CREATE TABLE compaction_runs (
+  id SERIAL PRIMARY KEY,
+  action VARCHAR NOT NULL,
+  dead_tuples BIGINT NOT NULL,
+  index_churn BIGINT NOT NULL,
+  table_bytes_before BIGINT NOT NULL,
+  table_bytes_after BIGINT NOT NULL,
+  index_bytes_before BIGINT NOT NULL,
+  index_bytes_after BIGINT NOT NULL,
+  duration_ms BIGINT NOT NULL,
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX compaction_runs_action_created_at_idx ON compaction_runs (action, created_at);
+
+CREATE TABLE private_repositories (
+  repository_full_name VARCHAR PRIMARY KEY,
+  updated_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
@@ -0,0 +1,187 @@
+use chrono::{DateTime, Utc};
+use nanoid::nanoid;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+use crate::storage::{ApiKey, Database, Storage, StorageError};
+
+const KEY_PREFIX: &str = "lore_";
+
+/// What an API key grants access to, admin keys being granted every scope
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    Admin,
+    Export,
+    Index,
+    Search,
+}
+
+impl Scope {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Admin => "admin",
+            Self::Export => "export",
+            Self::Index => "index",
+            Self::Search => "search",
+        }
+    }
+}
+
+/// Scope required by a route, see [crate::routes::SecretValidator]
+pub trait RequiredScope {
+    const SCOPE: Scope;
+}
+
+pub struct AdminScope;
+
+impl RequiredScope for AdminScope {
+    const SCOPE: Scope = Scope::Admin;
+}
+
+pub struct ExportScope;
+
+impl RequiredScope for ExportScope {
+    const SCOPE: Scope = Scope::Export;
+}
+
+pub struct IndexScope;
+
+impl RequiredScope for IndexScope {
+    const SCOPE: Scope = Scope::Index;
+}
+
+pub struct SearchScope;
+
+impl RequiredScope for SearchScope {
+    const SCOPE: Scope = Scope::Search;
+}
+
+fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+fn grants(key: &ApiKey, scope: Scope, now: DateTime<Utc>) -> bool {
+    key.expires_at.is_none_or(|expires_at| expires_at > now)
+        && key
+            .scopes
+            .split(',')
+            .any(|s| s == scope.as_str() || s == Scope::Admin.as_str())
+}
+
+/// API keys stored in the database
+///
+/// The configuration's `auth_token` remains valid as an admin key, to create the first keys.
+#[derive(Clone)]
+pub struct ApiKeys {
+    bootstrap_token: String,
+    db: Database,
+}
+
+impl ApiKeys {
+    pub fn new(bootstrap_token: String, db: Database) -> Self {
+        Self {
+            bootstrap_token,
+            db,
+        }
+    }
+
+    /// whether `key` is valid and grants `scope`
+    pub async fn authorize(&self, key: &str, scope: Scope) -> Result<bool, StorageError> {
+        if bool::from(key.as_bytes().ct_eq(self.bootstrap_token.as_bytes())) {
+            return Ok(true);
+        }
+        Ok(self
+            .db
+            .api_key(&hash_key(key))
+            .await?
+            .is_some_and(|api_key| grants(&api_key, scope, Utc::now())))
+    }
+
+    /// returns the new key's id and the key itself, which can't be retrieved afterwards
+    pub async fn create(
+        &self,
+        name: &str,
+        scopes: &[Scope],
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<(i32, String), StorageError> {
+        let key = format!("{KEY_PREFIX}{}", nanoid!(32));
+        let scopes = scopes
+            .iter()
+            .map(|s| s.as_str())
+            .collect::<Vec<_>>()
+            .join(",");
+        let id = self
+            .db
+            .insert_api_key(name, &hash_key(&key), &scopes, expires_at)
+            .await?;
+        Ok((id, key))
+    }
+
+    pub async fn list(&self) -> Result<Vec<ApiKey>, StorageError> {
+        self.db.api_keys().await
+    }
+
+    /// returns false if there was no key with this id
+    pub async fn delete(&self, id: i32) -> Result<bool, StorageError> {
+        self.db.delete_api_key(id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use crate::{storage::ApiKey, test_harness::test_db};
+
+    use super::{grants, ApiKeys, Scope};
+
+    #[test]
+    fn test_grants() {
+        let now = Utc::now();
+        let key = |scopes: &str, expires_at| ApiKey {
+            id: 1,
+            name: "ci".to_owned(),
+            scopes: scopes.to_owned(),
+            expires_at,
+            created_at: now,
+        };
+
+        assert!(grants(&key("index,search", None), Scope::Search, now));
+        assert!(!grants(&key("index,search", None), Scope::Admin, now));
+        assert!(grants(&key("admin", None), Scope::Export, now));
+        assert!(!grants(
+            &key("index", Some(now - Duration::hours(1))),
+            Scope::Index,
+            now
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_authorize() {
+        let db = test_db().await;
+        let api_keys = ApiKeys::new("bootstrap".to_owned(), db);
+
+        assert!(api_keys.authorize("bootstrap", Scope::Admin).await.unwrap());
+        assert!(!api_keys.authorize("bootstra", Scope::Search).await.unwrap());
+        assert!(!api_keys.authorize("", Scope::Search).await.unwrap());
+
+        let (id, key) = api_keys.create("ci", &[Scope::Search], None).await.unwrap();
+        assert!(api_keys.authorize(&key, Scope::Search).await.unwrap());
+        assert!(!api_keys.authorize(&key, Scope::Index).await.unwrap());
+
+        let (_, expired) = api_keys
+            .create(
+                "old",
+                &[Scope::Search],
+                Some(Utc::now() - Duration::hours(1)),
+            )
+            .await
+            .unwrap();
+        assert!(!api_keys.authorize(&expired, Scope::Search).await.unwrap());
+
+        assert!(api_keys.delete(id).await.unwrap());
+        assert!(!api_keys.authorize(&key, Scope::Search).await.unwrap());
+    }
+}
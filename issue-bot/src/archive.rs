@@ -0,0 +1,110 @@
+use std::sync::Arc;
+
+use futures::{stream, StreamExt, TryStreamExt};
+use object_store::{
+    aws::{AmazonS3, AmazonS3Builder},
+    path::Path,
+    ObjectStore, PutPayload,
+};
+use thiserror::Error;
+use tracing::info;
+
+use crate::{
+    config::ArchiveConfig,
+    storage::{ArchivedIssue, Database, Storage, StorageError},
+};
+
+/// issues archived concurrently by [Archive::archive_issues]
+const ARCHIVE_CONCURRENCY: usize = 8;
+
+#[derive(Debug, Error)]
+pub enum ArchiveError {
+    #[error("object store error: {0}")]
+    ObjectStore(#[from] object_store::Error),
+    #[error("serde json error: {0}")]
+    SerdeJson(#[from] serde_json::Error),
+    #[error("storage error: {0}")]
+    Storage(#[from] StorageError),
+}
+
+#[derive(Clone)]
+enum Target {
+    Database,
+    ObjectStore {
+        prefix: String,
+        store: Arc<AmazonS3>,
+    },
+}
+
+/// Keeps a copy of issues deleted upstream, so that they aren't lost from the corpus
+#[derive(Clone)]
+pub struct Archive {
+    db: Database,
+    target: Target,
+}
+
+impl Archive {
+    pub fn new(cfg: ArchiveConfig, db: Database) -> Result<Self, ArchiveError> {
+        let target = match cfg {
+            ArchiveConfig::Database => Target::Database,
+            ArchiveConfig::ObjectStore(cfg) => {
+                let mut builder = AmazonS3Builder::new()
+                    .with_access_key_id(cfg.access_key_id)
+                    .with_bucket_name(cfg.bucket)
+                    .with_region(cfg.region)
+                    .with_secret_access_key(cfg.secret_access_key);
+                if let Some(endpoint) = cfg.endpoint {
+                    builder = builder
+                        .with_allow_http(endpoint.starts_with("http://"))
+                        .with_endpoint(endpoint);
+                }
+                Target::ObjectStore {
+                    prefix: cfg.prefix,
+                    store: Arc::new(builder.build()?),
+                }
+            }
+        };
+        Ok(Self { db, target })
+    }
+
+    /// Snapshots the issue with its comments and embedding, does nothing if it isn't stored
+    pub async fn archive_issue(&self, source_id: i64) -> Result<(), ArchiveError> {
+        let Some(issue) = self.db.archived_issue(source_id).await? else {
+            return Ok(());
+        };
+        match &self.target {
+            Target::Database => self.db.archive_issue(&issue).await?,
+            Target::ObjectStore { prefix, store } => {
+                let path = object_path(prefix, &issue);
+                store
+                    .put(&path, PutPayload::from(serde_json::to_vec(&issue)?))
+                    .await?;
+            }
+        }
+        info!(issue_id = source_id, "archived issue");
+        Ok(())
+    }
+
+    /// [Archive::archive_issue] for several issues at once, stopping at the first error
+    pub async fn archive_issues(&self, source_ids: &[i64]) -> Result<(), ArchiveError> {
+        stream::iter(source_ids.to_vec())
+            .map(|source_id| {
+                let archive = self.clone();
+                async move { archive.archive_issue(source_id).await }
+            })
+            .buffer_unordered(ARCHIVE_CONCURRENCY)
+            .try_collect()
+            .await
+    }
+}
+
+fn object_path(prefix: &str, issue: &ArchivedIssue) -> Path {
+    let path = format!(
+        "{}/{}/{}.json",
+        issue.source, issue.repository_full_name, issue.source_id
+    );
+    match prefix.trim_matches('/') {
+        "" => Path::from(path),
+        prefix => Path::from(format!("{prefix}/{path}")),
+    }
+}
@@ -0,0 +1,206 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use thiserror::Error;
+use tracing::info;
+
+use crate::{
+    comment_queue::CommentQueue,
+    config::{BacklinksConfig, RepositoryConfig},
+    github::{GithubApi, GithubApiError},
+    storage::{Database, Storage, StorageError},
+    ClosestIssue, IssueData, Source,
+};
+
+#[derive(Debug, Error)]
+pub enum BacklinkError {
+    #[error("github api error: {0}")]
+    Github(#[from] GithubApiError),
+    #[error("storage error: {0}")]
+    Storage(#[from] StorageError),
+}
+
+/// At most `max` events per key over a sliding `window`
+struct RateLimiter {
+    events: HashMap<String, VecDeque<Instant>>,
+    max: usize,
+    window: Duration,
+}
+
+impl RateLimiter {
+    fn new(max: usize, window: Duration) -> Self {
+        Self {
+            events: HashMap::new(),
+            max,
+            window,
+        }
+    }
+
+    fn has_capacity(&mut self, key: &str, now: Instant) -> bool {
+        let Some(events) = self.events.get_mut(key) else {
+            return self.max > 0;
+        };
+        while events
+            .front()
+            .is_some_and(|e| now.duration_since(*e) >= self.window)
+        {
+            events.pop_front();
+        }
+        events.len() < self.max
+    }
+
+    fn record(&mut self, key: &str, now: Instant) {
+        self.events
+            .entry(key.to_owned())
+            .or_default()
+            .push_back(now);
+    }
+}
+
+/// Comments on an older open issue when a new, closely matching, one is reported
+#[derive(Clone)]
+pub struct Backlinker {
+    cfg: BacklinksConfig,
+    comment_queue: CommentQueue,
+    db: Database,
+    enabled_repositories: HashSet<String>,
+    github_api: GithubApi,
+    rate_limiter: Arc<Mutex<RateLimiter>>,
+}
+
+impl Backlinker {
+    pub fn new(
+        cfg: BacklinksConfig,
+        comment_queue: CommentQueue,
+        db: Database,
+        github_api: GithubApi,
+        repositories: &HashMap<String, RepositoryConfig>,
+    ) -> Self {
+        let enabled_repositories = repositories
+            .iter()
+            .filter(|(_, r)| r.backlinks)
+            .map(|(name, _)| name.clone())
+            .collect();
+        let rate_limiter = RateLimiter::new(cfg.max_per_hour, Duration::from_secs(3_600));
+        Self {
+            cfg,
+            comment_queue,
+            db,
+            enabled_repositories,
+            github_api,
+            rate_limiter: Arc::new(Mutex::new(rate_limiter)),
+        }
+    }
+
+    /// Links back from the most similar older issue of the same repository, if any
+    pub async fn link(
+        &self,
+        issue: &IssueData,
+        closest_issues: &[ClosestIssue],
+    ) -> Result<(), BacklinkError> {
+        if !self
+            .enabled_repositories
+            .contains(&issue.repository_full_name)
+        {
+            return Ok(());
+        }
+        let Some(older) = closest_issues.iter().find(|ci| {
+            ci.repository_full_name == issue.repository_full_name
+                && ci.number < issue.number
+                && ci.cosine_similarity >= self.cfg.similarity_threshold
+        }) else {
+            return Ok(());
+        };
+        if !self
+            .rate_limiter
+            .lock()
+            .unwrap()
+            .has_capacity(&issue.repository_full_name, Instant::now())
+        {
+            info!(
+                issue_id = issue.source_id,
+                "backlinks rate limit reached, skipping"
+            );
+            return Ok(());
+        }
+        let older_url = self
+            .github_api
+            .issue_url(&older.repository_full_name, older.number);
+        match self
+            .github_api
+            .is_issue_open(&older.repository_full_name, older.number)
+            .await
+        {
+            Ok(true) => (),
+            Ok(false) => return Ok(()),
+            Err(GithubApiError::Gone(_)) => {
+                info!(
+                    issue_id = issue.source_id,
+                    older_issue = older.number,
+                    "older issue is gone upstream, skipping backlink"
+                );
+                self.db.mark_issue_gone(&older_url).await?;
+                return Ok(());
+            }
+            Err(err) => return Err(err.into()),
+        }
+        if self
+            .db
+            .is_issue_number_suppressed(&older.repository_full_name, older.number)
+            .await?
+        {
+            info!(
+                issue_id = issue.source_id,
+                older_issue = older.number,
+                "older issue is suppressed, skipping backlink"
+            );
+            return Ok(());
+        }
+
+        let body = self
+            .cfg
+            .message
+            .replace("{issue}", &format!("#{}", issue.number));
+        self.comment_queue
+            .enqueue(
+                &Source::Github,
+                &older.repository_full_name,
+                &older_url,
+                body,
+            )
+            .await?;
+        self.rate_limiter
+            .lock()
+            .unwrap()
+            .record(&issue.repository_full_name, Instant::now());
+        info!(
+            issue_id = issue.source_id,
+            older_issue = older.number,
+            "enqueued backlink"
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::RateLimiter;
+
+    #[test]
+    fn test_rate_limiter() {
+        let mut limiter = RateLimiter::new(2, Duration::from_secs(60));
+        let now = Instant::now();
+
+        assert!(limiter.has_capacity("a", now));
+        limiter.record("a", now);
+        limiter.record("a", now + Duration::from_secs(10));
+        assert!(!limiter.has_capacity("a", now + Duration::from_secs(20)));
+        assert!(limiter.has_capacity("b", now + Duration::from_secs(20)));
+        assert!(limiter.has_capacity("a", now + Duration::from_secs(60)));
+    }
+}
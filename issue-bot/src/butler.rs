@@ -0,0 +1,207 @@
+use std::time::Duration;
+
+use futures::pin_mut;
+use thiserror::Error;
+use tokio::{select, time::interval};
+use tracing::{error, info, warn};
+
+use crate::{
+    config::ButlerConfig,
+    debug::DebugState,
+    github::{GithubApi, GithubApiError},
+    shutdown_signal,
+    storage::{ClosureProposal, ClosureProposalStatus, Database, Storage, StorageError},
+    ClosestIssue, IssueData,
+};
+
+#[derive(Debug, Error)]
+pub enum ButlerError {
+    #[error("github api error: {0}")]
+    GithubApi(#[from] GithubApiError),
+    #[error("approvers team must be of the form `org/team-slug`, got '{0}'")]
+    InvalidApproversTeam(String),
+    #[error("storage error: {0}")]
+    Storage(#[from] StorageError),
+}
+
+/// Proposes closing very high-confidence duplicates and closes them once a maintainer approved
+#[derive(Clone)]
+pub struct Butler {
+    cfg: ButlerConfig,
+    github_api: GithubApi,
+    db: Database,
+    debug_state: DebugState,
+}
+
+impl Butler {
+    pub fn new(
+        cfg: ButlerConfig,
+        github_api: GithubApi,
+        db: Database,
+        debug_state: DebugState,
+    ) -> Self {
+        Self {
+            cfg,
+            db,
+            debug_state,
+            github_api,
+        }
+    }
+
+    fn approvers_team(&self) -> Result<(&str, &str), ButlerError> {
+        self.cfg
+            .approvers_team
+            .split_once('/')
+            .ok_or_else(|| ButlerError::InvalidApproversTeam(self.cfg.approvers_team.clone()))
+    }
+
+    /// Posts a closure proposal if the closest issue from the same repository is similar enough
+    pub async fn propose_closure(
+        &self,
+        issue: &IssueData,
+        closest_issues: &[ClosestIssue],
+    ) -> Result<(), ButlerError> {
+        if !self.cfg.enabled {
+            return Ok(());
+        }
+        let Some(duplicate) = closest_issues.iter().find(|ci| {
+            ci.repository_full_name == issue.repository_full_name
+                && ci.cosine_similarity >= self.cfg.similarity_threshold
+        }) else {
+            return Ok(());
+        };
+
+        let body = self.cfg.message.replace("{duplicate}", &duplicate.html_url);
+        let Some(comment_url) = self.github_api.comment(&issue.url, body).await? else {
+            return Ok(());
+        };
+        self.db
+            .insert_closure_proposal(
+                issue.source_id,
+                &issue.url,
+                &duplicate.html_url,
+                &comment_url,
+            )
+            .await?;
+        info!(
+            issue_id = issue.source_id,
+            duplicate_of = duplicate.html_url,
+            "proposed closure of duplicate issue"
+        );
+        Ok(())
+    }
+
+    /// Expires stale proposals and closes the issues whose proposal got approved, a proposal
+    /// failing to be processed being retried on the next poll without holding up the others
+    async fn process_proposals(&self) -> Result<(), ButlerError> {
+        let (org, team_slug) = self.approvers_team()?;
+
+        self.db
+            .expire_closure_proposals(self.cfg.expire_after_hours)
+            .await?;
+
+        let proposals = self.db.pending_closure_proposals().await?;
+
+        for proposal in proposals {
+            if let Err(err) = self.process_proposal(org, team_slug, &proposal).await {
+                self.debug_state.record_error("butler", &err);
+                error!(
+                    issue_id = proposal.issue_source_id,
+                    err = err.to_string(),
+                    "failed to process closure proposal"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Closes the proposal's issue if a member of the approvers team reacted with a thumbs up
+    async fn process_proposal(
+        &self,
+        org: &str,
+        team_slug: &str,
+        proposal: &ClosureProposal,
+    ) -> Result<(), ButlerError> {
+        // a maintainer may have muted the bot since the proposal was posted
+        if self
+            .db
+            .is_issue_suppressed(proposal.issue_source_id)
+            .await?
+        {
+            info!(
+                issue_id = proposal.issue_source_id,
+                "issue was suppressed, expiring closure proposal"
+            );
+            self.db
+                .set_closure_proposal_status(proposal.id, ClosureProposalStatus::Expired)
+                .await?;
+            return Ok(());
+        }
+        let reactions = self
+            .github_api
+            .get_thumbs_up_reactions(&proposal.comment_url)
+            .await?;
+        let mut approver = None;
+        for reaction in reactions {
+            if self
+                .github_api
+                .is_team_member(org, team_slug, &reaction.user.login)
+                .await?
+            {
+                approver = Some(reaction.user.login);
+                break;
+            }
+        }
+        let Some(approver) = approver else {
+            return Ok(());
+        };
+
+        match self.github_api.close_issue(&proposal.issue_url).await {
+            Ok(()) => (),
+            Err(GithubApiError::Gone(_)) => {
+                warn!(
+                    issue_id = proposal.issue_source_id,
+                    "issue is gone upstream, expiring closure proposal"
+                );
+                self.db.mark_issue_gone(&proposal.issue_url).await?;
+                self.db
+                    .set_closure_proposal_status(proposal.id, ClosureProposalStatus::Expired)
+                    .await?;
+                return Ok(());
+            }
+            Err(err) => return Err(err.into()),
+        }
+        self.db
+            .set_closure_proposal_status(proposal.id, ClosureProposalStatus::Approved)
+            .await?;
+        info!(
+            issue_id = proposal.issue_source_id,
+            approver, "closed duplicate issue"
+        );
+        Ok(())
+    }
+}
+
+pub async fn start_butler(butler: Butler) -> anyhow::Result<()> {
+    if !butler.cfg.enabled {
+        return Ok(());
+    }
+    // fail fast on misconfiguration rather than on every poll
+    butler.approvers_team()?;
+
+    info!("starting butler");
+    let mut ticker = interval(Duration::from_secs(butler.cfg.poll_interval_secs));
+    let shutdown = shutdown_signal();
+    pin_mut!(shutdown);
+    loop {
+        select! {
+            _ = ticker.tick() => {
+                if let Err(err) = butler.process_proposals().await {
+                    error!(err = err.to_string(), "error processing closure proposals");
+                }
+            }
+            _ = &mut shutdown => break,
+        }
+    }
+    Ok(())
+}
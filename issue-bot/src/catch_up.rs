@@ -0,0 +1,403 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Duration, Utc};
+use thiserror::Error;
+use tokio::sync::mpsc::{error::SendError, Sender};
+use tracing::{error, info};
+
+use crate::{
+    config::CatchUpConfig,
+    debug::DebugState,
+    github::{GithubApi, GithubApiError},
+    ignore::{EventMetadata, IgnoreRules},
+    locks::{self, Locks},
+    middlewares::RequestId,
+    storage::{Database, RepositoryCursor, Storage, StorageError},
+    Action, CommentData, EventData, IndexIssueData, IssueData, IssueMetadata, QueuedEvent, Source,
+};
+
+#[derive(Debug, Error)]
+pub enum CatchUpError {
+    #[error("github api error: {0}")]
+    GithubApi(#[from] GithubApiError),
+    #[error("send error: {0}")]
+    Send(#[from] SendError<QueuedEvent>),
+    #[error("storage error: {0}")]
+    Storage(#[from] StorageError),
+}
+
+/// start of the window listed for a repository, `cursor` being `None` when no webhook of it was
+/// ever received
+fn since(cfg: &CatchUpConfig, cursor: Option<DateTime<Utc>>, now: DateTime<Utc>) -> DateTime<Utc> {
+    let earliest = now - Duration::hours(cfg.max_lookback_hours);
+    cursor.map_or(earliest, |cursor| {
+        (cursor - Duration::seconds(cfg.overlap_secs)).max(earliest)
+    })
+}
+
+/// issue of a repository whose comments are replayed along with it
+struct ReplayedIssue {
+    author: Option<String>,
+    labels: Vec<String>,
+    source_id: i64,
+    title: String,
+}
+
+/// Cursor of a repository the webhooks worker moves once it handled the event carrying it, see
+/// [CatchUp::cursor_update]
+pub struct CursorUpdate {
+    pub repository_full_name: String,
+    pub processed_at: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct Replayed {
+    comments: usize,
+    issues: usize,
+}
+
+/// Replays the GitHub issues and comments whose webhooks were missed, see [CatchUpConfig]
+///
+/// Missed issues are replayed as created, so they get their comment, and the others as edited,
+/// which the webhooks worker handles idempotently.
+#[derive(Clone)]
+pub struct CatchUp {
+    cfg: CatchUpConfig,
+    db: Database,
+    debug_state: DebugState,
+    github_api: GithubApi,
+    ignore_rules: IgnoreRules,
+    locks: Locks,
+    tx: Sender<QueuedEvent>,
+}
+
+impl CatchUp {
+    pub fn new(
+        cfg: CatchUpConfig,
+        db: Database,
+        debug_state: DebugState,
+        github_api: GithubApi,
+        ignore_rules: IgnoreRules,
+        locks: Locks,
+        tx: Sender<QueuedEvent>,
+    ) -> Self {
+        Self {
+            cfg,
+            db,
+            debug_state,
+            github_api,
+            ignore_rules,
+            locks,
+            tx,
+        }
+    }
+
+    /// cursor update of a webhook just received from the repository, applied once its event is
+    /// handled so that events lost to a restart are replayed, `None` when catching up is disabled
+    pub fn cursor_update(&self, repository_full_name: &str) -> Option<CursorUpdate> {
+        self.cfg.enabled.then(|| CursorUpdate {
+            repository_full_name: repository_full_name.to_owned(),
+            processed_at: Utc::now(),
+        })
+    }
+
+    /// replays what was missed since the current cursors
+    pub async fn run(&self) {
+        match self.db.repository_cursors().await {
+            Ok(cursors) => self.run_from(cursors).await,
+            Err(err) => {
+                self.debug_state.record_error("database", &err);
+                error!(err = err.to_string(), "failed to fetch repository cursors");
+            }
+        }
+    }
+
+    /// replays what was missed since `cursors`, only one instance of the bot doing so at a time
+    async fn run_from(&self, cursors: Vec<RepositoryCursor>) {
+        let lease = match self.locks.try_acquire(locks::CATCH_UP).await {
+            Ok(Some(lease)) => lease,
+            Ok(None) => {
+                info!("another instance is catching up on missed webhooks");
+                return;
+            }
+            Err(err) => {
+                error!(err = err.to_string(), "failed to acquire catch-up lock");
+                return;
+            }
+        };
+        if let Err(err) = self.replay(cursors).await {
+            self.debug_state.record_error("catch_up", &err);
+            error!(
+                err = err.to_string(),
+                "failed to catch up on missed webhooks"
+            );
+        }
+        if let Err(err) = lease.release().await {
+            error!(err = err.to_string(), "failed to release catch-up lock");
+        }
+    }
+
+    async fn replay(&self, cursors: Vec<RepositoryCursor>) -> Result<(), CatchUpError> {
+        let started_at = Utc::now();
+        // indexed or onboarded repositories that never sent a webhook are listed as far back as
+        // allowed
+        let mut repositories: BTreeMap<String, Option<DateTime<Utc>>> = self
+            .db
+            .repository_metadata(None)
+            .await?
+            .into_iter()
+            .map(|metadata| (metadata.full_name, None))
+            .collect();
+        repositories.extend(
+            self.db
+                .onboarded_repositories()
+                .await?
+                .into_iter()
+                .map(|repository| (repository.repository_full_name, None)),
+        );
+        repositories.extend(
+            cursors
+                .into_iter()
+                .map(|cursor| (cursor.repository_full_name, Some(cursor.processed_at))),
+        );
+
+        let mut replayed = Replayed::default();
+        for (repository_full_name, cursor) in repositories {
+            let since = since(&self.cfg, cursor, started_at);
+            if let Err(err) = self
+                .replay_repository(&repository_full_name, since, started_at, &mut replayed)
+                .await
+            {
+                self.debug_state.record_error("catch_up", &err);
+                error!(
+                    repository = repository_full_name,
+                    err = err.to_string(),
+                    "failed to catch up on repository"
+                );
+            }
+        }
+        metrics::counter!("issue_bot_catch_up_events_total", "kind" => "issue")
+            .increment(replayed.issues as u64);
+        metrics::counter!("issue_bot_catch_up_events_total", "kind" => "comment")
+            .increment(replayed.comments as u64);
+        info!(
+            issues = replayed.issues,
+            comments = replayed.comments,
+            "caught up on missed webhooks"
+        );
+        Ok(())
+    }
+
+    /// replays the repository's events since `since`, the last one moving its cursor to
+    /// `started_at` once handled
+    async fn replay_repository(
+        &self,
+        repository_full_name: &str,
+        since: DateTime<Utc>,
+        started_at: DateTime<Utc>,
+        replayed: &mut Replayed,
+    ) -> Result<(), CatchUpError> {
+        // keyed by api url, which is how comments refer to their issue
+        let mut issues = HashMap::new();
+        let mut events = Vec::new();
+        for updated in self
+            .github_api
+            .issues_updated_since(repository_full_name, since)
+            .await?
+        {
+            let created_at = updated.created_at;
+            let author = updated.user.as_ref().map(|u| u.login.clone());
+            let issue = updated.into_issue();
+            if self.ignored(
+                repository_full_name,
+                author.iter().map(String::as_str).collect(),
+                &issue.labels,
+                &issue.title,
+            ) {
+                continue;
+            }
+            issues.insert(
+                issue.url.clone(),
+                ReplayedIssue {
+                    author: author.clone(),
+                    labels: issue.labels.clone(),
+                    source_id: issue.id,
+                    title: issue.title.clone(),
+                },
+            );
+
+            let event = if issue.is_pull_request {
+                // fetched again through the issues API, as the pull request webhooks are
+                EventData::IssueIndexation(IndexIssueData {
+                    issue_number: issue.number,
+                    repository_full_name: repository_full_name.to_owned(),
+                    head_sha: None,
+                })
+            } else if self.db.issue_id(issue.id).await?.is_some() {
+                let stored = self.db.issue_text(issue.id).await?;
+                if stored.title == issue.title && stored.body == issue.body {
+                    EventData::IssueMetadata(IssueMetadata {
+                        source_id: issue.id,
+                        labels: issue.labels,
+                        milestone: issue.milestone,
+                    })
+                } else {
+                    EventData::Issue(IssueData {
+                        source_id: issue.id,
+                        action: Action::Edited,
+                        author,
+                        labels: issue.labels,
+                        milestone: issue.milestone,
+                        title: issue.title,
+                        body: issue.body,
+                        is_pull_request: false,
+                        number: issue.number,
+                        html_url: issue.html_url,
+                        url: issue.url,
+                        repository_full_name: repository_full_name.to_owned(),
+                        source: Source::Github,
+                    })
+                }
+            } else if created_at >= since {
+                EventData::Issue(IssueData {
+                    source_id: issue.id,
+                    action: Action::Created,
+                    author,
+                    labels: issue.labels,
+                    milestone: issue.milestone,
+                    title: issue.title,
+                    body: issue.body,
+                    is_pull_request: false,
+                    number: issue.number,
+                    html_url: issue.html_url,
+                    url: issue.url,
+                    repository_full_name: repository_full_name.to_owned(),
+                    source: Source::Github,
+                })
+            } else {
+                // opened before the window and never indexed, left to repository indexations
+                continue;
+            };
+            events.push(event);
+            replayed.issues += 1;
+        }
+
+        for comment in self
+            .github_api
+            .comments_updated_since(repository_full_name, since)
+            .await?
+        {
+            // commenting updates the issue, which is missing when it is ignored
+            let Some(issue) = issues.get(&comment.issue_url) else {
+                continue;
+            };
+            let mut authors: Vec<&str> = issue.author.iter().map(String::as_str).collect();
+            authors.extend(comment.user.as_ref().map(|u| u.login.as_str()));
+            if self.ignored(repository_full_name, authors, &issue.labels, &issue.title) {
+                continue;
+            }
+            // stores the comment when it wasn't, without triggering suggestions on stale comments
+            events.push(EventData::Comment(CommentData {
+                source_id: comment.id,
+                action: Action::Edited,
+                issue_id: issue.source_id,
+                author: comment.user.map(|u| u.login),
+                body: comment.body,
+                url: comment.url,
+                is_review: false,
+            }));
+            replayed.comments += 1;
+        }
+
+        let update = CursorUpdate {
+            repository_full_name: repository_full_name.to_owned(),
+            processed_at: started_at,
+        };
+        let Some(last) = events.pop() else {
+            self.db
+                .advance_repository_cursor(repository_full_name, started_at)
+                .await?;
+            return Ok(());
+        };
+        for event in events {
+            self.send(QueuedEvent::new(event, &RequestId::new()))
+                .await?;
+        }
+        self.send(QueuedEvent::new(last, &RequestId::new()).with_cursor_update(Some(update)))
+            .await
+    }
+
+    fn ignored(
+        &self,
+        repository_full_name: &str,
+        authors: Vec<&str>,
+        labels: &[String],
+        title: &str,
+    ) -> bool {
+        let metadata = EventMetadata {
+            authors,
+            labels: labels.iter().map(String::as_str).collect(),
+            repository: repository_full_name,
+            title,
+        };
+        match self.ignore_rules.ignore_reason(&metadata) {
+            Some(reason) => {
+                info!(
+                    repository = repository_full_name,
+                    title, "not replaying ignored event: {}", reason
+                );
+                true
+            }
+            None => false,
+        }
+    }
+
+    async fn send(&self, event: QueuedEvent) -> Result<(), CatchUpError> {
+        self.tx.send(event).await?;
+        Ok(())
+    }
+}
+
+/// Catches up on startup from `cursors`, read before any webhook could move them
+pub async fn start_catch_up(
+    catch_up: CatchUp,
+    cursors: Vec<RepositoryCursor>,
+) -> anyhow::Result<()> {
+    if !catch_up.cfg.enabled {
+        return Ok(());
+    }
+
+    info!("catching up on missed webhooks");
+    catch_up.run_from(cursors).await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+
+    use crate::config::CatchUpConfig;
+
+    use super::since;
+
+    #[test]
+    fn test_since() {
+        let cfg = CatchUpConfig {
+            enabled: true,
+            max_lookback_hours: 72,
+            overlap_secs: 300,
+        };
+        let now = Utc.with_ymd_and_hms(2024, 6, 10, 12, 0, 0).unwrap();
+
+        let cursor = now - Duration::hours(2);
+        assert_eq!(
+            since(&cfg, Some(cursor), now),
+            cursor - Duration::minutes(5)
+        );
+        assert_eq!(
+            since(&cfg, Some(now - Duration::days(30)), now),
+            now - Duration::hours(72)
+        );
+        assert_eq!(since(&cfg, None, now), now - Duration::hours(72));
+    }
+}
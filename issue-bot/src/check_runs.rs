@@ -0,0 +1,141 @@
+use thiserror::Error;
+use tracing::info;
+
+use crate::{
+    config::CheckRunsConfig,
+    github::{GithubApi, GithubApiError},
+    storage::{Database, Storage, StorageError},
+    ClosestIssue,
+};
+
+#[derive(Debug, Error)]
+pub enum CheckRunError {
+    #[error("github api error: {0}")]
+    GithubApi(#[from] GithubApiError),
+    #[error("storage error: {0}")]
+    Storage(#[from] StorageError),
+}
+
+/// title and summary of the check run, e.g. "Related issues found: #12, #88"
+fn output(repository_full_name: &str, closest_issues: &[ClosestIssue]) -> (String, String) {
+    let references: Vec<String> = closest_issues
+        .iter()
+        .map(|ci| {
+            if ci.repository_full_name == repository_full_name {
+                format!("#{}", ci.number)
+            } else {
+                format!("{}#{}", ci.repository_full_name, ci.number)
+            }
+        })
+        .collect();
+    let items: Vec<String> = closest_issues
+        .iter()
+        .map(|ci| ci.to_markdown_list_item(repository_full_name))
+        .collect();
+    (
+        format!("Related issues found: {}", references.join(", ")),
+        items.join("\n"),
+    )
+}
+
+/// Lists the issues related to pull requests in a check run on their new commits, see
+/// [CheckRunsConfig]
+///
+/// The related issues are ranked like the suggestions commented on issues.
+#[derive(Clone)]
+pub struct CheckRuns {
+    cfg: CheckRunsConfig,
+    db: Database,
+    github_api: GithubApi,
+}
+
+impl CheckRuns {
+    pub fn new(cfg: CheckRunsConfig, db: Database, github_api: GithubApi) -> Self {
+        Self {
+            cfg,
+            db,
+            github_api,
+        }
+    }
+
+    /// whether check runs are published, sparing the search of related issues otherwise
+    pub fn enabled(&self) -> bool {
+        self.cfg.enabled && self.github_api.has_app()
+    }
+
+    /// publishes the check run of `closest_issues` on commit `head_sha` of pull request
+    /// `number`, returns whether it was
+    pub async fn publish(
+        &self,
+        repository_full_name: &str,
+        number: i32,
+        head_sha: &str,
+        closest_issues: &[ClosestIssue],
+    ) -> Result<bool, CheckRunError> {
+        if !self.enabled() || closest_issues.is_empty() {
+            return Ok(false);
+        }
+        if self
+            .db
+            .is_issue_number_suppressed(repository_full_name, number)
+            .await?
+        {
+            return Ok(false);
+        }
+
+        let (title, summary) = output(repository_full_name, closest_issues);
+        self.github_api
+            .create_check_run(
+                repository_full_name,
+                head_sha,
+                &self.cfg.name,
+                &title,
+                &summary,
+            )
+            .await?;
+        metrics::counter!("issue_bot_check_runs_total").increment(1);
+        info!(
+            repository = repository_full_name,
+            number,
+            related = closest_issues.len(),
+            "published related issues check run"
+        );
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ClosestIssue;
+
+    use super::output;
+
+    #[test]
+    fn test_output() {
+        let issue = |repository_full_name: &str, number| ClosestIssue {
+            title: format!("issue {number}"),
+            number,
+            html_url: format!("https://github.com/{repository_full_name}/issues/{number}"),
+            labels: vec!["bug".to_owned()],
+            repository_full_name: repository_full_name.to_owned(),
+            cosine_similarity: 0.9,
+            boost: 0.,
+            embedding: Vec::new(),
+        };
+        let (title, summary) = output(
+            "huggingface/transformers",
+            &[
+                issue("huggingface/transformers", 12),
+                issue("huggingface/tokenizers", 88),
+            ],
+        );
+        assert_eq!(
+            title,
+            "Related issues found: #12, huggingface/tokenizers#88"
+        );
+        assert_eq!(
+            summary,
+            "- issue 12 ([#12](https://github.com/huggingface/transformers/issues/12)) `bug`\n- issue 88 ([huggingface/tokenizers#88](https://github.com/huggingface/tokenizers/issues/88)) `bug`"
+        );
+    }
+}
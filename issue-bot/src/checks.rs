@@ -0,0 +1,167 @@
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::{
+    config::IssueBotConfig,
+    embeddings::{inference_endpoints::EmbeddingApi, EmbeddingError},
+    github::{GithubApi, GithubApiError},
+    slack::{Slack, SlackError},
+    storage::{Database, Storage, StorageError},
+    summarization::{SummarizationApi, SummarizationApiError},
+};
+
+#[derive(Debug, Error)]
+pub enum StartupCheckError {
+    #[error("database check failed, verify `database.connection_string`: {0}")]
+    Database(StorageError),
+    #[error("embedding api check failed, verify `embedding_api.url` and `embedding_api.auth_token`: {0}")]
+    EmbeddingApi(EmbeddingError),
+    #[error("github api check failed, verify `github_api.auth_token`: {0}")]
+    GithubApi(GithubApiError),
+    #[error("github token is missing the {0:?} scope(s), grant them or disable the features needing them (`github_api.comments_enabled`, `butler.enabled`)")]
+    GithubMissingScopes(Vec<&'static str>),
+    #[error("slack check failed, verify `slack.auth_token` and `slack.auth_test_url`: {0}")]
+    Slack(SlackError),
+    #[error("summarization api check failed, verify `summarization_api.url`, `summarization_api.model` and `summarization_api.auth_token`: {0}")]
+    SummarizationApi(SummarizationApiError),
+}
+
+/// Scopes missing from `granted` for the enabled features
+///
+/// Commenting and closing issues need write access to repositories, checking the butler's
+/// approvers team membership needs to read the organization.
+fn missing_scopes(
+    granted: &[String],
+    comments_enabled: bool,
+    butler_enabled: bool,
+) -> Vec<&'static str> {
+    let has_any = |scopes: &[&str]| granted.iter().any(|g| scopes.contains(&g.as_str()));
+    let mut missing = Vec::new();
+    if (comments_enabled || butler_enabled) && !has_any(&["repo", "public_repo"]) {
+        missing.push("public_repo");
+    }
+    if butler_enabled && !has_any(&["read:org", "write:org", "admin:org"]) {
+        missing.push("read:org");
+    }
+    missing
+}
+
+/// Integrations given the url or credentials they need, only those are checked at startup
+#[derive(Clone, Copy, Debug)]
+pub struct ConfiguredIntegrations {
+    pub embedding_api: bool,
+    pub github_api: bool,
+    pub slack: bool,
+    pub summarization_api: bool,
+}
+
+impl ConfiguredIntegrations {
+    pub fn new(config: &IssueBotConfig) -> Self {
+        Self {
+            embedding_api: !config.embedding_api.url.is_empty(),
+            github_api: !config.github_api.auth_token.is_empty() || config.github_api.app.is_some(),
+            slack: !config.slack.auth_token.is_empty() || !config.slack.workspaces.is_empty(),
+            // the default url is a hosted endpoint, unusable without a token
+            summarization_api: !config.summarization_api.url.is_empty()
+                && !config.summarization_api.auth_token.is_empty(),
+        }
+    }
+}
+
+/// Verifies every configured external dependency is reachable and correctly configured, so that
+/// bad tokens or urls fail the startup instead of the first webhook
+pub async fn run_startup_checks(
+    configured: ConfiguredIntegrations,
+    db: &Database,
+    embedding_api: &EmbeddingApi,
+    github_api: &GithubApi,
+    butler_enabled: bool,
+    slack: &Slack,
+    summarization_api: &SummarizationApi,
+) -> Result<(), StartupCheckError> {
+    info!("running startup checks");
+    db.check().await.map_err(StartupCheckError::Database)?;
+    if configured.embedding_api {
+        embedding_api
+            .check()
+            .await
+            .map_err(StartupCheckError::EmbeddingApi)?;
+    } else {
+        warn!("embedding_api.url is unset, skipping its check");
+    }
+    if configured.summarization_api {
+        summarization_api
+            .check()
+            .await
+            .map_err(StartupCheckError::SummarizationApi)?;
+    } else {
+        warn!("summarization_api.url or auth_token is unset, skipping its check");
+    }
+    if configured.github_api {
+        match github_api
+            .token_scopes()
+            .await
+            .map_err(StartupCheckError::GithubApi)?
+        {
+            Some(scopes) => {
+                let missing =
+                    missing_scopes(&scopes, github_api.comments_enabled(), butler_enabled);
+                if !missing.is_empty() {
+                    return Err(StartupCheckError::GithubMissingScopes(missing));
+                }
+            }
+            None => warn!("github token doesn't advertise its scopes, skipping scopes check"),
+        }
+    } else {
+        warn!("github_api.auth_token and github_api.app are unset, skipping its check");
+    }
+    if configured.slack {
+        slack.auth_test().await.map_err(StartupCheckError::Slack)?;
+    } else {
+        info!("slack isn't configured, skipping its check");
+    }
+    info!("startup checks passed");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::load_config;
+
+    use super::{missing_scopes, ConfiguredIntegrations};
+
+    #[test]
+    fn test_configured_integrations() {
+        let mut config = load_config("ISSUE_BOT_TEST").unwrap();
+        let configured = ConfiguredIntegrations::new(&config);
+        // the default configuration must not fail the startup on integrations it leaves unset
+        assert!(!configured.embedding_api);
+        assert!(!configured.github_api);
+        assert!(!configured.slack);
+        assert!(!configured.summarization_api);
+
+        config.embedding_api.url = "http://localhost:8080".to_owned();
+        config.github_api.auth_token = "ghp_token".to_owned();
+        config.slack.auth_token = "xoxb-token".to_owned();
+        config.summarization_api.auth_token = "hf_token".to_owned();
+        let configured = ConfiguredIntegrations::new(&config);
+        assert!(configured.embedding_api);
+        assert!(configured.github_api);
+        assert!(configured.slack);
+        assert!(configured.summarization_api);
+    }
+
+    #[test]
+    fn test_missing_scopes() {
+        let scopes = |s: &[&str]| s.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert!(missing_scopes(&[], false, false).is_empty());
+        assert_eq!(missing_scopes(&[], true, false), vec!["public_repo"]);
+        assert!(missing_scopes(&scopes(&["repo"]), true, false).is_empty());
+        assert_eq!(
+            missing_scopes(&scopes(&["public_repo"]), true, true),
+            vec!["read:org"]
+        );
+        assert!(missing_scopes(&scopes(&["repo", "read:org"]), false, true).is_empty());
+    }
+}
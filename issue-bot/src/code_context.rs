@@ -0,0 +1,164 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use tracing::warn;
+
+use crate::{
+    config::CodeContextConfig,
+    github::{GithubApi, GithubApiError},
+};
+
+static FILE_REFERENCE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"(?P<path>[\w\-./]*[\w\-]+\.(?:c|cc|cpp|cu|go|h|hpp|java|js|jsx|py|rs|ts|tsx))\b(?:(?::|#L|"?,? line )(?P<line>\d+))?"#,
+    )
+    .expect("valid file reference regex")
+});
+
+#[derive(Debug, PartialEq)]
+pub struct FileReference {
+    pub path: String,
+    pub line: Option<usize>,
+}
+
+/// file paths mentioned in `text`, with the line number when there is one, deduplicated
+pub fn find_file_references(text: &str, max: usize) -> Vec<FileReference> {
+    let mut references: Vec<FileReference> = Vec::new();
+    for captures in FILE_REFERENCE.captures_iter(text) {
+        let reference = FileReference {
+            path: captures["path"].trim_start_matches("./").to_owned(),
+            line: captures.name("line").and_then(|l| l.as_str().parse().ok()),
+        };
+        if references.contains(&reference) {
+            continue;
+        }
+        references.push(reference);
+        if references.len() == max {
+            break;
+        }
+    }
+    references
+}
+
+/// `context_lines` around `line` (1-indexed), or the start of the file when there is no line
+fn snippet(content: &str, line: Option<usize>, context_lines: usize) -> String {
+    let start = line
+        .map(|l| l.saturating_sub(context_lines + 1))
+        .unwrap_or_default();
+    content
+        .lines()
+        .skip(start)
+        .take(context_lines * 2 + 1)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Enriches issue text with snippets of the repository files it references, improving matches on
+/// code-specific bugs
+///
+/// The snippets are stored along with the issue when it is created or edited, re-embeddings
+/// reusing them rather than searching the repository again. Bulk indexations skip them, sparing
+/// GitHub's code search rate limit.
+#[derive(Clone)]
+pub struct CodeContext {
+    cfg: CodeContextConfig,
+    github_api: GithubApi,
+}
+
+impl CodeContext {
+    pub fn new(cfg: CodeContextConfig, github_api: GithubApi) -> Self {
+        Self { cfg, github_api }
+    }
+
+    async fn fetch_snippet(
+        &self,
+        repository_full_name: &str,
+        reference: &FileReference,
+    ) -> Result<Option<(String, String)>, GithubApiError> {
+        let Some(path) = self
+            .github_api
+            .find_file(repository_full_name, &reference.path)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let Some(content) = self
+            .github_api
+            .get_file_content(repository_full_name, &path)
+            .await?
+        else {
+            return Ok(None);
+        };
+        Ok(Some((
+            path,
+            snippet(&content, reference.line, self.cfg.context_lines),
+        )))
+    }
+
+    /// Returns the snippets of the files `text` references, fetched from the repository's default
+    /// branch, to be appended to it, `None` when there are none
+    ///
+    /// Failing to fetch a snippet isn't an error, the file is simply left out.
+    pub async fn snippets(&self, repository_full_name: &str, text: &str) -> Option<String> {
+        if !self.cfg.enabled {
+            return None;
+        }
+        let mut snippets = String::new();
+        for reference in find_file_references(text, self.cfg.max_files) {
+            match self.fetch_snippet(repository_full_name, &reference).await {
+                Ok(Some((path, snippet))) => {
+                    snippets.push_str(&format!("\n----\nCode: {path}\n{snippet}"));
+                }
+                Ok(None) => (),
+                Err(err) => warn!(
+                    path = reference.path,
+                    err = err.to_string(),
+                    "failed to fetch code context"
+                ),
+            }
+        }
+        (!snippets.is_empty()).then_some(snippets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_file_references, snippet, FileReference};
+
+    #[test]
+    fn test_find_file_references() {
+        let text = r#"Fails in modeling_llama.py line 321:
+  File "/usr/lib/python3.12/site-packages/transformers/trainer.py", line 42, in train
+see also src/transformers/models/llama/modeling_llama.py#L10 and modeling_llama.py line 321"#;
+
+        assert_eq!(
+            find_file_references(text, 5),
+            vec![
+                FileReference {
+                    path: "modeling_llama.py".to_owned(),
+                    line: Some(321),
+                },
+                FileReference {
+                    path: "/usr/lib/python3.12/site-packages/transformers/trainer.py".to_owned(),
+                    line: Some(42),
+                },
+                FileReference {
+                    path: "src/transformers/models/llama/modeling_llama.py".to_owned(),
+                    line: Some(10),
+                },
+            ]
+        );
+        assert_eq!(find_file_references(text, 1).len(), 1);
+    }
+
+    #[test]
+    fn test_snippet() {
+        let content = (1..=10)
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        assert_eq!(snippet(&content, Some(5), 1), "4\n5\n6");
+        assert_eq!(snippet(&content, Some(1), 1), "1\n2\n3");
+        assert_eq!(snippet(&content, None, 1), "1\n2\n3");
+    }
+}
@@ -0,0 +1,706 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use futures::{pin_mut, stream, StreamExt};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tokio::{select, sync::Notify, time::sleep};
+use tracing::{error, info, warn};
+
+use crate::{
+    config::CommentQueueConfig,
+    github::{GithubApi, GithubApiError},
+    huggingface::{HuggingfaceApi, HuggingfaceApiError},
+    locks::Locks,
+    retry::{self, backoff, Classify, RetryBudget, RetryClass},
+    shutdown_signal,
+    slack::{Slack, SlackError},
+    storage::{Database, PendingComment, Storage, StorageError},
+    Source,
+};
+
+const BATCH_SIZE: i64 = 100;
+const LOCK_NAME: &str = "comment_queue";
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(3600);
+
+#[derive(Debug, Error)]
+pub enum CommentQueueError {
+    #[error("github api error: {0}")]
+    Github(#[from] GithubApiError),
+    #[error("huggingface api error: {0}")]
+    Huggingface(#[from] HuggingfaceApiError),
+    #[error("slack error: {0}")]
+    Slack(#[from] SlackError),
+    #[error("storage error: {0}")]
+    Storage(#[from] StorageError),
+    #[error("unknown comment source: '{0}'")]
+    UnknownSource(String),
+}
+
+impl Classify for CommentQueueError {
+    fn retry_class(&self) -> RetryClass {
+        match self {
+            Self::Github(err) => err.retry_class(),
+            Self::Huggingface(err) => err.retry_class(),
+            Self::Slack(err) => err.retry_class(),
+            Self::Storage(_) => RetryClass::Retryable,
+            Self::UnknownSource(_) => RetryClass::Fatal,
+        }
+    }
+
+    fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::Github(err) => err.retry_after(),
+            Self::Huggingface(err) => err.retry_after(),
+            Self::Slack(err) => err.retry_after(),
+            Self::Storage(_) | Self::UnknownSource(_) => None,
+        }
+    }
+}
+
+/// Earliest time each repository can be commented on again
+#[derive(Default)]
+struct Pacing {
+    next_allowed: HashMap<String, Instant>,
+}
+
+impl Pacing {
+    fn is_allowed(&self, repository_full_name: &str, now: Instant) -> bool {
+        self.next_allowed
+            .get(repository_full_name)
+            .is_none_or(|next| *next <= now)
+    }
+
+    fn delay(&mut self, repository_full_name: &str, until: Instant) {
+        let next = self
+            .next_allowed
+            .entry(repository_full_name.to_owned())
+            .or_insert(until);
+        *next = (*next).max(until);
+    }
+}
+
+/// Idempotency key of a comment on a given discussion, see [Storage::comment_idempotency_key]
+///
+/// Keyed by the pending comment too, an identical answer queued later on, e.g. after the
+/// discussion was edited, is a comment of its own.
+pub fn comment_fingerprint(pending_comment_id: i32, body: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(pending_comment_id.to_be_bytes());
+    hasher.update(body.trim().as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+/// oldest pending comment of every repository that can be commented on at `now`
+fn next_batch(pending: Vec<PendingComment>, pacing: &Pacing, now: Instant) -> Vec<PendingComment> {
+    let mut batch: Vec<PendingComment> = Vec::new();
+    for comment in pending {
+        if !pacing.is_allowed(&comment.repository_full_name, now)
+            || batch
+                .iter()
+                .any(|c| c.repository_full_name == comment.repository_full_name)
+        {
+            continue;
+        }
+        batch.push(comment);
+    }
+    batch
+}
+
+/// Outbound comments, persisted until posted and paced per repository to stay clear of GitHub's
+/// abuse detection
+///
+/// The butler's closure proposals bypass the queue, their url being needed right away. With
+/// approvals enabled, comments wait in Slack until a maintainer approves them, see
+/// [crate::config::CommentApprovalConfig].
+#[derive(Clone)]
+pub struct CommentQueue {
+    cfg: CommentQueueConfig,
+    db: Database,
+    github_api: GithubApi,
+    huggingface_api: HuggingfaceApi,
+    locks: Locks,
+    notify: Arc<Notify>,
+    retry_budget: RetryBudget,
+    slack: Slack,
+}
+
+impl CommentQueue {
+    pub fn new(
+        cfg: CommentQueueConfig,
+        db: Database,
+        github_api: GithubApi,
+        huggingface_api: HuggingfaceApi,
+        locks: Locks,
+        slack: Slack,
+    ) -> Self {
+        Self {
+            cfg,
+            db,
+            github_api,
+            huggingface_api,
+            locks,
+            notify: Arc::new(Notify::new()),
+            retry_budget: RetryBudget::default(),
+            slack,
+        }
+    }
+
+    /// retries of comments failing to post draw from the budget shared with the other retry loops
+    /// of the process, a private one otherwise
+    pub fn with_retry_budget(mut self, budget: RetryBudget) -> Self {
+        self.retry_budget = budget;
+        self
+    }
+
+    pub async fn enqueue(
+        &self,
+        source: &Source,
+        repository_full_name: &str,
+        issue_url: &str,
+        body: String,
+    ) -> Result<(), StorageError> {
+        self.enqueue_comment(source, repository_full_name, issue_url, body, false)
+            .await
+    }
+
+    /// queues the bot's answer to an issue, editing the previous one on GitHub when
+    /// `update_in_place` is set
+    ///
+    /// `run_id` is recorded so that the feedback links of the answer's footer are accepted, with
+    /// the `prompt_profile` the issue was summarized with to compare the feedback of profiles.
+    pub async fn enqueue_suggestions(
+        &self,
+        source: &Source,
+        repository_full_name: &str,
+        issue_url: &str,
+        run_id: &str,
+        prompt_profile: Option<&str>,
+        body: String,
+    ) -> Result<(), StorageError> {
+        self.db
+            .insert_comment_run(run_id, issue_url, prompt_profile)
+            .await?;
+        self.enqueue_comment(source, repository_full_name, issue_url, body, true)
+            .await
+    }
+
+    async fn enqueue_comment(
+        &self,
+        source: &Source,
+        repository_full_name: &str,
+        issue_url: &str,
+        body: String,
+        suggestions: bool,
+    ) -> Result<(), StorageError> {
+        self.db
+            .enqueue_comment(
+                source,
+                repository_full_name,
+                issue_url,
+                &body,
+                self.cfg.approval.enabled,
+                suggestions,
+            )
+            .await?;
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    /// queues an approved draft for posting, returns whether it was still awaiting approval
+    pub async fn approve_draft(&self, id: i32) -> Result<bool, StorageError> {
+        let approved = self.db.approve_comment_draft(id).await?;
+        if approved {
+            self.notify.notify_one();
+        }
+        Ok(approved)
+    }
+
+    /// returns whether the draft was still awaiting approval
+    pub async fn discard_draft(&self, id: i32) -> Result<bool, StorageError> {
+        self.db.discard_comment_draft(id).await
+    }
+
+    /// Drops the drafts left unapproved for too long and posts the new ones to Slack
+    async fn process_drafts(&self) -> Result<(), CommentQueueError> {
+        let expired = self
+            .db
+            .expire_comment_drafts(self.cfg.approval.expire_after_hours)
+            .await?;
+        if expired > 0 {
+            info!(expired, "discarded unapproved comment drafts");
+        }
+        for draft in self.db.unannounced_comment_drafts(BATCH_SIZE).await? {
+            let ts = self.slack.comment_draft(&draft).await?;
+            self.db.set_comment_draft_announced(draft.id, &ts).await?;
+            info!(
+                issue_url = draft.issue_url,
+                "posted comment draft for approval"
+            );
+        }
+        Ok(())
+    }
+
+    async fn post(&self, comment: &PendingComment) -> Result<(), CommentQueueError> {
+        match comment.source.as_str() {
+            "Github" if comment.suggestions && self.cfg.update_in_place => {
+                self.post_github_in_place(comment).await?
+            }
+            "Github" => {
+                self.comment_github(comment).await?;
+            }
+            "HuggingFace" => self.post_huggingface(comment).await?,
+            source => return Err(CommentQueueError::UnknownSource(source.to_owned())),
+        }
+        Ok(())
+    }
+
+    /// Edits the bot's comment on the issue, commenting when there is none yet or it was deleted,
+    /// and keeps the new body in the comment's revisions
+    async fn post_github_in_place(
+        &self,
+        comment: &PendingComment,
+    ) -> Result<(), CommentQueueError> {
+        let mut comment_url = self.db.bot_comment(&comment.issue_url).await?;
+        if let Some(url) = &comment_url {
+            match self
+                .github_api
+                .update_comment(url, comment.body.clone())
+                .await
+            {
+                Ok(()) => (),
+                Err(GithubApiError::Gone(_)) => {
+                    info!(
+                        issue_url = comment.issue_url,
+                        "bot comment was deleted, commenting again"
+                    );
+                    comment_url = None;
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        if comment_url.is_none() {
+            comment_url = self.comment_github(comment).await?;
+        }
+        if let Some(comment_url) = comment_url {
+            self.db
+                .save_bot_comment(&comment.issue_url, &comment_url, &comment.body)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Comments on a GitHub issue, unless an earlier attempt failed without an answer after
+    /// GitHub took the comment, returns the comment's api url
+    async fn comment_github(
+        &self,
+        comment: &PendingComment,
+    ) -> Result<Option<String>, CommentQueueError> {
+        if comment.attempts > 0 {
+            if let Some(comment_url) = self
+                .github_api
+                .find_comment(&comment.issue_url, &comment.body, comment.created_at)
+                .await?
+            {
+                info!(
+                    issue_url = comment.issue_url,
+                    "comment already posted, skipping"
+                );
+                return Ok(Some(comment_url));
+            }
+        }
+        Ok(self
+            .github_api
+            .comment(&comment.issue_url, comment.body.clone())
+            .await?)
+    }
+
+    /// Posts a comment on a Hugging Face discussion at most once, even when retried after a
+    /// request that failed without an answer
+    async fn post_huggingface(&self, comment: &PendingComment) -> Result<(), CommentQueueError> {
+        let fingerprint = comment_fingerprint(comment.id, &comment.body);
+        let posted = match self
+            .db
+            .comment_idempotency_key(&comment.issue_url, &fingerprint)
+            .await?
+        {
+            Some(true) => true,
+            Some(false) => {
+                self.huggingface_api
+                    .has_comment(&comment.issue_url, &comment.body)
+                    .await?
+            }
+            None => false,
+        };
+        if posted {
+            info!(
+                issue_url = comment.issue_url,
+                "comment already posted, skipping"
+            );
+        } else {
+            self.db
+                .save_comment_idempotency_key(&comment.issue_url, &fingerprint, false)
+                .await?;
+            self.huggingface_api
+                .comment(&comment.issue_url, comment.body.clone())
+                .await?;
+        }
+        self.db
+            .save_comment_idempotency_key(&comment.issue_url, &fingerprint, true)
+            .await?;
+        Ok(())
+    }
+
+    /// Posts a batch of pending comments, returns whether some were left in the queue
+    async fn process(&self, pacing: &mut Pacing) -> Result<bool, CommentQueueError> {
+        let pending = self.db.pending_comments(BATCH_SIZE).await?;
+        let pending_count = pending.len();
+        let mut batch = Vec::new();
+        for comment in next_batch(pending, pacing, Instant::now()) {
+            // a maintainer may have muted the bot since the comment was queued
+            if self.db.is_issue_url_suppressed(&comment.issue_url).await? {
+                info!(
+                    issue_url = comment.issue_url,
+                    "issue was suppressed, dropping queued comment"
+                );
+                self.db.delete_pending_comment(comment.id).await?;
+            } else if comment.attempts > 0
+                && !self.retry_budget.withdraw(&retry::host(&comment.issue_url))
+            {
+                // shed, the comment stays queued until the budget refills
+                continue;
+            } else {
+                batch.push(comment);
+            }
+        }
+        let batch_size = batch.len();
+        let results: Vec<(PendingComment, Result<(), CommentQueueError>)> = stream::iter(batch)
+            .map(|comment| async move {
+                let res = self.post(&comment).await;
+                (comment, res)
+            })
+            .buffer_unordered(self.cfg.max_concurrency)
+            .collect()
+            .await;
+
+        let now = Instant::now();
+        for (comment, res) in results {
+            let delay = match res.as_ref().map_err(|err| err.retry_class()) {
+                Ok(()) => Duration::from_secs(self.cfg.min_interval_secs),
+                Err(RetryClass::RateLimited) => {
+                    let pause = Duration::from_secs(self.cfg.rate_limit_pause_secs);
+                    res.as_ref()
+                        .err()
+                        .and_then(Classify::retry_after)
+                        .map_or(pause, |retry_after| retry_after.max(pause))
+                }
+                Err(RetryClass::Retryable) => Duration::from_secs(self.cfg.min_interval_secs),
+                Err(RetryClass::Fatal) => Duration::ZERO,
+            };
+            pacing.delay(&comment.repository_full_name, now + delay);
+            match res {
+                Ok(()) => {
+                    info!(
+                        repository = comment.repository_full_name,
+                        issue_url = comment.issue_url,
+                        "posted queued comment"
+                    );
+                    self.retry_budget.deposit(&retry::host(&comment.issue_url));
+                    self.db.delete_pending_comment(comment.id).await?;
+                    self.db.mark_events_commented(&comment.issue_url).await?;
+                }
+                Err(CommentQueueError::Github(GithubApiError::Gone(_))) => {
+                    warn!(
+                        issue_url = comment.issue_url,
+                        "issue is gone upstream, dropping queued comment"
+                    );
+                    self.db.mark_issue_gone(&comment.issue_url).await?;
+                    self.db.delete_pending_comment(comment.id).await?;
+                }
+                Err(err) if err.retry_class() == RetryClass::Fatal => {
+                    error!(
+                        issue_url = comment.issue_url,
+                        err = err.to_string(),
+                        "dropping queued comment"
+                    );
+                    self.db.delete_pending_comment(comment.id).await?;
+                }
+                Err(err) if comment.attempts + 1 >= self.cfg.max_attempts => {
+                    error!(
+                        issue_url = comment.issue_url,
+                        attempts = comment.attempts + 1,
+                        err = err.to_string(),
+                        "dropping queued comment after too many attempts"
+                    );
+                    self.db.delete_pending_comment(comment.id).await?;
+                }
+                Err(err) => {
+                    let delay = err.retry_after().unwrap_or_else(|| {
+                        backoff(
+                            Duration::from_secs(self.cfg.min_interval_secs),
+                            MAX_RETRY_DELAY,
+                            comment.attempts,
+                        )
+                    });
+                    self.db
+                        .reschedule_pending_comment(
+                            comment.id,
+                            delay.as_secs() as i64,
+                            &err.to_string(),
+                        )
+                        .await?;
+                    warn!(
+                        issue_url = comment.issue_url,
+                        delay_secs = delay.as_secs(),
+                        err = err.to_string(),
+                        "failed to post queued comment, will retry"
+                    );
+                }
+            }
+        }
+        Ok(pending_count > batch_size)
+    }
+}
+
+/// Drains the comment queue, only one instance of the bot does so at a time
+pub async fn start_comment_queue(queue: CommentQueue) -> anyhow::Result<()> {
+    info!("starting comment queue");
+    let mut pacing = Pacing::default();
+    let shutdown = shutdown_signal();
+    pin_mut!(shutdown);
+    loop {
+        let mut backlog = false;
+        match queue.locks.try_acquire(LOCK_NAME).await {
+            Ok(Some(lease)) => {
+                if queue.cfg.approval.enabled {
+                    if let Err(err) = queue.process_drafts().await {
+                        error!(err = err.to_string(), "error processing comment drafts");
+                    }
+                }
+                match queue.process(&mut pacing).await {
+                    Ok(has_backlog) => backlog = has_backlog,
+                    Err(err) => error!(err = err.to_string(), "error processing comment queue"),
+                }
+                if let Err(err) = lease.release().await {
+                    error!(
+                        err = err.to_string(),
+                        "failed to release comment queue lock"
+                    );
+                }
+            }
+            Ok(None) => (),
+            Err(err) => error!(
+                err = err.to_string(),
+                "failed to acquire comment queue lock"
+            ),
+        }
+        // comments held back by pacing are picked up again once their repository is allowed
+        let wait = if backlog {
+            Duration::from_secs(1)
+        } else {
+            Duration::from_secs(queue.cfg.poll_interval_secs)
+        };
+        select! {
+            _ = queue.notify.notified() => (),
+            _ = sleep(wait) => (),
+            _ = &mut shutdown => break,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use axum::{
+        http::{Method, StatusCode},
+        routing::{get, patch, post},
+        Json, Router,
+    };
+    use chrono::Utc;
+    use serde_json::json;
+
+    use crate::{
+        config::load_config,
+        footer::CommentFooter,
+        github::GithubApi,
+        huggingface::HuggingfaceApi,
+        live_config::LiveConfig,
+        locks::Locks,
+        slack::Slack,
+        storage::{PendingComment, Storage},
+        test_harness::{test_db, MockServer},
+    };
+
+    use super::{comment_fingerprint, next_batch, CommentQueue, Pacing};
+
+    fn pending(id: i32, repository_full_name: &str) -> PendingComment {
+        PendingComment {
+            id,
+            source: "Github".to_owned(),
+            repository_full_name: repository_full_name.to_owned(),
+            issue_url: format!("https://api.github.com/repos/{repository_full_name}/issues/{id}"),
+            body: String::new(),
+            suggestions: false,
+            attempts: 0,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_post_github_in_place() {
+        let github = MockServer::start(
+            Router::new()
+                .route("/repos/o/r", get(|| async { Json(json!({})) }))
+                .route(
+                    "/repos/o/r/issues/comments/1",
+                    patch(|| async { Json(json!({})) }),
+                )
+                .route(
+                    "/repos/o/r/issues/comments/2",
+                    patch(|| async { StatusCode::NOT_FOUND }),
+                )
+                .route(
+                    "/repos/o/r/issues/{number}/comments",
+                    post(|| async {
+                        (
+                            StatusCode::CREATED,
+                            Json(json!({ "url": "https://api.github.com/repos/o/r/issues/comments/3" })),
+                        )
+                    }),
+                ),
+        )
+        .await;
+        let mut config: crate::config::IssueBotConfig = load_config("ISSUE_BOT_TEST").unwrap();
+        config.github_api.base_url = github.url.clone();
+        config.github_api.comments_enabled = true;
+        config.comment_queue.update_in_place = true;
+        let live_config = LiveConfig::new((&config).into());
+        let footer = CommentFooter::new(&config.feedback, config.embedding_api.model.clone());
+        let db = test_db().await;
+        let queue = CommentQueue::new(
+            config.comment_queue,
+            db.clone(),
+            GithubApi::new(
+                config.github_api,
+                &config.http_client,
+                &config.timeouts,
+                live_config.clone(),
+                footer.clone(),
+            )
+            .unwrap(),
+            HuggingfaceApi::new(
+                config.huggingface_api,
+                &config.http_client,
+                &config.timeouts,
+                live_config.clone(),
+                footer,
+            )
+            .unwrap(),
+            Locks::new(db.clone()),
+            Slack::new(
+                &config.slack,
+                &config.http_client,
+                &config.timeouts,
+                live_config,
+                None,
+            )
+            .unwrap(),
+        );
+        let suggestions = |number: i32| PendingComment {
+            issue_url: format!("{}/repos/o/r/issues/{number}", github.url),
+            body: "Similar issues, updated".to_owned(),
+            suggestions: true,
+            ..pending(number, "o/r")
+        };
+
+        // the bot's comment is edited
+        let edited = suggestions(1);
+        let comment_1 = format!("{}/repos/o/r/issues/comments/1", github.url);
+        db.save_bot_comment(&edited.issue_url, &comment_1, "Similar issues")
+            .await
+            .unwrap();
+        queue.post(&edited).await.unwrap();
+        let request = github
+            .recorder
+            .wait_for(
+                Method::PATCH,
+                "/repos/o/r/issues/comments/1",
+                Duration::from_secs(5),
+            )
+            .await;
+        assert!(request.body.contains("Similar issues, updated"));
+        let revisions = db.bot_comment_revisions(&edited.issue_url).await.unwrap();
+        assert_eq!(revisions.len(), 2);
+        assert!(revisions.iter().all(|r| r.comment_url == comment_1));
+        assert_eq!(revisions[1].body, "Similar issues, updated");
+
+        // a deleted comment is replaced by a new one
+        let deleted = suggestions(2);
+        let comment_2 = format!("{}/repos/o/r/issues/comments/2", github.url);
+        db.save_bot_comment(&deleted.issue_url, &comment_2, "Similar issues")
+            .await
+            .unwrap();
+        queue.post(&deleted).await.unwrap();
+        github
+            .recorder
+            .wait_for(
+                Method::POST,
+                "/repos/o/r/issues/2/comments",
+                Duration::from_secs(5),
+            )
+            .await;
+        let comment_3 = "https://api.github.com/repos/o/r/issues/comments/3";
+        assert_eq!(
+            db.bot_comment(&deleted.issue_url).await.unwrap().as_deref(),
+            Some(comment_3)
+        );
+        let revisions = db.bot_comment_revisions(&deleted.issue_url).await.unwrap();
+        assert_eq!(revisions.len(), 2);
+        assert_eq!(revisions[1].comment_url, comment_3);
+        assert_eq!(revisions[1].body, "Similar issues, updated");
+    }
+
+    #[test]
+    fn test_comment_fingerprint() {
+        assert_eq!(
+            comment_fingerprint(1, "Similar issues"),
+            comment_fingerprint(1, "Similar issues\n")
+        );
+        assert_ne!(
+            comment_fingerprint(1, "Similar issues"),
+            comment_fingerprint(2, "Similar issues")
+        );
+    }
+
+    #[test]
+    fn test_next_batch() {
+        let now = Instant::now();
+        let mut pacing = Pacing::default();
+        pacing.delay("huggingface/tokenizers", now + Duration::from_secs(10));
+        let pending_comments = || {
+            vec![
+                pending(1, "huggingface/transformers"),
+                pending(2, "huggingface/transformers"),
+                pending(3, "huggingface/tokenizers"),
+                pending(4, "huggingface/lor-e"),
+            ]
+        };
+
+        let ids: Vec<i32> = next_batch(pending_comments(), &pacing, now)
+            .iter()
+            .map(|c| c.id)
+            .collect();
+        assert_eq!(ids, vec![1, 4]);
+
+        let ids: Vec<i32> = next_batch(pending_comments(), &pacing, now + Duration::from_secs(10))
+            .iter()
+            .map(|c| c.id)
+            .collect();
+        assert_eq!(ids, vec![1, 3, 4]);
+    }
+}
@@ -0,0 +1,260 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use nanoid::nanoid;
+use thiserror::Error;
+use tracing::info;
+
+use crate::{
+    comment_queue::CommentQueue,
+    config::{CommentTriggerConfig, RepositoryConfig},
+    embeddings::{
+        queue::{EmbeddingQueue, Priority},
+        EmbeddingError,
+    },
+    github::GithubApi,
+    huggingface::HuggingfaceApi,
+    repo_groups::RepoGroups,
+    settings::Settings,
+    storage::{Database, Storage, StorageError},
+    CommentData, Source,
+};
+
+#[derive(Debug, Error)]
+pub enum CommentTriggerError {
+    #[error("embedding error: {0}")]
+    Embedding(#[from] EmbeddingError),
+    #[error("storage error: {0}")]
+    Storage(#[from] StorageError),
+    #[error("unknown issue source: '{0}'")]
+    UnknownSource(String),
+}
+
+/// window of [CommentTriggerConfig::max_triggers_per_hour]
+const TRIGGER_WINDOW: Duration = Duration::from_secs(3_600);
+
+/// `body` without `keyword`, when it contains it, matched case insensitively
+///
+/// Characters are compared one by one on the original body, lowercasing it as a whole could
+/// change its length and shift the offsets.
+fn strip_keyword(body: &str, keyword: &str) -> Option<String> {
+    let match_end = |start: usize| {
+        let mut rest = body[start..].char_indices();
+        for expected in keyword.chars() {
+            let (_, c) = rest.next()?;
+            if !c.to_lowercase().eq(expected.to_lowercase()) {
+                return None;
+            }
+        }
+        Some(rest.next().map_or(body.len(), |(offset, _)| start + offset))
+    };
+    let (start, end) = body
+        .char_indices()
+        .find_map(|(start, _)| Some((start, match_end(start)?)))?;
+    Some(
+        format!("{} {}", &body[..start], &body[end..])
+            .trim()
+            .to_owned(),
+    )
+}
+
+/// Replies to comments asking whether their issue is the same as another one with the issues
+/// closest to the comment alone, only when asked to with the configured keyword
+#[derive(Clone)]
+pub struct CommentTrigger {
+    cfg: CommentTriggerConfig,
+    comment_queue: CommentQueue,
+    db: Database,
+    embedding_queue: EmbeddingQueue,
+    github_api: GithubApi,
+    huggingface_api: HuggingfaceApi,
+    repo_groups: RepoGroups,
+    repositories: HashMap<String, RepositoryConfig>,
+    settings: Settings,
+    /// recent triggers of each author, see [CommentTriggerConfig::max_triggers_per_hour]
+    triggers: Arc<Mutex<HashMap<String, Vec<Instant>>>>,
+}
+
+impl CommentTrigger {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        cfg: CommentTriggerConfig,
+        comment_queue: CommentQueue,
+        db: Database,
+        embedding_queue: EmbeddingQueue,
+        github_api: GithubApi,
+        huggingface_api: HuggingfaceApi,
+        repo_groups: RepoGroups,
+        repositories: HashMap<String, RepositoryConfig>,
+        settings: Settings,
+    ) -> Self {
+        Self {
+            cfg,
+            comment_queue,
+            db,
+            embedding_queue,
+            github_api,
+            huggingface_api,
+            repo_groups,
+            repositories,
+            settings,
+            triggers: Arc::default(),
+        }
+    }
+
+    /// whether `author` may trigger another reply, recording the trigger when it may
+    fn allow_trigger(&self, author: Option<&str>) -> bool {
+        let mut triggers = self.triggers.lock().unwrap();
+        triggers.retain(|_, recent| {
+            recent.retain(|at| at.elapsed() < TRIGGER_WINDOW);
+            !recent.is_empty()
+        });
+        let recent = triggers
+            .entry(author.unwrap_or_default().to_lowercase())
+            .or_default();
+        if recent.len() >= self.cfg.max_triggers_per_hour {
+            return false;
+        }
+        recent.push(Instant::now());
+        true
+    }
+
+    /// run id of the reply, when one was queued
+    pub async fn handle(
+        &self,
+        comment: &CommentData,
+    ) -> Result<Option<String>, CommentTriggerError> {
+        let Some(text) = strip_keyword(&comment.body, &self.cfg.keyword) else {
+            return Ok(None);
+        };
+        if text.is_empty() {
+            return Ok(None);
+        }
+        let Some(issue) = self.db.stored_issue(comment.issue_id).await? else {
+            return Ok(None);
+        };
+        if self.db.is_issue_suppressed(comment.issue_id).await? {
+            info!(
+                comment_id = comment.source_id,
+                "issue is suppressed, not replying to triggering comment"
+            );
+            return Ok(None);
+        }
+        let Some(repository) = self
+            .repositories
+            .get(&issue.repository_full_name)
+            .filter(|r| r.comment_trigger)
+        else {
+            return Ok(None);
+        };
+        if !self.allow_trigger(comment.author.as_deref()) {
+            info!(
+                comment_id = comment.source_id,
+                author = comment.author,
+                "too many triggering comments from author, not replying"
+            );
+            return Ok(None);
+        }
+        let source = match issue.source.as_str() {
+            "Github" => Source::Github,
+            "HuggingFace" => Source::HuggingFace,
+            source => return Err(CommentTriggerError::UnknownSource(source.to_owned())),
+        };
+
+        let similarity = self
+            .settings
+            .similarity(Some(&issue.repository_full_name))
+            .await?;
+        let embedding = self
+            .embedding_queue
+            .generate_embedding(text, Priority::Interactive)
+            .await?;
+        let mut closest_issues = self
+            .db
+            .closest_issues(
+                &embedding,
+                &repository.excluded_labels,
+                &self.repo_groups.search_scope(&issue.repository_full_name),
+                // the commented issue is likely among the closest
+                similarity.max_suggestions + 1,
+                false,
+            )
+            .await?;
+        closest_issues.retain(|ci| {
+            ci.cosine_similarity >= similarity.min_similarity
+                && (ci.repository_full_name != issue.repository_full_name
+                    || ci.number != issue.number)
+        });
+        closest_issues.truncate(similarity.max_suggestions.max(0) as usize);
+        if closest_issues.is_empty() {
+            info!(
+                comment_id = comment.source_id,
+                "no similar issue found for triggering comment"
+            );
+            return Ok(None);
+        }
+
+        let run_id = nanoid!();
+        let body = match source {
+            Source::Github => self.github_api.suggestions_comment(
+                &issue.repository_full_name,
+                &closest_issues,
+                None,
+                &run_id,
+            ),
+            Source::HuggingFace => self.huggingface_api.suggestions_comment(
+                &issue.repository_full_name,
+                &closest_issues,
+                &run_id,
+            ),
+        };
+        self.comment_queue
+            .enqueue_suggestions(
+                &source,
+                &issue.repository_full_name,
+                &issue.url,
+                &run_id,
+                None,
+                body,
+            )
+            .await?;
+        info!(
+            comment_id = comment.source_id,
+            suggestions = closest_issues.len(),
+            "enqueued reply to triggering comment"
+        );
+        Ok(Some(run_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::strip_keyword;
+
+    #[test]
+    fn test_strip_keyword() {
+        let keyword = "@lor-e similar";
+        assert_eq!(
+            strip_keyword(
+                "@Lor-E Similar is this the same as the tokenizer crash?",
+                keyword
+            )
+            .as_deref(),
+            Some("is this the same as the tokenizer crash?")
+        );
+        assert_eq!(
+            strip_keyword("same error here @lor-e similar", keyword).as_deref(),
+            Some("same error here")
+        );
+        assert!(strip_keyword("is this the same as #12?", keyword).is_none());
+        // lowercasing 'İ' lengthens it, which used to shift the keyword's offsets
+        assert_eq!(
+            strip_keyword("İİİ @LOR-E SIMILAR ünicode", keyword).as_deref(),
+            Some("İİİ   ünicode")
+        );
+        assert_eq!(strip_keyword("İ", keyword), None);
+    }
+}
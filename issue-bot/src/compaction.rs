@@ -0,0 +1,292 @@
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, TimeDelta, Timelike, Utc};
+use futures::pin_mut;
+use tokio::{select, time::interval};
+use tracing::{error, info, warn};
+
+use crate::{
+    config::CompactionConfig,
+    debug::DebugState,
+    locks::Locks,
+    shutdown_signal,
+    storage::{CompactionRun, Database, Storage, StorageError, TableHealth},
+};
+
+const LOCK_NAME: &str = "compaction";
+
+const VACUUM: &str = "vacuum";
+const REINDEX: &str = "reindex";
+
+/// an action isn't repeated sooner, e.g. when a long running transaction keeps dead rows around
+const MIN_INTERVAL: TimeDelta = TimeDelta::hours(20);
+
+/// whether `hour` is within `[start, end)`, which may span midnight
+fn in_window(hour: u32, start: u32, end: u32) -> bool {
+    if start <= end {
+        (start..end).contains(&hour)
+    } else {
+        hour >= start || hour < end
+    }
+}
+
+/// time left before the window ending at `end_hour` closes, `now` being within it
+fn window_left(now: DateTime<Utc>, end_hour: u32) -> Duration {
+    let end = now
+        .date_naive()
+        .and_hms_opt(end_hour, 0, 0)
+        .map(|end| end.and_utc())
+        .unwrap_or(now);
+    let end = if end <= now {
+        end + TimeDelta::days(1)
+    } else {
+        end
+    };
+    (end - now).to_std().unwrap_or_default()
+}
+
+fn dead_tuple_ratio(health: &TableHealth) -> f64 {
+    let tuples = health.live_tuples + health.dead_tuples;
+    if tuples == 0 {
+        return 0.;
+    }
+    health.dead_tuples as f64 / tuples as f64
+}
+
+/// stale index entries per live row since the last reindex, `baseline` being the churn back then
+fn index_churn_ratio(health: &TableHealth, baseline: i64) -> f64 {
+    // statistics were reset since
+    let churn = if health.index_churn < baseline {
+        health.index_churn
+    } else {
+        health.index_churn - baseline
+    };
+    churn as f64 / health.live_tuples.max(1) as f64
+}
+
+/// Keeps the issues table and its HNSW index from bloating under frequent embedding updates
+#[derive(Clone)]
+pub struct Compaction {
+    cfg: CompactionConfig,
+    db: Database,
+    debug_state: DebugState,
+    locks: Locks,
+}
+
+impl Compaction {
+    pub fn new(cfg: CompactionConfig, db: Database, debug_state: DebugState, locks: Locks) -> Self {
+        Self {
+            cfg,
+            db,
+            debug_state,
+            locks,
+        }
+    }
+
+    /// time left in the maintenance window, `None` once it closed
+    fn time_left(&self) -> Option<Duration> {
+        let now = Utc::now();
+        in_window(
+            now.hour(),
+            self.cfg.window_start_hour,
+            self.cfg.window_end_hour,
+        )
+        .then(|| window_left(now, self.cfg.window_end_hour))
+    }
+
+    async fn check(&self) -> Result<(), StorageError> {
+        let Some(health) = self.db.issues_table_health().await? else {
+            return Ok(());
+        };
+        if self.cfg.apply {
+            for index in self.db.drop_invalid_issues_indexes().await? {
+                info!(index, "dropped index left behind by a cancelled reindex");
+            }
+        }
+        let dead_ratio = dead_tuple_ratio(&health);
+        if dead_ratio > self.cfg.max_dead_tuple_ratio {
+            info!(
+                dead_tuples = health.dead_tuples,
+                dead_ratio, "issues table has too many dead rows"
+            );
+            self.compact(VACUUM, health).await?;
+        }
+
+        let Some(health) = self.db.issues_table_health().await? else {
+            return Ok(());
+        };
+        let baseline = self
+            .db
+            .last_compaction_run(REINDEX)
+            .await?
+            .map(|run| run.index_churn)
+            .unwrap_or_default();
+        let churn_ratio = index_churn_ratio(&health, baseline);
+        if churn_ratio > self.cfg.max_index_churn_ratio {
+            info!(
+                index_bytes = health.index_bytes,
+                churn_ratio, "issues table indexes are bloated"
+            );
+            self.compact(REINDEX, health).await?;
+        }
+        Ok(())
+    }
+
+    /// runs `action`, reporting the sizes before and after, or only advises it when not applying
+    ///
+    /// Only the vector indexes are rebuilt, B-trees shrinking when vacuumed. Nothing is started
+    /// once the maintenance window closed, and statements still running then are cancelled.
+    async fn compact(&self, action: &str, before: TableHealth) -> Result<(), StorageError> {
+        if let Some(last) = self.db.last_compaction_run(action).await? {
+            if Utc::now() - last.created_at < MIN_INTERVAL {
+                info!(action, last_run = %last.created_at, "skipping recent compaction");
+                return Ok(());
+            }
+        }
+        if !self.cfg.apply {
+            warn!(
+                action,
+                table_bytes = before.table_bytes,
+                index_bytes = before.index_bytes,
+                "issues table needs compaction, run it manually or turn `compaction.apply` on"
+            );
+            return Ok(());
+        }
+
+        let created_at = Utc::now();
+        let start = Instant::now();
+        let statements = match action {
+            VACUUM => vec![None],
+            _ => self
+                .db
+                .issues_vector_indexes()
+                .await?
+                .into_iter()
+                .map(Some)
+                .collect(),
+        };
+        for index in statements {
+            let Some(time_left) = self.time_left() else {
+                info!(action, "maintenance window closed, stopping compaction");
+                return Ok(());
+            };
+            match &index {
+                None => self.db.vacuum_issues(time_left).await?,
+                Some(index) => {
+                    info!(index, "rebuilding index");
+                    self.db.reindex_issues_index(index, time_left).await?
+                }
+            }
+        }
+        let duration_ms = start.elapsed().as_millis() as i64;
+        let after = self.db.issues_table_health().await?.unwrap_or(before);
+        info!(
+            action,
+            duration_ms,
+            table_bytes_before = before.table_bytes,
+            table_bytes_after = after.table_bytes,
+            index_bytes_before = before.index_bytes,
+            index_bytes_after = after.index_bytes,
+            "compacted issues table"
+        );
+        self.db
+            .insert_compaction_run(&CompactionRun {
+                action: action.to_owned(),
+                dead_tuples: before.dead_tuples,
+                index_churn: before.index_churn,
+                table_bytes_before: before.table_bytes,
+                table_bytes_after: after.table_bytes,
+                index_bytes_before: before.index_bytes,
+                index_bytes_after: after.index_bytes,
+                duration_ms,
+                created_at,
+            })
+            .await
+    }
+}
+
+/// Checks the issues table during the maintenance window, only one instance of the bot does so at
+/// a time
+pub async fn start_compaction(compaction: Compaction) -> anyhow::Result<()> {
+    if !compaction.cfg.enabled {
+        return Ok(());
+    }
+
+    info!("starting compaction");
+    let mut ticker = interval(Duration::from_secs(compaction.cfg.check_interval_secs));
+    let shutdown = shutdown_signal();
+    pin_mut!(shutdown);
+    loop {
+        select! {
+            _ = ticker.tick() => {
+                let hour = Utc::now().hour();
+                if !in_window(hour, compaction.cfg.window_start_hour, compaction.cfg.window_end_hour) {
+                    continue;
+                }
+                match compaction.locks.try_acquire(LOCK_NAME).await {
+                    Ok(Some(lease)) => {
+                        if let Err(err) = compaction.check().await {
+                            compaction.debug_state.record_error("compaction", &err);
+                            error!(err = err.to_string(), "error compacting issues table");
+                        }
+                        if let Err(err) = lease.release().await {
+                            error!(err = err.to_string(), "failed to release compaction lock");
+                        }
+                    }
+                    Ok(None) => (),
+                    Err(err) => error!(err = err.to_string(), "failed to acquire compaction lock"),
+                }
+            }
+            _ = &mut shutdown => break,
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{TimeZone, Utc};
+
+    use super::{dead_tuple_ratio, in_window, index_churn_ratio, window_left};
+    use crate::storage::TableHealth;
+
+    fn health(live_tuples: i64, dead_tuples: i64, index_churn: i64) -> TableHealth {
+        TableHealth {
+            live_tuples,
+            dead_tuples,
+            index_churn,
+            table_bytes: 0,
+            index_bytes: 0,
+        }
+    }
+
+    #[test]
+    fn test_in_window() {
+        assert!(in_window(3, 2, 5));
+        assert!(!in_window(5, 2, 5));
+        assert!(in_window(23, 22, 4));
+        assert!(in_window(1, 22, 4));
+        assert!(!in_window(12, 22, 4));
+    }
+
+    #[test]
+    fn test_window_left() {
+        let now = Utc.with_ymd_and_hms(2026, 3, 1, 3, 30, 0).unwrap();
+        assert_eq!(window_left(now, 5), Duration::from_secs(90 * 60));
+        // the window spans midnight
+        let now = Utc.with_ymd_and_hms(2026, 3, 1, 23, 0, 0).unwrap();
+        assert_eq!(window_left(now, 4), Duration::from_secs(5 * 3600));
+    }
+
+    #[test]
+    fn test_ratios() {
+        assert_eq!(dead_tuple_ratio(&health(0, 0, 0)), 0.);
+        assert_eq!(dead_tuple_ratio(&health(75, 25, 0)), 0.25);
+        assert_eq!(index_churn_ratio(&health(100, 0, 150), 100), 0.5);
+        // statistics were reset after the last reindex
+        assert_eq!(index_churn_ratio(&health(100, 0, 30), 100), 0.3);
+    }
+}
@@ -0,0 +1,354 @@
+use std::{collections::HashMap, str::FromStr};
+
+use futures::{stream, StreamExt, TryStreamExt};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    config::RepositoryConfig,
+    embeddings::{
+        cosine_similarity,
+        queue::{EmbeddingQueue, Priority},
+        EmbeddingError,
+    },
+    extraction::Extractor,
+    repo_groups::RepoGroups,
+    repo_metadata::RepoMetadata,
+    settings::Settings,
+    storage::{Database, HotIssue, IssueText, Storage, StorageError},
+    ClosestIssue,
+};
+
+/// comments past the first ones are left out of the heatmap, each chunk costing an embedding
+const MAX_CHUNKS: usize = 8;
+/// chunk embeddings requested at once, the heatmap only being a debugging aid
+const CONCURRENT_EMBEDDINGS: usize = 2;
+
+#[derive(Debug, Error)]
+pub enum CompareError {
+    #[error("embedding error: {0}")]
+    Embedding(#[from] EmbeddingError),
+    #[error("invalid issue reference '{0}', expected a source id or `owner/repository#number`")]
+    InvalidReference(String),
+    #[error("issue {0} not found")]
+    NotFound(String),
+    #[error("storage error: {0}")]
+    Storage(#[from] StorageError),
+}
+
+/// Issue compared, by source id or as `huggingface/transformers#123`
+#[derive(Clone, Debug, PartialEq)]
+pub enum IssueReference {
+    SourceId(i64),
+    Number {
+        repository_full_name: String,
+        number: i32,
+    },
+}
+
+impl FromStr for IssueReference {
+    type Err = CompareError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || CompareError::InvalidReference(s.to_owned());
+        match s.split_once('#') {
+            Some((repository_full_name, number)) if repository_full_name.contains('/') => {
+                Ok(Self::Number {
+                    repository_full_name: repository_full_name.to_owned(),
+                    number: number.parse().map_err(|_| invalid())?,
+                })
+            }
+            Some(_) => Err(invalid()),
+            None => s.parse().map(Self::SourceId).map_err(|_| invalid()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ComparedIssue {
+    pub source_id: i64,
+    pub repository_full_name: String,
+    pub number: i32,
+    pub title: String,
+    pub html_url: String,
+    /// rows or columns of [IssueComparison::heatmap], the description then every comment
+    pub chunks: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IssueComparison {
+    pub issue_a: ComparedIssue,
+    pub issue_b: ComparedIssue,
+    /// of the stored embeddings, the one suggestions are based on and `min_similarity` applies to
+    pub similarity: f64,
+    /// added by the topic and cohort boosts when ranking `issue_b` as a suggestion for `issue_a`,
+    /// the links boost only telling candidates apart
+    pub boost: f64,
+    /// `similarity` plus `boost`, which suggestions are ordered by
+    pub rank_score: f64,
+    /// `min_similarity` of `issue_a`'s repository
+    pub min_similarity: f64,
+    /// why `issue_b` is never suggested for `issue_a`, empty when it may be
+    pub filtered_by: Vec<String>,
+    /// `heatmap[i][j]` is the similarity of chunk `i` of `issue_a` with chunk `j` of `issue_b`
+    pub heatmap: Vec<Vec<f64>>,
+}
+
+/// Explains the similarity of two stored issues, chunk by chunk, and how `issue_b` ranks as a
+/// suggestion for `issue_a`
+#[derive(Clone)]
+pub struct IssueComparer {
+    db: Database,
+    embedding_queue: EmbeddingQueue,
+    extractor: Extractor,
+    repo_groups: RepoGroups,
+    repo_metadata: RepoMetadata,
+    repositories: HashMap<String, RepositoryConfig>,
+    settings: Settings,
+}
+
+fn heatmap(a: &[Vec<f32>], b: &[Vec<f32>]) -> Vec<Vec<f64>> {
+    a.iter()
+        .map(|a| b.iter().map(|b| cosine_similarity(a, b)).collect())
+        .collect()
+}
+
+/// why `issue_b` is left out of the searches made for `issue_a`
+fn filters(
+    issue_a: &HotIssue,
+    issue_b: &HotIssue,
+    search_scope: &[String],
+    excluded_labels: &[String],
+) -> Vec<String> {
+    let mut filtered_by = Vec::new();
+    if issue_a.source_id == issue_b.source_id {
+        filtered_by.push("same issue".to_owned());
+    }
+    if !search_scope.contains(&issue_b.repository_full_name) {
+        filtered_by.push(format!(
+            "outside the search scope of {}",
+            issue_a.repository_full_name
+        ));
+    }
+    for label in issue_b
+        .labels
+        .iter()
+        .filter(|label| excluded_labels.contains(label))
+    {
+        filtered_by.push(format!("excluded label `{label}`"));
+    }
+    filtered_by
+}
+
+impl IssueComparer {
+    pub fn new(
+        db: Database,
+        embedding_queue: EmbeddingQueue,
+        extractor: Extractor,
+        repo_groups: RepoGroups,
+        repo_metadata: RepoMetadata,
+        repositories: HashMap<String, RepositoryConfig>,
+        settings: Settings,
+    ) -> Self {
+        Self {
+            db,
+            embedding_queue,
+            extractor,
+            repo_groups,
+            repo_metadata,
+            repositories,
+            settings,
+        }
+    }
+
+    pub async fn compare(
+        &self,
+        issue_a: &IssueReference,
+        issue_b: &IssueReference,
+    ) -> Result<IssueComparison, CompareError> {
+        let issue_a = self.issue(issue_a).await?;
+        let issue_b = self.issue(issue_b).await?;
+        let text_a = self.db.issue_text(issue_a.source_id).await?;
+        let text_b = self.db.issue_text(issue_b.source_id).await?;
+        let repository_full_name = issue_a.repository_full_name.clone();
+        let similarity = cosine_similarity(&issue_a.embedding, &issue_b.embedding);
+        let min_similarity = self
+            .settings
+            .similarity(Some(repository_full_name.as_str()))
+            .await?
+            .min_similarity;
+        let excluded_labels = self
+            .repositories
+            .get(&repository_full_name)
+            .map(|r| r.excluded_labels.clone())
+            .unwrap_or_default();
+        let mut filtered_by = filters(
+            &issue_a,
+            &issue_b,
+            &self.repo_groups.search_scope(&repository_full_name),
+            &excluded_labels,
+        );
+        if similarity < min_similarity {
+            filtered_by.push(format!("similarity below {min_similarity}"));
+        }
+
+        // ranked like in [crate::rank_suggestions]
+        let candidate = ClosestIssue {
+            title: issue_b.title.clone(),
+            number: issue_b.number,
+            html_url: issue_b.html_url.clone(),
+            labels: issue_b.labels.clone(),
+            repository_full_name: issue_b.repository_full_name.clone(),
+            cosine_similarity: similarity,
+            boost: 0.,
+            embedding: Vec::new(),
+        };
+        let candidates = self
+            .repo_metadata
+            .boost_topics(&repository_full_name, vec![candidate])
+            .await;
+        let system_info = self.extractor.parse(&repository_full_name, &text_a.body);
+        let boost = self
+            .extractor
+            .boost_cohort(&repository_full_name, &system_info, candidates)
+            .await
+            .first()
+            .map(|candidate| candidate.boost)
+            .unwrap_or_default();
+
+        let (issue_a, chunks_a) = self.chunks(issue_a, text_a).await?;
+        let (issue_b, chunks_b) = self.chunks(issue_b, text_b).await?;
+        Ok(IssueComparison {
+            issue_a,
+            issue_b,
+            similarity,
+            boost,
+            rank_score: similarity + boost,
+            min_similarity,
+            filtered_by,
+            heatmap: heatmap(&chunks_a, &chunks_b),
+        })
+    }
+
+    async fn issue(&self, reference: &IssueReference) -> Result<HotIssue, CompareError> {
+        let source_id = match reference {
+            IssueReference::SourceId(source_id) => Some(*source_id),
+            IssueReference::Number {
+                repository_full_name,
+                number,
+            } => {
+                self.db
+                    .issue_source_id(repository_full_name, *number)
+                    .await?
+            }
+        };
+        let not_found = || match reference {
+            IssueReference::SourceId(source_id) => CompareError::NotFound(source_id.to_string()),
+            IssueReference::Number {
+                repository_full_name,
+                number,
+            } => CompareError::NotFound(format!("{repository_full_name}#{number}")),
+        };
+        let source_id = source_id.ok_or_else(not_found)?;
+        self.db
+            .hot_issues(&[source_id])
+            .await?
+            .pop()
+            .ok_or_else(not_found)
+    }
+
+    /// labels and embeddings of the issue's chunks
+    async fn chunks(
+        &self,
+        issue: HotIssue,
+        text: IssueText,
+    ) -> Result<(ComparedIssue, Vec<Vec<f32>>), CompareError> {
+        let mut labels = vec!["description".to_owned()];
+        let mut texts = vec![format!("{}\n{}", text.title, text.body)];
+        for (i, comment) in text.comments.into_iter().take(MAX_CHUNKS - 1).enumerate() {
+            labels.push(format!("comment {}", i + 1));
+            texts.push(comment);
+        }
+        let embeddings: Vec<Vec<f32>> = stream::iter(texts)
+            .map(|text| {
+                self.embedding_queue
+                    .generate_embedding(text, Priority::Background)
+            })
+            .buffered(CONCURRENT_EMBEDDINGS)
+            .try_collect()
+            .await?;
+        let compared = ComparedIssue {
+            source_id: issue.source_id,
+            repository_full_name: issue.repository_full_name,
+            number: issue.number,
+            title: issue.title,
+            html_url: issue.html_url,
+            chunks: labels,
+        };
+        Ok((compared, embeddings))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::HotIssue;
+
+    use super::{filters, heatmap, IssueReference};
+
+    #[test]
+    fn test_compare_helpers() {
+        assert_eq!(
+            "huggingface/transformers#123"
+                .parse::<IssueReference>()
+                .unwrap(),
+            IssueReference::Number {
+                repository_full_name: "huggingface/transformers".to_owned(),
+                number: 123
+            }
+        );
+        assert_eq!(
+            "4321".parse::<IssueReference>().unwrap(),
+            IssueReference::SourceId(4321)
+        );
+        assert!("transformers#123".parse::<IssueReference>().is_err());
+        assert!("huggingface/transformers#abc"
+            .parse::<IssueReference>()
+            .is_err());
+
+        let a = vec![vec![1., 0.], vec![0., 1.]];
+        let b = vec![vec![1., 0.]];
+        assert_eq!(heatmap(&a, &b), vec![vec![1.], vec![0.]]);
+
+        let issue = |source_id: i64, repository_full_name: &str, labels: &[&str]| HotIssue {
+            source_id,
+            title: "title".to_owned(),
+            number: 1,
+            html_url: String::new(),
+            labels: labels.iter().map(|l| l.to_string()).collect(),
+            repository_full_name: repository_full_name.to_owned(),
+            embedding: Vec::new(),
+        };
+        let scope = vec!["huggingface/transformers".to_owned()];
+        let excluded = vec!["wontfix".to_owned()];
+        let a = issue(1, "huggingface/transformers", &[]);
+        assert!(filters(
+            &a,
+            &issue(2, "huggingface/transformers", &["bug"]),
+            &scope,
+            &excluded
+        )
+        .is_empty());
+        assert_eq!(
+            filters(
+                &a,
+                &issue(3, "huggingface/tokenizers", &["wontfix"]),
+                &scope,
+                &excluded
+            ),
+            vec![
+                "outside the search scope of huggingface/transformers".to_owned(),
+                "excluded label `wontfix`".to_owned(),
+            ]
+        );
+    }
+}
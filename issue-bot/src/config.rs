@@ -1,44 +1,737 @@
+use std::{collections::HashMap, fmt, path::PathBuf, time::Duration};
+
 use config::{Config, ConfigError};
 use serde::Deserialize;
+use tracing::warn;
+
+use crate::storage::EMBEDDING_DIMENSIONS;
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct EmbeddingApiConfig {
     pub auth_token: String,
+    /// requests sent at once before rate limiting kicks in
+    pub burst: u32,
+    pub client_certificate: Option<ClientCertificateConfig>,
+    /// output size requested from OpenAI-style APIs serving matryoshka models, the model's own when
+    /// unset, it must match the size of the embedding columns, see
+    /// [crate::storage::EMBEDDING_DIMENSIONS]
+    #[serde(default)]
+    pub dimensions: Option<u32>,
+    #[serde(default)]
+    pub failover: FailoverConfig,
+    /// tried in order when `url` keeps failing, see [FailoverConfig]
+    #[serde(default)]
+    pub fallback_urls: Vec<String>,
+    /// texts are cut down to this many characters before being embedded, none when unset
+    #[serde(default)]
+    pub max_input_chars: Option<usize>,
+    /// model name reported in the comments' metadata
+    #[serde(default)]
+    pub model: String,
+    /// global limit shared by every pipeline
+    pub requests_per_sec: f64,
+    /// asks the provider to cut inputs down to the model limit instead of rejecting them, the
+    /// provider's default when unset
+    #[serde(default)]
+    pub truncate: Option<bool>,
     pub url: String,
 }
 
+/// S3-compatible bucket, `endpoint` being left empty for AWS itself
+#[derive(Clone, Debug, Deserialize)]
+pub struct ObjectStoreConfig {
+    pub access_key_id: String,
+    pub bucket: String,
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    #[serde(default)]
+    pub prefix: String,
+    pub region: String,
+    pub secret_access_key: String,
+}
+
+/// Where deleted issues are copied to before being removed from the database
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum ArchiveConfig {
+    /// `archived_issues` table
+    #[default]
+    Database,
+    ObjectStore(ObjectStoreConfig),
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct SummarizationApiConfig {
     pub auth_token: String,
+    /// characters per token used to estimate token counts, lower being more conservative
+    pub chars_per_token: f64,
+    pub client_certificate: Option<ClientCertificateConfig>,
+    /// tokens the model accepts, prompt and summary included, longer issues being truncated
+    pub context_window_tokens: usize,
+    #[serde(default)]
+    pub failover: FailoverConfig,
+    /// tried in order when `url` keeps failing, see [FailoverConfig]
+    #[serde(default)]
+    pub fallback_urls: Vec<String>,
+    #[serde(default)]
+    pub guardrails: SummaryGuardrailsConfig,
     pub model: String,
+    /// named system prompts, used instead of `system_prompt` by the repositories assigned to them,
+    /// see [RepositoryConfig::summarization_prompts]
+    #[serde(default)]
+    pub prompts: HashMap<String, String>,
     pub special_tokens_used: Vec<String>,
     pub system_prompt: String,
     pub url: String,
 }
 
+/// Checks of generated summaries, replaced by the first `fallback_sentences` of the issue when
+/// failing
+///
+/// Summaries are rejected when empty, longer than `max_chars`, containing one of the
+/// `banned_phrases` (case insensitive) or lines of the system prompt. With a `critique_prompt`, the
+/// model is then asked whether the summary is faithful to the issue, answering `PASS` or `FAIL`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct SummaryGuardrailsConfig {
+    pub banned_phrases: Vec<String>,
+    pub critique_prompt: Option<String>,
+    pub enabled: bool,
+    pub fallback_sentences: usize,
+    pub max_chars: usize,
+}
+
+impl Default for SummaryGuardrailsConfig {
+    fn default() -> Self {
+        Self {
+            banned_phrases: Vec::new(),
+            critique_prompt: None,
+            enabled: false,
+            fallback_sentences: 2,
+            max_chars: 500,
+        }
+    }
+}
+
+/// PEM encoded content, read from a file or given inline
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PemSource {
+    Path(PathBuf),
+    Pem(String),
+}
+
+/// Client certificate presented to endpoints requiring mutual TLS
+///
+/// `key` must be an unencrypted PKCS#8 private key (`BEGIN PRIVATE KEY`), PKCS#1 keys can be
+/// converted with `openssl pkcs8 -topk8 -nocrypt`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ClientCertificateConfig {
+    pub cert: PemSource,
+    pub key: PemSource,
+}
+
+/// Switching between an API's `url` and its `fallback_urls`
+///
+/// Requests move on to the next endpoint after `failure_threshold` consecutive failures, or right
+/// away when the current one reports being unavailable. Every `recovery_check_secs`, a request is
+/// sent to the primary endpoint again, which is used from then on if it succeeds.
+#[derive(Clone, Debug, Deserialize)]
+pub struct FailoverConfig {
+    pub failure_threshold: u32,
+    pub recovery_check_secs: u64,
+}
+
+impl Default for FailoverConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 3,
+            recovery_check_secs: 60,
+        }
+    }
+}
+
+/// Comments posted on older open issues when a new issue closely matches them, only for
+/// repositories with `backlinks` enabled
+///
+/// `{issue}` in `message` is replaced by the new issue's reference. At most `max_per_hour`
+/// backlinks are posted per repository.
+#[derive(Clone, Debug, Deserialize)]
+pub struct BacklinksConfig {
+    pub max_per_hour: usize,
+    pub message: String,
+    pub similarity_threshold: f64,
+}
+
+/// Replays the GitHub issues and comments whose webhooks were missed, e.g. while the bot was down
+///
+/// Runs on startup and on demand, listing what was updated since the last webhook received from
+/// each repository, minus `overlap_secs` for deliveries in flight, and at most `max_lookback_hours`
+/// ago.
+#[derive(Clone, Debug, Deserialize)]
+pub struct CatchUpConfig {
+    pub enabled: bool,
+    pub max_lookback_hours: i64,
+    pub overlap_secs: i64,
+}
+
+impl Default for CatchUpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_lookback_hours: 72,
+            overlap_secs: 300,
+        }
+    }
+}
+
+/// Newly opened pull requests get a neutral check run named `name` listing their related issues
+/// instead of a comment, which spares everyone watching the repository a notification
+///
+/// Requires `github_api.app`, installed on the repositories with the `checks:write` permission.
+#[derive(Clone, Debug, Deserialize)]
+pub struct CheckRunsConfig {
+    pub enabled: bool,
+    pub name: String,
+}
+
+impl Default for CheckRunsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            name: "Related issues".to_owned(),
+        }
+    }
+}
+
+/// Appends snippets of the files referenced in new issues (e.g. `modeling_llama.py line 321`) to
+/// the text their embedding is computed from
+///
+/// At most `max_files` files are fetched per issue, `context_lines` lines being kept on each side
+/// of the referenced line.
+#[derive(Clone, Debug, Deserialize)]
+pub struct CodeContextConfig {
+    pub context_lines: usize,
+    pub enabled: bool,
+    pub max_files: usize,
+}
+
+/// Alerts when at least `min_issues` new issues within `window_minutes` were matched with the
+/// same issue, with a similarity of at least `min_similarity`, hinting at a regression in a fresh
+/// release
+///
+/// Alerts go to `channel`, or the notifications channel when empty, starting with `mention`
+/// (e.g. `<!here>`). A given issue is escalated at most once per window.
+#[derive(Clone, Debug, Deserialize)]
+pub struct EscalationConfig {
+    #[serde(default)]
+    pub channel: String,
+    pub enabled: bool,
+    pub mention: String,
+    pub min_issues: usize,
+    pub min_similarity: f64,
+    pub window_minutes: i32,
+}
+
+/// Entries of the event log are deleted once older than `retention_days`, see
+/// [crate::event_log]
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct EventLogConfig {
+    pub retention_days: i32,
+}
+
+impl Default for EventLogConfig {
+    fn default() -> Self {
+        Self { retention_days: 30 }
+    }
+}
+
+impl Default for EscalationConfig {
+    fn default() -> Self {
+        Self {
+            channel: String::new(),
+            enabled: false,
+            mention: "<!here>".to_owned(),
+            min_issues: 3,
+            min_similarity: 0.85,
+            window_minutes: 1440,
+        }
+    }
+}
+
+/// Extracts the package, Python and PyTorch versions, OS and hardware from the system info
+/// section of issues, see [crate::extraction]
+///
+/// With `llm_fallback`, new issues the regexes get nothing from are sent to the summarization API
+/// with `llm_prompt`, which must ask for a JSON object with the fields of
+/// [crate::extraction::SystemInfo].
+///
+/// Candidates reported with the same accelerator as the new issue get `accelerator_boost` added to
+/// their similarity, those on the same minor version of the package `version_boost`, so that
+/// version-specific regressions match their own cohort first. `0` disables a boost.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ExtractionConfig {
+    #[serde(default)]
+    pub accelerator_boost: f64,
+    pub enabled: bool,
+    pub llm_fallback: bool,
+    pub llm_prompt: String,
+    #[serde(default)]
+    pub version_boost: f64,
+}
+
+impl Default for ExtractionConfig {
+    fn default() -> Self {
+        Self {
+            accelerator_boost: 0.,
+            enabled: true,
+            llm_fallback: false,
+            llm_prompt: "Extract the environment the user reports from this GitHub issue. Answer with a single JSON object with the string or null fields package_version, python_version, torch_version, os (linux, windows or macos), accelerator (cuda, rocm, mps, xpu or cpu) and gpu, and nothing else.".to_owned(),
+            version_boost: 0.,
+        }
+    }
+}
+
+/// New issues whose last traceback matches the one of an existing issue are answered with
+/// `message` and that issue alone, skipping the similarity search
+///
+/// Tracebacks with fewer than `min_frames` frames in installed packages are too generic to be
+/// told apart and are ignored.
+#[derive(Clone, Debug, Deserialize)]
+pub struct FingerprintConfig {
+    pub enabled: bool,
+    pub message: String,
+    pub min_frames: usize,
+}
+
+impl Default for FingerprintConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            message: "Hello!\n\nThis issue has the same stack trace as an existing one, it is likely a duplicate of:\n".to_owned(),
+            min_frames: 3,
+        }
+    }
+}
+
+/// Opt-in workflow closing high-confidence duplicates once a maintainer approves it
+///
+/// When the closest issue's similarity is above `similarity_threshold`, `message` is posted
+/// (`{duplicate}` being replaced by the duplicate's url). The issue is closed as soon as a member
+/// of `approvers_team` (`org/team-slug`) reacts with 👍 to that comment, unless the proposal is
+/// older than `expire_after_hours`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ButlerConfig {
+    pub approvers_team: String,
+    pub enabled: bool,
+    pub expire_after_hours: i32,
+    pub message: String,
+    pub poll_interval_secs: u64,
+    pub similarity_threshold: f64,
+}
+
+/// When enabled, comments are posted to the Slack channel as drafts first, and only queued once
+/// approved there, drafts being discarded after `expire_after_hours`
+///
+/// Slack's interactivity request url must point to `/slack/interactions`, see
+/// [SlackConfig::signing_secret].
+#[derive(Clone, Debug, Deserialize)]
+pub struct CommentApprovalConfig {
+    pub enabled: bool,
+    pub expire_after_hours: i32,
+}
+
+impl Default for CommentApprovalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            expire_after_hours: 24,
+        }
+    }
+}
+
+/// Outbound comments are posted at most every `min_interval_secs` per repository, and no more
+/// than `max_concurrency` at once
+///
+/// A repository is paused for `rate_limit_pause_secs` when its comments get rate limited. A
+/// comment failing to post is retried with an exponential backoff, or once `Retry-After` passed,
+/// and dropped after `max_attempts` failed attempts.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct CommentQueueConfig {
+    pub approval: CommentApprovalConfig,
+    pub max_attempts: i32,
+    pub max_concurrency: usize,
+    pub min_interval_secs: u64,
+    pub poll_interval_secs: u64,
+    pub rate_limit_pause_secs: u64,
+    /// GitHub issues get a single answer from the bot, edited with the latest suggestions rather
+    /// than commented on again, its previous bodies being kept in `bot_comment_revisions`
+    pub update_in_place: bool,
+}
+
+impl Default for CommentQueueConfig {
+    fn default() -> Self {
+        Self {
+            approval: CommentApprovalConfig::default(),
+            max_attempts: 10,
+            max_concurrency: 4,
+            min_interval_secs: 30,
+            poll_interval_secs: 10,
+            rate_limit_pause_secs: 600,
+            update_in_place: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailMode {
+    /// all new issues of the day in a single email
+    #[default]
+    Daily,
+    /// one email per new issue
+    Immediate,
+}
+
+/// New issues and their closest matches are emailed to `recipients` when `enabled`, daily digests
+/// being sent at `digest_hour` UTC
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct EmailConfig {
+    #[serde(default)]
+    pub digest_hour: u32,
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub from: String,
+    #[serde(default)]
+    pub mode: EmailMode,
+    #[serde(default)]
+    pub recipients: Vec<String>,
+    #[serde(default)]
+    pub smtp_host: String,
+    #[serde(default)]
+    pub smtp_password: String,
+    #[serde(default = "default_smtp_port")]
+    pub smtp_port: u16,
+    #[serde(default)]
+    pub smtp_username: String,
+}
+
+fn default_smtp_port() -> u16 {
+    465
+}
+
+fn default_hub_base_url() -> String {
+    "https://huggingface.co".to_owned()
+}
+
+/// `base_url` is the bot's public url, used to build the links rating suggestions, they are left
+/// out of comments when it isn't set
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct FeedbackConfig {
+    #[serde(default)]
+    pub base_url: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct DatabaseConfig {
     pub connection_string: String,
     pub max_connections: u32,
+    #[serde(default)]
+    pub read_replica: Option<ReadReplicaConfig>,
+    #[serde(default)]
+    pub vector_search: VectorSearchConfig,
+}
+
+/// Postgres read replica similarity searches are sent to, everything else staying on the primary
+///
+/// Searches go to the primary while the replica hasn't replayed this instance's last write of
+/// issues yet, or can't be reached. A search waits at most `acquire_timeout_ms` for a replica
+/// connection before falling back to the primary, the replica being left alone for a while.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ReadReplicaConfig {
+    #[serde(default = "default_replica_acquire_timeout_ms")]
+    pub acquire_timeout_ms: u64,
+    pub connection_string: String,
+    pub max_connections: u32,
+}
+
+fn default_replica_acquire_timeout_ms() -> u64 {
+    500
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Quantization {
+    /// half precision embeddings, as stored
+    #[default]
+    Halfvec,
+    /// binary quantized embeddings, 16 times smaller than halfvec ones
+    Binary,
+}
+
+/// pgvector 0.8 `hnsw.iterative_scan` mode, scanning more of the index when filters leave too
+/// few results
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IterativeScan {
+    #[default]
+    Off,
+    StrictOrder,
+    /// faster, results are sorted again after the scan
+    RelaxedOrder,
+}
+
+impl IterativeScan {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::StrictOrder => "strict_order",
+            Self::RelaxedOrder => "relaxed_order",
+        }
+    }
+}
+
+/// How Postgres searches for similar issues, SQLite always compares full precision embeddings
+///
+/// With `binary` quantization, the `max_suggestions * rescore_factor` closest issues by hamming
+/// distance are rescored on their halfvec embeddings, `1` skips fetching extra candidates. They
+/// are only filtered when rescored unless `iterative_scan` is set, raise `rescore_factor` for
+/// searches scoped to a few repositories. It needs migrations/binary_quantization.sql.
+///
+/// Searches scoped to a few repositories can come back short when the HNSW index is scanned
+/// before filtering: `iterative_scan` keeps scanning until enough issues match, and
+/// `partial_indexes` repositories get their own index, built in the background after starting
+/// and rebuilt when a previous build left it invalid.
+///
+/// `two_stage` searches replace both: see [TwoStageSearchConfig].
+#[derive(Clone, Debug, Deserialize)]
+pub struct VectorSearchConfig {
+    /// `hnsw.ef_search`, pgvector's default of 40 when unset
+    #[serde(default)]
+    pub ef_search: Option<u32>,
+    #[serde(default)]
+    pub iterative_scan: IterativeScan,
+    /// `hnsw.max_scan_tuples`, bounding iterative scans, pgvector's default when unset
+    #[serde(default)]
+    pub max_scan_tuples: Option<u32>,
+    #[serde(default)]
+    pub partial_indexes: Vec<String>,
+    pub quantization: Quantization,
+    pub rescore_factor: i64,
+    #[serde(default)]
+    pub two_stage: Option<TwoStageSearchConfig>,
+}
+
+/// Searches fetching `prefetch` candidates from the HNSW index, rescored on their exact cosine
+/// similarity to keep the closest ones
+///
+/// The recall of the index scan is how many of the exact closest issues are among the candidates,
+/// raising `prefetch` raises it at the cost of rescoring more embeddings.
+#[derive(Clone, Debug, Deserialize)]
+pub struct TwoStageSearchConfig {
+    /// label and repository filters are only applied when rescoring, leaving the index scan
+    /// unfiltered, for Postgres instances without iterative scans
+    #[serde(default)]
+    pub filter_on_rescore: bool,
+    pub prefetch: i64,
+    /// one in `recall_sample_every` searches is also run exhaustively in the background to
+    /// measure recall, none when unset
+    #[serde(default)]
+    pub recall_sample_every: Option<u64>,
+}
+
+impl Default for VectorSearchConfig {
+    fn default() -> Self {
+        Self {
+            ef_search: None,
+            iterative_scan: IterativeScan::Off,
+            max_scan_tuples: None,
+            partial_indexes: Vec::new(),
+            quantization: Quantization::Halfvec,
+            rescore_factor: 4,
+            two_stage: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct PushGatewayConfig {
+    pub interval_secs: u64,
+    pub job: String,
+    #[serde(default)]
+    pub password: Option<String>,
+    pub url: String,
+    #[serde(default)]
+    pub username: Option<String>,
+}
+
+/// When `auth_token` is set, `/metrics` requires an `Authorization: Bearer <auth_token>` header
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct MetricsConfig {
+    #[serde(default)]
+    pub auth_token: Option<String>,
+    #[serde(default)]
+    pub push_gateway: Option<PushGatewayConfig>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ServerConfig {
     pub ip: String,
+    /// larger request bodies are rejected with a 413
+    pub max_body_bytes: usize,
     pub metrics_port: u16,
     pub port: u16,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 pub struct GithubApiConfig {
+    /// GitHub App used for the APIs personal access tokens can't call, e.g. check runs
+    #[serde(default)]
+    pub app: Option<GithubAppConfig>,
     pub auth_token: String,
+    pub base_url: String,
     pub comments_enabled: bool,
+    #[serde(default)]
+    pub response_cache: ResponseCacheConfig,
+    /// url repositories' webhooks must send events to, i.e. the bot's public url followed by
+    /// `/event/github`, see [crate::webhooks::WebhookChecker]
+    #[serde(default)]
+    pub webhook_url: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+/// GitHub GET responses carrying an ETag are stored for `max_age_days`, requested again
+/// conditionally and replayed when unchanged, see [crate::response_cache]
+#[derive(Clone, Debug, Deserialize)]
+pub struct ResponseCacheConfig {
+    pub enabled: bool,
+    pub max_age_days: i32,
+}
+
+impl Default for ResponseCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_age_days: 7,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct GithubAppConfig {
+    pub app_id: u64,
+    /// PEM encoded RSA private key of the app
+    pub private_key: String,
+}
+
+/// GitHub Actions OIDC tokens accepted by `/index` and `/regenerate-embeddings` instead of an API
+/// key, sent as `Authorization: Bearer <token>`, see [crate::github_oidc]
+///
+/// Workflows need the `id-token: write` permission to request one for `audience`. A workflow may
+/// only index its own repository, which must be one of `repositories`, and only the workflows of
+/// `regeneration_repositories` may regenerate every embedding. Repositories are matched on their
+/// ids as well as their name, a recreated repository or a renamed owner's not being trusted.
+#[derive(Clone, Debug, Deserialize)]
+pub struct GithubOidcConfig {
+    pub audience: String,
+    pub enabled: bool,
+    pub issuer: String,
+    /// signing keys are fetched again after this long, or when a token is signed with an unknown one
+    pub jwks_cache_secs: u64,
+    pub jwks_url: String,
+    #[serde(default)]
+    pub regeneration_repositories: Vec<OidcRepository>,
+    #[serde(default)]
+    pub repositories: Vec<OidcRepository>,
+}
+
+/// Repository whose workflows are trusted, ids as listed by `GET /repos/{full_name}`
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct OidcRepository {
+    pub full_name: String,
+    pub id: u64,
+    pub owner_id: u64,
+}
+
+impl Default for GithubOidcConfig {
+    fn default() -> Self {
+        Self {
+            audience: "lor-e".to_owned(),
+            enabled: false,
+            issuer: "https://token.actions.githubusercontent.com".to_owned(),
+            jwks_cache_secs: 3600,
+            jwks_url: "https://token.actions.githubusercontent.com/.well-known/jwks".to_owned(),
+            regeneration_repositories: Vec::new(),
+            repositories: Vec::new(),
+        }
+    }
+}
+
+/// README, CONTRIBUTING and issue templates of the indexed GitHub repositories are split into
+/// sections, re-indexed every `refresh_interval_secs`
+///
+/// New issues that look like support questions rather than bug reports get `message` and a link
+/// to the closest section appended to the bot's comment, when its similarity reaches
+/// `min_similarity`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct GuidanceConfig {
+    pub enabled: bool,
+    pub message: String,
+    pub min_similarity: f64,
+    pub refresh_interval_secs: u64,
+}
+
+impl Default for GuidanceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            message: "This looks like a usage question, this section of the repository's documentation might help:".to_owned(),
+            min_similarity: 0.8,
+            refresh_interval_secs: 86400,
+        }
+    }
+}
+
+/// Keeps the title, labels and embedding of the `capacity` issues most frequently matched in
+/// memory, for at most `ttl_secs` so updates made by other instances are eventually picked up
+///
+/// The similarity search itself still runs on the database, in a single query leaving out the
+/// content of the cached issues.
+#[derive(Clone, Debug, Deserialize)]
+pub struct HotIssuesConfig {
+    pub capacity: usize,
+    pub enabled: bool,
+    pub ttl_secs: u64,
+}
+
+impl Default for HotIssuesConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 1_000,
+            enabled: false,
+            ttl_secs: 600,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
 pub struct HuggingfaceApiConfig {
     pub auth_token: String,
+    /// the hub's url, which the discussion urls of webhooks are rebased onto, e.g. a mock's
+    #[serde(default = "default_hub_base_url")]
+    pub base_url: String,
     pub comments_enabled: bool,
+    /// fetches new and edited discussions from the Hub before handling them, their webhooks only
+    /// carrying the opening comment, see [crate::huggingface::HubDiscussion]
+    #[serde(default)]
+    pub hydrate_discussions: bool,
+    /// retries of transient failures and rate limited requests
+    pub max_retries: u32,
+    /// deprecated alias of `timeouts.huggingface_secs`, which it overrides when set
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
 }
 
 /// bot's comment message
@@ -62,27 +755,967 @@ pub struct MessageConfig {
     pub post: String,
 }
 
+/// Set of rules used to drop incoming events before they are enqueued
+///
+/// `repositories` and `titles` are regexes, `authors` and `labels` are matched exactly
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct IgnoreRuleSet {
+    #[serde(default)]
+    pub authors: Vec<String>,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    #[serde(default)]
+    pub repositories: Vec<String>,
+    #[serde(default)]
+    pub titles: Vec<String>,
+}
+
+/// `global` rules apply to every repository, rules under `repositories` are keyed by repository
+/// full name and extend the global ones for that repository only
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct IgnoreRulesConfig {
+    #[serde(default)]
+    pub global: IgnoreRuleSet,
+    #[serde(default)]
+    pub repositories: HashMap<String, IgnoreRuleSet>,
+}
+
+/// Upstream an outbound HTTP client talks to, see [HttpClientConfig::proxy_overrides]
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum HttpTarget {
+    Embeddings,
+    Github,
+    Huggingface,
+    Slack,
+    Summarization,
+    WebhookMirror,
+}
+
+/// Proxy outbound requests go through, `no_proxy` lists hosts, domains or CIDR ranges reached
+/// directly, in the `NO_PROXY` environment variable format
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct ProxyConfig {
+    /// sent directly when unset
+    pub url: Option<String>,
+    #[serde(default)]
+    pub no_proxy: Vec<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+/// Connection pool and proxy settings shared by the outbound HTTP clients
+///
+/// `http2_prior_knowledge` skips the HTTP/1.1 upgrade, only enable it when every upstream
+/// (GitHub, Hugging Face, Slack, the embedding and summarization endpoints) supports HTTP/2.
+///
+/// Without a `proxy` url, the `HTTP_PROXY`, `HTTPS_PROXY` and `NO_PROXY` environment variables
+/// apply, unless overridden for the target in `proxy_overrides`. Proxy urls may be `socks5://`, or
+/// `socks5h://` to resolve hostnames through the proxy.
+#[derive(Clone, Debug, Deserialize)]
+pub struct HttpClientConfig {
+    pub http2_prior_knowledge: bool,
+    pub pool_idle_timeout_secs: u64,
+    pub pool_max_idle_per_host: usize,
+    #[serde(default)]
+    pub proxy: ProxyConfig,
+    /// replace `proxy` for the given targets, an override without url sends requests directly
+    #[serde(default)]
+    pub proxy_overrides: HashMap<HttpTarget, ProxyConfig>,
+    pub tcp_keepalive_secs: Option<u64>,
+}
+
+/// Timeouts, in seconds, of the requests the server handles and of those sent to each upstream,
+/// which have none when unset
+///
+/// `database_statement_secs` is Postgres' `statement_timeout`, set on every connection.
+#[derive(Clone, Debug, Deserialize)]
+pub struct TimeoutsConfig {
+    #[serde(default)]
+    pub database_statement_secs: Option<u64>,
+    #[serde(default)]
+    pub embeddings_secs: Option<u64>,
+    #[serde(default)]
+    pub github_secs: Option<u64>,
+    #[serde(default)]
+    pub huggingface_secs: Option<u64>,
+    /// of every request the server handles, webhooks included
+    pub request_secs: u64,
+    #[serde(default)]
+    pub slack_secs: Option<u64>,
+    #[serde(default)]
+    pub summarization_secs: Option<u64>,
+    #[serde(default)]
+    pub webhook_mirror_secs: Option<u64>,
+}
+
+impl TimeoutsConfig {
+    pub fn http(&self, target: HttpTarget) -> Option<Duration> {
+        let secs = match target {
+            HttpTarget::Embeddings => self.embeddings_secs,
+            HttpTarget::Github => self.github_secs,
+            HttpTarget::Huggingface => self.huggingface_secs,
+            HttpTarget::Slack => self.slack_secs,
+            HttpTarget::Summarization => self.summarization_secs,
+            HttpTarget::WebhookMirror => self.webhook_mirror_secs,
+        };
+        secs.map(Duration::from_secs)
+    }
+}
+
+impl Default for TimeoutsConfig {
+    fn default() -> Self {
+        Self {
+            database_statement_secs: None,
+            embeddings_secs: Some(30),
+            github_secs: None,
+            huggingface_secs: Some(30),
+            request_secs: 10,
+            slack_secs: None,
+            summarization_secs: None,
+            webhook_mirror_secs: Some(10),
+        }
+    }
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        Self {
+            http2_prior_knowledge: false,
+            pool_idle_timeout_secs: 90,
+            pool_max_idle_per_host: 32,
+            proxy: ProxyConfig::default(),
+            proxy_overrides: HashMap::new(),
+            tcp_keepalive_secs: Some(60),
+        }
+    }
+}
+
+/// Comments containing `keyword` get a reply listing the issues closest to the rest of the
+/// comment, in repositories with `comment_trigger` set
+///
+/// Each commenter triggers at most `max_triggers_per_hour` replies, the comments of unknown
+/// authors sharing that limit.
+#[derive(Clone, Debug, Deserialize)]
+pub struct CommentTriggerConfig {
+    pub keyword: String,
+    #[serde(default = "default_max_triggers_per_hour")]
+    pub max_triggers_per_hour: usize,
+}
+
+fn default_max_triggers_per_hour() -> usize {
+    5
+}
+
+impl Default for CommentTriggerConfig {
+    fn default() -> Self {
+        Self {
+            keyword: "@lor-e similar".to_owned(),
+            max_triggers_per_hour: default_max_triggers_per_hour(),
+        }
+    }
+}
+
+/// New issues carrying one of `security_labels` (case insensitive), or whose title or body
+/// matches one of the `security_patterns` regexes, are security reports, `critical_labels` and
+/// `critical_patterns` marking critical issues the same way
+///
+/// Both are announced in `channel`, or the notifications channel when empty, starting with
+/// `mention` as soon as they are received, without waiting for the similarity search and
+/// summarization. Security reports get no public comment, to avoid drawing attention to them.
+#[derive(Clone, Debug, Deserialize)]
+pub struct PriorityConfig {
+    #[serde(default)]
+    pub channel: String,
+    pub critical_labels: Vec<String>,
+    pub critical_patterns: Vec<String>,
+    pub enabled: bool,
+    pub mention: String,
+    pub security_labels: Vec<String>,
+    pub security_patterns: Vec<String>,
+}
+
+impl Default for PriorityConfig {
+    fn default() -> Self {
+        Self {
+            channel: String::new(),
+            critical_labels: vec!["critical".to_owned()],
+            critical_patterns: Vec::new(),
+            enabled: false,
+            mention: "<!here>".to_owned(),
+            security_labels: vec!["security".to_owned()],
+            security_patterns: Vec::new(),
+        }
+    }
+}
+
+/// Pattern whose matches are replaced by `[REDACTED:<name>]`
+#[derive(Clone, Debug, Deserialize)]
+pub struct RedactionPattern {
+    pub name: String,
+    pub regex: String,
+}
+
+/// Scrubs secrets and personal data out of issues and comments before they are embedded,
+/// summarized or stored, see [crate::redaction]
+#[derive(Clone, Debug, Deserialize)]
+pub struct RedactionConfig {
+    /// tokens, keys and email addresses, see [crate::redaction::BUILTIN_PATTERNS]
+    pub builtin_patterns: bool,
+    /// off by default, redacted text is also what gets embedded and summarized
+    pub enabled: bool,
+    /// applied after the built-in ones, e.g. internal hostnames
+    #[serde(default)]
+    pub patterns: Vec<RedactionPattern>,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            builtin_patterns: true,
+            enabled: false,
+            patterns: Vec::new(),
+        }
+    }
+}
+
+/// `POST /onboard` estimates a repository's backfill to take `seconds_per_issue` per issue or pull
+/// request, see [crate::onboarding]
+///
+/// Only the repositories of the users or organizations of `allowed_owners` may be onboarded, none
+/// when it is empty.
+#[derive(Clone, Debug, Deserialize)]
+pub struct OnboardingConfig {
+    #[serde(default)]
+    pub allowed_owners: Vec<String>,
+    pub seconds_per_issue: f64,
+}
+
+impl OnboardingConfig {
+    pub fn is_allowed(&self, repository_full_name: &str) -> bool {
+        repository_full_name
+            .split_once('/')
+            .is_some_and(|(owner, _)| {
+                self.allowed_owners
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(owner))
+            })
+    }
+}
+
+impl Default for OnboardingConfig {
+    fn default() -> Self {
+        Self {
+            allowed_owners: Vec::new(),
+            seconds_per_issue: 0.5,
+        }
+    }
+}
+
+/// Slack user groups mentioned in new issue notifications, owning the files referenced in the
+/// issue according to the repository's CODEOWNERS, or its labels
+///
+/// At most `max_files` references are looked up, CODEOWNERS files being cached for `cache_secs`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct OwnersConfig {
+    pub cache_secs: u64,
+    /// CODEOWNERS owner, e.g. `@huggingface/tokenizers`, to Slack user group id
+    #[serde(default)]
+    pub codeowners_teams: HashMap<String, String>,
+    pub max_files: usize,
+}
+
+impl Default for OwnersConfig {
+    fn default() -> Self {
+        Self {
+            cache_secs: 3_600,
+            codeowners_teams: HashMap::new(),
+            max_files: 3,
+        }
+    }
+}
+
+/// Per repository settings, keyed by repository full name in [IssueBotConfig]
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct RepositoryConfig {
+    /// opts in to comments linking older issues to new similar ones, see [BacklinksConfig]
+    #[serde(default)]
+    pub backlinks: bool,
+    /// opts in to mentioning the CODEOWNERS teams in Slack, see [OwnersConfig]
+    #[serde(default)]
+    pub codeowners: bool,
+    /// opts in to replying to comments asking for similar issues, see [CommentTriggerConfig]
+    #[serde(default)]
+    pub comment_trigger: bool,
+    /// issues carrying any of these labels are never suggested as similar
+    #[serde(default)]
+    pub excluded_labels: Vec<String>,
+    /// opts in to answering new issues from the knowledge base, see [KnowledgeBaseConfig]
+    #[serde(default)]
+    pub first_responder: bool,
+    /// label to the Slack user group id mentioned when an issue carries it
+    #[serde(default)]
+    pub label_teams: HashMap<String, String>,
+    /// profiles of `summarization_api.prompts` the repository's issues are summarized with,
+    /// issues being split between them when there are several to A/B test them
+    #[serde(default)]
+    pub summarization_prompts: Vec<String>,
+}
+
+/// What a [RetentionRule] does to the issues it concerns
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RetentionAction {
+    /// flags issues so that they are left out of similarity searches
+    Exclude,
+    /// moves issues to the archive, see [ArchiveConfig]
+    Archive,
+}
+
+/// Whether an issue is open or was closed upstream
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueState {
+    Open,
+    Closed,
+}
+
+/// Issues of `repository`, or of all repositories when unset, not updated for `max_age_days`
+///
+/// When `labels` isn't empty, only issues with at least one of them are concerned, when `state` is
+/// set, only issues in that state.
+#[derive(Clone, Debug, Deserialize)]
+pub struct RetentionRule {
+    pub action: RetentionAction,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    pub max_age_days: i64,
+    #[serde(default)]
+    pub repository: Option<String>,
+    #[serde(default)]
+    pub state: Option<IssueState>,
+}
+
+/// Every `interval_secs`, exports the backlog of the comment queue, of the Slack outbox and of the
+/// resumable jobs, along with the events handled in the last `window_minutes`, see
+/// [crate::queue_metrics]
+#[derive(Clone, Debug, Deserialize)]
+pub struct QueueMetricsConfig {
+    pub enabled: bool,
+    pub interval_secs: u64,
+    pub window_minutes: i32,
+}
+
+impl Default for QueueMetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: 30,
+            window_minutes: 15,
+        }
+    }
+}
+
+/// The webhook worker is restarted when it panics or has been handling the same event for
+/// `stall_threshold_secs`, readiness reporting unavailable meanwhile, see [crate::supervisor]
+#[derive(Clone, Debug, Deserialize)]
+pub struct SupervisorConfig {
+    pub check_interval_secs: u64,
+    pub restart_backoff_secs: u64,
+    pub stall_threshold_secs: u64,
+}
+
+impl Default for SupervisorConfig {
+    fn default() -> Self {
+        Self {
+            check_interval_secs: 10,
+            restart_backoff_secs: 5,
+            stall_threshold_secs: 600,
+        }
+    }
+}
+
+/// Every `check_interval_secs` between `window_start_hour` and `window_end_hour` (UTC), checks the
+/// bloat of the issues table, see [crate::compaction]
+///
+/// The table is vacuumed once more than `max_dead_tuple_ratio` of its rows are dead, and its
+/// vector indexes rebuilt once rows were updated or deleted more than `max_index_churn_ratio` times
+/// their number since the last rebuild, until the window closes. When `apply` is off, the default,
+/// these are only advised in the logs.
+#[derive(Clone, Debug, Deserialize)]
+pub struct CompactionConfig {
+    pub apply: bool,
+    pub check_interval_secs: u64,
+    pub enabled: bool,
+    pub max_dead_tuple_ratio: f64,
+    pub max_index_churn_ratio: f64,
+    pub window_end_hour: u32,
+    pub window_start_hour: u32,
+}
+
+impl Default for CompactionConfig {
+    fn default() -> Self {
+        Self {
+            apply: false,
+            check_interval_secs: 3600,
+            enabled: true,
+            max_dead_tuple_ratio: 0.2,
+            max_index_churn_ratio: 0.5,
+            window_end_hour: 5,
+            window_start_hour: 2,
+        }
+    }
+}
+
+/// Retries allowed against each host, shared by every retry loop, see [crate::retry::RetryBudget]
+///
+/// Hosts start with `max_tokens` retries, refilled by `refill_per_sec` and by `success_deposit` on
+/// every successful call, up to `max_tokens`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct RetryBudgetConfig {
+    pub enabled: bool,
+    pub max_tokens: f64,
+    pub refill_per_sec: f64,
+    pub success_deposit: f64,
+}
+
+impl Default for RetryBudgetConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_tokens: 20.,
+            refill_per_sec: 0.1,
+            success_deposit: 0.1,
+        }
+    }
+}
+
+/// Rules applied to stored issues every `interval_secs` when `enabled`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    pub enabled: bool,
+    pub interval_secs: u64,
+    pub rules: Vec<RetentionRule>,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: 86400,
+            rules: Vec::new(),
+        }
+    }
+}
+
+/// Candidates linked to one another in their GitHub timeline get `boost` added to their similarity,
+/// `ingest_on_index` fetches the timeline of every issue during repository indexation
+#[derive(Clone, Debug, Deserialize)]
+pub struct IssueLinksConfig {
+    pub boost: f64,
+    pub ingest_on_index: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum IssueTextStrategy {
+    /// title, body and the last comment, standing in for the accepted answer GitHub issues lack
+    AcceptedAnswer,
+    /// title, body and every comment
+    #[default]
+    AllComments,
+    /// title, body and the first `count` comments
+    FirstComments { count: usize },
+    /// title and body only
+    TitleBody,
+    /// title repeated `repeats` times to weigh more than the body, no comments
+    WeightedTitle { repeats: usize },
+}
+
+/// How the text issues are embedded from is assembled
+///
+/// `embedding_template`, when set, wraps the composed text with metadata before embedding it,
+/// replacing `{repository}`, `{labels}`, `{component}` and `{text}`; `{component}` is the
+/// directory of the first file the issue mentions, empty when there is none. Search queries are
+/// wrapped too, and issues re-embedded when their labels change if the template shows them. A
+/// short hash of the template is recorded with every embedding, telling those of an older template
+/// apart.
+///
+/// The bug description of issues created from issue forms is embedded `description_repeats`
+/// times to weigh more than the other sections, see [crate::issue_forms::IssueForm].
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct IssueTextConfig {
+    #[serde(default)]
+    pub description_repeats: usize,
+    #[serde(default)]
+    pub embedding_template: Option<String>,
+    #[serde(default)]
+    pub strategy: IssueTextStrategy,
+}
+
+/// Curated answers are posted instead of the suggestions when a new issue's similarity to an
+/// entry's question reaches `similarity_threshold`, in repositories with `first_responder` set
+///
+/// They are introduced by `message` and followed by `message_config.post` and the footer.
+#[derive(Clone, Debug, Deserialize)]
+pub struct KnowledgeBaseConfig {
+    pub message: String,
+    pub similarity_threshold: f64,
+}
+
+/// Metadata of the indexed repositories is refreshed every `refresh_interval_secs`
+///
+/// Candidates from repositories sharing a topic with the new issue's get `topic_boost` added to
+/// their similarity, `0` disabling the boost.
+#[derive(Clone, Debug, Deserialize)]
+pub struct RepoMetadataConfig {
+    pub refresh_interval_secs: u64,
+    pub topic_boost: f64,
+}
+
+/// Issues are re-embedded at most every `min_interval_secs` when edited or commented on, pending
+/// re-embeddings being checked every `flush_interval_secs`
+#[derive(Clone, Debug, Deserialize)]
+pub struct ReembedConfig {
+    pub flush_interval_secs: u64,
+    pub min_interval_secs: u64,
+}
+
+/// When `enabled`, `candidate_pool_factor` times more issues than suggested are fetched and the
+/// suggestions picked among them by maximal marginal relevance
+///
+/// `lambda` trades relevance (`1`) for diversity (`0`).
+#[derive(Clone, Debug, Deserialize)]
+pub struct DiversityConfig {
+    pub candidate_pool_factor: i64,
+    pub enabled: bool,
+    pub lambda: f64,
+}
+
+/// Similarities of up to `sample_size` linked issues, sampled under `baseline_model` before
+/// embeddings are regenerated and under the current model after, to spot drifts in what the
+/// new model considers similar
+///
+/// No baseline is sampled when `baseline_model` is empty.
+#[derive(Clone, Debug, Deserialize)]
+pub struct DriftConfig {
+    pub baseline_model: String,
+    pub sample_size: i64,
+}
+
+impl Default for DriftConfig {
+    fn default() -> Self {
+        Self {
+            baseline_model: String::new(),
+            sample_size: 500,
+        }
+    }
+}
+
+/// Defaults of the similar issues suggestions, overridable at runtime through
+/// `/settings/similarity`
+#[derive(Clone, Debug, Deserialize)]
+pub struct SimilarityConfig {
+    pub max_suggestions: i64,
+    pub min_similarity: f64,
+}
+
+/// Notifications for the same repository received within `batch_window_secs` are sent as a
+/// single digest, `0` sends them right away. Batched notifications are persisted when the
+/// outbox is enabled and kept for another window when sending the digest failed
 #[derive(Clone, Debug, Deserialize)]
 pub struct SlackConfig {
+    pub auth_test_url: String,
     pub auth_token: String,
+    #[serde(default)]
+    pub batch_window_secs: u64,
     pub channel: String,
     pub chat_write_url: String,
+    #[serde(default)]
+    pub outbox: SlackOutboxConfig,
+    /// sends notifications as plain text rather than Block Kit, for integrations that only read
+    /// the text of messages
+    #[serde(default)]
+    pub plain_text: bool,
+    /// prefixes notifications with the repository's description and stars, for channels shared
+    /// by several repositories
+    #[serde(default)]
+    pub repository_context: bool,
+    /// verifies the interaction requests of the comment approval buttons
+    #[serde(default)]
+    pub signing_secret: String,
+    /// notified along the workspace of `auth_token`, which alone gets comment drafts
+    #[serde(default)]
+    pub workspaces: Vec<SlackWorkspaceConfig>,
+}
+
+/// Closest issues notifications Slack failed to take are persisted and retried every
+/// `poll_interval_secs` with an exponential backoff, or once Slack's `Retry-After` passed, until
+/// delivered, `max_attempts` attempts failed or `ttl_minutes` passed
+#[derive(Clone, Debug, Deserialize)]
+pub struct SlackOutboxConfig {
+    pub enabled: bool,
+    pub max_attempts: i32,
+    pub poll_interval_secs: u64,
+    pub ttl_minutes: i32,
+}
+
+impl Default for SlackOutboxConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_attempts: 10,
+            poll_interval_secs: 30,
+            ttl_minutes: 1440,
+        }
+    }
+}
+
+/// Additional Slack workspace, notified of a repository's issues in its
+/// `repository_channels` entry or else in `channel`, left out when both are unset
+#[derive(Clone, Debug, Deserialize)]
+pub struct SlackWorkspaceConfig {
+    pub auth_token: String,
+    #[serde(default)]
+    pub channel: String,
+    pub name: String,
+    #[serde(default)]
+    pub repository_channels: HashMap<String, String>,
+}
+
+/// Searches rank the `max_candidates` issues closest to the query, served in pages of at most
+/// `max_page_size` issues. The ranked candidates are kept for `cache_secs` so that the following
+/// pages are served without embedding the query again.
+#[derive(Clone, Debug, Deserialize)]
+pub struct SearchConfig {
+    #[serde(default = "default_search_cache_secs")]
+    pub cache_secs: u64,
+    pub max_candidates: i64,
+    pub max_page_size: usize,
+}
+
+fn default_search_cache_secs() -> u64 {
+    300
+}
+
+/// Read-only HTML pages under `/ui`, gated by the admin token, listing the suggestions posted on
+/// the last `recent_suggestions` issues
+#[derive(Clone, Debug, Deserialize)]
+pub struct WebUiConfig {
+    pub enabled: bool,
+    pub recent_suggestions: usize,
+}
+
+impl Default for WebUiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            recent_suggestions: 100,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct IssueBotConfig {
+    #[serde(default)]
+    pub archive: ArchiveConfig,
     pub auth_token: String,
+    pub backlinks: BacklinksConfig,
+    pub butler: ButlerConfig,
+    #[serde(default)]
+    pub catch_up: CatchUpConfig,
+    #[serde(default)]
+    pub check_runs: CheckRunsConfig,
+    pub code_context: CodeContextConfig,
+    #[serde(default)]
+    pub comment_queue: CommentQueueConfig,
+    #[serde(default)]
+    pub comment_trigger: CommentTriggerConfig,
+    #[serde(default)]
+    pub compaction: CompactionConfig,
     pub database: DatabaseConfig,
+    pub diversity: DiversityConfig,
+    #[serde(default)]
+    pub drift: DriftConfig,
+    #[serde(default)]
+    pub email: EmailConfig,
     pub embedding_api: EmbeddingApiConfig,
+    #[serde(default)]
+    pub escalation: EscalationConfig,
+    #[serde(default)]
+    pub event_log: EventLogConfig,
+    #[serde(default)]
+    pub feedback: FeedbackConfig,
+    #[serde(default)]
+    pub extraction: ExtractionConfig,
+    #[serde(default)]
+    pub fingerprints: FingerprintConfig,
     pub github_api: GithubApiConfig,
+    #[serde(default)]
+    pub github_oidc: GithubOidcConfig,
+    #[serde(default)]
+    pub guidance: GuidanceConfig,
+    #[serde(default)]
+    pub hot_issues: HotIssuesConfig,
+    #[serde(default)]
+    pub http_client: HttpClientConfig,
     pub huggingface_api: HuggingfaceApiConfig,
+    #[serde(default)]
+    pub ignore_rules: IgnoreRulesConfig,
+    pub issue_links: IssueLinksConfig,
+    #[serde(default)]
+    pub issue_text: IssueTextConfig,
+    pub knowledge_base: KnowledgeBaseConfig,
     pub message_config: MessageConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    #[serde(default)]
+    pub onboarding: OnboardingConfig,
+    #[serde(default)]
+    pub owners: OwnersConfig,
+    #[serde(default)]
+    pub priority: PriorityConfig,
+    #[serde(default)]
+    pub queue_metrics: QueueMetricsConfig,
+    #[serde(default)]
+    pub redaction: RedactionConfig,
+    pub reembed: ReembedConfig,
+    /// group name to member repositories, sharing their issues for similarity searches
+    #[serde(default)]
+    pub repo_groups: HashMap<String, Vec<String>>,
+    pub repo_metadata: RepoMetadataConfig,
+    #[serde(default)]
+    pub repositories: HashMap<String, RepositoryConfig>,
+    #[serde(default)]
+    pub retention: RetentionConfig,
+    #[serde(default)]
+    pub retry_budget: RetryBudgetConfig,
+    pub search: SearchConfig,
     pub server: ServerConfig,
+    pub similarity: SimilarityConfig,
+    /// skips the connectivity checks run before starting the servers
+    #[serde(default)]
+    pub skip_startup_checks: bool,
     pub slack: SlackConfig,
     pub summarization_api: SummarizationApiConfig,
+    #[serde(default)]
+    pub supervisor: SupervisorConfig,
+    #[serde(default)]
+    pub timeouts: TimeoutsConfig,
+    #[serde(default)]
+    pub web_ui: WebUiConfig,
+    #[serde(default)]
+    pub webhook_mirror: WebhookMirrorConfig,
+}
+
+/// Verified GitHub and Hugging Face webhooks forwarded to the `/github` and `/huggingface` routes
+/// under `url`, e.g. `https://staging.example.com/event`, signed with or carrying `secret`, see
+/// [crate::mirror::WebhookMirror]
+///
+/// At most `max_in_flight` are forwarded at once, the others are dropped.
+#[derive(Clone, Debug, Deserialize)]
+pub struct WebhookMirrorConfig {
+    pub enabled: bool,
+    pub max_in_flight: usize,
+    pub secret: String,
+    pub url: String,
+}
+
+impl Default for WebhookMirrorConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_in_flight: 32,
+            secret: String::new(),
+            url: String::new(),
+        }
+    }
+}
+
+/// Every problem found in the configuration, reported at once rather than one per restart
+#[derive(Debug)]
+pub struct ConfigErrors(pub Vec<String>);
+
+impl fmt::Display for ConfigErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid configuration:")?;
+        for problem in &self.0 {
+            write!(f, "\n  - {problem}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigErrors {}
+
+impl From<ConfigError> for ConfigErrors {
+    fn from(err: ConfigError) -> Self {
+        Self(vec![err.to_string()])
+    }
+}
+
+impl IssueBotConfig {
+    /// urls that are set but don't parse, the webhook mirror's only being checked when it is
+    /// enabled, empty ones being left to the startup checks
+    fn url_problems(&self) -> Vec<String> {
+        let mut urls: Vec<(String, &str)> = vec![
+            ("embedding_api.url".to_owned(), &self.embedding_api.url),
+            ("github_api.base_url".to_owned(), &self.github_api.base_url),
+            (
+                "huggingface_api.base_url".to_owned(),
+                &self.huggingface_api.base_url,
+            ),
+            (
+                "github_oidc.jwks_url".to_owned(),
+                &self.github_oidc.jwks_url,
+            ),
+            ("slack.auth_test_url".to_owned(), &self.slack.auth_test_url),
+            (
+                "slack.chat_write_url".to_owned(),
+                &self.slack.chat_write_url,
+            ),
+            (
+                "summarization_api.url".to_owned(),
+                &self.summarization_api.url,
+            ),
+        ];
+        for (i, url) in self.embedding_api.fallback_urls.iter().enumerate() {
+            urls.push((format!("embedding_api.fallback_urls[{i}]"), url));
+        }
+        for (i, url) in self.summarization_api.fallback_urls.iter().enumerate() {
+            urls.push((format!("summarization_api.fallback_urls[{i}]"), url));
+        }
+        if let Some(url) = &self.feedback.base_url {
+            urls.push(("feedback.base_url".to_owned(), url));
+        }
+        if let Some(url) = &self.github_api.webhook_url {
+            urls.push(("github_api.webhook_url".to_owned(), url));
+        }
+        if let Some(push_gateway) = &self.metrics.push_gateway {
+            urls.push(("metrics.push_gateway.url".to_owned(), &push_gateway.url));
+        }
+        if let Some(url) = &self.http_client.proxy.url {
+            urls.push(("http_client.proxy.url".to_owned(), url));
+        }
+        for (target, proxy) in &self.http_client.proxy_overrides {
+            if let Some(url) = &proxy.url {
+                urls.push((format!("http_client.proxy_overrides.{target:?}.url"), url));
+            }
+        }
+        if self.webhook_mirror.enabled {
+            urls.push(("webhook_mirror.url".to_owned(), &self.webhook_mirror.url));
+        }
+
+        let mut problems: Vec<String> = urls
+            .into_iter()
+            .filter(|(_, url)| !url.is_empty())
+            .filter_map(|(key, url)| {
+                reqwest::Url::parse(url)
+                    .err()
+                    .map(|err| format!("invalid url for key `{key}`: {err} ({url:?})"))
+            })
+            .collect();
+        problems.sort();
+        problems
+    }
+
+    /// moves the values of deprecated keys over to the keys replacing them
+    fn apply_deprecated_keys(&mut self) {
+        if let Some(secs) = self.huggingface_api.timeout_secs.take() {
+            warn!("`huggingface_api.timeout_secs` is deprecated, use `timeouts.huggingface_secs`");
+            self.timeouts.huggingface_secs = Some(secs);
+        }
+    }
+
+    /// values that deserialize but can't work, e.g. a rate limit of zero
+    fn value_problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let requests_per_sec = self.embedding_api.requests_per_sec;
+        if !(requests_per_sec.is_finite() && requests_per_sec > 0.) {
+            problems.push(format!(
+                "`embedding_api.requests_per_sec` must be greater than 0, got {requests_per_sec}"
+            ));
+        }
+        if self.embedding_api.burst == 0 {
+            problems.push("`embedding_api.burst` must be at least 1".to_owned());
+        }
+        if let Some(dimensions) = self
+            .embedding_api
+            .dimensions
+            .filter(|dimensions| *dimensions as usize != EMBEDDING_DIMENSIONS)
+        {
+            problems.push(format!(
+                "`embedding_api.dimensions` must be {EMBEDDING_DIMENSIONS}, the size of the stored embeddings, got {dimensions}"
+            ));
+        }
+        if self.email.enabled && self.email.recipients.is_empty() {
+            problems.push("`email.recipients` must not be empty when email is enabled".to_owned());
+        }
+        if self.event_log.retention_days < 1 {
+            problems.push(format!(
+                "`event_log.retention_days` must be at least 1, got {}",
+                self.event_log.retention_days
+            ));
+        }
+        if self.comment_trigger.max_triggers_per_hour == 0 {
+            problems.push("`comment_trigger.max_triggers_per_hour` must be at least 1".to_owned());
+        }
+        if self.webhook_mirror.enabled {
+            if self.webhook_mirror.url.is_empty() {
+                problems.push(
+                    "`webhook_mirror.url` must not be empty when the mirror is enabled".to_owned(),
+                );
+            }
+            if self.webhook_mirror.secret.is_empty() {
+                problems.push(
+                    "`webhook_mirror.secret` must not be empty when the mirror is enabled"
+                        .to_owned(),
+                );
+            }
+        }
+        if self.github_oidc.enabled && self.github_oidc.repositories.is_empty() {
+            problems.push(
+                "`github_oidc.repositories` must not be empty when github oidc is enabled"
+                    .to_owned(),
+            );
+        }
+        if self.email.digest_hour > 23 {
+            problems.push(format!(
+                "`email.digest_hour` must be an hour of the day, got {}",
+                self.email.digest_hour
+            ));
+        }
+        problems
+    }
+}
+
+/// deserializes `config` strictly, collecting unknown keys along the first deserialization error
+/// and, when there is none, the urls that don't parse and the values that can't work
+fn parse_config(config: Config) -> Result<IssueBotConfig, ConfigErrors> {
+    let mut unknown_keys = Vec::new();
+    let mut track_unknown_key = |path: serde_ignored::Path| {
+        unknown_keys.push(format!("unknown key `{path}`"));
+    };
+    let deserializer = serde_ignored::Deserializer::new(config, &mut track_unknown_key);
+    let result = IssueBotConfig::deserialize(deserializer);
+    let mut problems = unknown_keys;
+    match result {
+        Ok(mut config) => {
+            config.apply_deprecated_keys();
+            problems.extend(config.url_problems());
+            problems.extend(config.value_problems());
+            if problems.is_empty() {
+                return Ok(config);
+            }
+        }
+        // config's errors already name the key at fault
+        Err(err) => problems.push(err.to_string()),
+    }
+    Err(ConfigErrors(problems))
 }
 
-pub fn load_config<'de, T: Deserialize<'de>>(prefix: &str) -> Result<T, ConfigError> {
+pub fn load_config(prefix: &str) -> Result<IssueBotConfig, ConfigErrors> {
     let base_path = std::env::current_dir().expect("Failed to determine the current directory");
     let configuration_directory = base_path.join("configuration");
 
@@ -94,6 +1727,138 @@ pub fn load_config<'de, T: Deserialize<'de>>(prefix: &str) -> Result<T, ConfigEr
         .prefix(prefix)
         .prefix_separator("__");
     config_builder = config_builder.add_source(environment);
-    let config = config_builder.build()?.try_deserialize()?;
-    Ok(config)
+    parse_config(config_builder.build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use config::{Config, File, FileFormat};
+
+    use super::parse_config;
+
+    fn config_with(overrides: &str) -> Config {
+        Config::builder()
+            .add_source(File::with_name("configuration/base.yaml"))
+            .add_source(File::from_str(overrides, FileFormat::Yaml))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_base_configuration_is_valid() {
+        parse_config(config_with("{}")).unwrap();
+    }
+
+    #[test]
+    fn test_reports_every_unknown_key_and_invalid_url() {
+        let overrides = r##"
+similarty:
+  max_suggestions: 3
+slack:
+  chanel: "#bots"
+  chat_write_url: "not a url"
+"##;
+        let errors = parse_config(config_with(overrides)).unwrap_err();
+
+        assert!(errors.0.contains(&"unknown key `similarty`".to_owned()));
+        assert!(errors.0.contains(&"unknown key `slack.chanel`".to_owned()));
+        assert!(errors
+            .0
+            .iter()
+            .any(|problem| problem.starts_with("invalid url for key `slack.chat_write_url`")));
+    }
+
+    #[test]
+    fn test_rejects_email_without_recipients() {
+        let overrides = r##"
+email:
+  digest_hour: 24
+  enabled: true
+"##;
+        let errors = parse_config(config_with(overrides)).unwrap_err();
+
+        assert_eq!(
+            errors.0,
+            vec![
+                "`email.recipients` must not be empty when email is enabled".to_owned(),
+                "`email.digest_hour` must be an hour of the day, got 24".to_owned(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rejects_github_oidc_without_repositories() {
+        let overrides = r##"
+github_oidc:
+  enabled: true
+"##;
+        let errors = parse_config(config_with(overrides)).unwrap_err();
+
+        assert_eq!(
+            errors.0,
+            vec![
+                "`github_oidc.repositories` must not be empty when github oidc is enabled"
+                    .to_owned()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rejects_webhook_mirror_without_secret() {
+        let overrides = r##"
+webhook_mirror:
+  enabled: true
+  url: https://staging.example.com/event
+"##;
+        let errors = parse_config(config_with(overrides)).unwrap_err();
+
+        assert_eq!(
+            errors.0,
+            vec!["`webhook_mirror.secret` must not be empty when the mirror is enabled".to_owned()]
+        );
+    }
+
+    #[test]
+    fn test_rejects_zero_embedding_rate() {
+        let overrides = r##"
+embedding_api:
+  burst: 0
+  requests_per_sec: 0
+"##;
+        let errors = parse_config(config_with(overrides)).unwrap_err();
+
+        assert_eq!(
+            errors.0,
+            vec![
+                "`embedding_api.requests_per_sec` must be greater than 0, got 0".to_owned(),
+                "`embedding_api.burst` must be at least 1".to_owned(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rejects_embedding_dimensions_of_another_size() {
+        let errors = parse_config(config_with("embedding_api:\n  dimensions: 1024\n")).unwrap_err();
+
+        assert_eq!(
+            errors.0,
+            vec![
+                "`embedding_api.dimensions` must be 2560, the size of the stored embeddings, got 1024"
+                    .to_owned()
+            ]
+        );
+        parse_config(config_with("embedding_api:\n  dimensions: 2560\n")).unwrap();
+    }
+
+    #[test]
+    fn test_deprecated_huggingface_timeout() {
+        let overrides = r##"
+huggingface_api:
+  timeout_secs: 5
+"##;
+        let config = parse_config(config_with(overrides)).unwrap();
+
+        assert_eq!(config.timeouts.huggingface_secs, Some(5));
+        assert_eq!(config.huggingface_api.timeout_secs, None);
+    }
 }
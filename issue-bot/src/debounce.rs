@@ -0,0 +1,149 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use futures::pin_mut;
+use tokio::{select, time::interval};
+use tracing::{error, info};
+
+use crate::{
+    config::ReembedConfig, debug::DebugState, embeddings::queue::EmbeddingQueue,
+    issue_text::IssueTextComposer, shutdown_signal, storage::Database, update_issue_embedding,
+};
+
+#[derive(Default)]
+struct DebounceState {
+    /// issues changed since their last re-embedding
+    dirty: HashSet<i64>,
+    last_embedded: HashMap<i64, Instant>,
+}
+
+impl DebounceState {
+    /// dirty issues not re-embedded within the last `min_interval`, all of them when `force`
+    fn due(&mut self, now: Instant, min_interval: Duration, force: bool) -> Vec<i64> {
+        self.last_embedded
+            .retain(|_, embedded_at| now.duration_since(*embedded_at) < min_interval);
+        let due: Vec<i64> = self
+            .dirty
+            .iter()
+            .filter(|id| force || !self.last_embedded.contains_key(id))
+            .copied()
+            .collect();
+        for id in &due {
+            self.dirty.remove(id);
+            self.last_embedded.insert(*id, now);
+        }
+        due
+    }
+}
+
+/// Coalesces the re-embeddings of busy issues, an issue being re-embedded at most once every
+/// `min_interval_secs` however many times it changes
+#[derive(Clone)]
+pub struct ReembedDebouncer {
+    cfg: ReembedConfig,
+    state: Arc<Mutex<DebounceState>>,
+}
+
+impl ReembedDebouncer {
+    pub fn new(cfg: ReembedConfig) -> Self {
+        Self {
+            cfg,
+            state: Arc::default(),
+        }
+    }
+
+    /// schedules the re-embedding of the issue with source id `issue_id`
+    pub fn mark_dirty(&self, issue_id: i64) {
+        self.state.lock().unwrap().dirty.insert(issue_id);
+    }
+
+    async fn flush(
+        &self,
+        embedding_queue: &EmbeddingQueue,
+        issue_text: &IssueTextComposer,
+        db: &Database,
+        debug_state: &DebugState,
+        force: bool,
+    ) {
+        let due = self.state.lock().unwrap().due(
+            Instant::now(),
+            Duration::from_secs(self.cfg.min_interval_secs),
+            force,
+        );
+        for issue_id in due {
+            if let Err(err) =
+                update_issue_embedding(embedding_queue, issue_text, db, issue_id).await
+            {
+                debug_state.record_error("embeddings", &err);
+                error!(
+                    issue_id = issue_id,
+                    err = err.to_string(),
+                    "error updating issue embeddings"
+                );
+            }
+        }
+    }
+}
+
+/// Re-embeds dirty issues as they become due, and all of them on shutdown
+pub async fn start_reembed_flusher(
+    debouncer: ReembedDebouncer,
+    embedding_queue: EmbeddingQueue,
+    issue_text: IssueTextComposer,
+    db: Database,
+    debug_state: DebugState,
+) -> anyhow::Result<()> {
+    info!("starting re-embedding flusher");
+    let mut ticker = interval(Duration::from_secs(debouncer.cfg.flush_interval_secs));
+    let shutdown = shutdown_signal();
+    pin_mut!(shutdown);
+    loop {
+        select! {
+            _ = ticker.tick() => {
+                debouncer.flush(&embedding_queue, &issue_text, &db, &debug_state, false).await;
+            }
+            _ = &mut shutdown => {
+                debouncer.flush(&embedding_queue, &issue_text, &db, &debug_state, true).await;
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::DebounceState;
+
+    #[test]
+    fn test_due() {
+        let now = Instant::now();
+        let min_interval = Duration::from_secs(60);
+        let mut state = DebounceState::default();
+
+        state.dirty.insert(1);
+        assert_eq!(state.due(now, min_interval, false), vec![1]);
+
+        // changes within the interval are coalesced into a single re-embedding
+        state.dirty.insert(1);
+        state.dirty.insert(1);
+        assert!(state
+            .due(now + Duration::from_secs(30), min_interval, false)
+            .is_empty());
+        assert_eq!(
+            state.due(now + Duration::from_secs(60), min_interval, false),
+            vec![1]
+        );
+
+        state.dirty.insert(1);
+        assert_eq!(
+            state.due(now + Duration::from_secs(61), min_interval, true),
+            vec![1]
+        );
+    }
+}
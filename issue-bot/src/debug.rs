@@ -0,0 +1,121 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct InFlightEvent {
+    pub event: String,
+    pub started_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct IndexationProgress {
+    pub processed: usize,
+    pub total: Option<usize>,
+    pub next_url: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SubsystemError {
+    pub message: String,
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct DebugStateSnapshot {
+    pub channel_capacity: usize,
+    pub channel_depth: usize,
+    /// subsystems currently holding requests back, with the time they started to
+    pub circuit_breakers: BTreeMap<String, DateTime<Utc>>,
+    pub in_flight: BTreeMap<String, InFlightEvent>,
+    pub indexations: BTreeMap<String, IndexationProgress>,
+    pub last_errors: BTreeMap<String, SubsystemError>,
+}
+
+/// Shared view of what the background workers are doing, exposed via `GET /debug/state`
+#[derive(Clone, Default)]
+pub struct DebugState {
+    inner: Arc<Mutex<DebugStateSnapshot>>,
+}
+
+impl DebugState {
+    pub fn snapshot(&self) -> DebugStateSnapshot {
+        self.inner.lock().unwrap().clone()
+    }
+
+    pub fn set_in_flight(&self, worker: &str, event: impl Display) {
+        self.inner.lock().unwrap().in_flight.insert(
+            worker.to_owned(),
+            InFlightEvent {
+                event: event.to_string(),
+                started_at: Utc::now(),
+            },
+        );
+    }
+
+    pub fn clear_in_flight(&self, worker: &str) {
+        self.inner.lock().unwrap().in_flight.remove(worker);
+    }
+
+    pub fn start_indexation(&self, name: &str, total: Option<usize>) {
+        let now = Utc::now();
+        self.inner.lock().unwrap().indexations.insert(
+            name.to_owned(),
+            IndexationProgress {
+                processed: 0,
+                total,
+                next_url: None,
+                started_at: now,
+                updated_at: now,
+            },
+        );
+    }
+
+    pub fn indexation_progress(&self, name: &str, next_url: Option<String>) {
+        if let Some(progress) = self.inner.lock().unwrap().indexations.get_mut(name) {
+            progress.processed += 1;
+            if next_url.is_some() {
+                progress.next_url = next_url;
+            }
+            progress.updated_at = Utc::now();
+        }
+    }
+
+    pub fn finish_indexation(&self, name: &str) {
+        self.inner.lock().unwrap().indexations.remove(name);
+    }
+
+    pub fn open_circuit_breaker(&self, subsystem: &str) {
+        self.inner
+            .lock()
+            .unwrap()
+            .circuit_breakers
+            .entry(subsystem.to_owned())
+            .or_insert_with(Utc::now);
+    }
+
+    pub fn close_circuit_breaker(&self, subsystem: &str) {
+        self.inner
+            .lock()
+            .unwrap()
+            .circuit_breakers
+            .remove(subsystem);
+    }
+
+    pub fn record_error(&self, subsystem: &str, err: impl Display) {
+        self.inner.lock().unwrap().last_errors.insert(
+            subsystem.to_owned(),
+            SubsystemError {
+                message: err.to_string(),
+                at: Utc::now(),
+            },
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use crate::{config::DiversityConfig, embeddings::cosine_similarity, ClosestIssue};
+
+/// Picks `limit` of the `candidates` with maximal marginal relevance, so that suggestions don't
+/// all come from the same cluster of near-identical issues
+///
+/// Each pick maximizes `lambda * relevance - (1 - lambda) * redundancy`, relevance being the
+/// boosted similarity to the new issue and redundancy the highest similarity to the issues already picked.
+fn maximal_marginal_relevance(
+    mut candidates: Vec<ClosestIssue>,
+    limit: usize,
+    lambda: f64,
+) -> Vec<ClosestIssue> {
+    let mut picked: Vec<ClosestIssue> = Vec::with_capacity(limit);
+    while picked.len() < limit && !candidates.is_empty() {
+        let score = |candidate: &ClosestIssue| {
+            let redundancy = picked
+                .iter()
+                .map(|p| cosine_similarity(&candidate.embedding, &p.embedding))
+                .fold(0., f64::max);
+            lambda * candidate.rank_score() - (1. - lambda) * redundancy
+        };
+        let best = candidates
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (i, score(c)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i)
+            .unwrap();
+        picked.push(candidates.swap_remove(best));
+    }
+    picked
+}
+
+/// Number of candidates to fetch for `max_suggestions` suggestions
+pub fn candidate_count(cfg: &DiversityConfig, max_suggestions: i64) -> i64 {
+    if cfg.enabled {
+        max_suggestions * cfg.candidate_pool_factor.max(1)
+    } else {
+        max_suggestions
+    }
+}
+
+/// Narrows `candidates`, ordered by decreasing similarity, down to `max_suggestions`
+pub fn diversify(
+    cfg: &DiversityConfig,
+    candidates: Vec<ClosestIssue>,
+    max_suggestions: i64,
+) -> Vec<ClosestIssue> {
+    let limit = max_suggestions.max(0) as usize;
+    if !cfg.enabled {
+        let mut candidates = candidates;
+        candidates.truncate(limit);
+        return candidates;
+    }
+    maximal_marginal_relevance(candidates, limit, cfg.lambda)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ClosestIssue;
+
+    use super::maximal_marginal_relevance;
+
+    fn candidate(number: i32, cosine_similarity: f64, embedding: &[f32]) -> ClosestIssue {
+        ClosestIssue {
+            title: format!("issue {number}"),
+            number,
+            html_url: format!("https://github.com/huggingface/lor-e/issues/{number}"),
+            labels: vec![],
+            repository_full_name: "huggingface/lor-e".to_owned(),
+            cosine_similarity,
+            boost: 0.,
+            embedding: embedding.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_maximal_marginal_relevance() {
+        let candidates = || {
+            vec![
+                candidate(1, 0.95, &[1., 0.]),
+                candidate(2, 0.94, &[1., 0.01]),
+                candidate(3, 0.93, &[0.99, 0.]),
+                candidate(4, 0.8, &[0., 1.]),
+            ]
+        };
+
+        let numbers =
+            |picked: Vec<ClosestIssue>| picked.iter().map(|c| c.number).collect::<Vec<_>>();
+        // duplicates of the closest issue make way for a distinct one
+        assert_eq!(
+            numbers(maximal_marginal_relevance(candidates(), 2, 0.5)),
+            vec![1, 4]
+        );
+        // relevance only
+        assert_eq!(
+            numbers(maximal_marginal_relevance(candidates(), 2, 1.)),
+            vec![1, 2]
+        );
+    }
+}
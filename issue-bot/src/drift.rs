@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use tracing::info;
+
+use crate::{
+    config::DriftConfig,
+    storage::{Database, LinkSimilarity, Storage, StorageError},
+};
+
+/// Spread of the similarities of linked issues under a model
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Distribution {
+    pub count: usize,
+    pub mean: f64,
+    pub p10: f64,
+    pub p50: f64,
+    pub p90: f64,
+}
+
+impl Distribution {
+    fn new(similarities: &[LinkSimilarity]) -> Option<Self> {
+        if similarities.is_empty() {
+            return None;
+        }
+        let mut values: Vec<f64> = similarities.iter().map(|s| s.cosine_similarity).collect();
+        values.sort_by(f64::total_cmp);
+        let quantile = |q: f64| values[((values.len() - 1) as f64 * q).round() as usize];
+        Some(Self {
+            count: values.len(),
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            p10: quantile(0.1),
+            p50: quantile(0.5),
+            p90: quantile(0.9),
+        })
+    }
+}
+
+/// Linked issues similarities under the current model, compared with the baseline model
+#[derive(Debug, Serialize)]
+pub struct DriftReport {
+    pub model: String,
+    pub current: Option<Distribution>,
+    pub baseline_model: Option<String>,
+    pub baseline: Option<Distribution>,
+    /// pairs of linked issues sampled under both models
+    pub common_pairs: usize,
+    /// mean change of the similarity of the common pairs, negative when the current model finds
+    /// linked issues less similar
+    pub mean_delta: Option<f64>,
+}
+
+fn report(
+    model: &str,
+    current: &[LinkSimilarity],
+    baseline_model: Option<&str>,
+    baseline: &[LinkSimilarity],
+) -> DriftReport {
+    let baseline_by_pair: HashMap<_, _> = baseline
+        .iter()
+        .map(|s| {
+            (
+                (s.repository_full_name.as_str(), s.number, s.linked_number),
+                s.cosine_similarity,
+            )
+        })
+        .collect();
+    let deltas: Vec<f64> = current
+        .iter()
+        .filter_map(|s| {
+            baseline_by_pair
+                .get(&(s.repository_full_name.as_str(), s.number, s.linked_number))
+                .map(|previous| s.cosine_similarity - previous)
+        })
+        .collect();
+    DriftReport {
+        model: model.to_owned(),
+        current: Distribution::new(current),
+        baseline_model: baseline_model.map(ToOwned::to_owned),
+        baseline: Distribution::new(baseline),
+        common_pairs: deltas.len(),
+        mean_delta: (!deltas.is_empty()).then(|| deltas.iter().sum::<f64>() / deltas.len() as f64),
+    }
+}
+
+fn record_distribution(model: &str, distribution: &Distribution) {
+    for (stat, value) in [
+        ("mean", distribution.mean),
+        ("p10", distribution.p10),
+        ("p50", distribution.p50),
+        ("p90", distribution.p90),
+    ] {
+        metrics::gauge!("issue_bot_linked_issues_similarity", "model" => model.to_owned(), "stat" => stat)
+            .set(value);
+    }
+}
+
+/// Tracks how the similarity of issues known to be related, from their timeline links, shifts
+/// when the embedding model changes
+#[derive(Clone)]
+pub struct EmbeddingDrift {
+    cfg: DriftConfig,
+    db: Database,
+    model: String,
+}
+
+impl EmbeddingDrift {
+    pub fn new(cfg: DriftConfig, db: Database, model: String) -> Self {
+        Self { cfg, db, model }
+    }
+
+    fn baseline_model(&self) -> Option<&str> {
+        Some(self.cfg.baseline_model.as_str()).filter(|m| !m.is_empty() && *m != self.model)
+    }
+
+    async fn sample(&self, model: &str) -> Result<(), StorageError> {
+        let similarities = self
+            .db
+            .linked_issue_similarities(self.cfg.sample_size)
+            .await?;
+        self.db
+            .record_link_similarities(model, &similarities)
+            .await?;
+        info!(
+            model,
+            pairs = similarities.len(),
+            "sampled linked issues similarities"
+        );
+        Ok(())
+    }
+
+    /// samples the embeddings about to be regenerated as the baseline model's
+    pub async fn sample_baseline(&self) -> Result<(), StorageError> {
+        match self.baseline_model() {
+            Some(baseline_model) => self.sample(baseline_model).await,
+            None => Ok(()),
+        }
+    }
+
+    /// samples the embeddings as the current model's and reports the drift from the baseline
+    pub async fn sample_current(&self) -> Result<DriftReport, StorageError> {
+        self.sample(&self.model).await?;
+        self.report().await
+    }
+
+    pub async fn report(&self) -> Result<DriftReport, StorageError> {
+        let current = self.db.link_similarities(&self.model).await?;
+        let baseline_model = self.baseline_model();
+        let baseline = match baseline_model {
+            Some(baseline_model) => self.db.link_similarities(baseline_model).await?,
+            None => Vec::new(),
+        };
+        let report = report(&self.model, &current, baseline_model, &baseline);
+        if let Some(distribution) = &report.current {
+            record_distribution(&report.model, distribution);
+        }
+        if let (Some(model), Some(distribution)) = (&report.baseline_model, &report.baseline) {
+            record_distribution(model, distribution);
+        }
+        if let Some(mean_delta) = report.mean_delta {
+            metrics::gauge!("issue_bot_embedding_drift_mean_delta").set(mean_delta);
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::LinkSimilarity;
+
+    use super::report;
+
+    #[test]
+    fn test_report() {
+        let similarity = |number, cosine_similarity| LinkSimilarity {
+            repository_full_name: "huggingface/lor-e".to_owned(),
+            number,
+            linked_number: 1,
+            cosine_similarity,
+        };
+        let baseline = [similarity(2, 0.9), similarity(3, 0.8), similarity(4, 0.7)];
+        let current = [similarity(2, 0.7), similarity(3, 0.7), similarity(5, 0.1)];
+
+        let drift = report("new", &current, Some("old"), &baseline);
+        assert_eq!(drift.common_pairs, 2);
+        assert!((drift.mean_delta.unwrap() + 0.15).abs() < 1e-9);
+        let distribution = drift.current.unwrap();
+        assert_eq!(distribution.count, 3);
+        assert_eq!(distribution.p10, 0.1);
+        assert_eq!(distribution.p50, 0.7);
+        assert!((drift.baseline.unwrap().mean - 0.8).abs() < 1e-9);
+
+        let drift = report("new", &[similarity(2, 0.5)], None, &[]);
+        assert_eq!(drift.common_pairs, 0);
+        assert!(drift.mean_delta.is_none());
+        assert!(drift.baseline.is_none());
+    }
+}
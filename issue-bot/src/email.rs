@@ -0,0 +1,287 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{DateTime, Days, NaiveTime, Utc};
+use futures::pin_mut;
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use thiserror::Error;
+use tokio::{select, time::sleep};
+use tracing::{error, info};
+
+use crate::{
+    config::{EmailConfig, EmailMode},
+    shutdown_signal, ClosestIssue, IssueData,
+};
+
+#[derive(Debug, Error)]
+pub enum EmailError {
+    #[error("invalid email address: {0}")]
+    Address(#[from] lettre::address::AddressError),
+    #[error("failed to build email: {0}")]
+    Message(#[from] lettre::error::Error),
+    #[error("smtp error: {0}")]
+    Smtp(#[from] lettre::transport::smtp::Error),
+}
+
+/// New issue and its closest matches, as listed in emails
+struct Entry {
+    closest_issues: Vec<String>,
+    html_url: String,
+    number: i32,
+    repository_full_name: String,
+    title: String,
+}
+
+impl Entry {
+    fn new(issue: &IssueData, closest_issues: &[ClosestIssue]) -> Self {
+        Self {
+            closest_issues: closest_issues
+                .iter()
+                .map(|ci| format!("  - {} ({})", ci.title, ci.html_url))
+                .collect(),
+            html_url: issue.html_url.clone(),
+            number: issue.number,
+            repository_full_name: issue.repository_full_name.clone(),
+            title: issue.title.clone(),
+        }
+    }
+}
+
+/// time left until the next digest, sent every day at `hour` UTC
+fn until_next_digest(now: DateTime<Utc>, hour: u32) -> Duration {
+    let time = NaiveTime::from_hms_opt(hour, 0, 0).unwrap_or(NaiveTime::MIN);
+    let mut next = now.date_naive().and_time(time).and_utc();
+    if next <= now {
+        next = next + Days::new(1);
+    }
+    (next - now).to_std().unwrap_or_default()
+}
+
+fn email_text(entries: &[Entry]) -> String {
+    let mut text = Vec::new();
+    for entry in entries {
+        text.push(format!(
+            "{}#{} {}\n{}\nClosest issues:",
+            entry.repository_full_name, entry.number, entry.title, entry.html_url
+        ));
+        text.extend(entry.closest_issues.iter().cloned());
+        text.push(String::new());
+    }
+    text.join("\n")
+}
+
+/// Emails new issues and their closest matches to maintainers, either right away or as a daily
+/// digest
+#[derive(Clone)]
+pub struct EmailNotifier {
+    digest_hour: u32,
+    from: Option<Mailbox>,
+    mode: EmailMode,
+    /// entries waiting for the next daily digest
+    pending: Arc<Mutex<Vec<Entry>>>,
+    recipients: Vec<Mailbox>,
+    transport: Option<AsyncSmtpTransport<Tokio1Executor>>,
+}
+
+impl EmailNotifier {
+    pub fn new(cfg: &EmailConfig) -> Result<Self, EmailError> {
+        if !cfg.enabled {
+            return Ok(Self {
+                digest_hour: cfg.digest_hour,
+                from: None,
+                mode: cfg.mode,
+                pending: Arc::default(),
+                recipients: Vec::new(),
+                transport: None,
+            });
+        }
+        let transport = AsyncSmtpTransport::<Tokio1Executor>::relay(&cfg.smtp_host)?
+            .port(cfg.smtp_port)
+            .credentials(Credentials::new(
+                cfg.smtp_username.clone(),
+                cfg.smtp_password.clone(),
+            ))
+            .build();
+        Ok(Self {
+            digest_hour: cfg.digest_hour,
+            from: Some(cfg.from.parse()?),
+            mode: cfg.mode,
+            pending: Arc::default(),
+            recipients: cfg
+                .recipients
+                .iter()
+                .map(|r| r.parse())
+                .collect::<Result<_, _>>()?,
+            transport: Some(transport),
+        })
+    }
+
+    async fn send(&self, subject: String, entries: &[Entry]) -> Result<(), EmailError> {
+        let (Some(transport), Some(from)) = (&self.transport, &self.from) else {
+            return Ok(());
+        };
+        let mut builder = Message::builder()
+            .from(from.clone())
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN);
+        for recipient in &self.recipients {
+            builder = builder.to(recipient.clone());
+        }
+        transport.send(builder.body(email_text(entries))?).await?;
+        info!(issues = entries.len(), "sent closest issues email");
+        Ok(())
+    }
+
+    pub async fn closest_issues(
+        &self,
+        issue: &IssueData,
+        closest_issues: &[ClosestIssue],
+    ) -> Result<(), EmailError> {
+        if self.transport.is_none() {
+            return Ok(());
+        }
+        let entry = Entry::new(issue, closest_issues);
+        match self.mode {
+            EmailMode::Daily => {
+                self.pending.lock().unwrap().push(entry);
+                Ok(())
+            }
+            EmailMode::Immediate => {
+                let subject = format!(
+                    "[{}] New issue #{}: {}",
+                    issue.repository_full_name, issue.number, issue.title
+                );
+                self.send(subject, &[entry]).await
+            }
+        }
+    }
+
+    /// sends the pending entries, which are kept for the next digest when sending fails
+    async fn send_digest(&self) -> Result<(), EmailError> {
+        let mut entries = std::mem::take(&mut *self.pending.lock().unwrap());
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let subject = format!("Daily digest: {} new issues", entries.len());
+        let res = self.send(subject, &entries).await;
+        if res.is_err() {
+            let mut pending = self.pending.lock().unwrap();
+            entries.append(&mut pending);
+            *pending = entries;
+        }
+        res
+    }
+
+    /// issues waiting for the next daily digest
+    #[cfg(test)]
+    pub fn pending_issues(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+}
+
+/// Sends the daily digest at `digest_hour` UTC, pending entries are sent on shutdown
+pub async fn start_email_digest(notifier: EmailNotifier) -> anyhow::Result<()> {
+    if notifier.transport.is_none() || notifier.mode != EmailMode::Daily {
+        return Ok(());
+    }
+
+    info!("starting email digest");
+    let shutdown = shutdown_signal();
+    pin_mut!(shutdown);
+    loop {
+        let shutting_down = select! {
+            _ = sleep(until_next_digest(Utc::now(), notifier.digest_hour)) => false,
+            _ = &mut shutdown => true,
+        };
+        if let Err(err) = notifier.send_digest().await {
+            error!(err = err.to_string(), "failed to send email digest");
+        }
+        if shutting_down {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{TimeZone, Utc};
+
+    use crate::{config::EmailConfig, Action, IssueData, Source};
+
+    use super::{email_text, until_next_digest, EmailNotifier, Entry};
+
+    #[test]
+    fn test_until_next_digest() {
+        let now = Utc.with_ymd_and_hms(2024, 5, 1, 6, 30, 0).unwrap();
+        assert_eq!(until_next_digest(now, 8), Duration::from_secs(90 * 60));
+        assert_eq!(
+            until_next_digest(now, 6),
+            Duration::from_secs(23 * 3_600 + 30 * 60)
+        );
+        let midnight = Utc.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap();
+        assert_eq!(
+            until_next_digest(midnight, 0),
+            Duration::from_secs(24 * 3_600)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_digest_keeps_entries() {
+        let notifier = EmailNotifier::new(&EmailConfig {
+            enabled: true,
+            from: "lor-e <bot@example.com>".to_owned(),
+            recipients: vec!["maintainers@example.com".to_owned()],
+            smtp_host: "127.0.0.1".to_owned(),
+            smtp_port: 1,
+            ..Default::default()
+        })
+        .unwrap();
+        let issue = IssueData {
+            source_id: 1,
+            action: Action::Created,
+            author: None,
+            labels: Vec::new(),
+            milestone: None,
+            title: "New issue".to_owned(),
+            body: String::new(),
+            is_pull_request: false,
+            number: 1,
+            html_url: "https://github.com/huggingface/lor-e/issues/1".to_owned(),
+            url: "https://api.github.com/repos/huggingface/lor-e/issues/1".to_owned(),
+            repository_full_name: "huggingface/lor-e".to_owned(),
+            source: Source::Github,
+        };
+        notifier.closest_issues(&issue, &[]).await.unwrap();
+        notifier.closest_issues(&issue, &[]).await.unwrap();
+
+        assert!(notifier.send_digest().await.is_err());
+        assert_eq!(notifier.pending_issues(), 2);
+    }
+
+    #[test]
+    fn test_email_text() {
+        let entries = vec![Entry {
+            closest_issues: vec![
+                "  - Older issue (https://github.com/huggingface/lor-e/issues/1)".to_owned(),
+            ],
+            html_url: "https://github.com/huggingface/lor-e/issues/2".to_owned(),
+            number: 2,
+            repository_full_name: "huggingface/lor-e".to_owned(),
+            title: "New issue".to_owned(),
+        }];
+
+        assert_eq!(
+            email_text(&entries),
+            "huggingface/lor-e#2 New issue\nhttps://github.com/huggingface/lor-e/issues/2\nClosest issues:\n  - Older issue (https://github.com/huggingface/lor-e/issues/1)\n"
+        );
+    }
+}
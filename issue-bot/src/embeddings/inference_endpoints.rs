@@ -7,18 +7,36 @@ use reqwest::{
 use serde::{Deserialize, Serialize};
 use tracing::warn;
 
-use crate::{config::EmbeddingApiConfig, APP_USER_AGENT};
+use crate::{
+    config::{EmbeddingApiConfig, HttpClientConfig, HttpTarget, TimeoutsConfig},
+    debug::DebugState,
+    failover::Endpoints,
+    http_client::{client_builder, with_client_certificate},
+    retry::{host, RetryBudget},
+};
 
-use super::EmbeddingError;
+use super::{EmbeddingError, EmbeddingMetadata};
 
 #[derive(Serialize)]
 struct OAIEmbedRequest {
     input: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dimensions: Option<u32>,
+    /// TEI extension, other OpenAI-style APIs ignore it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    truncate: Option<bool>,
 }
 
 #[derive(Deserialize)]
 struct OAIEmbedResponse {
     data: Vec<OAIEmbedData>,
+    #[serde(default)]
+    usage: Option<OAIEmbedUsage>,
+}
+
+#[derive(Deserialize)]
+struct OAIEmbedUsage {
+    prompt_tokens: i32,
 }
 
 #[derive(Deserialize)]
@@ -26,49 +44,174 @@ struct OAIEmbedData {
     embedding: Vec<f32>,
 }
 
+/// Error bodies of OpenAI-style APIs and of TEI
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ProviderErrorBody {
+    OpenAI {
+        error: OAIError,
+    },
+    Tei {
+        error: String,
+        #[serde(default)]
+        error_type: Option<String>,
+    },
+}
+
+#[derive(Deserialize)]
+struct OAIError {
+    message: String,
+    #[serde(default)]
+    code: Option<String>,
+    #[serde(default, rename = "type")]
+    kind: Option<String>,
+}
+
+/// how providers word inputs over the model limit when they don't send a dedicated code
+const TOO_LONG_HINTS: [&str; 3] = ["maximum context length", "must have less than", "too long"];
+
+/// Typed error out of a 4xx response, [EmbeddingError::HttpClientError] when it's none of the
+/// ones callers react to
+fn provider_error(status: StatusCode, body: &str) -> EmbeddingError {
+    let (message, kind) = match serde_json::from_str::<ProviderErrorBody>(body) {
+        Ok(ProviderErrorBody::OpenAI { error }) => (error.message, error.code.or(error.kind)),
+        Ok(ProviderErrorBody::Tei { error, error_type }) => (error, error_type),
+        Err(_) => (body.to_owned(), None),
+    };
+    let kind = kind.unwrap_or_default().to_lowercase();
+    let lowercase = message.to_lowercase();
+    if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+        EmbeddingError::Unauthorized(status)
+    } else if status == StatusCode::PAYLOAD_TOO_LARGE
+        || kind == "context_length_exceeded"
+        || TOO_LONG_HINTS.iter().any(|hint| lowercase.contains(hint))
+    {
+        EmbeddingError::TooLong(message)
+    } else if status == StatusCode::TOO_MANY_REQUESTS
+        || kind == "overloaded"
+        || lowercase.contains("overloaded")
+    {
+        EmbeddingError::Overloaded(message)
+    } else {
+        EmbeddingError::HttpClientError(status)
+    }
+}
+
 #[derive(Clone)]
 pub struct EmbeddingApi {
-    cfg: EmbeddingApiConfig,
     client: Client,
+    debug_state: DebugState,
+    dimensions: Option<u32>,
+    endpoints: Endpoints,
+    max_input_chars: Option<usize>,
+    model: String,
+    retry_budget: RetryBudget,
+    truncate: Option<bool>,
 }
 
 impl EmbeddingApi {
-    pub fn new(cfg: EmbeddingApiConfig) -> Result<Self, EmbeddingError> {
+    pub fn new(
+        cfg: EmbeddingApiConfig,
+        http_cfg: &HttpClientConfig,
+        timeouts: &TimeoutsConfig,
+        debug_state: DebugState,
+        retry_budget: RetryBudget,
+    ) -> Result<Self, EmbeddingError> {
         let mut headers = HeaderMap::new();
         let mut auth_value = HeaderValue::from_str(&format!("Bearer {}", cfg.auth_token))?;
         auth_value.set_sensitive(true);
         headers.insert(AUTHORIZATION, auth_value);
-        let client = Client::builder()
-            .timeout(Duration::from_secs(30))
-            .user_agent(APP_USER_AGENT)
+        let builder = client_builder(http_cfg, timeouts, HttpTarget::Embeddings)?;
+        let client = with_client_certificate(builder, cfg.client_certificate.as_ref())?
             .default_headers(headers)
             .build()?;
 
-        Ok(Self { cfg, client })
+        Ok(Self {
+            client,
+            debug_state,
+            dimensions: cfg.dimensions,
+            endpoints: Endpoints::new("embedding_api", cfg.failover, cfg.url, cfg.fallback_urls),
+            max_input_chars: cfg.max_input_chars,
+            model: cfg.model,
+            retry_budget,
+            truncate: cfg.truncate,
+        })
     }
 
-    pub async fn generate_embedding(&self, text: String) -> Result<Vec<f32>, EmbeddingError> {
+    fn request(&self, input: String) -> OAIEmbedRequest {
+        OAIEmbedRequest {
+            input,
+            dimensions: self.dimensions,
+            truncate: self.truncate,
+        }
+    }
+
+    /// single request without retries, an endpoint scaled to zero is considered reachable
+    pub async fn check(&self) -> Result<(), EmbeddingError> {
+        let res = self
+            .client
+            .post(format!("{}/v1/embeddings", self.endpoints.primary()))
+            .json(&self.request("startup check".to_owned()))
+            .send()
+            .await?;
+        let status = res.status();
+        if status == StatusCode::SERVICE_UNAVAILABLE {
+            warn!("Embedding API is scaled to zero, skipping embedding check");
+            return Ok(());
+        }
+        if status.is_client_error() {
+            return Err(provider_error(status, &res.text().await?));
+        }
+        res.error_for_status()?
+            .json::<OAIEmbedResponse>()
+            .await?
+            .data
+            .pop()
+            .ok_or(EmbeddingError::MissingEmbedding)?;
+        Ok(())
+    }
+
+    /// embedding of `text`, along with how it was generated
+    pub async fn generate_embedding(
+        &self,
+        text: String,
+    ) -> Result<(Vec<f32>, EmbeddingMetadata), EmbeddingError> {
+        let (mut text, mut truncated) = match self.max_input_chars {
+            Some(max_chars) if text.chars().count() > max_chars => {
+                (text.chars().take(max_chars).collect(), true)
+            }
+            _ => (text, false),
+        };
         const MAX_RETRIES: u32 = 5;
         const MAX_WAKE_UP_RETRIES: u32 = 30;
+        // times the input is halved when the provider rejects it as too long
+        const MAX_SHORTENINGS: u32 = 3;
         let mut retries = 0;
         let mut wake_up_retries = 0;
+        let mut shortenings = 0;
         loop {
+            let (endpoint, url) = self.endpoints.select();
             let res = self
                 .client
-                .post(format!("{}/v1/embeddings", self.cfg.url))
-                .json(&OAIEmbedRequest {
-                    input: text.clone(),
-                })
+                .post(format!("{url}/v1/embeddings"))
+                .json(&self.request(text.clone()))
                 .send()
                 .await;
             let res = match res {
                 Err(e) => {
                     if e.is_timeout() {
                         warn!("Embedding API request timed out");
+                        if self.endpoints.failed(endpoint) {
+                            continue;
+                        }
                         retries += 1;
                         if retries > MAX_RETRIES {
                             return Err(EmbeddingError::MaxRetriesExceeded(MAX_RETRIES));
                         }
+                        let host = host(url);
+                        if !self.retry_budget.withdraw(&host) {
+                            return Err(EmbeddingError::RetryBudgetExhausted(host));
+                        }
                         tokio::time::sleep(Duration::from_secs(2_u64.pow(retries))).await;
                         continue;
                     }
@@ -84,12 +227,31 @@ impl EmbeddingApi {
                     "[status: {}] Embedding API returned: '{}'",
                     status, response_content
                 );
-                return Err(EmbeddingError::HttpClientError(status));
+                let err = provider_error(status, &response_content);
+                let input_chars = text.chars().count();
+                if matches!(err, EmbeddingError::TooLong(_))
+                    && shortenings < MAX_SHORTENINGS
+                    && input_chars > 1
+                {
+                    shortenings += 1;
+                    warn!(
+                        input_chars,
+                        "Embedding input too long, retrying with half of it"
+                    );
+                    text = text.chars().take(input_chars / 2).collect();
+                    truncated = true;
+                    continue;
+                }
+                return Err(err);
             }
             if res.status() != StatusCode::OK {
                 // Autoscaled to 0, waiting for wake up
                 if res.status() == StatusCode::SERVICE_UNAVAILABLE {
                     warn!("Embedding API service unavailable, retrying...");
+                    self.debug_state.open_circuit_breaker("embedding_api");
+                    if self.endpoints.unavailable(endpoint) {
+                        continue;
+                    }
                     wake_up_retries += 1;
                     if wake_up_retries > MAX_WAKE_UP_RETRIES {
                         return Err(EmbeddingError::ServiceUnavailable(MAX_WAKE_UP_RETRIES));
@@ -103,20 +265,74 @@ impl EmbeddingApi {
                     "[status: {}] Embedding API returned: '{}'",
                     status, response_content
                 );
+                if self.endpoints.failed(endpoint) {
+                    continue;
+                }
                 retries += 1;
                 if retries > MAX_RETRIES {
                     return Err(EmbeddingError::MaxRetriesExceeded(MAX_RETRIES));
                 }
+                let host = host(url);
+                if !self.retry_budget.withdraw(&host) {
+                    return Err(EmbeddingError::RetryBudgetExhausted(host));
+                }
                 tokio::time::sleep(Duration::from_secs(2_u64.pow(retries))).await;
                 continue;
             }
-            return res
-                .json::<OAIEmbedResponse>()
-                .await?
+            self.debug_state.close_circuit_breaker("embedding_api");
+            self.endpoints.succeeded(endpoint);
+            self.retry_budget.deposit(&host(url));
+            let mut res = res.json::<OAIEmbedResponse>().await?;
+            let embedding = res
                 .data
                 .pop()
                 .map(|d| d.embedding)
-                .ok_or(EmbeddingError::MissingEmbedding);
+                .ok_or(EmbeddingError::MissingEmbedding)?;
+            let metadata = EmbeddingMetadata {
+                model: self.model.clone(),
+                endpoint: url.to_owned(),
+                input_chars: text.chars().count() as i32,
+                input_tokens: res.usage.map(|usage| usage.prompt_tokens),
+                truncated,
+                // set by the callers wrapping issues in the template
+                template: None,
+            };
+            return Ok((embedding, metadata));
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use reqwest::StatusCode;
+
+    use super::provider_error;
+    use crate::embeddings::EmbeddingError;
+
+    #[test]
+    fn test_provider_error() {
+        let tei = r#"{"error":"Input validation error: `inputs` must have less than 512 tokens. Given: 600","error_type":"Validation"}"#;
+        assert!(matches!(
+            provider_error(StatusCode::PAYLOAD_TOO_LARGE, tei),
+            EmbeddingError::TooLong(_)
+        ));
+        let openai = r#"{"error":{"message":"This model's maximum context length is 8192 tokens","type":"invalid_request_error","code":"context_length_exceeded"}}"#;
+        assert!(matches!(
+            provider_error(StatusCode::BAD_REQUEST, openai),
+            EmbeddingError::TooLong(_)
+        ));
+        let overloaded = r#"{"error":"Model is overloaded","error_type":"overloaded"}"#;
+        assert!(matches!(
+            provider_error(StatusCode::TOO_MANY_REQUESTS, overloaded),
+            EmbeddingError::Overloaded(_)
+        ));
+        assert!(matches!(
+            provider_error(StatusCode::UNAUTHORIZED, "Unauthorized"),
+            EmbeddingError::Unauthorized(StatusCode::UNAUTHORIZED)
+        ));
+        assert!(matches!(
+            provider_error(StatusCode::NOT_FOUND, "not found"),
+            EmbeddingError::HttpClientError(StatusCode::NOT_FOUND)
+        ));
+    }
+}
@@ -1,15 +1,56 @@
 use reqwest::StatusCode;
+use serde::Serialize;
 use thiserror::Error;
 
+use crate::{
+    http_client::ClientCertificateError,
+    retry::{classify_reqwest, classify_status, Classify, RetryClass},
+};
+
 pub mod inference_endpoints;
+pub mod queue;
 // mod local;
 
+/// cosine similarity of two embeddings, `0` when either is null
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f64 {
+    let (mut dot, mut norm_a, mut norm_b) = (0f64, 0f64, 0f64);
+    for (x, y) in a.iter().zip(b) {
+        let (x, y) = (*x as f64, *y as f64);
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+    if norm_a == 0. || norm_b == 0. {
+        return 0.;
+    }
+    dot / (norm_a.sqrt() * norm_b.sqrt())
+}
+
+/// How an embedding was generated, stored along with issue embeddings to reproduce rankings
+#[derive(Clone, Debug, Serialize)]
+pub struct EmbeddingMetadata {
+    pub model: String,
+    /// url of the endpoint that answered, which differs from the primary one after failing over
+    pub endpoint: String,
+    /// characters embedded, after truncation
+    pub input_chars: i32,
+    /// as reported by the API's usage, when it does
+    pub input_tokens: Option<i32>,
+    pub truncated: bool,
+    /// version of the metadata template the input was wrapped in, changing along with the
+    /// template so that embeddings of an older one can be told apart, see
+    /// [crate::issue_text::IssueTextComposer::template_version]
+    pub template: Option<String>,
+}
+
 #[derive(Debug, Error)]
 pub enum EmbeddingError {
     // #[error("candle error: {0}")]
     // Candle(#[from] candle::Error),
     // #[error("hf hub error: {0}")]
     // HfHub(#[from] hf_hub::api::tokio::ApiError),
+    #[error("client certificate error: {0}")]
+    ClientCertificate(#[from] ClientCertificateError),
     #[error("http client error: {0}")]
     HttpClientError(StatusCode),
     #[error("invalid header value: {0}")]
@@ -22,12 +63,35 @@ pub enum EmbeddingError {
     MaxRetriesExceeded(u32),
     #[error("no embedding was returned from the API")]
     MissingEmbedding,
+    #[error("embedding API overloaded: {0}")]
+    Overloaded(String),
+    #[error("embedding queue closed")]
+    QueueClosed,
     #[error("reqwest error: {0}")]
     Reqwest(#[from] reqwest::Error),
+    #[error("retry budget of {0} exhausted")]
+    RetryBudgetExhausted(String),
     #[error("serde json error: {0}")]
     SerdeJson(#[from] serde_json::Error),
     #[error("max retries ({0}) to wake up from autoscaling exceeded, service unavailable")]
     ServiceUnavailable(u32),
+    #[error("input exceeds the model limit: {0}")]
+    TooLong(String),
+    #[error("embedding API rejected the credentials ({0})")]
+    Unauthorized(StatusCode),
     // #[error("tokenizers error: {0}")]
     // Tokenizers(#[from] tokenizers::Error),
 }
+
+impl Classify for EmbeddingError {
+    fn retry_class(&self) -> RetryClass {
+        match self {
+            Self::HttpClientError(status) => classify_status(*status),
+            Self::Overloaded(_) => RetryClass::RateLimited,
+            Self::Reqwest(err) => classify_reqwest(err),
+            // `MaxRetriesExceeded` and `ServiceUnavailable` were already retried by the api client,
+            // `TooLong` inputs were already shortened by it, `RetryBudgetExhausted` retries were shed
+            _ => RetryClass::Fatal,
+        }
+    }
+}
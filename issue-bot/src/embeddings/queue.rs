@@ -0,0 +1,162 @@
+use std::time::{Duration, Instant};
+
+use tokio::{
+    select,
+    sync::{mpsc, oneshot},
+    time::sleep,
+};
+use tracing::warn;
+
+use crate::config::EmbeddingApiConfig;
+
+use super::{inference_endpoints::EmbeddingApi, EmbeddingError, EmbeddingMetadata};
+
+const QUEUE_SIZE: usize = 1_024;
+
+/// Jobs of higher priority are always sent first when the rate limit is reached
+#[derive(Clone, Copy, Debug)]
+pub enum Priority {
+    /// newly opened issues, a user is waiting for the bot's comment
+    Interactive,
+    /// indexation and embeddings regeneration
+    Background,
+}
+
+struct Job {
+    text: String,
+    tx: oneshot::Sender<Result<(Vec<f32>, EmbeddingMetadata), EmbeddingError>>,
+}
+
+/// Allows bursts of up to `capacity` requests, refilled at `refill_per_sec`
+struct TokenBucket {
+    capacity: f64,
+    last_refill: Instant,
+    refill_per_sec: f64,
+    tokens: f64,
+}
+
+impl TokenBucket {
+    fn new(capacity: u32, refill_per_sec: f64, now: Instant) -> Self {
+        Self {
+            capacity: capacity as f64,
+            last_refill: now,
+            refill_per_sec,
+            tokens: capacity as f64,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
+    }
+
+    /// time until a token is available
+    fn wait_time(&mut self, now: Instant) -> Duration {
+        self.refill(now);
+        if self.tokens >= 1. {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1. - self.tokens) / self.refill_per_sec)
+        }
+    }
+
+    fn take(&mut self, now: Instant) {
+        self.refill(now);
+        self.tokens -= 1.;
+    }
+}
+
+/// Shared entrypoint to the embedding API, keeping every pipeline under a global request rate
+#[derive(Clone)]
+pub struct EmbeddingQueue {
+    background: mpsc::Sender<Job>,
+    interactive: mpsc::Sender<Job>,
+}
+
+impl EmbeddingQueue {
+    /// Spawns the task sending queued jobs, it stops once every handle to the queue is dropped
+    pub fn new(cfg: &EmbeddingApiConfig, embedding_api: EmbeddingApi) -> Self {
+        let (background, mut background_rx) = mpsc::channel::<Job>(QUEUE_SIZE);
+        let (interactive, mut interactive_rx) = mpsc::channel::<Job>(QUEUE_SIZE);
+        let mut bucket = TokenBucket::new(cfg.burst, cfg.requests_per_sec, Instant::now());
+        tokio::spawn(async move {
+            loop {
+                sleep(bucket.wait_time(Instant::now())).await;
+                let job = select! {
+                    biased;
+                    Some(job) = interactive_rx.recv() => job,
+                    Some(job) = background_rx.recv() => job,
+                    else => break,
+                };
+                bucket.take(Instant::now());
+                let embedding_api = embedding_api.clone();
+                tokio::spawn(async move {
+                    let res = embedding_api.generate_embedding(job.text).await;
+                    if job.tx.send(res).is_err() {
+                        warn!("embedding job dropped before completion");
+                    }
+                });
+            }
+        });
+        Self {
+            background,
+            interactive,
+        }
+    }
+
+    pub async fn generate_embedding(
+        &self,
+        text: String,
+        priority: Priority,
+    ) -> Result<Vec<f32>, EmbeddingError> {
+        let (embedding, _) = self
+            .generate_embedding_with_metadata(text, priority)
+            .await?;
+        Ok(embedding)
+    }
+
+    /// same as [Self::generate_embedding], for embeddings that are stored, see [EmbeddingMetadata]
+    pub async fn generate_embedding_with_metadata(
+        &self,
+        text: String,
+        priority: Priority,
+    ) -> Result<(Vec<f32>, EmbeddingMetadata), EmbeddingError> {
+        let (tx, rx) = oneshot::channel();
+        let queue = match priority {
+            Priority::Interactive => &self.interactive,
+            Priority::Background => &self.background,
+        };
+        queue
+            .send(Job { text, tx })
+            .await
+            .map_err(|_| EmbeddingError::QueueClosed)?;
+        rx.await.map_err(|_| EmbeddingError::QueueClosed)?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::TokenBucket;
+
+    #[test]
+    fn test_token_bucket() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(2, 4., now);
+
+        assert_eq!(bucket.wait_time(now), Duration::ZERO);
+        bucket.take(now);
+        bucket.take(now);
+        assert_eq!(bucket.wait_time(now), Duration::from_millis(250));
+        assert_eq!(
+            bucket.wait_time(now + Duration::from_millis(250)),
+            Duration::ZERO
+        );
+        // refills never exceed the burst capacity
+        bucket.take(now + Duration::from_millis(250));
+        bucket.refill(now + Duration::from_secs(10));
+        assert_eq!(bucket.tokens, 2.);
+    }
+}
@@ -1,13 +1,16 @@
 use axum::{
-    http::StatusCode,
+    http::{header::CONTENT_TYPE, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
-    Json,
 };
-use serde_json::json;
+use serde::Serialize;
 use thiserror::Error;
+use tokio::sync::mpsc::error::SendError;
 use tracing::error;
 
-use crate::EventData;
+use crate::{
+    compare::CompareError, onboarding::OnboardingError, search::SearchError,
+    webhooks::WebhookCheckError, QueuedEvent,
+};
 
 #[derive(Debug, Error)]
 pub enum ApiError {
@@ -15,93 +18,247 @@ pub enum ApiError {
     Auth,
     #[error("auth error")]
     Axum(#[from] axum::Error),
+    #[error("bad request: {0}")]
+    BadRequest(String),
+    #[error("missed webhooks are already being caught up on")]
+    CatchUpInProgress,
+    #[error("compare error: {0}")]
+    Compare(#[from] CompareError),
     #[error("embedding error: {0}")]
     Embedding(#[from] crate::embeddings::EmbeddingError),
     #[error("hmac key invalid length")]
     Hmac(#[from] hmac::digest::InvalidLength),
+    #[error("repository {0} is already being indexed")]
+    IndexationInProgress(String),
+    #[error("knowledge base error: {0}")]
+    KnowledgeBase(#[from] crate::knowledge_base::KnowledgeBaseError),
+    #[error("background jobs are held for maintenance")]
+    Maintenance,
     #[error("malformed webhook: {0}")]
     MalformedWebhook(String),
+    #[error("not found")]
+    NotFound,
+    #[error("onboarding error: {0}")]
+    Onboarding(#[from] OnboardingError),
+    #[error("payload too large")]
+    PayloadTooLarge,
+    #[error("embeddings are already being regenerated")]
+    RegenerationInProgress,
+    #[error("search error: {0}")]
+    Search(#[from] SearchError),
     #[error("send error: {0}")]
-    Send(#[from] tokio::sync::mpsc::error::SendError<EventData>),
+    Send(Box<SendError<QueuedEvent>>),
     #[error("serde json error: {0}")]
     SerdeJson(#[from] serde_json::Error),
     #[error("signatures don't match")]
     SignatureMismatch,
     #[error("sqlx error: {0}")]
     Sqlx(#[from] sqlx::error::Error),
+    #[error("storage error: {0}")]
+    Storage(#[from] crate::storage::StorageError),
     #[error("to str error: {0}")]
     ToStr(#[from] axum::http::header::ToStrError),
+    #[error("unsupported media type: {0}")]
+    UnsupportedMediaType(String),
+    #[error("webhook check error: {0}")]
+    WebhookCheck(#[from] WebhookCheckError),
 }
 
-impl IntoResponse for ApiError {
-    fn into_response(self) -> Response {
-        let (status, error_message) = match self {
-            ApiError::Auth => (
-                StatusCode::UNAUTHORIZED,
-                StatusCode::UNAUTHORIZED.to_string(),
-            ),
-            ApiError::Axum(err) => {
-                error!("{}", err);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "Internal server error".to_string(),
-                )
-            }
-            ApiError::Embedding(err) => {
-                error!("{}", err);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "Internal server error".to_string(),
-                )
-            }
-            ApiError::Hmac(err) => {
-                error!("{}", err);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "Internal server error".to_string(),
-                )
+impl ApiError {
+    /// status, stable machine readable code and details safe to return to clients
+    fn problem_parts(&self) -> (StatusCode, &'static str, Option<String>) {
+        match self {
+            ApiError::Auth => (StatusCode::UNAUTHORIZED, "unauthorized", None),
+            ApiError::Axum(_) | ApiError::Hmac(_) | ApiError::SerdeJson(_) | ApiError::ToStr(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal_error", None)
             }
-            ApiError::MalformedWebhook(err) => {
-                error!("{}", err);
-                (StatusCode::BAD_REQUEST, "Bad request".to_string())
+            ApiError::BadRequest(detail) => {
+                (StatusCode::BAD_REQUEST, "bad_request", Some(detail.clone()))
             }
-            ApiError::Send(err) => {
-                error!("failed to send to background thread: {}", err);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "Internal server error".to_string(),
-                )
-            }
-            ApiError::SerdeJson(err) => {
-                error!("{}", err);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "Internal server error".to_string(),
-                )
-            }
-            ApiError::SignatureMismatch => {
-                (StatusCode::FORBIDDEN, StatusCode::FORBIDDEN.to_string())
+            ApiError::CatchUpInProgress => (
+                StatusCode::CONFLICT,
+                "catch_up_in_progress",
+                Some(self.to_string()),
+            ),
+            ApiError::Compare(CompareError::InvalidReference(_)) => (
+                StatusCode::BAD_REQUEST,
+                "bad_request",
+                Some(self.to_string()),
+            ),
+            ApiError::Compare(CompareError::NotFound(_)) => {
+                (StatusCode::NOT_FOUND, "not_found", Some(self.to_string()))
             }
-            ApiError::Sqlx(err) => {
-                error!("{}", err);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "Internal server error".to_string(),
-                )
+            ApiError::Compare(_) => (StatusCode::INTERNAL_SERVER_ERROR, "compare_failed", None),
+            ApiError::Embedding(_) => (StatusCode::INTERNAL_SERVER_ERROR, "embedding_failed", None),
+            ApiError::IndexationInProgress(_) => (
+                StatusCode::CONFLICT,
+                "indexation_in_progress",
+                Some(self.to_string()),
+            ),
+            ApiError::KnowledgeBase(_) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "knowledge_base_failed",
+                None,
+            ),
+            ApiError::Maintenance => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "maintenance",
+                Some(self.to_string()),
+            ),
+            ApiError::MalformedWebhook(detail) => (
+                StatusCode::BAD_REQUEST,
+                "malformed_webhook",
+                Some(detail.clone()),
+            ),
+            ApiError::NotFound => (StatusCode::NOT_FOUND, "not_found", None),
+            ApiError::Onboarding(OnboardingError::WebhookCheck(WebhookCheckError::MissingUrl)) => (
+                StatusCode::CONFLICT,
+                "webhook_url_not_set",
+                Some(self.to_string()),
+            ),
+            ApiError::Onboarding(OnboardingError::NotAllowed(_)) => (
+                StatusCode::FORBIDDEN,
+                "onboarding_not_allowed",
+                Some(self.to_string()),
+            ),
+            ApiError::Onboarding(_) => (StatusCode::BAD_GATEWAY, "onboarding_failed", None),
+            ApiError::PayloadTooLarge => (StatusCode::PAYLOAD_TOO_LARGE, "payload_too_large", None),
+            ApiError::RegenerationInProgress => (
+                StatusCode::CONFLICT,
+                "regeneration_in_progress",
+                Some(self.to_string()),
+            ),
+            ApiError::Search(SearchError::InvalidCursor(_)) => (
+                StatusCode::BAD_REQUEST,
+                "invalid_cursor",
+                Some(self.to_string()),
+            ),
+            ApiError::Search(_) => (StatusCode::INTERNAL_SERVER_ERROR, "search_failed", None),
+            ApiError::Send(_) => (StatusCode::INTERNAL_SERVER_ERROR, "queue_closed", None),
+            ApiError::SignatureMismatch => (StatusCode::FORBIDDEN, "signature_mismatch", None),
+            ApiError::Sqlx(_) | ApiError::Storage(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "storage_error", None)
             }
-            ApiError::ToStr(err) => {
-                error!("{}", err);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "Internal server error".to_string(),
-                )
+            ApiError::UnsupportedMediaType(content_type) => (
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "unsupported_media_type",
+                Some(format!("expected application/json, got '{content_type}'")),
+            ),
+            ApiError::WebhookCheck(WebhookCheckError::MissingUrl) => (
+                StatusCode::CONFLICT,
+                "webhook_url_not_set",
+                Some(self.to_string()),
+            ),
+            ApiError::WebhookCheck(_) => (StatusCode::BAD_GATEWAY, "webhook_check_failed", None),
+        }
+    }
+}
+
+// boxed by hand, queued events would otherwise make every `Result<_, ApiError>` large
+impl From<SendError<QueuedEvent>> for ApiError {
+    fn from(err: SendError<QueuedEvent>) -> Self {
+        Self::Send(Box::new(err))
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, code, detail) = self.problem_parts();
+        if status.is_server_error()
+            || matches!(self, Self::BadRequest(_) | Self::MalformedWebhook(_))
+        {
+            error!("{}", self);
+        }
+        Problem::new(status, code, detail).into_response()
+    }
+}
+
+/// [RFC 7807](https://www.rfc-editor.org/rfc/rfc7807) error body
+///
+/// Responses carry their `Problem` as an extension, for
+/// [crate::middlewares::add_request_id] to fill in the request id.
+#[derive(Clone, Debug, Serialize)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    problem_type: &'static str,
+    title: String,
+    status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+    /// stable, to be matched on by clients
+    code: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+}
+
+impl Problem {
+    fn new(status: StatusCode, code: &'static str, detail: Option<String>) -> Self {
+        Self {
+            problem_type: "about:blank",
+            title: status
+                .canonical_reason()
+                .unwrap_or("Unknown error")
+                .to_owned(),
+            status: status.as_u16(),
+            detail,
+            code,
+            request_id: None,
+        }
+    }
+
+    pub fn with_request_id(self, request_id: String) -> Self {
+        Self {
+            request_id: Some(request_id),
+            ..self
+        }
+    }
+}
+
+impl IntoResponse for Problem {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let body = match serde_json::to_vec(&self) {
+            Ok(body) => body,
+            Err(err) => {
+                error!("failed to serialize problem: {}", err);
+                return status.into_response();
             }
         };
+        let mut res = (
+            status,
+            [(
+                CONTENT_TYPE,
+                HeaderValue::from_static("application/problem+json"),
+            )],
+            body,
+        )
+            .into_response();
+        res.extensions_mut().insert(self);
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        http::{header::CONTENT_TYPE, StatusCode},
+        response::IntoResponse,
+    };
+
+    use super::ApiError;
 
-        let body = Json(json!({
-            "error": error_message,
-        }));
+    #[tokio::test]
+    async fn test_problem_response() {
+        let res = ApiError::IndexationInProgress("huggingface/lor-e".to_owned()).into_response();
 
-        (status, body).into_response()
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+        assert_eq!(res.headers()[CONTENT_TYPE], "application/problem+json");
+        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem["code"], "indexation_in_progress");
+        assert_eq!(problem["status"], 409);
+        assert!(problem.get("request_id").is_none());
     }
 }
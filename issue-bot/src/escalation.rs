@@ -0,0 +1,158 @@
+use thiserror::Error;
+use tracing::info;
+
+use crate::{
+    config::EscalationConfig,
+    slack::{Slack, SlackError},
+    storage::{Database, Storage, StorageError},
+    ClosestIssue, IssueData,
+};
+
+#[derive(Debug, Error)]
+pub enum EscalationError {
+    #[error("slack error: {0}")]
+    Slack(#[from] SlackError),
+    #[error("storage error: {0}")]
+    Storage(#[from] StorageError),
+}
+
+/// Alerts when many new issues match the same existing issue in a short time, usually a
+/// regression in a fresh release, see [EscalationConfig]
+#[derive(Clone)]
+pub struct Escalation {
+    cfg: EscalationConfig,
+    db: Database,
+    slack: Slack,
+}
+
+impl Escalation {
+    pub fn new(cfg: EscalationConfig, db: Database, slack: Slack) -> Self {
+        Self { cfg, db, slack }
+    }
+
+    /// records the issues `issue` was matched with, escalating those matched often enough
+    pub async fn track(
+        &self,
+        issue: &IssueData,
+        closest_issues: &[ClosestIssue],
+    ) -> Result<(), EscalationError> {
+        if !self.cfg.enabled {
+            return Ok(());
+        }
+        let targets: Vec<&ClosestIssue> = closest_issues
+            .iter()
+            .filter(|ci| ci.cosine_similarity >= self.cfg.min_similarity)
+            .collect();
+        if targets.is_empty() {
+            return Ok(());
+        }
+        self.db
+            .prune_suggestion_targets(self.cfg.window_minutes)
+            .await?;
+        self.db.record_suggestion_targets(issue, &targets).await?;
+        for target in targets {
+            if self
+                .db
+                .is_issue_number_suppressed(&target.repository_full_name, target.number)
+                .await?
+            {
+                continue;
+            }
+            let cluster = self
+                .db
+                .suggestion_cluster(
+                    &target.repository_full_name,
+                    target.number,
+                    self.cfg.window_minutes,
+                )
+                .await?;
+            if cluster.len() < self.cfg.min_issues {
+                continue;
+            }
+            // another instance, or a previous issue of the same cluster, may have escalated it
+            if !self
+                .db
+                .claim_escalation(
+                    &target.repository_full_name,
+                    target.number,
+                    self.cfg.window_minutes,
+                )
+                .await?
+            {
+                continue;
+            }
+            self.slack.escalation(&self.cfg, target, &cluster).await?;
+            metrics::counter!("issue_bot_escalations_total").increment(1);
+            info!(
+                repository = target.repository_full_name,
+                number = target.number,
+                issues = cluster.len(),
+                "escalated frequently matched issue"
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{storage::Storage, test_harness::test_db, Action, ClosestIssue, IssueData, Source};
+
+    fn issue(number: i32) -> IssueData {
+        IssueData {
+            source_id: number.into(),
+            action: Action::Created,
+            author: None,
+            labels: Vec::new(),
+            milestone: None,
+            title: format!("issue {number}"),
+            body: String::new(),
+            is_pull_request: false,
+            number,
+            html_url: format!("https://github.com/huggingface/lor-e/issues/{number}"),
+            url: format!("https://api.github.com/repos/huggingface/lor-e/issues/{number}"),
+            repository_full_name: "huggingface/lor-e".to_owned(),
+            source: Source::Github,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_suggestion_cluster() {
+        let db = test_db().await;
+        let target = ClosestIssue {
+            title: "Llama fails to load after upgrading".to_owned(),
+            number: 1,
+            html_url: "https://github.com/huggingface/lor-e/issues/1".to_owned(),
+            labels: Vec::new(),
+            repository_full_name: "huggingface/lor-e".to_owned(),
+            cosine_similarity: 0.93,
+            boost: 0.,
+            embedding: Vec::new(),
+        };
+        for number in [2, 3, 3] {
+            db.record_suggestion_targets(&issue(number), &[&target])
+                .await
+                .unwrap();
+        }
+        let cluster = db
+            .suggestion_cluster("huggingface/lor-e", 1, 60)
+            .await
+            .unwrap();
+        // redelivered events are counted once
+        let numbers: Vec<i32> = cluster.iter().map(|i| i.number).collect();
+        assert_eq!(numbers, vec![2, 3]);
+
+        assert!(db
+            .claim_escalation("huggingface/lor-e", 1, 60)
+            .await
+            .unwrap());
+        assert!(!db
+            .claim_escalation("huggingface/lor-e", 1, 60)
+            .await
+            .unwrap());
+        assert!(db
+            .claim_escalation("huggingface/lor-e", 2, 60)
+            .await
+            .unwrap());
+    }
+}
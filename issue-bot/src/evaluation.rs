@@ -0,0 +1,129 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::{
+    embeddings::cosine_similarity,
+    issue_links::IssueLinks,
+    storage::{Database, IssueEmbedding, Storage},
+};
+
+/// Precision and recall of the top `k` suggestions, over the issues with known related issues
+#[derive(Debug, PartialEq, Serialize)]
+pub struct EvaluationReport {
+    pub evaluated_issues: usize,
+    pub hits: usize,
+    pub k: usize,
+    pub precision: f64,
+    pub recall: f64,
+    pub relevant: usize,
+}
+
+/// Replays `issues` in order, suggesting for each the `k` closest of the ones before it
+///
+/// `ground_truth` maps an issue number to the earlier issues it is known to be related to, issues
+/// without any are left out of the report.
+fn evaluate(
+    issues: &[IssueEmbedding],
+    ground_truth: &HashMap<i32, HashSet<i32>>,
+    k: usize,
+) -> EvaluationReport {
+    let (mut evaluated_issues, mut hits, mut relevant) = (0, 0, 0);
+    for (i, issue) in issues.iter().enumerate() {
+        let Some(related) = ground_truth.get(&issue.number).filter(|r| !r.is_empty()) else {
+            continue;
+        };
+        let mut earlier: Vec<(f64, i32)> = issues[..i]
+            .iter()
+            .map(|e| (cosine_similarity(&issue.embedding, &e.embedding), e.number))
+            .collect();
+        earlier.sort_by(|a, b| b.0.total_cmp(&a.0));
+        evaluated_issues += 1;
+        relevant += related.len();
+        hits += earlier
+            .iter()
+            .take(k)
+            .filter(|(_, number)| related.contains(number))
+            .count();
+    }
+    let ratio = |num: usize, den: usize| {
+        if den == 0 {
+            0.
+        } else {
+            num as f64 / den as f64
+        }
+    };
+    EvaluationReport {
+        evaluated_issues,
+        hits,
+        k,
+        precision: ratio(hits, evaluated_issues * k),
+        recall: ratio(hits, relevant),
+        relevant,
+    }
+}
+
+/// Evaluates the suggestions the bot would have made on `repository_full_name`'s stored issues,
+/// using the links ingested from their GitHub timeline as ground truth
+pub async fn run_evaluation(
+    db: &Database,
+    issue_links: &IssueLinks,
+    repository_full_name: &str,
+    k: usize,
+) -> anyhow::Result<EvaluationReport> {
+    let issues = db.repository_issues(repository_full_name).await?;
+    info!(
+        repository = repository_full_name,
+        issues = issues.len(),
+        "ingesting ground truth"
+    );
+    for issue in &issues {
+        if let Err(err) = issue_links.ingest(repository_full_name, issue.number).await {
+            warn!(
+                issue_number = issue.number,
+                err = err.to_string(),
+                "failed to ingest issue timeline, skipping"
+            );
+        }
+    }
+    // issues that aren't stored can't be suggested, counting them would only lower the recall
+    let stored: HashSet<i32> = issues.iter().map(|issue| issue.number).collect();
+    let mut ground_truth = issue_links.ground_truth(repository_full_name).await?;
+    ground_truth.retain(|number, related| {
+        related.retain(|number| stored.contains(number));
+        stored.contains(number)
+    });
+    Ok(evaluate(&issues, &ground_truth, k))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use crate::storage::IssueEmbedding;
+
+    use super::evaluate;
+
+    #[test]
+    fn test_evaluate() {
+        let issue = |number, embedding: &[f32]| IssueEmbedding {
+            number,
+            embedding: embedding.to_vec(),
+        };
+        let issues = vec![
+            issue(1, &[1., 0.]),
+            issue(2, &[0., 1.]),
+            issue(3, &[1., 0.1]),
+            issue(4, &[0.1, 1.]),
+        ];
+        let ground_truth = HashMap::from([(3, HashSet::from([1])), (4, HashSet::from([1, 2]))]);
+
+        let report = evaluate(&issues, &ground_truth, 1);
+        assert_eq!(report.evaluated_issues, 2);
+        assert_eq!(report.hits, 2);
+        assert_eq!(report.relevant, 3);
+        assert_eq!(report.precision, 1.);
+        assert!((report.recall - 2. / 3.).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,309 @@
+use std::{
+    fmt::Display,
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use futures::pin_mut;
+use tokio::{select, time::interval};
+use tracing::{error, info};
+
+use crate::{
+    config::EventLogConfig,
+    debug::DebugState,
+    shutdown_signal,
+    storage::{Database, EventLogEntry, EventLogFilter, EventOutcome, Storage, StorageError},
+    EventData,
+};
+
+/// Timed stages of the handling of a new issue
+#[derive(Clone, Copy, Debug)]
+pub enum LoggedStage {
+    Embed,
+    Search,
+    Summarize,
+    Comment,
+}
+
+fn elapsed_ms(start: Instant) -> i64 {
+    start.elapsed().as_millis() as i64
+}
+
+/// Audit log of the events handled by the webhooks worker, to find out why an issue did not get
+/// a comment
+#[derive(Clone)]
+pub struct EventLog {
+    cfg: EventLogConfig,
+    db: Database,
+    debug_state: DebugState,
+}
+
+impl EventLog {
+    pub fn new(cfg: EventLogConfig, db: Database, debug_state: DebugState) -> Self {
+        Self {
+            cfg,
+            db,
+            debug_state,
+        }
+    }
+
+    async fn save(&self, entry: &EventLogEntry) {
+        if let Err(err) = self.db.insert_event_log_entry(entry).await {
+            self.debug_state.record_error("database", &err);
+            error!(
+                err = err.to_string(),
+                event = entry.description,
+                "failed to save event log entry"
+            );
+        }
+    }
+
+    /// starts recording the handling of `event`, queued by request `request_id`
+    pub fn start(&self, event: &EventData, request_id: String) -> EventRecord {
+        let (source_id, repository_full_name, issue_number) = match event {
+            EventData::Issue(issue) => (
+                Some(issue.source_id),
+                Some(issue.repository_full_name.clone()),
+                Some(issue.number),
+            ),
+            EventData::IssueMetadata(metadata) => (Some(metadata.source_id), None, None),
+            EventData::IssueStateChange(change) => (Some(change.source_id), None, None),
+            EventData::Comment(comment) => (Some(comment.source_id), None, None),
+            EventData::ReviewComment(comment) => (
+                Some(comment.source_id),
+                Some(comment.repository_full_name.clone()),
+                Some(comment.number),
+            ),
+            EventData::IssueIndexation(data) => (
+                None,
+                Some(data.repository_full_name.clone()),
+                Some(data.issue_number),
+            ),
+            EventData::RepositoryIndexation(data) => (None, Some(data.full_name.clone()), None),
+            EventData::RepositoryUpdate(update) => (None, Some(update.full_name.clone()), None),
+            EventData::DuplicateClosure(closure) => (
+                Some(closure.source_id),
+                Some(closure.repository_full_name.clone()),
+                Some(closure.number),
+            ),
+            EventData::IssueSuppression(suppression) => (Some(suppression.source_id), None, None),
+            EventData::RegenerateEmbeddings
+            | EventData::Feedback(_)
+            | EventData::AuthorOptOut(_)
+            | EventData::IssueEmbeddings(_) => (None, None, None),
+        };
+        EventRecord {
+            entry: Some(EventLogEntry {
+                event_type: event.kind().to_owned(),
+                description: event.to_string(),
+                source_id,
+                repository_full_name,
+                issue_number,
+                request_id,
+                outcome: EventOutcome::Handled,
+                run_id: None,
+                error: None,
+                embed_ms: None,
+                search_ms: None,
+                summarize_ms: None,
+                comment_ms: None,
+                total_ms: 0,
+                created_at: Utc::now(),
+            }),
+            log: self.clone(),
+            started_at: Instant::now(),
+        }
+    }
+
+    pub async fn entries(
+        &self,
+        filter: &EventLogFilter,
+        limit: i64,
+    ) -> Result<Vec<EventLogEntry>, StorageError> {
+        self.db.event_log(filter, limit).await
+    }
+}
+
+/// Handling of a single event, saved to the event log by [EventRecord::finish]
+///
+/// Records dropped before being finished, e.g. by a panic, are saved in the background as errors.
+pub struct EventRecord {
+    entry: Option<EventLogEntry>,
+    log: EventLog,
+    started_at: Instant,
+}
+
+impl EventRecord {
+    fn entry(&mut self) -> &mut EventLogEntry {
+        self.entry.as_mut().expect("entry taken when finishing")
+    }
+
+    /// awaits `fut`, recording how long it took as the duration of `stage`
+    pub async fn time<F: Future>(&mut self, stage: LoggedStage, fut: F) -> F::Output {
+        let start = Instant::now();
+        let output = fut.await;
+        let duration = Some(elapsed_ms(start));
+        let entry = self.entry();
+        match stage {
+            LoggedStage::Embed => entry.embed_ms = duration,
+            LoggedStage::Search => entry.search_ms = duration,
+            LoggedStage::Summarize => entry.summarize_ms = duration,
+            LoggedStage::Comment => entry.comment_ms = duration,
+        }
+        output
+    }
+
+    /// the comment of run `run_id` was queued, the comment queue marks the event as commented
+    /// once it is posted
+    pub fn queued(&mut self, run_id: &str) {
+        let entry = self.entry();
+        entry.outcome = EventOutcome::Queued;
+        entry.run_id = Some(run_id.to_owned());
+    }
+
+    pub fn skipped(&mut self) {
+        self.entry().outcome = EventOutcome::Skipped;
+    }
+
+    pub fn error(&mut self, err: impl Display) {
+        let entry = self.entry();
+        entry.outcome = EventOutcome::Error;
+        entry.error = Some(err.to_string());
+    }
+
+    /// saves the entry, `handled` unless another outcome was recorded
+    pub async fn finish(mut self) {
+        if let Some(mut entry) = self.entry.take() {
+            entry.total_ms = elapsed_ms(self.started_at);
+            self.log.save(&entry).await;
+        }
+    }
+}
+
+impl Drop for EventRecord {
+    fn drop(&mut self) {
+        let Some(mut entry) = self.entry.take() else {
+            return;
+        };
+        entry.total_ms = elapsed_ms(self.started_at);
+        if entry.outcome != EventOutcome::Error {
+            entry.outcome = EventOutcome::Error;
+            entry.error = Some("handling stopped before finishing".to_owned());
+        }
+        let log = self.log.clone();
+        tokio::spawn(async move { log.save(&entry).await });
+    }
+}
+
+/// Deletes the entries past their retention every day
+pub async fn start_event_log_pruner(event_log: EventLog) -> anyhow::Result<()> {
+    let mut ticker = interval(Duration::from_secs(24 * 60 * 60));
+    let shutdown = shutdown_signal();
+    pin_mut!(shutdown);
+    loop {
+        select! {
+            _ = ticker.tick() => {
+                match event_log.db.prune_event_log(event_log.cfg.retention_days).await {
+                    Ok(0) => (),
+                    Ok(deleted) => info!(deleted, "pruned event log"),
+                    Err(err) => {
+                        event_log.debug_state.record_error("database", &err);
+                        error!(err = err.to_string(), "failed to prune event log");
+                    }
+                }
+            }
+            _ = &mut shutdown => break,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use nanoid::nanoid;
+
+    use crate::{
+        config::EventLogConfig,
+        debug::DebugState,
+        storage::{EventLogFilter, EventOutcome},
+        test_harness::test_database,
+        EventData, IndexIssueData,
+    };
+
+    use super::{EventLog, LoggedStage};
+
+    #[tokio::test]
+    async fn test_event_log() {
+        let log = EventLog::new(
+            EventLogConfig::default(),
+            test_database().await,
+            DebugState::default(),
+        );
+        // unique, as the database may be shared between test runs
+        let request_id = nanoid!();
+        let repository_full_name = format!("huggingface/event-log-{request_id}");
+        let event = EventData::IssueIndexation(IndexIssueData {
+            issue_number: 42,
+            repository_full_name: repository_full_name.clone(),
+            head_sha: None,
+        });
+
+        let mut record = log.start(&event, request_id.clone());
+        record
+            .time(
+                LoggedStage::Embed,
+                tokio::time::sleep(Duration::from_millis(5)),
+            )
+            .await;
+        record.error("embedding api is down");
+        record.finish().await;
+        log.start(&event, nanoid!()).finish().await;
+
+        let filter = EventLogFilter {
+            repository_full_name: Some(repository_full_name),
+            issue_number: Some(42),
+            ..Default::default()
+        };
+        let entries = log.entries(&filter, 10).await.unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries.iter().any(|e| e.outcome == EventOutcome::Handled));
+
+        // records dropped early aren't taken as handled
+        let interrupted_id = nanoid!();
+        drop(log.start(&event, interrupted_id.clone()));
+        let filter = EventLogFilter {
+            request_id: Some(interrupted_id),
+            ..Default::default()
+        };
+        let mut interrupted = Vec::new();
+        for _ in 0..50 {
+            interrupted = log.entries(&filter, 10).await.unwrap();
+            if !interrupted.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(interrupted.len(), 1);
+        assert_eq!(interrupted[0].outcome, EventOutcome::Error);
+
+        let failed = log
+            .entries(
+                &EventLogFilter {
+                    request_id: Some(request_id),
+                    ..Default::default()
+                },
+                10,
+            )
+            .await
+            .unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].event_type, "issue_indexation");
+        assert_eq!(failed[0].outcome, EventOutcome::Error);
+        assert_eq!(failed[0].error.as_deref(), Some("embedding api is down"));
+        assert!(failed[0].embed_ms.is_some_and(|ms| ms >= 5));
+        assert!(failed[0].search_ms.is_none());
+    }
+}
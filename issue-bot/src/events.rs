@@ -0,0 +1,125 @@
+use std::convert::Infallible;
+
+use async_stream::stream;
+use axum::response::sse::Event;
+use chrono::{DateTime, Utc};
+use futures::{pin_mut, Stream};
+use serde::Serialize;
+use tokio::{select, sync::broadcast};
+use tracing::warn;
+
+use crate::{shutdown_signal, IssueData};
+
+/// events not yet sent to a slow subscriber beyond this are dropped for it
+const CAPACITY: usize = 1_024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    Received,
+    Embedded,
+    Matched,
+    Commented,
+    Failed,
+}
+
+/// Progress of an issue through the webhook pipeline
+#[derive(Clone, Debug, Serialize)]
+pub struct PipelineEvent {
+    pub at: DateTime<Utc>,
+    /// e.g. the number of matches, or the error of a failed stage
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub issue_number: i32,
+    pub repository: String,
+    pub stage: Stage,
+}
+
+/// Broadcasts pipeline events to the `/events/stream` subscribers, events are dropped when there
+/// are none
+#[derive(Clone)]
+pub struct PipelineEvents {
+    tx: broadcast::Sender<PipelineEvent>,
+}
+
+impl Default for PipelineEvents {
+    fn default() -> Self {
+        Self {
+            tx: broadcast::channel(CAPACITY).0,
+        }
+    }
+}
+
+impl PipelineEvents {
+    pub fn emit(&self, issue: &IssueData, stage: Stage, detail: Option<String>) {
+        let _ = self.tx.send(PipelineEvent {
+            at: Utc::now(),
+            detail,
+            issue_number: issue.number,
+            repository: issue.repository_full_name.clone(),
+            stage,
+        });
+    }
+
+    /// SSE stream of the events emitted from now on, ending on shutdown
+    pub fn subscribe(&self) -> impl Stream<Item = Result<Event, Infallible>> {
+        let mut rx = self.tx.subscribe();
+        stream! {
+            let shutdown = shutdown_signal();
+            pin_mut!(shutdown);
+            loop {
+                let event = select! {
+                    event = rx.recv() => event,
+                    _ = &mut shutdown => break,
+                };
+                match event {
+                    Ok(event) => match Event::default().json_data(&event) {
+                        Ok(sse_event) => yield Ok(sse_event),
+                        Err(err) => warn!(err = err.to_string(), "failed to serialize pipeline event"),
+                    },
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!(skipped, "events stream subscriber lagging, dropped events");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+
+    use crate::{Action, IssueData, Source};
+
+    use super::{PipelineEvents, Stage};
+
+    #[tokio::test]
+    async fn test_subscribe() {
+        let events = PipelineEvents::default();
+        let issue = IssueData {
+            source_id: 1,
+            action: Action::Created,
+            author: None,
+            labels: Vec::new(),
+            milestone: None,
+            title: "title".to_owned(),
+            body: "body".to_owned(),
+            is_pull_request: false,
+            number: 42,
+            html_url: "https://github.com/huggingface/lor-e/issues/42".to_owned(),
+            url: "https://api.github.com/repos/huggingface/lor-e/issues/42".to_owned(),
+            repository_full_name: "huggingface/lor-e".to_owned(),
+            source: Source::Github,
+        };
+
+        // events emitted before subscribing aren't replayed
+        events.emit(&issue, Stage::Received, None);
+        let stream = events.subscribe();
+        futures::pin_mut!(stream);
+        events.emit(&issue, Stage::Matched, Some("2 matches".to_owned()));
+
+        assert!(stream.next().await.unwrap().is_ok());
+    }
+}
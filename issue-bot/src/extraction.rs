@@ -0,0 +1,556 @@
+use std::collections::HashMap;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+
+use crate::{
+    config::ExtractionConfig,
+    debug::DebugState,
+    issue_forms::IssueForm,
+    storage::{Database, IssueCohort, Storage, StorageError},
+    summarization::SummarizationApi,
+    ClosestIssue, IssueData,
+};
+
+/// enough for the JSON object of [SystemInfo]
+const MAX_EXTRACTION_TOKENS: u32 = 150;
+
+static SECTION_HEADING: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)^\s*(?:#+\s*|\*\*)(?:system info|environment|system information)")
+        .expect("valid section heading regex")
+});
+static PYTHON: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?im)^\W*python(?:\s+version)?\W*\s*[:=]?\s*v?(?P<version>\d+\.\d+(?:\.\d+)?)")
+        .expect("valid python version regex")
+});
+static TORCH: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?im)^\W*(?:pytorch|torch)(?:\s+version)?(?:\s*\([^)]*\))?\W*\s*(?::|==)\s*v?(?P<version>\d+\.\d+(?:\.\d+)?(?:\+[\w.]+)?)",
+    )
+    .expect("valid torch version regex")
+});
+static PLATFORM: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?im)^\W*(?:platform|os|operating system)(?:\s+version)?\W*\s*:\s*(?P<os>.+)$")
+        .expect("valid platform regex")
+});
+static GPU: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?im)^\W*gpu(?:\s+(?:type|model|name)|\(s\))?\W*\s*:\s*(?P<gpu>.+)$")
+        .expect("valid gpu regex")
+});
+
+/// Environment an issue was reported in, `None` fields being unknown
+///
+/// `os` is one of `linux`, `windows` or `macos` and `accelerator` one of `cuda`, `rocm`, `mps`,
+/// `xpu` or `cpu`, see [normalize_os] and [normalize_accelerator].
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct SystemInfo {
+    pub package_version: Option<String>,
+    pub python_version: Option<String>,
+    pub torch_version: Option<String>,
+    pub os: Option<String>,
+    pub accelerator: Option<String>,
+    pub gpu: Option<String>,
+}
+
+fn normalize_os(os: &str) -> Option<String> {
+    let os = os.to_lowercase();
+    let normalized = if [
+        "linux", "ubuntu", "debian", "centos", "fedora", "rhel", "wsl",
+    ]
+    .iter()
+    .any(|k| os.contains(k))
+    {
+        "linux"
+    } else if os.contains("windows") || os.starts_with("win") {
+        "windows"
+    } else if ["macos", "mac os", "darwin", "osx"]
+        .iter()
+        .any(|k| os.contains(k))
+    {
+        "macos"
+    } else {
+        return None;
+    };
+    Some(normalized.to_owned())
+}
+
+/// accelerator named in `text`, e.g. a PyTorch build suffix or a GPU model
+fn normalize_accelerator(text: &str) -> Option<String> {
+    let text = text.to_lowercase();
+    let accelerator = if ["rocm", "amd", "radeon", "instinct", "mi250", "mi300"]
+        .iter()
+        .any(|k| text.contains(k))
+    {
+        "rocm"
+    } else if [
+        "cuda", "+cu", "nvidia", "geforce", "rtx", "tesla", "a100", "h100",
+    ]
+    .iter()
+    .any(|k| text.contains(k))
+    {
+        "cuda"
+    } else if ["mps", "apple", "metal"].iter().any(|k| text.contains(k)) {
+        "mps"
+    } else if ["xpu", "intel arc", "gaudi"]
+        .iter()
+        .any(|k| text.contains(k))
+    {
+        "xpu"
+    } else if text.contains("cpu") {
+        "cpu"
+    } else {
+        return None;
+    };
+    Some(accelerator.to_owned())
+}
+
+/// system info section of `body`, the whole body when there's none
+fn system_info_section(body: &str) -> String {
+    let mut section = Vec::new();
+    let mut in_section = false;
+    for line in body.lines() {
+        if SECTION_HEADING.is_match(line) {
+            in_section = true;
+            continue;
+        }
+        if in_section && line.trim_start().starts_with('#') {
+            break;
+        }
+        if in_section {
+            section.push(line);
+        }
+    }
+    if section.is_empty() {
+        body.to_owned()
+    } else {
+        section.join("\n")
+    }
+}
+
+/// value of a `key: value` line without markdown or placeholders like `N/A`
+fn clean_value(value: &str) -> Option<String> {
+    let value = value.trim().trim_matches(|c| c == '`' || c == '*').trim();
+    let lowered = value.to_lowercase();
+    if value.is_empty() || ["n/a", "na", "no", "none", "-", "yes"].contains(&lowered.as_str()) {
+        return None;
+    }
+    Some(value.to_owned())
+}
+
+impl SystemInfo {
+    /// system info reported in `body`, `package` being the library the issue is about
+    pub fn parse(body: &str, package: &str) -> Self {
+        let section = system_info_section(body);
+        let package_version = Regex::new(&format!(
+            r"(?im)^\W*{}\W*(?:\s+version)?\s*(?::|==)\s*v?(?P<version>\d[\w.+-]*)",
+            regex::escape(package)
+        ))
+        .ok()
+        .and_then(|regex| regex.captures(&section))
+        .map(|c| c["version"].to_owned());
+        let capture = |regex: &Regex, name: &str| {
+            regex.captures(&section).and_then(|c| clean_value(&c[name]))
+        };
+        let torch_version = capture(&TORCH, "version");
+        let gpu = capture(&GPU, "gpu");
+        let accelerator = torch_version
+            .as_deref()
+            .and_then(|version| version.split_once('+'))
+            .and_then(|(_, build)| normalize_accelerator(&format!("+{build}")))
+            .or_else(|| gpu.as_deref().and_then(normalize_accelerator))
+            .or_else(|| {
+                section
+                    .lines()
+                    .filter(|line| {
+                        let line = line.to_lowercase();
+                        line.contains("rocm") || line.contains("cuda") || line.contains("mps")
+                    })
+                    .find_map(normalize_accelerator)
+            });
+        Self {
+            package_version,
+            python_version: capture(&PYTHON, "version"),
+            torch_version,
+            os: capture(&PLATFORM, "os").as_deref().and_then(normalize_os),
+            accelerator,
+            gpu,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// e.g. `transformers 4.45.0 · Python 3.11 · PyTorch 2.4.0+rocm6.1 · linux · rocm (MI250X)`
+    pub fn summary(&self, package: &str) -> Option<String> {
+        let accelerator = match (&self.accelerator, &self.gpu) {
+            (Some(accelerator), Some(gpu)) => Some(format!("{accelerator} ({gpu})")),
+            (Some(accelerator), None) => Some(accelerator.clone()),
+            (None, gpu) => gpu.clone(),
+        };
+        let parts: Vec<String> = [
+            self.package_version
+                .as_ref()
+                .map(|v| format!("{package} {v}")),
+            self.python_version.as_ref().map(|v| format!("Python {v}")),
+            self.torch_version.as_ref().map(|v| format!("PyTorch {v}")),
+            self.os.clone(),
+            accelerator,
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        (!parts.is_empty()).then(|| parts.join(" · "))
+    }
+
+    /// `os` and `accelerator` normalized, e.g. after being extracted by a model
+    fn normalized(mut self) -> Self {
+        self.os = self.os.as_deref().and_then(normalize_os);
+        self.accelerator = self.accelerator.as_deref().and_then(normalize_accelerator);
+        self
+    }
+}
+
+/// library the issues of a repository are about, e.g. `transformers` for
+/// `huggingface/transformers`
+pub fn package_name(repository_full_name: &str) -> &str {
+    repository_full_name
+        .rsplit('/')
+        .next()
+        .unwrap_or(repository_full_name)
+}
+
+/// `major.minor` of `version`, e.g. `4.45` for `4.45.0.dev0`
+fn minor_version(version: &str) -> &str {
+    match version.match_indices('.').nth(1) {
+        Some((i, _)) => &version[..i],
+        None => version,
+    }
+}
+
+/// boosts candidates sharing the accelerator of `info`, or the minor version of its package when
+/// from the same repository, `cohorts` being the candidates' environments by repository
+fn boost_shared_cohort(
+    info: &SystemInfo,
+    repository_full_name: &str,
+    cohorts: &HashMap<String, Vec<IssueCohort>>,
+    mut candidates: Vec<ClosestIssue>,
+    accelerator_boost: f64,
+    version_boost: f64,
+) -> Vec<ClosestIssue> {
+    let version = info.package_version.as_deref().map(minor_version);
+    for candidate in &mut candidates {
+        let Some(cohort) = cohorts
+            .get(&candidate.repository_full_name)
+            .and_then(|cohorts| cohorts.iter().find(|c| c.number == candidate.number))
+        else {
+            continue;
+        };
+        if info.accelerator.is_some() && cohort.accelerator == info.accelerator {
+            candidate.boost += accelerator_boost;
+        }
+        if candidate.repository_full_name == repository_full_name
+            && version.is_some()
+            && cohort.package_version.as_deref().map(minor_version) == version
+        {
+            candidate.boost += version_boost;
+        }
+    }
+    candidates.sort_by(|a, b| b.rank_score().total_cmp(&a.rank_score()));
+    candidates
+}
+
+/// Stores the environment of issues in typed columns, used to filter searches, boost candidates
+/// and shown in Slack notifications, along with the fields of issue forms, see [IssueForm]
+#[derive(Clone)]
+pub struct Extractor {
+    cfg: ExtractionConfig,
+    db: Database,
+    debug_state: DebugState,
+}
+
+impl Extractor {
+    pub fn new(cfg: ExtractionConfig, db: Database, debug_state: DebugState) -> Self {
+        Self {
+            cfg,
+            db,
+            debug_state,
+        }
+    }
+
+    /// boosts candidates from the same cohort as an issue of the repository reported with `info`,
+    /// returning them untouched when the boosts are disabled or the environments unavailable
+    pub async fn boost_cohort(
+        &self,
+        repository_full_name: &str,
+        info: &SystemInfo,
+        candidates: Vec<ClosestIssue>,
+    ) -> Vec<ClosestIssue> {
+        if (self.cfg.accelerator_boost == 0. && self.cfg.version_boost == 0.)
+            || info.is_empty()
+            || candidates.is_empty()
+        {
+            return candidates;
+        }
+        let mut numbers: HashMap<String, Vec<i32>> = HashMap::new();
+        for candidate in &candidates {
+            numbers
+                .entry(candidate.repository_full_name.clone())
+                .or_default()
+                .push(candidate.number);
+        }
+        let mut cohorts = HashMap::new();
+        for (repository, numbers) in numbers {
+            match self.db.issue_cohorts(&repository, &numbers).await {
+                Ok(repository_cohorts) => {
+                    cohorts.insert(repository, repository_cohorts);
+                }
+                Err(err) => {
+                    self.debug_state.record_error("database", &err);
+                    error!(
+                        repository,
+                        err = err.to_string(),
+                        "failed to fetch issue environments"
+                    );
+                    return candidates;
+                }
+            }
+        }
+        boost_shared_cohort(
+            info,
+            repository_full_name,
+            &cohorts,
+            candidates,
+            self.cfg.accelerator_boost,
+            self.cfg.version_boost,
+        )
+    }
+
+    /// system info the regexes find in `body`, e.g. of a pull request
+    pub fn parse(&self, repository_full_name: &str, body: &str) -> SystemInfo {
+        if !self.cfg.enabled {
+            return SystemInfo::default();
+        }
+        SystemInfo::parse(body, package_name(repository_full_name))
+    }
+
+    /// system info of `issue`, asking `summarization_api` when the regexes get nothing and
+    /// `llm_fallback` is set
+    pub async fn extract(
+        &self,
+        issue: &IssueData,
+        summarization_api: Option<&SummarizationApi>,
+    ) -> SystemInfo {
+        if !self.cfg.enabled {
+            return SystemInfo::default();
+        }
+        let mut info = SystemInfo::parse(&issue.body, package_name(&issue.repository_full_name));
+        let mut method = "regex";
+        if info.is_empty() {
+            method = "none";
+            if let Some(summarization_api) = summarization_api.filter(|_| self.cfg.llm_fallback) {
+                info = self.extract_with_llm(issue, summarization_api).await;
+                if !info.is_empty() {
+                    method = "llm";
+                }
+            }
+        }
+        metrics::counter!("issue_bot_system_info_extractions_total", "method" => method)
+            .increment(1);
+        info
+    }
+
+    async fn extract_with_llm(
+        &self,
+        issue: &IssueData,
+        summarization_api: &SummarizationApi,
+    ) -> SystemInfo {
+        let res = summarization_api
+            .complete(
+                &self.cfg.llm_prompt,
+                format!("{}\n{}", issue.title, issue.body),
+                MAX_EXTRACTION_TOKENS,
+            )
+            .await;
+        let completion = match res {
+            Ok(completion) => completion,
+            Err(err) => {
+                warn!(
+                    issue_id = issue.source_id,
+                    err = err.to_string(),
+                    "failed to extract system info"
+                );
+                return SystemInfo::default();
+            }
+        };
+        let json = match (completion.find('{'), completion.rfind('}')) {
+            (Some(start), Some(end)) if start < end => &completion[start..=end],
+            _ => "",
+        };
+        match serde_json::from_str::<SystemInfo>(json) {
+            Ok(info) => info.normalized(),
+            Err(err) => {
+                warn!(
+                    issue_id = issue.source_id,
+                    err = err.to_string(),
+                    "invalid system info extracted"
+                );
+                SystemInfo::default()
+            }
+        }
+    }
+
+    /// stores `info` and the issue form fields of `body` for a stored issue, the latter even when
+    /// extraction is disabled
+    pub async fn store(
+        &self,
+        source_id: i64,
+        body: &str,
+        info: &SystemInfo,
+    ) -> Result<(), StorageError> {
+        self.store_form(source_id, body).await?;
+        if !self.cfg.enabled {
+            return Ok(());
+        }
+        self.db.set_system_info(source_id, info).await
+    }
+
+    /// Stores the system info the regexes get from a stored issue's current `body`
+    ///
+    /// Only the fields they find are overwritten, those the LLM completed when the issue was
+    /// opened are kept otherwise.
+    pub async fn update(
+        &self,
+        source_id: i64,
+        repository_full_name: &str,
+        body: &str,
+    ) -> Result<(), StorageError> {
+        self.store_form(source_id, body).await?;
+        if !self.cfg.enabled {
+            return Ok(());
+        }
+        let info = SystemInfo::parse(body, package_name(repository_full_name));
+        self.db.merge_system_info(source_id, &info).await
+    }
+
+    /// issue forms are stored whatever [ExtractionConfig::enabled], which only covers system info,
+    /// see [crate::issue_forms]
+    async fn store_form(&self, source_id: i64, body: &str) -> Result<(), StorageError> {
+        let form = IssueForm::parse(body).unwrap_or_default();
+        self.db.set_issue_form(source_id, &form).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{storage::IssueCohort, ClosestIssue};
+
+    use super::{boost_shared_cohort, SystemInfo};
+
+    #[test]
+    fn test_parse() {
+        let body = r#"### System Info
+
+- `transformers` version: 4.45.0.dev0
+- Platform: Linux-5.15.0-1048-aws-x86_64-with-glibc2.31
+- Python version: 3.10.12
+- PyTorch version (GPU?): 2.4.0+rocm6.1 (True)
+- Using GPU in script?: yes
+- GPU type: AMD Instinct MI250X
+
+### Who can help?
+
+@ArthurZucker
+"#;
+        let info = SystemInfo::parse(body, "transformers");
+        assert_eq!(
+            info,
+            SystemInfo {
+                package_version: Some("4.45.0.dev0".to_owned()),
+                python_version: Some("3.10.12".to_owned()),
+                torch_version: Some("2.4.0+rocm6.1".to_owned()),
+                os: Some("linux".to_owned()),
+                accelerator: Some("rocm".to_owned()),
+                gpu: Some("AMD Instinct MI250X".to_owned()),
+            }
+        );
+        assert_eq!(
+            info.summary("transformers").as_deref(),
+            Some(
+                "transformers 4.45.0.dev0 · Python 3.10.12 · PyTorch 2.4.0+rocm6.1 · linux · rocm (AMD Instinct MI250X)"
+            )
+        );
+
+        let info = SystemInfo::parse(
+            "Crashes on my Mac.\n\n**Environment**\nOS: macOS 14.5\ntorch==2.3.1\nDevice: mps",
+            "diffusers",
+        );
+        assert_eq!(info.os.as_deref(), Some("macos"));
+        assert_eq!(info.torch_version.as_deref(), Some("2.3.1"));
+        assert_eq!(info.accelerator.as_deref(), Some("mps"));
+        assert!(info.package_version.is_none());
+
+        assert!(SystemInfo::parse("The tokenizer panics.", "tokenizers").is_empty());
+    }
+
+    #[test]
+    fn test_boost_shared_cohort() {
+        let candidate =
+            |repository_full_name: &str, number: i32, cosine_similarity: f64| ClosestIssue {
+                title: format!("issue {number}"),
+                number,
+                html_url: format!("https://github.com/{repository_full_name}/issues/{number}"),
+                labels: Vec::new(),
+                repository_full_name: repository_full_name.to_owned(),
+                cosine_similarity,
+                boost: 0.,
+                embedding: Vec::new(),
+            };
+        let cohort = |number, package_version: &str, accelerator: &str| IssueCohort {
+            number,
+            package_version: Some(package_version.to_owned()),
+            accelerator: Some(accelerator.to_owned()),
+        };
+        let cohorts = HashMap::from([
+            (
+                "huggingface/transformers".to_owned(),
+                vec![
+                    cohort(1, "4.44.2", "cuda"),
+                    cohort(2, "4.45.1", "cuda"),
+                    cohort(3, "4.45.0", "rocm"),
+                ],
+            ),
+            (
+                "huggingface/accelerate".to_owned(),
+                vec![cohort(4, "4.45.0", "cuda")],
+            ),
+        ]);
+        let info = SystemInfo {
+            package_version: Some("4.45.0.dev0".to_owned()),
+            accelerator: Some("rocm".to_owned()),
+            ..Default::default()
+        };
+        let boosted = boost_shared_cohort(
+            &info,
+            "huggingface/transformers",
+            &cohorts,
+            vec![
+                candidate("huggingface/transformers", 1, 0.9),
+                candidate("huggingface/transformers", 2, 0.85),
+                candidate("huggingface/accelerate", 4, 0.84),
+                candidate("huggingface/transformers", 3, 0.8),
+            ],
+            0.08,
+            0.04,
+        );
+        let numbers: Vec<i32> = boosted.iter().map(|c| c.number).collect();
+        // 3 shares both, 2 the version, 4 nothing as versions of other packages don't compare
+        assert_eq!(numbers, [3, 1, 2, 4]);
+        assert!((boosted[0].rank_score() - 0.92).abs() < 1e-9);
+        assert_eq!(boosted[0].cosine_similarity, 0.8);
+    }
+}
@@ -0,0 +1,162 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tracing::{info, warn};
+
+use crate::config::FailoverConfig;
+
+#[derive(Debug)]
+struct FailoverState {
+    /// index of the endpoint requests are sent to
+    active: usize,
+    consecutive_failures: u32,
+    /// when the primary endpoint was last left or probed
+    last_primary_attempt: Instant,
+}
+
+/// Prioritized endpoints of an API, shared by the clones of its client
+///
+/// Requests go to the first healthy endpoint, see [FailoverConfig].
+#[derive(Clone, Debug)]
+pub struct Endpoints {
+    api: &'static str,
+    cfg: FailoverConfig,
+    state: Arc<Mutex<FailoverState>>,
+    urls: Arc<[String]>,
+}
+
+impl Endpoints {
+    pub fn new(
+        api: &'static str,
+        cfg: FailoverConfig,
+        url: String,
+        fallbacks: Vec<String>,
+    ) -> Self {
+        Self {
+            api,
+            cfg,
+            state: Arc::new(Mutex::new(FailoverState {
+                active: 0,
+                consecutive_failures: 0,
+                last_primary_attempt: Instant::now(),
+            })),
+            urls: std::iter::once(url).chain(fallbacks).collect(),
+        }
+    }
+
+    pub fn primary(&self) -> &str {
+        &self.urls[0]
+    }
+
+    /// endpoint requests go to, the primary unless failed over, primary probes aside
+    pub fn active(&self) -> &str {
+        &self.urls[self.state.lock().unwrap().active]
+    }
+
+    /// endpoint the next request should be sent to, with its index to report its outcome
+    ///
+    /// Once failed over, the primary is periodically probed again.
+    pub fn select(&self) -> (usize, &str) {
+        let mut state = self.state.lock().unwrap();
+        let recovery_check = Duration::from_secs(self.cfg.recovery_check_secs);
+        if state.active > 0 && state.last_primary_attempt.elapsed() >= recovery_check {
+            state.last_primary_attempt = Instant::now();
+            return (0, &self.urls[0]);
+        }
+        (state.active, &self.urls[state.active])
+    }
+
+    pub fn succeeded(&self, index: usize) {
+        metrics::counter!(
+            "issue_bot_endpoint_requests_total",
+            "api" => self.api,
+            "endpoint" => self.urls[index].clone(),
+        )
+        .increment(1);
+        let mut state = self.state.lock().unwrap();
+        if index < state.active {
+            info!(api = self.api, endpoint = self.urls[index], "failing back");
+            state.active = index;
+        }
+        if index == state.active {
+            state.consecutive_failures = 0;
+        }
+    }
+
+    /// returns whether another endpoint should be tried
+    pub fn failed(&self, index: usize) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if index != state.active {
+            // failed primary probe, the fallback stays active and is retried as usual
+            return false;
+        }
+        state.consecutive_failures += 1;
+        if state.consecutive_failures < self.cfg.failure_threshold {
+            return false;
+        }
+        self.fail_over(&mut state)
+    }
+
+    /// fails over right away, e.g. when the endpoint's circuit breaker opened, returning whether
+    /// another endpoint should be tried
+    pub fn unavailable(&self, index: usize) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if index != state.active {
+            return false;
+        }
+        self.fail_over(&mut state)
+    }
+
+    fn fail_over(&self, state: &mut FailoverState) -> bool {
+        if state.active + 1 >= self.urls.len() {
+            return false;
+        }
+        if state.active == 0 {
+            state.last_primary_attempt = Instant::now();
+        }
+        state.active += 1;
+        state.consecutive_failures = 0;
+        warn!(
+            api = self.api,
+            from = self.urls[state.active - 1],
+            to = self.urls[state.active],
+            "failing over"
+        );
+        metrics::counter!("issue_bot_endpoint_failovers_total", "api" => self.api).increment(1);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::FailoverConfig;
+
+    use super::Endpoints;
+
+    #[test]
+    fn test_failover() {
+        let endpoints = Endpoints::new(
+            "embedding_api",
+            FailoverConfig {
+                failure_threshold: 2,
+                recovery_check_secs: 0,
+            },
+            "https://us-east-1".to_owned(),
+            vec!["https://eu-west-1".to_owned()],
+        );
+        assert_eq!(endpoints.select(), (0, "https://us-east-1"));
+        assert!(!endpoints.failed(0));
+        assert!(endpoints.failed(0));
+        // the primary is probed again as soon as the recovery check is due
+        assert_eq!(endpoints.select(), (0, "https://us-east-1"));
+        assert!(!endpoints.failed(0));
+        assert_eq!(endpoints.state.lock().unwrap().active, 1);
+        endpoints.succeeded(1);
+        // no endpoint left to fail over to
+        assert!(!endpoints.unavailable(1));
+        endpoints.succeeded(0);
+        assert_eq!(endpoints.state.lock().unwrap().active, 0);
+    }
+}
@@ -0,0 +1,293 @@
+use futures::pin_mut;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use tokio::select;
+use tracing::{error, info};
+
+use crate::{
+    config::FingerprintConfig,
+    locks::Locks,
+    shutdown_signal,
+    storage::{Database, Storage, StorageError},
+    ClosestIssue, IssueData,
+};
+
+const BACKFILL_BATCH_SIZE: i64 = 500;
+const LOCK_NAME: &str = "fingerprint_backfill";
+
+static FRAME: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"^\s*File "(?P<path>[^"]+)", line \d+, in (?P<function>\S+)"#)
+        .expect("valid traceback frame regex")
+});
+static EXCEPTION: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?P<kind>[A-Za-z_][\w.]*(?:Error|Exception|Interrupt|Exit)):?\s*(?P<message>.*)$")
+        .expect("valid exception regex")
+});
+static ADDRESS: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"0x[0-9a-fA-F]+").expect("valid address regex"));
+static PATH: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?:[\w.~:-]*/)+(?P<file>[\w.-]+)").expect("valid path regex"));
+static NUMBER: Lazy<Regex> = Lazy::new(|| Regex::new(r"\d+").expect("valid number regex"));
+
+/// directories installed packages live in, whatever the environment
+const PACKAGE_DIRS: &[&str] = &["site-packages/", "dist-packages/"];
+
+/// path of a frame relative to its package directory, `None` for frames outside of packages
+fn package_path(path: &str) -> Option<&str> {
+    PACKAGE_DIRS
+        .iter()
+        .find_map(|dir| path.rsplit_once(dir).map(|(_, path)| path))
+}
+
+/// exception message without what differs between reports of the same error: addresses, paths
+/// and numbers
+fn normalize_message(message: &str) -> String {
+    let message = message.trim().replace('\\', "/");
+    let message = ADDRESS.replace_all(&message, "0x");
+    let message = PATH.replace_all(&message, "$file");
+    NUMBER.replace_all(&message, "N").into_owned()
+}
+
+/// last Python traceback of `text`, normalized so that reports of the same error match
+///
+/// Only frames of installed packages are kept, the scripts calling into them differing between
+/// reporters, and line numbers are dropped. Tracebacks with fewer than `min_frames` frames are
+/// ignored.
+fn normalized_traceback(text: &str, min_frames: usize) -> Option<String> {
+    let mut traceback = None;
+    let mut frames: Vec<(String, String)> = Vec::new();
+    let mut in_traceback = false;
+    for line in text.lines() {
+        let line = line.trim_start_matches('>').trim_end();
+        if line
+            .trim_start()
+            .starts_with("Traceback (most recent call last)")
+        {
+            in_traceback = true;
+            frames.clear();
+            continue;
+        }
+        if !in_traceback {
+            continue;
+        }
+        if let Some(captures) = FRAME.captures(line) {
+            frames.push((
+                captures["path"].replace('\\', "/"),
+                captures["function"].to_owned(),
+            ));
+            continue;
+        }
+        // code lines and `^^^` markers are indented, unlike the exception
+        if line.starts_with(char::is_whitespace) {
+            continue;
+        }
+        let Some(captures) = EXCEPTION.captures(line) else {
+            continue;
+        };
+        in_traceback = false;
+        let mut locations: Vec<String> = frames
+            .iter()
+            .filter_map(|(path, function)| package_path(path).map(|p| format!("{p} in {function}")))
+            .collect();
+        if locations.is_empty() {
+            locations = frames
+                .iter()
+                .map(|(path, function)| {
+                    let file = path.rsplit('/').next().unwrap_or(path);
+                    format!("{file} in {function}")
+                })
+                .collect();
+        }
+        if locations.len() < min_frames {
+            continue;
+        }
+        locations.push(format!(
+            "{}: {}",
+            &captures["kind"],
+            normalize_message(&captures["message"])
+        ));
+        traceback = Some(locations.join("\n"));
+    }
+    traceback
+}
+
+/// hash of the last traceback of `text`, see [normalized_traceback]
+pub fn fingerprint(text: &str, min_frames: usize) -> Option<String> {
+    normalized_traceback(text, min_frames)
+        .map(|traceback| hex::encode(Sha256::digest(traceback.as_bytes())))
+}
+
+/// Finds issues reporting the exact same error as a new one from their traceback fingerprints,
+/// more precise than embeddings for this class of duplicates
+#[derive(Clone)]
+pub struct Fingerprints {
+    cfg: FingerprintConfig,
+    db: Database,
+}
+
+impl Fingerprints {
+    pub fn new(cfg: FingerprintConfig, db: Database) -> Self {
+        Self { cfg, db }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.cfg.message
+    }
+
+    /// oldest issue of `repositories` with the same fingerprint as `issue`
+    pub async fn exact_duplicate(
+        &self,
+        issue: &IssueData,
+        excluded_labels: &[String],
+        repositories: &[String],
+    ) -> Result<Option<ClosestIssue>, StorageError> {
+        if !self.cfg.enabled {
+            return Ok(None);
+        }
+        let Some(fingerprint) = fingerprint(&issue.body, self.cfg.min_frames) else {
+            return Ok(None);
+        };
+        let duplicate = self
+            .db
+            .issue_by_fingerprint(&fingerprint, excluded_labels, repositories)
+            .await?;
+        if duplicate.is_some() {
+            metrics::counter!("issue_bot_exact_duplicates_total").increment(1);
+        }
+        Ok(duplicate)
+    }
+
+    /// fingerprint stored along an issue's `body`, `None` when fingerprints are disabled
+    pub fn of(&self, body: &str) -> Option<String> {
+        if !self.cfg.enabled {
+            return None;
+        }
+        fingerprint(body, self.cfg.min_frames)
+    }
+
+    /// fingerprints the issues stored before fingerprints were enabled, returns how many were
+    async fn backfill(&self) -> Result<u64, StorageError> {
+        let mut after_id = 0;
+        let mut fingerprinted = 0;
+        loop {
+            let issues = self
+                .db
+                .unfingerprinted_issues(after_id, BACKFILL_BATCH_SIZE)
+                .await?;
+            let Some(last) = issues.last() else {
+                return Ok(fingerprinted);
+            };
+            after_id = last.id;
+            for issue in &issues {
+                if let Some(fingerprint) = self.of(&issue.body) {
+                    self.db
+                        .set_issue_fingerprint(issue.source_id, Some(&fingerprint))
+                        .await?;
+                    fingerprinted += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Fingerprints the stored issues quoting a traceback once after starting, only one instance of
+/// the bot doing so
+///
+/// New and edited issues are fingerprinted as they are stored, this catches up with those stored
+/// before fingerprints were enabled.
+pub async fn start_fingerprint_backfill(
+    fingerprints: Fingerprints,
+    locks: Locks,
+) -> anyhow::Result<()> {
+    if !fingerprints.cfg.enabled {
+        return Ok(());
+    }
+    let lease = match locks.try_acquire(LOCK_NAME).await {
+        Ok(Some(lease)) => lease,
+        Ok(None) => return Ok(()),
+        Err(err) => {
+            error!(
+                err = err.to_string(),
+                "failed to acquire fingerprint backfill lock"
+            );
+            return Ok(());
+        }
+    };
+
+    let shutdown = shutdown_signal();
+    pin_mut!(shutdown);
+    select! {
+        res = fingerprints.backfill() => match res {
+            Ok(fingerprinted) => info!(fingerprinted, "backfilled issue fingerprints"),
+            Err(err) => error!(err = err.to_string(), "error backfilling issue fingerprints"),
+        },
+        _ = &mut shutdown => (),
+    }
+    if let Err(err) = lease.release().await {
+        error!(
+            err = err.to_string(),
+            "failed to release fingerprint backfill lock"
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fingerprint;
+
+    #[test]
+    fn test_fingerprint() {
+        let report = |venv: &str, script: &str, line: u32, address: &str| {
+            format!(
+                r#"Loading the model fails:
+
+```
+Traceback (most recent call last):
+  File "{script}", line 4, in <module>
+    model = AutoModel.from_pretrained("meta-llama/Llama-3.2-1B")
+  File "{venv}/site-packages/transformers/models/auto/auto_factory.py", line {line}, in from_pretrained
+    return model_class.from_pretrained(
+           ^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+  File "{venv}/site-packages/transformers/modeling_utils.py", line 4097, in from_pretrained
+    model = cls(config, *model_args, **model_kwargs)
+RuntimeError: CUDA error: out of memory at {address} ({venv}/site-packages/torch/cuda/memory.py:{line})
+```
+"#
+            )
+        };
+        let first = fingerprint(
+            &report("/usr/lib/python3.10", "/content/train.py", 564, "0x7f3a"),
+            2,
+        );
+        assert!(first.is_some());
+        assert_eq!(
+            first,
+            fingerprint(
+                &report(
+                    "C:\\Users\\me\\venv\\lib",
+                    "C:\\Users\\me\\run.py",
+                    571,
+                    "0x55d2"
+                ),
+                2
+            )
+        );
+        assert_ne!(
+            first,
+            fingerprint(
+                &report("/usr/lib/python3.10", "/content/train.py", 564, "0x7f3a")
+                    .replace("RuntimeError", "ValueError"),
+                2
+            )
+        );
+        // too few frames to tell errors apart
+        assert!(fingerprint(
+            &report("/usr/lib/python3.10", "/content/train.py", 564, "0x7f3a"),
+            3
+        )
+        .is_none());
+        assert!(fingerprint("RuntimeError: CUDA error: out of memory", 0).is_none());
+    }
+}
@@ -0,0 +1,94 @@
+use serde::Serialize;
+
+use crate::{config::FeedbackConfig, ClosestIssue};
+
+#[derive(Serialize)]
+struct FooterMetadata<'a> {
+    model: &'a str,
+    run_id: &'a str,
+    scores: Vec<f64>,
+}
+
+/// Footer appended to the bot's suggestion comments
+///
+/// Contains a hidden html comment with the run's metadata as JSON, for later analysis of the
+/// suggestions' quality, followed by feedback links when a public url is configured.
+#[derive(Clone)]
+pub struct CommentFooter {
+    feedback_base_url: Option<String>,
+    model: String,
+}
+
+impl CommentFooter {
+    pub fn new(cfg: &FeedbackConfig, model: String) -> Self {
+        Self {
+            feedback_base_url: cfg
+                .base_url
+                .as_ref()
+                .map(|url| url.trim_end_matches('/').to_owned()),
+            model,
+        }
+    }
+
+    pub fn render(&self, run_id: &str, closest_issues: &[ClosestIssue]) -> String {
+        let metadata = FooterMetadata {
+            model: &self.model,
+            run_id,
+            scores: closest_issues
+                .iter()
+                .map(|ci| ci.cosine_similarity)
+                .collect(),
+        };
+        // `--` would end the html comment early, it can only appear within JSON strings
+        let metadata = serde_json::to_string(&metadata)
+            .expect("footer metadata is serializable")
+            .replace("--", "-\\u002d");
+        let mut footer = format!("\n\n<!-- lor-e: {metadata} -->");
+        if let Some(base_url) = &self.feedback_base_url {
+            let link = |vote: &str| format!("{base_url}/feedback?run_id={run_id}&vote={vote}");
+            footer.push_str(&format!(
+                "\n<sub>Was this helpful? [👍]({}) [👎]({})</sub>",
+                link("up"),
+                link("down")
+            ));
+        }
+        footer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{config::FeedbackConfig, ClosestIssue};
+
+    use super::CommentFooter;
+
+    #[test]
+    fn test_render() {
+        let closest_issues = vec![ClosestIssue {
+            title: "Test issue".to_owned(),
+            number: 29,
+            html_url: "https://github.com/huggingface/lor-e/issues/29".to_owned(),
+            labels: vec![],
+            repository_full_name: "huggingface/lor-e".to_owned(),
+            cosine_similarity: 0.5,
+            boost: 0.,
+            embedding: vec![],
+        }];
+
+        let footer = CommentFooter::new(&FeedbackConfig::default(), "m--1".to_owned());
+        assert_eq!(
+            footer.render("abc", &closest_issues),
+            "\n\n<!-- lor-e: {\"model\":\"m-\\u002d1\",\"run_id\":\"abc\",\"scores\":[0.5]} -->"
+        );
+
+        let footer = CommentFooter::new(
+            &FeedbackConfig {
+                base_url: Some("https://bot.example.com/".to_owned()),
+            },
+            "m".to_owned(),
+        );
+        assert!(footer.render("abc", &closest_issues).ends_with(
+            "\n<sub>Was this helpful? [👍](https://bot.example.com/feedback?run_id=abc&vote=up) [👎](https://bot.example.com/feedback?run_id=abc&vote=down)</sub>"
+        ));
+    }
+}
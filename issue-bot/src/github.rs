@@ -1,33 +1,59 @@
-use std::time::Duration;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use async_stream::try_stream;
-use chrono::Utc;
+use chrono::{DateTime, SecondsFormat, Utc};
 use futures::Stream;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use once_cell::sync::Lazy;
+use regex::Regex;
 use reqwest::{
-    header::{HeaderMap, HeaderName, HeaderValue, ACCEPT, AUTHORIZATION, LINK},
-    Client,
+    header::{HeaderMap, HeaderName, HeaderValue, ACCEPT, AUTHORIZATION, LINK, RETRY_AFTER},
+    Client, RequestBuilder, Response, StatusCode,
 };
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use thiserror::Error;
 use tokio::time::sleep;
 use tracing::{error, info};
 
 use crate::{
-    config::{GithubApiConfig, MessageConfig},
-    deserialize_null_default, ClosestIssue, RepositoryData, APP_USER_AGENT,
+    config::{GithubApiConfig, GithubAppConfig, HttpClientConfig, HttpTarget, TimeoutsConfig},
+    deserialize_null_default,
+    footer::CommentFooter,
+    http_client::client_builder,
+    live_config::LiveConfig,
+    response_cache::ResponseCache,
+    retry::{classify_reqwest, Classify, RetryClass},
+    storage::{IssueLink, IssueLinkKind, RepositoryMetadata},
+    ClosestIssue, RepositoryData,
 };
 
+const X_OAUTH_SCOPES: HeaderName = HeaderName::from_static("x-oauth-scopes");
 const X_RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("x-ratelimit-remaining");
 const X_RATELIMIT_RESET: HeaderName = HeaderName::from_static("x-ratelimit-reset");
 
 #[derive(Debug, Error)]
 pub enum GithubApiError {
+    /// the issue was deleted or transferred away upstream, answered with a 410, or a 404 while its
+    /// repository can still be read
+    #[error("issue is gone: {0}")]
+    Gone(String),
     #[error("invalid header value: {0}")]
     InvalidHeaderValue(#[from] reqwest::header::InvalidHeaderValue),
+    #[error("jwt error: {0}")]
+    Jwt(#[from] jsonwebtoken::errors::Error),
+    #[error("no github app configured")]
+    MissingApp,
     #[error("missing rate limit headers: {0:?} {1:?}")]
     MissingRateLimitHeaders(Option<HeaderValue>, Option<HeaderValue>),
     #[error("parse int error: {0}")]
     ParseInt(#[from] std::num::ParseIntError),
+    /// primary or secondary rate limit, with how long GitHub asked to wait when it did
+    #[error("rate limited by github: {0}")]
+    RateLimited(String, Option<Duration>),
     #[error("reqwest error: {0}")]
     Reqwest(#[from] reqwest::Error),
     #[error("semaphore acquire error: {0}")]
@@ -40,13 +66,72 @@ pub enum GithubApiError {
     ToStr(#[from] axum::http::header::ToStrError),
 }
 
+impl Classify for GithubApiError {
+    fn retry_class(&self) -> RetryClass {
+        match self {
+            Self::RateLimited(..) => RetryClass::RateLimited,
+            Self::Reqwest(err) => classify_reqwest(err),
+            _ => RetryClass::Fatal,
+        }
+    }
+
+    fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimited(_, retry_after) => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+/// [Response::error_for_status], telling rate limits apart from other errors
+///
+/// GitHub answers its secondary rate limits with a 403 rather than a 429, along with a
+/// `retry-after` header or an exhausted `x-ratelimit-remaining`.
+fn error_for_status(res: Response) -> Result<Response, GithubApiError> {
+    let status = res.status();
+    if matches!(
+        status,
+        StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS
+    ) {
+        let headers = res.headers();
+        let retry_after = headers
+            .get(RETRY_AFTER)
+            .and_then(|v| v.to_str().ok()?.parse().ok())
+            .map(Duration::from_secs);
+        let reset_after = headers
+            .get(X_RATELIMIT_RESET)
+            .and_then(|v| v.to_str().ok()?.parse::<i64>().ok())
+            .map(|reset| Duration::from_secs((reset - Utc::now().timestamp()).max(0) as u64 + 1));
+        let exhausted = headers
+            .get(X_RATELIMIT_REMAINING)
+            .is_some_and(|v| v.as_bytes() == b"0");
+        if status == StatusCode::TOO_MANY_REQUESTS || retry_after.is_some() || exhausted {
+            return Err(GithubApiError::RateLimited(
+                res.url().to_string(),
+                retry_after.or(reset_after.filter(|_| exhausted)),
+            ));
+        }
+    }
+    Ok(res.error_for_status()?)
+}
+
 #[derive(Debug, Deserialize)]
-#[allow(unused)]
 struct PullRequest {
+    #[allow(unused)]
     html_url: String,
     url: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct Label {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Milestone {
+    title: String,
+}
+
 #[derive(Debug, Deserialize)]
 struct Issue {
     #[serde(default, deserialize_with = "deserialize_null_default")]
@@ -54,11 +139,17 @@ struct Issue {
     comments_url: String,
     html_url: String,
     id: i64,
+    #[serde(default, deserialize_with = "deserialize_null_default")]
+    labels: Vec<Label>,
+    #[serde(default)]
+    milestone: Option<Milestone>,
     number: i32,
     #[serde(default)]
     pull_request: Option<PullRequest>,
     title: String,
     url: String,
+    #[serde(default)]
+    user: Option<User>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -66,15 +157,30 @@ pub(crate) struct Comment {
     pub(crate) body: String,
     pub(crate) id: i64,
     pub(crate) url: String,
+    #[serde(default)]
+    pub(crate) user: Option<User>,
+    /// made on the diff of a pull request
+    #[serde(skip)]
+    pub(crate) is_review: bool,
+}
+
+impl Comment {
+    pub(crate) fn author(&self) -> Option<&str> {
+        self.user.as_ref().map(|u| u.login.as_str())
+    }
 }
 
 #[derive(Debug)]
 pub(crate) struct IssueWithComments {
+    /// login of whoever opened the issue, when known
+    pub(crate) author: Option<String>,
     pub(crate) body: String,
     pub(crate) comments: Vec<Comment>,
     pub(crate) html_url: String,
     pub(crate) id: i64,
     pub(crate) is_pull_request: bool,
+    pub(crate) labels: Vec<String>,
+    pub(crate) milestone: Option<String>,
     pub(crate) number: i32,
     pub(crate) title: String,
     pub(crate) url: String,
@@ -83,11 +189,14 @@ pub(crate) struct IssueWithComments {
 impl IssueWithComments {
     fn new(issue: Issue, comments: Vec<Comment>) -> Self {
         IssueWithComments {
+            author: issue.user.map(|u| u.login),
             body: issue.body,
             comments,
             html_url: issue.html_url,
             id: issue.id,
             is_pull_request: issue.pull_request.is_some(),
+            labels: issue.labels.into_iter().map(|l| l.name).collect(),
+            milestone: issue.milestone.map(|m| m.title),
             number: issue.number,
             title: issue.title,
             url: issue.url,
@@ -95,16 +204,365 @@ impl IssueWithComments {
     }
 }
 
+/// Issue updated since a catch-up's cursor, see [crate::catch_up::CatchUp]
+#[derive(Debug, Deserialize)]
+pub(crate) struct UpdatedIssue {
+    pub(crate) created_at: DateTime<Utc>,
+    #[serde(flatten)]
+    issue: Issue,
+    #[serde(default)]
+    pub(crate) user: Option<User>,
+}
+
+impl UpdatedIssue {
+    pub(crate) fn into_issue(self) -> IssueWithComments {
+        // the flattened issue misses the user, deserialized here
+        let mut issue = IssueWithComments::new(self.issue, Vec::new());
+        issue.author = self.user.map(|u| u.login);
+        issue
+    }
+}
+
+/// Comment updated since a catch-up's cursor, see [crate::catch_up::CatchUp]
+#[derive(Debug, Deserialize)]
+pub(crate) struct UpdatedComment {
+    #[serde(default, deserialize_with = "deserialize_null_default")]
+    pub(crate) body: String,
+    pub(crate) id: i64,
+    /// api url of the issue commented on
+    pub(crate) issue_url: String,
+    pub(crate) url: String,
+    #[serde(default)]
+    pub(crate) user: Option<User>,
+}
+
 #[derive(Serialize)]
 struct CommentBody {
     body: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct CreatedComment {
+    url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListedComment {
+    body: Option<String>,
+    url: String,
+}
+
+#[derive(Serialize)]
+struct CloseIssueBody {
+    state: &'static str,
+    state_reason: &'static str,
+}
+
+#[derive(Debug, Deserialize)]
+struct IssueNumber {
+    number: i32,
+}
+
+/// Webhook of a repository, its secret never being returned
+#[derive(Debug, Deserialize)]
+pub struct RepositoryHook {
+    pub id: u64,
+    pub active: bool,
+    pub events: Vec<String>,
+    pub config: RepositoryHookConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RepositoryHookConfig {
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub content_type: Option<String>,
+    /// `********` when set
+    #[serde(default)]
+    pub secret: Option<String>,
+}
+
+/// Body creating or updating a repository webhook
+#[derive(Debug, Serialize)]
+pub struct RepositoryHookBody<'a> {
+    /// only sent when creating the webhook
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<&'static str>,
+    pub active: bool,
+    pub events: Vec<String>,
+    pub config: RepositoryHookBodyConfig<'a>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RepositoryHookBodyConfig<'a> {
+    pub url: &'a str,
+    pub content_type: &'static str,
+    /// left out, an updated webhook keeps its secret
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<&'a str>,
+}
+
+/// Delivery attempt of a repository webhook
+#[derive(Debug, Deserialize)]
+pub struct HookDelivery {
+    pub id: u64,
+    /// status code of the bot's response
+    pub status_code: u16,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct User {
+    pub(crate) login: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Reaction {
+    pub(crate) user: User,
+}
+
+#[derive(Debug, Deserialize)]
+struct CodeSearchItem {
+    path: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CodeSearchResults {
+    items: Vec<CodeSearchItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ContentEntry {
+    path: String,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct IssueState {
+    state: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TimelineIssue {
+    /// set on pull requests, which may close the issue
+    #[serde(default)]
+    body: Option<String>,
+    number: i32,
+    pull_request: Option<PullRequest>,
+    repository_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TimelineSource {
+    issue: Option<TimelineIssue>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TimelineEvent {
+    /// set on `commented` events
+    body: Option<String>,
+    event: String,
+    source: Option<TimelineSource>,
+}
+
+/// `fixes #12`, `closes huggingface/lor-e#12` or `resolves https://github.com/huggingface/lor-e/issues/12`
+static CLOSING_REFERENCE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)\b(?:close[sd]?|fix(?:e[sd])?|resolve[sd]?):?\s+(?:(?P<repository>[\w.-]+/[\w.-]+)?#|https://github\.com/(?P<url_repository>[\w.-]+/[\w.-]+)/issues/)(?P<number>\d+)\b")
+        .expect("valid closing reference regex")
+});
+
+/// whether the description `body` of a pull request of `repository_full_name` uses a closing
+/// keyword on issue `number` of the same repository
+fn closes_issue(body: &str, repository_full_name: &str, number: i32) -> bool {
+    CLOSING_REFERENCE.captures_iter(body).any(|captures| {
+        let repository = captures
+            .name("repository")
+            .or_else(|| captures.name("url_repository"))
+            .map_or(repository_full_name, |m| m.as_str());
+        repository.eq_ignore_ascii_case(repository_full_name)
+            && captures["number"].parse() == Ok(number)
+    })
+}
+
+/// Links of issue `number` found in its timeline `events`, oldest first
+///
+/// Pull requests only count as links when they close the issue, mentions being too loose.
+///
+/// GitHub only turns "Duplicate of" comments of collaborators into a `marked_as_duplicate` event,
+/// which `unmarked_as_duplicate` undoes: the duplicate link is the last such comment before the
+/// issue was marked, if it still is.
+fn links_from_timeline(
+    repository_url: &str,
+    number: i32,
+    events: Vec<TimelineEvent>,
+) -> Vec<IssueLink> {
+    let repository_full_name = repository_url
+        .split_once("/repos/")
+        .map_or(repository_url, |(_, full_name)| full_name);
+    let mut links = Vec::new();
+    let mut duplicate_comment = None;
+    let mut duplicate_of_number = None;
+    let mut marked_as_duplicate = false;
+    for event in events {
+        match event.event.as_str() {
+            "cross-referenced" => {
+                let Some(issue) = event.source.and_then(|source| source.issue) else {
+                    continue;
+                };
+                if issue.repository_url != repository_url {
+                    continue;
+                }
+                let kind = match issue.pull_request {
+                    Some(_) => {
+                        let closes = issue
+                            .body
+                            .as_deref()
+                            .is_some_and(|body| closes_issue(body, repository_full_name, number));
+                        if !closes {
+                            continue;
+                        }
+                        IssueLinkKind::FixedBy
+                    }
+                    None => IssueLinkKind::CrossReference,
+                };
+                links.push(IssueLink {
+                    number,
+                    linked_number: issue.number,
+                    kind,
+                });
+            }
+            "commented" => {
+                if let Some(linked_number) = event.body.as_deref().and_then(duplicate_of) {
+                    duplicate_comment = Some(linked_number);
+                }
+            }
+            "marked_as_duplicate" => {
+                marked_as_duplicate = true;
+                duplicate_of_number = duplicate_comment;
+            }
+            "unmarked_as_duplicate" => marked_as_duplicate = false,
+            _ => (),
+        }
+    }
+    // the event can come before its comment when both were created within the same second
+    if let Some(linked_number) = duplicate_of_number
+        .or(duplicate_comment)
+        .filter(|_| marked_as_duplicate)
+    {
+        links.push(IssueLink {
+            number,
+            linked_number,
+            kind: IssueLinkKind::Duplicate,
+        });
+    }
+    links
+}
+
+/// `#123` of a "Duplicate of #123" comment
+fn duplicate_of(comment: &str) -> Option<i32> {
+    comment
+        .trim_start()
+        .strip_prefix("Duplicate of #")?
+        .split(|c: char| !c.is_ascii_digit())
+        .next()?
+        .parse()
+        .ok()
+}
+
+#[derive(Debug, Deserialize)]
+struct TeamMembership {
+    state: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Repository {
+    full_name: String,
+    description: Option<String>,
+    stargazers_count: i64,
+    #[serde(default)]
+    topics: Vec<String>,
+}
+
+/// Claims of the JWT authenticating as the GitHub App itself
+#[derive(Serialize)]
+struct AppClaims {
+    exp: i64,
+    iat: i64,
+    iss: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Installation {
+    id: i64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct InstallationToken {
+    expires_at: DateTime<Utc>,
+    token: String,
+}
+
+#[derive(Serialize)]
+struct CheckRunOutput<'a> {
+    title: &'a str,
+    summary: &'a str,
+}
+
+#[derive(Serialize)]
+struct NewCheckRun<'a> {
+    name: &'a str,
+    head_sha: &'a str,
+    status: &'static str,
+    conclusion: &'static str,
+    output: CheckRunOutput<'a>,
+}
+
+#[derive(Clone)]
+struct GithubApp {
+    app_id: u64,
+    key: EncodingKey,
+    /// installation tokens by repository, valid for an hour
+    tokens: Arc<Mutex<HashMap<String, InstallationToken>>>,
+}
+
+impl GithubApp {
+    fn new(cfg: &GithubAppConfig) -> Result<Self, GithubApiError> {
+        Ok(Self {
+            app_id: cfg.app_id,
+            key: EncodingKey::from_rsa_pem(cfg.private_key.as_bytes())?,
+            tokens: Arc::default(),
+        })
+    }
+
+    /// JWT authenticating as the app, which only allows fetching installation tokens
+    fn jwt(&self) -> Result<String, GithubApiError> {
+        let now = Utc::now().timestamp();
+        Ok(jsonwebtoken::encode(
+            &Header::new(Algorithm::RS256),
+            &AppClaims {
+                // GitHub rejects tokens issued in the future, allow for clock drift
+                iat: now - 60,
+                exp: now + 540,
+                iss: self.app_id.to_string(),
+            },
+            &self.key,
+        )?)
+    }
+}
+
 #[derive(Clone)]
 pub struct GithubApi {
+    /// set when the bot is also a GitHub App, see [GithubAppConfig]
+    app: Option<GithubApp>,
+    /// `https://api.github.com` unless pointed at a GitHub Enterprise instance or a mock
+    base_url: String,
     client: Client,
-    comments_enabled: bool,
-    message_config: MessageConfig,
+    footer: CommentFooter,
+    /// holds `comments_enabled` and the message templates
+    live_config: LiveConfig,
+    /// set to make read requests conditional, see [ResponseCache]
+    response_cache: Option<ResponseCache>,
 }
 
 fn get_next_page(link_header: Option<HeaderValue>) -> Result<Option<String>, GithubApiError> {
@@ -127,7 +585,10 @@ fn get_next_page(link_header: Option<HeaderValue>) -> Result<Option<String>, Git
 impl GithubApi {
     pub fn new(
         cfg: GithubApiConfig,
-        message_config: MessageConfig,
+        http_cfg: &HttpClientConfig,
+        timeouts: &TimeoutsConfig,
+        live_config: LiveConfig,
+        footer: CommentFooter,
     ) -> Result<Self, GithubApiError> {
         let mut headers = HeaderMap::new();
         let mut auth_value = HeaderValue::from_str(&format!("Bearer {}", cfg.auth_token))?;
@@ -138,66 +599,701 @@ impl GithubApi {
             HeaderValue::from_str("application/vnd.github+json")?,
         );
         headers.insert("X-GitHub-Api-Version", HeaderValue::from_str("2022-11-28")?);
-        let client = Client::builder()
-            .user_agent(APP_USER_AGENT)
+        let client = client_builder(http_cfg, timeouts, HttpTarget::Github)?
             .default_headers(headers)
             .build()?;
 
         Ok(Self {
+            app: cfg.app.as_ref().map(GithubApp::new).transpose()?,
+            base_url: cfg.base_url.trim_end_matches('/').to_owned(),
             client,
-            comments_enabled: cfg.comments_enabled,
-            message_config,
+            footer,
+            live_config,
+            response_cache: None,
+        })
+    }
+
+    pub fn with_response_cache(mut self, response_cache: Option<ResponseCache>) -> Self {
+        self.response_cache = response_cache;
+        self
+    }
+
+    /// sends a GET request, through the response cache when there is one
+    async fn send_get(&self, request: RequestBuilder) -> Result<Response, GithubApiError> {
+        Ok(match &self.response_cache {
+            Some(response_cache) => response_cache.send(request).await?,
+            None => request.send().await?,
         })
     }
 
-    pub async fn comment_on_issue(
+    /// API url of issue `number`, as received in webhooks
+    pub fn issue_url(&self, repository_full_name: &str, number: i32) -> String {
+        format!(
+            "{}/repos/{repository_full_name}/issues/{number}",
+            self.base_url
+        )
+    }
+
+    pub fn comments_enabled(&self) -> bool {
+        self.live_config.get().github_comments_enabled
+    }
+
+    pub fn has_app(&self) -> bool {
+        self.app.is_some()
+    }
+
+    /// token of the app's installation on the repository, reused until shortly before it expires
+    ///
+    /// The cache isn't locked while a token is requested, concurrent requests for a repository
+    /// may each get a token.
+    async fn installation_token(
         &self,
-        issue_url: &str,
-        closest_issues: Vec<ClosestIssue>,
-    ) -> Result<(), GithubApiError> {
-        if !self.comments_enabled {
-            return Ok(());
+        app: &GithubApp,
+        repository_full_name: &str,
+    ) -> Result<String, GithubApiError> {
+        if let Some(token) = app
+            .tokens
+            .lock()
+            .unwrap()
+            .get(repository_full_name)
+            .filter(|t| t.expires_at - chrono::Duration::minutes(5) > Utc::now())
+        {
+            return Ok(token.token.clone());
         }
+        let jwt = app.jwt()?;
+        let installation = self
+            .client
+            .get(format!(
+                "{}/repos/{repository_full_name}/installation",
+                self.base_url
+            ))
+            .bearer_auth(&jwt)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Installation>()
+            .await?;
+        let token = self
+            .client
+            .post(format!(
+                "{}/app/installations/{}/access_tokens",
+                self.base_url, installation.id
+            ))
+            .bearer_auth(&jwt)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<InstallationToken>()
+            .await?;
+        app.tokens
+            .lock()
+            .unwrap()
+            .insert(repository_full_name.to_owned(), token.clone());
+        Ok(token.token)
+    }
 
-        let comment_url = format!("{issue_url}/comments");
+    /// publishes a completed check run with a neutral conclusion on commit `head_sha`, shown in
+    /// the checks of its pull requests without notifying anyone
+    pub async fn create_check_run(
+        &self,
+        repository_full_name: &str,
+        head_sha: &str,
+        name: &str,
+        title: &str,
+        summary: &str,
+    ) -> Result<(), GithubApiError> {
+        let app = self.app.as_ref().ok_or(GithubApiError::MissingApp)?;
+        let token = self.installation_token(app, repository_full_name).await?;
+        let res = self
+            .client
+            .post(format!(
+                "{}/repos/{repository_full_name}/check-runs",
+                self.base_url
+            ))
+            .bearer_auth(token)
+            .json(&NewCheckRun {
+                name,
+                head_sha,
+                status: "completed",
+                conclusion: "neutral",
+                output: CheckRunOutput { title, summary },
+            })
+            .send()
+            .await?;
+        error_for_status(res)?;
+        Ok(())
+    }
+
+    /// scopes granted to the token, `None` for fine-grained and app tokens which don't advertise
+    /// them
+    pub async fn token_scopes(&self) -> Result<Option<Vec<String>>, GithubApiError> {
+        let res = self
+            .client
+            .get(format!("{}/rate_limit", self.base_url))
+            .send()
+            .await?
+            .error_for_status()?;
+        let Some(scopes) = res.headers().get(X_OAUTH_SCOPES) else {
+            return Ok(None);
+        };
+        Ok(Some(
+            scopes
+                .to_str()?
+                .split(',')
+                .map(|s| s.trim().to_owned())
+                .filter(|s| !s.is_empty())
+                .collect(),
+        ))
+    }
+
+    /// body of the comment listing the issues similar to a new one, followed by `guidance` when
+    /// the issue looks like a support question
+    pub fn suggestions_comment(
+        &self,
+        repository_full_name: &str,
+        closest_issues: &[ClosestIssue],
+        guidance: Option<&str>,
+        run_id: &str,
+    ) -> String {
         let issues: Vec<String> = closest_issues
-            .into_iter()
-            .map(|i| format!("- {} ([#{}]({}))", i.title, i.number, i.html_url))
+            .iter()
+            .map(|ci| ci.to_markdown_list_item(repository_full_name))
             .collect();
-        let body = format!(
-            "{}{}{}",
-            self.message_config.pre,
+        let live_config = self.live_config.get();
+        let guidance = guidance
+            .map(|guidance| format!("\n\n{guidance}"))
+            .unwrap_or_default();
+        format!(
+            "{}{}{}{}{}",
+            live_config.message_config.pre,
             issues.join("\n"),
-            self.message_config.post
-        );
-        self.client
+            guidance,
+            live_config.message_config.post,
+            self.footer.render(run_id, closest_issues)
+        )
+    }
+
+    /// body of the comment pointing a support question without similar issues to the guidance
+    pub fn guidance_comment(&self, guidance: &str, run_id: &str) -> String {
+        format!("{}{}", guidance, self.footer.render(run_id, &[]))
+    }
+
+    /// body of the comment answering a new issue from the knowledge base
+    pub fn curated_answer_comment(&self, message: &str, answer: &str, run_id: &str) -> String {
+        format!(
+            "{}{}{}{}",
+            message,
+            answer,
+            self.live_config.get().message_config.post,
+            self.footer.render(run_id, &[])
+        )
+    }
+
+    /// body of the comment pointing to the issue a new one has the same traceback as
+    pub fn duplicate_comment(
+        &self,
+        repository_full_name: &str,
+        duplicate: &ClosestIssue,
+        message: &str,
+        run_id: &str,
+    ) -> String {
+        format!(
+            "{}{}{}",
+            message,
+            duplicate.to_markdown_list_item(repository_full_name),
+            self.footer.render(run_id, std::slice::from_ref(duplicate))
+        )
+    }
+
+    /// returns the api url of the created comment, `None` when comments are disabled
+    pub async fn comment(
+        &self,
+        issue_url: &str,
+        body: String,
+    ) -> Result<Option<String>, GithubApiError> {
+        if !self.comments_enabled() {
+            return Ok(None);
+        }
+
+        let comment_url = format!("{issue_url}/comments");
+        let res = self
+            .client
             .post(comment_url)
             .json(&CommentBody { body })
             .send()
             .await?;
+        let comment = self
+            .issue_response(res)
+            .await?
+            .json::<CreatedComment>()
+            .await?;
+        Ok(Some(comment.url))
+    }
+
+    /// api url of a comment of the issue with `body`, updated after `since`, used to tell whether a
+    /// comment whose request failed without an answer was posted nonetheless
+    pub async fn find_comment(
+        &self,
+        issue_url: &str,
+        body: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Option<String>, GithubApiError> {
+        let res = self
+            .client
+            .get(format!("{issue_url}/comments"))
+            .query(&[
+                (
+                    "since",
+                    since.to_rfc3339_opts(SecondsFormat::Secs, true).as_str(),
+                ),
+                ("per_page", "100"),
+            ])
+            .send()
+            .await?;
+        let comments = self
+            .issue_response(res)
+            .await?
+            .json::<Vec<ListedComment>>()
+            .await?;
+        Ok(comments
+            .into_iter()
+            .find(|comment| comment.body.as_deref().map(str::trim) == Some(body.trim()))
+            .map(|comment| comment.url))
+    }
+
+    /// edits a comment, `comment_url` being its api url, does nothing when comments are disabled
+    pub async fn update_comment(
+        &self,
+        comment_url: &str,
+        body: String,
+    ) -> Result<(), GithubApiError> {
+        if !self.comments_enabled() {
+            return Ok(());
+        }
+
+        let res = self
+            .client
+            .patch(comment_url)
+            .json(&CommentBody { body })
+            .send()
+            .await?;
+        self.issue_response(res).await?;
+        Ok(())
+    }
+
+    /// closes the issue with `state_reason` set to `duplicate`
+    pub async fn close_issue(&self, issue_url: &str) -> Result<(), GithubApiError> {
+        let res = self
+            .client
+            .patch(issue_url)
+            .json(&CloseIssueBody {
+                state: "closed",
+                state_reason: "duplicate",
+            })
+            .send()
+            .await?;
+        self.issue_response(res).await?;
         Ok(())
     }
 
+    /// 👍 reactions on a comment, `comment_url` being the comment's api url
+    pub(crate) async fn get_thumbs_up_reactions(
+        &self,
+        comment_url: &str,
+    ) -> Result<Vec<Reaction>, GithubApiError> {
+        let reactions = self
+            .client
+            .get(format!("{comment_url}/reactions"))
+            .query(&[("content", "+1"), ("per_page", "100")])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Vec<Reaction>>()
+            .await?;
+        Ok(reactions)
+    }
+
+    pub async fn is_issue_open(
+        &self,
+        repository_full_name: &str,
+        number: i32,
+    ) -> Result<bool, GithubApiError> {
+        let res = self
+            .client
+            .get(self.issue_url(repository_full_name, number))
+            .send()
+            .await?;
+        let issue = self.issue_response(res).await?.json::<IssueState>().await?;
+        Ok(issue.state == "open")
+    }
+
+    /// links of issue `number` to the issues and pull requests of the same repository, from every
+    /// page of its timeline
+    pub async fn timeline_links(
+        &self,
+        repository_full_name: &str,
+        number: i32,
+    ) -> Result<Vec<IssueLink>, GithubApiError> {
+        let repository_url = format!("{}/repos/{repository_full_name}", self.base_url);
+        let mut next_url = Some(format!(
+            "{repository_url}/issues/{number}/timeline?per_page=100"
+        ));
+        let mut events = Vec::new();
+        while let Some(url) = next_url.take() {
+            let res = self.client.get(&url).send().await?;
+            next_url = get_next_page(res.headers().get(LINK).cloned())?;
+            events.extend(error_for_status(res)?.json::<Vec<TimelineEvent>>().await?);
+        }
+        Ok(links_from_timeline(&repository_url, number, events))
+    }
+
+    /// path in the repository of the file `mentioned_path` refers to, which can be a bare file
+    /// name or an absolute path from a stack trace
+    pub async fn find_file(
+        &self,
+        repository_full_name: &str,
+        mentioned_path: &str,
+    ) -> Result<Option<String>, GithubApiError> {
+        let file_name = mentioned_path.rsplit('/').next().unwrap_or(mentioned_path);
+        let results = self
+            .client
+            .get(format!("{}/search/code", self.base_url))
+            .query(&[(
+                "q",
+                format!("filename:{file_name} repo:{repository_full_name}"),
+            )])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<CodeSearchResults>()
+            .await?;
+        let best_match = results
+            .items
+            .iter()
+            .find(|item| {
+                mentioned_path.ends_with(&item.path) || item.path.ends_with(mentioned_path)
+            })
+            .or(results.items.first())
+            .map(|item| item.path.clone());
+        Ok(best_match)
+    }
+
+    /// raw content of a file on the default branch, `None` if it doesn't exist
+    pub async fn get_file_content(
+        &self,
+        repository_full_name: &str,
+        path: &str,
+    ) -> Result<Option<String>, GithubApiError> {
+        let res = self
+            .client
+            .get(format!(
+                "{}/repos/{repository_full_name}/contents/{path}",
+                self.base_url
+            ))
+            .header(ACCEPT, "application/vnd.github.raw+json")
+            .send()
+            .await?;
+        if res.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        Ok(Some(error_for_status(res)?.text().await?))
+    }
+
+    /// paths of the files of a directory on the default branch, empty if it doesn't exist
+    pub async fn list_directory(
+        &self,
+        repository_full_name: &str,
+        path: &str,
+    ) -> Result<Vec<String>, GithubApiError> {
+        let res = self
+            .client
+            .get(format!(
+                "{}/repos/{repository_full_name}/contents/{path}",
+                self.base_url
+            ))
+            .send()
+            .await?;
+        if res.status() == StatusCode::NOT_FOUND {
+            return Ok(Vec::new());
+        }
+        let entries = error_for_status(res)?.json::<Vec<ContentEntry>>().await?;
+        Ok(entries
+            .into_iter()
+            .filter(|e| e.kind == "file")
+            .map(|e| e.path)
+            .collect())
+    }
+
+    pub async fn repository_metadata(
+        &self,
+        repository_full_name: &str,
+    ) -> Result<RepositoryMetadata, GithubApiError> {
+        let repository = self
+            .send_get(
+                self.client
+                    .get(format!("{}/repos/{repository_full_name}", self.base_url)),
+            )
+            .await?
+            .error_for_status()?
+            .json::<Repository>()
+            .await?;
+        Ok(RepositoryMetadata {
+            full_name: repository.full_name,
+            description: repository.description,
+            stars: repository.stargazers_count,
+            topics: repository.topics,
+        })
+    }
+
+    /// number of the repository's most recent issue or pull request, i.e. roughly how many it has
+    pub async fn latest_issue_number(
+        &self,
+        repository_full_name: &str,
+    ) -> Result<Option<i32>, GithubApiError> {
+        let issues = self
+            .client
+            .get(format!(
+                "{}/repos/{repository_full_name}/issues",
+                self.base_url
+            ))
+            .query(&[
+                ("state", "all"),
+                ("sort", "created"),
+                ("direction", "desc"),
+                ("per_page", "1"),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Vec<IssueNumber>>()
+            .await?;
+        Ok(issues.first().map(|issue| issue.number))
+    }
+
+    /// requires the token to have the `admin:repo_hook` scope
+    pub async fn repository_hooks(
+        &self,
+        repository_full_name: &str,
+    ) -> Result<Vec<RepositoryHook>, GithubApiError> {
+        let hooks = self
+            .client
+            .get(format!(
+                "{}/repos/{repository_full_name}/hooks",
+                self.base_url
+            ))
+            .query(&[("per_page", "100")])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Vec<RepositoryHook>>()
+            .await?;
+        Ok(hooks)
+    }
+
+    /// most recent deliveries of the webhook first, requires the `admin:repo_hook` scope
+    pub async fn hook_deliveries(
+        &self,
+        repository_full_name: &str,
+        hook_id: u64,
+    ) -> Result<Vec<HookDelivery>, GithubApiError> {
+        let deliveries = self
+            .client
+            .get(format!(
+                "{}/repos/{repository_full_name}/hooks/{hook_id}/deliveries",
+                self.base_url
+            ))
+            .query(&[("per_page", "10")])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Vec<HookDelivery>>()
+            .await?;
+        Ok(deliveries)
+    }
+
+    /// creates the webhook when `hook_id` is `None`, updates it otherwise
+    pub async fn save_repository_hook(
+        &self,
+        repository_full_name: &str,
+        hook_id: Option<u64>,
+        hook: &RepositoryHookBody<'_>,
+    ) -> Result<RepositoryHook, GithubApiError> {
+        let hooks_url = format!("{}/repos/{repository_full_name}/hooks", self.base_url);
+        let request = match hook_id {
+            Some(id) => self.client.patch(format!("{hooks_url}/{id}")),
+            None => self.client.post(hooks_url),
+        };
+        let hook = request
+            .json(hook)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<RepositoryHook>()
+            .await?;
+        Ok(hook)
+    }
+
+    /// requires the token to have the `read:org` scope
+    pub async fn is_team_member(
+        &self,
+        org: &str,
+        team_slug: &str,
+        username: &str,
+    ) -> Result<bool, GithubApiError> {
+        let res = self
+            .client
+            .get(format!(
+                "{}/orgs/{org}/teams/{team_slug}/memberships/{username}",
+                self.base_url
+            ))
+            .send()
+            .await?;
+        if res.status() == StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+        let membership = error_for_status(res)?.json::<TeamMembership>().await?;
+        Ok(membership.state == "active")
+    }
+
+    /// every page of a listing of the repository's issues or comments updated since `since`,
+    /// oldest first
+    async fn list_updated_since<T: DeserializeOwned>(
+        &self,
+        url: String,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<T>, GithubApiError> {
+        let since = since.to_rfc3339_opts(SecondsFormat::Secs, true);
+        let separator = if url.contains('?') { '&' } else { '?' };
+        let mut next_url = Some(format!(
+            "{url}{separator}since={since}&sort=updated&direction=asc&per_page=100"
+        ));
+        let mut items = Vec::new();
+        while let Some(url) = next_url.take() {
+            let res = self.send_get(self.client.get(&url)).await?;
+            let ratelimit_remaining = res.headers().get(X_RATELIMIT_REMAINING).cloned();
+            let ratelimit_reset = res.headers().get(X_RATELIMIT_RESET).cloned();
+            if handle_ratelimit(ratelimit_remaining, ratelimit_reset).await? {
+                next_url = Some(url);
+                continue;
+            }
+            next_url = get_next_page(res.headers().get(LINK).cloned())?;
+            items.extend(error_for_status(res)?.json::<Vec<T>>().await?);
+        }
+        Ok(items)
+    }
+
+    /// issues and pull requests of the repository updated since `since`, oldest first
+    pub(crate) async fn issues_updated_since(
+        &self,
+        repository_full_name: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<UpdatedIssue>, GithubApiError> {
+        self.list_updated_since(
+            format!(
+                "{}/repos/{repository_full_name}/issues?state=all",
+                self.base_url
+            ),
+            since,
+        )
+        .await
+    }
+
+    /// comments on the repository's issues and pull requests updated since `since`, oldest first
+    pub(crate) async fn comments_updated_since(
+        &self,
+        repository_full_name: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<UpdatedComment>, GithubApiError> {
+        self.list_updated_since(
+            format!(
+                "{}/repos/{repository_full_name}/issues/comments",
+                self.base_url
+            ),
+            since,
+        )
+        .await
+    }
+
+    /// a 410 on a single issue means it is gone, as does a 404 while its repository can still be
+    /// read: callers stop bothering with it rather than retrying. A 404 on a repository that can't
+    /// be read rather means the token lost access to it.
+    async fn issue_response(&self, res: Response) -> Result<Response, GithubApiError> {
+        let gone = match res.status() {
+            StatusCode::GONE => true,
+            StatusCode::NOT_FOUND => match repository_api_url(res.url().as_str()) {
+                Some(url) => self.client.get(url).send().await?.status().is_success(),
+                None => false,
+            },
+            _ => false,
+        };
+        if gone {
+            return Err(GithubApiError::Gone(res.url().to_string()));
+        }
+        error_for_status(res)
+    }
+
     pub(crate) async fn get_issue(
         &self,
         number: i32,
         repository_full_name: &str,
     ) -> Result<IssueWithComments, GithubApiError> {
-        let url = format!(
-            "https://api.github.com/repos/{}/issues/{}",
-            repository_full_name, number
-        );
-        let issue = self.client.get(&url).send().await?.json::<Issue>().await?;
+        let url = self.issue_url(repository_full_name, number);
+        let issue = self
+            .issue_response(self.send_get(self.client.get(&url)).await?)
+            .await?
+            .json::<Issue>()
+            .await?;
         let comments = self
-            .client
-            .get(&issue.comments_url)
-            .query(&[("direction", "asc")])
-            .send()
+            .send_get(
+                self.client
+                    .get(&issue.comments_url)
+                    .query(&[("direction", "asc")]),
+            )
             .await?
             .json::<Vec<Comment>>()
             .await?;
+        // only fetched here, sparing a request per pull request when indexing whole repositories
+        let review_comments = match &issue.pull_request {
+            Some(pull_request) => self.review_comments(&pull_request.url).await?,
+            None => Vec::new(),
+        };
 
-        Ok(IssueWithComments::new(issue, comments))
+        Ok(IssueWithComments::new(
+            issue,
+            comments.into_iter().chain(review_comments).collect(),
+        ))
+    }
+
+    /// every page of the review comments of the pull request, oldest first
+    async fn review_comments(
+        &self,
+        pull_request_url: &str,
+    ) -> Result<Vec<Comment>, GithubApiError> {
+        let mut next_url = Some(format!(
+            "{pull_request_url}/comments?direction=asc&per_page=100"
+        ));
+        let mut comments = Vec::new();
+        while let Some(url) = next_url.take() {
+            let res = self.send_get(self.client.get(&url)).await?;
+            let ratelimit_remaining = res.headers().get(X_RATELIMIT_REMAINING).cloned();
+            let ratelimit_reset = res.headers().get(X_RATELIMIT_RESET).cloned();
+            if handle_ratelimit(ratelimit_remaining, ratelimit_reset).await? {
+                next_url = Some(url);
+                continue;
+            }
+            next_url = get_next_page(res.headers().get(LINK).cloned())?;
+            comments.extend(
+                error_for_status(res)?
+                    .json::<Vec<Comment>>()
+                    .await?
+                    .into_iter()
+                    .map(|comment| Comment {
+                        is_review: true,
+                        ..comment
+                    }),
+            );
+        }
+        Ok(comments)
     }
 
     pub(crate) fn get_issues(
@@ -212,17 +1308,16 @@ impl GithubApi {
                 info!("resuming fetching issues from repo {} at {}", repo_data.full_name, from_url);
                 from_url
             } else {
-                format!("https://api.github.com/repos/{}/issues", repo_data.full_name)
+                format!("{}/repos/{}/issues", self.base_url, repo_data.full_name)
             };
             loop {
-                let res = client
+                let res = self.send_get(client
                     .get(&url)
                     .query(&[
                         ("state", "all"),
                         ("direction", "desc"),
                         ("per_page", "100"),
-                    ])
-                .send()
+                    ]))
                 .await?;
                 let link_header = res.headers().get(LINK).cloned();
                 let ratelimit_remaining = res.headers().get(X_RATELIMIT_REMAINING).cloned();
@@ -246,10 +1341,9 @@ impl GithubApi {
                 };
                 for (i, issue) in issues.into_iter().enumerate() {
                     loop {
-                        let res = client
+                        let res = self.send_get(client
                             .get(&issue.comments_url)
-                            .query(&[("direction", "asc")])
-                            .send()
+                            .query(&[("direction", "asc")]))
                             .await?;
                         let ratelimit_remaining = res.headers().get(X_RATELIMIT_REMAINING).cloned();
                         let ratelimit_reset = res.headers().get(X_RATELIMIT_RESET).cloned();
@@ -277,6 +1371,16 @@ impl GithubApi {
     }
 }
 
+/// api url of the repository of `url`, e.g. `https://api.github.com/repos/huggingface/lor-e` for
+/// the url of one of its issues or comments
+fn repository_api_url(url: &str) -> Option<&str> {
+    let start = url.find("/repos/")? + "/repos/".len();
+    let mut segments = url[start..].splitn(3, '/');
+    let owner = segments.next().filter(|owner| !owner.is_empty())?;
+    let repo = segments.next().filter(|repo| !repo.is_empty())?;
+    Some(&url[..start + owner.len() + 1 + repo.len()])
+}
+
 /// returns true if rate limited and sleeps until reset
 async fn handle_ratelimit(
     remaining: Option<HeaderValue>,
@@ -297,3 +1401,138 @@ async fn handle_ratelimit(
         (remaining, reset) => Err(GithubApiError::MissingRateLimitHeaders(remaining, reset)),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use reqwest::{Response, StatusCode};
+
+    use crate::{
+        retry::{Classify, RetryClass},
+        storage::IssueLinkKind,
+    };
+
+    use super::{
+        closes_issue, error_for_status, links_from_timeline, repository_api_url, TimelineEvent,
+    };
+
+    fn response(status: StatusCode, headers: &[(&str, &str)]) -> Response {
+        let mut builder = axum::http::Response::builder().status(status);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        Response::from(builder.body("").unwrap())
+    }
+
+    #[test]
+    fn test_error_for_status() {
+        assert!(error_for_status(response(StatusCode::OK, &[])).is_ok());
+
+        // secondary rate limit
+        let err = error_for_status(response(StatusCode::FORBIDDEN, &[("retry-after", "30")]))
+            .unwrap_err();
+        assert_eq!(err.retry_class(), RetryClass::RateLimited);
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(30)));
+
+        // primary rate limit
+        let err = error_for_status(response(
+            StatusCode::FORBIDDEN,
+            &[("x-ratelimit-remaining", "0"), ("x-ratelimit-reset", "0")],
+        ))
+        .unwrap_err();
+        assert_eq!(err.retry_class(), RetryClass::RateLimited);
+
+        // missing permissions
+        let err = error_for_status(response(
+            StatusCode::FORBIDDEN,
+            &[("x-ratelimit-remaining", "4999")],
+        ))
+        .unwrap_err();
+        assert_eq!(err.retry_class(), RetryClass::Fatal);
+        assert_eq!(err.retry_after(), None);
+    }
+
+    #[test]
+    fn test_repository_api_url() {
+        assert_eq!(
+            repository_api_url("https://api.github.com/repos/huggingface/lor-e/issues/12"),
+            Some("https://api.github.com/repos/huggingface/lor-e")
+        );
+        assert_eq!(
+            repository_api_url(
+                "https://github.example.com/api/v3/repos/org/repo/issues/comments/7"
+            ),
+            Some("https://github.example.com/api/v3/repos/org/repo")
+        );
+        assert_eq!(
+            repository_api_url("https://api.github.com/repos/huggingface"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_links_from_timeline() {
+        let repository_url = "https://api.github.com/repos/huggingface/lor-e";
+        let events: Vec<TimelineEvent> = serde_json::from_str(&format!(
+            r#"[
+                {{"event":"cross-referenced","source":{{"issue":{{"number":3,"repository_url":"{repository_url}"}}}}}},
+                {{"event":"cross-referenced","source":{{"issue":{{"number":4,"body":"Fixes #9","pull_request":{{"html_url":"","url":""}},"repository_url":"{repository_url}"}}}}}},
+                {{"event":"cross-referenced","source":{{"issue":{{"number":6,"body":"Related to #9, fixes #19","pull_request":{{"html_url":"","url":""}},"repository_url":"{repository_url}"}}}}}},
+                {{"event":"cross-referenced","source":{{"issue":{{"number":5,"repository_url":"https://api.github.com/repos/huggingface/other"}}}}}},
+                {{"event":"commented","body":"Duplicate of #1"}},
+                {{"event":"marked_as_duplicate"}},
+                {{"event":"commented","body":"Duplicate of #2"}}
+            ]"#
+        ))
+        .unwrap();
+        let links: Vec<(i32, IssueLinkKind)> = links_from_timeline(repository_url, 9, events)
+            .into_iter()
+            .map(|link| (link.linked_number, link.kind))
+            .collect();
+        assert_eq!(
+            links,
+            vec![
+                (3, IssueLinkKind::CrossReference),
+                (4, IssueLinkKind::FixedBy),
+                (1, IssueLinkKind::Duplicate),
+            ]
+        );
+
+        // only collaborators' comments mark issues as duplicates
+        let events: Vec<TimelineEvent> =
+            serde_json::from_str(r#"[{"event":"commented","body":"Duplicate of #1"}]"#).unwrap();
+        assert!(links_from_timeline(repository_url, 9, events).is_empty());
+
+        let events: Vec<TimelineEvent> = serde_json::from_str(
+            r#"[
+                {"event":"commented","body":"Duplicate of #1"},
+                {"event":"marked_as_duplicate"},
+                {"event":"unmarked_as_duplicate"}
+            ]"#,
+        )
+        .unwrap();
+        assert!(links_from_timeline(repository_url, 9, events).is_empty());
+    }
+
+    #[test]
+    fn test_closes_issue() {
+        let repository = "huggingface/lor-e";
+        assert!(closes_issue("Fixes #12", repository, 12));
+        assert!(closes_issue("this PR closes: #12.", repository, 12));
+        assert!(closes_issue(
+            "Resolves huggingface/lor-e#12",
+            repository,
+            12
+        ));
+        assert!(closes_issue(
+            "resolved https://github.com/huggingface/lor-e/issues/12",
+            repository,
+            12
+        ));
+        assert!(!closes_issue("Fixes #123", repository, 12));
+        assert!(!closes_issue("See #12", repository, 12));
+        assert!(!closes_issue("Fixes huggingface/other#12", repository, 12));
+        assert!(!closes_issue("prefixes #12", repository, 12));
+    }
+}
@@ -0,0 +1,209 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use reqwest::Client;
+use serde::Deserialize;
+use thiserror::Error;
+use tokio::sync::RwLock;
+use tracing::info;
+
+use crate::{
+    config::{GithubOidcConfig, HttpClientConfig, HttpTarget, OidcRepository, TimeoutsConfig},
+    http_client::client_builder,
+};
+
+/// signing keys aren't fetched again more often than this for tokens signed with an unknown one
+const MIN_JWKS_REFRESH: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Error)]
+pub enum GithubOidcError {
+    #[error("github oidc tokens are not accepted")]
+    Disabled,
+    #[error("jwt error: {0}")]
+    Jwt(#[from] jsonwebtoken::errors::Error),
+    #[error("token has no key id")]
+    MissingKeyId,
+    #[error("reqwest error: {0}")]
+    Reqwest(#[from] reqwest::Error),
+    #[error("unknown signing key: {0}")]
+    UnknownKey(String),
+}
+
+/// Claims of a GitHub Actions OIDC token, among others
+#[derive(Clone, Debug, Deserialize)]
+pub struct ActionsClaims {
+    /// e.g. `huggingface/transformers`
+    pub repository: String,
+    /// numeric ids, serialized as strings
+    pub repository_id: String,
+    pub repository_owner_id: String,
+    pub actor: String,
+    pub workflow: String,
+}
+
+impl ActionsClaims {
+    fn is_from(&self, repository: &OidcRepository) -> bool {
+        self.repository == repository.full_name
+            && self.repository_id == repository.id.to_string()
+            && self.repository_owner_id == repository.owner_id.to_string()
+    }
+}
+
+struct CachedKeys {
+    fetched_at: Instant,
+    keys: JwkSet,
+}
+
+/// Verifies GitHub Actions OIDC tokens, see [GithubOidcConfig]
+#[derive(Clone)]
+pub struct GithubOidc {
+    cfg: GithubOidcConfig,
+    client: Client,
+    keys: Arc<RwLock<Option<CachedKeys>>>,
+}
+
+impl GithubOidc {
+    pub fn new(
+        cfg: GithubOidcConfig,
+        http_cfg: &HttpClientConfig,
+        timeouts: &TimeoutsConfig,
+    ) -> Result<Self, GithubOidcError> {
+        let client = client_builder(http_cfg, timeouts, HttpTarget::Github)?.build()?;
+        Ok(Self {
+            cfg,
+            client,
+            keys: Arc::default(),
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.cfg.enabled
+    }
+
+    /// checks the signature, issuer, audience and expiry of `token`
+    pub async fn verify(&self, token: &str) -> Result<ActionsClaims, GithubOidcError> {
+        if !self.cfg.enabled {
+            return Err(GithubOidcError::Disabled);
+        }
+        let header = decode_header(token)?;
+        let kid = header.kid.ok_or(GithubOidcError::MissingKeyId)?;
+        let key = self.decoding_key(&kid).await?;
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_audience(&[&self.cfg.audience]);
+        validation.set_issuer(&[&self.cfg.issuer]);
+        Ok(decode::<ActionsClaims>(token, &key, &validation)?.claims)
+    }
+
+    /// whether the workflow `claims` were issued to may index `repository_full_name`
+    pub fn may_index(&self, claims: &ActionsClaims, repository_full_name: &str) -> bool {
+        claims.repository == repository_full_name
+            && self.cfg.repositories.iter().any(|r| claims.is_from(r))
+    }
+
+    pub fn may_regenerate(&self, claims: &ActionsClaims) -> bool {
+        self.cfg
+            .regeneration_repositories
+            .iter()
+            .any(|r| claims.is_from(r))
+    }
+
+    async fn decoding_key(&self, kid: &str) -> Result<DecodingKey, GithubOidcError> {
+        let ttl = Duration::from_secs(self.cfg.jwks_cache_secs);
+        if let Some(cached) = self.keys.read().await.as_ref() {
+            let fresh = cached.fetched_at.elapsed() < ttl;
+            // keys GitHub rotated in are fetched, though not on every token using an unknown one
+            let recent = cached.fetched_at.elapsed() < MIN_JWKS_REFRESH;
+            match cached.keys.find(kid) {
+                Some(jwk) if fresh => return Ok(DecodingKey::from_jwk(jwk)?),
+                None if recent => return Err(GithubOidcError::UnknownKey(kid.to_owned())),
+                _ => {}
+            }
+        }
+        let keys: JwkSet = self
+            .client
+            .get(&self.cfg.jwks_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        info!(keys = keys.keys.len(), "fetched github oidc signing keys");
+        let key = keys
+            .find(kid)
+            .map(DecodingKey::from_jwk)
+            .transpose()?
+            .ok_or_else(|| GithubOidcError::UnknownKey(kid.to_owned()));
+        *self.keys.write().await = Some(CachedKeys {
+            fetched_at: Instant::now(),
+            keys,
+        });
+        key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{GithubOidcConfig, HttpClientConfig, OidcRepository, TimeoutsConfig};
+
+    use super::{ActionsClaims, GithubOidc};
+
+    #[test]
+    fn test_workflow_permissions() {
+        let repository = |full_name: &str, id: u64| OidcRepository {
+            full_name: full_name.to_owned(),
+            id,
+            owner_id: 25720743,
+        };
+        let cfg = GithubOidcConfig {
+            enabled: true,
+            regeneration_repositories: vec![repository("huggingface/lor-e", 1)],
+            repositories: vec![repository("huggingface/transformers", 2)],
+            ..Default::default()
+        };
+        let oidc = GithubOidc::new(
+            cfg.clone(),
+            &HttpClientConfig::default(),
+            &TimeoutsConfig::default(),
+        )
+        .unwrap();
+        let claims = |repository: &str, id: u64| ActionsClaims {
+            repository: repository.to_owned(),
+            repository_id: id.to_string(),
+            repository_owner_id: "25720743".to_owned(),
+            actor: "octocat".to_owned(),
+            workflow: "reindex".to_owned(),
+        };
+
+        assert!(oidc.may_index(
+            &claims("huggingface/transformers", 2),
+            "huggingface/transformers"
+        ));
+        assert!(!oidc.may_index(&claims("huggingface/transformers", 2), "huggingface/lor-e"));
+        // a recreated repository has another id
+        assert!(!oidc.may_index(
+            &claims("huggingface/transformers", 3),
+            "huggingface/transformers"
+        ));
+        assert!(!oidc.may_index(&claims("huggingface/lor-e", 1), "huggingface/lor-e"));
+        assert!(oidc.may_regenerate(&claims("huggingface/lor-e", 1)));
+        assert!(!oidc.may_regenerate(&claims("huggingface/transformers", 2)));
+
+        // no workflow is trusted by default
+        let oidc = GithubOidc::new(
+            GithubOidcConfig {
+                repositories: Vec::new(),
+                ..cfg
+            },
+            &HttpClientConfig::default(),
+            &TimeoutsConfig::default(),
+        )
+        .unwrap();
+        assert!(!oidc.may_index(
+            &claims("huggingface/transformers", 2),
+            "huggingface/transformers"
+        ));
+    }
+}
@@ -0,0 +1,387 @@
+use std::{collections::HashMap, time::Duration};
+
+use futures::pin_mut;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tokio::{select, time::interval};
+use tracing::{error, info};
+
+use crate::{
+    config::GuidanceConfig,
+    debug::DebugState,
+    embeddings::{
+        queue::{EmbeddingQueue, Priority},
+        EmbeddingError,
+    },
+    github::{GithubApi, GithubApiError},
+    locks::Locks,
+    shutdown_signal,
+    storage::{Database, GuidanceSection, Storage, StorageError},
+    IssueData, Source,
+};
+
+const LOCK_NAME: &str = "guidance";
+
+/// guidance files indexed when present, besides the issue templates
+const GUIDANCE_PATHS: &[&str] = &[
+    "README.md",
+    "CONTRIBUTING.md",
+    ".github/CONTRIBUTING.md",
+    "docs/CONTRIBUTING.md",
+];
+const ISSUE_TEMPLATES_DIR: &str = ".github/ISSUE_TEMPLATE";
+
+/// characters of a section embedded, the rest of long sections being mostly examples
+const MAX_EMBEDDED_CHARS: usize = 4_000;
+
+static HEADING: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^#{1,6}\s+(?P<heading>.+?)\s*#*\s*$").expect("valid markdown heading regex")
+});
+static QUESTION: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?i)^\W*(question|how (do|does|can|to|should)|is (it|there)|can (i|we|you)|what|where|which|why|should (i|we)|any (way|idea))\b",
+    )
+    .expect("valid question regex")
+});
+static BUG_REPORT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?i)traceback \(most recent call last\)|\b(bug|crash(es|ed)?|regression|segfault|reproduc(e|tion)|expected behaviou?r)\b",
+    )
+    .expect("valid bug report regex")
+});
+
+#[derive(Debug, Error)]
+pub enum GuidanceError {
+    #[error("embedding error: {0}")]
+    Embedding(#[from] EmbeddingError),
+    #[error("github api error: {0}")]
+    GithubApi(#[from] GithubApiError),
+    #[error("storage error: {0}")]
+    Storage(#[from] StorageError),
+}
+
+/// anchor GitHub gives a markdown heading
+fn anchor(heading: &str) -> String {
+    heading
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+fn strip_front_matter(content: &str) -> &str {
+    content
+        .strip_prefix("---\n")
+        .and_then(|rest| rest.split_once("\n---\n"))
+        .map_or(content, |(_, rest)| rest)
+}
+
+/// splits a markdown file at its headings, other files (e.g. issue forms) making a single section
+fn sections(repository_full_name: &str, path: &str, content: &str) -> Vec<GuidanceSection> {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    let mut sections = Vec::new();
+    let mut anchors: HashMap<String, usize> = HashMap::new();
+    let mut push = |heading: &str, body: &str| {
+        if body.trim().is_empty() {
+            return;
+        }
+        let anchor = if heading.is_empty() {
+            String::new()
+        } else {
+            let anchor = anchor(heading);
+            let seen = anchors.entry(anchor.clone()).or_default();
+            *seen += 1;
+            match *seen {
+                1 => anchor,
+                n => format!("{anchor}-{}", n - 1),
+            }
+        };
+        sections.push(GuidanceSection {
+            repository_full_name: repository_full_name.to_owned(),
+            path: path.to_owned(),
+            heading: if heading.is_empty() {
+                file_name.to_owned()
+            } else {
+                heading.to_owned()
+            },
+            anchor,
+            content: body.trim().to_owned(),
+        });
+    };
+    if !path.ends_with(".md") {
+        push("", content);
+        return sections;
+    }
+
+    let mut heading = String::new();
+    let mut body = String::new();
+    let mut in_code_block = false;
+    for line in strip_front_matter(content).lines() {
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+        }
+        match HEADING.captures(line) {
+            Some(captures) if !in_code_block => {
+                push(&heading, &body);
+                heading = captures["heading"].to_owned();
+                body.clear();
+            }
+            _ => {
+                body.push_str(line);
+                body.push('\n');
+            }
+        }
+    }
+    push(&heading, &body);
+    sections
+}
+
+/// whether the issue asks how to use the project rather than reports something broken
+pub fn is_support_question(issue: &IssueData) -> bool {
+    let labelled = |name: &str| issue.labels.iter().any(|l| l.to_lowercase().contains(name));
+    if labelled("bug") || BUG_REPORT.is_match(&issue.title) || BUG_REPORT.is_match(&issue.body) {
+        return false;
+    }
+    labelled("question") || issue.title.trim_end().ends_with('?') || QUESTION.is_match(&issue.title)
+}
+
+/// hash of the text a section is embedded from, unchanged sections keeping their embedding
+fn content_hash(text: &str) -> String {
+    hex::encode(Sha256::digest(text.as_bytes()))
+}
+
+fn section_url(section: &GuidanceSection) -> String {
+    let url = format!(
+        "https://github.com/{}/blob/HEAD/{}",
+        section.repository_full_name, section.path
+    );
+    if section.anchor.is_empty() {
+        url
+    } else {
+        format!("{url}#{}", section.anchor)
+    }
+}
+
+/// README, CONTRIBUTING and issue templates sections of the indexed GitHub repositories, pointed
+/// to from the comments on support questions
+#[derive(Clone)]
+pub struct Guidance {
+    cfg: GuidanceConfig,
+    db: Database,
+    debug_state: DebugState,
+    embedding_queue: EmbeddingQueue,
+    github_api: GithubApi,
+    locks: Locks,
+}
+
+impl Guidance {
+    pub fn new(
+        cfg: GuidanceConfig,
+        db: Database,
+        debug_state: DebugState,
+        embedding_queue: EmbeddingQueue,
+        github_api: GithubApi,
+        locks: Locks,
+    ) -> Self {
+        Self {
+            cfg,
+            db,
+            debug_state,
+            embedding_queue,
+            github_api,
+            locks,
+        }
+    }
+
+    /// re-indexes the guidance of the repository, returns the number of sections indexed
+    pub async fn index(&self, repository_full_name: &str) -> Result<usize, GuidanceError> {
+        if !self.cfg.enabled {
+            return Ok(0);
+        }
+        let mut paths: Vec<String> = GUIDANCE_PATHS.iter().map(|p| p.to_string()).collect();
+        paths.extend(
+            self.github_api
+                .list_directory(repository_full_name, ISSUE_TEMPLATES_DIR)
+                .await?
+                .into_iter()
+                // `config.yml` only configures the template chooser
+                .filter(|p| p.ends_with(".md") || p.ends_with(".yml") || p.ends_with(".yaml"))
+                .filter(|p| !p.ends_with("/config.yml") && !p.ends_with("/config.yaml")),
+        );
+        let embeddings = self.db.guidance_embeddings(repository_full_name).await?;
+        let mut indexed = Vec::new();
+        let mut reused = 0;
+        for path in paths {
+            let Some(content) = self
+                .github_api
+                .get_file_content(repository_full_name, &path)
+                .await?
+            else {
+                continue;
+            };
+            for section in sections(repository_full_name, &path, &content) {
+                let text: String = format!("{}\n\n{}", section.heading, section.content)
+                    .chars()
+                    .take(MAX_EMBEDDED_CHARS)
+                    .collect();
+                let hash = content_hash(&text);
+                let embedding = match embeddings.get(&hash).cloned() {
+                    Some(embedding) => {
+                        reused += 1;
+                        embedding
+                    }
+                    None => {
+                        self.embedding_queue
+                            .generate_embedding(text, Priority::Background)
+                            .await?
+                    }
+                };
+                indexed.push((section, hash, embedding));
+            }
+        }
+        self.db
+            .replace_guidance_sections(repository_full_name, &indexed)
+            .await?;
+        info!(
+            repository = repository_full_name,
+            sections = indexed.len(),
+            unchanged = reused,
+            "indexed repository guidance"
+        );
+        Ok(indexed.len())
+    }
+
+    async fn index_all(&self) {
+        let repositories = match self.db.repository_metadata(None).await {
+            Ok(repositories) => repositories,
+            Err(err) => {
+                self.debug_state.record_error("database", &err);
+                error!(
+                    err = err.to_string(),
+                    "failed to fetch repositories metadata"
+                );
+                return;
+            }
+        };
+        for repository in repositories {
+            if let Err(err) = self.index(&repository.full_name).await {
+                self.debug_state.record_error("guidance", &err);
+                error!(
+                    repository = repository.full_name,
+                    err = err.to_string(),
+                    "failed to index repository guidance"
+                );
+            }
+        }
+    }
+
+    /// pointer to the guidance section answering the issue embedded as `embedding`, for support
+    /// questions similar enough to one
+    pub async fn pointer_for(
+        &self,
+        issue: &IssueData,
+        embedding: &[f32],
+    ) -> Result<Option<String>, StorageError> {
+        if !self.cfg.enabled
+            || issue.is_pull_request
+            || !matches!(issue.source, Source::Github)
+            || !is_support_question(issue)
+        {
+            return Ok(None);
+        }
+        let Some(closest) = self
+            .db
+            .closest_guidance_section(embedding, &issue.repository_full_name)
+            .await?
+            .filter(|m| m.cosine_similarity >= self.cfg.min_similarity)
+        else {
+            return Ok(None);
+        };
+        metrics::counter!("issue_bot_guidance_pointers_total").increment(1);
+        Ok(Some(format!(
+            "{} [{}]({}) in `{}`",
+            self.cfg.message,
+            closest.section.heading,
+            section_url(&closest.section),
+            closest.section.path
+        )))
+    }
+}
+
+/// Re-indexes the guidance periodically, only one instance of the bot does so at a time
+pub async fn start_guidance_refresher(guidance: Guidance) -> anyhow::Result<()> {
+    if !guidance.cfg.enabled {
+        return Ok(());
+    }
+
+    info!("starting guidance refresher");
+    let mut ticker = interval(Duration::from_secs(guidance.cfg.refresh_interval_secs));
+    // guidance is indexed along with the repository's issues
+    ticker.tick().await;
+    let shutdown = shutdown_signal();
+    pin_mut!(shutdown);
+    loop {
+        select! {
+            _ = ticker.tick() => {
+                match guidance.locks.try_acquire(LOCK_NAME).await {
+                    Ok(Some(lease)) => {
+                        guidance.index_all().await;
+                        if let Err(err) = lease.release().await {
+                            error!(err = err.to_string(), "failed to release guidance lock");
+                        }
+                    }
+                    Ok(None) => (),
+                    Err(err) => error!(err = err.to_string(), "failed to acquire guidance lock"),
+                }
+            }
+            _ = &mut shutdown => break,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sections;
+
+    #[test]
+    fn test_sections() {
+        let readme = "Intro to the project\n\n# Installation\n\n```bash\n# not a heading\npip install lor-e\n```\n\n## FAQ: how to run?\n\nRun it.\n\n## Empty\n\n## Installation\n\nFrom source.\n";
+        let readme_sections = sections("huggingface/lor-e", "README.md", readme);
+
+        let headings: Vec<_> = readme_sections
+            .iter()
+            .map(|s| (s.heading.as_str(), s.anchor.as_str()))
+            .collect();
+        assert_eq!(
+            headings,
+            [
+                ("README.md", ""),
+                ("Installation", "installation"),
+                ("FAQ: how to run?", "faq-how-to-run"),
+                ("Installation", "installation-1"),
+            ]
+        );
+        assert!(readme_sections[1].content.contains("# not a heading"));
+
+        let template = "---\nname: Question\nabout: Ask for help\n---\n\n### Describe your question\n\nDetails\n";
+        let template_sections = sections(
+            "huggingface/lor-e",
+            ".github/ISSUE_TEMPLATE/question.md",
+            template,
+        );
+        assert_eq!(template_sections.len(), 1);
+        assert_eq!(template_sections[0].anchor, "describe-your-question");
+
+        let form = "name: Bug report\nbody:\n  - type: textarea\n";
+        let form_sections = sections("huggingface/lor-e", ".github/ISSUE_TEMPLATE/bug.yml", form);
+        assert_eq!(form_sections[0].heading, "bug.yml");
+        assert_eq!(form_sections[0].content, form.trim());
+    }
+}
@@ -0,0 +1,294 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tracing::warn;
+
+use crate::{
+    config::HotIssuesConfig,
+    invalidation::{self, Invalidation},
+    storage::{Database, HotIssue, Storage, StorageError},
+    ClosestIssue,
+};
+
+struct CachedIssue {
+    issue: HotIssue,
+    cached_at: Instant,
+    hits: u64,
+}
+
+/// Least frequently matched issues are evicted first, expired ones before looking up
+struct Cache {
+    capacity: usize,
+    entries: HashMap<i64, CachedIssue>,
+    /// `(hits, source_id)` of the entries, least matched first
+    by_hits: BTreeSet<(u64, i64)>,
+    /// `(cached_at, source_id)` of the entries, oldest first
+    by_age: BTreeSet<(Instant, i64)>,
+    ttl: Duration,
+}
+
+impl Cache {
+    fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            by_hits: BTreeSet::new(),
+            by_age: BTreeSet::new(),
+            ttl,
+        }
+    }
+
+    /// drops the expired issues, returning the ids of the others
+    fn fresh_ids(&mut self, now: Instant) -> Vec<i64> {
+        while let Some(&(cached_at, source_id)) = self.by_age.first() {
+            if now.duration_since(cached_at) < self.ttl {
+                break;
+            }
+            self.remove(source_id);
+        }
+        self.entries.keys().copied().collect()
+    }
+
+    fn get(&mut self, source_id: i64) -> Option<HotIssue> {
+        let cached = self.entries.get_mut(&source_id)?;
+        self.by_hits.remove(&(cached.hits, source_id));
+        cached.hits += 1;
+        self.by_hits.insert((cached.hits, source_id));
+        Some(cached.issue.clone())
+    }
+
+    fn insert(&mut self, issue: HotIssue, now: Instant) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.remove(issue.source_id);
+        if self.entries.len() >= self.capacity {
+            if let Some(&(_, source_id)) = self.by_hits.first() {
+                self.remove(source_id);
+            }
+        }
+        self.by_hits.insert((1, issue.source_id));
+        self.by_age.insert((now, issue.source_id));
+        self.entries.insert(
+            issue.source_id,
+            CachedIssue {
+                issue,
+                cached_at: now,
+                hits: 1,
+            },
+        );
+    }
+
+    fn remove(&mut self, source_id: i64) {
+        if let Some(cached) = self.entries.remove(&source_id) {
+            self.by_hits.remove(&(cached.hits, source_id));
+            self.by_age.remove(&(cached.cached_at, source_id));
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.by_hits.clear();
+        self.by_age.clear();
+    }
+}
+
+/// Serves the content of frequently matched issues from memory, see [HotIssuesConfig]
+#[derive(Clone)]
+pub struct HotIssues {
+    cache: Arc<Mutex<Cache>>,
+    db: Database,
+    enabled: bool,
+}
+
+impl HotIssues {
+    pub fn new(cfg: &HotIssuesConfig, db: Database) -> Self {
+        Self {
+            cache: Arc::new(Mutex::new(Cache::new(
+                cfg.capacity,
+                Duration::from_secs(cfg.ttl_secs),
+            ))),
+            db,
+            enabled: cfg.enabled,
+        }
+    }
+
+    /// same as [Storage::closest_issues], only fetching the content of the issues missing from the
+    /// cache
+    pub async fn closest_issues(
+        &self,
+        embedding: &[f32],
+        excluded_labels: &[String],
+        repositories: &[String],
+        limit: i64,
+        with_embeddings: bool,
+    ) -> Result<Vec<ClosestIssue>, StorageError> {
+        if !self.enabled {
+            return self
+                .db
+                .closest_issues(
+                    embedding,
+                    excluded_labels,
+                    repositories,
+                    limit,
+                    with_embeddings,
+                )
+                .await;
+        }
+        let cached = self.cache.lock().unwrap().fresh_ids(Instant::now());
+        let similarities = self
+            .db
+            .closest_hot_issues(embedding, excluded_labels, repositories, limit, &cached)
+            .await?;
+
+        let now = Instant::now();
+        let mut issues = HashMap::new();
+        let mut missing = Vec::new();
+        {
+            let mut cache = self.cache.lock().unwrap();
+            for similarity in &similarities {
+                match &similarity.issue {
+                    Some(issue) => cache.insert(issue.clone(), now),
+                    None => match cache.get(similarity.source_id) {
+                        Some(issue) => {
+                            issues.insert(issue.source_id, issue);
+                        }
+                        // evicted by a concurrent lookup since the search
+                        None => missing.push(similarity.source_id),
+                    },
+                }
+            }
+        }
+        metrics::counter!("issue_bot_hot_issues_lookups_total", "result" => "hit")
+            .increment(issues.len() as u64);
+        metrics::counter!("issue_bot_hot_issues_lookups_total", "result" => "miss")
+            .increment((similarities.len() - issues.len()) as u64);
+        if !missing.is_empty() {
+            for issue in self.db.hot_issues(&missing).await? {
+                issues.insert(issue.source_id, issue);
+            }
+        }
+
+        // issues deleted since the search are left out
+        Ok(similarities
+            .into_iter()
+            .filter_map(|similarity| {
+                let issue = similarity
+                    .issue
+                    .or_else(|| issues.remove(&similarity.source_id))?;
+                Some(ClosestIssue {
+                    title: issue.title,
+                    number: issue.number,
+                    html_url: issue.html_url,
+                    labels: issue.labels,
+                    repository_full_name: issue.repository_full_name,
+                    cosine_similarity: similarity.cosine_similarity,
+                    boost: 0.,
+                    embedding: issue.embedding,
+                })
+            })
+            .collect())
+    }
+
+    /// drops the issue on every instance, e.g. after it was edited
+    pub async fn invalidate(&self, source_id: i64) {
+        self.publish(Invalidation::HotIssue { source_id }).await;
+    }
+
+    /// drops the issue on every instance when only its number is known, e.g. after it was gone
+    /// upstream
+    pub async fn invalidate_number(&self, repository_full_name: &str, number: i32) {
+        self.publish(Invalidation::HotIssueNumber {
+            repository_full_name: repository_full_name.to_owned(),
+            number,
+        })
+        .await;
+    }
+
+    /// drops every issue on every instance, e.g. after embeddings were regenerated
+    pub async fn clear(&self) {
+        self.publish(Invalidation::HotIssues).await;
+    }
+
+    async fn publish(&self, invalidation: Invalidation) {
+        self.apply(&invalidation);
+        if !self.enabled {
+            return;
+        }
+        // the other instances keep serving the issues until they expire
+        if let Err(err) = invalidation::publish(&self.db, &invalidation).await {
+            warn!(
+                err = err.to_string(),
+                "failed to notify other instances of the hot issues invalidation"
+            );
+        }
+    }
+
+    /// drops the issues of `invalidation` on this instance only, see
+    /// [invalidation::start_invalidation_listener]
+    pub fn apply(&self, invalidation: &Invalidation) {
+        let mut cache = self.cache.lock().unwrap();
+        match invalidation {
+            Invalidation::HotIssue { source_id } => cache.remove(*source_id),
+            Invalidation::HotIssueNumber {
+                repository_full_name,
+                number,
+            } => {
+                let source_id = cache
+                    .entries
+                    .values()
+                    .find(|cached| {
+                        &cached.issue.repository_full_name == repository_full_name
+                            && cached.issue.number == *number
+                    })
+                    .map(|cached| cached.issue.source_id);
+                if let Some(source_id) = source_id {
+                    cache.remove(source_id);
+                }
+            }
+            Invalidation::HotIssues => cache.clear(),
+            Invalidation::Setting { .. } => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::storage::HotIssue;
+
+    use super::Cache;
+
+    fn issue(source_id: i64) -> HotIssue {
+        HotIssue {
+            source_id,
+            title: format!("issue {source_id}"),
+            number: source_id as i32,
+            html_url: format!("https://github.com/huggingface/lor-e/issues/{source_id}"),
+            labels: Vec::new(),
+            repository_full_name: "huggingface/lor-e".to_owned(),
+            embedding: vec![1., 0.],
+        }
+    }
+
+    #[test]
+    fn test_cache() {
+        let mut cache = Cache::new(2, Duration::from_secs(60));
+        let now = Instant::now();
+
+        cache.insert(issue(1), now);
+        cache.insert(issue(2), now + Duration::from_secs(1));
+        assert!(cache.get(1).is_some());
+        // 2 is the least frequently matched
+        cache.insert(issue(3), now + Duration::from_secs(3));
+        assert!(cache.get(2).is_none());
+        assert!(cache.get(3).is_some());
+
+        assert_eq!(cache.fresh_ids(now + Duration::from_secs(60)), vec![3]);
+        assert_eq!(cache.entries.len(), 1);
+    }
+}
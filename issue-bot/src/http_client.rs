@@ -0,0 +1,171 @@
+use std::{path::PathBuf, time::Duration};
+
+use reqwest::{Client, ClientBuilder, Identity, NoProxy, Proxy};
+use thiserror::Error;
+
+use crate::{
+    config::{
+        ClientCertificateConfig, HttpClientConfig, HttpTarget, PemSource, ProxyConfig,
+        TimeoutsConfig,
+    },
+    APP_USER_AGENT,
+};
+
+#[derive(Debug, Error)]
+pub enum ClientCertificateError {
+    #[error("invalid client certificate: {0}")]
+    Invalid(#[from] reqwest::Error),
+    #[error("failed to read '{0}': {1}")]
+    Read(PathBuf, std::io::Error),
+}
+
+fn proxy(url: &str, cfg: &ProxyConfig) -> Result<Proxy, reqwest::Error> {
+    let proxy = Proxy::all(url)?.no_proxy(NoProxy::from_string(&cfg.no_proxy.join(",")));
+    Ok(match &cfg.username {
+        Some(username) => proxy.basic_auth(username, cfg.password.as_deref().unwrap_or_default()),
+        None => proxy,
+    })
+}
+
+/// Builder of the outbound HTTP clients, with the configured connection pool and `target`'s
+/// proxy settings and timeout
+///
+/// Each subsystem keeps a single client, cloned wherever it is used, so that connections to a
+/// given host are pooled together.
+pub fn client_builder(
+    cfg: &HttpClientConfig,
+    timeouts: &TimeoutsConfig,
+    target: HttpTarget,
+) -> Result<ClientBuilder, reqwest::Error> {
+    let mut builder = Client::builder()
+        .user_agent(APP_USER_AGENT)
+        .pool_idle_timeout(Duration::from_secs(cfg.pool_idle_timeout_secs))
+        .pool_max_idle_per_host(cfg.pool_max_idle_per_host)
+        .tcp_keepalive(cfg.tcp_keepalive_secs.map(Duration::from_secs));
+    if let Some(timeout) = timeouts.http(target) {
+        builder = builder.timeout(timeout);
+    }
+    let proxy_override = cfg.proxy_overrides.get(&target);
+    let proxy_cfg = proxy_override.unwrap_or(&cfg.proxy);
+    let builder = match &proxy_cfg.url {
+        Some(url) => builder.proxy(proxy(url, proxy_cfg)?),
+        None if proxy_override.is_some() => builder.no_proxy(),
+        None => builder,
+    };
+    Ok(if cfg.http2_prior_knowledge {
+        builder.http2_prior_knowledge()
+    } else {
+        builder
+    })
+}
+
+fn read_pem(source: &PemSource) -> Result<Vec<u8>, ClientCertificateError> {
+    match source {
+        PemSource::Path(path) => {
+            std::fs::read(path).map_err(|err| ClientCertificateError::Read(path.clone(), err))
+        }
+        PemSource::Pem(pem) => Ok(pem.as_bytes().to_vec()),
+    }
+}
+
+/// `builder` presenting the configured client certificate, for endpoints requiring mutual TLS
+pub fn with_client_certificate(
+    builder: ClientBuilder,
+    cfg: Option<&ClientCertificateConfig>,
+) -> Result<ClientBuilder, ClientCertificateError> {
+    let Some(cfg) = cfg else {
+        return Ok(builder);
+    };
+    let identity = Identity::from_pkcs8_pem(&read_pem(&cfg.cert)?, &read_pem(&cfg.key)?)?;
+    Ok(builder.identity(identity))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, time::Duration};
+
+    use reqwest::Client;
+
+    use crate::config::{
+        ClientCertificateConfig, HttpClientConfig, HttpTarget, PemSource, ProxyConfig,
+        TimeoutsConfig,
+    };
+
+    use super::{client_builder, with_client_certificate, ClientCertificateError};
+
+    #[test]
+    fn test_client_builder() {
+        let cfg = HttpClientConfig {
+            http2_prior_knowledge: true,
+            ..Default::default()
+        };
+        let timeouts = TimeoutsConfig::default();
+        assert_eq!(
+            timeouts.http(HttpTarget::Embeddings),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(timeouts.http(HttpTarget::Summarization), None);
+        assert!(client_builder(&cfg, &timeouts, HttpTarget::Github)
+            .unwrap()
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_proxy_overrides() {
+        let cfg = HttpClientConfig {
+            proxy: ProxyConfig {
+                url: Some("http://proxy.internal:3128".to_owned()),
+                no_proxy: vec!["localhost".to_owned(), "10.0.0.0/8".to_owned()],
+                username: Some("bot".to_owned()),
+                password: Some("secret".to_owned()),
+            },
+            proxy_overrides: HashMap::from([
+                (HttpTarget::Slack, ProxyConfig::default()),
+                (
+                    HttpTarget::Summarization,
+                    ProxyConfig {
+                        url: Some("socks5h://proxy.internal:1080".to_owned()),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    HttpTarget::Embeddings,
+                    ProxyConfig {
+                        url: Some("not a url".to_owned()),
+                        ..Default::default()
+                    },
+                ),
+            ]),
+            ..Default::default()
+        };
+        let timeouts = TimeoutsConfig::default();
+        assert!(client_builder(&cfg, &timeouts, HttpTarget::Github)
+            .unwrap()
+            .build()
+            .is_ok());
+        assert!(client_builder(&cfg, &timeouts, HttpTarget::Slack)
+            .unwrap()
+            .build()
+            .is_ok());
+        assert!(client_builder(&cfg, &timeouts, HttpTarget::Summarization)
+            .unwrap()
+            .build()
+            .is_ok());
+        // the override replaces the global proxy
+        assert!(client_builder(&cfg, &timeouts, HttpTarget::Embeddings).is_err());
+    }
+
+    #[test]
+    fn test_client_certificate() {
+        assert!(with_client_certificate(Client::builder(), None).is_ok());
+        let cfg = ClientCertificateConfig {
+            cert: PemSource::Path("/nonexistent/client.crt".into()),
+            key: PemSource::Pem("not a key".to_owned()),
+        };
+        assert!(matches!(
+            with_client_certificate(Client::builder(), Some(&cfg)),
+            Err(ClientCertificateError::Read(..))
+        ));
+    }
+}
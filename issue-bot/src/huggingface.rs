@@ -1,21 +1,78 @@
+use std::time::Duration;
+
 use reqwest::{
-    header::{HeaderMap, HeaderValue, AUTHORIZATION},
-    Client,
+    header::{HeaderMap, HeaderValue, AUTHORIZATION, RETRY_AFTER},
+    Client, RequestBuilder, Response, StatusCode,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::{
-    config::{HuggingfaceApiConfig, MessageConfig},
-    ClosestIssue, APP_USER_AGENT,
+    config::{HttpClientConfig, HttpTarget, HuggingfaceApiConfig, TimeoutsConfig},
+    footer::CommentFooter,
+    http_client::client_builder,
+    live_config::LiveConfig,
+    retry::{classify_reqwest, with_retry, Classify, RetryBudget, RetryClass, RetryPolicy},
+    ClosestIssue,
 };
 
 #[derive(Debug, Error)]
 pub enum HuggingfaceApiError {
     #[error("invalid header value: {0}")]
     InvalidHeaderValue(#[from] reqwest::header::InvalidHeaderValue),
+    #[error("rate limited: {source}")]
+    RateLimited {
+        retry_after: Option<Duration>,
+        source: reqwest::Error,
+    },
     #[error("reqwest error: {0}")]
     Reqwest(#[from] reqwest::Error),
+    /// the request failed without an answer and may have gone through, so it isn't retried
+    #[error("request failed without an answer: {0}")]
+    Unanswered(reqwest::Error),
+}
+
+impl Classify for HuggingfaceApiError {
+    fn retry_class(&self) -> RetryClass {
+        match self {
+            Self::InvalidHeaderValue(_) => RetryClass::Fatal,
+            Self::RateLimited { .. } => RetryClass::RateLimited,
+            Self::Reqwest(err) => classify_reqwest(err),
+            Self::Unanswered(_) => RetryClass::Fatal,
+        }
+    }
+
+    fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimited { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+/// user id of `lor-e-bot`, whose comments aren't part of the discussions it answers
+pub const BOT_USER_ID: &str = "67e0825265e294ad98833748";
+
+/// delay before retrying a rate limited request when the hub doesn't say how long to wait
+const DEFAULT_RATE_LIMIT_DELAY: Duration = Duration::from_secs(60);
+
+/// total time a request may spend waiting between its attempts
+const MAX_TOTAL_RETRY_DELAY: Duration = Duration::from_secs(300);
+
+/// key of the hub in the [RetryBudget]
+const HUB_HOST: &str = "huggingface.co";
+
+/// prefix of the discussion urls webhooks carry, replaced by the configured `base_url`
+const HUB_URL: &str = "https://huggingface.co";
+
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
 }
 
 #[derive(Serialize)]
@@ -23,58 +80,389 @@ struct CommentBody {
     comment: String,
 }
 
+#[derive(Deserialize)]
+struct CommentRevision {
+    raw: String,
+}
+
+#[derive(Deserialize)]
+struct DiscussionEventData {
+    #[serde(default)]
+    hidden: bool,
+    #[serde(default)]
+    latest: Option<CommentRevision>,
+}
+
+#[derive(Deserialize)]
+struct HubUser {
+    #[serde(rename = "_id", default)]
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct DiscussionEvent {
+    #[serde(default)]
+    author: Option<HubUser>,
+    #[serde(default)]
+    data: Option<DiscussionEventData>,
+    #[serde(default)]
+    id: Option<i64>,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+#[derive(Deserialize)]
+struct Discussion {
+    #[serde(default)]
+    author: Option<HubUser>,
+    #[serde(default)]
+    events: Vec<DiscussionEvent>,
+    #[serde(default)]
+    status: String,
+    #[serde(default)]
+    title: String,
+}
+
+impl Discussion {
+    fn has_comment(&self, comment: &str) -> bool {
+        self.events
+            .iter()
+            .filter(|event| event.kind == "comment")
+            .filter_map(|event| event.data.as_ref()?.latest.as_ref())
+            .any(|revision| revision.raw.trim() == comment.trim())
+    }
+
+    /// latest revision of every comment left visible, with its event
+    fn comments(&self) -> impl Iterator<Item = (&DiscussionEvent, &CommentRevision)> {
+        self.events
+            .iter()
+            .filter(|event| event.kind == "comment")
+            .filter_map(|event| {
+                let data = event.data.as_ref()?;
+                (!data.hidden).then_some((event, data.latest.as_ref()?))
+            })
+    }
+}
+
+/// Comment of a [HubDiscussion], stored like the ones webhooks carry
+#[derive(Debug, PartialEq)]
+pub struct HubComment {
+    pub id: i64,
+    /// Hub user id of the commenter
+    pub author: Option<String>,
+    pub body: String,
+}
+
+/// Complete discussion as served by the Hub, webhooks only carrying its opening comment when
+/// there is one, see [HuggingfaceApi::discussion]
+#[derive(Debug)]
+pub struct HubDiscussion {
+    /// Hub user id of the author, which opt-outs refer to, see [crate::opt_out]
+    pub author: Option<String>,
+    /// opening comment
+    pub body: String,
+    /// following comments, the bot's and the ones without an id left out
+    pub comments: Vec<HubComment>,
+    /// `open`, `closed`, `merged` or `draft`
+    pub status: String,
+    pub title: String,
+}
+
+impl HubDiscussion {
+    pub fn is_closed(&self) -> bool {
+        matches!(self.status.as_str(), "closed" | "merged")
+    }
+}
+
+impl From<Discussion> for HubDiscussion {
+    fn from(discussion: Discussion) -> Self {
+        let mut comments = discussion.comments();
+        let body = comments
+            .next()
+            .map(|(_, revision)| revision.raw.clone())
+            .unwrap_or_default();
+        let comments = comments
+            .filter_map(|(event, revision)| {
+                let author = event.author.as_ref().map(|author| author.id.clone());
+                (author.as_deref() != Some(BOT_USER_ID)).then_some(HubComment {
+                    id: event.id?,
+                    author,
+                    body: revision.raw.clone(),
+                })
+            })
+            .collect();
+        Self {
+            author: discussion.author.as_ref().map(|author| author.id.clone()),
+            body,
+            comments,
+            status: discussion.status.clone(),
+            title: discussion.title.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct HuggingfaceApi {
+    base_url: String,
     client: Client,
-    comments_enabled: bool,
-    message_config: MessageConfig,
+    footer: CommentFooter,
+    hydrate_discussions: bool,
+    /// holds `comments_enabled` and the message templates
+    live_config: LiveConfig,
+    retry_policy: RetryPolicy,
 }
 
 impl HuggingfaceApi {
     pub fn new(
         cfg: HuggingfaceApiConfig,
-        message_config: MessageConfig,
+        http_cfg: &HttpClientConfig,
+        timeouts: &TimeoutsConfig,
+        live_config: LiveConfig,
+        footer: CommentFooter,
     ) -> Result<Self, HuggingfaceApiError> {
         let mut headers = HeaderMap::new();
         let mut auth_value = HeaderValue::from_str(&format!("Bearer {}", cfg.auth_token))?;
         auth_value.set_sensitive(true);
         headers.insert(AUTHORIZATION, auth_value);
-        let client = Client::builder()
-            .user_agent(APP_USER_AGENT)
+        let client = client_builder(http_cfg, timeouts, HttpTarget::Huggingface)?
             .default_headers(headers)
             .build()?;
 
         Ok(Self {
+            base_url: cfg.base_url.trim_end_matches('/').to_owned(),
             client,
-            comments_enabled: cfg.comments_enabled,
-            message_config,
+            footer,
+            hydrate_discussions: cfg.hydrate_discussions,
+            live_config,
+            retry_policy: RetryPolicy {
+                base_delay: Duration::from_secs(1),
+                budget: RetryBudget::default(),
+                max_total_delay: MAX_TOTAL_RETRY_DELAY,
+                max_retries: cfg.max_retries,
+                rate_limit_delay: DEFAULT_RATE_LIMIT_DELAY,
+            },
         })
     }
 
-    pub async fn comment_on_issue(
+    pub fn hydrates_discussions(&self) -> bool {
+        self.hydrate_discussions
+    }
+
+    /// the budget shared with the other retry loops of the process, a private one otherwise
+    pub fn with_retry_budget(mut self, budget: RetryBudget) -> Self {
+        self.retry_policy.budget = budget;
+        self
+    }
+
+    /// Sends the request built by `request` through [with_retry], retrying timeouts, server errors
+    /// and rate limited requests, the latter after the delay given by the `Retry-After` header
+    ///
+    /// Requests that may have gone through despite failing, e.g. timeouts, are only retried when
+    /// `retry_ambiguous` is set.
+    async fn send(
+        &self,
+        request: impl Fn() -> RequestBuilder,
+        retry_ambiguous: bool,
+    ) -> Result<Response, HuggingfaceApiError> {
+        with_retry(&self.retry_policy, HUB_HOST, || async {
+            let res = match request().send().await {
+                Ok(res) => res,
+                Err(err) if !retry_ambiguous => return Err(HuggingfaceApiError::Unanswered(err)),
+                Err(err) => return Err(err.into()),
+            };
+            if res.status() == StatusCode::TOO_MANY_REQUESTS {
+                return Err(HuggingfaceApiError::RateLimited {
+                    retry_after: retry_after(res.headers()),
+                    source: res.error_for_status().unwrap_err(),
+                });
+            }
+            Ok(res.error_for_status()?)
+        })
+        .await
+    }
+
+    /// body of the comment listing the discussions similar to a new one
+    pub fn suggestions_comment(
+        &self,
+        repository_full_name: &str,
+        closest_issues: &[ClosestIssue],
+        run_id: &str,
+    ) -> String {
+        let issues: Vec<String> = closest_issues
+            .iter()
+            .map(|ci| ci.to_markdown_list_item(repository_full_name))
+            .collect();
+        let live_config = self.live_config.get();
+        format!(
+            "{}{}{}{}",
+            live_config.message_config.pre,
+            issues.join("\n"),
+            live_config.message_config.post,
+            self.footer.render(run_id, closest_issues)
+        )
+    }
+
+    /// body of the comment answering a new discussion from the knowledge base
+    pub fn curated_answer_comment(&self, message: &str, answer: &str, run_id: &str) -> String {
+        format!(
+            "{}{}{}{}",
+            message,
+            answer,
+            self.live_config.get().message_config.post,
+            self.footer.render(run_id, &[])
+        )
+    }
+
+    /// body of the comment pointing to the issue a new one has the same traceback as
+    pub fn duplicate_comment(
+        &self,
+        repository_full_name: &str,
+        duplicate: &ClosestIssue,
+        message: &str,
+        run_id: &str,
+    ) -> String {
+        format!(
+            "{}{}{}",
+            message,
+            duplicate.to_markdown_list_item(repository_full_name),
+            self.footer.render(run_id, std::slice::from_ref(duplicate))
+        )
+    }
+
+    /// `url` of a webhook, pointed at the configured hub
+    fn rebase(&self, url: &str) -> String {
+        match url.strip_prefix(HUB_URL) {
+            Some(path) => format!("{}{path}", self.base_url),
+            None => url.to_owned(),
+        }
+    }
+
+    /// comments failing without an answer aren't retried, they may have been posted, see
+    /// [HuggingfaceApi::has_comment]
+    pub async fn comment(
         &self,
         issue_url: &str,
-        closest_issues: Vec<ClosestIssue>,
+        comment: String,
     ) -> Result<(), HuggingfaceApiError> {
-        if !self.comments_enabled {
+        if !self.live_config.get().huggingface_comments_enabled {
             return Ok(());
         }
 
-        let comment_url = format!("{issue_url}/comment");
-        let issues: Vec<String> = closest_issues
-            .into_iter()
-            .map(|i| format!("- {} ([#{}]({}))", i.title, i.number, i.html_url))
-            .collect();
-        let comment = format!(
-            "{}{}{}",
-            self.message_config.pre,
-            issues.join("\n"),
-            self.message_config.post
-        );
-        self.client
-            .post(comment_url)
-            .json(&CommentBody { comment })
-            .send()
+        let comment_url = format!("{}/comment", self.rebase(issue_url));
+        let body = CommentBody { comment };
+        self.send(|| self.client.post(&comment_url).json(&body), false)
             .await?;
         Ok(())
     }
+
+    /// whether the discussion of api url `issue_url` already has `comment`
+    pub async fn has_comment(
+        &self,
+        issue_url: &str,
+        comment: &str,
+    ) -> Result<bool, HuggingfaceApiError> {
+        let issue_url = self.rebase(issue_url);
+        let discussion: Discussion = self
+            .send(|| self.client.get(&issue_url), true)
+            .await?
+            .json()
+            .await?;
+        Ok(discussion.has_comment(comment))
+    }
+
+    /// the discussion of api url `issue_url`, with every comment
+    pub async fn discussion(&self, issue_url: &str) -> Result<HubDiscussion, HuggingfaceApiError> {
+        let issue_url = self.rebase(issue_url);
+        let discussion: Discussion = self
+            .send(|| self.client.get(&issue_url), true)
+            .await?
+            .json()
+            .await?;
+        Ok(discussion.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+
+    use super::{retry_after, Discussion, HubComment, HubDiscussion, BOT_USER_ID};
+
+    #[test]
+    fn test_discussion_has_comment() {
+        let discussion: Discussion = serde_json::from_value(serde_json::json!({
+            "events": [
+                { "type": "status-change", "data": { "status": "open" } },
+                { "type": "comment", "data": { "latest": { "raw": "Similar discussions:\n- #1\n" } } },
+            ]
+        }))
+        .unwrap();
+        assert!(discussion.has_comment("Similar discussions:\n- #1"));
+        assert!(!discussion.has_comment("Similar discussions:\n- #2"));
+    }
+
+    #[test]
+    fn test_hub_discussion() {
+        let discussion: Discussion = serde_json::from_value(serde_json::json!({
+            "author": { "_id": "1", "name": "jane" },
+            "status": "open",
+            "title": "Tokenizer crashes on empty input",
+            "events": [
+                {
+                    "type": "comment",
+                    "id": 10,
+                    "author": { "_id": "1", "name": "jane" },
+                    "data": { "hidden": false, "latest": { "raw": "It panics." } }
+                },
+                {
+                    "type": "comment",
+                    "id": 11,
+                    "author": { "_id": BOT_USER_ID, "name": "lor-e-bot" },
+                    "data": { "hidden": false, "latest": { "raw": "Similar discussions:" } }
+                },
+                {
+                    "type": "comment",
+                    "id": 12,
+                    "author": { "_id": "2", "name": "spammer" },
+                    "data": { "hidden": true, "latest": { "raw": "buy now" } }
+                },
+                { "type": "status-change", "data": { "status": "open" } },
+                {
+                    "type": "comment",
+                    "id": 13,
+                    "author": { "_id": "3", "name": "john" },
+                    "data": { "hidden": false, "latest": { "raw": "Same here on 0.21." } }
+                },
+            ]
+        }))
+        .unwrap();
+        let discussion = HubDiscussion::from(discussion);
+        assert_eq!(discussion.author.as_deref(), Some("1"));
+        assert_eq!(discussion.body, "It panics.");
+        assert_eq!(
+            discussion.comments,
+            vec![HubComment {
+                id: 13,
+                author: Some("3".to_owned()),
+                body: "Same here on 0.21.".to_owned(),
+            }]
+        );
+        assert!(!discussion.is_closed());
+        assert_eq!(discussion.title, "Tokenizer crashes on empty input");
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("42"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(42)));
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(retry_after(&headers), None);
+    }
 }
@@ -0,0 +1,200 @@
+use std::{collections::HashMap, fmt::Display};
+
+use regex::Regex;
+
+use crate::config::{IgnoreRuleSet, IgnoreRulesConfig};
+
+#[derive(Clone, Debug, Default)]
+struct Rules {
+    authors: Vec<String>,
+    labels: Vec<String>,
+    repositories: Vec<Regex>,
+    titles: Vec<Regex>,
+}
+
+impl Rules {
+    fn new(cfg: &IgnoreRuleSet) -> Result<Self, regex::Error> {
+        Ok(Self {
+            authors: cfg.authors.clone(),
+            labels: cfg.labels.clone(),
+            repositories: cfg
+                .repositories
+                .iter()
+                .map(|r| Regex::new(r))
+                .collect::<Result<_, _>>()?,
+            titles: cfg
+                .titles
+                .iter()
+                .map(|r| Regex::new(r))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    fn ignore_reason(&self, event: &EventMetadata) -> Option<IgnoreReason> {
+        if let Some(author) = event
+            .authors
+            .iter()
+            .find(|a| self.authors.iter().any(|ignored| ignored == *a))
+        {
+            return Some(IgnoreReason::Author(author.to_string()));
+        }
+        if let Some(label) = event
+            .labels
+            .iter()
+            .find(|l| self.labels.iter().any(|ignored| ignored == *l))
+        {
+            return Some(IgnoreReason::Label(label.to_string()));
+        }
+        if let Some(re) = self
+            .repositories
+            .iter()
+            .find(|re| re.is_match(event.repository))
+        {
+            return Some(IgnoreReason::Repository(re.to_string()));
+        }
+        if let Some(re) = self.titles.iter().find(|re| re.is_match(event.title)) {
+            return Some(IgnoreReason::Title(re.to_string()));
+        }
+        None
+    }
+}
+
+/// Fields of an incoming event that ignore rules are matched against
+pub struct EventMetadata<'a> {
+    /// issue author and, for comment events, the comment author
+    pub authors: Vec<&'a str>,
+    pub labels: Vec<&'a str>,
+    pub repository: &'a str,
+    pub title: &'a str,
+}
+
+#[derive(Debug)]
+pub enum IgnoreReason {
+    Author(String),
+    Label(String),
+    Repository(String),
+    Title(String),
+}
+
+impl Display for IgnoreReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Author(author) => write!(f, "author '{author}' is ignored"),
+            Self::Label(label) => write!(f, "label '{label}' is ignored"),
+            Self::Repository(re) => write!(f, "repository matches '{re}'"),
+            Self::Title(re) => write!(f, "title matches '{re}'"),
+        }
+    }
+}
+
+/// Rules applied in the webhook handlers to drop events before they get enqueued, only
+/// creations and edits are dropped so already stored issues keep receiving the rest
+#[derive(Clone, Debug, Default)]
+pub struct IgnoreRules {
+    global: Rules,
+    repositories: HashMap<String, Rules>,
+}
+
+impl IgnoreRules {
+    pub fn new(cfg: &IgnoreRulesConfig) -> Result<Self, regex::Error> {
+        Ok(Self {
+            global: Rules::new(&cfg.global)?,
+            repositories: cfg
+                .repositories
+                .iter()
+                .map(|(repo, rules)| Ok((repo.to_owned(), Rules::new(rules)?)))
+                .collect::<Result<_, regex::Error>>()?,
+        })
+    }
+
+    /// returns `Some` with the first matching rule if the event should be ignored
+    pub fn ignore_reason(&self, event: &EventMetadata) -> Option<IgnoreReason> {
+        self.global.ignore_reason(event).or_else(|| {
+            self.repositories
+                .get(event.repository)
+                .and_then(|rules| rules.ignore_reason(event))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::config::{IgnoreRuleSet, IgnoreRulesConfig};
+
+    use super::{EventMetadata, IgnoreReason, IgnoreRules};
+
+    #[test]
+    fn test_ignore_rules() {
+        let rules = IgnoreRules::new(&IgnoreRulesConfig {
+            global: IgnoreRuleSet {
+                authors: vec!["dependabot[bot]".to_owned()],
+                repositories: vec!["^huggingface/private-.*".to_owned()],
+                ..Default::default()
+            },
+            repositories: HashMap::from([(
+                "huggingface/transformers".to_owned(),
+                IgnoreRuleSet {
+                    labels: vec!["wontfix".to_owned()],
+                    titles: vec![r"^Release v\d+".to_owned()],
+                    ..Default::default()
+                },
+            )]),
+        })
+        .unwrap();
+
+        let event = |authors, labels, repository, title| EventMetadata {
+            authors,
+            labels,
+            repository,
+            title,
+        };
+
+        assert!(matches!(
+            rules.ignore_reason(&event(
+                vec!["dependabot[bot]"],
+                vec![],
+                "huggingface/lor-e",
+                "Bump serde"
+            )),
+            Some(IgnoreReason::Author(_))
+        ));
+        assert!(matches!(
+            rules.ignore_reason(&event(
+                vec!["me"],
+                vec![],
+                "huggingface/private-repo",
+                "bug"
+            )),
+            Some(IgnoreReason::Repository(_))
+        ));
+        assert!(matches!(
+            rules.ignore_reason(&event(
+                vec!["me"],
+                vec!["wontfix"],
+                "huggingface/transformers",
+                "bug"
+            )),
+            Some(IgnoreReason::Label(_))
+        ));
+        assert!(matches!(
+            rules.ignore_reason(&event(
+                vec!["me"],
+                vec![],
+                "huggingface/transformers",
+                "Release v4.50.0"
+            )),
+            Some(IgnoreReason::Title(_))
+        ));
+        // per repository rules must not leak to other repositories
+        assert!(rules
+            .ignore_reason(&event(
+                vec!["me"],
+                vec!["wontfix"],
+                "huggingface/lor-e",
+                "Release v1"
+            ))
+            .is_none());
+    }
+}
@@ -0,0 +1,121 @@
+use std::time::Duration;
+
+use futures::pin_mut;
+use serde::{Deserialize, Serialize};
+use tokio::{select, time::sleep};
+use tracing::{error, info, warn};
+
+use crate::{
+    hot_issues::HotIssues,
+    settings::Settings,
+    shutdown_signal,
+    storage::{Database, Storage, StorageError},
+};
+
+/// Postgres channel instances notify each other of their updates on
+const CHANNEL: &str = "issue_bot_invalidations";
+/// pause before listening again after the listener failed
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Cached value an instance updated, sent as the payload of a notification
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "cache", rename_all = "snake_case")]
+pub enum Invalidation {
+    /// settings stored under `key`, see [Settings]
+    Setting { key: String },
+    /// issue cached by [HotIssues], e.g. after it was edited
+    HotIssue { source_id: i64 },
+    /// issue cached by [HotIssues] whose number only is known, e.g. after it was gone upstream
+    HotIssueNumber {
+        repository_full_name: String,
+        number: i32,
+    },
+    /// every issue cached by [HotIssues]
+    HotIssues,
+}
+
+/// notifies every instance, this one included, that `invalidation` is stale
+pub async fn publish(db: &Database, invalidation: &Invalidation) -> Result<(), StorageError> {
+    db.notify(CHANNEL, &serde_json::to_string(invalidation)?)
+        .await
+}
+
+fn apply(settings: &Settings, hot_issues: &HotIssues, payload: &str) {
+    match serde_json::from_str(payload) {
+        Ok(Invalidation::Setting { key }) => settings.invalidate(Some(&key)),
+        Ok(invalidation) => hot_issues.apply(&invalidation),
+        Err(err) => warn!(
+            payload,
+            err = err.to_string(),
+            "ignoring malformed invalidation"
+        ),
+    }
+}
+
+/// Drops the cached values other instances updated as soon as they notify it, so that updates
+/// made through one instance's admin API apply to all of them within seconds
+///
+/// Every cached value is dropped when the connection is lost, the notifications sent meanwhile
+/// being lost too. Only runs on Postgres, SQLite databases not being shared.
+pub async fn start_invalidation_listener(
+    db: Database,
+    settings: Settings,
+    hot_issues: HotIssues,
+) -> anyhow::Result<()> {
+    let Some(mut listener) = db.listen(CHANNEL).await? else {
+        info!("not listening for invalidations, the database isn't shared");
+        return Ok(());
+    };
+    info!("starting invalidation listener");
+    let shutdown = shutdown_signal();
+    pin_mut!(shutdown);
+    loop {
+        select! {
+            notification = listener.try_recv() => match notification {
+                Ok(Some(notification)) => apply(&settings, &hot_issues, notification.payload()),
+                Ok(None) => {
+                    warn!("invalidation listener lost its connection, dropping cached values");
+                    settings.invalidate(None);
+                    hot_issues.apply(&Invalidation::HotIssues);
+                }
+                Err(err) => {
+                    error!(err = err.to_string(), "invalidation listener error");
+                    settings.invalidate(None);
+                    hot_issues.apply(&Invalidation::HotIssues);
+                    sleep(RETRY_DELAY).await;
+                }
+            },
+            _ = &mut shutdown => break,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Invalidation;
+
+    #[test]
+    fn test_invalidation_payload() {
+        let invalidation = Invalidation::Setting {
+            key: "similarity:huggingface/lor-e".to_owned(),
+        };
+        let payload = serde_json::to_string(&invalidation).unwrap();
+        assert_eq!(
+            payload,
+            r#"{"cache":"setting","key":"similarity:huggingface/lor-e"}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<Invalidation>(&payload).unwrap(),
+            invalidation
+        );
+        assert_eq!(
+            serde_json::to_string(&Invalidation::HotIssue { source_id: 42 }).unwrap(),
+            r#"{"cache":"hot_issue","source_id":42}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&Invalidation::HotIssues).unwrap(),
+            r#"{"cache":"hot_issues"}"#
+        );
+    }
+}
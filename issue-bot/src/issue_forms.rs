@@ -0,0 +1,115 @@
+/// value GitHub renders for the optional fields left empty
+const NO_RESPONSE: &str = "_No response_";
+
+/// Known fields of issues created from GitHub issue forms, rendered as `### <label>` sections
+#[derive(Debug, Default, PartialEq)]
+pub struct IssueForm {
+    /// the bug itself, e.g. "Describe the bug" or "What happened?"
+    pub description: Option<String>,
+    pub reproduction: Option<String>,
+    pub expected_behavior: Option<String>,
+    pub environment: Option<String>,
+}
+
+/// field of [IssueForm] a section label stands for, matched on keywords as the labels vary
+/// across repositories
+fn field<'a>(form: &'a mut IssueForm, label: &str) -> Option<&'a mut Option<String>> {
+    let label = label.to_lowercase();
+    let has = |keywords: &[&str]| keywords.iter().any(|k| label.contains(k));
+    // "expected" first, "expected behavior" also containing "behavior"
+    if has(&["expected"]) {
+        Some(&mut form.expected_behavior)
+    } else if has(&["reproduc", "steps to", "minimal example"]) {
+        Some(&mut form.reproduction)
+    } else if has(&[
+        "system info",
+        "environment",
+        "system information",
+        "versions",
+    ]) {
+        Some(&mut form.environment)
+    } else if has(&[
+        "describe the bug",
+        "bug description",
+        "description",
+        "what happened",
+        "current behavior",
+        "actual behavior",
+    ]) {
+        Some(&mut form.description)
+    } else {
+        None
+    }
+}
+
+impl IssueForm {
+    /// fields of `body`, `None` when it has none of the known sections
+    pub fn parse(body: &str) -> Option<Self> {
+        let mut sections: Vec<(&str, Vec<&str>)> = Vec::new();
+        for line in body.lines() {
+            match line.strip_prefix("### ") {
+                Some(label) => sections.push((label.trim(), Vec::new())),
+                None => {
+                    if let Some((_, lines)) = sections.last_mut() {
+                        lines.push(line);
+                    }
+                }
+            }
+        }
+        let mut form = Self::default();
+        for (label, lines) in sections {
+            let value = lines.join("\n").trim().to_owned();
+            if value.is_empty() || value == NO_RESPONSE {
+                continue;
+            }
+            if let Some(field) = field(&mut form, label) {
+                // the first section wins, e.g. over a later "Additional description"
+                field.get_or_insert(value);
+            }
+        }
+        (form != Self::default()).then_some(form)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IssueForm;
+
+    #[test]
+    fn test_parse() {
+        let body = r#"### Describe the bug
+
+Loading the tokenizer hangs.
+
+### Reproduction
+
+```python
+AutoTokenizer.from_pretrained("gpt2")
+```
+
+### Expected behavior
+
+It loads.
+
+### Logs
+
+_No response_
+
+### System info
+
+transformers 4.45.0
+"#;
+        assert_eq!(
+            IssueForm::parse(body),
+            Some(IssueForm {
+                description: Some("Loading the tokenizer hangs.".to_owned()),
+                reproduction: Some(
+                    "```python\nAutoTokenizer.from_pretrained(\"gpt2\")\n```".to_owned()
+                ),
+                expected_behavior: Some("It loads.".to_owned()),
+                environment: Some("transformers 4.45.0".to_owned()),
+            })
+        );
+        assert_eq!(IssueForm::parse("It hangs.\n\n### Logs\n\nnothing"), None);
+    }
+}
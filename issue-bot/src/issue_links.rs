@@ -0,0 +1,193 @@
+use std::collections::{HashMap, HashSet};
+
+use thiserror::Error;
+use tracing::error;
+
+use crate::{
+    config::IssueLinksConfig,
+    debug::DebugState,
+    github::{GithubApi, GithubApiError},
+    storage::{Database, IssueLink, IssueLinkKind, Storage, StorageError},
+    ClosestIssue,
+};
+
+#[derive(Debug, Error)]
+pub enum IssueLinksError {
+    #[error("github api error: {0}")]
+    GithubApi(#[from] GithubApiError),
+    #[error("storage error: {0}")]
+    Storage(#[from] StorageError),
+}
+
+/// Adds `boost` to the rank of the candidates linked to another one of the candidates, keeping
+/// them ordered by decreasing [ClosestIssue::rank_score]
+///
+/// `links` are keyed by repository full name.
+fn boost_linked_candidates(
+    mut candidates: Vec<ClosestIssue>,
+    links: &HashMap<String, Vec<IssueLink>>,
+    boost: f64,
+) -> Vec<ClosestIssue> {
+    let linked: HashSet<(&str, i32)> = links
+        .iter()
+        .flat_map(|(repository, links)| {
+            links.iter().flat_map(move |l| {
+                [
+                    (repository.as_str(), l.number),
+                    (repository.as_str(), l.linked_number),
+                ]
+            })
+        })
+        .collect();
+    for candidate in &mut candidates {
+        if linked.contains(&(candidate.repository_full_name.as_str(), candidate.number)) {
+            candidate.boost += boost;
+        }
+    }
+    candidates.sort_by(|a, b| b.rank_score().total_cmp(&a.rank_score()));
+    candidates
+}
+
+/// Cross-references, fixes and duplicates between issues, ingested from their GitHub timeline
+#[derive(Clone)]
+pub struct IssueLinks {
+    cfg: IssueLinksConfig,
+    db: Database,
+    debug_state: DebugState,
+    github_api: GithubApi,
+}
+
+impl IssueLinks {
+    pub fn new(
+        cfg: IssueLinksConfig,
+        db: Database,
+        debug_state: DebugState,
+        github_api: GithubApi,
+    ) -> Self {
+        Self {
+            cfg,
+            db,
+            debug_state,
+            github_api,
+        }
+    }
+
+    /// whether repository indexation also ingests the timeline of every issue
+    pub fn ingest_on_index(&self) -> bool {
+        self.cfg.ingest_on_index
+    }
+
+    pub async fn ingest(
+        &self,
+        repository_full_name: &str,
+        number: i32,
+    ) -> Result<(), IssueLinksError> {
+        let links = self
+            .github_api
+            .timeline_links(repository_full_name, number)
+            .await?;
+        self.db
+            .insert_issue_links(repository_full_name, &links)
+            .await?;
+        Ok(())
+    }
+
+    /// issue numbers to the earlier issues they are linked to, for [crate::evaluation]
+    ///
+    /// Links to pull requests are left out, suggestions only being made among issues.
+    pub async fn ground_truth(
+        &self,
+        repository_full_name: &str,
+    ) -> Result<HashMap<i32, HashSet<i32>>, StorageError> {
+        let mut ground_truth: HashMap<i32, HashSet<i32>> = HashMap::new();
+        for link in self.db.issue_links(repository_full_name, None).await? {
+            if link.kind == IssueLinkKind::FixedBy {
+                continue;
+            }
+            let (later, earlier) = if link.linked_number < link.number {
+                (link.number, link.linked_number)
+            } else {
+                (link.linked_number, link.number)
+            };
+            ground_truth.entry(later).or_default().insert(earlier);
+        }
+        Ok(ground_truth)
+    }
+
+    /// boosts candidates historically linked to other candidates, returning them untouched when
+    /// the boost is disabled or the links unavailable
+    pub async fn boost_linked(&self, candidates: Vec<ClosestIssue>) -> Vec<ClosestIssue> {
+        if self.cfg.boost == 0. || candidates.len() < 2 {
+            return candidates;
+        }
+        let mut numbers: HashMap<String, Vec<i32>> = HashMap::new();
+        for candidate in &candidates {
+            numbers
+                .entry(candidate.repository_full_name.clone())
+                .or_default()
+                .push(candidate.number);
+        }
+        let mut links = HashMap::new();
+        for (repository, numbers) in numbers {
+            match self.db.issue_links(&repository, Some(&numbers)).await {
+                Ok(repository_links) => {
+                    links.insert(repository, repository_links);
+                }
+                Err(err) => {
+                    self.debug_state.record_error("database", &err);
+                    error!(
+                        repository,
+                        err = err.to_string(),
+                        "failed to fetch issue links"
+                    );
+                    return candidates;
+                }
+            }
+        }
+        boost_linked_candidates(candidates, &links, self.cfg.boost)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        storage::{IssueLink, IssueLinkKind},
+        ClosestIssue,
+    };
+
+    use super::boost_linked_candidates;
+
+    fn candidate(number: i32, cosine_similarity: f64) -> ClosestIssue {
+        ClosestIssue {
+            title: format!("issue {number}"),
+            number,
+            html_url: format!("https://github.com/huggingface/lor-e/issues/{number}"),
+            labels: vec![],
+            repository_full_name: "huggingface/lor-e".to_owned(),
+            cosine_similarity,
+            boost: 0.,
+            embedding: vec![],
+        }
+    }
+
+    #[test]
+    fn test_boost_linked_candidates() {
+        let links = HashMap::from([(
+            "huggingface/lor-e".to_owned(),
+            vec![IssueLink {
+                number: 3,
+                linked_number: 2,
+                kind: IssueLinkKind::Duplicate,
+            }],
+        )]);
+        let candidates = vec![candidate(1, 0.9), candidate(2, 0.85), candidate(3, 0.82)];
+
+        let boosted = boost_linked_candidates(candidates, &links, 0.1);
+
+        let numbers: Vec<i32> = boosted.iter().map(|c| c.number).collect();
+        assert_eq!(numbers, vec![2, 3, 1]);
+        assert_eq!(boosted[0].cosine_similarity, 0.85);
+    }
+}
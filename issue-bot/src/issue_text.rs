@@ -0,0 +1,196 @@
+use std::sync::Arc;
+
+use sha2::{Digest, Sha256};
+
+use crate::{
+    code_context::find_file_references,
+    config::{IssueTextConfig, IssueTextStrategy},
+    issue_forms::IssueForm,
+};
+
+pub(crate) const COMMENT_SEPARATOR: &str = "\n----\nComment: ";
+
+/// Assembles the text an issue is embedded from, according to the configured strategy
+#[derive(Clone)]
+pub struct IssueTextComposer {
+    description_repeats: usize,
+    embedding_template: Option<Arc<str>>,
+    strategy: IssueTextStrategy,
+}
+
+/// directory of the first file mentioned in `body`, e.g. `src/transformers/models/llama`
+fn component(body: &str) -> Option<String> {
+    let reference = find_file_references(body, 1).pop()?;
+    reference
+        .path
+        .rsplit_once('/')
+        .map(|(dir, _)| dir.to_owned())
+}
+
+impl IssueTextComposer {
+    pub fn new(cfg: &IssueTextConfig) -> Self {
+        Self {
+            description_repeats: cfg.description_repeats,
+            embedding_template: cfg.embedding_template.as_deref().map(Into::into),
+            strategy: cfg.strategy,
+        }
+    }
+
+    /// `text` as composed by [Self::compose], with the bug description of issue forms repeated
+    /// and wrapped in the configured metadata template, if any
+    pub fn embedding_input(
+        &self,
+        mut text: String,
+        repository_full_name: &str,
+        labels: &[String],
+        body: &str,
+    ) -> String {
+        if self.description_repeats > 1 {
+            if let Some(description) = IssueForm::parse(body).and_then(|form| form.description) {
+                for _ in 1..self.description_repeats {
+                    text.push('\n');
+                    text.push_str(&description);
+                }
+            }
+        }
+        let Some(template) = &self.embedding_template else {
+            return text;
+        };
+        // `{text}` last so that placeholders in the issue itself are left alone
+        template
+            .replace("{repository}", repository_full_name)
+            .replace("{labels}", &labels.join(", "))
+            .replace("{component}", &component(body).unwrap_or_default())
+            .replace("{text}", &text)
+    }
+
+    /// `query` wrapped in the metadata template like the issues it is compared with, `{labels}`
+    /// and `{component}` being left empty unless the query mentions a file
+    pub fn query_input(&self, query: &str, repository_full_name: &str) -> String {
+        self.embedding_input(query.to_owned(), repository_full_name, &[], query)
+    }
+
+    /// whether label changes alter the embedding input, see [Self::embedding_input]
+    pub fn embeds_labels(&self) -> bool {
+        self.embedding_template
+            .as_deref()
+            .is_some_and(|template| template.contains("{labels}"))
+    }
+
+    /// short hash of the metadata template, recorded with the embeddings so that those of an
+    /// older template can be told apart, see [crate::embeddings::EmbeddingMetadata]
+    pub fn template_version(&self) -> Option<String> {
+        let template = self.embedding_template.as_deref()?;
+        Some(hex::encode(&Sha256::digest(template.as_bytes())[..6]))
+    }
+
+    /// `comments` are expected in chronological order
+    pub fn compose<S: AsRef<str>>(&self, title: &str, body: &str, comments: &[S]) -> String {
+        let (title_repeats, comments) = match self.strategy {
+            IssueTextStrategy::AcceptedAnswer => (1, &comments[comments.len().saturating_sub(1)..]),
+            IssueTextStrategy::AllComments => (1, comments),
+            IssueTextStrategy::FirstComments { count } => {
+                (1, &comments[..count.min(comments.len())])
+            }
+            IssueTextStrategy::TitleBody => (1, &[][..]),
+            IssueTextStrategy::WeightedTitle { repeats } => (repeats.max(1), &[][..]),
+        };
+        let mut text = vec![format!("# {}", title); title_repeats].join("\n");
+        text.push('\n');
+        text.push_str(body);
+        for comment in comments {
+            text.push_str(COMMENT_SEPARATOR);
+            text.push_str(comment.as_ref());
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{IssueTextConfig, IssueTextStrategy};
+
+    use super::IssueTextComposer;
+
+    #[test]
+    fn test_compose() {
+        let compose = |strategy| {
+            IssueTextComposer::new(&IssueTextConfig {
+                strategy,
+                ..Default::default()
+            })
+            .compose("Title", "Body", &["first", "second", "fix"])
+        };
+
+        assert_eq!(
+            compose(IssueTextStrategy::AllComments),
+            "# Title\nBody\n----\nComment: first\n----\nComment: second\n----\nComment: fix"
+        );
+        assert_eq!(compose(IssueTextStrategy::TitleBody), "# Title\nBody");
+        assert_eq!(
+            compose(IssueTextStrategy::FirstComments { count: 1 }),
+            "# Title\nBody\n----\nComment: first"
+        );
+        assert_eq!(
+            compose(IssueTextStrategy::AcceptedAnswer),
+            "# Title\nBody\n----\nComment: fix"
+        );
+        assert_eq!(
+            compose(IssueTextStrategy::WeightedTitle { repeats: 2 }),
+            "# Title\n# Title\nBody"
+        );
+    }
+
+    #[test]
+    fn test_embedding_input() {
+        let composer = IssueTextComposer::new(&IssueTextConfig {
+            embedding_template: Some(
+                "Repository: {repository}\nLabels: {labels}\nComponent: {component}\n{text}"
+                    .to_owned(),
+            ),
+            ..Default::default()
+        });
+        let body = "Crash in src/transformers/models/llama/modeling_llama.py:42 with {labels}";
+        let text = composer.compose::<&str>("Title", body, &[]);
+        assert_eq!(
+            composer.embedding_input(
+                text.clone(),
+                "huggingface/transformers",
+                &["bug".to_owned(), "llama".to_owned()],
+                body,
+            ),
+            format!(
+                "Repository: huggingface/transformers\nLabels: bug, llama\n\
+                Component: src/transformers/models/llama\n{text}"
+            )
+        );
+        assert_eq!(
+            IssueTextComposer::new(&IssueTextConfig::default()).embedding_input(
+                text.clone(),
+                "huggingface/transformers",
+                &[],
+                body,
+            ),
+            text
+        );
+        assert_eq!(
+            composer.query_input("tokenizer hangs", "huggingface/transformers"),
+            "Repository: huggingface/transformers\nLabels: \nComponent: \ntokenizer hangs"
+        );
+        assert!(composer.embeds_labels());
+        assert_eq!(composer.template_version().map(|v| v.len()), Some(12));
+        assert!(IssueTextComposer::new(&IssueTextConfig::default())
+            .template_version()
+            .is_none());
+
+        let weighted = IssueTextComposer::new(&IssueTextConfig {
+            description_repeats: 2,
+            ..Default::default()
+        });
+        let body = "### Describe the bug\n\nIt hangs.\n\n### Expected behavior\n\nIt loads.";
+        assert_eq!(
+            weighted.embedding_input(body.to_owned(), "huggingface/transformers", &[], body),
+            format!("{body}\nIt hangs.")
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use tracing::{error, info};
+
+use crate::{
+    debug::DebugState,
+    settings::Settings,
+    storage::{Database, JobData, JobState, JobType, Storage},
+    EventData, RepositoryData,
+};
+
+/// whether a job should stop at this checkpoint, because it was paused or maintenance mode is on,
+/// its progress being saved already
+///
+/// Errors are logged and let the job go on.
+pub async fn should_stop(
+    db: &Database,
+    settings: &Settings,
+    debug_state: &DebugState,
+    job_type: JobType,
+    repository_full_name: Option<&str>,
+) -> bool {
+    match settings.maintenance().await {
+        Ok(true) => {
+            info!("maintenance mode on, stopping job");
+            return true;
+        }
+        Ok(false) => (),
+        Err(err) => {
+            debug_state.record_error("database", &err);
+            error!(err = err.to_string(), "failed to check maintenance mode");
+        }
+    }
+    match db.is_job_paused(job_type, repository_full_name).await {
+        Ok(true) => {
+            info!("job paused, stopping");
+            true
+        }
+        Ok(false) => false,
+        Err(err) => {
+            debug_state.record_error("database", &err);
+            error!(
+                err = err.to_string(),
+                "failed to check whether job is paused"
+            );
+            false
+        }
+    }
+}
+
+/// event resuming `job` from its last checkpoint
+pub(crate) fn resume_event(job: &JobState) -> EventData {
+    match &job.data {
+        JobData::IssueIndexation { source, .. } => {
+            EventData::RepositoryIndexation(RepositoryData {
+                full_name: job.repository_full_name.clone().unwrap_or_default(),
+                source: source.clone(),
+            })
+        }
+        JobData::EmbeddingsRegeneration { .. } => EventData::RegenerateEmbeddings,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        storage::{JobData, JobType, Storage},
+        test_harness::test_db,
+        Source,
+    };
+
+    #[tokio::test]
+    async fn test_pause() {
+        let db = test_db().await;
+        let repository = Some("huggingface/lor-e");
+        assert!(!db
+            .is_job_paused(JobType::IssueIndexation, repository)
+            .await
+            .unwrap());
+        db.save_job(
+            JobType::IssueIndexation,
+            repository,
+            &JobData::IssueIndexation {
+                next_url: "https://api.github.com/repos/huggingface/lor-e/issues?page=2".to_owned(),
+                source: Source::Github,
+            },
+        )
+        .await
+        .unwrap();
+        let id = db.jobs().await.unwrap()[0].id;
+        assert!(db.set_job_paused(id + 1, true).await.unwrap().is_none());
+
+        let job = db.set_job_paused(id, true).await.unwrap().unwrap();
+        assert!(job.paused);
+        assert!(db
+            .is_job_paused(JobType::IssueIndexation, repository)
+            .await
+            .unwrap());
+        // progress being saved doesn't resume the job
+        db.save_job(
+            JobType::IssueIndexation,
+            repository,
+            &JobData::IssueIndexation {
+                next_url: "https://api.github.com/repos/huggingface/lor-e/issues?page=3".to_owned(),
+                source: Source::Github,
+            },
+        )
+        .await
+        .unwrap();
+        assert!(db.jobs().await.unwrap()[0].paused);
+
+        db.set_job_paused(id, false).await.unwrap();
+        assert!(!db
+            .is_job_paused(JobType::IssueIndexation, repository)
+            .await
+            .unwrap());
+    }
+}
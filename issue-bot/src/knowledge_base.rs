@@ -0,0 +1,156 @@
+use thiserror::Error;
+
+use crate::{
+    config::KnowledgeBaseConfig,
+    embeddings::{
+        queue::{EmbeddingQueue, Priority},
+        EmbeddingError,
+    },
+    storage::{Database, KnowledgeBaseEntry, Storage, StorageError},
+};
+
+#[derive(Debug, Error)]
+pub enum KnowledgeBaseError {
+    #[error("embedding error: {0}")]
+    Embedding(#[from] EmbeddingError),
+    #[error("storage error: {0}")]
+    Storage(#[from] StorageError),
+}
+
+/// Curated answers to frequently asked questions, matched against new issues by embedding
+/// similarity to the entries' questions
+#[derive(Clone)]
+pub struct KnowledgeBase {
+    cfg: KnowledgeBaseConfig,
+    db: Database,
+    embedding_queue: EmbeddingQueue,
+}
+
+impl KnowledgeBase {
+    pub fn new(cfg: KnowledgeBaseConfig, db: Database, embedding_queue: EmbeddingQueue) -> Self {
+        Self {
+            cfg,
+            db,
+            embedding_queue,
+        }
+    }
+
+    /// entries without `repository_full_name` apply to every repository, returns the new entry's id
+    pub async fn add(
+        &self,
+        repository_full_name: Option<&str>,
+        question: &str,
+        answer: &str,
+    ) -> Result<i32, KnowledgeBaseError> {
+        let embedding = self
+            .embedding_queue
+            .generate_embedding(question.to_owned(), Priority::Interactive)
+            .await?;
+        let id = self
+            .db
+            .insert_knowledge_base_entry(repository_full_name, question, answer, &embedding)
+            .await?;
+        Ok(id)
+    }
+
+    /// re-embeds the question, returns false if there was no entry with this id
+    pub async fn update(
+        &self,
+        id: i32,
+        repository_full_name: Option<&str>,
+        question: &str,
+        answer: &str,
+    ) -> Result<bool, KnowledgeBaseError> {
+        let embedding = self
+            .embedding_queue
+            .generate_embedding(question.to_owned(), Priority::Interactive)
+            .await?;
+        let updated = self
+            .db
+            .update_knowledge_base_entry(id, repository_full_name, question, answer, &embedding)
+            .await?;
+        Ok(updated)
+    }
+
+    /// introduces curated answers in comments
+    pub fn message(&self) -> &str {
+        &self.cfg.message
+    }
+
+    pub async fn list(&self) -> Result<Vec<KnowledgeBaseEntry>, StorageError> {
+        self.db.knowledge_base_entries().await
+    }
+
+    /// returns false if there was no entry with this id
+    pub async fn delete(&self, id: i32) -> Result<bool, StorageError> {
+        self.db.delete_knowledge_base_entry(id).await
+    }
+
+    /// entry answering the issue embedded as `embedding`, if one is similar enough
+    pub async fn answer_for(
+        &self,
+        embedding: &[f32],
+        repository_full_name: &str,
+    ) -> Result<Option<KnowledgeBaseEntry>, StorageError> {
+        Ok(self
+            .db
+            .closest_knowledge_base_entry(embedding, repository_full_name)
+            .await?
+            .filter(|m| m.cosine_similarity >= self.cfg.similarity_threshold)
+            .map(|m| m.entry))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{storage::Storage, test_harness::test_db};
+
+    #[tokio::test]
+    async fn test_closest_entry_scoped_to_repository() {
+        let db = test_db().await;
+        db.insert_knowledge_base_entry(None, "how to install?", "pip install", &[1., 0.])
+            .await
+            .unwrap();
+        db.insert_knowledge_base_entry(
+            Some("huggingface/other"),
+            "cuda oom",
+            "lower the batch size",
+            &[0., 1.],
+        )
+        .await
+        .unwrap();
+
+        let closest = db
+            .closest_knowledge_base_entry(&[0.1, 1.], "huggingface/lor-e")
+            .await
+            .unwrap()
+            .unwrap();
+        // the closer entry belongs to another repository
+        assert_eq!(closest.entry.answer, "pip install");
+
+        let closest = db
+            .closest_knowledge_base_entry(&[0.1, 1.], "huggingface/other")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(closest.entry.answer, "lower the batch size");
+        assert!(closest.cosine_similarity > 0.99);
+
+        // moved to every repository
+        let id = closest.entry.id;
+        assert!(db
+            .update_knowledge_base_entry(id, None, "cuda oom", "use bf16", &[0., 1.])
+            .await
+            .unwrap());
+        let closest = db
+            .closest_knowledge_base_entry(&[0.1, 1.], "huggingface/lor-e")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(closest.entry.answer, "use bf16");
+        assert!(!db
+            .update_knowledge_base_entry(id + 1, None, "q", "a", &[1., 0.])
+            .await
+            .unwrap());
+    }
+}
@@ -0,0 +1,132 @@
+use std::sync::{Arc, RwLock};
+
+use futures::pin_mut;
+use tracing::{error, info};
+
+use crate::{
+    config::{load_config, IssueBotConfig, MessageConfig, SimilarityConfig},
+    shutdown_signal,
+};
+
+/// Settings of the configuration file that can change without restarting the bot
+#[derive(Clone, Debug)]
+pub struct LiveValues {
+    pub github_comments_enabled: bool,
+    pub huggingface_comments_enabled: bool,
+    pub message_config: MessageConfig,
+    pub similarity: SimilarityConfig,
+    pub slack_channel: String,
+}
+
+impl From<&IssueBotConfig> for LiveValues {
+    fn from(cfg: &IssueBotConfig) -> Self {
+        Self {
+            github_comments_enabled: cfg.github_api.comments_enabled,
+            huggingface_comments_enabled: cfg.huggingface_api.comments_enabled,
+            message_config: cfg.message_config.clone(),
+            similarity: cfg.similarity.clone(),
+            slack_channel: cfg.slack.channel.clone(),
+        }
+    }
+}
+
+/// Shared handle on the [LiveValues], replaced as a whole on reload so that readers never see a
+/// mix of old and new values
+#[derive(Clone)]
+pub struct LiveConfig {
+    values: Arc<RwLock<Arc<LiveValues>>>,
+}
+
+impl LiveConfig {
+    pub fn new(values: LiveValues) -> Self {
+        Self {
+            values: Arc::new(RwLock::new(Arc::new(values))),
+        }
+    }
+
+    /// current values, callers should hold on to them for the duration of an operation
+    pub fn get(&self) -> Arc<LiveValues> {
+        self.values.read().unwrap().clone()
+    }
+
+    fn replace(&self, values: LiveValues) {
+        *self.values.write().unwrap() = Arc::new(values);
+    }
+
+    /// reloads the configuration, keeping the current values if it is invalid
+    fn reload(&self) {
+        match load_config("ISSUE_BOT") {
+            Ok(cfg) => {
+                let values = LiveValues::from(&cfg);
+                info!(?values, "reloaded configuration");
+                self.replace(values);
+            }
+            Err(err) => error!(
+                err = err.to_string(),
+                "failed to reload configuration, keeping the current one"
+            ),
+        }
+    }
+}
+
+/// Reloads the [LiveValues] from the configuration file on SIGHUP, other settings still require
+/// a restart
+#[cfg(unix)]
+pub async fn start_config_reloader(live_config: LiveConfig) -> anyhow::Result<()> {
+    use tokio::{
+        select,
+        signal::unix::{signal, SignalKind},
+    };
+
+    info!("starting configuration reloader");
+    let mut hangup = signal(SignalKind::hangup())?;
+    let shutdown = shutdown_signal();
+    pin_mut!(shutdown);
+    loop {
+        select! {
+            _ = hangup.recv() => live_config.reload(),
+            _ = &mut shutdown => break,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub async fn start_config_reloader(_live_config: LiveConfig) -> anyhow::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{MessageConfig, SimilarityConfig};
+
+    use super::{LiveConfig, LiveValues};
+
+    fn values(pre: &str) -> LiveValues {
+        LiveValues {
+            github_comments_enabled: false,
+            huggingface_comments_enabled: false,
+            message_config: MessageConfig {
+                pre: pre.to_owned(),
+                post: String::new(),
+            },
+            similarity: SimilarityConfig {
+                max_suggestions: 3,
+                min_similarity: 0.,
+            },
+            slack_channel: "#issues".to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_replace() {
+        let live_config = LiveConfig::new(values("before"));
+        let held = live_config.get();
+
+        live_config.clone().replace(values("after"));
+
+        // values held across the reload are left untouched
+        assert_eq!(held.message_config.pre, "before");
+        assert_eq!(live_config.get().message_config.pre, "after");
+    }
+}
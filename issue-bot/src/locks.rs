@@ -0,0 +1,119 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use nanoid::nanoid;
+use tokio::{task::JoinHandle, time::interval};
+use tracing::warn;
+
+use crate::storage::{Database, Storage, StorageError};
+
+const LEASE_DURATION: Duration = Duration::from_secs(60);
+
+pub const CATCH_UP: &str = "catch_up";
+
+pub const EMBEDDINGS_REGENERATION: &str = "embeddings_regeneration";
+
+pub fn repository_indexation(repository_full_name: &str) -> String {
+    format!("repository_indexation:{repository_full_name}")
+}
+
+/// Locks shared by every instance of the bot through the database
+///
+/// Locks are leases renewed in the background while held, so that another instance can take
+/// them over once the holder died.
+#[derive(Clone)]
+pub struct Locks {
+    db: Database,
+    holder: Arc<str>,
+}
+
+impl Locks {
+    pub fn new(db: Database) -> Self {
+        Self {
+            db,
+            holder: Arc::from(nanoid!()),
+        }
+    }
+
+    /// `None` when the lock is held by another instance
+    pub async fn try_acquire(&self, name: &str) -> Result<Option<Lease>, StorageError> {
+        if !self
+            .db
+            .try_acquire_lock(name, &self.holder, LEASE_DURATION)
+            .await?
+        {
+            return Ok(None);
+        }
+
+        let db = self.db.clone();
+        let holder = self.holder.clone();
+        let lock_name = name.to_owned();
+        let lost = Arc::new(AtomicBool::new(false));
+        let renewal_lost = lost.clone();
+        let renewal = tokio::spawn(async move {
+            let mut ticker = interval(LEASE_DURATION / 3);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                match db.renew_lock(&lock_name, &holder, LEASE_DURATION).await {
+                    Ok(true) => (),
+                    Ok(false) => {
+                        warn!(lock = lock_name, "lost lock, it was taken over");
+                        renewal_lost.store(true, Ordering::Relaxed);
+                        break;
+                    }
+                    Err(err) => warn!(
+                        lock = lock_name,
+                        err = err.to_string(),
+                        "failed to renew lock"
+                    ),
+                }
+            }
+        });
+        Ok(Some(Lease {
+            db: self.db.clone(),
+            holder: self.holder.clone(),
+            lost,
+            name: name.to_owned(),
+            renewal,
+        }))
+    }
+
+    /// whether the lock is currently held, by any instance
+    pub async fn is_held(&self, name: &str) -> Result<bool, StorageError> {
+        self.db.lock_held(name).await
+    }
+}
+
+/// Held lock, dropping it without calling [Lease::release] lets it expire
+pub struct Lease {
+    db: Database,
+    holder: Arc<str>,
+    lost: Arc<AtomicBool>,
+    name: String,
+    renewal: JoinHandle<()>,
+}
+
+impl Lease {
+    /// whether another instance took the lock over, the holder having failed to renew it in
+    /// time, in which case the guarded job should stop
+    pub fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::Relaxed)
+    }
+
+    pub async fn release(self) -> Result<(), StorageError> {
+        self.renewal.abort();
+        self.db.release_lock(&self.name, &self.holder).await
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        self.renewal.abort();
+    }
+}
@@ -1,69 +1,229 @@
 use std::{
+    collections::HashMap,
     env,
     fmt::Display,
+    mem,
     sync::{
         atomic::{AtomicBool, Ordering},
-        Once,
+        Arc, Once,
     },
     time::Duration,
 };
 
+use api_keys::ApiKeys;
+use archive::Archive;
 use axum::{
     error_handling::HandleErrorLayer,
+    extract::DefaultBodyLimit,
     http::{Response, StatusCode},
     middleware,
-    routing::{get, post},
+    routing::{delete, get, post, put},
     Router,
 };
-use config::{load_config, IssueBotConfig, ServerConfig};
-use embeddings::inference_endpoints::EmbeddingApi;
+use backlinks::Backlinker;
+use butler::{start_butler, Butler};
+use catch_up::{start_catch_up, CatchUp, CursorUpdate};
+use check_runs::CheckRuns;
+use checks::{run_startup_checks, ConfiguredIntegrations};
+use code_context::CodeContext;
+use comment_queue::{start_comment_queue, CommentQueue};
+use comment_trigger::CommentTrigger;
+use compaction::{start_compaction, Compaction};
+use compare::IssueComparer;
+use config::{load_config, DiversityConfig, IssueBotConfig, RepositoryConfig, ServerConfig};
+use debounce::{start_reembed_flusher, ReembedDebouncer};
+use debug::DebugState;
+use drift::EmbeddingDrift;
+use email::{start_email_digest, EmailNotifier};
+use embeddings::{
+    inference_endpoints::EmbeddingApi,
+    queue::{EmbeddingQueue, Priority},
+    EmbeddingMetadata,
+};
+use escalation::Escalation;
+use event_log::{start_event_log_pruner, EventLog, LoggedStage};
+use events::{PipelineEvents, Stage};
+use extraction::{Extractor, SystemInfo};
+use fingerprint::{start_fingerprint_backfill, Fingerprints};
+use footer::CommentFooter;
 use futures::{pin_mut, StreamExt};
-use github::GithubApi;
-use huggingface::HuggingfaceApi;
+use github::{GithubApi, GithubApiError};
+use github_oidc::GithubOidc;
+use guidance::{start_guidance_refresher, Guidance};
+use hot_issues::HotIssues;
+use huggingface::{HubDiscussion, HuggingfaceApi};
+use ignore::IgnoreRules;
+use invalidation::start_invalidation_listener;
+use issue_links::IssueLinks;
+use issue_text::IssueTextComposer;
+use job_control::should_stop;
+use knowledge_base::KnowledgeBase;
+use live_config::{start_config_reloader, LiveConfig};
+use locks::Locks;
 use metrics::start_metrics_server;
 use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
-use middlewares::RequestSpan;
+use middlewares::{RequestId, RequestSpan};
+use mirror::WebhookMirror;
+use nanoid::nanoid;
+use onboarding::Onboarding;
+use owners::Owners;
+use partial_indexes::start_partial_indexes;
 use pgvector::Vector;
-use routes::{health, index_repository, regenerate_embeddings};
+use priority::Priorities;
+use queue_metrics::{start_queue_metrics, QueueMetrics};
+use redaction::Redactor;
+use repo_groups::RepoGroups;
+use repo_metadata::{start_repo_metadata_refresher, RepoMetadata};
+use resolutions::DuplicateResolutions;
+use response_cache::ResponseCache;
+use retention::{start_retention, Retention};
+use retry::{with_retry_on, RetryBudget, RetryPolicy};
+use routes::{
+    bot_comment_revisions, catch_up, check_webhooks, compare_issues, create_api_key,
+    create_knowledge_base_entry, debug_state, delete_api_key, delete_knowledge_base_entry,
+    embedding_drift, embedding_metadata, event_log, events_stream, export_issues, feedback,
+    feedback_form, health, index_repository, list_api_keys, list_jobs, list_knowledge_base_entries,
+    maintenance, onboard_repository, onboarded_repositories, opt_out_author, opt_out_requests,
+    pause_job, regenerate_embeddings, resume_job, sample_embedding_drift, search_issues,
+    similarity_settings, slack_interaction, suppress_issue, unsuppress_issue,
+    update_knowledge_base_entry, update_maintenance, update_similarity_settings,
+};
+use search::IssueSearch;
 use serde::{Deserialize, Deserializer, Serialize};
+use settings::{Settings, SimilaritySettings};
 use slack::Slack;
-use sqlx::{
-    postgres::{PgConnectOptions, PgPoolOptions},
-    prelude::FromRow,
-    types::Json,
-    Pool, Postgres, QueryBuilder,
-};
+use slack_outbox::{start_slack_outbox, SlackOutbox};
+use sqlx::prelude::FromRow;
+use storage::{Database, JobData, JobType, Storage};
 use summarization::SummarizationApi;
+use supervisor::Supervisor;
+use systemd::start_watchdog;
 use tokio::{
     net::TcpListener,
-    select, signal,
-    sync::mpsc::{self, Receiver, Sender},
+    signal,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Mutex,
+    },
     task::JoinHandle,
 };
 use tower::{BoxError, ServiceBuilder};
 use tower_http::trace::TraceLayer;
-use tracing::{error, info, info_span, Instrument, Span};
+use tracing::{error, info, info_span, warn, Instrument, Span};
 use tracing_subscriber::EnvFilter;
+use web_ui::WebUi;
+use webhooks::WebhookChecker;
 
 use crate::routes::index_issue;
 
+mod api_keys;
+mod archive;
+mod backlinks;
+mod butler;
+mod catch_up;
+mod check_runs;
+mod checks;
+mod code_context;
+mod comment_queue;
+mod comment_trigger;
+mod compaction;
+mod compare;
 mod config;
+mod debounce;
+mod debug;
+mod diversity;
+mod drift;
+mod email;
 mod embeddings;
 mod errors;
+mod escalation;
+mod evaluation;
+mod event_log;
+mod events;
+mod extraction;
+mod failover;
+mod fingerprint;
+mod footer;
 mod github;
+mod github_oidc;
+mod guidance;
+mod hot_issues;
+mod http_client;
 mod huggingface;
+mod ignore;
+mod invalidation;
+mod issue_forms;
+mod issue_links;
+mod issue_text;
+mod job_control;
+mod knowledge_base;
+mod live_config;
+mod locks;
 mod metrics;
 mod middlewares;
+mod mirror;
+mod onboarding;
+mod opt_out;
+mod owners;
+mod partial_indexes;
+mod priority;
+mod queue_metrics;
+mod redaction;
+mod repo_groups;
+mod repo_metadata;
+mod resolutions;
+mod response_cache;
+mod retention;
+mod retry;
 mod routes;
+mod search;
+mod settings;
 mod slack;
+mod slack_outbox;
+mod storage;
 mod summarization;
+mod summary_guardrails;
+mod supervisor;
+mod suppression;
+mod systemd;
+#[cfg(test)]
+mod test_harness;
+mod web_ui;
+mod webhooks;
+
+/// issue ids read at once while regenerating embeddings
+const REGENERATION_PAGE_SIZE: i64 = 1_000;
 
 static APP_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
 
 #[derive(Clone)]
 pub struct AppState {
+    api_keys: ApiKeys,
     auth_token: String,
-    tx: Sender<EventData>,
+    catch_up: CatchUp,
+    comment_queue: CommentQueue,
+    comparer: IssueComparer,
+    db: Database,
+    debug_state: DebugState,
+    drift: EmbeddingDrift,
+    event_log: EventLog,
+    events: PipelineEvents,
+    github_oidc: GithubOidc,
+    hot_issues: HotIssues,
+    ignore_rules: IgnoreRules,
+    knowledge_base: KnowledgeBase,
+    locks: Locks,
+    max_body_bytes: usize,
+    onboarding: Onboarding,
+    request_timeout: Duration,
+    search: IssueSearch,
+    settings: Settings,
+    slack: Slack,
+    supervisor: Supervisor,
+    tx: Sender<QueuedEvent>,
+    web_ui: WebUi,
+    webhook_mirror: WebhookMirror,
+    webhooks: WebhookChecker,
 }
 
 fn setup_metrics_recorder() -> PrometheusHandle {
@@ -129,12 +289,64 @@ where
 }
 
 fn app(state: AppState) -> Router {
+    let max_body_bytes = state.max_body_bytes;
+    let request_timeout = state.request_timeout;
+    let web_ui_enabled = state.web_ui.enabled();
+    // form encoded, left out of [middlewares::require_json] only
+    let form_routes = Router::new()
+        .route("/feedback", get(feedback_form).post(feedback))
+        .route("/slack/interactions", post(slack_interaction));
     Router::new()
         .nest("/event", routes::event_router())
         .route("/index", post(index_repository))
         .route("/index-issue", post(index_issue))
         .route("/regenerate-embeddings", post(regenerate_embeddings))
+        .route("/jobs", get(list_jobs))
+        .route("/jobs/{id}/pause", post(pause_job))
+        .route("/jobs/{id}/resume", post(resume_job))
+        .route("/maintenance", get(maintenance).put(update_maintenance))
+        .route("/catch-up", post(catch_up))
+        .route("/suppress", post(suppress_issue).delete(unsuppress_issue))
+        .route("/opt-out", get(opt_out_requests).post(opt_out_author))
+        .route("/bot-comments", get(bot_comment_revisions))
+        .route(
+            "/embedding-drift",
+            get(embedding_drift).post(sample_embedding_drift),
+        )
+        .route("/export", get(export_issues))
+        .route("/check-webhooks", post(check_webhooks))
+        .route(
+            "/onboard",
+            get(onboarded_repositories).post(onboard_repository),
+        )
+        .route("/debug/state", get(debug_state))
+        .route("/debug/event-log", get(event_log))
+        .route(
+            "/debug/embedding-metadata/{source_id}",
+            get(embedding_metadata),
+        )
+        .route("/events/stream", get(events_stream))
+        .route("/api-keys", get(list_api_keys).post(create_api_key))
+        .route("/api-keys/{id}", delete(delete_api_key))
+        .route(
+            "/knowledge-base",
+            get(list_knowledge_base_entries).post(create_knowledge_base_entry),
+        )
+        .route(
+            "/knowledge-base/{id}",
+            put(update_knowledge_base_entry).delete(delete_knowledge_base_entry),
+        )
+        .route("/compare", get(compare_issues))
+        .route("/search", post(search_issues))
+        .route(
+            "/settings/similarity",
+            get(similarity_settings).put(update_similarity_settings),
+        )
+        .merge(web_ui::router(web_ui_enabled))
+        .layer(middleware::from_fn(middlewares::require_json))
+        .merge(form_routes)
         .route_layer(middleware::from_fn(middlewares::track_metrics))
+        .layer(DefaultBodyLimit::max(max_body_bytes))
         .layer(
             ServiceBuilder::new()
                 .layer(HandleErrorLayer::new(|error: BoxError| async move {
@@ -147,7 +359,7 @@ fn app(state: AppState) -> Router {
                         ))
                     }
                 }))
-                .timeout(Duration::from_secs(10))
+                .timeout(request_timeout)
                 .layer(
                     TraceLayer::new_for_http()
                         .make_span_with(RequestSpan)
@@ -170,6 +382,8 @@ async fn start_main_server(config: ServerConfig, state: AppState) -> anyhow::Res
     info!(addr, "starting server");
 
     let listener = TcpListener::bind(addr).await?;
+    // the database is connected by now too
+    systemd::notify("READY=1");
     axum::serve(listener, app(state))
         .with_graceful_shutdown(shutdown_signal())
         .await?;
@@ -177,9 +391,14 @@ async fn start_main_server(config: ServerConfig, state: AppState) -> anyhow::Res
     Ok(())
 }
 
+#[derive(Serialize)]
 struct IssueData {
     source_id: i64,
     action: Action,
+    /// login of whoever opened the issue, when known
+    author: Option<String>,
+    labels: Vec<String>,
+    milestone: Option<String>,
     title: String,
     body: String,
     is_pull_request: bool,
@@ -190,21 +409,89 @@ struct IssueData {
     source: Source,
 }
 
+#[derive(Serialize)]
+struct IssueMetadata {
+    source_id: i64,
+    labels: Vec<String>,
+    milestone: Option<String>,
+}
+
+#[derive(Serialize)]
 struct CommentData {
     source_id: i64,
     action: Action,
     issue_id: i64,
+    /// login of whoever commented, when known
+    author: Option<String>,
     body: String,
     url: String,
+    /// comment on the diff of a pull request, see [ReviewCommentData]
+    is_review: bool,
+}
+
+/// Comment on the diff of a pull request, whose issue id the webhook lacks
+///
+/// Once the pull request is found by its number, it is stored as a [CommentData] and embedded with
+/// the pull request.
+#[derive(Serialize)]
+struct ReviewCommentData {
+    source_id: i64,
+    action: Action,
+    repository_full_name: String,
+    number: i32,
+    author: Option<String>,
+    body: String,
+    url: String,
+}
+
+impl ReviewCommentData {
+    fn into_comment(self, issue_id: i64) -> CommentData {
+        CommentData {
+            source_id: self.source_id,
+            action: self.action,
+            issue_id,
+            author: self.author,
+            body: self.body,
+            url: self.url,
+            is_review: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Vote {
+    Up,
+    Down,
+}
+
+impl Display for Vote {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let vote = match self {
+            Self::Up => "up",
+            Self::Down => "down",
+        };
+        write!(f, "{}", vote)
+    }
+}
+
+/// Rating of the suggestions posted during run `run_id`, see [footer::CommentFooter]
+#[derive(Deserialize, Serialize)]
+struct FeedbackData {
+    run_id: String,
+    vote: Vote,
 }
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Deserialize, Serialize)]
 struct IndexIssueData {
     issue_number: i32,
     repository_full_name: String,
+    /// head commit of a newly opened pull request, which gets a check run listing related issues
+    #[serde(default)]
+    head_sha: Option<String>,
 }
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct RepositoryData {
     full_name: String,
     source: Source,
@@ -216,14 +503,176 @@ impl Display for RepositoryData {
     }
 }
 
+/// Issue closed or reopened upstream, for retention rules restricted to open or closed issues
+#[derive(Serialize)]
+struct IssueStateChange {
+    source_id: i64,
+    closed: bool,
+}
+
+/// Issue a maintainer closed as a duplicate, see [DuplicateResolutions]
+#[derive(Serialize)]
+struct DuplicateClosure {
+    source_id: i64,
+    repository_full_name: String,
+    number: i32,
+}
+
+/// Issue a maintainer muted the bot on, see [suppression]
+#[derive(Deserialize, Serialize)]
+struct IssueSuppression {
+    source_id: i64,
+    /// login of the maintainer, or `api` when suppressed through `POST /suppress`
+    suppressed_by: String,
+    /// the maintainer unmuted the bot instead
+    #[serde(default)]
+    lifted: bool,
+}
+
+/// Author asking to be left out of suggestions and exports, see [opt_out]
+#[derive(Serialize)]
+struct AuthorOptOut {
+    login: String,
+    purge: bool,
+}
+
+/// Issues whose embeddings are computed again from their stored content, e.g. once the comments
+/// of an opted-out author are left out of it
+#[derive(Serialize)]
+struct IssueEmbeddings {
+    source_ids: Vec<i64>,
+}
+
+/// Move or visibility change of a Hugging Face repository, whose stored discussions are updated
+#[derive(Serialize)]
+struct RepositoryUpdate {
+    full_name: String,
+    /// new full name of a moved repository
+    moved_to: Option<String>,
+    private: Option<bool>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
 enum EventData {
     Issue(IssueData),
+    IssueMetadata(IssueMetadata),
+    IssueStateChange(IssueStateChange),
     Comment(CommentData),
+    ReviewComment(ReviewCommentData),
     IssueIndexation(IndexIssueData),
     RepositoryIndexation(RepositoryData),
     RegenerateEmbeddings,
+    Feedback(FeedbackData),
+    RepositoryUpdate(RepositoryUpdate),
+    DuplicateClosure(DuplicateClosure),
+    IssueSuppression(IssueSuppression),
+    AuthorOptOut(AuthorOptOut),
+    IssueEmbeddings(IssueEmbeddings),
+}
+
+impl Display for EventData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Issue(issue) => write!(f, "issue {} ({})", issue.source_id, issue.action),
+            Self::IssueMetadata(metadata) => {
+                write!(f, "issue {} (metadata updated)", metadata.source_id)
+            }
+            Self::IssueStateChange(change) => {
+                let state = if change.closed { "closed" } else { "reopened" };
+                write!(f, "issue {} ({state})", change.source_id)
+            }
+            Self::Comment(comment) => {
+                write!(f, "comment {} ({})", comment.source_id, comment.action)
+            }
+            Self::ReviewComment(comment) => {
+                write!(
+                    f,
+                    "review comment {} ({})",
+                    comment.source_id, comment.action
+                )
+            }
+            Self::IssueIndexation(data) => write!(
+                f,
+                "issue indexation {}#{}",
+                data.repository_full_name, data.issue_number
+            ),
+            Self::RepositoryIndexation(repo_data) => {
+                write!(f, "repository indexation of {}", repo_data)
+            }
+            Self::RegenerateEmbeddings => write!(f, "embeddings regeneration"),
+            Self::Feedback(feedback) => {
+                write!(f, "feedback {} for run {}", feedback.vote, feedback.run_id)
+            }
+            Self::RepositoryUpdate(update) => {
+                write!(f, "repository update of '{}'", update.full_name)
+            }
+            Self::DuplicateClosure(closure) => {
+                write!(f, "issue {} (closed as duplicate)", closure.source_id)
+            }
+            Self::IssueSuppression(suppression) if suppression.lifted => {
+                write!(f, "issue {} (unsuppressed)", suppression.source_id)
+            }
+            Self::IssueSuppression(suppression) => {
+                write!(f, "issue {} (suppressed)", suppression.source_id)
+            }
+            Self::AuthorOptOut(opt_out) => write!(f, "opt-out of '{}'", opt_out.login),
+            Self::IssueEmbeddings(embeddings) => {
+                write!(f, "re-embedding of {} issues", embeddings.source_ids.len())
+            }
+        }
+    }
+}
+
+impl EventData {
+    /// serialized `type` tag of the event
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Issue(_) => "issue",
+            Self::IssueMetadata(_) => "issue_metadata",
+            Self::IssueStateChange(_) => "issue_state_change",
+            Self::Comment(_) => "comment",
+            Self::ReviewComment(_) => "review_comment",
+            Self::IssueIndexation(_) => "issue_indexation",
+            Self::RepositoryIndexation(_) => "repository_indexation",
+            Self::RegenerateEmbeddings => "regenerate_embeddings",
+            Self::Feedback(_) => "feedback",
+            Self::RepositoryUpdate(_) => "repository_update",
+            Self::DuplicateClosure(_) => "duplicate_closure",
+            Self::IssueSuppression(_) => "issue_suppression",
+            Self::AuthorOptOut(_) => "author_opt_out",
+            Self::IssueEmbeddings(_) => "issue_embeddings",
+        }
+    }
+}
+
+/// Event sent to the webhooks worker, along with the id of the request that queued it
+struct QueuedEvent {
+    data: EventData,
+    request_id: String,
+    /// applied once the event is handled, see [catch_up::CatchUp]
+    cursor_update: Option<CursorUpdate>,
+}
+
+impl QueuedEvent {
+    fn new(data: EventData, request_id: &RequestId) -> Self {
+        Self {
+            data,
+            request_id: request_id.0.clone(),
+            cursor_update: None,
+        }
+    }
+
+    fn with_cursor_update(self, cursor_update: Option<CursorUpdate>) -> Self {
+        Self {
+            cursor_update,
+            ..self
+        }
+    }
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
 enum Action {
     Created,
     Edited,
@@ -241,8 +690,9 @@ impl Display for Action {
     }
 }
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 enum Source {
+    #[default]
     Github,
     HuggingFace,
 }
@@ -262,448 +712,1123 @@ struct ClosestIssue {
     title: String,
     number: i32,
     html_url: String,
-    #[allow(unused)]
+    labels: Vec<String>,
+    repository_full_name: String,
+    /// raw similarity to the new issue, what `min_similarity` applies to and what is shown
     cosine_similarity: f64,
+    /// added by the boosts, only ranking the candidates, see [ClosestIssue::rank_score]
+    #[sqlx(skip)]
+    boost: f64,
+    /// used to tell apart near-identical suggestions, see [diversity::diversify], empty unless
+    /// requested from [Storage::closest_issues]
+    #[sqlx(default, try_from = "Vector")]
+    embedding: Vec<f32>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-enum JobData {
-    // FIXME: naming is a bit confusing, this means "repository issue indexation"
-    IssueIndexation { next_url: String },
-    EmbeddingsRegeneration { current_issue: i32 },
-}
-
-#[derive(Debug, sqlx::Type)]
-#[sqlx(type_name = "job_type", rename_all = "snake_case")]
-enum JobType {
-    // FIXME: naming is a bit confusing, this means "repository issue indexation"
-    IssueIndexation,
-    EmbeddingsRegeneration,
-}
+impl ClosestIssue {
+    /// similarity with the boosts, which candidates are ordered and picked by
+    fn rank_score(&self) -> f64 {
+        self.cosine_similarity + self.boost
+    }
 
-#[derive(Debug)]
-struct Job {
-    data: Json<JobData>,
+    /// formats the issue as a markdown list item, e.g. ``- Title ([#12](url)) `bug` ``
+    ///
+    /// Issues from another repository than `repository_full_name` are referenced with their
+    /// repository, e.g. `huggingface/tokenizers#12`.
+    fn to_markdown_list_item(&self, repository_full_name: &str) -> String {
+        let reference = if self.repository_full_name == repository_full_name {
+            format!("#{}", self.number)
+        } else {
+            format!("{}#{}", self.repository_full_name, self.number)
+        };
+        let mut item = format!("- {} ([{}]({}))", self.title, reference, self.html_url);
+        for label in &self.labels {
+            item.push_str(&format!(" `{label}`"));
+        }
+        item
+    }
 }
 
-async fn handle_webhooks_wrapper(
-    rx: Receiver<EventData>,
-    embedding_api: EmbeddingApi,
+/// Services and settings the webhook handlers share, cloned for every restart of the worker
+#[derive(Clone)]
+struct WebhookContext {
+    archive: Archive,
+    backlinker: Backlinker,
+    butler: Butler,
+    check_runs: CheckRuns,
+    code_context: CodeContext,
+    comment_queue: CommentQueue,
+    comment_trigger: CommentTrigger,
+    debouncer: ReembedDebouncer,
+    debug_state: DebugState,
+    diversity_cfg: DiversityConfig,
+    drift: EmbeddingDrift,
+    duplicate_resolutions: DuplicateResolutions,
+    email: EmailNotifier,
+    embedding_queue: EmbeddingQueue,
+    escalation: Escalation,
+    event_log: EventLog,
+    events: PipelineEvents,
+    extractor: Extractor,
+    fingerprints: Fingerprints,
     github_api: GithubApi,
+    guidance: Guidance,
+    hot_issues: HotIssues,
     huggingface_api: HuggingfaceApi,
+    issue_links: IssueLinks,
+    issue_text: IssueTextComposer,
+    knowledge_base: KnowledgeBase,
+    owners: Owners,
+    priorities: Priorities,
+    redactor: Redactor,
+    repo_groups: RepoGroups,
+    repo_metadata: RepoMetadata,
+    repositories: HashMap<String, RepositoryConfig>,
+    retry_policy: RetryPolicy,
+    settings: Settings,
     slack: Slack,
     summarization_api: SummarizationApi,
-    pool: Pool<Postgres>,
+    supervisor: Supervisor,
+    db: Database,
+    locks: Locks,
+}
+
+async fn handle_webhooks_wrapper(
+    rx: Receiver<QueuedEvent>,
+    ctx: WebhookContext,
 ) -> anyhow::Result<()> {
-    select! {
-        _ = handle_webhooks(rx, embedding_api, github_api, huggingface_api, slack, summarization_api, pool) => { Ok(()) },
-        _ = shutdown_signal() => { Ok(()) },
-    }
+    let rx = Arc::new(Mutex::new(rx));
+    ctx.supervisor
+        .clone()
+        .supervise("webhooks", move || handle_webhooks(rx.clone(), ctx.clone()))
+        .await
 }
 
-#[allow(clippy::too_many_arguments)]
-async fn handle_webhooks(
-    mut rx: Receiver<EventData>,
-    embedding_api: EmbeddingApi,
-    github_api: GithubApi,
-    huggingface_api: HuggingfaceApi,
-    slack: Slack,
-    summarization_api: SummarizationApi,
-    pool: Pool<Postgres>,
-) {
-    while let Some(webhook_data) = rx.recv().await {
+async fn handle_webhooks(rx: Arc<Mutex<Receiver<QueuedEvent>>>, ctx: WebhookContext) {
+    let WebhookContext {
+        archive,
+        backlinker,
+        butler,
+        check_runs,
+        code_context,
+        comment_queue,
+        comment_trigger,
+        debouncer,
+        debug_state,
+        diversity_cfg,
+        drift,
+        duplicate_resolutions,
+        email,
+        embedding_queue,
+        escalation,
+        event_log,
+        events,
+        extractor,
+        fingerprints,
+        github_api,
+        guidance,
+        hot_issues,
+        huggingface_api,
+        issue_links,
+        issue_text,
+        knowledge_base,
+        owners,
+        priorities,
+        redactor,
+        repo_groups,
+        repo_metadata,
+        repositories,
+        retry_policy,
+        settings,
+        slack,
+        summarization_api,
+        db,
+        supervisor,
+        locks,
+    } = ctx;
+    // held until the worker ends, released for its replacement when it panics or is aborted
+    let mut rx = rx.lock().await;
+    // moved once the previous event is done with, including when its handling was cut short
+    let mut cursor_update: Option<CursorUpdate> = None;
+    loop {
+        if let Some(update) = cursor_update.take() {
+            if let Err(err) = db
+                .advance_repository_cursor(&update.repository_full_name, update.processed_at)
+                .await
+            {
+                debug_state.record_error("database", &err);
+                error!(
+                    repository = update.repository_full_name,
+                    err = err.to_string(),
+                    "failed to move repository cursor"
+                );
+            }
+        }
+        debug_state.clear_in_flight("webhooks");
+        let Some(QueuedEvent {
+            data: mut webhook_data,
+            request_id,
+            cursor_update: update,
+        }) = rx.recv().await
+        else {
+            break;
+        };
+        cursor_update = update;
+        // Hugging Face webhooks only carry the opening comment of discussions
+        let mut hydrated = match &mut webhook_data {
+            EventData::Issue(issue)
+                if matches!(issue.source, Source::HuggingFace)
+                    && !matches!(issue.action, Action::Deleted)
+                    && huggingface_api.hydrates_discussions() =>
+            {
+                hydrate_discussion(&huggingface_api, &debug_state, issue).await
+            }
+            _ => None,
+        };
+        redactor.redact_event(&mut webhook_data);
+        if let (EventData::Issue(issue), Some(discussion)) = (&webhook_data, &mut hydrated) {
+            redactor.redact_comments(&mut discussion.comments, &issue.html_url);
+        }
+        debug_state.set_in_flight("webhooks", &webhook_data);
+        let mut record = event_log.start(&webhook_data, request_id);
         let issue_id = match webhook_data {
             EventData::Issue(issue) => {
                 info!("handling issue (state: {})", issue.action);
                 match issue.action {
                     Action::Created => {
-                        let issue_text = format!("# {}\n{}", issue.title, issue.body);
-                        let raw_embedding =
-                            match embedding_api.generate_embedding(issue_text.clone()).await {
-                                Ok(embedding) => embedding,
-                                Err(err) => {
-                                    error!(
-                                        issue_id = issue.source_id,
-                                        err = err.to_string(),
-                                        "generate embedding error"
-                                    );
-                                    continue;
-                                }
-                            };
-                        let embedding = Vector::from(raw_embedding);
-
-                        let closest_issues: Vec<ClosestIssue> = match sqlx::query_as(
-                            "select title, number, html_url, 1 - (embedding <=> $1) as cosine_similarity from issues order by embedding <=> $1 LIMIT 3",
-                        )
-                            .bind(embedding.clone())
-                            .fetch_all(&pool)
-                            .await {
-                            Ok(issues) => issues,
+                        events.emit(&issue, Stage::Received, None);
+                        // still indexed, but neither commented on nor sent to Slack
+                        let suppressed = match db.is_issue_suppressed(issue.source_id).await {
+                            Ok(suppressed) => suppressed,
                             Err(err) => {
+                                debug_state.record_error("database", &err);
                                 error!(
                                     issue_id = issue.source_id,
                                     err = err.to_string(),
-                                    "failed to fetch closest issues"
+                                    "failed to check whether issue is suppressed"
                                 );
-                                continue;
+                                false
                             }
                         };
-
-                        let summarized_issue = match summarization_api.summarize(issue_text).await {
-                            Ok(summary) => summary,
-                            Err(err) => {
+                        let priority = priorities.classify(&issue);
+                        if let Some(priority) = priority.filter(|_| !suppressed) {
+                            if let Err(err) = priorities.alert(&issue, priority).await {
+                                debug_state.record_error("slack", &err);
                                 error!(
                                     issue_id = issue.source_id,
                                     err = err.to_string(),
-                                    "summarization error"
+                                    "failed to send priority alert"
                                 );
-                                continue;
                             }
+                        }
+                        let is_security_report = priority.is_some_and(|p| p.is_security());
+                        let hydrated_comments: Vec<&str> = hydrated
+                            .iter()
+                            .flat_map(|discussion| &discussion.comments)
+                            .map(|comment| comment.body.as_str())
+                            .collect();
+                        let text =
+                            issue_text.compose(&issue.title, &issue.body, &hydrated_comments);
+                        let snippets = match issue.source {
+                            Source::Github => {
+                                code_context
+                                    .snippets(&issue.repository_full_name, &text)
+                                    .await
+                            }
+                            Source::HuggingFace => None,
                         };
-
-                        if let Err(err) = slack
-                            .closest_issues(summarized_issue, &issue, &closest_issues)
+                        let embedding_text = issue_text.embedding_input(
+                            format!("{text}{}", snippets.as_deref().unwrap_or_default()),
+                            &issue.repository_full_name,
+                            &issue.labels,
+                            &issue.body,
+                        );
+                        let summary_input = text;
+                        let (raw_embedding, embedding_metadata) = match record
+                            .time(
+                                LoggedStage::Embed,
+                                embedding_queue.generate_embedding_with_metadata(
+                                    embedding_text,
+                                    Priority::Interactive,
+                                ),
+                            )
                             .await
                         {
-                            error!(
-                                issue_id = issue.source_id,
-                                err = err.to_string(),
-                                "failed to send closest issues to slack"
-                            );
-                        }
-
-                        match (issue.is_pull_request, &issue.source) {
-                            (false, Source::Github) => {
-                                if let Err(err) = github_api
-                                    .comment_on_issue(&issue.url, closest_issues)
-                                    .await
-                                {
+                            Ok(embedding) => embedding,
+                            Err(err) => {
+                                debug_state.record_error("embedding_api", &err);
+                                events.emit(&issue, Stage::Failed, Some(err.to_string()));
+                                record.error(&err);
+                                error!(
+                                    issue_id = issue.source_id,
+                                    err = err.to_string(),
+                                    "generate embedding error"
+                                );
+                                record.finish().await;
+                                continue;
+                            }
+                        };
+                        events.emit(&issue, Stage::Embedded, None);
+                        let excluded_labels = repositories
+                            .get(&issue.repository_full_name)
+                            .map(|r| r.excluded_labels.clone())
+                            .unwrap_or_default();
+                        let search_scope = repo_groups.search_scope(&issue.repository_full_name);
+                        let similarity =
+                            match settings.similarity(Some(&issue.repository_full_name)).await {
+                                Ok(similarity) => similarity,
+                                Err(err) => {
+                                    debug_state.record_error("database", &err);
+                                    events.emit(&issue, Stage::Failed, Some(err.to_string()));
+                                    record.error(&err);
                                     error!(
                                         issue_id = issue.source_id,
                                         err = err.to_string(),
-                                        "failed to comment on issue"
+                                        "failed to fetch similarity settings"
                                     );
+                                    record.finish().await;
+                                    continue;
                                 }
+                            };
+
+                        let system_info = extractor.extract(&issue, Some(&summarization_api)).await;
+                        let exact_duplicate = match fingerprints
+                            .exact_duplicate(&issue, &excluded_labels, &search_scope)
+                            .await
+                        {
+                            Ok(duplicate) => duplicate,
+                            Err(err) => {
+                                debug_state.record_error("database", &err);
+                                error!(
+                                    issue_id = issue.source_id,
+                                    err = err.to_string(),
+                                    "failed to look up issues with the same traceback"
+                                );
+                                None
                             }
-                            (false, Source::HuggingFace) => {
-                                if let Err(err) = huggingface_api
-                                    .comment_on_issue(&issue.url, closest_issues)
+                        };
+                        let is_exact_duplicate = exact_duplicate.is_some();
+                        let closest_issues = if let Some(duplicate) = exact_duplicate {
+                            info!(
+                                issue_id = issue.source_id,
+                                duplicate = duplicate.number,
+                                "same traceback as an existing issue, skipping similarity search"
+                            );
+                            vec![duplicate]
+                        } else {
+                            match record
+                                .time(
+                                    LoggedStage::Search,
+                                    hot_issues.closest_issues(
+                                        &raw_embedding,
+                                        &excluded_labels,
+                                        &search_scope,
+                                        diversity::candidate_count(
+                                            &diversity_cfg,
+                                            similarity.max_suggestions,
+                                        ),
+                                        diversity_cfg.enabled,
+                                    ),
+                                )
+                                .await
+                            {
+                                Ok(issues) => {
+                                    rank_suggestions(
+                                        &repo_metadata,
+                                        &issue_links,
+                                        &extractor,
+                                        &diversity_cfg,
+                                        &issue.repository_full_name,
+                                        &system_info,
+                                        &similarity,
+                                        issues,
+                                    )
                                     .await
-                                {
+                                }
+                                Err(err) => {
+                                    debug_state.record_error("database", &err);
+                                    events.emit(&issue, Stage::Failed, Some(err.to_string()));
+                                    record.error(&err);
                                     error!(
                                         issue_id = issue.source_id,
                                         err = err.to_string(),
-                                        "failed to comment on issue"
+                                        "failed to fetch closest issues"
                                     );
+                                    record.finish().await;
+                                    continue;
                                 }
                             }
-                            _ => (),
-                        }
+                        };
 
-                        if let Err(err) = sqlx::query(
-                        r#"insert into issues (source_id, source, title, body, is_pull_request, number, html_url, url, repository_full_name, embedding)
-                           values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"#
-                        )
-                        .bind(issue.source_id)
-                        .bind(issue.source.to_string())
-                        .bind(issue.title)
-                        .bind(issue.body)
-                        .bind(issue.is_pull_request)
-                        .bind(issue.number)
-                        .bind(issue.html_url)
-                        .bind(issue.url)
-                        .bind(issue.repository_full_name)
-                        .bind(embedding)
-                        .execute(&pool)
-                        .await {
+                        events.emit(
+                            &issue,
+                            Stage::Matched,
+                            Some(format!("{} matches", closest_issues.len())),
+                        );
+                        if let Err(err) = escalation.track(&issue, &closest_issues).await {
+                            debug_state.record_error("escalation", &err);
                             error!(
                                 issue_id = issue.source_id,
                                 err = err.to_string(),
-                                "error inserting issue"
+                                "failed to track matched issues for escalation"
                             );
                         }
 
-                        None
-                    }
-                    Action::Edited => {
-                        if let Err(err) = sqlx::query!(
-                            r#"update issues
-                           set title = $1, body = $2, url = $3, updated_at = current_timestamp
-                           where source_id = $4"#,
-                            issue.title,
-                            issue.body,
-                            issue.url,
-                            issue.source_id,
-                        )
-                        .execute(&pool)
-                        .await
-                        {
-                            error!(
-                                issue_id = issue.source_id,
-                                err = err.to_string(),
-                                "error updating issue"
-                            );
-                        }
-                        Some(issue.source_id)
-                    }
-                    Action::Deleted => {
-                        if let Err(err) = sqlx::query!(
-                            r#"DELETE FROM issues WHERE source_id = $1"#,
-                            issue.source_id
-                        )
-                        .execute(&pool)
-                        .await
+                        let prompt_profile = summarization_api.prompt_profile(&issue);
+                        let summarized_issue = match record
+                            .time(
+                                LoggedStage::Summarize,
+                                with_retry_on(
+                                    &retry_policy,
+                                    || summarization_api.host(),
+                                    || {
+                                        summarization_api
+                                            .summarize(summary_input.clone(), prompt_profile)
+                                    },
+                                ),
+                            )
+                            .await
                         {
-                            error!(
-                                issue_id = issue.source_id,
-                                err = err.to_string(),
-                                "error deleting issue"
-                            );
-                        }
-                        None
-                    }
-                }
-            }
-            EventData::Comment(comment) => {
-                info!("handling comment (state: {})", comment.action);
-                match comment.action {
-                    Action::Created => {
-                        let issue_id = match sqlx::query!(
-                            "select id from issues where source_id = $1",
-                            comment.issue_id
-                        )
-                        .fetch_optional(&pool)
-                        .await
+                            Ok(summary) => summary,
+                            Err(err) => {
+                                debug_state.record_error("summarization_api", &err);
+                                events.emit(&issue, Stage::Failed, Some(err.to_string()));
+                                record.error(&err);
+                                error!(
+                                    issue_id = issue.source_id,
+                                    prompt_profile,
+                                    err = err.to_string(),
+                                    "summarization error"
+                                );
+                                record.finish().await;
+                                continue;
+                            }
+                        };
+
+                        let repository = match repo_metadata.get(&issue.repository_full_name).await
                         {
-                            Ok(id) => id,
+                            Ok(repository) => repository,
                             Err(err) => {
+                                debug_state.record_error("database", &err);
                                 error!(
-                                    comment_id = comment.source_id,
+                                    issue_id = issue.source_id,
                                     err = err.to_string(),
-                                    "failed to fetch issue id for comment"
+                                    "failed to fetch repository metadata"
                                 );
                                 None
                             }
                         };
-                        if let Some(issue_id) = issue_id {
-                            if let Err(err) = sqlx::query!(
-                                r#"insert into comments (source_id, body, url, issue_id)
-                               values ($1, $2, $3, $4)"#,
-                                comment.source_id,
-                                comment.body,
-                                comment.url,
-                                issue_id.id,
-                            )
-                            .execute(&pool)
-                            .await
+                        if !suppressed {
+                            let mentions = owners.slack_mentions(&issue).await;
+                            if let Err(err) = slack
+                                .closest_issues(
+                                    summarized_issue.clone(),
+                                    &issue,
+                                    repository.as_ref(),
+                                    &closest_issues,
+                                    &mentions,
+                                    &system_info,
+                                )
+                                .await
                             {
+                                debug_state.record_error("slack", &err);
                                 error!(
-                                    comment_id = comment.source_id,
+                                    issue_id = issue.source_id,
+                                    err = err.to_string(),
+                                    "failed to send closest issues to slack"
+                                );
+                            }
+                            if let Err(err) = email.closest_issues(&issue, &closest_issues).await {
+                                debug_state.record_error("email", &err);
+                                error!(
+                                    issue_id = issue.source_id,
                                     err = err.to_string(),
-                                    "error inserting comment"
+                                    "failed to email closest issues"
                                 );
                             }
-                            Some(comment.issue_id)
+                        }
+
+                        let first_responder = repositories
+                            .get(&issue.repository_full_name)
+                            .is_some_and(|r| r.first_responder);
+                        let curated_answer = if first_responder && !issue.is_pull_request {
+                            match knowledge_base
+                                .answer_for(&raw_embedding, &issue.repository_full_name)
+                                .await
+                            {
+                                Ok(entry) => entry,
+                                Err(err) => {
+                                    debug_state.record_error("database", &err);
+                                    error!(
+                                        issue_id = issue.source_id,
+                                        err = err.to_string(),
+                                        "failed to fetch knowledge base answer"
+                                    );
+                                    None
+                                }
+                            }
                         } else {
-                            error!(
-                                comment_id = comment.source_id,
-                                linked_issue_id = comment.issue_id,
-                                url = comment.url,
-                                "could not find issue associated with comment"
-                            );
                             None
-                        }
-                    }
-                    Action::Edited => {
-                        if let Err(err) = sqlx::query!(
-                            r#"update comments
-                           set body = $1, url = $2, updated_at = current_timestamp
-                           where source_id = $3"#,
-                            comment.body,
-                            comment.url,
-                            comment.source_id,
-                        )
-                        .execute(&pool)
-                        .await
+                        };
+
+                        let run_id = nanoid!();
+                        let suggests = curated_answer.is_none();
+                        let guidance_pointer = if suggests {
+                            match guidance.pointer_for(&issue, &raw_embedding).await {
+                                Ok(pointer) => pointer,
+                                Err(err) => {
+                                    debug_state.record_error("database", &err);
+                                    error!(
+                                        issue_id = issue.source_id,
+                                        err = err.to_string(),
+                                        "failed to fetch guidance section"
+                                    );
+                                    None
+                                }
+                            }
+                        } else {
+                            None
+                        };
+                        let comment = match (issue.is_pull_request, &issue.source) {
+                            _ if suppressed => {
+                                info!(
+                                    issue_id = issue.source_id,
+                                    "suppressed issue, not commenting"
+                                );
+                                None
+                            }
+                            _ if is_security_report => {
+                                info!(
+                                    issue_id = issue.source_id,
+                                    "security report, not commenting publicly"
+                                );
+                                None
+                            }
+                            _ if curated_answer.is_some() => curated_answer.map(|entry| {
+                                info!(
+                                    issue_id = issue.source_id,
+                                    entry_id = entry.id,
+                                    "answering from knowledge base"
+                                );
+                                let message = knowledge_base.message();
+                                match issue.source {
+                                    Source::Github => github_api.curated_answer_comment(
+                                        message,
+                                        &entry.answer,
+                                        &run_id,
+                                    ),
+                                    Source::HuggingFace => huggingface_api.curated_answer_comment(
+                                        message,
+                                        &entry.answer,
+                                        &run_id,
+                                    ),
+                                }
+                            }),
+                            _ if closest_issues.is_empty() => guidance_pointer
+                                .as_deref()
+                                .map(|pointer| github_api.guidance_comment(pointer, &run_id)),
+                            (false, Source::Github) if is_exact_duplicate => {
+                                Some(github_api.duplicate_comment(
+                                    &issue.repository_full_name,
+                                    &closest_issues[0],
+                                    fingerprints.message(),
+                                    &run_id,
+                                ))
+                            }
+                            (false, Source::HuggingFace) if is_exact_duplicate => {
+                                Some(huggingface_api.duplicate_comment(
+                                    &issue.repository_full_name,
+                                    &closest_issues[0],
+                                    fingerprints.message(),
+                                    &run_id,
+                                ))
+                            }
+                            (false, Source::Github) => Some(github_api.suggestions_comment(
+                                &issue.repository_full_name,
+                                &closest_issues,
+                                guidance_pointer.as_deref(),
+                                &run_id,
+                            )),
+                            (false, Source::HuggingFace) => {
+                                Some(huggingface_api.suggestions_comment(
+                                    &issue.repository_full_name,
+                                    &closest_issues,
+                                    &run_id,
+                                ))
+                            }
+                            _ => None,
+                        };
+                        if let Some(comment) = comment {
+                            match record
+                                .time(
+                                    LoggedStage::Comment,
+                                    comment_queue.enqueue_suggestions(
+                                        &issue.source,
+                                        &issue.repository_full_name,
+                                        &issue.url,
+                                        &run_id,
+                                        Some(prompt_profile),
+                                        comment,
+                                    ),
+                                )
+                                .await
+                            {
+                                Ok(()) => {
+                                    events.emit(&issue, Stage::Commented, None);
+                                    record.queued(&run_id);
+                                    if suggests {
+                                        if let Err(err) = duplicate_resolutions
+                                            .record_suggestions(
+                                                &issue,
+                                                &closest_issues,
+                                                &run_id,
+                                                prompt_profile,
+                                            )
+                                            .await
+                                        {
+                                            debug_state.record_error("database", &err);
+                                            error!(
+                                                issue_id = issue.source_id,
+                                                err = err.to_string(),
+                                                "failed to record suggestions"
+                                            );
+                                        }
+                                    }
+                                }
+                                Err(err) => {
+                                    debug_state.record_error("database", &err);
+                                    events.emit(&issue, Stage::Failed, Some(err.to_string()));
+                                    record.error(&err);
+                                    error!(
+                                        issue_id = issue.source_id,
+                                        err = err.to_string(),
+                                        "failed to enqueue comment"
+                                    );
+                                }
+                            }
+                        } else {
+                            record.skipped();
+                        }
+                        if let (false, Source::Github, false, false) = (
+                            issue.is_pull_request,
+                            &issue.source,
+                            is_security_report,
+                            suppressed,
+                        ) {
+                            if let Err(err) = butler.propose_closure(&issue, &closest_issues).await
+                            {
+                                debug_state.record_error("butler", &err);
+                                error!(
+                                    issue_id = issue.source_id,
+                                    err = err.to_string(),
+                                    "failed to propose closure"
+                                );
+                            }
+                            if let Err(err) = backlinker.link(&issue, &closest_issues).await {
+                                debug_state.record_error("backlinks", &err);
+                                error!(
+                                    issue_id = issue.source_id,
+                                    err = err.to_string(),
+                                    "failed to post backlink"
+                                );
+                            }
+                        }
+
+                        match db
+                            .insert_issue(
+                                &issue,
+                                &raw_embedding,
+                                fingerprints.of(&issue.body).as_deref(),
+                            )
+                            .await
+                        {
+                            Ok(()) => {
+                                record_embedding_metadata(
+                                    &db,
+                                    &debug_state,
+                                    &issue_text,
+                                    issue.source_id,
+                                    embedding_metadata,
+                                )
+                                .await;
+                                if snippets.is_some() {
+                                    store_code_context(
+                                        &db,
+                                        &debug_state,
+                                        issue.source_id,
+                                        snippets.as_deref(),
+                                    )
+                                    .await;
+                                }
+                                if let Some(discussion) = &hydrated {
+                                    store_hydrated_discussion(
+                                        &db,
+                                        &debug_state,
+                                        &issue,
+                                        discussion,
+                                    )
+                                    .await;
+                                }
+                            }
+                            Err(err) => {
+                                debug_state.record_error("database", &err);
+                                error!(
+                                    issue_id = issue.source_id,
+                                    err = err.to_string(),
+                                    "error inserting issue"
+                                );
+                            }
+                        }
+                        if let Err(err) = extractor
+                            .store(issue.source_id, &issue.body, &system_info)
+                            .await
                         {
+                            debug_state.record_error("database", &err);
                             error!(
-                                comment_id = comment.source_id,
+                                issue_id = issue.source_id,
                                 err = err.to_string(),
-                                "error updating comment"
+                                "error storing issue system info"
                             );
                         }
-                        Some(comment.issue_id)
+
+                        None
                     }
-                    Action::Deleted => {
-                        if let Err(err) = sqlx::query!(
-                            r#"DELETE FROM comments WHERE source_id = $1"#,
-                            comment.source_id
-                        )
-                        .execute(&pool)
-                        .await
+                    Action::Edited => {
+                        hot_issues.invalidate(issue.source_id).await;
+                        if let Err(err) = db
+                            .update_issue(&issue, fingerprints.of(&issue.body).as_deref())
+                            .await
+                        {
+                            debug_state.record_error("database", &err);
+                            error!(
+                                issue_id = issue.source_id,
+                                err = err.to_string(),
+                                "error updating issue"
+                            );
+                        }
+                        // stored for the re-embedding to pick them up
+                        if let Some(discussion) = &hydrated {
+                            store_hydrated_discussion(&db, &debug_state, &issue, discussion).await;
+                        }
+                        if matches!(issue.source, Source::Github) {
+                            let text =
+                                issue_text.compose(&issue.title, &issue.body, &[] as &[&str]);
+                            let snippets = code_context
+                                .snippets(&issue.repository_full_name, &text)
+                                .await;
+                            store_code_context(
+                                &db,
+                                &debug_state,
+                                issue.source_id,
+                                snippets.as_deref(),
+                            )
+                            .await;
+                        }
+                        // edits only go through the regexes, sparing a completion per edit
+                        if let Err(err) = extractor
+                            .update(issue.source_id, &issue.repository_full_name, &issue.body)
+                            .await
                         {
+                            debug_state.record_error("database", &err);
+                            error!(
+                                issue_id = issue.source_id,
+                                err = err.to_string(),
+                                "error storing issue system info"
+                            );
+                        }
+                        Some(issue.source_id)
+                    }
+                    Action::Deleted => {
+                        if let Err(err) = archive.archive_issue(issue.source_id).await {
+                            debug_state.record_error("archive", &err);
+                            error!(
+                                issue_id = issue.source_id,
+                                err = err.to_string(),
+                                "error archiving issue, keeping it in the database"
+                            );
+                            record.error(&err);
+                            record.finish().await;
+                            continue;
+                        }
+                        hot_issues.invalidate(issue.source_id).await;
+                        if let Err(err) = db.delete_issue(issue.source_id).await {
+                            debug_state.record_error("database", &err);
+                            error!(
+                                issue_id = issue.source_id,
+                                err = err.to_string(),
+                                "error deleting issue"
+                            );
+                        }
+                        None
+                    }
+                }
+            }
+            EventData::IssueMetadata(metadata) => {
+                info!("handling issue metadata update");
+                hot_issues.invalidate(metadata.source_id).await;
+                if let Err(err) = db
+                    .update_issue_metadata(
+                        metadata.source_id,
+                        &metadata.labels,
+                        metadata.milestone.as_deref(),
+                    )
+                    .await
+                {
+                    debug_state.record_error("database", &err);
+                    error!(
+                        issue_id = metadata.source_id,
+                        err = err.to_string(),
+                        "error updating issue metadata"
+                    );
+                }
+                // labels are only embedded through the metadata template
+                Some(metadata.source_id).filter(|_| issue_text.embeds_labels())
+            }
+            EventData::IssueStateChange(change) => {
+                info!("handling issue state change");
+                if let Err(err) = db.set_issue_closed(change.source_id, change.closed).await {
+                    debug_state.record_error("database", &err);
+                    record.error(&err);
+                    error!(
+                        issue_id = change.source_id,
+                        err = err.to_string(),
+                        "error updating issue state"
+                    );
+                }
+                None
+            }
+            EventData::Comment(comment) => {
+                info!("handling comment (state: {})", comment.action);
+                if matches!(comment.action, Action::Created) {
+                    match comment_trigger.handle(&comment).await {
+                        Ok(Some(run_id)) => record.queued(&run_id),
+                        Ok(None) => (),
+                        Err(err) => {
+                            debug_state.record_error("comment_trigger", &err);
+                            record.error(&err);
                             error!(
                                 comment_id = comment.source_id,
                                 err = err.to_string(),
-                                "error deleting comment"
+                                "error suggesting similar issues for comment"
                             );
                         }
-                        Some(comment.issue_id)
+                    }
+                }
+                store_comment(&db, &debug_state, &comment).await
+            }
+            EventData::ReviewComment(comment) => {
+                info!("handling review comment (state: {})", comment.action);
+                match db
+                    .issue_source_id(&comment.repository_full_name, comment.number)
+                    .await
+                {
+                    Ok(Some(issue_id)) => {
+                        store_comment(&db, &debug_state, &comment.into_comment(issue_id)).await
+                    }
+                    Ok(None) => {
+                        info!(
+                            comment_id = comment.source_id,
+                            number = comment.number,
+                            "pull request isn't stored, skipping review comment"
+                        );
+                        None
+                    }
+                    Err(err) => {
+                        debug_state.record_error("database", &err);
+                        record.error(&err);
+                        error!(
+                            comment_id = comment.source_id,
+                            err = err.to_string(),
+                            "failed to fetch pull request of review comment"
+                        );
+                        None
                     }
                 }
             }
             EventData::RepositoryIndexation(repo_data) => {
-                let embedding_api = embedding_api.clone();
+                let debug_state = debug_state.clone();
+                let embedding_queue = embedding_queue.clone();
+                let issue_text = issue_text.clone();
+                let redactor = redactor.clone();
                 let github_api = github_api.clone();
-                let pool = pool.clone();
+                let extractor = extractor.clone();
+                let fingerprints = fingerprints.clone();
+                let issue_links = issue_links.clone();
+                let repo_metadata = repo_metadata.clone();
+                let guidance = guidance.clone();
+                let settings = settings.clone();
+                let db = db.clone();
+                let locks = locks.clone();
                 let span = info_span!(
                     "repository_indexation",
                     repository = repo_data.full_name,
                     source = repo_data.source.to_string()
                 );
-                tokio::spawn(async move {
-                    info!("indexing started");
-                    let job = match sqlx::query_as!(
-                        Job,
-                        r#"select data as "data: Json<JobData>" from jobs where repository_full_name = $1 and job_type = $2"#,
-                        repo_data.full_name,
-                        JobType::IssueIndexation as _,
-                    )
-                    .fetch_optional(&pool)
-                    .await {
-                        Ok(job) => job,
-                        Err(err) => {
-                            error!(err = err.to_string(), "error fetching job");
-                            return;
-                        }
-                    };
-                    let from_issues_page =
-                        job.and_then(|j| match j.data.0 { JobData::IssueIndexation { next_url } => Some(next_url), _ => None});
-                    let issues = github_api.get_issues(from_issues_page, repo_data.clone());
-                    pin_mut!(issues);
-                    while let Some(issue) = issues.next().await {
-                        let (issue, next_url) = match issue {
-                            Ok(issue) => issue,
-                            Err(err) => {
-                                error!(err = err.to_string(), "error fetching next item from issues stream");
-                                continue;
-                            }
-                        };
-                        let embedding_api = embedding_api.clone();
-                        let pool = pool.clone();
-                        let source = repo_data.source.to_string();
-                        let comment_string = format!(
-                            "\n----\nComment: {}",
-                            issue
-                                .comments
-                                .iter()
-                                .map(|c| c.body.to_owned())
-                                .collect::<Vec<String>>()
-                                .join("\n----\nComment: ")
-                        );
-                        let issue_text =
-                            format!("# {}\n{}{}", issue.title, issue.body, comment_string);
-                        let raw_embedding = match embedding_api.generate_embedding(issue_text).await {
-                            Ok(embedding) => embedding,
-                            Err(err) => {
-                                error!(issue_number = issue.number, err = err.to_string(), "generate embedding error");
-                                continue;
+                supervisor.spawn(
+                    "repository_indexation",
+                    async move {
+                        let lock_name = crate::locks::repository_indexation(&repo_data.full_name);
+                        let lease = match locks.try_acquire(&lock_name).await {
+                            Ok(Some(lease)) => lease,
+                            Ok(None) => {
+                                info!("repository already being indexed by another instance");
+                                return;
                             }
-                        };
-                        let embedding =
-                            Vector::from(raw_embedding);
-                        let issue_id: Option<i32> = match sqlx::query_scalar!(
-                            "select id from issues where source_id = $1",
-                            issue.id
-                        )
-                        .fetch_optional(&pool)
-                        .await {
-                            Ok(id) => id,
                             Err(err) => {
-                                error!(issue_number = issue.number, err = err.to_string(), "failed to fetch issue id");
-                                continue;
+                                debug_state.record_error("database", &err);
+                                error!(err = err.to_string(), "failed to acquire indexation lock");
+                                return;
                             }
                         };
-                        let issue_id = if let Some(id) = issue_id {
-                            id
-                        } else {
-                            match sqlx::query_scalar(
-                            r#"insert into issues (source_id, source, title, body, is_pull_request, number, html_url, url, repository_full_name, embedding)
-                               values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
-                               returning id"#
+                        async {
+                            if should_stop(
+                                &db,
+                                &settings,
+                                &debug_state,
+                                JobType::IssueIndexation,
+                                Some(&repo_data.full_name),
                             )
-                            .bind(issue.id)
-                            .bind(source)
-                            .bind(issue.title)
-                            .bind(issue.body)
-                            .bind(issue.is_pull_request)
-                            .bind(issue.number)
-                            .bind(issue.html_url)
-                            .bind(issue.url)
-                            .bind(&repo_data.full_name)
-                            .bind(embedding)
-                            .fetch_one(&pool)
-                            .await {
-                                Ok(id) => id,
-                                Err(err) => {
-                                    error!(issue_number = issue.number, err = err.to_string(), "error inserting issue");
-                                    continue;
+                            .await
+                            {
+                                return;
+                            }
+                            info!("indexing started");
+                            if let Source::Github = repo_data.source {
+                                if let Err(err) = repo_metadata.refresh(&repo_data.full_name).await
+                                {
+                                    debug_state.record_error("repo_metadata", &err);
+                                    error!(
+                                        err = err.to_string(),
+                                        "failed to fetch repository metadata"
+                                    );
+                                }
+                                if let Err(err) = guidance.index(&repo_data.full_name).await {
+                                    debug_state.record_error("guidance", &err);
+                                    error!(
+                                        err = err.to_string(),
+                                        "failed to index repository guidance"
+                                    );
                                 }
                             }
-                        };
-                        if !issue.comments.is_empty() {
-                            let mut qb = QueryBuilder::new(
-                                "insert into comments (source_id, body, url, issue_id)",
-                            );
-                            qb.push_values(issue.comments, |mut b, comment| {
-                                b.push_bind(comment.id)
-                                    .push_bind(comment.body)
-                                    .push_bind(comment.url)
-                                    .push_bind(issue_id);
+                            let indexation_name = repo_data.to_string();
+                            debug_state.start_indexation(&indexation_name, None);
+                            let job = match db
+                                .get_job(JobType::IssueIndexation, Some(&repo_data.full_name))
+                                .await
+                            {
+                                Ok(job) => job,
+                                Err(err) => {
+                                    debug_state.record_error("database", &err);
+                                    error!(err = err.to_string(), "error fetching job");
+                                    debug_state.finish_indexation(&indexation_name);
+                                    return;
+                                }
+                            };
+                            let from_issues_page = job.and_then(|j| match j {
+                                JobData::IssueIndexation { next_url, .. } => Some(next_url),
+                                _ => None,
                             });
-                            qb.push("on conflict do nothing");
-                            if let Err(err) = qb.build().execute(&pool).await {
-                                error!(issue_number = issue.number, err = err.to_string(), "error inserting comments");
+                            let issues = github_api.get_issues(from_issues_page, repo_data.clone());
+                            pin_mut!(issues);
+                            while let Some(issue) = issues.next().await {
+                                if lease.is_lost() {
+                                    warn!("indexation lock was taken over, stopping");
+                                    debug_state.finish_indexation(&indexation_name);
+                                    return;
+                                }
+                                let (mut issue, next_url) = match issue {
+                                    Ok(issue) => issue,
+                                    Err(err) => {
+                                        debug_state.record_error("github_api", &err);
+                                        error!(
+                                            err = err.to_string(),
+                                            "error fetching next item from issues stream"
+                                        );
+                                        continue;
+                                    }
+                                };
+                                debug_state.indexation_progress(&indexation_name, next_url.clone());
+                                redactor.redact_issue(&mut issue);
+                                let embedding_queue = embedding_queue.clone();
+                                let comments: Vec<&str> =
+                                    issue.comments.iter().map(|c| c.body.as_str()).collect();
+                                let embedding_text = issue_text.embedding_input(
+                                    issue_text.compose(&issue.title, &issue.body, &comments),
+                                    &repo_data.full_name,
+                                    &issue.labels,
+                                    &issue.body,
+                                );
+                                let (raw_embedding, embedding_metadata) = match embedding_queue
+                                    .generate_embedding_with_metadata(
+                                        embedding_text,
+                                        Priority::Background,
+                                    )
+                                    .await
+                                {
+                                    Ok(embedding) => embedding,
+                                    Err(err) => {
+                                        debug_state.record_error("embedding_api", &err);
+                                        error!(
+                                            issue_number = issue.number,
+                                            err = err.to_string(),
+                                            "generate embedding error"
+                                        );
+                                        continue;
+                                    }
+                                };
+                                let issue_id = match db.issue_id(issue.id).await {
+                                    Ok(id) => id,
+                                    Err(err) => {
+                                        debug_state.record_error("database", &err);
+                                        error!(
+                                            issue_number = issue.number,
+                                            err = err.to_string(),
+                                            "failed to fetch issue id"
+                                        );
+                                        continue;
+                                    }
+                                };
+                                let issue_id = if let Some(id) = issue_id {
+                                    if let Err(err) = db
+                                        .update_issue_metadata(
+                                            issue.id,
+                                            &issue.labels,
+                                            issue.milestone.as_deref(),
+                                        )
+                                        .await
+                                    {
+                                        debug_state.record_error("database", &err);
+                                        error!(
+                                            issue_number = issue.number,
+                                            err = err.to_string(),
+                                            "error updating issue metadata"
+                                        );
+                                    }
+                                    id
+                                } else {
+                                    match db
+                                        .insert_indexed_issue(
+                                            &issue,
+                                            &repo_data.source,
+                                            &repo_data.full_name,
+                                            &raw_embedding,
+                                            fingerprints.of(&issue.body).as_deref(),
+                                        )
+                                        .await
+                                    {
+                                        Ok(id) => {
+                                            record_embedding_metadata(
+                                                &db,
+                                                &debug_state,
+                                                &issue_text,
+                                                issue.id,
+                                                embedding_metadata,
+                                            )
+                                            .await;
+                                            id
+                                        }
+                                        Err(err) => {
+                                            debug_state.record_error("database", &err);
+                                            error!(
+                                                issue_number = issue.number,
+                                                err = err.to_string(),
+                                                "error inserting issue"
+                                            );
+                                            continue;
+                                        }
+                                    }
+                                };
+                                if let Err(err) = db.insert_indexed_comments(issue_id, &issue).await
+                                {
+                                    debug_state.record_error("database", &err);
+                                    error!(
+                                        issue_number = issue.number,
+                                        err = err.to_string(),
+                                        "error inserting comments"
+                                    );
+                                }
+                                if let Err(err) = extractor
+                                    .update(issue.id, &repo_data.full_name, &issue.body)
+                                    .await
+                                {
+                                    debug_state.record_error("database", &err);
+                                    error!(
+                                        issue_number = issue.number,
+                                        err = err.to_string(),
+                                        "error storing issue system info"
+                                    );
+                                }
+                                if issue_links.ingest_on_index() {
+                                    if let Err(err) =
+                                        issue_links.ingest(&repo_data.full_name, issue.number).await
+                                    {
+                                        debug_state.record_error("github_api", &err);
+                                        error!(
+                                            issue_number = issue.number,
+                                            err = err.to_string(),
+                                            "error ingesting issue timeline"
+                                        );
+                                    }
+                                }
+                                if let Some(next_url) = next_url {
+                                    if let Err(err) = db
+                                        .save_job(
+                                            JobType::IssueIndexation,
+                                            Some(&repo_data.full_name),
+                                            &JobData::IssueIndexation {
+                                                next_url,
+                                                source: repo_data.source.clone(),
+                                            },
+                                        )
+                                        .await
+                                    {
+                                        debug_state.record_error("database", &err);
+                                        error!(
+                                            issue_number = issue.number,
+                                            err = err.to_string(),
+                                            "error inserting job"
+                                        )
+                                    } else if should_stop(
+                                        &db,
+                                        &settings,
+                                        &debug_state,
+                                        JobType::IssueIndexation,
+                                        Some(&repo_data.full_name),
+                                    )
+                                    .await
+                                    {
+                                        debug_state.finish_indexation(&indexation_name);
+                                        return;
+                                    }
+                                }
                             }
-                        }
-                        if let Some(next_url) = next_url {
-                            if let Err(err) = sqlx::query(
-                                r#"insert into jobs (data, job_type, repository_full_name)
-                               values ($1, $2, $3)
-                               on conflict (repository_full_name)
-                               do update
-                               set
-                                   data = EXCLUDED.data,
-                                   updated_at = current_timestamp"#,
-                            )
-                            .bind(Json(JobData::IssueIndexation {
-                                next_url,
-                            }))
-                            .bind(JobType::IssueIndexation)
-                            .bind(&repo_data.full_name)
-                            .execute(&pool)
-                            .await {
-                                error!(issue_number = issue.number, err = err.to_string(), "error inserting job")
+                            debug_state.finish_indexation(&indexation_name);
+                            if let Err(err) = db
+                                .delete_job(JobType::IssueIndexation, Some(&repo_data.full_name))
+                                .await
+                            {
+                                debug_state.record_error("database", &err);
+                                error!(err = err.to_string(), "failed to delete job");
+                                return;
                             }
+                            info!("finished indexing");
+                        }
+                        .await;
+                        if let Err(err) = lease.release().await {
+                            debug_state.record_error("database", &err);
+                            error!(err = err.to_string(), "failed to release indexation lock");
                         }
                     }
-                    if let Err(err) = sqlx::query!(
-                        "delete from jobs where repository_full_name = $1",
-                        repo_data.full_name
-                    )
-                    .execute(&pool)
-                    .await {
-                        error!(err = err.to_string(), "failed to delete job");
-                        return;
-                    }
-                    info!("finished indexing");
-                }.instrument(span));
+                    .instrument(span),
+                );
                 None
             }
             EventData::IssueIndexation(index_issue_data) => {
-                let embedding_api = embedding_api.clone();
+                let embedding_queue = embedding_queue.clone();
+                let issue_text = issue_text.clone();
+                let redactor = redactor.clone();
                 let github_api = github_api.clone();
-                let pool = pool.clone();
+                let db = db.clone();
                 let span = info_span!(
                     "issue_indexation",
                     repository = index_issue_data.repository_full_name,
@@ -711,7 +1836,7 @@ async fn handle_webhooks(
                 );
                 async {
                     info!("indexing started");
-                    let issue = match github_api
+                    let mut issue = match github_api
                         .get_issue(
                             index_issue_data.issue_number,
                             &index_issue_data.repository_full_name,
@@ -719,7 +1844,26 @@ async fn handle_webhooks(
                         .await
                     {
                         Ok(issue) => issue,
+                        Err(GithubApiError::Gone(_)) => {
+                            warn!("issue is gone upstream, skipping");
+                            let url = github_api.issue_url(
+                                &index_issue_data.repository_full_name,
+                                index_issue_data.issue_number,
+                            );
+                            if let Err(err) = db.mark_issue_gone(&url).await {
+                                debug_state.record_error("database", &err);
+                                error!(err = err.to_string(), "failed to mark issue as gone");
+                            }
+                            hot_issues
+                                .invalidate_number(
+                                    &index_issue_data.repository_full_name,
+                                    index_issue_data.issue_number,
+                                )
+                                .await;
+                            return;
+                        }
                         Err(err) => {
+                            debug_state.record_error("github_api", &err);
                             error!(
                                 issue_number = index_issue_data.issue_number,
                                 err = err.to_string(),
@@ -728,20 +1872,22 @@ async fn handle_webhooks(
                             return;
                         }
                     };
-                    let source = "Github".to_string();
-                    let comment_string = format!(
-                        "\n----\nComment: {}",
-                        issue
-                            .comments
-                            .iter()
-                            .map(|c| c.body.to_owned())
-                            .collect::<Vec<String>>()
-                            .join("\n----\nComment: ")
+                    redactor.redact_issue(&mut issue);
+                    let comments: Vec<&str> =
+                        issue.comments.iter().map(|c| c.body.as_str()).collect();
+                    let embedding_text = issue_text.embedding_input(
+                        issue_text.compose(&issue.title, &issue.body, &comments),
+                        &index_issue_data.repository_full_name,
+                        &issue.labels,
+                        &issue.body,
                     );
-                    let issue_text = format!("# {}\n{}{}", issue.title, issue.body, comment_string);
-                    let raw_embedding = match embedding_api.generate_embedding(issue_text).await {
+                    let (raw_embedding, embedding_metadata) = match embedding_queue
+                        .generate_embedding_with_metadata(embedding_text, Priority::Background)
+                        .await
+                    {
                         Ok(embedding) => embedding,
                         Err(err) => {
+                            debug_state.record_error("embedding_api", &err);
                             error!(
                                 issue_number = issue.number,
                                 err = err.to_string(),
@@ -750,16 +1896,10 @@ async fn handle_webhooks(
                             return;
                         }
                     };
-                    let embedding = Vector::from(raw_embedding);
-                    let issue_id: Option<i32> = match sqlx::query_scalar!(
-                        "select id from issues where source_id = $1",
-                        issue.id
-                    )
-                    .fetch_optional(&pool)
-                    .await
-                    {
+                    let issue_id = match db.issue_id(issue.id).await {
                         Ok(id) => id,
                         Err(err) => {
+                            debug_state.record_error("database", &err);
                             error!(
                                 issue_number = issue.number,
                                 err = err.to_string(),
@@ -769,157 +1909,551 @@ async fn handle_webhooks(
                         }
                     };
                     let issue_id = if let Some(id) = issue_id {
+                        // pull request edits only come through here, the title and body may have
+                        // changed along with the embedding
+                        hot_issues.invalidate(issue.id).await;
+                        let edited = IssueData {
+                            source_id: issue.id,
+                            action: Action::Edited,
+                            author: issue.author.clone(),
+                            labels: issue.labels.clone(),
+                            milestone: issue.milestone.clone(),
+                            title: issue.title.clone(),
+                            body: issue.body.clone(),
+                            is_pull_request: issue.is_pull_request,
+                            number: issue.number,
+                            html_url: issue.html_url.clone(),
+                            url: issue.url.clone(),
+                            repository_full_name: index_issue_data.repository_full_name.clone(),
+                            source: Source::Github,
+                        };
+                        if let Err(err) = db
+                            .update_issue(&edited, fingerprints.of(&edited.body).as_deref())
+                            .await
+                        {
+                            debug_state.record_error("database", &err);
+                            error!(
+                                issue_number = issue.number,
+                                err = err.to_string(),
+                                "error updating issue"
+                            );
+                        }
+                        match db.update_issue_embedding(issue.id, &raw_embedding).await {
+                            Ok(()) => {
+                                record_embedding_metadata(
+                                    &db,
+                                    &debug_state,
+                                    &issue_text,
+                                    issue.id,
+                                    embedding_metadata,
+                                )
+                                .await
+                            }
+                            Err(err) => {
+                                debug_state.record_error("database", &err);
+                                error!(
+                                    issue_number = issue.number,
+                                    err = err.to_string(),
+                                    "error updating issue embedding"
+                                );
+                            }
+                        }
                         id
                     } else {
-                        match sqlx::query_scalar(
-                        r#"insert into issues (source_id, source, title, body, is_pull_request, number, html_url, url, repository_full_name, embedding)
-                           values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
-                           returning id"#
-                        )
-                        .bind(issue.id)
-                        .bind(source)
-                        .bind(issue.title)
-                        .bind(issue.body)
-                        .bind(issue.is_pull_request)
-                        .bind(issue.number)
-                        .bind(issue.html_url)
-                        .bind(issue.url)
-                        .bind(&index_issue_data.repository_full_name)
-                        .bind(embedding)
-                        .fetch_one(&pool)
-                        .await {
-                            Ok(id) => id,
+                        match db
+                            .insert_indexed_issue(
+                                &issue,
+                                &Source::Github,
+                                &index_issue_data.repository_full_name,
+                                &raw_embedding,
+                                fingerprints.of(&issue.body).as_deref(),
+                            )
+                            .await
+                        {
+                            Ok(id) => {
+                                record_embedding_metadata(
+                                    &db,
+                                    &debug_state,
+                                    &issue_text,
+                                    issue.id,
+                                    embedding_metadata,
+                                )
+                                .await;
+                                id
+                            }
                             Err(err) => {
-                                error!(issue_number = issue.number, err = err.to_string(), "error inserting issue");
+                                debug_state.record_error("database", &err);
+                                error!(
+                                    issue_number = issue.number,
+                                    err = err.to_string(),
+                                    "error inserting issue"
+                                );
                                 return;
                             }
                         }
                     };
-                    if !issue.comments.is_empty() {
-                        let mut qb = QueryBuilder::new(
-                            "insert into comments (source_id, body, url, issue_id)",
+                    if let Err(err) = db.insert_indexed_comments(issue_id, &issue).await {
+                        debug_state.record_error("database", &err);
+                        error!(
+                            issue_number = issue.number,
+                            err = err.to_string(),
+                            "error inserting comments"
                         );
-                        qb.push_values(issue.comments, |mut b, comment| {
-                            b.push_bind(comment.id)
-                                .push_bind(comment.body)
-                                .push_bind(comment.url)
-                                .push_bind(issue_id);
-                        });
-                        qb.push("on conflict do nothing");
-                        if let Err(err) = qb.build().execute(&pool).await {
-                            error!(issue_number = issue.number, err = err.to_string(), "error inserting comments");
+                    }
+                    if let Err(err) = extractor
+                        .update(
+                            issue.id,
+                            &index_issue_data.repository_full_name,
+                            &issue.body,
+                        )
+                        .await
+                    {
+                        debug_state.record_error("database", &err);
+                        error!(
+                            issue_number = issue.number,
+                            err = err.to_string(),
+                            "error storing issue system info"
+                        );
+                    }
+                    if let Some(head_sha) = index_issue_data
+                        .head_sha
+                        .as_deref()
+                        .filter(|_| issue.is_pull_request && check_runs.enabled())
+                    {
+                        let repository_full_name = &index_issue_data.repository_full_name;
+                        let excluded_labels = repositories
+                            .get(repository_full_name)
+                            .map(|r| r.excluded_labels.clone())
+                            .unwrap_or_default();
+                        let res = async {
+                            let similarity =
+                                settings.similarity(Some(repository_full_name)).await?;
+                            let mut candidates = hot_issues
+                                .closest_issues(
+                                    &raw_embedding,
+                                    &excluded_labels,
+                                    &repo_groups.search_scope(repository_full_name),
+                                    // the pull request was indexed before the search
+                                    diversity::candidate_count(
+                                        &diversity_cfg,
+                                        similarity.max_suggestions,
+                                    ) + 1,
+                                    diversity_cfg.enabled,
+                                )
+                                .await?;
+                            candidates.retain(|ci| {
+                                &ci.repository_full_name != repository_full_name
+                                    || ci.number != issue.number
+                            });
+                            let closest_issues = rank_suggestions(
+                                &repo_metadata,
+                                &issue_links,
+                                &extractor,
+                                &diversity_cfg,
+                                repository_full_name,
+                                &extractor.parse(repository_full_name, &issue.body),
+                                &similarity,
+                                candidates,
+                            )
+                            .await;
+                            check_runs
+                                .publish(
+                                    repository_full_name,
+                                    issue.number,
+                                    head_sha,
+                                    &closest_issues,
+                                )
+                                .await
+                        }
+                        .await;
+                        if let Err(err) = res {
+                            debug_state.record_error("check_runs", &err);
+                            error!(
+                                issue_number = issue.number,
+                                err = err.to_string(),
+                                "error publishing related issues check run"
+                            );
                         }
                     }
                     info!("finished indexing");
                 }
-                .instrument(span).await;
+                .instrument(span)
+                .await;
+                None
+            }
+            EventData::Feedback(feedback) => {
+                info!("handling feedback");
+                match db.insert_feedback(&feedback.run_id, feedback.vote).await {
+                    // compared per prompt profile, see [SummarizationApi::prompt_profile]
+                    Ok(prompt_profile) => ::metrics::counter!(
+                        "issue_bot_feedback_total",
+                        "profile" => prompt_profile.unwrap_or_else(|| "none".to_owned()),
+                        "vote" => feedback.vote.to_string(),
+                    )
+                    .increment(1),
+                    Err(err) => {
+                        debug_state.record_error("database", &err);
+                        error!(
+                            run_id = feedback.run_id,
+                            err = err.to_string(),
+                            "error inserting feedback"
+                        );
+                    }
+                }
+                None
+            }
+            EventData::RepositoryUpdate(update) => {
+                info!(repository = update.full_name, "handling repository update");
+                // cached issues of the repository are moved or made private
+                hot_issues.clear().await;
+                let mut full_name = update.full_name;
+                if let Some(moved_to) = update.moved_to {
+                    match db.move_repository(&full_name, &moved_to).await {
+                        Ok(moved) => {
+                            info!(from = full_name, to = moved_to, moved, "moved repository");
+                            full_name = moved_to;
+                        }
+                        Err(err) => {
+                            debug_state.record_error("database", &err);
+                            record.error(&err);
+                            error!(
+                                repository = full_name,
+                                err = err.to_string(),
+                                "error moving repository"
+                            );
+                        }
+                    }
+                }
+                if let Some(private) = update.private {
+                    if let Err(err) = db.set_repository_private(&full_name, private).await {
+                        debug_state.record_error("database", &err);
+                        record.error(&err);
+                        error!(
+                            repository = full_name,
+                            err = err.to_string(),
+                            "error updating repository visibility"
+                        );
+                    }
+                }
+                None
+            }
+            EventData::DuplicateClosure(closure) => {
+                info!("handling duplicate closure");
+                if let Err(err) = db.set_issue_closed(closure.source_id, true).await {
+                    debug_state.record_error("database", &err);
+                    error!(
+                        issue_id = closure.source_id,
+                        err = err.to_string(),
+                        "error updating issue state"
+                    );
+                }
+                if let Err(err) = duplicate_resolutions.resolve(&closure).await {
+                    debug_state.record_error("duplicate_resolutions", &err);
+                    record.error(&err);
+                    error!(
+                        issue_id = closure.source_id,
+                        err = err.to_string(),
+                        "failed to resolve duplicate closure"
+                    );
+                }
+                None
+            }
+            EventData::IssueSuppression(suppression) if suppression.lifted => {
+                info!("handling issue unsuppression");
+                match db.unsuppress_issue(suppression.source_id).await {
+                    Ok(was_suppressed) => info!(
+                        issue_id = suppression.source_id,
+                        unsuppressed_by = suppression.suppressed_by,
+                        was_suppressed,
+                        "unsuppressed issue"
+                    ),
+                    Err(err) => {
+                        debug_state.record_error("database", &err);
+                        record.error(&err);
+                        error!(
+                            issue_id = suppression.source_id,
+                            err = err.to_string(),
+                            "error unsuppressing issue"
+                        );
+                    }
+                }
+                None
+            }
+            EventData::IssueSuppression(suppression) => {
+                info!("handling issue suppression");
+                match db
+                    .suppress_issue(suppression.source_id, &suppression.suppressed_by)
+                    .await
+                {
+                    Ok(newly_suppressed) => info!(
+                        issue_id = suppression.source_id,
+                        suppressed_by = suppression.suppressed_by,
+                        newly_suppressed,
+                        "suppressed issue"
+                    ),
+                    Err(err) => {
+                        debug_state.record_error("database", &err);
+                        record.error(&err);
+                        error!(
+                            issue_id = suppression.source_id,
+                            err = err.to_string(),
+                            "error suppressing issue"
+                        );
+                    }
+                }
+                None
+            }
+            EventData::AuthorOptOut(opt_out) => {
+                info!("handling author opt-out");
+                let commented = match db.issues_commented_by(&opt_out.login).await {
+                    Ok(commented) => commented,
+                    Err(err) => {
+                        debug_state.record_error("database", &err);
+                        error!(
+                            login = opt_out.login,
+                            err = err.to_string(),
+                            "error fetching issues commented by author"
+                        );
+                        Vec::new()
+                    }
+                };
+                match db
+                    .opt_out_author(&opt_out.login, "comment", opt_out.purge)
+                    .await
+                {
+                    Ok(request) => {
+                        // the author's issues may be cached
+                        hot_issues.clear().await;
+                        info!(
+                            login = opt_out.login,
+                            purged = request.purged,
+                            issues = request.issues,
+                            comments = request.comments,
+                            "opted out author"
+                        );
+                        reembed_issues(
+                            &embedding_queue,
+                            &issue_text,
+                            &db,
+                            &debug_state,
+                            &commented,
+                        )
+                        .await;
+                    }
+                    Err(err) => {
+                        debug_state.record_error("database", &err);
+                        record.error(&err);
+                        error!(
+                            login = opt_out.login,
+                            err = err.to_string(),
+                            "error opting out author"
+                        );
+                    }
+                }
+                None
+            }
+            EventData::IssueEmbeddings(embeddings) => {
+                info!("handling issue embeddings");
+                reembed_issues(
+                    &embedding_queue,
+                    &issue_text,
+                    &db,
+                    &debug_state,
+                    &embeddings.source_ids,
+                )
+                .await;
+                hot_issues.clear().await;
                 None
             }
             EventData::RegenerateEmbeddings => {
-                let embedding_api = embedding_api.clone();
-                let pool = pool.clone();
+                let debug_state = debug_state.clone();
+                let drift = drift.clone();
+                let embedding_queue = embedding_queue.clone();
+                let hot_issues = hot_issues.clone();
+                let issue_text = issue_text.clone();
+                let settings = settings.clone();
+                let db = db.clone();
+                let locks = locks.clone();
                 let span = info_span!("embeddings_regeneration",);
-                tokio::spawn(
+                supervisor.spawn(
+                    "embeddings_regeneration",
                     async move {
-                        info!("embeddings regenaration started");
-                        let job = match sqlx::query_as!(
-                            Job,
-                            r#"select data as "data: Json<JobData>" from jobs where job_type = $1"#,
-                            JobType::EmbeddingsRegeneration as _,
-                        )
-                        .fetch_optional(&pool)
-                        .await
+                        let lease = match locks
+                            .try_acquire(crate::locks::EMBEDDINGS_REGENERATION)
+                            .await
                         {
-                            Ok(job) => job,
-                            Err(err) => {
-                                error!(err = err.to_string(), "error fetching job");
+                            Ok(Some(lease)) => lease,
+                            Ok(None) => {
+                                info!("embeddings already being regenerated by another instance");
                                 return;
                             }
-                        };
-                        let current_issue = job
-                            .as_ref()
-                            .and_then(|j| match j.data.0 {
-                                JobData::EmbeddingsRegeneration { current_issue } => {
-                                    Some(current_issue)
-                                }
-                                _ => None,
-                            })
-                            .unwrap_or(0);
-                        let issues = match sqlx::query!(
-                            r#"
-                                SELECT id, source_id
-                                FROM issues
-                                WHERE id > $1
-                                ORDER BY id
-                            "#,
-                            current_issue
-                        )
-                        .fetch_all(&pool)
-                        .await
-                        {
-                            Ok(ids) => ids,
                             Err(err) => {
+                                debug_state.record_error("database", &err);
                                 error!(
                                     err = err.to_string(),
-                                    "error fetching issue ids for embeddings regeneration"
+                                    "failed to acquire regeneration lock"
                                 );
                                 return;
                             }
                         };
-                        let total_issues = issues.len();
-                        info!("regenerating embeddings for {} issues", total_issues);
-                        for (current_issue_nb, issue) in issues.into_iter().enumerate() {
-                            if let Err(err) =
-                                update_issue_embedding(&embedding_api, &pool, issue.source_id).await
-                            {
-                                error!(
-                                    issue_id = issue.source_id,
-                                    err = err.to_string(),
-                                    "error regenerating issue embedding"
-                                );
-                            }
-                            if let Err(err) = sqlx::query(
-                                r#"insert into jobs (data, job_type)
-                               values ($1, $2)
-                               on conflict (job_type)
-                                   where job_type = $2
-                               do update
-                               set
-                                   data = EXCLUDED.data,
-                                   updated_at = current_timestamp"#,
+                        async {
+                            if should_stop(
+                                &db,
+                                &settings,
+                                &debug_state,
+                                JobType::EmbeddingsRegeneration,
+                                None,
                             )
-                            .bind(Json(JobData::EmbeddingsRegeneration {
-                                current_issue: issue.id,
-                            }))
-                            .bind(JobType::EmbeddingsRegeneration)
-                            .execute(&pool)
                             .await
                             {
-                                error!(
-                                    issue_id = issue.source_id,
-                                    err = err.to_string(),
-                                    "error inserting job"
+                                return;
+                            }
+                            info!("embeddings regenaration started");
+                            let job = match db.get_job(JobType::EmbeddingsRegeneration, None).await
+                            {
+                                Ok(job) => job,
+                                Err(err) => {
+                                    debug_state.record_error("database", &err);
+                                    error!(err = err.to_string(), "error fetching job");
+                                    return;
+                                }
+                            };
+                            let current_issue = job
+                                .as_ref()
+                                .and_then(|j| match j {
+                                    JobData::EmbeddingsRegeneration { current_issue } => {
+                                        Some(*current_issue)
+                                    }
+                                    _ => None,
+                                })
+                                .unwrap_or(0);
+                            if current_issue == 0 {
+                                if let Err(err) = drift.sample_baseline().await {
+                                    debug_state.record_error("database", &err);
+                                    error!(
+                                        err = err.to_string(),
+                                        "error sampling baseline linked issues similarities"
+                                    );
+                                }
+                            }
+                            let total_issues = match db.count_issues_after(current_issue).await {
+                                Ok(count) => count as usize,
+                                Err(err) => {
+                                    debug_state.record_error("database", &err);
+                                    error!(
+                                        err = err.to_string(),
+                                        "error counting issues for embeddings regeneration"
+                                    );
+                                    return;
+                                }
+                            };
+                            info!("regenerating embeddings for {} issues", total_issues);
+                            debug_state
+                                .start_indexation("embeddings_regeneration", Some(total_issues));
+                            let issues = db
+                                .issues_after(current_issue, REGENERATION_PAGE_SIZE)
+                                .enumerate();
+                            pin_mut!(issues);
+                            while let Some((current_issue_nb, issue)) = issues.next().await {
+                                if lease.is_lost() {
+                                    warn!("regeneration lock was taken over, stopping");
+                                    debug_state.finish_indexation("embeddings_regeneration");
+                                    return;
+                                }
+                                let issue = match issue {
+                                    Ok(issue) => issue,
+                                    Err(err) => {
+                                        debug_state.record_error("database", &err);
+                                        error!(
+                                            err = err.to_string(),
+                                            "error fetching issue ids for embeddings regeneration"
+                                        );
+                                        return;
+                                    }
+                                };
+                                if let Err(err) = update_issue_embedding(
+                                    &embedding_queue,
+                                    &issue_text,
+                                    &db,
+                                    issue.source_id,
                                 )
+                                .await
+                                {
+                                    debug_state.record_error("embeddings", &err);
+                                    error!(
+                                        issue_id = issue.source_id,
+                                        err = err.to_string(),
+                                        "error regenerating issue embedding"
+                                    );
+                                }
+                                if let Err(err) = db
+                                    .save_job(
+                                        JobType::EmbeddingsRegeneration,
+                                        None,
+                                        &JobData::EmbeddingsRegeneration {
+                                            current_issue: issue.id,
+                                        },
+                                    )
+                                    .await
+                                {
+                                    debug_state.record_error("database", &err);
+                                    error!(
+                                        issue_id = issue.source_id,
+                                        err = err.to_string(),
+                                        "error inserting job"
+                                    )
+                                } else if should_stop(
+                                    &db,
+                                    &settings,
+                                    &debug_state,
+                                    JobType::EmbeddingsRegeneration,
+                                    None,
+                                )
+                                .await
+                                {
+                                    debug_state.finish_indexation("embeddings_regeneration");
+                                    return;
+                                }
+                                debug_state.indexation_progress("embeddings_regeneration", None);
+                                if total_issues > 10 && current_issue_nb % (total_issues / 10) == 0
+                                {
+                                    info!(
+                                        issue_id = issue.source_id,
+                                        "regenerating embeddings, {}% completed",
+                                        current_issue_nb / total_issues * 100
+                                    );
+                                }
                             }
-                            if total_issues > 10 && current_issue_nb % (total_issues / 10) == 0 {
-                                info!(
-                                    issue_id = issue.source_id,
-                                    "regenerating embeddings, {}% completed",
-                                    current_issue_nb / total_issues * 100
-                                );
+                            debug_state.finish_indexation("embeddings_regeneration");
+                            hot_issues.clear().await;
+                            if let Err(err) =
+                                db.delete_job(JobType::EmbeddingsRegeneration, None).await
+                            {
+                                debug_state.record_error("database", &err);
+                                error!(err = err.to_string(), "failed to delete job");
+                                return;
+                            }
+                            info!("finished embeddings regeneration");
+                            match drift.sample_current().await {
+                                Ok(report) => info!(
+                                    common_pairs = report.common_pairs,
+                                    mean_delta = report.mean_delta,
+                                    "sampled linked issues similarities after regeneration"
+                                ),
+                                Err(err) => {
+                                    debug_state.record_error("database", &err);
+                                    error!(
+                                        err = err.to_string(),
+                                        "error sampling linked issues similarities"
+                                    );
+                                }
                             }
                         }
-                        if let Err(err) = sqlx::query!(
-                            "delete from jobs where job_type = $1",
-                            JobType::EmbeddingsRegeneration as _,
-                        )
-                        .execute(&pool)
-                        .await
-                        {
-                            error!(err = err.to_string(), "failed to delete job");
-                            return;
+                        .await;
+                        if let Err(err) = lease.release().await {
+                            debug_state.record_error("database", &err);
+                            error!(err = err.to_string(), "failed to release regeneration lock");
                         }
-                        info!("finished embeddings regeneration");
                     }
                     .instrument(span),
                 );
@@ -927,63 +2461,278 @@ async fn handle_webhooks(
             }
         };
 
+        record.finish().await;
         if let Some(issue_id) = issue_id {
-            if let Err(err) = update_issue_embedding(&embedding_api, &pool, issue_id).await {
+            debouncer.mark_dirty(issue_id);
+        }
+    }
+}
+
+/// Closest issues worth suggesting out of `candidates`, similar enough on their own then boosted
+/// and diversified, ranked alike wherever suggestions are made
+#[allow(clippy::too_many_arguments)]
+async fn rank_suggestions(
+    repo_metadata: &RepoMetadata,
+    issue_links: &IssueLinks,
+    extractor: &Extractor,
+    diversity_cfg: &DiversityConfig,
+    repository_full_name: &str,
+    system_info: &SystemInfo,
+    similarity: &SimilaritySettings,
+    candidates: Vec<ClosestIssue>,
+) -> Vec<ClosestIssue> {
+    // boosts only rank what is similar enough on its own
+    let candidates = candidates
+        .into_iter()
+        .filter(|ci| ci.cosine_similarity >= similarity.min_similarity)
+        .collect();
+    let candidates = repo_metadata
+        .boost_topics(repository_full_name, candidates)
+        .await;
+    let candidates = issue_links.boost_linked(candidates).await;
+    let candidates = extractor
+        .boost_cohort(repository_full_name, system_info, candidates)
+        .await;
+    diversity::diversify(diversity_cfg, candidates, similarity.max_suggestions)
+}
+
+/// Completes a Hugging Face discussion with the Hub's copy and returns its comments, keeping the
+/// webhook's copy when the Hub can't be reached
+async fn hydrate_discussion(
+    huggingface_api: &HuggingfaceApi,
+    debug_state: &DebugState,
+    issue: &mut IssueData,
+) -> Option<HubDiscussion> {
+    match huggingface_api.discussion(&issue.url).await {
+        Ok(mut discussion) => {
+            info!(
+                issue_id = issue.source_id,
+                status = discussion.status,
+                comments = discussion.comments.len(),
+                "hydrated discussion"
+            );
+            if !discussion.title.is_empty() {
+                issue.title = mem::take(&mut discussion.title);
+            }
+            if !discussion.body.is_empty() {
+                issue.body = mem::take(&mut discussion.body);
+            }
+            if discussion.author.is_some() {
+                issue.author = discussion.author.take();
+            }
+            Some(discussion)
+        }
+        Err(err) => {
+            debug_state.record_error("huggingface_api", &err);
+            warn!(
+                issue_id = issue.source_id,
+                err = err.to_string(),
+                "failed to hydrate discussion, keeping the webhook's copy"
+            );
+            None
+        }
+    }
+}
+
+/// Stores the comments of a discussion fetched from the Hub and whether it was closed, once the
+/// discussion itself is stored, see [hydrate_discussion]
+async fn store_hydrated_discussion(
+    db: &Database,
+    debug_state: &DebugState,
+    issue: &IssueData,
+    discussion: &HubDiscussion,
+) {
+    for comment in &discussion.comments {
+        let comment = CommentData {
+            source_id: comment.id,
+            // stored when new, updated otherwise
+            action: Action::Edited,
+            issue_id: issue.source_id,
+            author: comment.author.clone(),
+            body: comment.body.clone(),
+            url: format!("{}#{}", issue.html_url, comment.id),
+            is_review: false,
+        };
+        store_comment(db, debug_state, &comment).await;
+    }
+    if let Err(err) = db
+        .set_issue_closed(issue.source_id, discussion.is_closed())
+        .await
+    {
+        debug_state.record_error("database", &err);
+        error!(
+            issue_id = issue.source_id,
+            err = err.to_string(),
+            "error storing discussion status"
+        );
+    }
+}
+
+/// Applies a comment's action, returns the source id of its issue when the issue is to be embedded
+/// again
+async fn store_comment(
+    db: &Database,
+    debug_state: &DebugState,
+    comment: &CommentData,
+) -> Option<i64> {
+    match comment.action {
+        Action::Created => insert_comment(db, debug_state, comment).await,
+        Action::Edited => match db.update_comment(comment).await {
+            Ok(Some(issue_source_id)) => Some(issue_source_id),
+            // comments posted before their issue was indexed aren't stored yet
+            Ok(None) => insert_comment(db, debug_state, comment).await,
+            Err(err) => {
+                debug_state.record_error("database", &err);
                 error!(
-                    issue_id = issue_id,
+                    comment_id = comment.source_id,
                     err = err.to_string(),
-                    "error updating issue embeddings"
+                    "error updating comment"
+                );
+                None
+            }
+        },
+        Action::Deleted => match db.delete_comment(comment.source_id).await {
+            Ok(Some(issue_source_id)) => Some(issue_source_id),
+            Ok(None) => {
+                info!(
+                    comment_id = comment.source_id,
+                    "deleted comment wasn't stored, skipping re-embedding"
                 );
+                None
             }
+            Err(err) => {
+                debug_state.record_error("database", &err);
+                error!(
+                    comment_id = comment.source_id,
+                    err = err.to_string(),
+                    "error deleting comment"
+                );
+                None
+            }
+        },
+    }
+}
+
+/// Stores a comment on an already stored issue, returns the issue's source id when it is stored
+async fn insert_comment(
+    db: &Database,
+    debug_state: &DebugState,
+    comment: &CommentData,
+) -> Option<i64> {
+    let issue_id = match db.issue_id(comment.issue_id).await {
+        Ok(Some(id)) => id,
+        Ok(None) => {
+            error!(
+                comment_id = comment.source_id,
+                linked_issue_id = comment.issue_id,
+                url = comment.url,
+                "could not find issue associated with comment"
+            );
+            return None;
         }
+        Err(err) => {
+            debug_state.record_error("database", &err);
+            error!(
+                comment_id = comment.source_id,
+                err = err.to_string(),
+                "failed to fetch issue id for comment"
+            );
+            return None;
+        }
+    };
+    if let Err(err) = db.insert_comment(issue_id, comment).await {
+        debug_state.record_error("database", &err);
+        error!(
+            comment_id = comment.source_id,
+            err = err.to_string(),
+            "error inserting comment"
+        );
+        return None;
     }
+    Some(comment.issue_id)
 }
 
 async fn update_issue_embedding(
-    embedding_api: &EmbeddingApi,
-    pool: &Pool<Postgres>,
+    embedding_queue: &EmbeddingQueue,
+    issue_text: &IssueTextComposer,
+    db: &Database,
     issue_id: i64,
 ) -> anyhow::Result<()> {
-    let issue = sqlx::query!(
-        r#"
-            SELECT
-              i.title,
-              i.body,
-              (
-                SELECT JSON_AGG(c.body ORDER BY c.source_id)
-                FROM comments AS c
-                WHERE c.issue_id = i.id
-              ) AS comments
-            FROM
-              issues AS i
-            WHERE
-              i.source_id = $1;
-        "#,
-        issue_id,
-    )
-    .fetch_one(pool)
-    .await?;
-    let comment_string = match issue.comments {
-        Some(comments) => {
-            let comments: Vec<String> = serde_json::from_value(comments)?;
-            format!("\n----\nComment: {}", comments.join("\n----\nComment: "))
-        }
-        None => String::new(),
-    };
-    let issue_text = format!("# {}\n{}{}", issue.title, issue.body, comment_string);
-    let embedding = Vector::from(embedding_api.generate_embedding(issue_text).await?);
-    sqlx::query(
-        r#"update issues
-           set embedding = $1, updated_at = current_timestamp
-           where source_id = $2"#,
-    )
-    .bind(embedding)
-    .bind(issue_id)
-    .execute(pool)
-    .await?;
+    let issue = db.issue_text(issue_id).await?;
+    let mut text = issue_text.compose(&issue.title, &issue.body, &issue.comments);
+    if let Some(code_context) = &issue.code_context {
+        text.push_str(code_context);
+    }
+    let embedding_text = issue_text.embedding_input(
+        text,
+        &issue.repository_full_name,
+        &issue.labels,
+        &issue.body,
+    );
+    let (embedding, mut metadata) = embedding_queue
+        .generate_embedding_with_metadata(embedding_text, Priority::Background)
+        .await?;
+    metadata.template = issue_text.template_version();
+    db.update_issue_embedding(issue_id, &embedding).await?;
+    db.record_embedding_metadata(issue_id, &metadata).await?;
     Ok(())
 }
 
+/// computes the embeddings of the issues again, logging the ones that failed
+async fn reembed_issues(
+    embedding_queue: &EmbeddingQueue,
+    issue_text: &IssueTextComposer,
+    db: &Database,
+    debug_state: &DebugState,
+    source_ids: &[i64],
+) {
+    for &source_id in source_ids {
+        if let Err(err) = update_issue_embedding(embedding_queue, issue_text, db, source_id).await {
+            debug_state.record_error("embeddings", &err);
+            error!(
+                issue_id = source_id,
+                err = err.to_string(),
+                "error re-embedding issue"
+            );
+        }
+    }
+}
+
+async fn store_code_context(
+    db: &Database,
+    debug_state: &DebugState,
+    source_id: i64,
+    code_context: Option<&str>,
+) {
+    if let Err(err) = db.set_code_context(source_id, code_context).await {
+        debug_state.record_error("database", &err);
+        error!(
+            issue_id = source_id,
+            err = err.to_string(),
+            "error storing issue code context"
+        );
+    }
+}
+
+/// records how an issue was embedded, with the version of the template it was wrapped in
+async fn record_embedding_metadata(
+    db: &Database,
+    debug_state: &DebugState,
+    issue_text: &IssueTextComposer,
+    source_id: i64,
+    mut metadata: EmbeddingMetadata,
+) {
+    metadata.template = issue_text.template_version();
+    if let Err(err) = db.record_embedding_metadata(source_id, &metadata).await {
+        debug_state.record_error("database", &err);
+        error!(
+            issue_id = source_id,
+            err = err.to_string(),
+            "error recording embedding metadata"
+        );
+    }
+}
+
 pub static PRE_SHUTDOWN: AtomicBool = AtomicBool::new(false);
 
 async fn shutdown_signal() {
@@ -1001,7 +2750,23 @@ async fn shutdown_signal() {
             .await;
     };
 
-    #[cfg(not(unix))]
+    // sent by service wrappers to stop console processes running as Windows services
+    #[cfg(windows)]
+    let terminate = async {
+        let mut ctrl_break =
+            signal::windows::ctrl_break().expect("failed to install Ctrl+Break handler");
+        let mut ctrl_close =
+            signal::windows::ctrl_close().expect("failed to install Ctrl+Close handler");
+        let mut ctrl_shutdown =
+            signal::windows::ctrl_shutdown().expect("failed to install Ctrl+Shutdown handler");
+        tokio::select! {
+            _ = ctrl_break.recv() => {},
+            _ = ctrl_close.recv() => {},
+            _ = ctrl_shutdown.recv() => {},
+        }
+    };
+
+    #[cfg(not(any(unix, windows)))]
     let terminate = std::future::pending::<()>();
 
     tokio::select! {
@@ -1010,6 +2775,7 @@ async fn shutdown_signal() {
     }
 
     tracing::info!("Received termination signal shutting down");
+    systemd::notify("STOPPING=1");
 
     PRE_SHUTDOWN.store(true, Ordering::SeqCst);
 }
@@ -1018,25 +2784,313 @@ async fn shutdown_signal() {
 async fn main() -> anyhow::Result<()> {
     init_logging();
 
+    // `issue-bot check-config` reports every problem of the configuration without starting
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).map(String::as_str) == Some("check-config") {
+        return match load_config("ISSUE_BOT") {
+            Ok(_) => {
+                println!("configuration is valid");
+                Ok(())
+            }
+            Err(errors) => {
+                eprintln!("{errors}");
+                std::process::exit(1);
+            }
+        };
+    }
+
     let config: IssueBotConfig = load_config("ISSUE_BOT")?;
+    let configured_integrations = ConfiguredIntegrations::new(&config);
 
-    let opts: PgConnectOptions = config.database.connection_string.parse()?;
-    let pool = PgPoolOptions::new()
-        .max_connections(config.database.max_connections)
-        .connect_with(opts)
-        .await?;
+    let db = Database::connect(
+        &config.database,
+        config
+            .timeouts
+            .database_statement_secs
+            .map(Duration::from_secs),
+    )
+    .await?;
 
-    let embedding_api = EmbeddingApi::new(config.embedding_api)?;
-    let github_api = GithubApi::new(config.github_api, config.message_config.clone())?;
-    let huggingface_api = HuggingfaceApi::new(config.huggingface_api, config.message_config)?;
-    let slack = Slack::new(&config.slack)?;
-    let summarization_api = SummarizationApi::new(config.summarization_api)?;
+    let debug_state = DebugState::default();
+    let footer = CommentFooter::new(&config.feedback, config.embedding_api.model.clone());
+    let live_config = LiveConfig::new((&config).into());
 
+    // `issue-bot evaluate <repository full name> [k]` prints how past suggestions would have fared
+    // and `issue-bot check-webhooks <repository full name> [--fix]` checks a repository's webhook
+    if args.get(1).map(String::as_str) == Some("evaluate") {
+        let Some(repository_full_name) = args.get(2) else {
+            anyhow::bail!("usage: issue-bot evaluate <repository full name> [k]");
+        };
+        let k = args.get(3).map(|k| k.parse()).transpose()?.unwrap_or(3);
+        let github_api = GithubApi::new(
+            config.github_api,
+            &config.http_client,
+            &config.timeouts,
+            live_config,
+            footer,
+        )?;
+        let issue_links = IssueLinks::new(config.issue_links, db.clone(), debug_state, github_api);
+        let report = evaluation::run_evaluation(&db, &issue_links, repository_full_name, k).await?;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    if args.get(1).map(String::as_str) == Some("check-webhooks") {
+        let Some(repository_full_name) = args.get(2) else {
+            anyhow::bail!("usage: issue-bot check-webhooks <repository full name> [--fix]");
+        };
+        let fix = args.get(3).map(String::as_str) == Some("--fix");
+        let webhook_url = config.github_api.webhook_url.clone();
+        let github_api = GithubApi::new(
+            config.github_api,
+            &config.http_client,
+            &config.timeouts,
+            live_config,
+            footer,
+        )?;
+        let webhooks = WebhookChecker::new(github_api, config.auth_token, webhook_url);
+        let report = webhooks.check(repository_full_name, fix).await?;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    let retry_budget = RetryBudget::new(config.retry_budget);
+    let embedding_api = EmbeddingApi::new(
+        config.embedding_api.clone(),
+        &config.http_client,
+        &config.timeouts,
+        debug_state.clone(),
+        retry_budget.clone(),
+    )?;
+    let embedding_queue = EmbeddingQueue::new(&config.embedding_api, embedding_api.clone());
+    let webhook_url = config.github_api.webhook_url.clone();
+    let response_cache = config
+        .github_api
+        .response_cache
+        .enabled
+        .then(|| ResponseCache::new(config.github_api.response_cache.clone(), db.clone()));
+    let github_api = GithubApi::new(
+        config.github_api,
+        &config.http_client,
+        &config.timeouts,
+        live_config.clone(),
+        footer.clone(),
+    )?
+    .with_response_cache(response_cache);
+    let huggingface_api = HuggingfaceApi::new(
+        config.huggingface_api,
+        &config.http_client,
+        &config.timeouts,
+        live_config.clone(),
+        footer,
+    )?
+    .with_retry_budget(retry_budget.clone());
+    let locks = Locks::new(db.clone());
+    let slack_outbox = SlackOutbox::new(config.slack.outbox.clone(), db.clone(), locks.clone())
+        .with_retry_budget(retry_budget.clone());
+    let slack = Slack::new(
+        &config.slack,
+        &config.http_client,
+        &config.timeouts,
+        live_config.clone(),
+        Some(slack_outbox.clone()),
+    )?
+    .with_retry_budget(retry_budget.clone());
+    if let Err(err) = slack.resume_batches().await {
+        error!(
+            err = err.to_string(),
+            "failed to resume batched slack notifications"
+        );
+    }
+    let comment_queue = CommentQueue::new(
+        config.comment_queue,
+        db.clone(),
+        github_api.clone(),
+        huggingface_api.clone(),
+        locks.clone(),
+        slack.clone(),
+    )
+    .with_retry_budget(retry_budget.clone());
+    let backlinker = Backlinker::new(
+        config.backlinks,
+        comment_queue.clone(),
+        db.clone(),
+        github_api.clone(),
+        &config.repositories,
+    );
+    let code_context = CodeContext::new(config.code_context, github_api.clone());
+    let butler_enabled = config.butler.enabled;
+    let archive = Archive::new(config.archive, db.clone())?;
+    let butler = Butler::new(
+        config.butler,
+        github_api.clone(),
+        db.clone(),
+        debug_state.clone(),
+    );
+    let retention = Retention::new(
+        config.retention,
+        archive.clone(),
+        db.clone(),
+        debug_state.clone(),
+        locks.clone(),
+    );
+    let compaction = Compaction::new(
+        config.compaction,
+        db.clone(),
+        debug_state.clone(),
+        locks.clone(),
+    );
+    let queue_metrics = QueueMetrics::new(config.queue_metrics, db.clone(), locks.clone());
+    let email = EmailNotifier::new(&config.email)?;
+    let debouncer = ReembedDebouncer::new(config.reembed);
+    let summarization_api = SummarizationApi::new(
+        config.summarization_api,
+        &config.http_client,
+        &config.timeouts,
+        &config.repositories,
+    )?;
+
+    if config.skip_startup_checks {
+        info!("skipping startup checks");
+    } else {
+        run_startup_checks(
+            configured_integrations,
+            &db,
+            &embedding_api,
+            &github_api,
+            butler_enabled,
+            &slack,
+            &summarization_api,
+        )
+        .await?;
+    }
+
+    let issue_text = IssueTextComposer::new(&config.issue_text);
+    let repo_groups = RepoGroups::new(&config.repo_groups)?;
+    let repo_metadata = RepoMetadata::new(
+        config.repo_metadata,
+        db.clone(),
+        debug_state.clone(),
+        github_api.clone(),
+        locks.clone(),
+    );
+    let guidance = Guidance::new(
+        config.guidance,
+        db.clone(),
+        debug_state.clone(),
+        embedding_queue.clone(),
+        github_api.clone(),
+        locks.clone(),
+    );
+    let hot_issues = HotIssues::new(&config.hot_issues, db.clone());
+    let issue_links = IssueLinks::new(
+        config.issue_links,
+        db.clone(),
+        debug_state.clone(),
+        github_api.clone(),
+    );
+    let knowledge_base =
+        KnowledgeBase::new(config.knowledge_base, db.clone(), embedding_queue.clone());
+    let settings = Settings::new(live_config.clone(), db.clone());
+    let events = PipelineEvents::default();
+    let event_log = EventLog::new(config.event_log, db.clone(), debug_state.clone());
+    let escalation = Escalation::new(config.escalation, db.clone(), slack.clone());
+    let extractor = Extractor::new(config.extraction, db.clone(), debug_state.clone());
+    let fingerprints = Fingerprints::new(config.fingerprints, db.clone());
+    let duplicate_resolutions = DuplicateResolutions::new(db.clone(), github_api.clone());
+    let drift = EmbeddingDrift::new(config.drift, db.clone(), config.embedding_api.model.clone());
+    let owners = Owners::new(
+        config.owners,
+        github_api.clone(),
+        config.repositories.clone(),
+    );
+    let priorities = Priorities::new(config.priority, slack.clone())?;
+    let redactor = Redactor::new(&config.redaction)?;
+    let comment_trigger = CommentTrigger::new(
+        config.comment_trigger,
+        comment_queue.clone(),
+        db.clone(),
+        embedding_queue.clone(),
+        github_api.clone(),
+        huggingface_api.clone(),
+        repo_groups.clone(),
+        config.repositories.clone(),
+        settings.clone(),
+    );
+    let check_runs = CheckRuns::new(config.check_runs, db.clone(), github_api.clone());
     let (tx, rx) = mpsc::channel(4_096);
+    let ignore_rules = IgnoreRules::new(&config.ignore_rules)?;
+    let catch_up = CatchUp::new(
+        config.catch_up,
+        db.clone(),
+        debug_state.clone(),
+        github_api.clone(),
+        ignore_rules.clone(),
+        locks.clone(),
+        tx.clone(),
+    );
+    // read before the server starts, as webhooks move the cursors past the missed ones
+    let catch_up_cursors = db.repository_cursors().await?;
+    let webhooks = WebhookChecker::new(github_api.clone(), config.auth_token.clone(), webhook_url);
+    let onboarding = Onboarding::new(
+        config.onboarding,
+        db.clone(),
+        github_api.clone(),
+        slack.clone(),
+        tx.clone(),
+        webhooks.clone(),
+    );
+
+    let supervisor = Supervisor::new(config.supervisor, debug_state.clone());
 
     let state = AppState {
+        api_keys: ApiKeys::new(config.auth_token.clone(), db.clone()),
         auth_token: config.auth_token,
+        catch_up: catch_up.clone(),
+        comment_queue: comment_queue.clone(),
+        comparer: IssueComparer::new(
+            db.clone(),
+            embedding_queue.clone(),
+            extractor.clone(),
+            repo_groups.clone(),
+            repo_metadata.clone(),
+            config.repositories.clone(),
+            settings.clone(),
+        ),
+        db: db.clone(),
+        debug_state: debug_state.clone(),
+        drift: drift.clone(),
+        event_log: event_log.clone(),
+        events: events.clone(),
+        github_oidc: GithubOidc::new(config.github_oidc, &config.http_client, &config.timeouts)?,
+        hot_issues: hot_issues.clone(),
+        ignore_rules,
+        knowledge_base: knowledge_base.clone(),
+        locks: locks.clone(),
+        max_body_bytes: config.server.max_body_bytes,
+        onboarding,
+        request_timeout: Duration::from_secs(config.timeouts.request_secs),
+        search: IssueSearch::new(
+            config.search,
+            db.clone(),
+            embedding_queue.clone(),
+            issue_text.clone(),
+        ),
+        settings: settings.clone(),
+        slack: slack.clone(),
+        supervisor: supervisor.clone(),
         tx,
+        web_ui: WebUi::new(
+            config.web_ui,
+            db.clone(),
+            debug_state.clone(),
+            embedding_queue.clone(),
+        ),
+        webhook_mirror: WebhookMirror::new(
+            config.webhook_mirror,
+            &config.http_client,
+            &config.timeouts,
+        )?,
+        webhooks,
     };
 
     let host = config.server.ip.clone();
@@ -1048,16 +3102,91 @@ async fn main() -> anyhow::Result<()> {
             host,
             metrics_port,
             false,
+            config.metrics,
             setup_metrics_recorder()
         ))),
+        flatten(tokio::spawn(start_butler(butler.clone()))),
+        flatten(tokio::spawn(start_catch_up(catch_up, catch_up_cursors))),
+        flatten(tokio::spawn(start_comment_queue(comment_queue.clone()))),
+        flatten(tokio::spawn(start_slack_outbox(
+            slack_outbox,
+            slack.clone()
+        ))),
+        flatten(tokio::spawn(start_email_digest(email.clone()))),
+        flatten(tokio::spawn(start_retention(retention))),
+        flatten(tokio::spawn(start_event_log_pruner(event_log.clone()))),
+        flatten(tokio::spawn(start_compaction(compaction))),
+        flatten(tokio::spawn(start_fingerprint_backfill(
+            fingerprints.clone(),
+            locks.clone()
+        ))),
+        flatten(tokio::spawn(start_partial_indexes(
+            db.clone(),
+            debug_state.clone(),
+            locks.clone()
+        ))),
+        flatten(tokio::spawn(start_queue_metrics(queue_metrics))),
+        flatten(tokio::spawn(start_repo_metadata_refresher(
+            repo_metadata.clone()
+        ))),
+        flatten(tokio::spawn(start_guidance_refresher(guidance.clone()))),
+        flatten(tokio::spawn(start_config_reloader(live_config))),
+        flatten(tokio::spawn(start_invalidation_listener(
+            db.clone(),
+            settings.clone(),
+            hot_issues.clone()
+        ))),
+        flatten(tokio::spawn(start_watchdog(supervisor.clone()))),
+        flatten(tokio::spawn(start_reembed_flusher(
+            debouncer.clone(),
+            embedding_queue.clone(),
+            issue_text.clone(),
+            db.clone(),
+            debug_state.clone()
+        ))),
         handle_webhooks_wrapper(
             rx,
-            embedding_api,
-            github_api,
-            huggingface_api,
-            slack,
-            summarization_api,
-            pool
+            WebhookContext {
+                archive,
+                backlinker,
+                butler,
+                check_runs,
+                code_context,
+                comment_queue,
+                comment_trigger,
+                debouncer,
+                debug_state,
+                diversity_cfg: config.diversity,
+                drift: drift.clone(),
+                duplicate_resolutions,
+                email,
+                embedding_queue,
+                escalation,
+                event_log,
+                events,
+                extractor,
+                fingerprints,
+                github_api,
+                guidance,
+                hot_issues,
+                huggingface_api,
+                issue_links,
+                issue_text,
+                knowledge_base,
+                owners,
+                priorities,
+                redactor,
+                repo_groups,
+                repo_metadata,
+                repositories: config.repositories,
+                retry_policy: RetryPolicy::new(retry_budget),
+                settings,
+                slack,
+                summarization_api,
+                db,
+                supervisor: supervisor.clone(),
+                locks,
+            },
         )
     )?;
 
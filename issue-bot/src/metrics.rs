@@ -1,14 +1,50 @@
-use std::future::ready;
+use std::{future::ready, sync::Arc, time::Duration};
 
-use axum::{http::StatusCode, response::IntoResponse, routing::get, Router};
+use axum::{
+    extract::{Request, State},
+    http::{header::AUTHORIZATION, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use futures::pin_mut;
 use metrics_exporter_prometheus::PrometheusHandle;
-use tokio::net::TcpListener;
-use tracing::info;
+use reqwest::{header::CONTENT_TYPE, Client};
+use subtle::ConstantTimeEq;
+use tokio::{net::TcpListener, select, time::interval};
+use tracing::{error, info};
 
-use crate::shutdown_signal;
+use crate::{
+    config::{MetricsConfig, PushGatewayConfig},
+    shutdown_signal, APP_USER_AGENT,
+};
 
-fn metrics_app(recorder_handle: PrometheusHandle, health: bool) -> Router {
+async fn bearer_auth(State(token): State<Arc<str>>, req: Request, next: Next) -> Response {
+    let authorized = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|value| bool::from(value.as_bytes().ct_eq(token.as_bytes())));
+    if !authorized {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    next.run(req).await
+}
+
+fn metrics_app(
+    recorder_handle: PrometheusHandle,
+    health: bool,
+    auth_token: Option<String>,
+) -> Router {
     let mut router = Router::new().route("/metrics", get(move || ready(recorder_handle.render())));
+    if let Some(token) = auth_token {
+        router = router.route_layer(middleware::from_fn_with_state(
+            Arc::<str>::from(token),
+            bearer_auth,
+        ));
+    }
     if health {
         router = router.route("/health", get(|| ready(StatusCode::OK.into_response())));
     }
@@ -16,18 +52,111 @@ fn metrics_app(recorder_handle: PrometheusHandle, health: bool) -> Router {
     router
 }
 
+/// Periodically pushes the rendered metrics to a Prometheus Pushgateway
+async fn push_metrics(
+    cfg: PushGatewayConfig,
+    recorder_handle: PrometheusHandle,
+) -> anyhow::Result<()> {
+    let client = Client::builder().user_agent(APP_USER_AGENT).build()?;
+    let url = format!("{}/metrics/job/{}", cfg.url.trim_end_matches('/'), cfg.job);
+
+    info!(url, "pushing metrics to push gateway");
+    let mut ticker = interval(Duration::from_secs(cfg.interval_secs));
+    let shutdown = shutdown_signal();
+    pin_mut!(shutdown);
+    loop {
+        select! {
+            _ = ticker.tick() => {
+                let mut req = client
+                    .put(&url)
+                    .header(CONTENT_TYPE, "text/plain; version=0.0.4")
+                    .body(recorder_handle.render());
+                if let Some(username) = &cfg.username {
+                    req = req.basic_auth(username, cfg.password.as_ref());
+                }
+                if let Err(err) = req.send().await.and_then(|res| res.error_for_status()) {
+                    error!(err = err.to_string(), "failed to push metrics");
+                }
+            }
+            _ = &mut shutdown => break,
+        }
+    }
+    Ok(())
+}
+
 pub async fn start_metrics_server(
     ip: String,
     port: u16,
     health: bool,
+    cfg: MetricsConfig,
     recorder_handle: PrometheusHandle,
 ) -> anyhow::Result<()> {
-    let app = metrics_app(recorder_handle, health);
+    let app = metrics_app(recorder_handle.clone(), health, cfg.auth_token);
 
     info!(ip, port, "starting metrics server");
     let listener = TcpListener::bind(format!("{}:{}", ip, port)).await?;
-    axum::serve(listener, app.into_make_service())
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
+    let server = async {
+        axum::serve(listener, app.into_make_service())
+            .with_graceful_shutdown(shutdown_signal())
+            .await?;
+        Ok::<_, anyhow::Error>(())
+    };
+    match cfg.push_gateway {
+        Some(push_gateway) => {
+            tokio::try_join!(server, push_metrics(push_gateway, recorder_handle))?;
+        }
+        None => server.await?,
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{header::AUTHORIZATION, Request, StatusCode},
+    };
+    use metrics_exporter_prometheus::PrometheusBuilder;
+    use tower::ServiceExt;
+
+    use super::metrics_app;
+
+    #[tokio::test]
+    async fn test_bearer_auth() {
+        let handle = PrometheusBuilder::new().build_recorder().handle();
+        let app = metrics_app(handle, true, Some("metrics-token".to_owned()));
+
+        let request = |authorization: Option<&str>| {
+            let mut builder = Request::builder().uri("/metrics");
+            if let Some(authorization) = authorization {
+                builder = builder.header(AUTHORIZATION, authorization);
+            }
+            builder.body(Body::empty()).unwrap()
+        };
+        for (authorization, status) in [
+            (None, StatusCode::UNAUTHORIZED),
+            (Some("Bearer wrong-token"), StatusCode::UNAUTHORIZED),
+            (
+                Some("Bearer metrics-token-suffix"),
+                StatusCode::UNAUTHORIZED,
+            ),
+            (Some("metrics-token"), StatusCode::UNAUTHORIZED),
+            (Some("Bearer metrics-token"), StatusCode::OK),
+        ] {
+            let response = app.clone().oneshot(request(authorization)).await.unwrap();
+            assert_eq!(response.status(), status, "{authorization:?}");
+        }
+
+        // the health check stays reachable by probes without the token
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/health")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
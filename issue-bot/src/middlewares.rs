@@ -2,12 +2,17 @@ use std::time::Instant;
 
 use axum::{
     extract::{MatchedPath, Request},
-    http::HeaderValue,
+    http::{
+        header::{CONTENT_LENGTH, CONTENT_TYPE, TRANSFER_ENCODING},
+        HeaderMap, HeaderValue,
+    },
     middleware::Next,
-    response::IntoResponse,
+    response::{IntoResponse, Response},
 };
 use nanoid::nanoid;
 
+use crate::errors::{ApiError, Problem};
+
 pub async fn track_metrics(req: Request, next: Next) -> impl IntoResponse {
     let start = Instant::now();
     let path = if let Some(matched_path) = req.extensions().get::<MatchedPath>() {
@@ -33,6 +38,37 @@ pub async fn track_metrics(req: Request, next: Next) -> impl IntoResponse {
     response
 }
 
+fn has_body(headers: &HeaderMap) -> bool {
+    headers.contains_key(TRANSFER_ENCODING)
+        || headers
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok())
+            .is_some_and(|length| length > 0)
+}
+
+fn is_json(content_type: &str) -> bool {
+    content_type
+        .split(';')
+        .next()
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/json"))
+}
+
+/// Rejects request bodies that aren't JSON with a 415, every route consuming one expects JSON
+pub async fn require_json(req: Request, next: Next) -> Response {
+    if has_body(req.headers()) {
+        let content_type = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        if !is_json(content_type) {
+            return ApiError::UnsupportedMediaType(content_type.to_owned()).into_response();
+        }
+    }
+    next.run(req).await
+}
+
 pub const X_REQUEST_ID: &str = "X-Request-Id";
 
 #[derive(Clone, Debug)]
@@ -59,6 +95,9 @@ pub async fn add_request_id(mut req: Request, next: Next) -> impl IntoResponse {
         .unwrap_or_else(|| nanoid!());
     req.extensions_mut().insert(RequestId(request_id.clone()));
     let mut res = next.run(req).await;
+    if let Some(problem) = res.extensions_mut().remove::<Problem>() {
+        res = problem.with_request_id(request_id.clone()).into_response();
+    }
     res.headers_mut()
         .insert(X_REQUEST_ID, HeaderValue::from_str(&request_id).unwrap());
     res
@@ -0,0 +1,150 @@
+use std::sync::Arc;
+
+use axum::{body::Bytes, http::HeaderValue};
+use reqwest::{Client, RequestBuilder};
+use tokio::sync::Semaphore;
+use tracing::warn;
+
+use crate::{
+    config::{HttpClientConfig, HttpTarget, TimeoutsConfig, WebhookMirrorConfig},
+    http_client::client_builder,
+    routes::compute_signature,
+};
+
+/// Forwards verified GitHub and Hugging Face webhooks to another deployment, see
+/// [WebhookMirrorConfig]
+///
+/// Deliveries happen in the background: a slow or failing mirror neither delays nor fails the
+/// webhook, and the mirror's own comments depend on its configuration only.
+#[derive(Clone)]
+pub struct WebhookMirror {
+    cfg: WebhookMirrorConfig,
+    client: Client,
+    in_flight: Arc<Semaphore>,
+}
+
+impl WebhookMirror {
+    pub fn new(
+        cfg: WebhookMirrorConfig,
+        http_cfg: &HttpClientConfig,
+        timeouts: &TimeoutsConfig,
+    ) -> Result<Self, reqwest::Error> {
+        let client = client_builder(http_cfg, timeouts, HttpTarget::WebhookMirror)?.build()?;
+        Ok(Self {
+            in_flight: Arc::new(Semaphore::new(cfg.max_in_flight)),
+            cfg,
+            client,
+        })
+    }
+
+    fn url(&self, source: &str) -> String {
+        format!("{}/{source}", self.cfg.url.trim_end_matches('/'))
+    }
+
+    /// forwards `body` as a GitHub webhook of type `event`, re-signed with the mirror's secret
+    pub fn mirror(&self, event: String, delivery: Option<HeaderValue>, body: Bytes) {
+        if !self.cfg.enabled {
+            return;
+        }
+        let signature = compute_signature(&body, &self.cfg.secret);
+        let mut request = self
+            .client
+            .post(self.url("github"))
+            .header("x-github-event", &event)
+            .header("x-hub-signature-256", signature);
+        if let Some(delivery) = delivery {
+            request = request.header("x-github-delivery", delivery);
+        }
+        self.forward(request, body, event);
+    }
+
+    /// forwards `body` as a Hugging Face webhook, along with the mirror's secret
+    pub fn mirror_huggingface(&self, body: Bytes) {
+        if !self.cfg.enabled {
+            return;
+        }
+        let request = self
+            .client
+            .post(self.url("huggingface"))
+            .header("x-webhook-secret", &self.cfg.secret);
+        self.forward(request, body, "huggingface".to_owned());
+    }
+
+    fn forward(&self, request: RequestBuilder, body: Bytes, event: String) {
+        let Ok(permit) = self.in_flight.clone().try_acquire_owned() else {
+            metrics::counter!("issue_bot_mirrored_webhooks_total", "result" => "dropped")
+                .increment(1);
+            return;
+        };
+        tokio::spawn(async move {
+            let result = match request
+                .header("content-type", "application/json")
+                .body(body)
+                .send()
+                .await
+                .and_then(|res| res.error_for_status())
+            {
+                Ok(_) => "sent",
+                Err(err) => {
+                    warn!(event, err = err.to_string(), "failed to mirror webhook");
+                    "failed"
+                }
+            };
+            metrics::counter!("issue_bot_mirrored_webhooks_total", "result" => result).increment(1);
+            drop(permit);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::{body::Bytes, http::Method, routing::post, Router};
+
+    use crate::{
+        config::{HttpClientConfig, TimeoutsConfig, WebhookMirrorConfig},
+        test_harness::MockServer,
+    };
+
+    use super::WebhookMirror;
+
+    #[tokio::test]
+    async fn test_mirror() {
+        let server = MockServer::start(
+            Router::new()
+                .route("/event/github", post(|| async {}))
+                .route("/event/huggingface", post(|| async {})),
+        )
+        .await;
+        let mirror = WebhookMirror::new(
+            WebhookMirrorConfig {
+                enabled: true,
+                secret: "secret".to_owned(),
+                url: format!("{}/event/", server.url),
+                ..Default::default()
+            },
+            &HttpClientConfig::default(),
+            &TimeoutsConfig::default(),
+        )
+        .unwrap();
+
+        mirror.mirror(
+            "issues".to_owned(),
+            None,
+            Bytes::from_static(br#"{"action":"opened"}"#),
+        );
+        mirror.mirror_huggingface(Bytes::from_static(br#"{"event":{}}"#));
+        let timeout = Duration::from_secs(5);
+        let github = server
+            .recorder
+            .wait_for(Method::POST, "/event/github", timeout)
+            .await;
+        assert_eq!(github.body, r#"{"action":"opened"}"#);
+        let huggingface = server
+            .recorder
+            .wait_for(Method::POST, "/event/huggingface", timeout)
+            .await;
+        assert_eq!(huggingface.body, r#"{"event":{}}"#);
+    }
+}
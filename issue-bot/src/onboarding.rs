@@ -0,0 +1,179 @@
+use std::time::Duration;
+
+use serde::Serialize;
+use thiserror::Error;
+use tokio::sync::mpsc::{error::SendError, Sender};
+use tracing::{error, info};
+
+use crate::{
+    config::OnboardingConfig,
+    github::{GithubApi, GithubApiError},
+    middlewares::RequestId,
+    slack::Slack,
+    storage::{Database, OnboardedRepository, Storage, StorageError},
+    webhooks::{WebhookCheckError, WebhookChecker, WebhookReport},
+    EventData, QueuedEvent, RepositoryData, Source,
+};
+
+#[derive(Debug, Error)]
+pub enum OnboardingError {
+    #[error("github api error: {0}")]
+    GithubApi(#[from] GithubApiError),
+    #[error("{0} is not owned by one of onboarding.allowed_owners")]
+    NotAllowed(String),
+    #[error("send error: {0}")]
+    Send(Box<SendError<QueuedEvent>>),
+    #[error("storage error: {0}")]
+    Storage(#[from] StorageError),
+    #[error("webhook check error: {0}")]
+    WebhookCheck(#[from] WebhookCheckError),
+}
+
+// boxed by hand, keeping [crate::errors::ApiError] small
+impl From<SendError<QueuedEvent>> for OnboardingError {
+    fn from(err: SendError<QueuedEvent>) -> Self {
+        Self::Send(Box::new(err))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct OnboardingReport {
+    pub repository: OnboardedRepository,
+    pub webhook: WebhookReport,
+    /// rough duration of the backfill indexation
+    pub estimated_secs: u64,
+}
+
+/// Sets up a GitHub repository in one go: fixes or creates its webhook, records it in
+/// `onboarded_repositories`, queues the indexation of its existing issues and confirms in Slack
+///
+/// Any repository whose webhook reaches the bot is handled, onboarding only saves the manual
+/// steps, the per repository settings staying in the configuration. Onboarded repositories are
+/// caught up on like indexed ones, see [crate::catch_up].
+#[derive(Clone)]
+pub struct Onboarding {
+    cfg: OnboardingConfig,
+    db: Database,
+    github_api: GithubApi,
+    slack: Slack,
+    tx: Sender<QueuedEvent>,
+    webhooks: WebhookChecker,
+}
+
+/// how long indexing `issues` issues should take
+fn estimated_duration(cfg: &OnboardingConfig, issues: i64) -> Duration {
+    Duration::from_secs_f64(issues.max(0) as f64 * cfg.seconds_per_issue.max(0.))
+}
+
+/// `estimated` rounded for humans, e.g. `about 2 hours`
+pub fn describe_duration(estimated: Duration) -> String {
+    let minutes = estimated.as_secs().div_ceil(60);
+    match minutes {
+        0..=1 => "about a minute".to_owned(),
+        2..=119 => format!("about {minutes} minutes"),
+        _ => format!("about {} hours", (minutes + 30) / 60),
+    }
+}
+
+impl Onboarding {
+    pub fn new(
+        cfg: OnboardingConfig,
+        db: Database,
+        github_api: GithubApi,
+        slack: Slack,
+        tx: Sender<QueuedEvent>,
+        webhooks: WebhookChecker,
+    ) -> Self {
+        Self {
+            cfg,
+            db,
+            github_api,
+            slack,
+            tx,
+            webhooks,
+        }
+    }
+
+    pub async fn onboard(
+        &self,
+        repository_full_name: &str,
+        request_id: &RequestId,
+    ) -> Result<OnboardingReport, OnboardingError> {
+        if !self.cfg.is_allowed(repository_full_name) {
+            return Err(OnboardingError::NotAllowed(repository_full_name.to_owned()));
+        }
+        let webhook = self.webhooks.check(repository_full_name, true).await?;
+        let estimated_issues = self
+            .github_api
+            .latest_issue_number(repository_full_name)
+            .await?
+            .unwrap_or_default();
+        let repository = self
+            .db
+            .record_onboarding(
+                repository_full_name,
+                webhook.hook_id.map(|id| id as i64),
+                estimated_issues.into(),
+            )
+            .await?;
+        self.tx
+            .send(QueuedEvent::new(
+                EventData::RepositoryIndexation(RepositoryData {
+                    full_name: repository_full_name.to_owned(),
+                    source: Source::Github,
+                }),
+                request_id,
+            ))
+            .await?;
+        let report = OnboardingReport {
+            estimated_secs: estimated_duration(&self.cfg, repository.estimated_issues).as_secs(),
+            repository,
+            webhook,
+        };
+        info!(
+            repository_full_name,
+            estimated_issues = report.repository.estimated_issues,
+            webhook_fixed = report.webhook.fixed,
+            "onboarded repository"
+        );
+        // the repository is set up either way
+        if let Err(err) = self.slack.onboarding_confirmation(&report).await {
+            error!(
+                repository_full_name,
+                err = err.to_string(),
+                "failed to confirm onboarding in slack"
+            );
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::config::OnboardingConfig;
+
+    use super::{describe_duration, estimated_duration};
+
+    #[test]
+    fn test_estimated_duration() {
+        let cfg = OnboardingConfig {
+            allowed_owners: vec!["huggingface".to_owned()],
+            seconds_per_issue: 0.5,
+        };
+        assert!(cfg.is_allowed("HuggingFace/lor-e"));
+        assert!(!cfg.is_allowed("octocat/lor-e"));
+        assert!(!cfg.is_allowed("huggingface"));
+        assert_eq!(estimated_duration(&cfg, 600), Duration::from_secs(300));
+        assert_eq!(describe_duration(Duration::from_secs(20)), "about a minute");
+        assert_eq!(
+            describe_duration(Duration::from_secs(300)),
+            "about 5 minutes"
+        );
+        assert_eq!(
+            describe_duration(estimated_duration(&cfg, 40_000)),
+            "about 6 hours"
+        );
+    }
+}
@@ -0,0 +1,40 @@
+/// Comment command leaving the issues and comments of its author out of suggestions and exports,
+/// as does `POST /opt-out`
+///
+/// Followed by `purge`, the stored issues and comments of the author are deleted instead of
+/// flagged. Either way the request is recorded, and content the author posts afterwards is flagged
+/// when stored. Hugging Face authors are identified by their Hub user id rather than their name.
+pub const OPT_OUT_COMMAND: &str = "/lor-e opt-out";
+
+/// whether a line of `body` is the opt-out command, along with whether it asks for a purge
+pub fn opt_out_command(body: &str) -> Option<bool> {
+    body.lines().find_map(|line| {
+        let mut words = line.split_whitespace();
+        let command = format!("{} {}", words.next()?, words.next()?);
+        if !command.eq_ignore_ascii_case(OPT_OUT_COMMAND) {
+            return None;
+        }
+        match (words.next(), words.next()) {
+            (None, _) => Some(false),
+            (Some(option), None) if option.eq_ignore_ascii_case("purge") => Some(true),
+            _ => None,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::opt_out_command;
+
+    #[test]
+    fn test_opt_out_command() {
+        assert_eq!(opt_out_command("/lor-e opt-out"), Some(false));
+        assert_eq!(
+            opt_out_command("please forget me\n\n  /lor-e OPT-OUT purge "),
+            Some(true)
+        );
+        assert_eq!(opt_out_command("/lor-e opt-out everything"), None);
+        assert_eq!(opt_out_command("how do I /lor-e opt-out?"), None);
+        assert_eq!(opt_out_command("/lor-e mute"), None);
+    }
+}
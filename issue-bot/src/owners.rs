@@ -0,0 +1,235 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use regex::Regex;
+use tracing::warn;
+
+use crate::{
+    code_context::find_file_references,
+    config::{OwnersConfig, RepositoryConfig},
+    github::{GithubApi, GithubApiError},
+    IssueData, Source,
+};
+
+/// where GitHub looks for the CODEOWNERS file, in order
+const CODEOWNERS_PATHS: &[&str] = &[".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+#[derive(Debug)]
+struct Rule {
+    pattern: Regex,
+    owners: Vec<String>,
+}
+
+/// rules of a repository's CODEOWNERS file, and when they were fetched
+type CachedRules = (Instant, Arc<[Rule]>);
+
+/// gitignore-style CODEOWNERS pattern as a regex matching the paths it covers
+fn pattern_regex(pattern: &str) -> Option<Regex> {
+    // a slash anywhere but at the end anchors the pattern to the repository root
+    let anchored = pattern.trim_end_matches('/').contains('/');
+    let directory = pattern.ends_with('/');
+    let pattern = pattern.trim_matches('/');
+    if pattern.is_empty() {
+        return None;
+    }
+    let mut re = String::from(if anchored { "^" } else { "(^|/)" });
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    re.push_str("(.*/)?");
+                } else {
+                    re.push_str(".*");
+                }
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            c => re.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    // files below a matching directory are matched too
+    re.push_str(if directory { "/" } else { "(/|$)" });
+    Regex::new(&re).ok()
+}
+
+fn parse_codeowners(content: &str) -> Vec<Rule> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut parts = line.split_whitespace();
+            let pattern = pattern_regex(parts.next()?)?;
+            Some(Rule {
+                pattern,
+                owners: parts.map(str::to_lowercase).collect(),
+            })
+        })
+        .collect()
+}
+
+/// owners of `path`, the last matching rule taking precedence
+fn path_owners<'a>(rules: &'a [Rule], path: &str) -> &'a [String] {
+    rules
+        .iter()
+        .rev()
+        .find(|rule| rule.pattern.is_match(path))
+        .map(|rule| rule.owners.as_slice())
+        .unwrap_or_default()
+}
+
+/// Finds the Slack user groups to mention in a new issue's notification, from the labels it
+/// carries and the CODEOWNERS of the files it references
+#[derive(Clone)]
+pub struct Owners {
+    codeowners: Arc<Mutex<HashMap<String, CachedRules>>>,
+    cfg: OwnersConfig,
+    github_api: GithubApi,
+    repositories: HashMap<String, RepositoryConfig>,
+}
+
+impl Owners {
+    pub fn new(
+        cfg: OwnersConfig,
+        github_api: GithubApi,
+        repositories: HashMap<String, RepositoryConfig>,
+    ) -> Self {
+        Self {
+            codeowners: Arc::default(),
+            cfg: OwnersConfig {
+                codeowners_teams: cfg
+                    .codeowners_teams
+                    .into_iter()
+                    .map(|(owner, team)| (owner.to_lowercase(), team))
+                    .collect(),
+                ..cfg
+            },
+            github_api,
+            repositories,
+        }
+    }
+
+    async fn codeowners(&self, repository_full_name: &str) -> Result<Arc<[Rule]>, GithubApiError> {
+        let ttl = Duration::from_secs(self.cfg.cache_secs);
+        if let Some((fetched_at, rules)) = self.codeowners.lock().unwrap().get(repository_full_name)
+        {
+            if fetched_at.elapsed() < ttl {
+                return Ok(rules.clone());
+            }
+        }
+        let mut rules: Arc<[Rule]> = Arc::new([]);
+        for path in CODEOWNERS_PATHS {
+            if let Some(content) = self
+                .github_api
+                .get_file_content(repository_full_name, path)
+                .await?
+            {
+                rules = parse_codeowners(&content).into();
+                break;
+            }
+        }
+        self.codeowners.lock().unwrap().insert(
+            repository_full_name.to_owned(),
+            (Instant::now(), rules.clone()),
+        );
+        Ok(rules)
+    }
+
+    async fn codeowners_teams(&self, issue: &IssueData) -> Result<Vec<String>, GithubApiError> {
+        let references = find_file_references(&issue.body, self.cfg.max_files);
+        if references.is_empty() {
+            return Ok(Vec::new());
+        }
+        let rules = self.codeowners(&issue.repository_full_name).await?;
+        if rules.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut teams = Vec::new();
+        for reference in references {
+            let Some(path) = self
+                .github_api
+                .find_file(&issue.repository_full_name, &reference.path)
+                .await?
+            else {
+                continue;
+            };
+            teams.extend(
+                path_owners(&rules, &path)
+                    .iter()
+                    .filter_map(|owner| self.cfg.codeowners_teams.get(owner))
+                    .cloned(),
+            );
+        }
+        Ok(teams)
+    }
+
+    /// Slack mentions of the teams owning `issue`, deduplicated
+    ///
+    /// Failing to look up the CODEOWNERS isn't an error, only the label teams are mentioned then.
+    pub async fn slack_mentions(&self, issue: &IssueData) -> Vec<String> {
+        let Some(repository) = self.repositories.get(&issue.repository_full_name) else {
+            return Vec::new();
+        };
+        let mut teams: Vec<String> = issue
+            .labels
+            .iter()
+            .filter_map(|label| repository.label_teams.get(label))
+            .cloned()
+            .collect();
+        if repository.codeowners && matches!(issue.source, Source::Github) {
+            match self.codeowners_teams(issue).await {
+                Ok(codeowners_teams) => teams.extend(codeowners_teams),
+                Err(err) => warn!(
+                    issue_id = issue.source_id,
+                    err = err.to_string(),
+                    "failed to find the owners of the referenced files"
+                ),
+            }
+        }
+        let mut mentions: Vec<String> = Vec::new();
+        for team in teams {
+            let mention = format!("<!subteam^{team}>");
+            if !mentions.contains(&mention) {
+                mentions.push(mention);
+            }
+        }
+        mentions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_codeowners, path_owners};
+
+    #[test]
+    fn test_codeowners() {
+        let rules = parse_codeowners(
+            r#"# default reviewers
+* @huggingface/core
+*.md @huggingface/docs
+/src/transformers/models/llama/ @huggingface/llama @Alice
+src/**/tokenization_*.py @huggingface/tokenizers # fast and slow
+docs/ @huggingface/docs
+"#,
+        );
+        assert_eq!(rules.len(), 5);
+        let owners = |path| path_owners(&rules, path).to_vec();
+        assert_eq!(owners("setup.py"), ["@huggingface/core"]);
+        assert_eq!(owners("examples/README.md"), ["@huggingface/docs"]);
+        assert_eq!(
+            owners("src/transformers/models/llama/modeling_llama.py"),
+            ["@huggingface/llama", "@alice"]
+        );
+        assert_eq!(
+            owners("src/transformers/models/bert/tokenization_bert.py"),
+            ["@huggingface/tokenizers"]
+        );
+        assert_eq!(owners("docs/source/en/index.py"), ["@huggingface/docs"]);
+        assert_eq!(owners("src/transformers/docs.py"), ["@huggingface/core"]);
+    }
+}
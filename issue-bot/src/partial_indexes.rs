@@ -0,0 +1,55 @@
+use futures::pin_mut;
+use tokio::select;
+use tracing::{error, info};
+
+use crate::{
+    debug::DebugState,
+    locks::Locks,
+    shutdown_signal,
+    storage::{Database, Storage},
+};
+
+const LOCK_NAME: &str = "partial_indexes";
+
+/// Builds the partial embedding indexes once after starting, see
+/// [crate::config::VectorSearchConfig]
+///
+/// Building an index over a large repository takes a while, the bot serves requests meanwhile.
+/// Only one instance of the bot builds them, the others leaving it to the lock holder.
+pub async fn start_partial_indexes(
+    db: Database,
+    debug_state: DebugState,
+    locks: Locks,
+) -> anyhow::Result<()> {
+    let lease = match locks.try_acquire(LOCK_NAME).await {
+        Ok(Some(lease)) => lease,
+        Ok(None) => return Ok(()),
+        Err(err) => {
+            error!(
+                err = err.to_string(),
+                "failed to acquire partial indexes lock"
+            );
+            return Ok(());
+        }
+    };
+
+    let shutdown = shutdown_signal();
+    pin_mut!(shutdown);
+    select! {
+        res = db.create_partial_indexes() => match res {
+            Ok(()) => info!("partial embedding indexes are up to date"),
+            Err(err) => {
+                debug_state.record_error("partial_indexes", &err);
+                error!(err = err.to_string(), "error creating partial embedding indexes");
+            }
+        },
+        _ = &mut shutdown => (),
+    }
+    if let Err(err) = lease.release().await {
+        error!(
+            err = err.to_string(),
+            "failed to release partial indexes lock"
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,189 @@
+use std::sync::Arc;
+
+use regex::Regex;
+use tracing::info;
+
+use crate::{
+    config::PriorityConfig,
+    slack::{Slack, SlackError},
+    IssueData,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IssuePriority {
+    Critical,
+    Security,
+}
+
+impl IssuePriority {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Critical => "critical issue",
+            Self::Security => "security report",
+        }
+    }
+
+    /// security reports get no public comment, see [PriorityConfig]
+    pub fn is_security(&self) -> bool {
+        *self == Self::Security
+    }
+}
+
+struct Rules {
+    labels: Vec<String>,
+    patterns: Vec<Regex>,
+}
+
+impl Rules {
+    fn new(labels: &[String], patterns: &[String]) -> Result<Self, regex::Error> {
+        Ok(Self {
+            labels: labels.iter().map(|l| l.to_lowercase()).collect(),
+            patterns: patterns
+                .iter()
+                .map(|p| Regex::new(p))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    fn matches(&self, issue: &IssueData) -> bool {
+        issue
+            .labels
+            .iter()
+            .any(|l| self.labels.contains(&l.to_lowercase()))
+            || self
+                .patterns
+                .iter()
+                .any(|p| p.is_match(&issue.title) || p.is_match(&issue.body))
+    }
+}
+
+/// Detects security reports and critical issues to alert about them right away, see
+/// [PriorityConfig]
+#[derive(Clone)]
+pub struct Priorities {
+    cfg: PriorityConfig,
+    critical: Arc<Rules>,
+    security: Arc<Rules>,
+    slack: Slack,
+}
+
+impl Priorities {
+    pub fn new(cfg: PriorityConfig, slack: Slack) -> Result<Self, regex::Error> {
+        Ok(Self {
+            critical: Rules::new(&cfg.critical_labels, &cfg.critical_patterns)?.into(),
+            security: Rules::new(&cfg.security_labels, &cfg.security_patterns)?.into(),
+            cfg,
+            slack,
+        })
+    }
+
+    /// security takes precedence, a critical security report being handled as a security report
+    pub fn classify(&self, issue: &IssueData) -> Option<IssuePriority> {
+        if !self.cfg.enabled {
+            return None;
+        }
+        if self.security.matches(issue) {
+            Some(IssuePriority::Security)
+        } else if self.critical.matches(issue) {
+            Some(IssuePriority::Critical)
+        } else {
+            None
+        }
+    }
+
+    pub async fn alert(
+        &self,
+        issue: &IssueData,
+        priority: IssuePriority,
+    ) -> Result<(), SlackError> {
+        self.slack
+            .priority_alert(&self.cfg, issue, priority.as_str())
+            .await?;
+        metrics::counter!("issue_bot_priority_alerts_total", "priority" => priority.as_str())
+            .increment(1);
+        info!(
+            issue_id = issue.source_id,
+            priority = priority.as_str(),
+            "sent priority alert"
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::{load_config, IssueBotConfig, PriorityConfig},
+        live_config::LiveConfig,
+        slack::Slack,
+        Action, IssueData, Source,
+    };
+
+    use super::{IssuePriority, Priorities};
+
+    fn issue(title: &str, body: &str, labels: &[&str]) -> IssueData {
+        IssueData {
+            source_id: 1,
+            action: Action::Created,
+            author: None,
+            labels: labels.iter().map(|l| l.to_string()).collect(),
+            milestone: None,
+            title: title.to_owned(),
+            body: body.to_owned(),
+            is_pull_request: false,
+            number: 1,
+            html_url: "https://github.com/huggingface/lor-e/issues/1".to_owned(),
+            url: "https://api.github.com/repos/huggingface/lor-e/issues/1".to_owned(),
+            repository_full_name: "huggingface/lor-e".to_owned(),
+            source: Source::Github,
+        }
+    }
+
+    #[test]
+    fn test_classify() {
+        let config: IssueBotConfig = load_config("ISSUE_BOT_TEST").unwrap();
+        let slack = Slack::new(
+            &config.slack,
+            &config.http_client,
+            &config.timeouts,
+            LiveConfig::new((&config).into()),
+            None,
+        )
+        .unwrap();
+        let priorities = Priorities::new(
+            PriorityConfig {
+                enabled: true,
+                critical_patterns: vec![r"(?i)\bdata loss\b".to_owned()],
+                security_patterns: vec![r"(?i)\bCVE-\d{4}-\d+\b".to_owned()],
+                ..Default::default()
+            },
+            slack,
+        )
+        .unwrap();
+
+        let classify = |title: &str, body: &str, labels: &[&str]| {
+            priorities.classify(&issue(title, body, labels))
+        };
+        assert_eq!(
+            classify("Fix for CVE-2024-1234", "", &[]),
+            Some(IssuePriority::Security)
+        );
+        assert_eq!(
+            classify(
+                "Checkpoints deleted",
+                "silent data loss",
+                &["Critical", "Security"]
+            ),
+            Some(IssuePriority::Security)
+        );
+        assert_eq!(
+            classify("Checkpoints deleted", "silent data loss", &[]),
+            Some(IssuePriority::Critical)
+        );
+        assert_eq!(
+            classify("Typo in README", "", &["critical"]),
+            Some(IssuePriority::Critical)
+        );
+        assert_eq!(classify("Typo in README", "", &[]), None);
+    }
+}
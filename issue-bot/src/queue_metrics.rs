@@ -0,0 +1,222 @@
+use std::{collections::HashSet, time::Duration};
+
+use futures::pin_mut;
+use metrics::gauge;
+use tokio::{select, time::interval};
+use tracing::{error, info};
+
+use crate::{
+    config::QueueMetricsConfig,
+    locks::{Lease, Locks},
+    shutdown_signal,
+    storage::{
+        CommentBacklog, Database, EventStats, JobBacklog, SlackOutboxBacklog, Storage, StorageError,
+    },
+};
+
+const LOCK_NAME: &str = "queue_metrics";
+
+/// Samples the database-backed queues into gauges, for Prometheus to alert when the pipeline falls
+/// behind
+///
+/// Label sets gone since the previous sample are reset to 0 rather than left at their last value.
+/// Only the instance holding the lock samples the queues, the gauges of an instance that lost it
+/// being reset too, so that summing them over the instances doesn't count the queues twice.
+pub struct QueueMetrics {
+    cfg: QueueMetricsConfig,
+    db: Database,
+    locks: Locks,
+    /// `(event_type, outcome)` pairs exported by the previous sample
+    events: HashSet<(String, String)>,
+    jobs: HashSet<String>,
+}
+
+impl QueueMetrics {
+    pub fn new(cfg: QueueMetricsConfig, db: Database, locks: Locks) -> Self {
+        Self {
+            cfg,
+            db,
+            locks,
+            events: HashSet::new(),
+            jobs: HashSet::new(),
+        }
+    }
+
+    async fn sample(&mut self) -> Result<(), StorageError> {
+        let comments = self.db.comment_backlog().await?;
+        let slack_outbox = self.db.slack_outbox_backlog().await?;
+        let jobs = self.db.job_backlog().await?;
+        let events = self.db.event_stats(self.cfg.window_minutes).await?;
+        self.export(comments, slack_outbox, jobs, events);
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.export(
+            CommentBacklog::default(),
+            SlackOutboxBacklog::default(),
+            Vec::new(),
+            Vec::new(),
+        );
+    }
+
+    fn export(
+        &mut self,
+        comments: CommentBacklog,
+        slack_outbox: SlackOutboxBacklog,
+        job_backlog: Vec<JobBacklog>,
+        event_stats: Vec<EventStats>,
+    ) {
+        gauge!("issue_bot_pending_comments", "state" => "queued").set(comments.queued as f64);
+        gauge!("issue_bot_pending_comments", "state" => "awaiting_approval")
+            .set(comments.awaiting_approval as f64);
+        gauge!("issue_bot_oldest_pending_comment_age_seconds")
+            .set(comments.oldest_queued_secs.unwrap_or_default());
+        gauge!("issue_bot_slack_outbox_messages").set(slack_outbox.messages as f64);
+        gauge!("issue_bot_oldest_slack_outbox_message_age_seconds")
+            .set(slack_outbox.oldest_secs.unwrap_or_default());
+
+        let mut jobs = HashSet::new();
+        for backlog in job_backlog {
+            gauge!("issue_bot_jobs", "job_type" => backlog.job_type.clone())
+                .set(backlog.jobs as f64);
+            gauge!("issue_bot_job_stalled_seconds", "job_type" => backlog.job_type.clone())
+                .set(backlog.stalled_secs);
+            jobs.insert(backlog.job_type);
+        }
+        for job_type in self.jobs.difference(&jobs) {
+            gauge!("issue_bot_jobs", "job_type" => job_type.clone()).set(0.);
+            gauge!("issue_bot_job_stalled_seconds", "job_type" => job_type.clone()).set(0.);
+        }
+        self.jobs = jobs;
+
+        let mut events = HashSet::new();
+        for stats in event_stats {
+            let labels = [
+                ("event_type", stats.event_type.clone()),
+                ("outcome", stats.outcome.clone()),
+            ];
+            gauge!("issue_bot_logged_events", &labels).set(stats.events as f64);
+            gauge!("issue_bot_event_processing_avg_seconds", &labels)
+                .set(stats.avg_total_ms / 1000.);
+            events.insert((stats.event_type, stats.outcome));
+        }
+        for (event_type, outcome) in self.events.difference(&events) {
+            let labels = [
+                ("event_type", event_type.clone()),
+                ("outcome", outcome.clone()),
+            ];
+            gauge!("issue_bot_logged_events", &labels).set(0.);
+            gauge!("issue_bot_event_processing_avg_seconds", &labels).set(0.);
+        }
+        self.events = events;
+    }
+}
+
+pub async fn start_queue_metrics(mut queue_metrics: QueueMetrics) -> anyhow::Result<()> {
+    if !queue_metrics.cfg.enabled {
+        return Ok(());
+    }
+
+    info!("starting queue metrics");
+    let mut ticker = interval(Duration::from_secs(queue_metrics.cfg.interval_secs));
+    let shutdown = shutdown_signal();
+    pin_mut!(shutdown);
+    let mut lease = None;
+    loop {
+        select! {
+            _ = ticker.tick() => {
+                if lease.as_ref().is_none_or(Lease::is_lost) {
+                    let lost = lease.take().is_some();
+                    match queue_metrics.locks.try_acquire(LOCK_NAME).await {
+                        Ok(acquired) => lease = acquired,
+                        Err(err) => {
+                            error!(err = err.to_string(), "failed to acquire queue metrics lock")
+                        }
+                    }
+                    if lost && lease.is_none() {
+                        queue_metrics.reset();
+                    }
+                }
+                if lease.is_none() {
+                    continue;
+                }
+                if let Err(err) = queue_metrics.sample().await {
+                    error!(err = err.to_string(), "failed to sample queue metrics");
+                }
+            }
+            _ = &mut shutdown => break,
+        }
+    }
+    if let Some(lease) = lease {
+        if let Err(err) = lease.release().await {
+            error!(
+                err = err.to_string(),
+                "failed to release queue metrics lock"
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        storage::{JobData, JobType, Storage},
+        test_harness::test_db,
+        Source,
+    };
+
+    #[tokio::test]
+    async fn test_backlog() {
+        let db = test_db().await;
+        let backlog = db.comment_backlog().await.unwrap();
+        assert_eq!((backlog.queued, backlog.awaiting_approval), (0, 0));
+        assert!(backlog.oldest_queued_secs.is_none());
+
+        let issue_url = "https://api.github.com/repos/huggingface/lor-e/issues/1";
+        for awaiting_approval in [false, true] {
+            db.enqueue_comment(
+                &Source::Github,
+                "huggingface/lor-e",
+                issue_url,
+                "body",
+                awaiting_approval,
+                true,
+            )
+            .await
+            .unwrap();
+        }
+        db.save_job(
+            JobType::IssueIndexation,
+            Some("huggingface/lor-e"),
+            &JobData::IssueIndexation {
+                next_url: issue_url.to_owned(),
+                source: Source::Github,
+            },
+        )
+        .await
+        .unwrap();
+
+        let backlog = db.comment_backlog().await.unwrap();
+        assert_eq!((backlog.queued, backlog.awaiting_approval), (1, 1));
+        assert!(backlog.oldest_queued_secs.is_some_and(|secs| secs >= 0.));
+        let jobs = db.job_backlog().await.unwrap();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(
+            (jobs[0].job_type.as_str(), jobs[0].jobs),
+            ("issue_indexation", 1)
+        );
+        assert!(db.event_stats(15).await.unwrap().is_empty());
+
+        let slack_outbox = db.slack_outbox_backlog().await.unwrap();
+        assert_eq!(slack_outbox.messages, 0);
+        assert!(slack_outbox.oldest_secs.is_none());
+        db.enqueue_slack_message("huggingface/lor-e", &serde_json::json!({}), "timeout")
+            .await
+            .unwrap();
+        let slack_outbox = db.slack_outbox_backlog().await.unwrap();
+        assert_eq!(slack_outbox.messages, 1);
+        assert!(slack_outbox.oldest_secs.is_some_and(|secs| secs >= 0.));
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum RepoGroupsError {
+    #[error("repository '{repository}' belongs to both '{first}' and '{second}' groups")]
+    DuplicateMember {
+        repository: String,
+        first: String,
+        second: String,
+    },
+}
+
+/// Repositories whose issues are searched together, e.g. a monorepo and its satellites
+///
+/// Repositories outside of any group only match their own issues.
+#[derive(Clone, Debug, Default)]
+pub struct RepoGroups {
+    /// repository full name to the group's members, itself included
+    members: HashMap<String, Vec<String>>,
+}
+
+impl RepoGroups {
+    pub fn new(cfg: &HashMap<String, Vec<String>>) -> Result<Self, RepoGroupsError> {
+        let mut group_of: HashMap<&str, &str> = HashMap::new();
+        let mut members = HashMap::new();
+        for (group, repositories) in cfg {
+            for repository in repositories {
+                if let Some(first) = group_of.insert(repository, group) {
+                    return Err(RepoGroupsError::DuplicateMember {
+                        repository: repository.clone(),
+                        first: first.to_owned(),
+                        second: group.clone(),
+                    });
+                }
+                members.insert(repository.clone(), repositories.clone());
+            }
+        }
+        Ok(Self { members })
+    }
+
+    /// repositories searched for issues similar to one of `repository_full_name`
+    pub fn search_scope(&self, repository_full_name: &str) -> Vec<String> {
+        self.members
+            .get(repository_full_name)
+            .cloned()
+            .unwrap_or_else(|| vec![repository_full_name.to_owned()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::RepoGroups;
+
+    #[test]
+    fn test_search_scope() {
+        let cfg = HashMap::from([(
+            "transformers".to_owned(),
+            vec![
+                "huggingface/transformers".to_owned(),
+                "huggingface/tokenizers".to_owned(),
+            ],
+        )]);
+        let groups = RepoGroups::new(&cfg).unwrap();
+
+        assert_eq!(
+            groups.search_scope("huggingface/tokenizers"),
+            vec!["huggingface/transformers", "huggingface/tokenizers"]
+        );
+        assert_eq!(
+            groups.search_scope("huggingface/lor-e"),
+            vec!["huggingface/lor-e"]
+        );
+
+        let mut cfg = cfg;
+        cfg.insert(
+            "tokenizers".to_owned(),
+            vec!["huggingface/tokenizers".to_owned()],
+        );
+        assert!(RepoGroups::new(&cfg).is_err());
+    }
+}
@@ -0,0 +1,235 @@
+use std::time::Duration;
+
+use futures::pin_mut;
+use thiserror::Error;
+use tokio::{select, time::interval};
+use tracing::{error, info};
+
+use crate::{
+    config::RepoMetadataConfig,
+    debug::DebugState,
+    github::{GithubApi, GithubApiError},
+    locks::Locks,
+    shutdown_signal,
+    storage::{Database, RepositoryMetadata, Storage, StorageError},
+    ClosestIssue,
+};
+
+const LOCK_NAME: &str = "repository_metadata";
+
+#[derive(Debug, Error)]
+pub enum RepoMetadataError {
+    #[error("github api error: {0}")]
+    GithubApi(#[from] GithubApiError),
+    #[error("storage error: {0}")]
+    Storage(#[from] StorageError),
+}
+
+/// Adds `boost` to the rank of the candidates whose repository shares a topic with `topics`,
+/// keeping them ordered by decreasing [ClosestIssue::rank_score]
+fn boost_shared_topics(
+    topics: &[String],
+    metadata: &[RepositoryMetadata],
+    mut candidates: Vec<ClosestIssue>,
+    boost: f64,
+) -> Vec<ClosestIssue> {
+    for candidate in &mut candidates {
+        let shares_topic = metadata
+            .iter()
+            .find(|m| m.full_name == candidate.repository_full_name)
+            .is_some_and(|m| m.topics.iter().any(|t| topics.contains(t)));
+        if shares_topic {
+            candidate.boost += boost;
+        }
+    }
+    candidates.sort_by(|a, b| b.rank_score().total_cmp(&a.rank_score()));
+    candidates
+}
+
+/// Description, stars and topics of the indexed GitHub repositories
+#[derive(Clone)]
+pub struct RepoMetadata {
+    cfg: RepoMetadataConfig,
+    db: Database,
+    debug_state: DebugState,
+    github_api: GithubApi,
+    locks: Locks,
+}
+
+impl RepoMetadata {
+    pub fn new(
+        cfg: RepoMetadataConfig,
+        db: Database,
+        debug_state: DebugState,
+        github_api: GithubApi,
+        locks: Locks,
+    ) -> Self {
+        Self {
+            cfg,
+            db,
+            debug_state,
+            github_api,
+            locks,
+        }
+    }
+
+    pub async fn refresh(&self, repository_full_name: &str) -> Result<(), RepoMetadataError> {
+        let metadata = self
+            .github_api
+            .repository_metadata(repository_full_name)
+            .await?;
+        self.db.upsert_repository_metadata(&metadata).await?;
+        Ok(())
+    }
+
+    async fn refresh_all(&self) {
+        let repositories = match self.db.repository_metadata(None).await {
+            Ok(repositories) => repositories,
+            Err(err) => {
+                self.debug_state.record_error("database", &err);
+                error!(
+                    err = err.to_string(),
+                    "failed to fetch repositories metadata"
+                );
+                return;
+            }
+        };
+        for repository in repositories {
+            if let Err(err) = self.refresh(&repository.full_name).await {
+                self.debug_state.record_error("repo_metadata", &err);
+                error!(
+                    repository = repository.full_name,
+                    err = err.to_string(),
+                    "failed to refresh repository metadata"
+                );
+            }
+        }
+    }
+
+    pub async fn get(
+        &self,
+        repository_full_name: &str,
+    ) -> Result<Option<RepositoryMetadata>, StorageError> {
+        Ok(self
+            .db
+            .repository_metadata(Some(&[repository_full_name.to_owned()]))
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    /// boosts candidates from the same topic area as `repository_full_name`, returning them
+    /// untouched when the boost is disabled or the metadata unavailable
+    pub async fn boost_topics(
+        &self,
+        repository_full_name: &str,
+        candidates: Vec<ClosestIssue>,
+    ) -> Vec<ClosestIssue> {
+        if self.cfg.topic_boost == 0. || candidates.is_empty() {
+            return candidates;
+        }
+        let mut full_names: Vec<String> = candidates
+            .iter()
+            .map(|c| c.repository_full_name.clone())
+            .collect();
+        full_names.push(repository_full_name.to_owned());
+        full_names.sort();
+        full_names.dedup();
+        let metadata = match self.db.repository_metadata(Some(&full_names)).await {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                self.debug_state.record_error("database", &err);
+                error!(
+                    err = err.to_string(),
+                    "failed to fetch repositories metadata"
+                );
+                return candidates;
+            }
+        };
+        let Some(topics) = metadata
+            .iter()
+            .find(|m| m.full_name == repository_full_name)
+            .map(|m| m.topics.clone())
+        else {
+            return candidates;
+        };
+        boost_shared_topics(&topics, &metadata, candidates, self.cfg.topic_boost)
+    }
+}
+
+/// Refreshes the stored metadata periodically, only one instance of the bot does so at a time
+pub async fn start_repo_metadata_refresher(repo_metadata: RepoMetadata) -> anyhow::Result<()> {
+    info!("starting repository metadata refresher");
+    let mut ticker = interval(Duration::from_secs(repo_metadata.cfg.refresh_interval_secs));
+    // metadata was just fetched at index time, or will be
+    ticker.tick().await;
+    let shutdown = shutdown_signal();
+    pin_mut!(shutdown);
+    loop {
+        select! {
+            _ = ticker.tick() => {
+                match repo_metadata.locks.try_acquire(LOCK_NAME).await {
+                    Ok(Some(lease)) => {
+                        repo_metadata.refresh_all().await;
+                        if let Err(err) = lease.release().await {
+                            error!(err = err.to_string(), "failed to release repository metadata lock");
+                        }
+                    }
+                    Ok(None) => (),
+                    Err(err) => error!(err = err.to_string(), "failed to acquire repository metadata lock"),
+                }
+            }
+            _ = &mut shutdown => break,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{storage::RepositoryMetadata, ClosestIssue};
+
+    use super::boost_shared_topics;
+
+    fn candidate(repository_full_name: &str, cosine_similarity: f64) -> ClosestIssue {
+        ClosestIssue {
+            title: "title".to_owned(),
+            number: 1,
+            html_url: format!("https://github.com/{repository_full_name}/issues/1"),
+            labels: vec![],
+            repository_full_name: repository_full_name.to_owned(),
+            cosine_similarity,
+            boost: 0.,
+            embedding: vec![],
+        }
+    }
+
+    fn metadata(full_name: &str, topics: &[&str]) -> RepositoryMetadata {
+        RepositoryMetadata {
+            full_name: full_name.to_owned(),
+            description: None,
+            stars: 0,
+            topics: topics.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_boost_shared_topics() {
+        let metadata = vec![
+            metadata("huggingface/diffusers", &["diffusion"]),
+            metadata("huggingface/tokenizers", &["nlp"]),
+        ];
+        let candidates = vec![
+            candidate("huggingface/diffusers", 0.8),
+            candidate("huggingface/tokenizers", 0.75),
+        ];
+
+        let boosted = boost_shared_topics(&["nlp".to_owned()], &metadata, candidates, 0.1);
+
+        assert_eq!(boosted[0].repository_full_name, "huggingface/tokenizers");
+        assert!((boosted[0].rank_score() - 0.85).abs() < 1e-9);
+        // the similarity shown stays the raw one
+        assert_eq!(boosted[0].cosine_similarity, 0.75);
+        assert_eq!(boosted[1].rank_score(), 0.8);
+    }
+}
@@ -0,0 +1,143 @@
+use thiserror::Error;
+use tracing::info;
+
+use crate::{
+    github::{GithubApi, GithubApiError},
+    storage::{Database, DuplicateResolution, IssueLinkKind, Storage, StorageError, Suggestion},
+    ClosestIssue, DuplicateClosure, IssueData,
+};
+
+#[derive(Debug, Error)]
+pub enum ResolutionError {
+    #[error("github api error: {0}")]
+    GithubApi(#[from] GithubApiError),
+    #[error("storage error: {0}")]
+    Storage(#[from] StorageError),
+}
+
+/// compares the duplicate a maintainer chose with the suggestions made for the issue
+fn resolution(
+    closure: &DuplicateClosure,
+    duplicate_of_number: i32,
+    suggestions: &[Suggestion],
+) -> DuplicateResolution {
+    let suggested_rank = suggestions
+        .iter()
+        .find(|s| {
+            s.repository_full_name == closure.repository_full_name
+                && s.number == duplicate_of_number
+        })
+        .map(|s| s.rank);
+    DuplicateResolution {
+        issue_source_id: closure.source_id,
+        repository_full_name: closure.repository_full_name.clone(),
+        number: closure.number,
+        duplicate_of_number,
+        had_suggestions: !suggestions.is_empty(),
+        suggested_rank,
+    }
+}
+
+/// Measures the accuracy of the suggestions from the issues maintainers later close as
+/// duplicates, without relying on feedback votes
+#[derive(Clone)]
+pub struct DuplicateResolutions {
+    db: Database,
+    github_api: GithubApi,
+}
+
+impl DuplicateResolutions {
+    pub fn new(db: Database, github_api: GithubApi) -> Self {
+        Self { db, github_api }
+    }
+
+    /// remembers the issues suggested in the comment posted on `issue`
+    pub async fn record_suggestions(
+        &self,
+        issue: &IssueData,
+        suggestions: &[ClosestIssue],
+        run_id: &str,
+        prompt_profile: &str,
+    ) -> Result<(), StorageError> {
+        self.db
+            .record_suggestions(issue.source_id, suggestions, run_id, Some(prompt_profile))
+            .await
+    }
+
+    /// records which issue `closure` was a duplicate of, from the "Duplicate of #123" comment
+    /// in its timeline
+    pub async fn resolve(&self, closure: &DuplicateClosure) -> Result<(), ResolutionError> {
+        let links = self
+            .github_api
+            .timeline_links(&closure.repository_full_name, closure.number)
+            .await?;
+        let Some(duplicate_of_number) = links
+            .iter()
+            .rev()
+            .find(|l| l.kind == IssueLinkKind::Duplicate)
+            .map(|l| l.linked_number)
+        else {
+            info!(
+                issue_id = closure.source_id,
+                "no duplicate found in the timeline of the closed issue"
+            );
+            return Ok(());
+        };
+        let suggestions = self.db.suggestions(closure.source_id).await?;
+        let resolution = resolution(closure, duplicate_of_number, &suggestions);
+        self.db.upsert_duplicate_resolution(&resolution).await?;
+        let outcome = match (resolution.had_suggestions, resolution.suggested_rank) {
+            (_, Some(_)) => "suggested",
+            (true, None) => "missed",
+            (false, None) => "no_suggestions",
+        };
+        metrics::counter!("issue_bot_duplicate_resolutions_total", "outcome" => outcome)
+            .increment(1);
+        info!(
+            issue_id = closure.source_id,
+            duplicate_of = duplicate_of_number,
+            outcome,
+            "recorded duplicate resolution"
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{storage::Suggestion, DuplicateClosure};
+
+    use super::resolution;
+
+    #[test]
+    fn test_resolution() {
+        let closure = DuplicateClosure {
+            source_id: 4321,
+            repository_full_name: "huggingface/lor-e".to_owned(),
+            number: 12,
+        };
+        let suggestion = |repository_full_name: &str, number, rank| Suggestion {
+            repository_full_name: repository_full_name.to_owned(),
+            number,
+            rank,
+        };
+        let suggestions = [
+            suggestion("huggingface/transformers", 3, 0),
+            suggestion("huggingface/lor-e", 7, 1),
+            suggestion("huggingface/lor-e", 3, 2),
+        ];
+
+        let resolved = resolution(&closure, 3, &suggestions);
+        assert!(resolved.had_suggestions);
+        // the issue with the same number in another repository isn't the duplicate
+        assert_eq!(resolved.suggested_rank, Some(2));
+
+        let resolved = resolution(&closure, 5, &suggestions);
+        assert!(resolved.had_suggestions);
+        assert_eq!(resolved.suggested_rank, None);
+
+        let resolved = resolution(&closure, 5, &[]);
+        assert!(!resolved.had_suggestions);
+        assert_eq!(resolved.duplicate_of_number, 5);
+    }
+}
@@ -0,0 +1,172 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use axum::http;
+use reqwest::{
+    header::{HeaderMap, HeaderValue, ETAG, IF_NONE_MATCH, LINK},
+    RequestBuilder, Response, StatusCode,
+};
+use tracing::{info, warn};
+
+use crate::{
+    config::ResponseCacheConfig,
+    storage::{CachedResponse, Database, Storage},
+};
+
+/// stale responses are purged once every this many stored
+const PURGE_EVERY: u64 = 500;
+
+/// Stores the GitHub GET responses carrying an ETag, sending the ETag back in `If-None-Match` so
+/// that GitHub answers with a 304 Not Modified when nothing changed, which doesn't count against
+/// the rate limit, and replaying the stored response then
+///
+/// Database errors only cost the cache, requests go on unconditionally.
+#[derive(Clone)]
+pub struct ResponseCache {
+    cfg: ResponseCacheConfig,
+    db: Database,
+    stored: Arc<AtomicU64>,
+}
+
+/// `cached` replayed with the headers of the 304, e.g. the rate limit ones
+fn replay(not_modified: &HeaderMap, cached: CachedResponse) -> Response {
+    let mut response = http::Response::new(cached.body);
+    *response.headers_mut() = not_modified.clone();
+    if let Some(link) = cached
+        .link
+        .and_then(|link| HeaderValue::from_str(&link).ok())
+    {
+        response.headers_mut().insert(LINK, link);
+    }
+    response.into()
+}
+
+impl ResponseCache {
+    pub fn new(cfg: ResponseCacheConfig, db: Database) -> Self {
+        Self {
+            cfg,
+            db,
+            stored: Arc::default(),
+        }
+    }
+
+    pub async fn send(&self, request: RequestBuilder) -> Result<Response, reqwest::Error> {
+        let (client, request) = request.build_split();
+        let mut request = request?;
+        let url = request.url().to_string();
+        let cached = match self.db.cached_response(&url).await {
+            Ok(cached) => cached,
+            Err(err) => {
+                warn!(
+                    url,
+                    err = err.to_string(),
+                    "failed to fetch cached response"
+                );
+                None
+            }
+        };
+        if let Some(etag) = cached
+            .as_ref()
+            .and_then(|cached| HeaderValue::from_str(&cached.etag).ok())
+        {
+            request.headers_mut().insert(IF_NONE_MATCH, etag);
+        }
+        let res = client.execute(request).await?;
+        if let Some(cached) = cached.filter(|_| res.status() == StatusCode::NOT_MODIFIED) {
+            metrics::counter!("issue_bot_github_response_cache_total", "outcome" => "hit")
+                .increment(1);
+            // still current, it shouldn't be purged as stale
+            if let Err(err) = self.db.refresh_cached_response(&url).await {
+                warn!(
+                    url,
+                    err = err.to_string(),
+                    "failed to refresh cached response"
+                );
+            }
+            return Ok(replay(res.headers(), cached));
+        }
+        metrics::counter!("issue_bot_github_response_cache_total", "outcome" => "miss")
+            .increment(1);
+        let etag = res
+            .headers()
+            .get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(ToOwned::to_owned);
+        let Some(etag) = etag.filter(|_| res.status().is_success()) else {
+            return Ok(res);
+        };
+
+        // the body is read to be stored, the response rebuilt from it
+        let status = res.status();
+        let headers = res.headers().clone();
+        let body = res.bytes().await?.to_vec();
+        let cached = CachedResponse {
+            etag,
+            link: headers
+                .get(LINK)
+                .and_then(|link| link.to_str().ok())
+                .map(ToOwned::to_owned),
+            body,
+        };
+        if let Err(err) = self.db.cache_response(&url, &cached).await {
+            warn!(url, err = err.to_string(), "failed to cache response");
+        }
+        self.purge().await;
+        let mut response = http::Response::new(cached.body);
+        *response.status_mut() = status;
+        *response.headers_mut() = headers;
+        Ok(response.into())
+    }
+
+    async fn purge(&self) {
+        if !self
+            .stored
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(PURGE_EVERY)
+        {
+            return;
+        }
+        match self.db.purge_cached_responses(self.cfg.max_age_days).await {
+            Ok(0) => (),
+            Ok(purged) => info!(purged, "purged stale cached github responses"),
+            Err(err) => warn!(err = err.to_string(), "failed to purge cached responses"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::{
+        header::{HeaderMap, HeaderValue, LINK},
+        StatusCode,
+    };
+
+    use crate::storage::CachedResponse;
+
+    use super::replay;
+
+    #[tokio::test]
+    async fn test_replay() {
+        let mut not_modified = HeaderMap::new();
+        not_modified.insert("x-ratelimit-remaining", HeaderValue::from_static("4999"));
+        let response = replay(
+            &not_modified,
+            CachedResponse {
+                etag: "\"abc\"".to_owned(),
+                link: Some(
+                    "<https://api.github.com/repos/a/b/issues?page=2>; rel=\"next\"".to_owned(),
+                ),
+                body: b"[]".to_vec(),
+            },
+        );
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-ratelimit-remaining"], "4999");
+        assert!(response.headers()[LINK]
+            .to_str()
+            .unwrap()
+            .contains("page=2"));
+        assert_eq!(response.text().await.unwrap(), "[]");
+    }
+}
@@ -0,0 +1,181 @@
+use std::time::Duration;
+
+use futures::pin_mut;
+use thiserror::Error;
+use tokio::{select, time::interval};
+use tracing::{error, info};
+
+use crate::{
+    archive::{Archive, ArchiveError},
+    config::{RetentionAction, RetentionConfig, RetentionRule},
+    debug::DebugState,
+    locks::Locks,
+    shutdown_signal,
+    storage::{Database, Storage, StorageError},
+};
+
+const LOCK_NAME: &str = "retention";
+
+/// issues archived before being deleted together
+const ARCHIVE_BATCH_SIZE: usize = 100;
+
+#[derive(Debug, Error)]
+pub enum RetentionError {
+    #[error("archive error: {0}")]
+    Archive(#[from] ArchiveError),
+    #[error("storage error: {0}")]
+    Storage(#[from] StorageError),
+}
+
+/// Keeps old issues out of the suggestions, either by excluding them from similarity searches or
+/// by archiving them
+#[derive(Clone)]
+pub struct Retention {
+    archive: Archive,
+    cfg: RetentionConfig,
+    db: Database,
+    debug_state: DebugState,
+    locks: Locks,
+}
+
+impl Retention {
+    pub fn new(
+        cfg: RetentionConfig,
+        archive: Archive,
+        db: Database,
+        debug_state: DebugState,
+        locks: Locks,
+    ) -> Self {
+        Self {
+            archive,
+            cfg,
+            db,
+            debug_state,
+            locks,
+        }
+    }
+
+    async fn apply(&self, rule: &RetentionRule) -> Result<(), RetentionError> {
+        let source_ids = self
+            .db
+            .stale_issues(
+                rule.repository.as_deref(),
+                rule.max_age_days,
+                &rule.labels,
+                rule.state,
+            )
+            .await?;
+        if source_ids.is_empty() {
+            return Ok(());
+        }
+        match rule.action {
+            RetentionAction::Exclude => self.db.exclude_issues(&source_ids).await?,
+            RetentionAction::Archive => {
+                for chunk in source_ids.chunks(ARCHIVE_BATCH_SIZE) {
+                    self.archive.archive_issues(chunk).await?;
+                    self.db.delete_issues(chunk).await?;
+                }
+            }
+        }
+        info!(
+            repository = rule.repository,
+            action = ?rule.action,
+            issues = source_ids.len(),
+            "applied retention rule"
+        );
+        Ok(())
+    }
+
+    async fn enforce(&self) {
+        for rule in &self.cfg.rules {
+            if let Err(err) = self.apply(rule).await {
+                self.debug_state.record_error("retention", &err);
+                error!(
+                    repository = rule.repository,
+                    err = err.to_string(),
+                    "error applying retention rule"
+                );
+            }
+        }
+    }
+}
+
+/// Enforces the retention rules periodically, only one instance of the bot does so at a time
+pub async fn start_retention(retention: Retention) -> anyhow::Result<()> {
+    if !retention.cfg.enabled || retention.cfg.rules.is_empty() {
+        return Ok(());
+    }
+
+    info!("starting retention");
+    let mut ticker = interval(Duration::from_secs(retention.cfg.interval_secs));
+    let shutdown = shutdown_signal();
+    pin_mut!(shutdown);
+    loop {
+        select! {
+            _ = ticker.tick() => {
+                match retention.locks.try_acquire(LOCK_NAME).await {
+                    Ok(Some(lease)) => {
+                        retention.enforce().await;
+                        if let Err(err) = lease.release().await {
+                            error!(err = err.to_string(), "failed to release retention lock");
+                        }
+                    }
+                    Ok(None) => (),
+                    Err(err) => error!(err = err.to_string(), "failed to acquire retention lock"),
+                }
+            }
+            _ = &mut shutdown => break,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{storage::Storage, test_harness::test_db, Action, IssueData, Source};
+
+    fn issue(source_id: i64, number: i32) -> IssueData {
+        IssueData {
+            source_id,
+            action: Action::Created,
+            author: None,
+            labels: Vec::new(),
+            milestone: None,
+            title: format!("issue {number}"),
+            body: String::new(),
+            is_pull_request: false,
+            number,
+            html_url: format!("https://github.com/huggingface/lor-e/issues/{number}"),
+            url: format!("https://api.github.com/repos/huggingface/lor-e/issues/{number}"),
+            repository_full_name: "huggingface/lor-e".to_owned(),
+            source: Source::Github,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_excluded_issues_left_out_of_searches() {
+        let db = test_db().await;
+        db.insert_issue(&issue(1, 1), &[1., 0.], None)
+            .await
+            .unwrap();
+        db.insert_issue(&issue(2, 2), &[0.9, 0.1], None)
+            .await
+            .unwrap();
+        let repositories = vec!["huggingface/lor-e".to_owned()];
+
+        // freshly inserted issues aren't stale
+        assert!(db
+            .stale_issues(None, 30, &[], None)
+            .await
+            .unwrap()
+            .is_empty());
+
+        db.exclude_issues(&[1]).await.unwrap();
+        let closest = db
+            .closest_issues(&[1., 0.], &[], &repositories, 3, false)
+            .await
+            .unwrap();
+        let numbers: Vec<i32> = closest.iter().map(|ci| ci.number).collect();
+        assert_eq!(numbers, vec![2]);
+    }
+}
@@ -0,0 +1,423 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use reqwest::{StatusCode, Url};
+use tokio::time::sleep;
+use tracing::warn;
+
+use crate::config::RetryBudgetConfig;
+
+/// How a failed outbound API call should be handled
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RetryClass {
+    /// transient failure (timeouts, connection errors, 5xx), retry with backoff
+    Retryable,
+    /// the remote asked us to slow down, retry after a longer pause
+    RateLimited,
+    /// retrying won't help (bad request, auth, malformed response, ...)
+    Fatal,
+}
+
+pub trait Classify {
+    fn retry_class(&self) -> RetryClass;
+
+    /// how long the remote asked to wait before retrying, when it did
+    fn retry_after(&self) -> Option<Duration> {
+        None
+    }
+}
+
+pub fn classify_status(status: StatusCode) -> RetryClass {
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        RetryClass::RateLimited
+    } else if status.is_server_error() || status == StatusCode::REQUEST_TIMEOUT {
+        RetryClass::Retryable
+    } else {
+        RetryClass::Fatal
+    }
+}
+
+pub fn classify_reqwest(err: &reqwest::Error) -> RetryClass {
+    if let Some(status) = err.status() {
+        return classify_status(status);
+    }
+    if err.is_timeout() || err.is_connect() || err.is_request() {
+        RetryClass::Retryable
+    } else {
+        RetryClass::Fatal
+    }
+}
+
+/// delay before the next attempt at a persisted delivery after `attempts` failed ones, doubling
+/// from `base` up to `max`
+pub fn backoff(base: Duration, max: Duration, attempts: i32) -> Duration {
+    base.saturating_mul(2_u32.saturating_pow(attempts.max(0) as u32))
+        .min(max)
+}
+
+/// host of `url`, used as the [RetryBudget] key, `url` itself when it has none
+pub fn host(url: &str) -> String {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_owned))
+        .unwrap_or_else(|| url.to_owned())
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// Retries every retry loop of the process may attempt against each host, so that they can't
+/// collectively overwhelm a struggling dependency
+///
+/// Each host gets a token bucket, refilled over time and by every successful call, a retry taking
+/// a token. Retries are shed, the error being returned as is, once the bucket is empty.
+#[derive(Clone)]
+pub struct RetryBudget {
+    buckets: Arc<Mutex<HashMap<String, Bucket>>>,
+    cfg: RetryBudgetConfig,
+}
+
+impl RetryBudget {
+    pub fn new(cfg: RetryBudgetConfig) -> Self {
+        Self {
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+            cfg,
+        }
+    }
+
+    /// adds `amount` to the bucket of `host`, along with what was refilled since it last was, and
+    /// takes `cost` from it when there is enough
+    fn settle(&self, host: &str, amount: f64, cost: f64) -> bool {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry(host.to_owned()).or_insert(Bucket {
+            tokens: self.cfg.max_tokens,
+            refilled_at: now,
+        });
+        let refilled = (now - bucket.refilled_at).as_secs_f64() * self.cfg.refill_per_sec;
+        bucket.tokens = (bucket.tokens + refilled + amount).min(self.cfg.max_tokens);
+        bucket.refilled_at = now;
+        if bucket.tokens < cost {
+            return false;
+        }
+        bucket.tokens -= cost;
+        true
+    }
+
+    /// records a successful call to `host`, growing its budget
+    pub fn deposit(&self, host: &str) {
+        if self.cfg.enabled {
+            self.settle(host, self.cfg.success_deposit, 0.);
+        }
+    }
+
+    /// whether a retry against `host` may be attempted, counting it otherwise in
+    /// `issue_bot_retries_shed_total`
+    pub fn withdraw(&self, host: &str) -> bool {
+        if !self.cfg.enabled || self.settle(host, 0., 1.) {
+            return true;
+        }
+        warn!(host, "retry budget exhausted, shedding retry");
+        metrics::counter!("issue_bot_retries_shed_total", "host" => host.to_owned()).increment(1);
+        false
+    }
+}
+
+impl Default for RetryBudget {
+    fn default() -> Self {
+        Self::new(RetryBudgetConfig::default())
+    }
+}
+
+#[derive(Clone)]
+pub struct RetryPolicy {
+    /// delay before the first retry of a [RetryClass::Retryable] error, doubled on every attempt
+    pub base_delay: Duration,
+    /// shared with the other retry loops of the process
+    pub budget: RetryBudget,
+    /// total time spent waiting between attempts, the error being returned for the caller to
+    /// queue the call once the next delay would exceed it, rather than holding up its worker
+    pub max_total_delay: Duration,
+    pub max_retries: u32,
+    /// delay before retrying a [RetryClass::RateLimited] error, unless the remote said how long
+    pub rate_limit_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(budget: RetryBudget) -> Self {
+        Self {
+            base_delay: Duration::from_secs(1),
+            budget,
+            max_total_delay: Duration::from_secs(30),
+            max_retries: 3,
+            rate_limit_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Calls `f` until it succeeds, returns a [RetryClass::Fatal] error, or retries against `host` are
+/// exhausted, shed by the [RetryBudget] or would wait longer than `max_total_delay`
+pub async fn with_retry<T, E, F, Fut>(policy: &RetryPolicy, host: &str, f: F) -> Result<T, E>
+where
+    E: Classify + Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    with_retry_on(policy, || host.to_owned(), f).await
+}
+
+/// Same as [with_retry] for APIs failing over between endpoints, the budget of the host `host`
+/// returns at the time being taken, e.g. a fallback's once failed over
+pub async fn with_retry_on<T, E, H, F, Fut>(policy: &RetryPolicy, host: H, mut f: F) -> Result<T, E>
+where
+    E: Classify + Display,
+    H: Fn() -> String,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut attempt = 0;
+    let mut waited = Duration::ZERO;
+    loop {
+        let err = match f().await {
+            Ok(res) => {
+                policy.budget.deposit(&host());
+                return Ok(res);
+            }
+            Err(err) => err,
+        };
+        let delay = match err.retry_class() {
+            RetryClass::Fatal => return Err(err),
+            _ if attempt >= policy.max_retries => return Err(err),
+            RetryClass::Retryable => policy.base_delay * 2_u32.pow(attempt),
+            RetryClass::RateLimited => err.retry_after().unwrap_or(policy.rate_limit_delay),
+        };
+        if waited + delay > policy.max_total_delay || !policy.budget.withdraw(&host()) {
+            return Err(err);
+        }
+        waited += delay;
+        attempt += 1;
+        warn!(
+            err = err.to_string(),
+            attempt,
+            "retrying in {}ms",
+            delay.as_millis()
+        );
+        sleep(delay).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use reqwest::StatusCode;
+
+    use super::{
+        backoff, classify_status, host, with_retry, with_retry_on, Classify, RetryBudget,
+        RetryClass, RetryPolicy,
+    };
+    use crate::config::RetryBudgetConfig;
+
+    #[derive(Debug)]
+    struct TestError(RetryClass);
+
+    #[derive(Debug)]
+    struct RetryAfterError(Duration);
+
+    impl std::fmt::Display for RetryAfterError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "retry after {:?}", self.0)
+        }
+    }
+
+    impl Classify for RetryAfterError {
+        fn retry_class(&self) -> RetryClass {
+            RetryClass::RateLimited
+        }
+
+        fn retry_after(&self) -> Option<Duration> {
+            Some(self.0)
+        }
+    }
+
+    impl std::fmt::Display for TestError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{:?}", self.0)
+        }
+    }
+
+    impl Classify for TestError {
+        fn retry_class(&self) -> RetryClass {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_backoff() {
+        let backoff =
+            |attempts| backoff(Duration::from_secs(30), Duration::from_secs(3600), attempts);
+        assert_eq!(backoff(0), Duration::from_secs(30));
+        assert_eq!(backoff(3), Duration::from_secs(240));
+        assert_eq!(backoff(7), Duration::from_secs(3600));
+        assert_eq!(backoff(100), Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn test_classify_status() {
+        assert_eq!(
+            classify_status(StatusCode::TOO_MANY_REQUESTS),
+            RetryClass::RateLimited
+        );
+        assert_eq!(
+            classify_status(StatusCode::BAD_GATEWAY),
+            RetryClass::Retryable
+        );
+        assert_eq!(classify_status(StatusCode::NOT_FOUND), RetryClass::Fatal);
+    }
+
+    #[tokio::test]
+    async fn test_with_retry() {
+        let policy = RetryPolicy {
+            base_delay: Duration::ZERO,
+            budget: RetryBudget::default(),
+            max_total_delay: Duration::from_secs(1),
+            max_retries: 2,
+            rate_limit_delay: Duration::ZERO,
+        };
+
+        let mut calls = 0;
+        let res: Result<(), _> = with_retry(&policy, "example.com", || {
+            calls += 1;
+            async { Err(TestError(RetryClass::Retryable)) }
+        })
+        .await;
+        assert!(res.is_err());
+        assert_eq!(calls, 3);
+
+        let mut calls = 0;
+        let res: Result<(), _> = with_retry(&policy, "example.com", || {
+            calls += 1;
+            async { Err(TestError(RetryClass::Fatal)) }
+        })
+        .await;
+        assert!(res.is_err());
+        assert_eq!(calls, 1);
+
+        let mut calls = 0;
+        let res = with_retry(&policy, "example.com", || {
+            calls += 1;
+            let calls = calls;
+            async move {
+                if calls < 2 {
+                    Err(TestError(RetryClass::RateLimited))
+                } else {
+                    Ok(calls)
+                }
+            }
+        })
+        .await;
+        assert_eq!(res.unwrap(), 2);
+
+        // waiting as long as the remote asks would hold the caller up for too long
+        let mut calls = 0;
+        let res: Result<(), _> = with_retry(&policy, "example.com", || {
+            calls += 1;
+            async { Err(RetryAfterError(Duration::from_secs(60))) }
+        })
+        .await;
+        assert!(res.is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[tokio::test]
+    async fn test_retry_budget() {
+        let budget = RetryBudget::new(RetryBudgetConfig {
+            enabled: true,
+            max_tokens: 2.,
+            refill_per_sec: 0.,
+            success_deposit: 0.5,
+        });
+        assert!(budget.withdraw("a.example.com"));
+        assert!(budget.withdraw("a.example.com"));
+        assert!(!budget.withdraw("a.example.com"));
+        // hosts have a budget of their own
+        assert!(budget.withdraw("b.example.com"));
+        budget.deposit("a.example.com");
+        budget.deposit("a.example.com");
+        assert!(budget.withdraw("a.example.com"));
+        assert!(!budget.withdraw("a.example.com"));
+
+        let policy = RetryPolicy {
+            base_delay: Duration::ZERO,
+            budget,
+            max_total_delay: Duration::from_secs(1),
+            max_retries: 5,
+            rate_limit_delay: Duration::ZERO,
+        };
+        let mut calls = 0;
+        let res: Result<(), _> = with_retry(&policy, "b.example.com", || {
+            calls += 1;
+            async { Err(TestError(RetryClass::Retryable)) }
+        })
+        .await;
+        assert!(res.is_err());
+        // the one retry left, shed afterwards
+        assert_eq!(calls, 2);
+    }
+
+    #[tokio::test]
+    async fn test_with_retry_on_failover() {
+        let budget = RetryBudget::new(RetryBudgetConfig {
+            enabled: true,
+            max_tokens: 1.,
+            refill_per_sec: 0.,
+            success_deposit: 0.,
+        });
+        let policy = RetryPolicy {
+            base_delay: Duration::ZERO,
+            budget: budget.clone(),
+            max_total_delay: Duration::from_secs(1),
+            max_retries: 5,
+            rate_limit_delay: Duration::ZERO,
+        };
+        let calls = std::cell::Cell::new(0);
+        let res: Result<(), _> = with_retry_on(
+            &policy,
+            // failed over to the fallback after the first call
+            || {
+                if calls.get() > 1 {
+                    "fallback.example.com"
+                } else {
+                    "primary.example.com"
+                }
+                .to_owned()
+            },
+            || {
+                calls.set(calls.get() + 1);
+                async { Err(TestError(RetryClass::Retryable)) }
+            },
+        )
+        .await;
+        assert!(res.is_err());
+        // one retry from each host's budget
+        assert_eq!(calls.get(), 3);
+        assert!(!budget.withdraw("primary.example.com"));
+        assert!(!budget.withdraw("fallback.example.com"));
+    }
+
+    #[test]
+    fn test_host() {
+        assert_eq!(
+            host("https://api.example.com/v1/embeddings"),
+            "api.example.com"
+        );
+        assert_eq!(host("not a url"), "not a url");
+    }
+}
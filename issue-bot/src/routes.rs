@@ -1,25 +1,54 @@
-use std::{fmt::Display, sync::atomic::Ordering};
+use std::{convert::Infallible, fmt::Display, marker::PhantomData, sync::atomic::Ordering};
 
+use async_stream::try_stream;
 use axum::{
-    body::Body,
-    extract::{FromRef, FromRequestParts, Request, State},
-    http::{request::Parts, HeaderName, StatusCode},
-    response::IntoResponse,
+    body::{Body, Bytes},
+    extract::{FromRef, FromRequest, FromRequestParts, Path, Query, Request, State},
+    http::{header::CONTENT_TYPE, request::Parts, HeaderName, HeaderValue, StatusCode},
+    response::{
+        sse::{self, KeepAlive, Sse},
+        Html, IntoResponse,
+    },
     routing::post,
-    Json, Router,
+    Extension, Form, Json, Router,
 };
+use chrono::{DateTime, Utc};
+use futures::Stream;
 use hmac::{Hmac, Mac};
 use reqwest::header::AUTHORIZATION;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use sha2::Sha256;
-use tracing::info;
+use tracing::{error, info, warn};
 
 use crate::{
-    deserialize_null_default, errors::ApiError, Action, AppState, EventData, IndexIssueData,
-    RepositoryData, Source, PRE_SHUTDOWN,
+    api_keys::{self, AdminScope, ExportScope, IndexScope, RequiredScope, SearchScope},
+    compare::{IssueComparison, IssueReference},
+    debug::DebugStateSnapshot,
+    deserialize_null_default,
+    drift::DriftReport,
+    errors::ApiError,
+    github_oidc::ActionsClaims,
+    huggingface::BOT_USER_ID,
+    ignore::EventMetadata,
+    job_control::resume_event,
+    locks,
+    middlewares::RequestId,
+    onboarding::OnboardingReport,
+    opt_out::opt_out_command,
+    search::{SearchPage, SearchRequest},
+    settings::SimilaritySettings,
+    slack::{DraftAction, DraftDecision},
+    storage::{
+        ApiKey, BotCommentRevision, Database, EmbeddingRecord, EventLogEntry, EventLogFilter,
+        JobState, KnowledgeBaseEntry, OnboardedRepository, OptOutRequest, Storage, StorageError,
+    },
+    suppression::{is_maintainer, is_mute_command, is_unmute_command},
+    webhooks::WebhookReport,
+    Action, AppState, AuthorOptOut, EventData, FeedbackData, IndexIssueData, IssueEmbeddings,
+    IssueSuppression, QueuedEvent, RepositoryData, ReviewCommentData, Source, Vote, PRE_SHUTDOWN,
 };
 
-fn compute_signature(payload: &[u8], secret: &str) -> String {
+pub(crate) fn compute_signature(payload: &[u8], secret: &str) -> String {
     let key = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
     let mut mac = key;
     mac.update(payload);
@@ -57,6 +86,14 @@ enum IssueActionType {
     Opened,
     Edited,
     Deleted,
+    Labeled,
+    Unlabeled,
+    Milestoned,
+    Demilestoned,
+    Closed,
+    Reopened,
+    /// commits were pushed to a pull request
+    Synchronize,
     /// We don't care about other action types
     #[serde(other)]
     Ignored,
@@ -67,7 +104,16 @@ impl IssueActionType {
             Self::Opened => Action::Created,
             Self::Edited => Action::Edited,
             Self::Deleted => Action::Deleted,
-            Self::Ignored => unreachable!("IssueActionType::to_action called with Ignored"),
+            Self::Labeled
+            | Self::Unlabeled
+            | Self::Milestoned
+            | Self::Demilestoned
+            | Self::Closed
+            | Self::Reopened
+            | Self::Synchronize
+            | Self::Ignored => {
+                unreachable!("IssueActionType::to_action called with {self}")
+            }
         }
     }
 }
@@ -80,9 +126,29 @@ impl Display for IssueActionType {
 
 #[derive(Debug, Deserialize, Serialize)]
 struct Comment {
+    /// e.g. `MEMBER`, telling maintainers apart
+    #[serde(default)]
+    author_association: Option<String>,
     body: String,
     id: i64,
     url: String,
+    #[serde(default)]
+    user: Option<User>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Label {
+    name: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Milestone {
+    title: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct User {
+    login: String,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -91,24 +157,83 @@ struct PullRequest {
     url: String,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+struct Change {
+    #[serde(default)]
+    from: Option<String>,
+}
+
+/// Previous values of the fields changed by an `edited` action
+#[derive(Debug, Deserialize, Serialize)]
+struct IssueChanges {
+    #[serde(default)]
+    title: Option<Change>,
+    #[serde(default)]
+    body: Option<Change>,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 struct Issue {
     action: IssueActionType,
+    #[serde(default)]
+    changes: Option<IssueChanges>,
     issue: IssueData,
     repository: Repository,
 }
 
+impl Issue {
+    /// whether an `edited` action changed the title or the body, assumed when `changes` is missing
+    fn text_changed(&self) -> bool {
+        let Some(changes) = &self.changes else {
+            return true;
+        };
+        let title_changed = changes
+            .title
+            .as_ref()
+            .is_some_and(|c| c.from.as_deref() != Some(self.issue.title.as_str()));
+        let body_changed = changes
+            .body
+            .as_ref()
+            .is_some_and(|c| c.from.as_deref().unwrap_or_default() != self.issue.body);
+        title_changed || body_changed
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 struct IssueData {
     #[serde(default, deserialize_with = "deserialize_null_default")]
     body: String,
     html_url: String,
     id: i64,
+    #[serde(default, deserialize_with = "deserialize_null_default")]
+    labels: Vec<Label>,
+    #[serde(default)]
+    milestone: Option<Milestone>,
     number: i32,
     #[serde(default)]
     pull_request: Option<PullRequest>,
+    /// why the issue was closed, e.g. `duplicate`
+    #[serde(default)]
+    state_reason: Option<String>,
     title: String,
     url: String,
+    #[serde(default)]
+    user: Option<User>,
+}
+
+impl IssueData {
+    fn label_names(&self) -> Vec<String> {
+        self.labels.iter().map(|l| l.name.clone()).collect()
+    }
+
+    fn event_metadata<'a>(&'a self, repository: &'a Repository) -> EventMetadata<'a> {
+        EventMetadata {
+            authors: self.user.iter().map(|u| u.login.as_str()).collect(),
+            labels: self.labels.iter().map(|l| l.name.as_str()).collect(),
+            repository: &repository.full_name,
+            title: &self.title,
+        }
+    }
 }
 
 /// Issue & Pull Request comments
@@ -120,16 +245,47 @@ struct IssueComment {
     repository: Repository,
 }
 
+/// Pull request events, whose `pull_request` has the fields of an issue but not its id
+#[derive(Debug, Deserialize, Serialize)]
+struct PullRequestEvent {
+    action: IssueActionType,
+    pull_request: PullRequestData,
+    repository: Repository,
+}
+
+/// Comments on the diff of a pull request
+#[derive(Debug, Deserialize, Serialize)]
+struct PullRequestReviewComment {
+    action: CommentActionType,
+    comment: Comment,
+    pull_request: PullRequestData,
+    repository: Repository,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct PullRequestData {
+    #[serde(flatten)]
+    issue: IssueData,
+    #[serde(default)]
+    head: Option<Head>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Head {
+    sha: String,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 struct Repository {
     full_name: String,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(untagged)]
+#[derive(Debug)]
 enum GithubWebhook {
     IssueComment(IssueComment),
     Issue(Issue),
+    PullRequest(PullRequestEvent),
+    PullRequestReviewComment(PullRequestReviewComment),
 }
 
 impl Display for GithubWebhook {
@@ -137,73 +293,427 @@ impl Display for GithubWebhook {
         let webhook_type = match self {
             Self::Issue(_) => "issue",
             Self::IssueComment(_) => "issue comment",
+            Self::PullRequest(_) => "pull request",
+            Self::PullRequestReviewComment(_) => "pull request review comment",
         };
         write!(f, "{}", webhook_type)
     }
 }
 
-pub async fn github_webhook(
-    State(state): State<AppState>,
+/// Event a webhook translates to, returned as is by the `dry-run` routes
+#[derive(Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum ParsedWebhook {
+    Event { event: EventData },
+    Ignored { reason: String },
+}
+
+const X_GITHUB_EVENT: HeaderName = HeaderName::from_static("x-github-event");
+const X_GITHUB_DELIVERY: HeaderName = HeaderName::from_static("x-github-delivery");
+
+/// event name and body of a GitHub webhook request, once its signature is verified
+async fn verified_github_body(
+    state: &AppState,
     req: Request<Body>,
-) -> anyhow::Result<(), ApiError> {
+) -> Result<(String, Bytes), ApiError> {
+    let (event, body_bytes, _) = verified_github_delivery(state, req).await?;
+    Ok((event, body_bytes))
+}
+
+/// [verified_github_body], along with the delivery id
+async fn verified_github_delivery(
+    state: &AppState,
+    req: Request<Body>,
+) -> Result<(String, Bytes, Option<HeaderValue>), ApiError> {
     let header_name = HeaderName::from_static("x-hub-signature-256");
     let sig = req
         .headers()
         .get(header_name)
         .ok_or(ApiError::SignatureMismatch)?
         .clone();
-    let body = req.into_body();
-    let body_bytes = axum::body::to_bytes(body, usize::MAX).await?;
+    let event = req.headers().get(X_GITHUB_EVENT).cloned();
+    let delivery = req.headers().get(X_GITHUB_DELIVERY).cloned();
+    // honors the body size limit, unlike `axum::body::to_bytes`
+    let body_bytes =
+        Bytes::from_request(req, &())
+            .await
+            .map_err(|rejection| match rejection.status() {
+                StatusCode::PAYLOAD_TOO_LARGE => ApiError::PayloadTooLarge,
+                _ => ApiError::BadRequest(rejection.body_text()),
+            })?;
     let expected_sig = compute_signature(&body_bytes, &state.auth_token);
 
     if expected_sig != sig {
         return Err(ApiError::SignatureMismatch);
     }
+    let event = event
+        .ok_or_else(|| ApiError::MalformedWebhook("Missing X-GitHub-Event header".to_owned()))?
+        .to_str()
+        .map_err(|_| ApiError::MalformedWebhook("Invalid X-GitHub-Event header".to_owned()))?
+        .to_owned();
+    Ok((event, body_bytes, delivery))
+}
+
+/// deserializes a webhook payload, reporting the path of the field that failed
+fn deserialize_webhook<T: DeserializeOwned>(body_bytes: &[u8]) -> Result<T, ApiError> {
+    let deserializer = &mut serde_json::Deserializer::from_slice(body_bytes);
+    serde_path_to_error::deserialize(deserializer).map_err(|err| {
+        metrics::counter!("issue_bot_malformed_webhooks_total").increment(1);
+        match err.path().to_string().as_str() {
+            "." => ApiError::MalformedWebhook(err.inner().to_string()),
+            path => ApiError::MalformedWebhook(format!("Invalid {path}: {}", err.inner())),
+        }
+    })
+}
+
+/// events repositories' webhooks must send, handled by [deserialize_github_webhook]
+pub const GITHUB_WEBHOOK_EVENTS: [&str; 4] = [
+    "issue_comment",
+    "issues",
+    "pull_request",
+    "pull_request_review_comment",
+];
+
+/// payload of a GitHub webhook, typed from its `X-GitHub-Event` header, `None` for the events
+/// we don't subscribe to
+fn deserialize_github_webhook(
+    event: &str,
+    body_bytes: &[u8],
+) -> Result<Option<GithubWebhook>, ApiError> {
+    let webhook = match event {
+        "issues" => GithubWebhook::Issue(deserialize_webhook(body_bytes)?),
+        "issue_comment" => GithubWebhook::IssueComment(deserialize_webhook(body_bytes)?),
+        "pull_request" => GithubWebhook::PullRequest(deserialize_webhook(body_bytes)?),
+        "pull_request_review_comment" => {
+            GithubWebhook::PullRequestReviewComment(deserialize_webhook(body_bytes)?)
+        }
+        _ => {
+            metrics::counter!("issue_bot_unsupported_webhooks_total", "event" => event.to_owned())
+                .increment(1);
+            return Ok(None);
+        }
+    };
+    Ok(Some(webhook))
+}
 
-    let webhook = serde_json::from_slice::<GithubWebhook>(&body_bytes)?;
+async fn parse_github_webhook(
+    state: &AppState,
+    event: &str,
+    body_bytes: &[u8],
+) -> Result<ParsedWebhook, ApiError> {
+    let Some(webhook) = deserialize_github_webhook(event, body_bytes)? else {
+        info!("ignoring unsupported {} event", event);
+        return Ok(ParsedWebhook::Ignored {
+            reason: format!("unsupported {event} event"),
+        });
+    };
     let webhook_type = webhook.to_string();
-    match webhook {
+    let event = match webhook {
         GithubWebhook::Issue(issue) => {
             info!("received {} (state: {})", webhook_type, issue.action);
+            // deletions, closures and metadata changes still reach issues stored before they
+            // matched a rule
+            let ignorable = matches!(
+                issue.action,
+                IssueActionType::Opened | IssueActionType::Edited
+            );
+            if let Some(reason) = state
+                .ignore_rules
+                .ignore_reason(&issue.issue.event_metadata(&issue.repository))
+                .filter(|_| ignorable)
+            {
+                info!("ignoring {}: {}", webhook_type, reason);
+                return Ok(ParsedWebhook::Ignored {
+                    reason: reason.to_string(),
+                });
+            }
             match issue.action {
+                // only the text is embedded, spare the re-embedding of metadata only edits
+                IssueActionType::Edited if !issue.text_changed() => {
+                    info!("title and body unchanged, only updating issue metadata");
+                    EventData::IssueMetadata(crate::IssueMetadata {
+                        source_id: issue.issue.id,
+                        labels: issue.issue.label_names(),
+                        milestone: issue.issue.milestone.map(|m| m.title),
+                    })
+                }
                 IssueActionType::Opened | IssueActionType::Edited | IssueActionType::Deleted => {
-                    state
-                        .tx
-                        .send(EventData::Issue(crate::IssueData {
-                            source_id: issue.issue.id,
-                            action: issue.action.to_action(),
-                            title: issue.issue.title,
-                            body: issue.issue.body,
-                            is_pull_request: issue.issue.pull_request.is_some(),
-                            number: issue.issue.number,
-                            html_url: issue.issue.html_url,
-                            url: issue.issue.url,
-                            repository_full_name: issue.repository.full_name,
-                            source: Source::Github,
-                        }))
-                        .await?
+                    EventData::Issue(crate::IssueData {
+                        source_id: issue.issue.id,
+                        action: issue.action.to_action(),
+                        author: issue.issue.user.as_ref().map(|u| u.login.clone()),
+                        labels: issue.issue.label_names(),
+                        milestone: issue.issue.milestone.map(|m| m.title),
+                        title: issue.issue.title,
+                        body: issue.issue.body,
+                        is_pull_request: issue.issue.pull_request.is_some(),
+                        number: issue.issue.number,
+                        html_url: issue.issue.html_url,
+                        url: issue.issue.url,
+                        repository_full_name: issue.repository.full_name,
+                        source: Source::Github,
+                    })
+                }
+                IssueActionType::Labeled
+                | IssueActionType::Unlabeled
+                | IssueActionType::Milestoned
+                | IssueActionType::Demilestoned => EventData::IssueMetadata(crate::IssueMetadata {
+                    source_id: issue.issue.id,
+                    labels: issue.issue.label_names(),
+                    milestone: issue.issue.milestone.map(|m| m.title),
+                }),
+                IssueActionType::Closed
+                    if issue.issue.state_reason.as_deref() == Some("duplicate") =>
+                {
+                    EventData::DuplicateClosure(crate::DuplicateClosure {
+                        source_id: issue.issue.id,
+                        repository_full_name: issue.repository.full_name,
+                        number: issue.issue.number,
+                    })
+                }
+                IssueActionType::Closed | IssueActionType::Reopened => {
+                    EventData::IssueStateChange(crate::IssueStateChange {
+                        source_id: issue.issue.id,
+                        closed: matches!(issue.action, IssueActionType::Closed),
+                    })
+                }
+                IssueActionType::Synchronize | IssueActionType::Ignored => {
+                    return Ok(ParsedWebhook::Ignored {
+                        reason: format!("unhandled {webhook_type} action"),
+                    })
                 }
-                IssueActionType::Ignored => (),
             }
         }
         GithubWebhook::IssueComment(comment) => {
             info!("received {} (state: {})", webhook_type, comment.action);
-            state
-                .tx
-                .send(EventData::Comment(crate::CommentData {
+            let mut metadata = comment.issue.event_metadata(&comment.repository);
+            if let Some(user) = &comment.comment.user {
+                metadata.authors.push(&user.login);
+            }
+            let ignorable = !matches!(comment.action, CommentActionType::Deleted);
+            if let Some(reason) = state
+                .ignore_rules
+                .ignore_reason(&metadata)
+                .filter(|_| ignorable)
+            {
+                info!("ignoring {}: {}", webhook_type, reason);
+                return Ok(ParsedWebhook::Ignored {
+                    reason: reason.to_string(),
+                });
+            }
+            let from_maintainer = comment
+                .comment
+                .author_association
+                .as_deref()
+                .is_some_and(is_maintainer);
+            let muted_by = comment
+                .comment
+                .user
+                .as_ref()
+                .filter(|_| matches!(comment.action, CommentActionType::Created) && from_maintainer)
+                .and_then(|user| {
+                    if is_mute_command(&comment.comment.body) {
+                        Some((user.login.clone(), false))
+                    } else if is_unmute_command(&comment.comment.body) {
+                        Some((user.login.clone(), true))
+                    } else {
+                        None
+                    }
+                });
+            // commenters opt themselves out, see [crate::opt_out]
+            let opt_out = comment
+                .comment
+                .user
+                .as_ref()
+                .filter(|_| matches!(comment.action, CommentActionType::Created))
+                .and_then(|user| {
+                    let purge = opt_out_command(&comment.comment.body)?;
+                    Some((user.login.clone(), purge))
+                });
+            if let Some((suppressed_by, lifted)) = muted_by {
+                EventData::IssueSuppression(IssueSuppression {
+                    source_id: comment.issue.id,
+                    suppressed_by,
+                    lifted,
+                })
+            } else if let Some((login, purge)) = opt_out {
+                EventData::AuthorOptOut(AuthorOptOut { login, purge })
+            } else {
+                EventData::Comment(crate::CommentData {
                     source_id: comment.comment.id,
                     issue_id: comment.issue.id,
                     action: comment.action.to_action(),
+                    author: comment.comment.user.map(|u| u.login),
                     body: comment.comment.body,
                     url: comment.comment.url,
-                }))
-                .await?;
+                    is_review: false,
+                })
+            }
         }
-    }
+        GithubWebhook::PullRequestReviewComment(review_comment) => {
+            info!(
+                "received {} (state: {})",
+                webhook_type, review_comment.action
+            );
+            let mut metadata = review_comment
+                .pull_request
+                .issue
+                .event_metadata(&review_comment.repository);
+            if let Some(user) = &review_comment.comment.user {
+                metadata.authors.push(&user.login);
+            }
+            let ignorable = !matches!(review_comment.action, CommentActionType::Deleted);
+            if let Some(reason) = state
+                .ignore_rules
+                .ignore_reason(&metadata)
+                .filter(|_| ignorable)
+            {
+                info!("ignoring {}: {}", webhook_type, reason);
+                return Ok(ParsedWebhook::Ignored {
+                    reason: reason.to_string(),
+                });
+            }
+            EventData::ReviewComment(ReviewCommentData {
+                source_id: review_comment.comment.id,
+                action: review_comment.action.to_action(),
+                repository_full_name: review_comment.repository.full_name,
+                number: review_comment.pull_request.issue.number,
+                author: review_comment.comment.user.map(|u| u.login),
+                body: review_comment.comment.body,
+                url: review_comment.comment.url,
+            })
+        }
+        GithubWebhook::PullRequest(pull_request) => {
+            info!("received {} (state: {})", webhook_type, pull_request.action);
+            let ignorable = matches!(
+                pull_request.action,
+                IssueActionType::Opened | IssueActionType::Edited
+            );
+            if let Some(reason) = state
+                .ignore_rules
+                .ignore_reason(
+                    &pull_request
+                        .pull_request
+                        .issue
+                        .event_metadata(&pull_request.repository),
+                )
+                .filter(|_| ignorable)
+            {
+                info!("ignoring {}: {}", webhook_type, reason);
+                return Ok(ParsedWebhook::Ignored {
+                    reason: reason.to_string(),
+                });
+            }
+            // new commits get their check run too, see [crate::check_runs::CheckRuns]
+            let head_sha = pull_request
+                .pull_request
+                .head
+                .map(|head| head.sha)
+                .filter(|_| {
+                    matches!(
+                        pull_request.action,
+                        IssueActionType::Opened
+                            | IssueActionType::Synchronize
+                            | IssueActionType::Reopened
+                    )
+                });
+            let metadata_only = matches!(
+                pull_request.action,
+                IssueActionType::Labeled
+                    | IssueActionType::Unlabeled
+                    | IssueActionType::Milestoned
+                    | IssueActionType::Demilestoned
+            );
+            // pull requests are stored under their issue id, which the payload lacks
+            let source_id = if metadata_only {
+                state
+                    .db
+                    .issue_source_id(
+                        &pull_request.repository.full_name,
+                        pull_request.pull_request.issue.number,
+                    )
+                    .await?
+            } else {
+                None
+            };
+            if let Some(source_id) = source_id {
+                // spare fetching the pull request again, metadata changes only re-embed it when the
+                // labels are part of the embedding input
+                EventData::IssueMetadata(crate::IssueMetadata {
+                    source_id,
+                    labels: pull_request.pull_request.issue.label_names(),
+                    milestone: pull_request.pull_request.issue.milestone.map(|m| m.title),
+                })
+            } else {
+                match pull_request.action {
+                    // fetched again through the issues API, including pull requests not stored yet
+                    IssueActionType::Opened
+                    | IssueActionType::Edited
+                    | IssueActionType::Synchronize
+                    | IssueActionType::Reopened
+                    | IssueActionType::Labeled
+                    | IssueActionType::Unlabeled
+                    | IssueActionType::Milestoned
+                    | IssueActionType::Demilestoned => EventData::IssueIndexation(IndexIssueData {
+                        issue_number: pull_request.pull_request.issue.number,
+                        repository_full_name: pull_request.repository.full_name,
+                        head_sha,
+                    }),
+                    IssueActionType::Deleted
+                    | IssueActionType::Closed
+                    | IssueActionType::Ignored => {
+                        return Ok(ParsedWebhook::Ignored {
+                            reason: format!("unhandled {webhook_type} action"),
+                        })
+                    }
+                }
+            }
+        }
+    };
+    Ok(ParsedWebhook::Event { event })
+}
+
+/// Repository every GitHub webhook we subscribe to is about
+#[derive(Deserialize)]
+struct WebhookRepository {
+    repository: Repository,
+}
 
+pub async fn github_webhook(
+    State(state): State<AppState>,
+    Extension(request_id): Extension<RequestId>,
+    req: Request<Body>,
+) -> anyhow::Result<(), ApiError> {
+    let (event, body_bytes, delivery) = verified_github_delivery(&state, req).await?;
+    state
+        .webhook_mirror
+        .mirror(event.clone(), delivery, body_bytes.clone());
+    if let ParsedWebhook::Event { event } =
+        parse_github_webhook(&state, &event, &body_bytes).await?
+    {
+        // the repository's cursor only moves past handled events, see [crate::catch_up::CatchUp]
+        let cursor_update = serde_json::from_slice::<WebhookRepository>(&body_bytes)
+            .ok()
+            .and_then(|payload| state.catch_up.cursor_update(&payload.repository.full_name));
+        state
+            .tx
+            .send(QueuedEvent::new(event, &request_id).with_cursor_update(cursor_update))
+            .await?;
+    }
     Ok(())
 }
 
+/// Validates a GitHub webhook, returning what it would have enqueued without enqueuing it
+pub async fn github_webhook_dry_run(
+    State(state): State<AppState>,
+    req: Request<Body>,
+) -> Result<Json<ParsedWebhook>, ApiError> {
+    let (event, body_bytes) = verified_github_body(&state, req).await?;
+    Ok(Json(
+        parse_github_webhook(&state, &event, &body_bytes).await?,
+    ))
+}
+
 const X_WEBHOOK_SECRET: HeaderName = HeaderName::from_static("x-webhook-secret");
 
 pub struct HfWebhookSecretValidator;
@@ -237,6 +747,8 @@ enum HfAction {
     Create,
     Update,
     Delete,
+    /// only sent for the `repo` scope
+    Move,
 }
 
 impl HfAction {
@@ -245,6 +757,7 @@ impl HfAction {
             Self::Create => Action::Created,
             Self::Update => Action::Edited,
             Self::Delete => Action::Deleted,
+            Self::Move => unreachable!("HfAction::to_action called with {self}"),
         }
     }
 }
@@ -255,6 +768,7 @@ impl Display for HfAction {
             Self::Create => "create",
             Self::Update => "update",
             Self::Delete => "delete",
+            Self::Move => "move",
         };
         write!(f, "{}", action)
     }
@@ -266,6 +780,13 @@ enum Scope {
     Discussion,
     #[serde(rename = "discussion.comment")]
     DiscussionComment,
+    #[serde(rename = "repo")]
+    Repo,
+    #[serde(rename = "repo.config")]
+    RepoConfig,
+    /// e.g. `repo.content`, sent along with the `repo` scope ones
+    #[serde(other)]
+    Ignored,
 }
 
 impl Display for Scope {
@@ -273,6 +794,9 @@ impl Display for Scope {
         let scope = match self {
             Self::Discussion => "discussion",
             Self::DiscussionComment => "discussion.comment",
+            Self::Repo => "repo",
+            Self::RepoConfig => "repo.config",
+            Self::Ignored => "ignored scope",
         };
         write!(f, "{}", scope)
     }
@@ -298,6 +822,8 @@ struct Discussion {
     num: i32,
     title: String,
     url: Url,
+    #[serde(default)]
+    author: Option<Author>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -321,21 +847,85 @@ struct HfComment {
 }
 
 #[derive(Debug, Deserialize)]
+struct HfRepo {
+    name: String,
+    #[serde(default)]
+    private: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MovedTo {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct HuggingfaceWebhook {
     event: Event,
     discussion: Option<Discussion>,
     comment: Option<HfComment>,
+    repo: Option<HfRepo>,
+    /// set for `move` actions
+    #[serde(default)]
+    moved_to: Option<MovedTo>,
 }
 
-pub async fn huggingface_webhook(
-    HfWebhookSecretValidator: HfWebhookSecretValidator,
-    State(state): State<AppState>,
-    Json(webhook): Json<HuggingfaceWebhook>,
-) -> Result<(), ApiError> {
+/// Remaps the stored discussions of moved repositories and flags those of private ones
+fn parse_huggingface_repo_webhook(webhook: HuggingfaceWebhook) -> Result<ParsedWebhook, ApiError> {
+    let Some(repo) = webhook.repo else {
+        return Err(ApiError::MalformedWebhook(format!(
+            r#"Missing repo when event.scope = "{}""#,
+            webhook.event.scope
+        )));
+    };
+    let moved_to = match webhook.event.action {
+        HfAction::Move => match webhook.moved_to {
+            Some(moved_to) => Some(moved_to.name),
+            None => {
+                return Err(ApiError::MalformedWebhook(
+                    r#"Missing movedTo when event.action = "move""#.to_owned(),
+                ))
+            }
+        },
+        HfAction::Update => None,
+        HfAction::Create | HfAction::Delete => {
+            return Ok(ParsedWebhook::Ignored {
+                reason: format!("unhandled repo {} action", webhook.event.action),
+            })
+        }
+    };
+    Ok(ParsedWebhook::Event {
+        event: EventData::RepositoryUpdate(crate::RepositoryUpdate {
+            full_name: repo.name,
+            moved_to,
+            private: repo.private,
+        }),
+    })
+}
+
+fn parse_huggingface_webhook(
+    state: &AppState,
+    webhook: HuggingfaceWebhook,
+) -> Result<ParsedWebhook, ApiError> {
     info!(
         "received {} (status: {})",
         webhook.event.scope, webhook.event.action
     );
+    match webhook.event.scope {
+        Scope::Repo | Scope::RepoConfig => return parse_huggingface_repo_webhook(webhook),
+        Scope::Ignored => {
+            return Ok(ParsedWebhook::Ignored {
+                reason: "unhandled webhook scope".to_owned(),
+            })
+        }
+        Scope::Discussion | Scope::DiscussionComment => {}
+    }
+    if let HfAction::Move = webhook.event.action {
+        return Err(ApiError::MalformedWebhook(format!(
+            r#"Unexpected event.action = "move" when event.scope = "{}""#,
+            webhook.event.scope
+        )));
+    }
 
     let discussion = match webhook.discussion {
         Some(discussion) => discussion,
@@ -346,27 +936,49 @@ pub async fn huggingface_webhook(
             )))
         }
     };
-    match webhook.event.scope {
+    let repository_full_name = webhook.repo.map(|r| r.name).unwrap_or_default();
+    let mut metadata = EventMetadata {
+        authors: Vec::new(),
+        labels: Vec::new(),
+        repository: &repository_full_name,
+        title: &discussion.title,
+    };
+    if let Some(comment) = &webhook.comment {
+        metadata.authors.push(&comment.author.id);
+    }
+    let ignorable = matches!(webhook.event.action, HfAction::Create | HfAction::Update);
+    if let Some(reason) = state
+        .ignore_rules
+        .ignore_reason(&metadata)
+        .filter(|_| ignorable)
+    {
+        info!("ignoring {}: {}", webhook.event.scope, reason);
+        return Ok(ParsedWebhook::Ignored {
+            reason: reason.to_string(),
+        });
+    }
+    let event = match webhook.event.scope {
         Scope::Discussion => {
-            let comment_content = match webhook.comment {
-                Some(comment) => comment.content,
-                None => String::new(),
+            let (comment_content, comment_author) = match webhook.comment {
+                Some(comment) => (comment.content, Some(comment.author.id)),
+                None => (String::new(), None),
             };
-            state
-                .tx
-                .send(EventData::Issue(crate::IssueData {
-                    source_id: discussion.id,
-                    action: webhook.event.action.to_action(),
-                    title: discussion.title,
-                    body: comment_content,
-                    is_pull_request: discussion.is_pull_request,
-                    number: discussion.num,
-                    html_url: discussion.url.web,
-                    url: discussion.url.api,
-                    repository_full_name: String::new(), // TODO: extract repository full name from discussion url
-                    source: Source::HuggingFace,
-                }))
-                .await?;
+            EventData::Issue(crate::IssueData {
+                source_id: discussion.id,
+                action: webhook.event.action.to_action(),
+                // Hugging Face authors are identified by their Hub user id
+                author: discussion.author.map(|a| a.id).or(comment_author),
+                labels: Vec::new(),
+                milestone: None,
+                title: discussion.title,
+                body: comment_content,
+                is_pull_request: discussion.is_pull_request,
+                number: discussion.num,
+                html_url: discussion.url.web,
+                url: discussion.url.api,
+                repository_full_name,
+                source: Source::HuggingFace,
+            })
         }
         Scope::DiscussionComment => {
             let comment = match webhook.comment {
@@ -378,35 +990,79 @@ pub async fn huggingface_webhook(
                     )))
                 }
             };
-            // NOTE: check if comment is from `lor-e-bot`
-            if comment.author.id != "67e0825265e294ad98833748" {
-                state
-                    .tx
-                    .send(EventData::Comment(crate::CommentData {
-                        source_id: comment.id,
-                        action: webhook.event.action.to_action(),
-                        body: comment.content,
-                        issue_id: discussion.id,
-                        url: comment.url.web,
-                    }))
-                    .await?;
+            if comment.author.id == BOT_USER_ID {
+                return Ok(ParsedWebhook::Ignored {
+                    reason: "comment posted by the bot".to_owned(),
+                });
             }
+            // commenters opt themselves out, see [crate::opt_out]
+            if let (HfAction::Create, Some(purge)) =
+                (&webhook.event.action, opt_out_command(&comment.content))
+            {
+                return Ok(ParsedWebhook::Event {
+                    event: EventData::AuthorOptOut(AuthorOptOut {
+                        login: comment.author.id,
+                        purge,
+                    }),
+                });
+            }
+            EventData::Comment(crate::CommentData {
+                source_id: comment.id,
+                action: webhook.event.action.to_action(),
+                // Hugging Face authors are identified by their Hub user id
+                author: Some(comment.author.id),
+                body: comment.content,
+                issue_id: discussion.id,
+                url: comment.url.web,
+                is_review: false,
+            })
+        }
+        Scope::Repo | Scope::RepoConfig | Scope::Ignored => {
+            unreachable!("{} webhooks are handled above", webhook.event.scope)
         }
+    };
+    Ok(ParsedWebhook::Event { event })
+}
+
+pub async fn huggingface_webhook(
+    HfWebhookSecretValidator: HfWebhookSecretValidator,
+    State(state): State<AppState>,
+    Extension(request_id): Extension<RequestId>,
+    body: Bytes,
+) -> Result<(), ApiError> {
+    let webhook: HuggingfaceWebhook =
+        serde_json::from_slice(&body).map_err(|err| ApiError::MalformedWebhook(err.to_string()))?;
+    state.webhook_mirror.mirror_huggingface(body);
+    if let ParsedWebhook::Event { event } = parse_huggingface_webhook(&state, webhook)? {
+        state.tx.send(QueuedEvent::new(event, &request_id)).await?;
     }
     Ok(())
 }
 
+/// Validates a Hugging Face webhook, returning what it would have enqueued without enqueuing it
+pub async fn huggingface_webhook_dry_run(
+    HfWebhookSecretValidator: HfWebhookSecretValidator,
+    State(state): State<AppState>,
+    Json(webhook): Json<HuggingfaceWebhook>,
+) -> Result<Json<ParsedWebhook>, ApiError> {
+    Ok(Json(parse_huggingface_webhook(&state, webhook)?))
+}
+
 pub fn event_router() -> Router<AppState> {
     Router::new()
         .route("/github", post(github_webhook))
+        .route("/github/dry-run", post(github_webhook_dry_run))
         .route("/huggingface", post(huggingface_webhook))
+        .route("/huggingface/dry-run", post(huggingface_webhook_dry_run))
 }
 
-pub struct SecretValidator;
+/// Requires an API key granting the `R` scope
+pub struct SecretValidator<R: RequiredScope>(PhantomData<R>);
 
-impl<S> FromRequestParts<S> for SecretValidator
+impl<R, S> FromRequestParts<S> for SecretValidator<R>
 where
     AppState: FromRef<S>,
+    R: RequiredScope,
     S: Send + Sync,
 {
     type Rejection = ApiError;
@@ -419,108 +1075,1169 @@ where
             .cloned()
             .ok_or(ApiError::Auth)?;
 
-        if secret != state.auth_token {
+        if !state.api_keys.authorize(secret.to_str()?, R::SCOPE).await? {
             return Err(ApiError::Auth);
         }
 
-        Ok(Self)
+        Ok(Self(PhantomData))
+    }
+}
+
+/// Caller of the indexing routes: an API key granting the `index` scope, or a GitHub Actions
+/// workflow with an OIDC token, see [crate::github_oidc]
+pub enum IndexCaller {
+    ApiKey,
+    Workflow(ActionsClaims),
+}
+
+impl<S> FromRequestParts<S> for IndexCaller
+where
+    AppState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let app_state = AppState::from_ref(state);
+        let token = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        match token {
+            Some(token) if app_state.github_oidc.enabled() => {
+                let claims = app_state.github_oidc.verify(token).await.map_err(|err| {
+                    warn!(err = err.to_string(), "rejected github oidc token");
+                    ApiError::Auth
+                })?;
+                Ok(Self::Workflow(claims))
+            }
+            _ => {
+                SecretValidator::<IndexScope>::from_request_parts(parts, state).await?;
+                Ok(Self::ApiKey)
+            }
+        }
     }
 }
 
 // TODO: reply id and endpoint to query progress?
 pub async fn index_repository(
-    SecretValidator: SecretValidator,
+    caller: IndexCaller,
     State(state): State<AppState>,
+    Extension(request_id): Extension<RequestId>,
     Json(repo_data): Json<RepositoryData>,
 ) -> Result<(), ApiError> {
+    if let IndexCaller::Workflow(claims) = &caller {
+        if !matches!(repo_data.source, Source::Github)
+            || !state.github_oidc.may_index(claims, &repo_data.full_name)
+        {
+            return Err(ApiError::Auth);
+        }
+        info!(
+            repository_full_name = repo_data.full_name,
+            actor = claims.actor,
+            workflow = claims.workflow,
+            "indexation requested by a workflow"
+        );
+    }
+    if state
+        .locks
+        .is_held(&locks::repository_indexation(&repo_data.full_name))
+        .await?
+    {
+        return Err(ApiError::IndexationInProgress(repo_data.full_name));
+    }
+    if state.settings.maintenance().await? {
+        return Err(ApiError::Maintenance);
+    }
     state
         .tx
-        .send(EventData::RepositoryIndexation(repo_data))
+        .send(QueuedEvent::new(
+            EventData::RepositoryIndexation(repo_data),
+            &request_id,
+        ))
         .await?;
     Ok(())
 }
 
 pub async fn index_issue(
-    SecretValidator: SecretValidator,
+    _: SecretValidator<IndexScope>,
     State(state): State<AppState>,
+    Extension(request_id): Extension<RequestId>,
     Json(index_issue_data): Json<IndexIssueData>,
 ) -> Result<(), ApiError> {
     state
         .tx
-        .send(EventData::IssueIndexation(index_issue_data))
+        .send(QueuedEvent::new(
+            EventData::IssueIndexation(index_issue_data),
+            &request_id,
+        ))
         .await?;
     Ok(())
 }
 
 pub async fn regenerate_embeddings(
-    SecretValidator: SecretValidator,
+    caller: IndexCaller,
     State(state): State<AppState>,
+    Extension(request_id): Extension<RequestId>,
 ) -> Result<(), ApiError> {
-    state.tx.send(EventData::RegenerateEmbeddings).await?;
+    if let IndexCaller::Workflow(claims) = &caller {
+        if !state.github_oidc.may_regenerate(claims) {
+            return Err(ApiError::Auth);
+        }
+        info!(
+            repository_full_name = claims.repository,
+            actor = claims.actor,
+            workflow = claims.workflow,
+            "embeddings regeneration requested by a workflow"
+        );
+    }
+    if state.locks.is_held(locks::EMBEDDINGS_REGENERATION).await? {
+        return Err(ApiError::RegenerationInProgress);
+    }
+    if state.settings.maintenance().await? {
+        return Err(ApiError::Maintenance);
+    }
+    state
+        .tx
+        .send(QueuedEvent::new(
+            EventData::RegenerateEmbeddings,
+            &request_id,
+        ))
+        .await?;
     Ok(())
 }
 
-pub async fn health() -> impl IntoResponse {
-    if !PRE_SHUTDOWN.load(Ordering::SeqCst) {
-        StatusCode::OK
-    } else {
-        StatusCode::SERVICE_UNAVAILABLE
-    }
+#[derive(Deserialize)]
+pub struct SuppressIssue {
+    source_id: i64,
 }
 
-#[cfg(test)]
-mod tests {
-    use std::borrow::BorrowMut;
+#[derive(Serialize)]
+pub struct SuppressedIssue {
+    /// false when the issue already was
+    newly_suppressed: bool,
+}
 
-    use axum::{
-        body::Body,
-        http::{header::CONTENT_TYPE, Request, StatusCode},
-    };
-    use tokio::sync::mpsc;
-    use tower::ServiceExt;
+/// stops the bot from commenting on the issue or sending it to Slack, while still indexing it
+pub async fn suppress_issue(
+    _: SecretValidator<AdminScope>,
+    State(state): State<AppState>,
+    Json(issue): Json<SuppressIssue>,
+) -> Result<Json<SuppressedIssue>, ApiError> {
+    let newly_suppressed = state.db.suppress_issue(issue.source_id, "api").await?;
+    info!(
+        issue_id = issue.source_id,
+        newly_suppressed, "suppressed issue"
+    );
+    Ok(Json(SuppressedIssue { newly_suppressed }))
+}
 
-    use crate::{
-        app,
-        config::{load_config, IssueBotConfig},
-        AppState,
-    };
+#[derive(Serialize)]
+pub struct UnsuppressedIssue {
+    /// false when the issue wasn't suppressed
+    was_suppressed: bool,
+}
 
-    #[tokio::test]
-    async fn test_github_webhook_handler() {
-        let config: IssueBotConfig = load_config("ISSUE_BOT_TEST").unwrap();
-        let (tx, _rx) = mpsc::channel(8);
-        let state = AppState {
-            auth_token: config.auth_token.clone(),
-            tx,
-        };
-        let mut app = app(state);
+/// lets the bot comment on the issue again, see [suppress_issue]
+pub async fn unsuppress_issue(
+    _: SecretValidator<AdminScope>,
+    State(state): State<AppState>,
+    Json(issue): Json<SuppressIssue>,
+) -> Result<Json<UnsuppressedIssue>, ApiError> {
+    let was_suppressed = state.db.unsuppress_issue(issue.source_id).await?;
+    info!(
+        issue_id = issue.source_id,
+        was_suppressed, "unsuppressed issue"
+    );
+    Ok(Json(UnsuppressedIssue { was_suppressed }))
+}
 
-        let payload_body = r#"{"action":"opened","issue":{"title":"my great contribution to the world","body":"superb work, isnt it","id":4321,"number":5,"html_url":"https://github.com/huggingface/lor-e/5", "url":"https://github.com/api/huggingface/lor-e/5"}, "repository":{"full_name":"huggingface/lor-e"}}"#;
-        let sig = "sha256=8e288dccf7b2744c5f3f30ab1e82672f16c0cb0f809d384df85cac2421e153af";
+#[derive(Deserialize)]
+pub struct OptOutAuthor {
+    /// GitHub login, or Hub user id of a Hugging Face author
+    login: String,
+    /// deletes the stored issues and comments of the author instead of flagging them
+    #[serde(default)]
+    purge: bool,
+}
 
-        let response = app
-            .borrow_mut()
-            .oneshot(
-                Request::builder()
-                    .method(axum::http::Method::POST)
-                    .uri("/event/github")
-                    .header("x-hub-signature-256", sig)
-                    .body(Body::from(payload_body))
-                    .unwrap(),
-            )
-            .await
-            .unwrap();
+/// leaves the issues and comments of the author out of suggestions and exports, see [crate::opt_out]
+pub async fn opt_out_author(
+    _: SecretValidator<AdminScope>,
+    State(state): State<AppState>,
+    Extension(request_id): Extension<RequestId>,
+    Json(author): Json<OptOutAuthor>,
+) -> Result<Json<OptOutRequest>, ApiError> {
+    let commented = state.db.issues_commented_by(&author.login).await?;
+    let request = state
+        .db
+        .opt_out_author(&author.login, "api", author.purge)
+        .await?;
+    // the author's issues may be cached
+    state.hot_issues.clear().await;
+    info!(
+        login = author.login,
+        purged = request.purged,
+        issues = request.issues,
+        comments = request.comments,
+        "opted out author"
+    );
+    if !commented.is_empty() {
+        // the author's comments are part of these issues' embeddings
+        state
+            .tx
+            .send(QueuedEvent::new(
+                EventData::IssueEmbeddings(IssueEmbeddings {
+                    source_ids: commented,
+                }),
+                &request_id,
+            ))
+            .await?;
+    }
+    Ok(Json(request))
+}
 
-        assert_eq!(response.status(), StatusCode::OK);
+/// audit trail of the opt-out requests, most recent first
+pub async fn opt_out_requests(
+    _: SecretValidator<AdminScope>,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<OptOutRequest>>, ApiError> {
+    Ok(Json(state.db.opt_out_requests().await?))
+}
 
-        let payload_body = r#"{"action":"created","comment":{"body":"test review","id":1234,"url":"https://github.com/huggingface/lor-e/5#comment-123"},"issue":{"title":"my great contribution to the world","body":"superb work, isnt it","id":4321,"number":5,"html_url":"https://github.com/huggingface/lor-e/5", "url":"https://github.com/api/huggingface/lor-e/5"}, "repository":{"full_name":"huggingface/lor-e"}}"#;
-        let sig = "sha256=017815fdb6eda66aa8f62123844001fa64e1b2c137808a0ac68f60091ca36f56";
+#[derive(Deserialize)]
+pub struct BotCommentQuery {
+    issue_url: String,
+}
 
-        let response = app
-            .oneshot(
-                Request::builder()
-                    .method(axum::http::Method::POST)
-                    .uri("/event/github")
+/// every body the bot's comment on an issue had, oldest first, see
+/// [crate::config::CommentQueueConfig::update_in_place]
+pub async fn bot_comment_revisions(
+    _: SecretValidator<AdminScope>,
+    State(state): State<AppState>,
+    Query(query): Query<BotCommentQuery>,
+) -> Result<Json<Vec<BotCommentRevision>>, ApiError> {
+    Ok(Json(
+        state.db.bot_comment_revisions(&query.issue_url).await?,
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct CheckWebhooks {
+    repository_full_name: String,
+    /// creates the webhook, or fixes it, when it has problems
+    #[serde(default)]
+    fix: bool,
+}
+
+/// checks that the repository's webhook sends events to the bot, see [crate::webhooks]
+pub async fn check_webhooks(
+    _: SecretValidator<AdminScope>,
+    State(state): State<AppState>,
+    Json(check): Json<CheckWebhooks>,
+) -> Result<Json<WebhookReport>, ApiError> {
+    let report = state
+        .webhooks
+        .check(&check.repository_full_name, check.fix)
+        .await?;
+    Ok(Json(report))
+}
+
+#[derive(Deserialize)]
+pub struct OnboardRepository {
+    repository_full_name: String,
+}
+
+/// sets up a GitHub repository and indexes its issues in the background, see [crate::onboarding]
+pub async fn onboard_repository(
+    _: SecretValidator<AdminScope>,
+    State(state): State<AppState>,
+    Extension(request_id): Extension<RequestId>,
+    Json(repository): Json<OnboardRepository>,
+) -> Result<Json<OnboardingReport>, ApiError> {
+    if state
+        .locks
+        .is_held(&locks::repository_indexation(
+            &repository.repository_full_name,
+        ))
+        .await?
+    {
+        return Err(ApiError::IndexationInProgress(
+            repository.repository_full_name,
+        ));
+    }
+    let report = state
+        .onboarding
+        .onboard(&repository.repository_full_name, &request_id)
+        .await?;
+    Ok(Json(report))
+}
+
+/// most recently onboarded first
+pub async fn onboarded_repositories(
+    _: SecretValidator<AdminScope>,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<OnboardedRepository>>, ApiError> {
+    Ok(Json(state.db.onboarded_repositories().await?))
+}
+
+/// replays the GitHub issues and comments updated since the last webhook of each repository, in
+/// the background
+pub async fn catch_up(
+    _: SecretValidator<IndexScope>,
+    State(state): State<AppState>,
+) -> Result<(), ApiError> {
+    if state.locks.is_held(locks::CATCH_UP).await? {
+        return Err(ApiError::CatchUpInProgress);
+    }
+    tokio::spawn(async move { state.catch_up.run().await });
+    Ok(())
+}
+
+/// drift of the linked issues similarities from the baseline embedding model, as last sampled
+pub async fn embedding_drift(
+    _: SecretValidator<AdminScope>,
+    State(state): State<AppState>,
+) -> Result<Json<DriftReport>, ApiError> {
+    Ok(Json(state.drift.report().await?))
+}
+
+/// samples the linked issues similarities under the current embeddings, e.g. after issues were
+/// indexed with a new model without regenerating all embeddings
+pub async fn sample_embedding_drift(
+    _: SecretValidator<AdminScope>,
+    State(state): State<AppState>,
+) -> Result<Json<DriftReport>, ApiError> {
+    Ok(Json(state.drift.sample_current().await?))
+}
+
+fn validate_run_id(run_id: &str) -> Result<(), ApiError> {
+    let valid = !run_id.is_empty()
+        && run_id.len() <= 64
+        && run_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(ApiError::BadRequest(format!(
+            "invalid feedback run id: '{run_id}'"
+        )))
+    }
+}
+
+/// Target of the feedback links in the bot's comments, hence unauthenticated
+///
+/// Only asks to confirm the vote, link previews and crawlers following the links must not
+/// cast any.
+pub async fn feedback_form(Query(feedback): Query<FeedbackData>) -> Result<Html<String>, ApiError> {
+    validate_run_id(&feedback.run_id)?;
+    // the run id was validated and the vote is either `up` or `down`, neither needs escaping
+    Ok(Html(format!(
+        r#"<!doctype html>
+<html><head><meta charset="utf-8"><title>lor-e feedback</title></head>
+<body><form method="post">
+<input type="hidden" name="run_id" value="{}">
+<input type="hidden" name="vote" value="{}">
+<button type="submit">{}</button>
+</form></body></html>"#,
+        feedback.run_id,
+        feedback.vote,
+        match feedback.vote {
+            Vote::Up => "👍 These suggestions were helpful",
+            Vote::Down => "👎 These suggestions were not helpful",
+        }
+    )))
+}
+
+/// Records a vote confirmed through [feedback_form], for runs of comments the bot posted
+pub async fn feedback(
+    State(state): State<AppState>,
+    Extension(request_id): Extension<RequestId>,
+    Form(feedback): Form<FeedbackData>,
+) -> Result<&'static str, ApiError> {
+    validate_run_id(&feedback.run_id)?;
+    if !state.db.comment_run_exists(&feedback.run_id).await? {
+        return Err(ApiError::NotFound);
+    }
+    state
+        .tx
+        .send(QueuedEvent::new(EventData::Feedback(feedback), &request_id))
+        .await?;
+    Ok("Thanks for your feedback!")
+}
+
+#[derive(Deserialize)]
+struct SlackInteraction {
+    payload: String,
+}
+
+/// Slack's interactivity request url, approving or discarding comment drafts
+///
+/// Mounted outside of the JSON-only routes, Slack sending form encoded requests.
+pub async fn slack_interaction(
+    State(state): State<AppState>,
+    req: Request<Body>,
+) -> Result<(), ApiError> {
+    // read before the body is awaited, the borrowed request isn't `Sync`
+    let (timestamp, signature) = {
+        let header = |name: &str| {
+            req.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_owned()
+        };
+        (
+            header("x-slack-request-timestamp"),
+            header("x-slack-signature"),
+        )
+    };
+    let body_bytes = Bytes::from_request(req, &())
+        .await
+        .map_err(|rejection| ApiError::BadRequest(rejection.body_text()))?;
+    if !state
+        .slack
+        .verify_signature(&timestamp, &body_bytes, &signature)
+    {
+        return Err(ApiError::SignatureMismatch);
+    }
+    let form_req = axum::http::Request::builder()
+        .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from(body_bytes))
+        .map_err(|err| ApiError::BadRequest(err.to_string()))?;
+    let Form(interaction) = Form::<SlackInteraction>::from_request(form_req, &())
+        .await
+        .map_err(|rejection| ApiError::BadRequest(rejection.body_text()))?;
+    let Some(action) = DraftAction::from_payload(&interaction.payload)
+        .map_err(|err| ApiError::BadRequest(err.to_string()))?
+    else {
+        return Ok(());
+    };
+
+    let (pending, text) = match action.decision {
+        DraftDecision::Approve => (
+            state.comment_queue.approve_draft(action.comment_id).await?,
+            format!(
+                "✅ Approved by {}, the comment will be posted shortly",
+                action.user
+            ),
+        ),
+        DraftDecision::Discard => (
+            state.comment_queue.discard_draft(action.comment_id).await?,
+            format!("🗑️ Discarded by {}", action.user),
+        ),
+    };
+    info!(
+        comment_id = action.comment_id,
+        decision = ?action.decision,
+        user = action.user,
+        "comment draft reviewed"
+    );
+    let text = if pending {
+        text
+    } else {
+        "This draft was already reviewed or has expired".to_owned()
+    };
+    if let Err(err) = state.slack.respond(&action.response_url, text).await {
+        error!(
+            comment_id = action.comment_id,
+            err = err.to_string(),
+            "failed to update comment draft message"
+        );
+    }
+    Ok(())
+}
+
+pub async fn search_issues(
+    _: SecretValidator<SearchScope>,
+    State(state): State<AppState>,
+    Json(req): Json<SearchRequest>,
+) -> Result<Json<SearchPage>, ApiError> {
+    if req.q.trim().is_empty() {
+        return Err(ApiError::BadRequest("empty search query".to_owned()));
+    }
+    Ok(Json(state.search.search(req).await?))
+}
+
+/// Issues compared by [compare_issues], by source id or as `huggingface/transformers#123`
+#[derive(Deserialize)]
+pub struct CompareQuery {
+    issue_a: String,
+    issue_b: String,
+}
+
+/// similarity of two issues, overall and between each of their description and comments, to
+/// understand why they were or weren't suggested for one another
+pub async fn compare_issues(
+    _: SecretValidator<SearchScope>,
+    State(state): State<AppState>,
+    Query(query): Query<CompareQuery>,
+) -> Result<Json<IssueComparison>, ApiError> {
+    let issue_a: IssueReference = query.issue_a.parse()?;
+    let issue_b: IssueReference = query.issue_b.parse()?;
+    Ok(Json(state.comparer.compare(&issue_a, &issue_b).await?))
+}
+
+/// Settings of `repository`, or the global ones when it isn't set
+#[derive(Deserialize)]
+pub struct SettingsScope {
+    repository: Option<String>,
+}
+
+pub async fn similarity_settings(
+    _: SecretValidator<AdminScope>,
+    State(state): State<AppState>,
+    Query(scope): Query<SettingsScope>,
+) -> Result<Json<SimilaritySettings>, ApiError> {
+    let settings = state
+        .settings
+        .similarity(scope.repository.as_deref())
+        .await?;
+    Ok(Json(settings))
+}
+
+pub async fn update_similarity_settings(
+    _: SecretValidator<AdminScope>,
+    State(state): State<AppState>,
+    Query(scope): Query<SettingsScope>,
+    Json(settings): Json<SimilaritySettings>,
+) -> Result<Json<SimilaritySettings>, ApiError> {
+    if settings.max_suggestions < 1 || !(-1. ..=1.).contains(&settings.min_similarity) {
+        return Err(ApiError::BadRequest(format!(
+            "invalid similarity settings: {settings:?}"
+        )));
+    }
+    state
+        .settings
+        .set_similarity(scope.repository.as_deref(), settings)
+        .await?;
+    info!(
+        repository = scope.repository,
+        "updated similarity settings: {settings:?}"
+    );
+    Ok(Json(settings))
+}
+
+pub async fn list_jobs(
+    _: SecretValidator<AdminScope>,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<JobState>>, ApiError> {
+    Ok(Json(state.db.jobs().await?))
+}
+
+/// stops the job at its next checkpoint, until resumed
+pub async fn pause_job(
+    _: SecretValidator<AdminScope>,
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<Json<JobState>, ApiError> {
+    let job = state
+        .db
+        .set_job_paused(id, true)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    info!(id, job_type = job.job_type, "paused job");
+    Ok(Json(job))
+}
+
+/// restarts the job from its last checkpoint, unless maintenance mode is on
+pub async fn resume_job(
+    _: SecretValidator<AdminScope>,
+    State(state): State<AppState>,
+    Extension(request_id): Extension<RequestId>,
+    Path(id): Path<i32>,
+) -> Result<Json<JobState>, ApiError> {
+    let job = state
+        .db
+        .set_job_paused(id, false)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    info!(id, job_type = job.job_type, "resumed job");
+    if !state.settings.maintenance().await? {
+        state
+            .tx
+            .send(QueuedEvent::new(resume_event(&job), &request_id))
+            .await?;
+    }
+    Ok(Json(job))
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct Maintenance {
+    enabled: bool,
+}
+
+pub async fn maintenance(
+    _: SecretValidator<AdminScope>,
+    State(state): State<AppState>,
+) -> Result<Json<Maintenance>, ApiError> {
+    Ok(Json(Maintenance {
+        enabled: state.settings.maintenance().await?,
+    }))
+}
+
+/// holds background jobs at their next checkpoint, the jobs that aren't paused resuming once
+/// maintenance mode is turned off
+pub async fn update_maintenance(
+    _: SecretValidator<AdminScope>,
+    State(state): State<AppState>,
+    Extension(request_id): Extension<RequestId>,
+    Json(maintenance): Json<Maintenance>,
+) -> Result<Json<Maintenance>, ApiError> {
+    state.settings.set_maintenance(maintenance.enabled).await?;
+    info!(enabled = maintenance.enabled, "updated maintenance mode");
+    if !maintenance.enabled {
+        for job in state.db.jobs().await? {
+            if !job.paused {
+                state
+                    .tx
+                    .send(QueuedEvent::new(resume_event(&job), &request_id))
+                    .await?;
+            }
+        }
+    }
+    Ok(Json(maintenance))
+}
+
+pub async fn debug_state(
+    _: SecretValidator<AdminScope>,
+    State(state): State<AppState>,
+) -> Json<DebugStateSnapshot> {
+    let mut snapshot = state.debug_state.snapshot();
+    snapshot.channel_capacity = state.tx.max_capacity();
+    snapshot.channel_depth = state.tx.max_capacity() - state.tx.capacity();
+    Json(snapshot)
+}
+
+fn default_event_log_limit() -> i64 {
+    100
+}
+
+/// Event log lookup, e.g. `?repository=huggingface/transformers&issue_number=123` to find out
+/// why an issue did not get a comment
+#[derive(Deserialize)]
+pub struct EventLogQuery {
+    repository: Option<String>,
+    issue_number: Option<i32>,
+    source_id: Option<i64>,
+    request_id: Option<String>,
+    #[serde(default = "default_event_log_limit")]
+    limit: i64,
+}
+
+pub async fn event_log(
+    _: SecretValidator<AdminScope>,
+    State(state): State<AppState>,
+    Query(query): Query<EventLogQuery>,
+) -> Result<Json<Vec<EventLogEntry>>, ApiError> {
+    let filter = EventLogFilter {
+        repository_full_name: query.repository,
+        issue_number: query.issue_number,
+        source_id: query.source_id,
+        request_id: query.request_id,
+    };
+    let entries = state
+        .event_log
+        .entries(&filter, query.limit.clamp(1, 1_000))
+        .await?;
+    Ok(Json(entries))
+}
+
+/// how the stored embedding of an issue was generated
+pub async fn embedding_metadata(
+    _: SecretValidator<AdminScope>,
+    State(state): State<AppState>,
+    Path(source_id): Path<i64>,
+) -> Result<Json<EmbeddingRecord>, ApiError> {
+    match state.db.embedding_metadata(source_id).await? {
+        Some(record) => Ok(Json(record)),
+        None => Err(ApiError::NotFound),
+    }
+}
+
+pub async fn events_stream(
+    _: SecretValidator<AdminScope>,
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<sse::Event, Infallible>>> {
+    Sse::new(state.events.subscribe()).keep_alive(KeepAlive::default())
+}
+
+/// issues read from the database at a time while exporting
+const EXPORT_PAGE_SIZE: i64 = 500;
+
+/// issues as lines of JSON, read from the database page by page as the response is sent
+fn export_lines(db: Database) -> impl Stream<Item = Result<Vec<u8>, StorageError>> {
+    try_stream! {
+        let mut after_id = 0;
+        loop {
+            let issues = db.export_issues(after_id, EXPORT_PAGE_SIZE).await?;
+            let Some(last) = issues.last() else {
+                break;
+            };
+            after_id = last.id;
+            let mut lines = Vec::new();
+            for issue in &issues {
+                serde_json::to_writer(&mut lines, issue)?;
+                lines.push(b'\n');
+            }
+            yield lines;
+        }
+    }
+}
+
+/// every issue that can be suggested, as newline delimited JSON
+pub async fn export_issues(
+    _: SecretValidator<ExportScope>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    (
+        [(CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(export_lines(state.db)),
+    )
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewApiKey {
+    name: String,
+    scopes: Vec<api_keys::Scope>,
+    #[serde(default)]
+    expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct CreatedApiKey {
+    id: i32,
+    /// only ever returned here
+    key: String,
+}
+
+pub async fn create_api_key(
+    _: SecretValidator<AdminScope>,
+    State(state): State<AppState>,
+    Json(new_key): Json<NewApiKey>,
+) -> Result<Json<CreatedApiKey>, ApiError> {
+    if new_key.scopes.is_empty() {
+        return Err(ApiError::BadRequest(format!(
+            "api key '{}' has no scopes",
+            new_key.name
+        )));
+    }
+    let (id, key) = state
+        .api_keys
+        .create(&new_key.name, &new_key.scopes, new_key.expires_at)
+        .await?;
+    info!(id, name = new_key.name, "created api key");
+    Ok(Json(CreatedApiKey { id, key }))
+}
+
+pub async fn list_api_keys(
+    _: SecretValidator<AdminScope>,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<ApiKey>>, ApiError> {
+    Ok(Json(state.api_keys.list().await?))
+}
+
+pub async fn delete_api_key(
+    _: SecretValidator<AdminScope>,
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, ApiError> {
+    if !state.api_keys.delete(id).await? {
+        return Err(ApiError::NotFound);
+    }
+    info!(id, "deleted api key");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewKnowledgeBaseEntry {
+    /// the entry applies to every repository when omitted
+    #[serde(default)]
+    repository_full_name: Option<String>,
+    question: String,
+    answer: String,
+}
+
+#[derive(Serialize)]
+pub struct CreatedKnowledgeBaseEntry {
+    id: i32,
+}
+
+pub async fn create_knowledge_base_entry(
+    _: SecretValidator<AdminScope>,
+    State(state): State<AppState>,
+    Json(entry): Json<NewKnowledgeBaseEntry>,
+) -> Result<Json<CreatedKnowledgeBaseEntry>, ApiError> {
+    if entry.question.trim().is_empty() || entry.answer.trim().is_empty() {
+        return Err(ApiError::BadRequest(
+            "knowledge base entries need a question and an answer".to_owned(),
+        ));
+    }
+    let id = state
+        .knowledge_base
+        .add(
+            entry.repository_full_name.as_deref(),
+            &entry.question,
+            &entry.answer,
+        )
+        .await?;
+    info!(
+        id,
+        repository = entry.repository_full_name,
+        "created knowledge base entry"
+    );
+    Ok(Json(CreatedKnowledgeBaseEntry { id }))
+}
+
+pub async fn update_knowledge_base_entry(
+    _: SecretValidator<AdminScope>,
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+    Json(entry): Json<NewKnowledgeBaseEntry>,
+) -> Result<StatusCode, ApiError> {
+    if entry.question.trim().is_empty() || entry.answer.trim().is_empty() {
+        return Err(ApiError::BadRequest(
+            "knowledge base entries need a question and an answer".to_owned(),
+        ));
+    }
+    let updated = state
+        .knowledge_base
+        .update(
+            id,
+            entry.repository_full_name.as_deref(),
+            &entry.question,
+            &entry.answer,
+        )
+        .await?;
+    if !updated {
+        return Err(ApiError::NotFound);
+    }
+    info!(
+        id,
+        repository = entry.repository_full_name,
+        "updated knowledge base entry"
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_knowledge_base_entries(
+    _: SecretValidator<AdminScope>,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<KnowledgeBaseEntry>>, ApiError> {
+    Ok(Json(state.knowledge_base.list().await?))
+}
+
+pub async fn delete_knowledge_base_entry(
+    _: SecretValidator<AdminScope>,
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, ApiError> {
+    if !state.knowledge_base.delete(id).await? {
+        return Err(ApiError::NotFound);
+    }
+    info!(id, "deleted knowledge base entry");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// unavailable when shutting down or while the webhook worker is stalled
+pub async fn health(State(state): State<AppState>) -> impl IntoResponse {
+    if !PRE_SHUTDOWN.load(Ordering::SeqCst) && state.supervisor.is_ready() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::BorrowMut, time::Duration};
+
+    use axum::{
+        body::Body,
+        http::{
+            header::{AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE},
+            Request, StatusCode,
+        },
+    };
+    use tokio::sync::mpsc::{self, Sender};
+    use tower::ServiceExt;
+
+    use crate::{
+        api_keys::ApiKeys,
+        app,
+        catch_up::CatchUp,
+        comment_queue::CommentQueue,
+        compare::IssueComparer,
+        config::{load_config, IssueBotConfig},
+        debug::DebugState,
+        drift::EmbeddingDrift,
+        embeddings::{inference_endpoints::EmbeddingApi, queue::EmbeddingQueue},
+        event_log::EventLog,
+        events::PipelineEvents,
+        extraction::Extractor,
+        footer::CommentFooter,
+        github::GithubApi,
+        github_oidc::GithubOidc,
+        hot_issues::HotIssues,
+        huggingface::HuggingfaceApi,
+        ignore::IgnoreRules,
+        issue_text::IssueTextComposer,
+        knowledge_base::KnowledgeBase,
+        live_config::LiveConfig,
+        locks::Locks,
+        middlewares::X_REQUEST_ID,
+        mirror::WebhookMirror,
+        onboarding::Onboarding,
+        repo_groups::RepoGroups,
+        repo_metadata::RepoMetadata,
+        retry::RetryBudget,
+        search::IssueSearch,
+        settings::Settings,
+        slack::Slack,
+        storage::Storage,
+        supervisor::Supervisor,
+        test_harness::test_db,
+        web_ui::WebUi,
+        webhooks::WebhookChecker,
+        AppState, EventData, FeedbackData, QueuedEvent, Vote,
+    };
+
+    use super::{
+        deserialize_github_webhook, parse_github_webhook, parse_huggingface_repo_webhook,
+        GithubWebhook, HuggingfaceWebhook, Issue, ParsedWebhook,
+    };
+
+    fn test_embedding_queue(config: &IssueBotConfig) -> EmbeddingQueue {
+        let embedding_api = EmbeddingApi::new(
+            config.embedding_api.clone(),
+            &config.http_client,
+            &config.timeouts,
+            DebugState::default(),
+            RetryBudget::default(),
+        )
+        .unwrap();
+        EmbeddingQueue::new(&config.embedding_api, embedding_api)
+    }
+
+    async fn test_knowledge_base(config: &IssueBotConfig) -> KnowledgeBase {
+        KnowledgeBase::new(
+            config.knowledge_base.clone(),
+            test_db().await,
+            test_embedding_queue(config),
+        )
+    }
+
+    async fn test_search(config: &IssueBotConfig) -> IssueSearch {
+        IssueSearch::new(
+            config.search.clone(),
+            test_db().await,
+            test_embedding_queue(config),
+            IssueTextComposer::new(&config.issue_text),
+        )
+    }
+
+    fn test_slack(config: &IssueBotConfig) -> Slack {
+        Slack::new(
+            &config.slack,
+            &config.http_client,
+            &config.timeouts,
+            LiveConfig::new(config.into()),
+            None,
+        )
+        .unwrap()
+    }
+
+    async fn test_comment_queue(config: &IssueBotConfig) -> CommentQueue {
+        let live_config = LiveConfig::new(config.into());
+        let footer = CommentFooter::new(&config.feedback, config.embedding_api.model.clone());
+        let db = test_db().await;
+        CommentQueue::new(
+            config.comment_queue.clone(),
+            db.clone(),
+            GithubApi::new(
+                config.github_api.clone(),
+                &config.http_client,
+                &config.timeouts,
+                live_config.clone(),
+                footer.clone(),
+            )
+            .unwrap(),
+            HuggingfaceApi::new(
+                config.huggingface_api.clone(),
+                &config.http_client,
+                &config.timeouts,
+                live_config,
+                footer,
+            )
+            .unwrap(),
+            Locks::new(db),
+            test_slack(config),
+        )
+    }
+
+    async fn test_catch_up(config: &IssueBotConfig, tx: Sender<QueuedEvent>) -> CatchUp {
+        let db = test_db().await;
+        CatchUp::new(
+            config.catch_up.clone(),
+            db.clone(),
+            DebugState::default(),
+            GithubApi::new(
+                config.github_api.clone(),
+                &config.http_client,
+                &config.timeouts,
+                LiveConfig::new(config.into()),
+                CommentFooter::new(&config.feedback, config.embedding_api.model.clone()),
+            )
+            .unwrap(),
+            IgnoreRules::default(),
+            Locks::new(db),
+            tx,
+        )
+    }
+
+    async fn test_web_ui(config: &IssueBotConfig) -> WebUi {
+        WebUi::new(
+            config.web_ui.clone(),
+            test_db().await,
+            DebugState::default(),
+            test_embedding_queue(config),
+        )
+    }
+
+    fn test_webhooks(config: &IssueBotConfig) -> WebhookChecker {
+        let github_api = GithubApi::new(
+            config.github_api.clone(),
+            &config.http_client,
+            &config.timeouts,
+            LiveConfig::new(config.into()),
+            CommentFooter::new(&config.feedback, config.embedding_api.model.clone()),
+        )
+        .unwrap();
+        WebhookChecker::new(
+            github_api,
+            config.auth_token.clone(),
+            config.github_api.webhook_url.clone(),
+        )
+    }
+
+    fn test_github_oidc(config: &IssueBotConfig) -> GithubOidc {
+        GithubOidc::new(
+            config.github_oidc.clone(),
+            &config.http_client,
+            &config.timeouts,
+        )
+        .unwrap()
+    }
+
+    async fn test_onboarding(config: &IssueBotConfig, tx: Sender<QueuedEvent>) -> Onboarding {
+        let github_api = GithubApi::new(
+            config.github_api.clone(),
+            &config.http_client,
+            &config.timeouts,
+            LiveConfig::new(config.into()),
+            CommentFooter::new(&config.feedback, config.embedding_api.model.clone()),
+        )
+        .unwrap();
+        Onboarding::new(
+            config.onboarding.clone(),
+            test_db().await,
+            github_api,
+            test_slack(config),
+            tx,
+            test_webhooks(config),
+        )
+    }
+
+    async fn test_comparer(config: &IssueBotConfig) -> IssueComparer {
+        let db = test_db().await;
+        let github_api = GithubApi::new(
+            config.github_api.clone(),
+            &config.http_client,
+            &config.timeouts,
+            LiveConfig::new(config.into()),
+            CommentFooter::new(&config.feedback, config.embedding_api.model.clone()),
+        )
+        .unwrap();
+        IssueComparer::new(
+            db.clone(),
+            test_embedding_queue(config),
+            Extractor::new(config.extraction.clone(), db.clone(), DebugState::default()),
+            RepoGroups::new(&config.repo_groups).unwrap(),
+            RepoMetadata::new(
+                config.repo_metadata.clone(),
+                db.clone(),
+                DebugState::default(),
+                github_api,
+                Locks::new(db.clone()),
+            ),
+            config.repositories.clone(),
+            Settings::new(LiveConfig::new(config.into()), db),
+        )
+    }
+
+    async fn test_state(config: &IssueBotConfig, tx: Sender<QueuedEvent>) -> AppState {
+        AppState {
+            api_keys: ApiKeys::new(config.auth_token.clone(), test_db().await),
+            auth_token: config.auth_token.clone(),
+            catch_up: test_catch_up(config, tx.clone()).await,
+            comment_queue: test_comment_queue(config).await,
+            comparer: test_comparer(config).await,
+            db: test_db().await,
+            debug_state: DebugState::default(),
+            drift: EmbeddingDrift::new(
+                config.drift.clone(),
+                test_db().await,
+                config.embedding_api.model.clone(),
+            ),
+            event_log: EventLog::new(
+                config.event_log.clone(),
+                test_db().await,
+                DebugState::default(),
+            ),
+            events: PipelineEvents::default(),
+            github_oidc: test_github_oidc(config),
+            hot_issues: HotIssues::new(&config.hot_issues, test_db().await),
+            ignore_rules: IgnoreRules::new(&config.ignore_rules).unwrap(),
+            knowledge_base: test_knowledge_base(config).await,
+            locks: Locks::new(test_db().await),
+            max_body_bytes: config.server.max_body_bytes,
+            onboarding: test_onboarding(config, tx.clone()).await,
+            request_timeout: Duration::from_secs(config.timeouts.request_secs),
+            search: test_search(config).await,
+            settings: Settings::new(LiveConfig::new(config.into()), test_db().await),
+            slack: test_slack(config),
+            supervisor: Supervisor::new(config.supervisor.clone(), DebugState::default()),
+            tx,
+            web_ui: test_web_ui(config).await,
+            webhook_mirror: WebhookMirror::new(
+                config.webhook_mirror.clone(),
+                &config.http_client,
+                &config.timeouts,
+            )
+            .unwrap(),
+            webhooks: test_webhooks(config),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_github_webhook_handler() {
+        let config: IssueBotConfig = load_config("ISSUE_BOT_TEST").unwrap();
+        let (tx, _rx) = mpsc::channel(8);
+        let state = test_state(&config, tx).await;
+        let mut app = app(state);
+
+        let payload_body = r#"{"action":"opened","issue":{"title":"my great contribution to the world","body":"superb work, isnt it","id":4321,"number":5,"html_url":"https://github.com/huggingface/lor-e/5", "url":"https://github.com/api/huggingface/lor-e/5"}, "repository":{"full_name":"huggingface/lor-e"}}"#;
+        let sig = "sha256=8e288dccf7b2744c5f3f30ab1e82672f16c0cb0f809d384df85cac2421e153af";
+
+        let response = app
+            .borrow_mut()
+            .oneshot(
+                Request::builder()
+                    .method(axum::http::Method::POST)
+                    .uri("/event/github")
+                    .header("x-github-event", "issues")
                     .header("x-hub-signature-256", sig)
                     .body(Body::from(payload_body))
                     .unwrap(),
@@ -529,6 +2246,110 @@ mod tests {
             .unwrap();
 
         assert_eq!(response.status(), StatusCode::OK);
+
+        let payload_body = r#"{"action":"created","comment":{"body":"test review","id":1234,"url":"https://github.com/huggingface/lor-e/5#comment-123"},"issue":{"title":"my great contribution to the world","body":"superb work, isnt it","id":4321,"number":5,"html_url":"https://github.com/huggingface/lor-e/5", "url":"https://github.com/api/huggingface/lor-e/5"}, "repository":{"full_name":"huggingface/lor-e"}}"#;
+        let sig = "sha256=017815fdb6eda66aa8f62123844001fa64e1b2c137808a0ac68f60091ca36f56";
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(axum::http::Method::POST)
+                    .uri("/event/github")
+                    .header("x-github-event", "issue_comment")
+                    .header("x-hub-signature-256", sig)
+                    .body(Body::from(payload_body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_github_webhook_validation() {
+        let payload_body = r#"{"action":"opened","issue":{"title":"my great contribution to the world","body":"superb work, isnt it","id":4321,"number":"5","html_url":"https://github.com/huggingface/lor-e/5", "url":"https://github.com/api/huggingface/lor-e/5"}, "repository":{"full_name":"huggingface/lor-e"}}"#;
+        let err = deserialize_github_webhook("issues", payload_body.as_bytes()).unwrap_err();
+        assert!(err.to_string().contains("Invalid issue.number"), "{err}");
+
+        let err = deserialize_github_webhook("issue_comment", payload_body.as_bytes()).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Invalid action: unknown variant `opened`"),
+            "{err}"
+        );
+
+        let payload_body = payload_body.replace(r#""5""#, "5");
+        assert!(matches!(
+            deserialize_github_webhook("issues", payload_body.as_bytes()),
+            Ok(Some(GithubWebhook::Issue(_)))
+        ));
+        let payload_body = r#"{"action":"opened","pull_request":{"title":"fix tokenizer crash","body":null,"id":98765,"number":6,"html_url":"https://github.com/huggingface/lor-e/pull/6", "url":"https://api.github.com/repos/huggingface/lor-e/pulls/6"}, "repository":{"full_name":"huggingface/lor-e"}}"#;
+        assert!(matches!(
+            deserialize_github_webhook("pull_request", payload_body.as_bytes()),
+            Ok(Some(GithubWebhook::PullRequest(_)))
+        ));
+        let payload_body = r#"{"action":"created","comment":{"id":555,"body":"this unwrap panics on empty input","url":"https://api.github.com/repos/huggingface/lor-e/pulls/comments/555","user":{"login":"octocat"}},"pull_request":{"title":"fix tokenizer crash","body":null,"id":98765,"number":6,"html_url":"https://github.com/huggingface/lor-e/pull/6", "url":"https://api.github.com/repos/huggingface/lor-e/pulls/6"}, "repository":{"full_name":"huggingface/lor-e"}}"#;
+        assert!(matches!(
+            deserialize_github_webhook("pull_request_review_comment", payload_body.as_bytes()),
+            Ok(Some(GithubWebhook::PullRequestReviewComment(_)))
+        ));
+        // unsupported events aren't validated
+        assert!(matches!(
+            deserialize_github_webhook("ping", br#"{"zen":"Keep it logically awesome."}"#),
+            Ok(None)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_pull_request_label_change() {
+        let config: IssueBotConfig = load_config("ISSUE_BOT_TEST").unwrap();
+        let (tx, _rx) = mpsc::channel(8);
+        let state = test_state(&config, tx).await;
+        let payload_body = r#"{"action":"labeled","pull_request":{"title":"fix tokenizer crash","body":null,"id":98765,"number":6,"labels":[{"name":"bug"}],"html_url":"https://github.com/huggingface/lor-e/pull/6", "url":"https://api.github.com/repos/huggingface/lor-e/pulls/6"}, "repository":{"full_name":"huggingface/lor-e"}}"#;
+
+        // not stored yet, indexed through the issues API
+        let parsed = parse_github_webhook(&state, "pull_request", payload_body.as_bytes())
+            .await
+            .unwrap();
+        assert!(matches!(
+            parsed,
+            ParsedWebhook::Event {
+                event: EventData::IssueIndexation(_)
+            }
+        ));
+
+        let pull_request = crate::IssueData {
+            source_id: 4321,
+            action: crate::Action::Created,
+            author: None,
+            labels: vec![],
+            milestone: None,
+            title: "fix tokenizer crash".to_owned(),
+            body: String::new(),
+            is_pull_request: true,
+            number: 6,
+            html_url: "https://github.com/huggingface/lor-e/pull/6".to_owned(),
+            url: "https://api.github.com/repos/huggingface/lor-e/issues/6".to_owned(),
+            repository_full_name: "huggingface/lor-e".to_owned(),
+            source: crate::Source::Github,
+        };
+        state
+            .db
+            .insert_issue(&pull_request, &[1., 0.], None)
+            .await
+            .unwrap();
+        let parsed = parse_github_webhook(&state, "pull_request", payload_body.as_bytes())
+            .await
+            .unwrap();
+        let ParsedWebhook::Event {
+            event: EventData::IssueMetadata(metadata),
+        } = parsed
+        else {
+            panic!("label change not handled as a metadata update");
+        };
+        assert_eq!(metadata.source_id, 4321);
+        assert_eq!(metadata.labels, ["bug"]);
     }
 
     #[tokio::test]
@@ -536,10 +2357,7 @@ mod tests {
         let config: IssueBotConfig = load_config("ISSUE_BOT_TEST").unwrap();
         let auth_token = config.auth_token.clone();
         let (tx, _rx) = mpsc::channel(8);
-        let state = AppState {
-            auth_token: auth_token.clone(),
-            tx,
-        };
+        let state = test_state(&config, tx).await;
         let mut app = app(state);
 
         let payload_body = r#"{"event":{"action":"create", "scope":"discussion"}, "discussion":{"id":1234, "isPullRequest":false, "num":1, "title":"my test issue","url":{"api":"https://huggingface.co/test", "web":"https://huggingface.co/test"}}}"#;
@@ -577,4 +2395,236 @@ mod tests {
 
         assert_eq!(response.status(), StatusCode::OK);
     }
+
+    #[tokio::test]
+    async fn test_hf_webhook_dry_run() {
+        let config: IssueBotConfig = load_config("ISSUE_BOT_TEST").unwrap();
+        let auth_token = config.auth_token.clone();
+        let (tx, mut rx) = mpsc::channel(8);
+        let state = test_state(&config, tx).await;
+
+        let payload_body = r#"{"event":{"action":"create", "scope":"discussion"}, "discussion":{"id":1234, "isPullRequest":false, "num":1, "title":"my test issue","url":{"api":"https://huggingface.co/test", "web":"https://huggingface.co/test"}}}"#;
+
+        let response = app(state)
+            .oneshot(
+                Request::builder()
+                    .method(axum::http::Method::POST)
+                    .uri("/event/huggingface/dry-run")
+                    .header("x-webhook-secret", &auth_token)
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(Body::from(payload_body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let parsed: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(parsed["outcome"], "event");
+        assert_eq!(parsed["event"]["type"], "issue");
+        assert_eq!(parsed["event"]["data"]["source_id"], 1234);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_body_limits() {
+        let config: IssueBotConfig = load_config("ISSUE_BOT_TEST").unwrap();
+        let auth_token = config.auth_token.clone();
+        let (tx, _rx) = mpsc::channel(8);
+        let state = AppState {
+            max_body_bytes: 64,
+            ..test_state(&config, tx).await
+        };
+        let mut app = app(state);
+
+        let request = |content_type: &str, body: String| {
+            Request::builder()
+                .method(axum::http::Method::POST)
+                .uri("/event/huggingface")
+                .header("x-webhook-secret", &auth_token)
+                .header(CONTENT_TYPE, content_type)
+                .header(CONTENT_LENGTH, body.len())
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let response = app
+            .borrow_mut()
+            .oneshot(request("text/plain", "{}".to_owned()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let response = app
+            .oneshot(request(
+                "application/json",
+                format!(r#"{{"padding":"{}"}}"#, "a".repeat(128)),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_debug_state() {
+        let config: IssueBotConfig = load_config("ISSUE_BOT_TEST").unwrap();
+        let (tx, _rx) = mpsc::channel(8);
+        let state = test_state(&config, tx).await;
+        state.debug_state.set_in_flight("webhooks", "issue 1234");
+        state
+            .debug_state
+            .record_error("database", "connection reset");
+        let mut app = app(state);
+
+        let request = |authorization: Option<&str>| {
+            let mut builder = Request::builder()
+                .method(axum::http::Method::GET)
+                .uri("/debug/state");
+            if let Some(authorization) = authorization {
+                builder = builder.header(AUTHORIZATION, authorization);
+            }
+            builder.body(Body::empty()).unwrap()
+        };
+
+        let response = app.borrow_mut().oneshot(request(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = app
+            .oneshot(request(Some(&config.auth_token)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let parsed: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(parsed["channel_capacity"], 8);
+        assert_eq!(parsed["channel_depth"], 0);
+        assert_eq!(parsed["in_flight"]["webhooks"]["event"], "issue 1234");
+        assert_eq!(
+            parsed["last_errors"]["database"]["message"],
+            "connection reset"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_feedback() {
+        let config: IssueBotConfig = load_config("ISSUE_BOT_TEST").unwrap();
+        let (tx, mut rx) = mpsc::channel(8);
+        let state = test_state(&config, tx).await;
+        state
+            .db
+            .insert_comment_run(
+                "known-run",
+                "https://api.github.com/repos/huggingface/lor-e/issues/1",
+                None,
+            )
+            .await
+            .unwrap();
+        let mut app = app(state);
+
+        let response = app
+            .borrow_mut()
+            .oneshot(
+                Request::builder()
+                    .method(axum::http::Method::GET)
+                    .uri("/feedback?run_id=known-run&vote=up")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(rx.try_recv().is_err());
+
+        let vote = |run_id: &str| {
+            let body = format!("run_id={run_id}&vote=down");
+            Request::builder()
+                .method(axum::http::Method::POST)
+                .uri("/feedback")
+                .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .header(CONTENT_LENGTH, body.len())
+                .body(Body::from(body))
+                .unwrap()
+        };
+        let response = app.borrow_mut().oneshot(vote("unknown-run")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(rx.try_recv().is_err());
+
+        let response = app.oneshot(vote("known-run")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(matches!(
+            rx.try_recv().unwrap().data,
+            EventData::Feedback(FeedbackData {
+                vote: Vote::Down,
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_slack_interactions_mounted_with_middlewares() {
+        let config: IssueBotConfig = load_config("ISSUE_BOT_TEST").unwrap();
+        let (tx, _rx) = mpsc::channel(8);
+        let app = app(test_state(&config, tx).await);
+
+        let body = "payload=%7B%7D";
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(axum::http::Method::POST)
+                    .uri("/slack/interactions")
+                    .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+                    .header(CONTENT_LENGTH, body.len())
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        // form encoded bodies get through, the signature being checked
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(response.headers().contains_key(X_REQUEST_ID));
+    }
+
+    #[test]
+    fn test_hf_repo_webhook() {
+        let webhook: HuggingfaceWebhook = serde_json::from_str(
+            r#"{"event":{"action":"move","scope":"repo"},"repo":{"type":"model","name":"org/old-name","private":true},"movedTo":{"name":"org/new-name"}}"#,
+        )
+        .unwrap();
+        let ParsedWebhook::Event {
+            event: EventData::RepositoryUpdate(update),
+        } = parse_huggingface_repo_webhook(webhook).unwrap()
+        else {
+            panic!("expected a repository update");
+        };
+        assert_eq!(update.full_name, "org/old-name");
+        assert_eq!(update.moved_to.as_deref(), Some("org/new-name"));
+        assert_eq!(update.private, Some(true));
+
+        let webhook: HuggingfaceWebhook = serde_json::from_str(
+            r#"{"event":{"action":"update","scope":"repo.content"},"repo":{"name":"org/model"}}"#,
+        )
+        .unwrap();
+        assert!(matches!(webhook.event.scope, super::Scope::Ignored));
+    }
+
+    #[test]
+    fn test_issue_text_changed() {
+        let edited = |changes: &str| {
+            let payload = format!(
+                r#"{{"action":"edited",{changes}"issue":{{"title":"new title","body":"same body","id":1,"number":1,"html_url":"","url":""}},"repository":{{"full_name":"huggingface/lor-e"}}}}"#
+            );
+            serde_json::from_str::<Issue>(&payload).unwrap()
+        };
+
+        assert!(edited("").text_changed());
+        assert!(edited(r#""changes":{"title":{"from":"old title"}},"#).text_changed());
+        assert!(edited(r#""changes":{"body":{"from":null}},"#).text_changed());
+        assert!(!edited(r#""changes":{},"#).text_changed());
+        assert!(!edited(r#""changes":{"body":{"from":"same body"}},"#).text_changed());
+    }
 }
@@ -0,0 +1,418 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+use crate::{
+    config::SearchConfig,
+    embeddings::{
+        queue::{EmbeddingQueue, Priority},
+        EmbeddingError,
+    },
+    issue_text::IssueTextComposer,
+    storage::{Database, SearchHit, Storage, StorageError},
+};
+
+#[derive(Debug, Error)]
+pub enum SearchError {
+    #[error("embedding error: {0}")]
+    Embedding(#[from] EmbeddingError),
+    #[error("invalid cursor: '{0}'")]
+    InvalidCursor(String),
+    #[error("serde json error: {0}")]
+    SerdeJson(#[from] serde_json::Error),
+    #[error("storage error: {0}")]
+    Storage(#[from] StorageError),
+}
+
+/// Order of the results, every one of them descending
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchSort {
+    #[default]
+    Similarity,
+    Recency,
+    Comments,
+}
+
+impl SearchSort {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Similarity => "similarity",
+            Self::Recency => "recency",
+            Self::Comments => "comments",
+        }
+    }
+
+    /// similarities are scaled to integers so that cursors compare exactly
+    fn key(&self, hit: &SearchHit) -> i64 {
+        match self {
+            Self::Similarity => (hit.cosine_similarity * 1e12).round() as i64,
+            Self::Recency => hit.created_at.timestamp_micros(),
+            Self::Comments => hit.comment_count,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchField {
+    SourceId,
+    Title,
+    Number,
+    HtmlUrl,
+    Labels,
+    RepositoryFullName,
+    IsPullRequest,
+    Body,
+    CreatedAt,
+    CommentCount,
+    CosineSimilarity,
+    Accelerator,
+    Os,
+}
+
+impl SearchField {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::SourceId => "source_id",
+            Self::Title => "title",
+            Self::Number => "number",
+            Self::HtmlUrl => "html_url",
+            Self::Labels => "labels",
+            Self::RepositoryFullName => "repository_full_name",
+            Self::IsPullRequest => "is_pull_request",
+            Self::Body => "body",
+            Self::CreatedAt => "created_at",
+            Self::CommentCount => "comment_count",
+            Self::CosineSimilarity => "cosine_similarity",
+            Self::Accelerator => "accelerator",
+            Self::Os => "os",
+        }
+    }
+}
+
+/// every field but the body
+const DEFAULT_FIELDS: &[SearchField] = &[
+    SearchField::SourceId,
+    SearchField::Title,
+    SearchField::Number,
+    SearchField::HtmlUrl,
+    SearchField::Labels,
+    SearchField::RepositoryFullName,
+    SearchField::IsPullRequest,
+    SearchField::CreatedAt,
+    SearchField::CommentCount,
+    SearchField::CosineSimilarity,
+    SearchField::Accelerator,
+    SearchField::Os,
+];
+
+#[derive(Debug, Deserialize)]
+pub struct SearchRequest {
+    pub q: String,
+    /// searches every repository when empty
+    #[serde(default)]
+    pub repositories: Vec<String>,
+    /// only issues reported with this accelerator, e.g. `rocm`, see [crate::extraction]
+    pub accelerator: Option<String>,
+    /// only issues reported on this OS, e.g. `windows`
+    pub os: Option<String>,
+    #[serde(default)]
+    pub sort: SearchSort,
+    /// defaults to [DEFAULT_FIELDS]
+    pub fields: Option<Vec<SearchField>>,
+    /// capped to the configured `max_page_size`
+    pub limit: Option<usize>,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchPage {
+    pub issues: Vec<Map<String, Value>>,
+    /// unset on the last page
+    pub next_cursor: Option<String>,
+    /// whether more issues matched than the `max_candidates` ranked, the furthest ones being left
+    /// out of every page
+    pub capped: bool,
+}
+
+/// Position after the last issue of a page: the sort it was issued for, that issue's sort key
+/// and id, hex encoded to keep clients from relying on its format
+#[derive(Debug)]
+struct Cursor {
+    sort: SearchSort,
+    key: i64,
+    id: i32,
+}
+
+impl Cursor {
+    fn encode(&self) -> String {
+        hex::encode(format!("{}:{}:{}", self.sort.as_str(), self.key, self.id))
+    }
+
+    fn decode(cursor: &str) -> Option<Self> {
+        let decoded = String::from_utf8(hex::decode(cursor).ok()?).ok()?;
+        let mut parts = decoded.split(':');
+        let sort = match parts.next()? {
+            "similarity" => SearchSort::Similarity,
+            "recency" => SearchSort::Recency,
+            "comments" => SearchSort::Comments,
+            _ => return None,
+        };
+        let key = parts.next()?.parse().ok()?;
+        let id = parts.next()?.parse().ok()?;
+        match parts.next() {
+            Some(_) => None,
+            None => Some(Self { sort, key, id }),
+        }
+    }
+
+    /// whether `hit` comes after the cursor, hits being ordered by descending key then id
+    fn precedes(&self, hit: &SearchHit) -> bool {
+        let key = self.sort.key(hit);
+        key < self.key || (key == self.key && hit.id > self.id)
+    }
+}
+
+/// keeps the `fields` of `hit`
+fn select_fields(
+    hit: &SearchHit,
+    fields: &[SearchField],
+) -> Result<Map<String, Value>, SearchError> {
+    let Value::Object(mut all) = serde_json::to_value(hit)? else {
+        unreachable!("search hits serialize to objects");
+    };
+    Ok(fields
+        .iter()
+        .filter_map(|f| all.remove_entry(f.as_str()))
+        .collect())
+}
+
+/// Candidates of a search, sorted, keyed by everything but the page's cursor, limit and fields
+type Candidates = Arc<[SearchHit]>;
+
+/// Semantic search over the stored issues, paginated with cursors
+#[derive(Clone)]
+pub struct IssueSearch {
+    candidates: Arc<Mutex<HashMap<String, (Instant, Candidates)>>>,
+    cfg: SearchConfig,
+    db: Database,
+    embedding_queue: EmbeddingQueue,
+    /// wraps queries in the metadata template the issues were embedded with
+    issue_text: IssueTextComposer,
+}
+
+impl IssueSearch {
+    pub fn new(
+        cfg: SearchConfig,
+        db: Database,
+        embedding_queue: EmbeddingQueue,
+        issue_text: IssueTextComposer,
+    ) -> Self {
+        Self {
+            candidates: Arc::default(),
+            cfg,
+            db,
+            embedding_queue,
+            issue_text,
+        }
+    }
+
+    /// ranks the `max_candidates` issues closest to the query
+    ///
+    /// The candidates are kept for `cache_secs`, the following pages reading them instead of
+    /// embedding the query again. Cursors stay valid past that, the candidates being looked up
+    /// again as issues are added or removed.
+    pub async fn search(&self, req: SearchRequest) -> Result<SearchPage, SearchError> {
+        let cursor = match &req.cursor {
+            Some(cursor) => match Cursor::decode(cursor) {
+                Some(decoded) if decoded.sort == req.sort => Some(decoded),
+                _ => return Err(SearchError::InvalidCursor(cursor.clone())),
+            },
+            None => None,
+        };
+        let fields = req.fields.as_deref().unwrap_or(DEFAULT_FIELDS);
+        let limit = req
+            .limit
+            .unwrap_or(self.cfg.max_page_size)
+            .clamp(1, self.cfg.max_page_size);
+
+        let candidates = self
+            .candidates(&req, fields.contains(&SearchField::Body), cursor.is_some())
+            .await?;
+        let (page, more) = paginate(&candidates, cursor.as_ref(), limit);
+        let next_cursor = if more {
+            page.last().map(|hit| {
+                Cursor {
+                    sort: req.sort,
+                    key: req.sort.key(hit),
+                    id: hit.id,
+                }
+                .encode()
+            })
+        } else {
+            None
+        };
+        Ok(SearchPage {
+            issues: page
+                .iter()
+                .map(|hit| select_fields(hit, fields))
+                .collect::<Result<_, _>>()?,
+            next_cursor,
+            capped: candidates.len() as i64 >= self.cfg.max_candidates,
+        })
+    }
+
+    /// the sorted candidates of `req`, cached for `cache_secs`
+    ///
+    /// The query of a following page whose candidates expired is embedded in the background,
+    /// clients walking through every page shouldn't delay the interactive requests.
+    async fn candidates(
+        &self,
+        req: &SearchRequest,
+        with_bodies: bool,
+        following_page: bool,
+    ) -> Result<Candidates, SearchError> {
+        let accelerator = req.accelerator.as_deref().map(str::to_lowercase);
+        let os = req.os.as_deref().map(str::to_lowercase);
+        let key = serde_json::to_string(&(
+            &req.q,
+            &req.repositories,
+            &accelerator,
+            &os,
+            req.sort.as_str(),
+            with_bodies,
+        ))?;
+        let ttl = Duration::from_secs(self.cfg.cache_secs);
+        if let Some((cached_at, candidates)) = self.candidates.lock().unwrap().get(&key) {
+            if cached_at.elapsed() < ttl {
+                return Ok(candidates.clone());
+            }
+        }
+
+        let priority = if following_page {
+            Priority::Background
+        } else {
+            Priority::Interactive
+        };
+        let repository_full_name = match req.repositories.as_slice() {
+            [repository_full_name] => repository_full_name.as_str(),
+            _ => "",
+        };
+        let query = self.issue_text.query_input(&req.q, repository_full_name);
+        let embedding = self
+            .embedding_queue
+            .generate_embedding(query, priority)
+            .await?;
+        let mut hits = self
+            .db
+            .search_issues(
+                &embedding,
+                &req.repositories,
+                accelerator.as_deref(),
+                os.as_deref(),
+                self.cfg.max_candidates,
+                with_bodies,
+            )
+            .await?;
+        hits.sort_by(|a, b| req.sort.key(b).cmp(&req.sort.key(a)).then(a.id.cmp(&b.id)));
+        let candidates: Candidates = hits.into();
+        let mut cache = self.candidates.lock().unwrap();
+        cache.retain(|_, (cached_at, _)| cached_at.elapsed() < ttl);
+        cache.insert(key, (Instant::now(), candidates.clone()));
+        Ok(candidates)
+    }
+}
+
+/// the at most `limit` hits of `sorted` after `cursor`, and whether more follow them
+fn paginate<'a>(
+    sorted: &'a [SearchHit],
+    cursor: Option<&Cursor>,
+    limit: usize,
+) -> (&'a [SearchHit], bool) {
+    let start = cursor.map_or(0, |cursor| {
+        sorted.partition_point(|hit| !cursor.precedes(hit))
+    });
+    let remaining = &sorted[start..];
+    (
+        &remaining[..limit.min(remaining.len())],
+        remaining.len() > limit,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use crate::storage::SearchHit;
+
+    use super::{paginate, select_fields, Cursor, SearchField, SearchSort};
+
+    fn hit(id: i32, cosine_similarity: f64, comment_count: i64, day: u32) -> SearchHit {
+        SearchHit {
+            id,
+            source_id: id as i64 * 100,
+            title: format!("issue {id}"),
+            number: id,
+            html_url: format!("https://github.com/huggingface/lor-e/issues/{id}"),
+            labels: Vec::new(),
+            repository_full_name: "huggingface/lor-e".to_owned(),
+            is_pull_request: false,
+            body: None,
+            created_at: Utc.with_ymd_and_hms(2025, 1, day, 0, 0, 0).unwrap(),
+            comment_count,
+            cosine_similarity,
+            accelerator: None,
+            os: None,
+        }
+    }
+
+    #[test]
+    fn test_pagination() {
+        let hits = vec![
+            hit(1, 0.9, 3, 1),
+            hit(2, 0.8, 10, 4),
+            hit(3, 0.8, 0, 2),
+            hit(4, 0.5, 3, 3),
+        ];
+        for (sort, expected) in [
+            (SearchSort::Similarity, [1, 2, 3, 4]),
+            (SearchSort::Recency, [2, 4, 3, 1]),
+            (SearchSort::Comments, [2, 1, 4, 3]),
+        ] {
+            let mut sorted = hits.clone();
+            sorted.sort_by(|a, b| sort.key(b).cmp(&sort.key(a)).then(a.id.cmp(&b.id)));
+            let mut ids = Vec::new();
+            let mut cursor = None;
+            loop {
+                let (page, more) = paginate(&sorted, cursor.as_ref(), 3);
+                ids.extend(page.iter().map(|h| h.id));
+                if !more {
+                    break;
+                }
+                let last = page.last().unwrap();
+                let encoded = Cursor {
+                    sort,
+                    key: sort.key(last),
+                    id: last.id,
+                }
+                .encode();
+                cursor = Some(Cursor::decode(&encoded).unwrap());
+            }
+            assert_eq!(ids, expected, "{sort:?}");
+        }
+        assert!(Cursor::decode("not a cursor").is_none());
+
+        let selected = select_fields(&hits[0], &[SearchField::Number, SearchField::Body]).unwrap();
+        assert_eq!(selected.len(), 2);
+        assert_eq!(selected["number"], 1);
+        assert!(selected["body"].is_null());
+    }
+}
@@ -0,0 +1,231 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    config::SimilarityConfig,
+    invalidation::{self, Invalidation},
+    live_config::LiveConfig,
+    storage::{Database, Storage, StorageError},
+};
+
+/// other instances' updates are picked up once cached settings are this old when their
+/// notification is missed, see [crate::invalidation]
+const CACHE_TTL: Duration = Duration::from_secs(60);
+const SIMILARITY_KEY: &str = "similarity";
+const MAINTENANCE_KEY: &str = "maintenance";
+
+/// value of a setting, none when unset, and when it was read
+type CachedSetting = (Instant, Option<String>);
+
+/// Parameters of the similar issues suggestions
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct SimilaritySettings {
+    /// number of similar issues suggested
+    pub max_suggestions: i64,
+    /// issues less similar than this are never suggested
+    pub min_similarity: f64,
+}
+
+impl From<&SimilarityConfig> for SimilaritySettings {
+    fn from(cfg: &SimilarityConfig) -> Self {
+        Self {
+            max_suggestions: cfg.max_suggestions,
+            min_similarity: cfg.min_similarity,
+        }
+    }
+}
+
+fn similarity_key(repository_full_name: Option<&str>) -> String {
+    match repository_full_name {
+        Some(repository_full_name) => format!("{SIMILARITY_KEY}:{repository_full_name}"),
+        None => SIMILARITY_KEY.to_owned(),
+    }
+}
+
+/// Settings tunable at runtime, stored in the database and cached in memory
+///
+/// Repository settings take precedence over the global ones, which take precedence over the
+/// configuration file's.
+#[derive(Clone)]
+pub struct Settings {
+    /// raw JSON values
+    cache: Arc<RwLock<HashMap<String, CachedSetting>>>,
+    db: Database,
+    /// holds the defaults
+    live_config: LiveConfig,
+}
+
+impl Settings {
+    pub fn new(live_config: LiveConfig, db: Database) -> Self {
+        Self {
+            cache: Arc::new(RwLock::new(HashMap::new())),
+            db,
+            live_config,
+        }
+    }
+
+    /// settings stored under `key`, `None` if there are none
+    async fn stored<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, StorageError> {
+        let cached = match self.cache.read().unwrap().get(key) {
+            Some((cached_at, value)) if cached_at.elapsed() < CACHE_TTL => Some(value.clone()),
+            _ => None,
+        };
+        let value = match cached {
+            Some(value) => value,
+            None => {
+                let value = self.db.setting(key).await?;
+                self.cache
+                    .write()
+                    .unwrap()
+                    .insert(key.to_owned(), (Instant::now(), value.clone()));
+                value
+            }
+        };
+        Ok(value.map(|v| serde_json::from_str(&v)).transpose()?)
+    }
+
+    async fn store<T: Serialize>(&self, key: &str, value: &T) -> Result<(), StorageError> {
+        self.db
+            .set_setting(key, &serde_json::to_string(value)?)
+            .await?;
+        self.invalidate(Some(key));
+        // other instances fall back to the cache's TTL
+        if let Err(err) = invalidation::publish(
+            &self.db,
+            &Invalidation::Setting {
+                key: key.to_owned(),
+            },
+        )
+        .await
+        {
+            warn!(
+                key,
+                err = err.to_string(),
+                "failed to notify other instances of the settings update"
+            );
+        }
+        Ok(())
+    }
+
+    /// drops the cached settings stored under `key`, or every cached setting when `None`
+    pub fn invalidate(&self, key: Option<&str>) {
+        let mut cache = self.cache.write().unwrap();
+        match key {
+            Some(key) => {
+                cache.remove(key);
+            }
+            None => cache.clear(),
+        }
+    }
+
+    /// settings applying to `repository_full_name`, or the global ones when `None`
+    pub async fn similarity(
+        &self,
+        repository_full_name: Option<&str>,
+    ) -> Result<SimilaritySettings, StorageError> {
+        if repository_full_name.is_some() {
+            if let Some(settings) = self.stored(&similarity_key(repository_full_name)).await? {
+                return Ok(settings);
+            }
+        }
+        Ok(self
+            .stored::<SimilaritySettings>(&similarity_key(None))
+            .await?
+            .unwrap_or_else(|| (&self.live_config.get().similarity).into()))
+    }
+
+    pub async fn set_similarity(
+        &self,
+        repository_full_name: Option<&str>,
+        settings: SimilaritySettings,
+    ) -> Result<(), StorageError> {
+        self.store(&similarity_key(repository_full_name), &settings)
+            .await
+    }
+
+    /// whether background jobs are held at their next checkpoint, e.g. while the embeddings
+    /// endpoint is under maintenance, see [crate::job_control]
+    pub async fn maintenance(&self) -> Result<bool, StorageError> {
+        Ok(self.stored(MAINTENANCE_KEY).await?.unwrap_or_default())
+    }
+
+    pub async fn set_maintenance(&self, enabled: bool) -> Result<(), StorageError> {
+        self.store(MAINTENANCE_KEY, &enabled).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::{MessageConfig, SimilarityConfig},
+        live_config::{LiveConfig, LiveValues},
+        test_harness::test_db,
+    };
+
+    use super::{Settings, SimilaritySettings};
+
+    #[tokio::test]
+    async fn test_similarity_precedence() {
+        let db = test_db().await;
+        let settings = Settings::new(
+            LiveConfig::new(LiveValues {
+                github_comments_enabled: false,
+                huggingface_comments_enabled: false,
+                message_config: MessageConfig {
+                    pre: String::new(),
+                    post: String::new(),
+                },
+                similarity: SimilarityConfig {
+                    max_suggestions: 3,
+                    min_similarity: 0.,
+                },
+                slack_channel: String::new(),
+            }),
+            db,
+        );
+        let global = SimilaritySettings {
+            max_suggestions: 5,
+            min_similarity: 0.5,
+        };
+        let repository = SimilaritySettings {
+            max_suggestions: 1,
+            min_similarity: 0.9,
+        };
+
+        assert_eq!(
+            settings
+                .similarity(Some("huggingface/lor-e"))
+                .await
+                .unwrap(),
+            SimilaritySettings {
+                max_suggestions: 3,
+                min_similarity: 0.,
+            }
+        );
+        settings.set_similarity(None, global).await.unwrap();
+        settings
+            .set_similarity(Some("huggingface/transformers"), repository)
+            .await
+            .unwrap();
+        assert_eq!(
+            settings
+                .similarity(Some("huggingface/lor-e"))
+                .await
+                .unwrap(),
+            global
+        );
+        assert_eq!(
+            settings
+                .similarity(Some("huggingface/transformers"))
+                .await
+                .unwrap(),
+            repository
+        );
+    }
+}
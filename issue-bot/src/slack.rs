@@ -1,16 +1,93 @@
-use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::Utc;
+use futures::future::join_all;
+use hmac::{Hmac, Mac};
+use reqwest::{
+    header::{HeaderMap, HeaderValue, AUTHORIZATION, RETRY_AFTER},
+    StatusCode,
+};
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
 use thiserror::Error;
-use tracing::info;
+use tokio::{select, time::sleep};
+use tracing::{error, info, warn};
+
+use crate::{
+    config::{
+        EscalationConfig, HttpClientConfig, HttpTarget, PriorityConfig, SlackConfig,
+        SlackWorkspaceConfig, TimeoutsConfig,
+    },
+    extraction::{package_name, SystemInfo},
+    http_client::client_builder,
+    live_config::LiveConfig,
+    onboarding::{describe_duration, OnboardingReport},
+    retry::{self, classify_reqwest, with_retry, Classify, RetryBudget, RetryClass, RetryPolicy},
+    shutdown_signal,
+    slack_outbox::SlackOutbox,
+    storage::{PendingComment, RepositoryMetadata, StorageError, SuggestedIssue},
+    webhooks::WebhookProblem,
+    ClosestIssue, IssueData,
+};
 
-use crate::{config::SlackConfig, ClosestIssue, IssueData};
+/// requests signed longer ago than this are rejected as possible replays
+const MAX_SIGNATURE_AGE_SECS: i64 = 300;
+/// Slack rejects section texts longer than 3000 characters
+const MAX_SECTION_CHARS: usize = 2_900;
+/// and header texts longer than 150
+const MAX_HEADER_CHARS: usize = 149;
+/// nor does it allow more elements in an actions block
+const MAX_BUTTONS: usize = 25;
+const APPROVE_ACTION: &str = "approve_comment";
+const DISCARD_ACTION: &str = "discard_comment";
 
 #[derive(Debug, Error)]
 pub enum SlackError {
+    #[error("slack api error: {0}")]
+    Api(String),
     #[error("http client error: {0}")]
     HttpClient(#[from] reqwest::Error),
     #[error("invalid auth token value: {0}")]
     InvalidHeader(#[from] reqwest::header::InvalidHeaderValue),
+    #[error("rate limited by slack")]
+    RateLimited(Option<u64>),
+    #[error("serde json error: {0}")]
+    SerdeJson(#[from] serde_json::Error),
+    #[error("storage error: {0}")]
+    Storage(#[from] StorageError),
+}
+
+impl Classify for SlackError {
+    fn retry_class(&self) -> RetryClass {
+        match self {
+            Self::Api(error) if error == "ratelimited" => RetryClass::RateLimited,
+            Self::RateLimited(_) => RetryClass::RateLimited,
+            Self::HttpClient(err) => classify_reqwest(err),
+            _ => RetryClass::Fatal,
+        }
+    }
+
+    /// delay Slack asked for with its `Retry-After` header
+    fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimited(secs) => secs.map(Duration::from_secs),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct AuthTestResponse {
+    #[serde(default)]
+    error: Option<String>,
+    ok: bool,
 }
 
 #[derive(Deserialize)]
@@ -20,6 +97,8 @@ struct PostMessageResponse {
 
 #[derive(Serialize)]
 struct SlackBody {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blocks: Option<Value>,
     channel: String,
     text: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -29,6 +108,7 @@ struct SlackBody {
 impl SlackBody {
     pub fn new(channel: &str, text: String, thread_ts: Option<String>) -> Self {
         Self {
+            blocks: None,
             channel: channel.to_owned(),
             text,
             thread_ts,
@@ -36,65 +116,1301 @@ impl SlackBody {
     }
 }
 
+#[derive(Deserialize)]
+struct InteractionAction {
+    action_id: String,
+    /// missing for url buttons, e.g. the links of notifications
+    #[serde(default)]
+    value: String,
+}
+
+#[derive(Deserialize)]
+struct InteractionUser {
+    #[serde(default)]
+    username: String,
+}
+
+/// `payload` of an interaction request, only block actions are handled
+#[derive(Deserialize)]
+struct InteractionPayload {
+    #[serde(default)]
+    actions: Vec<InteractionAction>,
+    response_url: String,
+    user: InteractionUser,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DraftDecision {
+    Approve,
+    Discard,
+}
+
+/// Click on one of the buttons of a comment draft
+#[derive(Debug)]
+pub struct DraftAction {
+    pub comment_id: i32,
+    pub decision: DraftDecision,
+    /// replaces the draft message when posted to
+    pub response_url: String,
+    pub user: String,
+}
+
+impl DraftAction {
+    /// `None` for interactions other than the draft buttons
+    pub fn from_payload(payload: &str) -> Result<Option<Self>, serde_json::Error> {
+        let payload: InteractionPayload = serde_json::from_str(payload)?;
+        let Some(action) = payload.actions.first() else {
+            return Ok(None);
+        };
+        let decision = match action.action_id.as_str() {
+            APPROVE_ACTION => DraftDecision::Approve,
+            DISCARD_ACTION => DraftDecision::Discard,
+            _ => return Ok(None),
+        };
+        let Ok(comment_id) = action.value.parse() else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            comment_id,
+            decision,
+            response_url: payload.response_url,
+            user: payload.user.username,
+        }))
+    }
+}
+
+fn request_signature(secret: &str, timestamp: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(format!("v0:{timestamp}:").as_bytes());
+    mac.update(body);
+    format!("v0={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// `text` cut to `max_chars`, ending with an ellipsis when it was
+fn truncate_chars(text: &str, max_chars: usize) -> String {
+    let mut truncated: String = text.chars().take(max_chars).collect();
+    if truncated.len() < text.len() {
+        truncated.push('…');
+    }
+    truncated
+}
+
+/// draft message, with the buttons approving or discarding it
+fn draft_blocks(comment: &PendingComment) -> Value {
+    let body = truncate_chars(&comment.body, MAX_SECTION_CHARS);
+    json!([
+        {
+            "type": "section",
+            "text": {
+                "type": "mrkdwn",
+                "text": format!("Comment draft for {} in *{}*:", comment.issue_url, comment.repository_full_name),
+            },
+        },
+        { "type": "section", "text": { "type": "plain_text", "text": body } },
+        {
+            "type": "actions",
+            "elements": [
+                {
+                    "type": "button",
+                    "action_id": APPROVE_ACTION,
+                    "style": "primary",
+                    "text": { "type": "plain_text", "text": "Approve & post" },
+                    "value": comment.id.to_string(),
+                },
+                {
+                    "type": "button",
+                    "action_id": DISCARD_ACTION,
+                    "style": "danger",
+                    "text": { "type": "plain_text", "text": "Discard" },
+                    "value": comment.id.to_string(),
+                },
+            ],
+        },
+    ])
+}
+
+/// Issue suggested in a notification, linked to by a button
+#[derive(Clone, Deserialize, Serialize)]
+struct SuggestionLink {
+    html_url: String,
+    number: i32,
+    repository_full_name: String,
+}
+
+/// Closest issues notification for a single new issue
+#[derive(Clone, Deserialize, Serialize)]
+struct Notification {
+    author: Option<String>,
+    body: String,
+    closest_issues: Vec<String>,
+    /// see [SystemInfo::summary]
+    environment: Option<String>,
+    html_url: String,
+    labels: Vec<String>,
+    /// owning teams, see [crate::owners::Owners]
+    mentions: Vec<String>,
+    number: i32,
+    /// see [repository_context]
+    repository_context: Option<String>,
+    repository_full_name: String,
+    suggestions: Vec<SuggestionLink>,
+    summary: String,
+    title: String,
+}
+
+impl Notification {
+    fn new(
+        summary: String,
+        issue: &IssueData,
+        repository: Option<&RepositoryMetadata>,
+        closest_issues: &[ClosestIssue],
+        mentions: &[String],
+        system_info: &SystemInfo,
+    ) -> Self {
+        Self {
+            author: issue.author.clone(),
+            body: issue.body.clone(),
+            closest_issues: closest_issues
+                .iter()
+                .map(|ci| format!("• {} (<{}|#{}>)", ci.title, ci.html_url, ci.number))
+                .collect(),
+            environment: system_info.summary(package_name(&issue.repository_full_name)),
+            html_url: issue.html_url.clone(),
+            labels: issue.labels.clone(),
+            mentions: mentions.to_vec(),
+            number: issue.number,
+            repository_context: repository.map(repository_context),
+            repository_full_name: issue.repository_full_name.clone(),
+            suggestions: closest_issues
+                .iter()
+                .map(|ci| SuggestionLink {
+                    html_url: ci.html_url.clone(),
+                    number: ci.number,
+                    repository_full_name: ci.repository_full_name.clone(),
+                })
+                .collect(),
+            summary,
+            title: issue.title.clone(),
+        }
+    }
+
+    /// Block Kit layout of the notification, its text being kept as the fallback shown in
+    /// notifications
+    ///
+    /// Slack collapses long sections behind "Show more", which keeps the summary out of the way.
+    fn blocks(&self) -> Value {
+        let or_none = |value: String| {
+            if value.is_empty() {
+                "_none_".to_owned()
+            } else {
+                value
+            }
+        };
+        let mut blocks = vec![
+            json!({
+                "type": "header",
+                "text": {
+                    "type": "plain_text",
+                    "text": truncate_chars(&format!("#{} {}", self.number, self.title), MAX_HEADER_CHARS),
+                },
+            }),
+            json!({
+                "type": "section",
+                "text": { "type": "mrkdwn", "text": format!("<{}|View issue #{}>", self.html_url, self.number) },
+                "fields": [
+                    { "type": "mrkdwn", "text": format!("*Repository*\n{}", self.repository_full_name) },
+                    { "type": "mrkdwn", "text": format!("*Author*\n{}", or_none(self.author.clone().unwrap_or_default())) },
+                    { "type": "mrkdwn", "text": format!("*Labels*\n{}", or_none(self.labels.join(", "))) },
+                ],
+            }),
+        ];
+        if let Some(context) = &self.repository_context {
+            blocks.push(json!({
+                "type": "context",
+                "elements": [{ "type": "mrkdwn", "text": context }],
+            }));
+        }
+        if let Some(environment) = &self.environment {
+            blocks.push(json!({
+                "type": "context",
+                "elements": [{ "type": "mrkdwn", "text": format!("*Environment* {environment}") }],
+            }));
+        }
+        if !self.summary.is_empty() {
+            blocks.push(json!({
+                "type": "section",
+                "text": { "type": "mrkdwn", "text": truncate_chars(&self.summary, MAX_SECTION_CHARS) },
+            }));
+        }
+        if !self.mentions.is_empty() {
+            blocks.push(json!({
+                "type": "section",
+                "text": { "type": "mrkdwn", "text": format!("cc {}", self.mentions.join(" ")) },
+            }));
+        }
+        if !self.suggestions.is_empty() {
+            let buttons: Vec<Value> = self
+                .suggestions
+                .iter()
+                .take(MAX_BUTTONS)
+                .enumerate()
+                .map(|(i, suggestion)| {
+                    let label = if suggestion.repository_full_name == self.repository_full_name {
+                        format!("#{}", suggestion.number)
+                    } else {
+                        format!("{}#{}", suggestion.repository_full_name, suggestion.number)
+                    };
+                    json!({
+                        "type": "button",
+                        "action_id": format!("open_suggestion_{i}"),
+                        "text": { "type": "plain_text", "text": label },
+                        "url": suggestion.html_url,
+                    })
+                })
+                .collect();
+            blocks.push(json!({
+                "type": "section",
+                "text": { "type": "mrkdwn", "text": format!("*Closest issues*\n{}", self.closest_issues.join("\n")) },
+            }));
+            blocks.push(json!({ "type": "actions", "elements": buttons }));
+        }
+        Value::Array(blocks)
+    }
+}
+
+/// e.g. `*huggingface/transformers* · ⭐ 130000 · 🤗 Transformers: ...`
+fn repository_context(metadata: &RepositoryMetadata) -> String {
+    let mut context = format!("*{}* · ⭐ {}", metadata.full_name, metadata.stars);
+    if let Some(description) = metadata.description.as_deref().filter(|d| !d.is_empty()) {
+        context.push_str(" · ");
+        context.push_str(description);
+    }
+    context
+}
+
+/// Message kept in the outbox, see [crate::slack_outbox]
+#[derive(Deserialize, Serialize)]
+struct OutboxEntry {
+    /// additional workspace the message is for, the main one when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    workspace: Option<WorkspaceTarget>,
+    #[serde(flatten)]
+    payload: OutboxPayload,
+}
+
+#[derive(Deserialize, Serialize)]
+struct WorkspaceTarget {
+    channel: String,
+    name: String,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum OutboxPayload {
+    Notification(Box<Notification>),
+    Digest { text: String },
+}
+
+fn digest_text(repository_full_name: &str, notifications: &[Notification]) -> String {
+    let repository = notifications
+        .first()
+        .and_then(|n| n.repository_context.clone())
+        .unwrap_or_else(|| repository_full_name.to_owned());
+    let mut msg = vec![format!(
+        "{} new issues in {}:",
+        notifications.len(),
+        repository
+    )];
+    for n in notifications {
+        msg.push(format!(
+            "\n*<{}|#{}> {}*\n{}",
+            n.html_url, n.number, n.title, n.summary
+        ));
+        if !n.mentions.is_empty() {
+            msg.push(format!("cc {}", n.mentions.join(" ")));
+        }
+        msg.extend(n.closest_issues.iter().cloned());
+    }
+    msg.join("\n")
+}
+
+fn client(
+    auth_token: &str,
+    http_cfg: &HttpClientConfig,
+    timeouts: &TimeoutsConfig,
+) -> Result<reqwest::Client, SlackError> {
+    let mut headers = HeaderMap::new();
+
+    let mut auth_value = HeaderValue::from_str(&format!("Bearer {auth_token}"))?;
+    auth_value.set_sensitive(true);
+    headers.insert(AUTHORIZATION, auth_value);
+
+    Ok(client_builder(http_cfg, timeouts, HttpTarget::Slack)?
+        .default_headers(headers)
+        .build()?)
+}
+
+/// see [SlackWorkspaceConfig]
+struct Workspace {
+    channel: String,
+    client: reqwest::Client,
+    name: String,
+    repository_channels: HashMap<String, String>,
+}
+
+impl Workspace {
+    fn new(
+        cfg: &SlackWorkspaceConfig,
+        http_cfg: &HttpClientConfig,
+        timeouts: &TimeoutsConfig,
+    ) -> Result<Self, SlackError> {
+        Ok(Self {
+            channel: cfg.channel.clone(),
+            client: client(&cfg.auth_token, http_cfg, timeouts)?,
+            name: cfg.name.clone(),
+            repository_channels: cfg.repository_channels.clone(),
+        })
+    }
+
+    fn channel(&self, repository_full_name: &str) -> Option<&str> {
+        self.repository_channels
+            .get(repository_full_name)
+            .or(Some(&self.channel))
+            .map(String::as_str)
+            .filter(|channel| !channel.is_empty())
+    }
+}
+
 #[derive(Clone)]
 pub struct Slack {
-    channel: String,
+    auth_test_url: String,
+    /// notifications waiting for their repository's batch window to end, unless the outbox
+    /// persisted them
+    batches: Arc<Mutex<HashMap<String, Vec<Notification>>>>,
+    batch_window: Duration,
+    /// repositories whose batch window is running
+    batch_windows: Arc<Mutex<HashSet<String>>>,
     chat_write_url: String,
     client: reqwest::Client,
+    /// holds the channel
+    live_config: LiveConfig,
+    /// keeps the notifications Slack fails to take for later
+    outbox: Option<SlackOutbox>,
+    plain_text: bool,
+    repository_context: bool,
+    /// retries of the main workspace's messages, none unless set with
+    /// [Slack::with_retry_budget]
+    retry_policy: RetryPolicy,
+    signing_secret: String,
+    workspaces: Arc<Vec<Workspace>>,
 }
 
 impl Slack {
-    pub fn new(config: &SlackConfig) -> Result<Self, SlackError> {
-        let mut headers = HeaderMap::new();
-
-        let mut auth_value = HeaderValue::from_str(&format!("Bearer {}", config.auth_token))?;
-        auth_value.set_sensitive(true);
-        headers.insert(AUTHORIZATION, auth_value);
-
-        let client = reqwest::Client::builder()
-            .default_headers(headers)
-            .build()?;
+    pub fn new(
+        config: &SlackConfig,
+        http_cfg: &HttpClientConfig,
+        timeouts: &TimeoutsConfig,
+        live_config: LiveConfig,
+        outbox: Option<SlackOutbox>,
+    ) -> Result<Self, SlackError> {
+        let workspaces = config
+            .workspaces
+            .iter()
+            .map(|cfg| Workspace::new(cfg, http_cfg, timeouts))
+            .collect::<Result<Vec<_>, _>>()?;
 
         Ok(Self {
-            channel: config.channel.to_owned(),
+            auth_test_url: config.auth_test_url.to_owned(),
+            batches: Arc::default(),
+            batch_window: Duration::from_secs(config.batch_window_secs),
+            batch_windows: Arc::default(),
             chat_write_url: config.chat_write_url.to_owned(),
-            client,
+            client: client(&config.auth_token, http_cfg, timeouts)?,
+            live_config,
+            outbox: outbox.filter(SlackOutbox::enabled),
+            plain_text: config.plain_text,
+            repository_context: config.repository_context,
+            retry_policy: RetryPolicy {
+                max_retries: 0,
+                ..RetryPolicy::new(RetryBudget::default())
+            },
+            signing_secret: config.signing_secret.clone(),
+            workspaces: workspaces.into(),
         })
     }
 
+    /// Retries the main workspace's messages within `budget`, shared with the other retry loops
+    /// of the process
+    ///
+    /// The additional workspaces aren't retried inline, their failed messages being queued in
+    /// the outbox, each on its own.
+    pub fn with_retry_budget(mut self, budget: RetryBudget) -> Self {
+        self.retry_policy = RetryPolicy::new(budget);
+        self
+    }
+
+    /// host messages are posted to, whose retries are budgeted, see [retry::RetryBudget]
+    pub fn host(&self) -> String {
+        retry::host(&self.chat_write_url)
+    }
+
+    /// checks an interaction request's `X-Slack-Signature` and `X-Slack-Request-Timestamp`
+    pub fn verify_signature(&self, timestamp: &str, body: &[u8], signature: &str) -> bool {
+        if self.signing_secret.is_empty() {
+            return false;
+        }
+        let fresh = timestamp
+            .parse::<i64>()
+            .is_ok_and(|ts| (Utc::now().timestamp() - ts).abs() <= MAX_SIGNATURE_AGE_SECS);
+        let expected = request_signature(&self.signing_secret, timestamp, body);
+        fresh && bool::from(expected.as_bytes().ct_eq(signature.as_bytes()))
+    }
+
+    /// posts a comment draft awaiting approval, returns the message's timestamp
+    pub async fn comment_draft(&self, comment: &PendingComment) -> Result<String, SlackError> {
+        let live_config = self.live_config.get();
+        let mut body = SlackBody::new(
+            &live_config.slack_channel,
+            format!("Comment draft for {}", comment.issue_url),
+            None,
+        );
+        body.blocks = Some(draft_blocks(comment));
+        Ok(self.post(&body).await?.ts)
+    }
+
+    /// replaces the message an interaction originated from with `text`
+    pub async fn respond(&self, response_url: &str, text: String) -> Result<(), SlackError> {
+        self.client
+            .post(response_url)
+            .json(&json!({ "replace_original": true, "text": text }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// alerts that `cluster`'s issues were all matched with `target`, see [EscalationConfig]
+    pub async fn escalation(
+        &self,
+        cfg: &EscalationConfig,
+        target: &ClosestIssue,
+        cluster: &[SuggestedIssue],
+    ) -> Result<(), SlackError> {
+        let mut msg = vec![format!(
+            "{} :rotating_light: {} new issues in the last {} minutes match <{}|{}#{}> {}, possibly a regression:",
+            cfg.mention,
+            cluster.len(),
+            cfg.window_minutes,
+            target.html_url,
+            target.repository_full_name,
+            target.number,
+            target.title
+        )];
+        msg.extend(cluster.iter().map(|issue| {
+            format!(
+                "• {} (<{}|{}#{}>)",
+                issue.title, issue.html_url, issue.repository_full_name, issue.number
+            )
+        }));
+        let live_config = self.live_config.get();
+        let channel = if cfg.channel.is_empty() {
+            &live_config.slack_channel
+        } else {
+            &cfg.channel
+        };
+        self.broadcast(&target.repository_full_name, channel, msg.join("\n"))
+            .await
+    }
+
+    /// announces a new security report or critical issue, see [PriorityConfig]
+    ///
+    /// The issue's body is left out and additional workspaces aren't alerted, security reports
+    /// shouldn't be spread further than needed.
+    pub async fn priority_alert(
+        &self,
+        cfg: &PriorityConfig,
+        issue: &IssueData,
+        kind: &str,
+    ) -> Result<(), SlackError> {
+        let text = format!(
+            "{} :rotating_light: New {kind} <{}|{}#{}> {}",
+            cfg.mention, issue.html_url, issue.repository_full_name, issue.number, issue.title
+        );
+        let live_config = self.live_config.get();
+        let channel = if cfg.channel.is_empty() {
+            &live_config.slack_channel
+        } else {
+            &cfg.channel
+        };
+        self.post(&SlackBody::new(channel, text, None)).await?;
+        Ok(())
+    }
+
+    /// confirms that a repository was onboarded, see [crate::onboarding]
+    pub async fn onboarding_confirmation(
+        &self,
+        report: &OnboardingReport,
+    ) -> Result<(), SlackError> {
+        let repository_full_name = &report.repository.repository_full_name;
+        let webhook = if !report.webhook.fixed {
+            "already configured"
+        } else if report.webhook.problems == [WebhookProblem::Missing] {
+            "created"
+        } else {
+            "fixed"
+        };
+        let text = format!(
+            ":white_check_mark: Onboarded <https://github.com/{repository_full_name}|{repository_full_name}>, webhook {webhook}. Indexing its ~{} issues and pull requests should take {}.",
+            report.repository.estimated_issues,
+            describe_duration(Duration::from_secs(report.estimated_secs))
+        );
+        let live_config = self.live_config.get();
+        self.broadcast(repository_full_name, &live_config.slack_channel, text)
+            .await
+    }
+
+    /// validates the tokens of every workspace without posting anything
+    pub async fn auth_test(&self) -> Result<(), SlackError> {
+        let clients = std::iter::once(&self.client)
+            .chain(self.workspaces.iter().map(|workspace| &workspace.client));
+        for client in clients {
+            let res: AuthTestResponse = client
+                .post(&self.auth_test_url)
+                .send()
+                .await?
+                .json()
+                .await?;
+            if !res.ok {
+                return Err(SlackError::Api(res.error.unwrap_or_default()));
+            }
+        }
+        Ok(())
+    }
+
+    async fn post(&self, body: &SlackBody) -> Result<PostMessageResponse, SlackError> {
+        self.post_with(&self.client, body).await
+    }
+
+    async fn post_with(
+        &self,
+        client: &reqwest::Client,
+        body: &SlackBody,
+    ) -> Result<PostMessageResponse, SlackError> {
+        let res = client.post(&self.chat_write_url).json(body).send().await?;
+        if res.status() == StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = res
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse().ok());
+            return Err(SlackError::RateLimited(retry_after));
+        }
+        Ok(res.error_for_status()?.json().await?)
+    }
+
+    /// Runs `send` once for each additional workspace notified of `repository_full_name`'s
+    /// issues, concurrently
+    ///
+    /// Their failures are queued in the outbox as `payload`, one message per workspace, rather
+    /// than returned: retrying the main workspace's message shouldn't notify the other
+    /// workspaces twice.
+    async fn fan_out<'a, F, Fut>(
+        &'a self,
+        repository_full_name: &str,
+        payload: &OutboxPayload,
+        send: F,
+    ) where
+        F: Fn(&'a reqwest::Client, &'a str) -> Fut,
+        Fut: Future<Output = Result<(), SlackError>>,
+    {
+        let sends = self.workspaces.iter().filter_map(|workspace| {
+            let channel = workspace.channel(repository_full_name)?;
+            let sent = send(&workspace.client, channel);
+            Some(async move { (workspace, channel, sent.await) })
+        });
+        for (workspace, channel, res) in join_all(sends).await {
+            if res.is_err() {
+                metrics::counter!(
+                    "issue_bot_slack_workspace_failures_total",
+                    "workspace" => workspace.name.clone()
+                )
+                .increment(1);
+            }
+            let target = WorkspaceTarget {
+                channel: channel.to_owned(),
+                name: workspace.name.clone(),
+            };
+            if let Err(err) = self
+                .defer_failed(repository_full_name, Some(target), payload, res)
+                .await
+            {
+                error!(
+                    workspace = workspace.name,
+                    err = err.to_string(),
+                    "failed to notify slack workspace"
+                );
+            }
+        }
+    }
+
+    /// posts `body` to the main workspace, retried as per [Slack::with_retry_budget]
+    async fn post_retried(&self, body: &SlackBody) -> Result<PostMessageResponse, SlackError> {
+        with_retry(&self.retry_policy, &self.host(), || self.post(body)).await
+    }
+
+    /// posts `text` to `channel` and to the additional workspaces, see [Slack::fan_out]
+    async fn broadcast(
+        &self,
+        repository_full_name: &str,
+        channel: &str,
+        text: String,
+    ) -> Result<(), SlackError> {
+        let body = SlackBody::new(channel, text.clone(), None);
+        let payload = OutboxPayload::Digest { text: text.clone() };
+        let (res, ()) = tokio::join!(
+            self.post_retried(&body),
+            self.fan_out(repository_full_name, &payload, |client, channel| {
+                let body = SlackBody::new(channel, text.clone(), None);
+                async move { self.post_with(client, &body).await.map(|_| ()) }
+            })
+        );
+        res.map(|_| ())
+    }
+
+    /// Sends the closest issues of a new issue, batched with the other notifications for the
+    /// same repository when a batch window is configured
+    ///
+    /// `repository` is only shown when `repository_context` is configured, `mentions` are added
+    /// as is and `system_info` is shown when anything was extracted.
     pub async fn closest_issues(
         &self,
         summary: String,
         issue: &IssueData,
+        repository: Option<&RepositoryMetadata>,
         closest_issues: &[ClosestIssue],
+        mentions: &[String],
+        system_info: &SystemInfo,
     ) -> Result<(), SlackError> {
-        let mut msg = vec![format!(
-            "Closest issues for <{}|#{}>:\n{}\n",
-            issue.html_url, issue.number, summary
-        )];
-        for ci in closest_issues {
-            msg.push(format!("• {} (<{}|#{}>)", ci.title, ci.html_url, ci.number));
-        }
-        let body = SlackBody::new(&self.channel, msg.join("\n"), None);
-        let res: PostMessageResponse = self
-            .client
-            .post(&self.chat_write_url)
-            .json(&body)
-            .send()
-            .await?
-            .json()
+        let repository = repository.filter(|_| self.repository_context);
+        let notification = Notification::new(
+            summary,
+            issue,
+            repository,
+            closest_issues,
+            mentions,
+            system_info,
+        );
+        if self.batch_window.is_zero() {
+            let res = self.send_notification(&notification).await;
+            return self
+                .defer_failed(
+                    &issue.repository_full_name,
+                    None,
+                    &OutboxPayload::Notification(Box::new(notification)),
+                    res,
+                )
+                .await;
+        }
+
+        self.add_to_batch(&issue.repository_full_name, notification)
             .await?;
+        self.start_batch_window(issue.repository_full_name.clone());
+        Ok(())
+    }
+
+    /// keeps a notification until its repository's batch window ends, in the outbox's database
+    /// when there is one so that restarts don't lose it
+    async fn add_to_batch(
+        &self,
+        repository_full_name: &str,
+        notification: Notification,
+    ) -> Result<(), SlackError> {
+        if let Some(outbox) = &self.outbox {
+            let payload = serde_json::to_value(&notification)?;
+            match outbox.batch(repository_full_name, &payload).await {
+                Ok(()) => return Ok(()),
+                Err(err) => error!(
+                    repository = repository_full_name,
+                    err = err.to_string(),
+                    "failed to persist batched slack notification, keeping it in memory"
+                ),
+            }
+        }
+        self.batches
+            .lock()
+            .unwrap()
+            .entry(repository_full_name.to_owned())
+            .or_default()
+            .push(notification);
+        Ok(())
+    }
+
+    async fn take_batch(
+        &self,
+        repository_full_name: &str,
+    ) -> Result<Vec<Notification>, SlackError> {
+        let persisted = match &self.outbox {
+            Some(outbox) => outbox.take_batch(repository_full_name).await?,
+            None => Vec::new(),
+        };
+        let mut notifications = self
+            .batches
+            .lock()
+            .unwrap()
+            .remove(repository_full_name)
+            .unwrap_or_default();
+        for payload in persisted {
+            notifications.push(serde_json::from_value(payload)?);
+        }
+        Ok(notifications)
+    }
+
+    /// flushes a repository's batch once its window ends, unless a window is running already
+    fn start_batch_window(&self, repository_full_name: String) {
+        if !self
+            .batch_windows
+            .lock()
+            .unwrap()
+            .insert(repository_full_name.clone())
+        {
+            return;
+        }
+        let slack = self.clone();
+        tokio::spawn(async move {
+            select! {
+                _ = sleep(slack.batch_window) => (),
+                _ = shutdown_signal() => (),
+            }
+            slack
+                .batch_windows
+                .lock()
+                .unwrap()
+                .remove(&repository_full_name);
+            if let Err(err) = slack.flush(&repository_full_name).await {
+                error!(
+                    repository = repository_full_name,
+                    err = err.to_string(),
+                    "failed to send slack digest"
+                );
+            }
+        });
+    }
+
+    /// restarts the batch windows of the notifications a previous run left batched
+    pub async fn resume_batches(&self) -> Result<(), SlackError> {
+        let Some(outbox) = &self.outbox else {
+            return Ok(());
+        };
+        for repository_full_name in outbox.batched_repositories().await? {
+            self.start_batch_window(repository_full_name);
+        }
+        Ok(())
+    }
+
+    /// sends a repository's batch, putting it back for another window when that failed for a
+    /// reason retrying may fix
+    async fn flush(&self, repository_full_name: &str) -> Result<(), SlackError> {
+        let notifications = match self.take_batch(repository_full_name).await {
+            Ok(notifications) => notifications,
+            Err(err) => {
+                self.start_batch_window(repository_full_name.to_owned());
+                return Err(err);
+            }
+        };
+        let res = self.send_batch(repository_full_name, &notifications).await;
+        if matches!(&res, Err(err) if err.retry_class() != RetryClass::Fatal) {
+            for notification in notifications {
+                self.add_to_batch(repository_full_name, notification)
+                    .await?;
+            }
+            self.start_batch_window(repository_full_name.to_owned());
+        }
+        res
+    }
+
+    async fn send_batch(
+        &self,
+        repository_full_name: &str,
+        notifications: &[Notification],
+    ) -> Result<(), SlackError> {
+        match notifications {
+            [] => Ok(()),
+            [notification] => {
+                let res = self.send_notification(notification).await;
+                self.defer_failed(
+                    repository_full_name,
+                    None,
+                    &OutboxPayload::Notification(Box::new(notification.clone())),
+                    res,
+                )
+                .await
+            }
+            notifications => {
+                let text = digest_text(repository_full_name, notifications);
+                let live_config = self.live_config.get();
+                let res = self
+                    .broadcast(
+                        repository_full_name,
+                        &live_config.slack_channel,
+                        text.clone(),
+                    )
+                    .await;
+                self.defer_failed(
+                    repository_full_name,
+                    None,
+                    &OutboxPayload::Digest { text },
+                    res,
+                )
+                .await?;
+                info!(
+                    repository = repository_full_name,
+                    issues = notifications.len(),
+                    "sent closest issues digest to slack channel"
+                );
+                Ok(())
+            }
+        }
+    }
+
+    /// Queues `payload` in the outbox for `workspace`, the main one when unset, when sending it
+    /// failed with `res` for a reason retrying may fix, `res` being returned as is otherwise
+    async fn defer_failed(
+        &self,
+        repository_full_name: &str,
+        workspace: Option<WorkspaceTarget>,
+        payload: &OutboxPayload,
+        res: Result<(), SlackError>,
+    ) -> Result<(), SlackError> {
+        let (Err(err), Some(outbox)) = (&res, &self.outbox) else {
+            return res;
+        };
+        if err.retry_class() == RetryClass::Fatal {
+            return res;
+        }
+        let workspace_name = workspace.as_ref().map(|target| target.name.clone());
+        let entry = OutboxEntry {
+            workspace,
+            payload: payload.clone(),
+        };
+        match outbox
+            .defer(repository_full_name, &serde_json::to_value(entry)?, err)
+            .await
+        {
+            Ok(()) => {
+                warn!(
+                    repository = repository_full_name,
+                    workspace = workspace_name,
+                    err = err.to_string(),
+                    "failed to notify slack, queued the notification for retry"
+                );
+                Ok(())
+            }
+            Err(outbox_err) => {
+                error!(
+                    repository = repository_full_name,
+                    err = outbox_err.to_string(),
+                    "failed to queue slack notification"
+                );
+                res
+            }
+        }
+    }
+
+    /// sends a message of the outbox again, to the single workspace it was queued for
+    pub async fn redeliver(&self, payload: &Value) -> Result<(), SlackError> {
+        let entry: OutboxEntry = serde_json::from_value(payload.clone())?;
+        let live_config = self.live_config.get();
+        let (client, channel) = match &entry.workspace {
+            Some(target) => {
+                let workspace = self
+                    .workspaces
+                    .iter()
+                    .find(|workspace| workspace.name == target.name)
+                    .ok_or_else(|| SlackError::Api(format!("unknown workspace {}", target.name)))?;
+                (&workspace.client, target.channel.as_str())
+            }
+            None => (&self.client, live_config.slack_channel.as_str()),
+        };
+        match entry.payload {
+            OutboxPayload::Notification(notification) => {
+                self.send_notification_with(client, channel, &notification)
+                    .await
+            }
+            OutboxPayload::Digest { text } => {
+                self.post_with(client, &SlackBody::new(channel, text, None))
+                    .await?;
+                Ok(())
+            }
+        }
+    }
+
+    async fn send_notification(&self, notification: &Notification) -> Result<(), SlackError> {
+        // both messages go to the same channel even if it changes in between
+        let live_config = self.live_config.get();
+        let payload = OutboxPayload::Notification(Box::new(notification.clone()));
+        let host = self.host();
+        let (res, ()) = tokio::join!(
+            with_retry(&self.retry_policy, &host, || {
+                self.send_notification_with(&self.client, &live_config.slack_channel, notification)
+            }),
+            self.fan_out(
+                &notification.repository_full_name,
+                &payload,
+                |client, channel| { self.send_notification_with(client, channel, notification) }
+            )
+        );
+        res
+    }
+
+    async fn send_notification_with(
+        &self,
+        client: &reqwest::Client,
+        channel: &str,
+        notification: &Notification,
+    ) -> Result<(), SlackError> {
+        let mut msg = Vec::new();
+        if let Some(context) = &notification.repository_context {
+            msg.push(context.clone());
+        }
+        msg.push(format!(
+            "Closest issues for <{}|#{}>:\n{}\n",
+            notification.html_url, notification.number, notification.summary
+        ));
+        if let Some(environment) = &notification.environment {
+            msg.push(format!("Environment: {environment}\n"));
+        }
+        if !notification.mentions.is_empty() {
+            msg.push(format!("cc {}\n", notification.mentions.join(" ")));
+        }
+        msg.extend(notification.closest_issues.iter().cloned());
+        let mut body = SlackBody::new(channel, msg.join("\n"), None);
+        if !self.plain_text {
+            body.blocks = Some(notification.blocks());
+        }
+        let res = self.post_with(client, &body).await?;
         let body = SlackBody::new(
-            &self.channel,
-            format!("*{}*\n---\n{}", issue.title, issue.body),
+            channel,
+            format!("*{}*\n---\n{}", notification.title, notification.body),
             Some(res.ts),
         );
-        self.client
-            .post(&self.chat_write_url)
-            .json(&body)
-            .send()
-            .await?;
+        self.post_with(client, &body).await?;
         info!("sent closest issues to slack channel:\n{}", body.text);
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        config::{load_config, IssueBotConfig, SlackWorkspaceConfig},
+        extraction::SystemInfo,
+        live_config::LiveConfig,
+        locks::Locks,
+        slack_outbox::SlackOutbox,
+        storage::Storage,
+        test_harness::test_db,
+        Action, ClosestIssue, IssueData, Source,
+    };
+
+    use super::{
+        request_signature, DraftAction, DraftDecision, Notification, Slack, SlackError, Workspace,
+    };
+
+    fn issue(number: i32) -> IssueData {
+        IssueData {
+            source_id: number.into(),
+            action: Action::Created,
+            author: None,
+            labels: Vec::new(),
+            milestone: None,
+            title: format!("issue {number}"),
+            body: String::new(),
+            is_pull_request: false,
+            number,
+            html_url: format!("https://github.com/huggingface/lor-e/issues/{number}"),
+            url: format!("https://api.github.com/repos/huggingface/lor-e/issues/{number}"),
+            repository_full_name: "huggingface/lor-e".to_owned(),
+            source: Source::Github,
+        }
+    }
+
+    /// batching Slack client whose messages can't be delivered
+    fn batching_slack(config: &IssueBotConfig, outbox: Option<SlackOutbox>) -> Slack {
+        let mut slack_config = config.slack.clone();
+        slack_config.batch_window_secs = 3600;
+        slack_config.chat_write_url = "http://127.0.0.1:1/api/chat.postMessage".to_owned();
+        Slack::new(
+            &slack_config,
+            &config.http_client,
+            &config.timeouts,
+            LiveConfig::new(config.into()),
+            outbox,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_batches_survive_restarts() {
+        let config: IssueBotConfig = load_config("ISSUE_BOT_TEST").unwrap();
+        let db = test_db().await;
+        let outbox = SlackOutbox::new(
+            config.slack.outbox.clone(),
+            db.clone(),
+            Locks::new(db.clone()),
+        );
+        let slack = batching_slack(&config, Some(outbox.clone()));
+        for number in 1..=2 {
+            slack
+                .closest_issues(
+                    format!("summary {number}"),
+                    &issue(number),
+                    None,
+                    &[],
+                    &[],
+                    &SystemInfo::default(),
+                )
+                .await
+                .unwrap();
+        }
+        assert!(slack.batches.lock().unwrap().is_empty());
+        assert_eq!(
+            db.slack_batch_repositories().await.unwrap(),
+            vec!["huggingface/lor-e"]
+        );
+
+        let restarted = batching_slack(&config, Some(outbox));
+        let numbers: Vec<i32> = restarted
+            .take_batch("huggingface/lor-e")
+            .await
+            .unwrap()
+            .iter()
+            .map(|notification| notification.number)
+            .collect();
+        assert_eq!(numbers, vec![1, 2]);
+        assert!(db.slack_batch_repositories().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failed_flush_keeps_batch() {
+        let config: IssueBotConfig = load_config("ISSUE_BOT_TEST").unwrap();
+        let slack = batching_slack(&config, None);
+        for number in 1..=2 {
+            let notification = Notification::new(
+                format!("summary {number}"),
+                &issue(number),
+                None,
+                &[],
+                &[],
+                &SystemInfo::default(),
+            );
+            slack
+                .add_to_batch("huggingface/lor-e", notification)
+                .await
+                .unwrap();
+        }
+
+        let res = slack.flush("huggingface/lor-e").await;
+        assert!(matches!(res, Err(SlackError::HttpClient(_))));
+        assert_eq!(slack.batches.lock().unwrap()["huggingface/lor-e"].len(), 2);
+        assert!(slack
+            .batch_windows
+            .lock()
+            .unwrap()
+            .contains("huggingface/lor-e"));
+    }
+
+    #[tokio::test]
+    async fn test_workspaces_queued_separately() {
+        let config: IssueBotConfig = load_config("ISSUE_BOT_TEST").unwrap();
+        let db = test_db().await;
+        let outbox = SlackOutbox::new(
+            config.slack.outbox.clone(),
+            db.clone(),
+            Locks::new(db.clone()),
+        );
+        let mut slack_config = config.slack.clone();
+        slack_config.chat_write_url = "http://127.0.0.1:1/api/chat.postMessage".to_owned();
+        slack_config.workspaces = vec![SlackWorkspaceConfig {
+            auth_token: "xoxb-partner".to_owned(),
+            channel: "#issues".to_owned(),
+            name: "partner".to_owned(),
+            repository_channels: Default::default(),
+        }];
+        let slack = Slack::new(
+            &slack_config,
+            &config.http_client,
+            &config.timeouts,
+            LiveConfig::new((&config).into()),
+            Some(outbox),
+        )
+        .unwrap();
+        slack
+            .closest_issues(
+                "summary".to_owned(),
+                &issue(1),
+                None,
+                &[],
+                &[],
+                &SystemInfo::default(),
+            )
+            .await
+            .unwrap();
+
+        let mut workspaces: Vec<Option<String>> = db
+            .due_slack_messages(10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|message| {
+                message.payload["workspace"]["name"]
+                    .as_str()
+                    .map(str::to_owned)
+            })
+            .collect();
+        workspaces.sort();
+        assert_eq!(workspaces, vec![None, Some("partner".to_owned())]);
+    }
+
+    #[test]
+    fn test_workspace_channel() {
+        let mut workspace = Workspace {
+            channel: "#issues".to_owned(),
+            client: reqwest::Client::new(),
+            name: "partner".to_owned(),
+            repository_channels: [(
+                "huggingface/transformers".to_owned(),
+                "#transformers".to_owned(),
+            )]
+            .into(),
+        };
+        assert_eq!(
+            workspace.channel("huggingface/transformers"),
+            Some("#transformers")
+        );
+        assert_eq!(workspace.channel("huggingface/lor-e"), Some("#issues"));
+        workspace.channel.clear();
+        assert_eq!(workspace.channel("huggingface/lor-e"), None);
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let mut config: IssueBotConfig = load_config("ISSUE_BOT_TEST").unwrap();
+        config.slack.signing_secret = "8f742231b10e8888abcd99yyyzzz85a5".to_owned();
+        let slack = batching_slack(&config, None);
+        let timestamp = chrono::Utc::now().timestamp().to_string();
+        let body = b"payload=%7B%7D";
+        let signature = request_signature(&config.slack.signing_secret, &timestamp, body);
+        assert!(slack.verify_signature(&timestamp, body, &signature));
+        assert!(!slack.verify_signature(&timestamp, b"payload=%7B%22a%22%7D", &signature));
+        assert!(!slack.verify_signature(&timestamp, body, &signature[..signature.len() - 1]));
+        let stale = (chrono::Utc::now().timestamp() - 3_600).to_string();
+        let stale_signature = request_signature(&config.slack.signing_secret, &stale, body);
+        assert!(!slack.verify_signature(&stale, body, &stale_signature));
+    }
+
+    #[test]
+    fn test_draft_action() {
+        // example from https://api.slack.com/authentication/verifying-requests-from-slack
+        assert_eq!(
+            request_signature(
+                "8f742231b10e8888abcd99yyyzzz85a5",
+                "1531420618",
+                b"token=xyzz0WbapA4vBCDEFasx0q6G&team_id=T1DC2JH3J&team_domain=testteamnow&channel_id=G8PSS9T3V&channel_name=foobar&user_id=U2CERLKJA&user_name=roadrunner&command=%2Fwebhook-collect&text=&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2FT1DC2JH3J%2F397700885554%2F96rGlfmibIGlgcZRskXaIFfN&trigger_id=398738663015.47445629121.803a0bc887a14d10d2c447fce8b6703c",
+            ),
+            "v0=a2114d57b48eac39b9ad189dd8316235a7b4a8d21a10bd27519666489c69b503"
+        );
+
+        let payload = json!({
+            "type": "block_actions",
+            "user": { "id": "U123", "username": "maintainer" },
+            "response_url": "https://hooks.slack.com/actions/T1/1/abc",
+            "actions": [{ "action_id": "discard_comment", "value": "42" }],
+        });
+        let action = DraftAction::from_payload(&payload.to_string())
+            .unwrap()
+            .unwrap();
+        assert_eq!(action.comment_id, 42);
+        assert_eq!(action.decision, DraftDecision::Discard);
+        assert_eq!(action.user, "maintainer");
+
+        let other = json!({
+            "user": { "id": "U123" },
+            "response_url": "https://hooks.slack.com/actions/T1/1/abc",
+            "actions": [{ "action_id": "something_else", "value": "42" }],
+        });
+        assert!(DraftAction::from_payload(&other.to_string())
+            .unwrap()
+            .is_none());
+
+        // url buttons come without a value
+        let link = json!({
+            "user": { "id": "U123" },
+            "response_url": "https://hooks.slack.com/actions/T1/1/abc",
+            "actions": [{ "action_id": "open_suggestion_0", "url": "https://github.com/huggingface/lor-e/issues/1" }],
+        });
+        assert!(DraftAction::from_payload(&link.to_string())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_notification_blocks() {
+        let issue = IssueData {
+            source_id: 4321,
+            action: Action::Created,
+            author: Some("octocat".to_owned()),
+            labels: vec!["bug".to_owned(), "tokenizers".to_owned()],
+            milestone: None,
+            title: "Tokenizer crashes on empty input".to_owned(),
+            body: "Calling the tokenizer with an empty string panics.".to_owned(),
+            is_pull_request: false,
+            number: 12,
+            html_url: "https://github.com/huggingface/lor-e/issues/12".to_owned(),
+            url: "https://api.github.com/repos/huggingface/lor-e/issues/12".to_owned(),
+            repository_full_name: "huggingface/lor-e".to_owned(),
+            source: Source::Github,
+        };
+        let closest_issue = |repository_full_name: &str, number| ClosestIssue {
+            title: "Empty input panics".to_owned(),
+            number,
+            html_url: format!("https://github.com/{repository_full_name}/issues/{number}"),
+            labels: Vec::new(),
+            repository_full_name: repository_full_name.to_owned(),
+            cosine_similarity: 0.9,
+            boost: 0.,
+            embedding: Vec::new(),
+        };
+        let notification = Notification::new(
+            "Tokenizer panics on empty strings".to_owned(),
+            &issue,
+            None,
+            &[
+                closest_issue("huggingface/lor-e", 3),
+                closest_issue("huggingface/tokenizers", 7),
+            ],
+            &[],
+            &SystemInfo::default(),
+        );
+        let blocks = notification.blocks();
+        let blocks = blocks.as_array().unwrap();
+        assert_eq!(
+            blocks[0]["text"]["text"],
+            "#12 Tokenizer crashes on empty input"
+        );
+        assert_eq!(blocks[1]["fields"][1]["text"], "*Author*\noctocat");
+        assert_eq!(blocks[1]["fields"][2]["text"], "*Labels*\nbug, tokenizers");
+        assert_eq!(
+            blocks[2]["text"]["text"],
+            "Tokenizer panics on empty strings"
+        );
+        let buttons = blocks.last().unwrap()["elements"].as_array().unwrap();
+        assert_eq!(buttons[0]["text"]["text"], "#3");
+        assert_eq!(buttons[1]["text"]["text"], "huggingface/tokenizers#7");
+        assert_eq!(
+            buttons[1]["url"],
+            "https://github.com/huggingface/tokenizers/issues/7"
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use std::time::Duration;
+
+use futures::pin_mut;
+use serde_json::Value;
+use tokio::{select, time::interval};
+use tracing::{error, info, warn};
+
+use crate::{
+    config::SlackOutboxConfig,
+    locks::Locks,
+    retry::{backoff, Classify, RetryBudget, RetryClass},
+    shutdown_signal,
+    slack::{Slack, SlackError},
+    storage::{Database, Storage, StorageError},
+};
+
+const BATCH_SIZE: i64 = 20;
+const LOCK_NAME: &str = "slack_outbox";
+/// delay before the second attempt, doubled on every failed one
+const BASE_DELAY: Duration = Duration::from_secs(30);
+const MAX_DELAY: Duration = Duration::from_secs(3600);
+
+/// Persisted Slack notifications whose delivery failed, retried until Slack takes them or they
+/// expire, see [SlackOutboxConfig]
+///
+/// Each message targets a single workspace, a failure of the main one leaving the additional
+/// ones, which get their own messages, alone.
+#[derive(Clone)]
+pub struct SlackOutbox {
+    cfg: SlackOutboxConfig,
+    db: Database,
+    locks: Locks,
+    retry_budget: RetryBudget,
+}
+
+impl SlackOutbox {
+    pub fn new(cfg: SlackOutboxConfig, db: Database, locks: Locks) -> Self {
+        Self {
+            cfg,
+            db,
+            locks,
+            retry_budget: RetryBudget::default(),
+        }
+    }
+
+    /// redeliveries, all of them retries, draw from the budget shared with the other retry loops
+    /// of the process, a private one otherwise
+    pub fn with_retry_budget(mut self, budget: RetryBudget) -> Self {
+        self.retry_budget = budget;
+        self
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.cfg.enabled
+    }
+
+    /// queues `payload`, which Slack failed to take with `err`
+    pub async fn defer(
+        &self,
+        repository_full_name: &str,
+        payload: &Value,
+        err: &SlackError,
+    ) -> Result<(), StorageError> {
+        self.db
+            .enqueue_slack_message(repository_full_name, payload, &err.to_string())
+            .await?;
+        metrics::counter!("issue_bot_slack_outbox_total", "outcome" => "queued").increment(1);
+        Ok(())
+    }
+
+    /// persists a notification waiting for its repository's batch window to end
+    pub async fn batch(
+        &self,
+        repository_full_name: &str,
+        payload: &Value,
+    ) -> Result<(), StorageError> {
+        self.db
+            .add_to_slack_batch(repository_full_name, payload)
+            .await
+    }
+
+    /// removes and returns the notifications batched for a repository, oldest first
+    pub async fn take_batch(&self, repository_full_name: &str) -> Result<Vec<Value>, StorageError> {
+        self.db.take_slack_batch(repository_full_name).await
+    }
+
+    /// repositories whose batch was left pending by a previous run
+    pub async fn batched_repositories(&self) -> Result<Vec<String>, StorageError> {
+        self.db.slack_batch_repositories().await
+    }
+
+    async fn process(&self, slack: &Slack) -> Result<(), StorageError> {
+        let expired = self.db.expire_slack_messages(self.cfg.ttl_minutes).await?;
+        if expired > 0 {
+            metrics::counter!("issue_bot_slack_outbox_total", "outcome" => "expired")
+                .increment(expired);
+            warn!(expired, "dropped slack notifications queued for too long");
+        }
+        let host = slack.host();
+        for message in self.db.due_slack_messages(BATCH_SIZE).await? {
+            // shed messages stay due, the next poll picking them up once the budget refilled
+            if !self.retry_budget.withdraw(&host) {
+                break;
+            }
+            let err = match slack.redeliver(&message.payload).await {
+                Ok(()) => {
+                    self.retry_budget.deposit(&host);
+                    self.db.delete_slack_message(message.id).await?;
+                    metrics::counter!("issue_bot_slack_outbox_total", "outcome" => "delivered")
+                        .increment(1);
+                    info!(
+                        repository = message.repository_full_name,
+                        attempts = message.attempts + 1,
+                        "delivered queued slack notification"
+                    );
+                    continue;
+                }
+                Err(err) => err,
+            };
+            if err.retry_class() == RetryClass::Fatal
+                || message.attempts + 1 >= self.cfg.max_attempts
+            {
+                self.db.delete_slack_message(message.id).await?;
+                metrics::counter!("issue_bot_slack_outbox_total", "outcome" => "dropped")
+                    .increment(1);
+                error!(
+                    repository = message.repository_full_name,
+                    attempts = message.attempts + 1,
+                    err = err.to_string(),
+                    "dropping queued slack notification"
+                );
+                continue;
+            }
+            let delay = err
+                .retry_after()
+                .unwrap_or_else(|| backoff(BASE_DELAY, MAX_DELAY, message.attempts));
+            self.db
+                .reschedule_slack_message(message.id, delay.as_secs() as i64, &err.to_string())
+                .await?;
+            warn!(
+                repository = message.repository_full_name,
+                delay_secs = delay.as_secs(),
+                err = err.to_string(),
+                "failed to deliver queued slack notification, will retry"
+            );
+            // the rest of the batch would be rate limited just the same
+            if err.retry_class() == RetryClass::RateLimited {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Retries the queued notifications, only one instance of the bot does so at a time
+pub async fn start_slack_outbox(outbox: SlackOutbox, slack: Slack) -> anyhow::Result<()> {
+    if !outbox.cfg.enabled {
+        return Ok(());
+    }
+
+    info!("starting slack outbox");
+    let mut ticker = interval(Duration::from_secs(outbox.cfg.poll_interval_secs));
+    let shutdown = shutdown_signal();
+    pin_mut!(shutdown);
+    loop {
+        select! {
+            _ = ticker.tick() => {
+                match outbox.locks.try_acquire(LOCK_NAME).await {
+                    Ok(Some(lease)) => {
+                        if let Err(err) = outbox.process(&slack).await {
+                            error!(err = err.to_string(), "error processing slack outbox");
+                        }
+                        if let Err(err) = lease.release().await {
+                            error!(err = err.to_string(), "failed to release slack outbox lock");
+                        }
+                    }
+                    Ok(None) => (),
+                    Err(err) => error!(err = err.to_string(), "failed to acquire slack outbox lock"),
+                }
+            }
+            _ = &mut shutdown => break,
+        }
+    }
+    Ok(())
+}
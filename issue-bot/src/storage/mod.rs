@@ -0,0 +1,2460 @@
+use std::{collections::HashMap, str::FromStr, time::Duration};
+
+use async_stream::try_stream;
+use chrono::{DateTime, Utc};
+use futures::Stream;
+use pgvector::Vector;
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgListener, prelude::FromRow};
+use thiserror::Error;
+
+use crate::{
+    config::{DatabaseConfig, IssueState},
+    embeddings::EmbeddingMetadata,
+    extraction::SystemInfo,
+    github::IssueWithComments,
+    issue_forms::IssueForm,
+    ClosestIssue, CommentData, IssueData, Source, Vote,
+};
+
+pub mod postgres;
+pub mod sqlite;
+
+use postgres::PgStorage;
+use sqlite::SqliteStorage;
+
+/// `name` quoted for use as an identifier in statements that can't take parameters
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// size of the `halfvec` embedding columns of init_db.sql, and of their `bit` quantization
+pub const EMBEDDING_DIMENSIONS: usize = 2560;
+
+/// tables referencing a repository by its `repository_full_name`, see [Storage::move_repository]
+const REPOSITORY_TABLES: &[&str] = &[
+    "archived_issues",
+    "duplicate_resolutions",
+    "escalations",
+    "event_log",
+    "guidance_sections",
+    "issue_links",
+    "knowledge_base",
+    "link_similarities",
+    "pending_comments",
+    "slack_batches",
+    "slack_outbox",
+    "suggestion_targets",
+    "suggestions",
+];
+
+/// tables holding a row per repository, keyed by the given column, see [Storage::move_repository]
+const REPOSITORY_KEYED_TABLES: &[(&str, &str)] = &[
+    ("jobs", "repository_full_name"),
+    ("onboarded_repositories", "repository_full_name"),
+    ("private_repositories", "repository_full_name"),
+    ("repository_cursors", "repository_full_name"),
+    ("repository_metadata", "full_name"),
+];
+
+/// columns holding the urls of issues and of the bot's comments on them, see
+/// [Storage::move_repository]
+const ISSUE_URL_COLUMNS: &[(&str, &[&str])] = &[
+    ("bot_comment_revisions", &["issue_url", "comment_url"]),
+    ("bot_comments", &["issue_url", "comment_url"]),
+    (
+        "closure_proposals",
+        &["issue_url", "duplicate_of_html_url", "comment_url"],
+    ),
+    ("comment_idempotency_keys", &["issue_url"]),
+    ("comment_runs", &["issue_url"]),
+    ("pending_comments", &["issue_url"]),
+    ("suggestion_targets", &["issue_html_url"]),
+];
+
+#[derive(Debug, Error)]
+pub enum StorageError {
+    #[error("invalid event outcome: '{0}'")]
+    InvalidEventOutcome(String),
+    #[error("invalid issue link kind: '{0}'")]
+    InvalidIssueLinkKind(String),
+    #[error("invalid repository name: '{0}'")]
+    InvalidRepositoryName(String),
+    #[error("missing '{0}' postgres extension, run `CREATE EXTENSION {0};` on the database")]
+    MissingExtension(&'static str),
+    #[error("missing '{index}' index, run `psql -f migrations/{migration}` on the database")]
+    MissingIndex {
+        index: &'static str,
+        migration: &'static str,
+    },
+    #[error("serde json error: {0}")]
+    SerdeJson(#[from] serde_json::Error),
+    #[error("sqlx error: {0}")]
+    Sqlx(#[from] sqlx::Error),
+    #[error("read replicas are only supported with postgres")]
+    UnsupportedReadReplica,
+    #[error("unsupported database connection string scheme: '{0}'")]
+    UnsupportedScheme(String),
+    #[error(
+        "'{extension}' postgres extension {found} is too old, {required} is needed for {feature}"
+    )]
+    UnsupportedExtensionVersion {
+        extension: &'static str,
+        feature: &'static str,
+        found: String,
+        required: &'static str,
+    },
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub enum JobData {
+    // FIXME: naming is a bit confusing, this means "repository issue indexation"
+    IssueIndexation {
+        next_url: String,
+        /// defaults to GitHub for jobs saved before the source was recorded
+        #[serde(default)]
+        source: Source,
+    },
+    EmbeddingsRegeneration {
+        current_issue: i32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, sqlx::Type)]
+#[sqlx(type_name = "job_type", rename_all = "snake_case")]
+pub enum JobType {
+    // FIXME: naming is a bit confusing, this means "repository issue indexation"
+    IssueIndexation,
+    EmbeddingsRegeneration,
+}
+
+impl JobType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::IssueIndexation => "issue_indexation",
+            Self::EmbeddingsRegeneration => "embeddings_regeneration",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, sqlx::Type)]
+#[sqlx(type_name = "closure_proposal_status", rename_all = "snake_case")]
+pub enum ClosureProposalStatus {
+    Pending,
+    Approved,
+    Expired,
+}
+
+impl ClosureProposalStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Approved => "approved",
+            Self::Expired => "expired",
+        }
+    }
+}
+
+/// How two issues of a repository are related, from the GitHub timeline
+#[derive(Clone, Copy, Debug, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "issue_link_kind", rename_all = "snake_case")]
+pub enum IssueLinkKind {
+    /// the linked issue mentions the issue
+    CrossReference,
+    /// the linked pull request mentions the issue, usually to fix it
+    FixedBy,
+    /// the issue was marked as a duplicate of the linked one
+    Duplicate,
+}
+
+impl IssueLinkKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::CrossReference => "cross_reference",
+            Self::FixedBy => "fixed_by",
+            Self::Duplicate => "duplicate",
+        }
+    }
+}
+
+impl FromStr for IssueLinkKind {
+    type Err = StorageError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cross_reference" => Ok(Self::CrossReference),
+            "fixed_by" => Ok(Self::FixedBy),
+            "duplicate" => Ok(Self::Duplicate),
+            _ => Err(StorageError::InvalidIssueLinkKind(s.to_owned())),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct IssueLink {
+    pub number: i32,
+    pub linked_number: i32,
+    pub kind: IssueLinkKind,
+}
+
+/// Extracted environment of a stored issue compared when boosting candidates, see
+/// [crate::extraction::SystemInfo]
+#[derive(Debug, FromRow)]
+pub struct IssueCohort {
+    pub number: i32,
+    pub package_version: Option<String>,
+    pub accelerator: Option<String>,
+}
+
+/// How the handling of an event ended, see [crate::event_log]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "event_outcome", rename_all = "snake_case")]
+pub enum EventOutcome {
+    /// the queued comment was posted on the issue
+    Commented,
+    /// a comment was queued on the issue, until the comment queue posts it
+    Queued,
+    /// the issue got no comment, e.g. no similar enough issue was found
+    Skipped,
+    Error,
+    /// events other than new issues, which are never commented on
+    Handled,
+}
+
+impl EventOutcome {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Commented => "commented",
+            Self::Queued => "queued",
+            Self::Skipped => "skipped",
+            Self::Error => "error",
+            Self::Handled => "handled",
+        }
+    }
+}
+
+impl FromStr for EventOutcome {
+    type Err = StorageError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "commented" => Ok(Self::Commented),
+            "queued" => Ok(Self::Queued),
+            "skipped" => Ok(Self::Skipped),
+            "error" => Ok(Self::Error),
+            "handled" => Ok(Self::Handled),
+            _ => Err(StorageError::InvalidEventOutcome(s.to_owned())),
+        }
+    }
+}
+
+/// Audit record of an event handled by the webhooks worker, stage durations are in milliseconds
+#[derive(Clone, Debug, Serialize)]
+pub struct EventLogEntry {
+    pub event_type: String,
+    pub description: String,
+    pub source_id: Option<i64>,
+    pub repository_full_name: Option<String>,
+    pub issue_number: Option<i32>,
+    pub request_id: String,
+    pub outcome: EventOutcome,
+    /// run of the comment queued for the event
+    pub run_id: Option<String>,
+    pub error: Option<String>,
+    pub embed_ms: Option<i64>,
+    pub search_ms: Option<i64>,
+    pub summarize_ms: Option<i64>,
+    pub comment_ms: Option<i64>,
+    pub total_ms: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Criteria of an event log lookup, unset ones match every entry
+#[derive(Debug, Default)]
+pub struct EventLogFilter {
+    pub repository_full_name: Option<String>,
+    pub issue_number: Option<i32>,
+    pub source_id: Option<i64>,
+    pub request_id: Option<String>,
+}
+
+/// Issue matching a search query, see [crate::search]
+#[derive(Clone, Debug, FromRow, Serialize)]
+pub struct SearchHit {
+    pub id: i32,
+    pub source_id: i64,
+    pub title: String,
+    pub number: i32,
+    pub html_url: String,
+    pub labels: Vec<String>,
+    pub repository_full_name: String,
+    pub is_pull_request: bool,
+    /// only fetched when requested
+    pub body: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub comment_count: i64,
+    pub cosine_similarity: f64,
+    /// see [crate::extraction::SystemInfo]
+    pub accelerator: Option<String>,
+    pub os: Option<String>,
+}
+
+/// Issue suggested in a posted comment, next to the issue it was suggested on, see
+/// [Storage::recent_suggestions]
+#[derive(Debug, FromRow)]
+pub struct RecentSuggestion {
+    /// when the comment was posted
+    pub created_at: DateTime<Utc>,
+    pub issue_source_id: i64,
+    pub issue_repository_full_name: String,
+    pub issue_number: i32,
+    pub issue_title: String,
+    pub issue_html_url: String,
+    pub repository_full_name: String,
+    pub number: i32,
+    pub title: String,
+    pub html_url: String,
+    pub cosine_similarity: f64,
+}
+
+/// Counts of the stored issues of a repository
+#[derive(Debug, FromRow)]
+pub struct RepositoryStats {
+    pub repository_full_name: String,
+    pub issues: i64,
+    pub pull_requests: i64,
+    pub excluded: i64,
+}
+
+/// Snapshot of an issue taken before deleting it
+#[derive(Debug, Serialize)]
+pub struct ArchivedIssue {
+    pub source_id: i64,
+    pub source: String,
+    pub title: String,
+    pub body: String,
+    pub is_pull_request: bool,
+    pub number: i32,
+    pub html_url: String,
+    pub url: String,
+    pub repository_full_name: String,
+    pub labels: Vec<String>,
+    pub milestone: Option<String>,
+    /// kept so that the snapshot is purged when the author opts out, see [Storage::opt_out_author]
+    pub author: Option<String>,
+    pub comments: Vec<ArchivedComment>,
+    pub embedding: Vec<f32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ArchivedComment {
+    pub source_id: i64,
+    pub body: String,
+    pub url: String,
+    pub author: Option<String>,
+}
+
+/// Issue content used to (re)compute its embedding
+pub struct IssueText {
+    pub repository_full_name: String,
+    pub labels: Vec<String>,
+    pub title: String,
+    pub body: String,
+    pub comments: Vec<String>,
+    /// snippets of the files the issue references, see [crate::code_context]
+    pub code_context: Option<String>,
+}
+
+/// Issue replayed by [crate::evaluation::run_evaluation]
+pub struct IssueEmbedding {
+    pub number: i32,
+    pub embedding: Vec<f32>,
+}
+
+/// Where to reply to a stored issue
+#[derive(Debug, FromRow)]
+pub struct StoredIssue {
+    pub source: String,
+    pub repository_full_name: String,
+    pub number: i32,
+    pub url: String,
+}
+
+pub struct StoredIssueId {
+    pub id: i32,
+    pub source_id: i64,
+}
+
+/// Body of a stored issue, see [Storage::unfingerprinted_issues]
+#[derive(Debug, FromRow)]
+pub struct IssueBody {
+    pub id: i32,
+    pub source_id: i64,
+    pub body: String,
+}
+
+/// Issue as served by [crate::routes::export_issues]
+#[derive(Debug, FromRow, Serialize)]
+pub struct ExportedIssue {
+    /// where the next page starts, see [Storage::export_issues]
+    #[serde(skip)]
+    pub id: i32,
+    pub source_id: i64,
+    pub source: String,
+    pub repository_full_name: String,
+    pub number: i32,
+    pub title: String,
+    pub body: String,
+    pub html_url: String,
+    pub is_pull_request: bool,
+    pub labels: Vec<String>,
+    pub milestone: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Issue found by [Storage::closest_hot_issues]
+#[derive(Debug)]
+pub struct IssueSimilarity {
+    pub source_id: i64,
+    pub cosine_similarity: f64,
+    /// left out for the issues the caller already has
+    pub issue: Option<HotIssue>,
+}
+
+/// What [crate::hot_issues::HotIssues] caches of a matched issue
+#[derive(Clone, Debug, FromRow)]
+pub struct HotIssue {
+    pub source_id: i64,
+    pub title: String,
+    pub number: i32,
+    pub html_url: String,
+    pub labels: Vec<String>,
+    pub repository_full_name: String,
+    #[sqlx(try_from = "Vector")]
+    pub embedding: Vec<f32>,
+}
+
+/// New issue that was matched with a given issue, see [crate::escalation::Escalation]
+#[derive(Debug, FromRow)]
+pub struct SuggestedIssue {
+    pub repository_full_name: String,
+    pub number: i32,
+    pub html_url: String,
+    pub title: String,
+}
+
+/// Issue suggested in a comment, see [crate::resolutions::DuplicateResolutions]
+#[derive(Debug, FromRow)]
+pub struct Suggestion {
+    pub repository_full_name: String,
+    pub number: i32,
+    /// 0-based position in the comment
+    pub rank: i32,
+}
+
+/// Issue closed as a duplicate by a maintainer, compared with what the bot suggested for it
+#[derive(Debug)]
+pub struct DuplicateResolution {
+    pub issue_source_id: i64,
+    pub repository_full_name: String,
+    pub number: i32,
+    pub duplicate_of_number: i32,
+    pub had_suggestions: bool,
+    /// rank of the duplicate among the suggestions, `None` when it wasn't suggested
+    pub suggested_rank: Option<i32>,
+}
+
+/// Cosine similarity of two linked issues of a repository, see [crate::drift]
+#[derive(Clone, Debug, FromRow, PartialEq, Serialize)]
+pub struct LinkSimilarity {
+    pub repository_full_name: String,
+    pub number: i32,
+    pub linked_number: i32,
+    pub cosine_similarity: f64,
+}
+
+/// Last time a webhook of a GitHub repository was received, see [crate::catch_up::CatchUp]
+#[derive(Debug, FromRow)]
+pub struct RepositoryCursor {
+    pub repository_full_name: String,
+    pub processed_at: DateTime<Utc>,
+}
+
+/// How the stored embedding of an issue was generated, see [EmbeddingMetadata]
+#[derive(Debug, FromRow, Serialize)]
+pub struct EmbeddingRecord {
+    pub source_id: i64,
+    pub model: String,
+    pub endpoint: String,
+    pub input_chars: i32,
+    pub input_tokens: Option<i32>,
+    pub truncated: bool,
+    /// see [crate::issue_text::IssueTextComposer::template_version]
+    pub template: Option<String>,
+    pub embedded_at: DateTime<Utc>,
+}
+
+/// Author's request to be left out of suggestions and exports, see [crate::opt_out]
+#[derive(Debug, Serialize)]
+pub struct OptOutRequest {
+    pub id: i32,
+    pub login: String,
+    /// `api` or `comment`
+    pub requested_through: String,
+    pub purged: bool,
+    /// issues and comments of the author flagged, or deleted when purged
+    pub issues: i64,
+    pub comments: i64,
+    pub requested_at: DateTime<Utc>,
+}
+
+/// Repository onboarded through `POST /onboard`, see [crate::onboarding]
+#[derive(Debug, FromRow, Serialize)]
+pub struct OnboardedRepository {
+    pub repository_full_name: String,
+    pub hook_id: Option<i64>,
+    /// issues and pull requests the backfill was estimated to index
+    pub estimated_issues: i64,
+    pub onboarded_at: DateTime<Utc>,
+}
+
+/// Comments of the comment queue, see [crate::queue_metrics]
+#[derive(Debug, Default, FromRow)]
+pub struct CommentBacklog {
+    pub queued: i64,
+    pub awaiting_approval: i64,
+    /// age of the oldest comment waiting to be posted
+    pub oldest_queued_secs: Option<f64>,
+}
+
+/// Body the bot's comment on an issue had at some point, see [Storage::save_bot_comment]
+#[derive(Debug, FromRow, Serialize)]
+pub struct BotCommentRevision {
+    pub comment_url: String,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Notifications waiting in the Slack outbox, see [crate::queue_metrics]
+#[derive(Debug, Default, FromRow)]
+pub struct SlackOutboxBacklog {
+    pub messages: i64,
+    pub oldest_secs: Option<f64>,
+}
+
+/// Size and churn of the issues table, see [crate::compaction]
+#[derive(Clone, Copy, Debug, FromRow)]
+pub struct TableHealth {
+    pub live_tuples: i64,
+    pub dead_tuples: i64,
+    /// rows deleted or updated outside of HOT updates since statistics were reset, each one
+    /// leaving a stale entry in the indexes
+    pub index_churn: i64,
+    pub table_bytes: i64,
+    pub index_bytes: i64,
+}
+
+/// Vacuum or reindex of the issues table, see [crate::compaction]
+#[derive(Debug, FromRow)]
+pub struct CompactionRun {
+    pub action: String,
+    pub dead_tuples: i64,
+    /// [TableHealth::index_churn] when the run started
+    pub index_churn: i64,
+    pub table_bytes_before: i64,
+    pub table_bytes_after: i64,
+    pub index_bytes_before: i64,
+    pub index_bytes_after: i64,
+    pub duration_ms: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Resumable job, see [crate::job_control]
+#[derive(Debug, Serialize)]
+pub struct JobState {
+    pub id: i32,
+    pub job_type: String,
+    pub repository_full_name: Option<String>,
+    pub data: JobData,
+    pub paused: bool,
+}
+
+/// Resumable jobs of a type, see [crate::queue_metrics]
+#[derive(Debug, FromRow)]
+pub struct JobBacklog {
+    pub job_type: String,
+    pub jobs: i64,
+    /// time since the least recently updated job progressed
+    pub stalled_secs: f64,
+}
+
+/// Events of a type handled with the same outcome, see [crate::queue_metrics]
+#[derive(Debug, FromRow)]
+pub struct EventStats {
+    pub event_type: String,
+    pub outcome: String,
+    pub events: i64,
+    pub avg_total_ms: f64,
+}
+
+/// Comment waiting to be posted, see [crate::comment_queue::CommentQueue]
+pub struct PendingComment {
+    pub id: i32,
+    pub source: String,
+    pub repository_full_name: String,
+    pub issue_url: String,
+    pub body: String,
+    /// the bot's answer to the issue, edited in place rather than posted again when enabled, see
+    /// [crate::config::CommentQueueConfig::update_in_place]
+    pub suggestions: bool,
+    /// failed attempts at posting the comment
+    pub attempts: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Slack notification whose delivery failed, retried by [crate::slack_outbox::SlackOutbox]
+#[derive(Debug)]
+pub struct SlackOutboxMessage {
+    pub id: i32,
+    pub repository_full_name: String,
+    pub payload: serde_json::Value,
+    pub attempts: i32,
+}
+
+/// Last response to a GitHub GET request, see [crate::response_cache::ResponseCache]
+#[derive(Debug)]
+pub struct CachedResponse {
+    pub etag: String,
+    /// pagination of listings
+    pub link: Option<String>,
+    pub body: Vec<u8>,
+}
+
+/// GitHub metadata of an indexed repository, see [crate::repo_metadata::RepoMetadata]
+#[derive(Clone, Debug)]
+pub struct RepositoryMetadata {
+    pub full_name: String,
+    pub description: Option<String>,
+    pub stars: i64,
+    pub topics: Vec<String>,
+}
+
+/// Key authorizing calls to the API, see [crate::api_keys::ApiKeys]
+#[derive(Debug, Serialize)]
+pub struct ApiKey {
+    pub id: i32,
+    pub name: String,
+    /// comma separated [crate::api_keys::Scope]s
+    pub scopes: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Curated answer, see [crate::knowledge_base::KnowledgeBase]
+#[derive(Debug, Serialize)]
+pub struct KnowledgeBaseEntry {
+    pub id: i32,
+    /// `None` for entries applying to every repository
+    pub repository_full_name: Option<String>,
+    pub question: String,
+    pub answer: String,
+}
+
+pub struct KnowledgeBaseMatch {
+    pub entry: KnowledgeBaseEntry,
+    pub cosine_similarity: f64,
+}
+
+/// Section of a repository's README, CONTRIBUTING or issue templates, see [crate::guidance]
+#[derive(Clone, Debug, PartialEq)]
+pub struct GuidanceSection {
+    pub repository_full_name: String,
+    pub path: String,
+    pub heading: String,
+    /// GitHub's anchor of the heading, empty for the content before the first heading
+    pub anchor: String,
+    pub content: String,
+}
+
+pub struct GuidanceMatch {
+    pub section: GuidanceSection,
+    pub cosine_similarity: f64,
+}
+
+pub struct ClosureProposal {
+    pub id: i32,
+    pub issue_source_id: i64,
+    pub issue_url: String,
+    pub comment_url: String,
+}
+
+/// Storage layer used by the background workers
+///
+/// `embedding`s are the raw vectors returned by the embedding API, backends are free to store
+/// them however they see fit
+pub(crate) trait Storage {
+    /// verifies the database is reachable and has everything the bot relies on
+    async fn check(&self) -> Result<(), StorageError>;
+
+    /// creates the missing `vector_search.partial_indexes`, rebuilding those left invalid by a
+    /// failed build, see [crate::config::VectorSearchConfig]
+    async fn create_partial_indexes(&self) -> Result<(), StorageError>;
+
+    /// issues of `repositories` closest to `embedding`, along with their own embeddings when
+    /// `with_embeddings`, e.g. for [crate::diversity], which may be left empty otherwise
+    async fn closest_issues(
+        &self,
+        embedding: &[f32],
+        excluded_labels: &[String],
+        repositories: &[String],
+        limit: i64,
+        with_embeddings: bool,
+    ) -> Result<Vec<ClosestIssue>, StorageError>;
+
+    /// same as [Storage::closest_issues], without the content of the `cached` issues
+    async fn closest_hot_issues(
+        &self,
+        embedding: &[f32],
+        excluded_labels: &[String],
+        repositories: &[String],
+        limit: i64,
+        cached: &[i64],
+    ) -> Result<Vec<IssueSimilarity>, StorageError>;
+
+    /// in no particular order, missing issues being left out
+    async fn hot_issues(&self, source_ids: &[i64]) -> Result<Vec<HotIssue>, StorageError>;
+
+    /// oldest issue of `repositories` with the given traceback fingerprint, see [crate::fingerprint]
+    async fn issue_by_fingerprint(
+        &self,
+        fingerprint: &str,
+        excluded_labels: &[String],
+        repositories: &[String],
+    ) -> Result<Option<ClosestIssue>, StorageError>;
+
+    async fn set_issue_fingerprint(
+        &self,
+        source_id: i64,
+        fingerprint: Option<&str>,
+    ) -> Result<(), StorageError>;
+
+    /// issues after `after_id` quoting a traceback without a fingerprint, ordered by id, see
+    /// [crate::fingerprint::start_fingerprint_backfill]
+    async fn unfingerprinted_issues(
+        &self,
+        after_id: i32,
+        limit: i64,
+    ) -> Result<Vec<IssueBody>, StorageError>;
+
+    /// see [crate::extraction]
+    async fn set_system_info(&self, source_id: i64, info: &SystemInfo) -> Result<(), StorageError>;
+
+    /// overwrites the fields of `info` that are set, keeping the stored ones otherwise, e.g.
+    /// completed by the LLM when the issue was opened
+    async fn merge_system_info(
+        &self,
+        source_id: i64,
+        info: &SystemInfo,
+    ) -> Result<(), StorageError>;
+
+    /// see [crate::issue_forms]
+    async fn set_issue_form(&self, source_id: i64, form: &IssueForm) -> Result<(), StorageError>;
+
+    /// see [crate::code_context]
+    async fn set_code_context(
+        &self,
+        source_id: i64,
+        code_context: Option<&str>,
+    ) -> Result<(), StorageError>;
+
+    /// `fingerprint` is the traceback fingerprint of the issue's body, see [crate::fingerprint]
+    async fn insert_issue(
+        &self,
+        issue: &IssueData,
+        embedding: &[f32],
+        fingerprint: Option<&str>,
+    ) -> Result<(), StorageError>;
+
+    /// also lifts the exclusion of retention rules, the issue being active again, unless it is
+    /// gone or its author opted out
+    async fn update_issue(
+        &self,
+        issue: &IssueData,
+        fingerprint: Option<&str>,
+    ) -> Result<(), StorageError>;
+
+    async fn update_issue_metadata(
+        &self,
+        source_id: i64,
+        labels: &[String],
+        milestone: Option<&str>,
+    ) -> Result<(), StorageError>;
+
+    async fn delete_issue(&self, source_id: i64) -> Result<(), StorageError>;
+
+    async fn delete_issues(&self, source_ids: &[i64]) -> Result<(), StorageError>;
+
+    /// records the issue as closed or reopened upstream, reopening it lifting the exclusion of
+    /// retention rules like [Storage::update_issue] does
+    async fn set_issue_closed(&self, source_id: i64, closed: bool) -> Result<(), StorageError>;
+
+    async fn issue_id(&self, source_id: i64) -> Result<Option<i32>, StorageError>;
+
+    /// source id of an issue only known by its number, e.g. the pull request of a review comment
+    async fn issue_source_id(
+        &self,
+        repository_full_name: &str,
+        number: i32,
+    ) -> Result<Option<i64>, StorageError>;
+
+    async fn stored_issue(&self, source_id: i64) -> Result<Option<StoredIssue>, StorageError>;
+
+    /// inserts an issue fetched while indexing a repository, returning its id
+    async fn insert_indexed_issue(
+        &self,
+        issue: &IssueWithComments,
+        source: &Source,
+        repository_full_name: &str,
+        embedding: &[f32],
+        fingerprint: Option<&str>,
+    ) -> Result<i32, StorageError>;
+
+    /// inserts the comments of an indexed issue, skipping the ones already stored
+    async fn insert_indexed_comments(
+        &self,
+        issue_id: i32,
+        issue: &IssueWithComments,
+    ) -> Result<(), StorageError>;
+
+    async fn insert_comment(
+        &self,
+        issue_id: i32,
+        comment: &CommentData,
+    ) -> Result<(), StorageError>;
+
+    /// source id of the comment's issue, `None` if the comment isn't stored
+    async fn update_comment(&self, comment: &CommentData) -> Result<Option<i64>, StorageError>;
+
+    /// source id of the comment's issue, `None` if the comment isn't stored
+    async fn delete_comment(&self, source_id: i64) -> Result<Option<i64>, StorageError>;
+
+    /// full issue with its comments and embedding, `None` if it isn't stored
+    async fn archived_issue(&self, source_id: i64) -> Result<Option<ArchivedIssue>, StorageError>;
+
+    async fn archive_issue(&self, issue: &ArchivedIssue) -> Result<(), StorageError>;
+
+    async fn issue_text(&self, source_id: i64) -> Result<IssueText, StorageError>;
+
+    async fn update_issue_embedding(
+        &self,
+        source_id: i64,
+        embedding: &[f32],
+    ) -> Result<(), StorageError>;
+
+    /// replaces the metadata of the issue's embedding, once the embedding is stored
+    async fn record_embedding_metadata(
+        &self,
+        source_id: i64,
+        metadata: &EmbeddingMetadata,
+    ) -> Result<(), StorageError>;
+
+    async fn embedding_metadata(
+        &self,
+        source_id: i64,
+    ) -> Result<Option<EmbeddingRecord>, StorageError>;
+
+    /// at most `limit` issues with an id greater than `id`, ordered by id
+    async fn issue_ids_after(
+        &self,
+        id: i32,
+        limit: i64,
+    ) -> Result<Vec<StoredIssueId>, StorageError>;
+
+    async fn count_issues_after(&self, id: i32) -> Result<i64, StorageError>;
+
+    /// page of the issues that can be suggested, ordered by id, the connection being released
+    /// between pages of a long export
+    async fn export_issues(
+        &self,
+        after_id: i32,
+        limit: i64,
+    ) -> Result<Vec<ExportedIssue>, StorageError>;
+
+    /// source ids of the issues not updated for `older_than_days` and not excluded yet, of
+    /// `repository_full_name` when set, carrying one of `labels` when it isn't empty and in `state`
+    /// when set
+    async fn stale_issues(
+        &self,
+        repository_full_name: Option<&str>,
+        older_than_days: i64,
+        labels: &[String],
+        state: Option<IssueState>,
+    ) -> Result<Vec<i64>, StorageError>;
+
+    /// leaves the issues out of similarity searches
+    async fn exclude_issues(&self, source_ids: &[i64]) -> Result<(), StorageError>;
+
+    /// leaves the issue of api url `url` out of similarity searches after it was deleted or
+    /// transferred away upstream, until a webhook about it arrives, returns whether it wasn't
+    /// already known to be gone
+    ///
+    /// Its cached GitHub responses are dropped, see [crate::response_cache].
+    async fn mark_issue_gone(&self, url: &str) -> Result<bool, StorageError>;
+
+    /// points the rows of repository `from` to `to`, returning the number of moved issues
+    ///
+    /// Rows of [REPOSITORY_TABLES] and [REPOSITORY_KEYED_TABLES] are renamed, replacing the
+    /// latter's rows of `to`, and the `/{from}/` path of [ISSUE_URL_COLUMNS] is rewritten. Cached
+    /// responses of `from` are dropped.
+    async fn move_repository(&self, from: &str, to: &str) -> Result<u64, StorageError>;
+
+    /// issues of private repositories are left out of suggestions and searches, the visibility is
+    /// kept per repository for the issues inserted later on, and their cached GitHub responses
+    /// are dropped
+    async fn set_repository_private(
+        &self,
+        repository_full_name: &str,
+        private: bool,
+    ) -> Result<u64, StorageError>;
+
+    /// issues of the repository ordered by number, pull requests excluded
+    async fn repository_issues(
+        &self,
+        repository_full_name: &str,
+    ) -> Result<Vec<IssueEmbedding>, StorageError>;
+
+    /// `limit` closest issues to `embedding`, in `repositories` or in every repository when empty,
+    /// reported with `accelerator` and on `os` when set
+    async fn search_issues(
+        &self,
+        embedding: &[f32],
+        repositories: &[String],
+        accelerator: Option<&str>,
+        os: Option<&str>,
+        limit: i64,
+        with_bodies: bool,
+    ) -> Result<Vec<SearchHit>, StorageError>;
+
+    /// ordered by repository full name
+    async fn repository_stats(&self) -> Result<Vec<RepositoryStats>, StorageError>;
+
+    /// suggestions of the last `limit` issues that got some, most recent first and by rank
+    async fn recent_suggestions(&self, limit: i64) -> Result<Vec<RecentSuggestion>, StorageError>;
+
+    /// already stored links are left untouched
+    async fn insert_issue_links(
+        &self,
+        repository_full_name: &str,
+        links: &[IssueLink],
+    ) -> Result<(), StorageError>;
+
+    /// extracted environment of the issues of the repository numbered `numbers`
+    async fn issue_cohorts(
+        &self,
+        repository_full_name: &str,
+        numbers: &[i32],
+    ) -> Result<Vec<IssueCohort>, StorageError>;
+
+    /// links of the repository, only those between `numbers` when set
+    async fn issue_links(
+        &self,
+        repository_full_name: &str,
+        numbers: Option<&[i32]>,
+    ) -> Result<Vec<IssueLink>, StorageError>;
+
+    async fn get_job(
+        &self,
+        job_type: JobType,
+        repository_full_name: Option<&str>,
+    ) -> Result<Option<JobData>, StorageError>;
+
+    /// jobs are unique per repository, or per job type when `repository_full_name` is `None`
+    async fn save_job(
+        &self,
+        job_type: JobType,
+        repository_full_name: Option<&str>,
+        data: &JobData,
+    ) -> Result<(), StorageError>;
+
+    async fn delete_job(
+        &self,
+        job_type: JobType,
+        repository_full_name: Option<&str>,
+    ) -> Result<(), StorageError>;
+
+    async fn jobs(&self) -> Result<Vec<JobState>, StorageError>;
+
+    /// `None` when there is no job `id`
+    async fn set_job_paused(&self, id: i32, paused: bool)
+        -> Result<Option<JobState>, StorageError>;
+
+    async fn is_job_paused(
+        &self,
+        job_type: JobType,
+        repository_full_name: Option<&str>,
+    ) -> Result<bool, StorageError>;
+
+    /// takes the lock if it is free, expired or already held by `holder`
+    async fn try_acquire_lock(
+        &self,
+        name: &str,
+        holder: &str,
+        lease: Duration,
+    ) -> Result<bool, StorageError>;
+
+    /// extends the lease, returns false if the lock isn't held by `holder` anymore
+    async fn renew_lock(
+        &self,
+        name: &str,
+        holder: &str,
+        lease: Duration,
+    ) -> Result<bool, StorageError>;
+
+    async fn release_lock(&self, name: &str, holder: &str) -> Result<(), StorageError>;
+
+    /// whether any holder has an unexpired lease on the lock
+    async fn lock_held(&self, name: &str) -> Result<bool, StorageError>;
+
+    async fn insert_closure_proposal(
+        &self,
+        issue_source_id: i64,
+        issue_url: &str,
+        duplicate_of_html_url: &str,
+        comment_url: &str,
+    ) -> Result<(), StorageError>;
+
+    /// records a vote, returning the prompt profile of its run to report it per profile
+    async fn insert_feedback(
+        &self,
+        run_id: &str,
+        vote: Vote,
+    ) -> Result<Option<String>, StorageError>;
+
+    /// remembers the run of a comment about to be posted, whose feedback links are only
+    /// accepted once it is, along with the prompt profile its issue was summarized with
+    async fn insert_comment_run(
+        &self,
+        run_id: &str,
+        issue_url: &str,
+        prompt_profile: Option<&str>,
+    ) -> Result<(), StorageError>;
+
+    async fn comment_run_exists(&self, run_id: &str) -> Result<bool, StorageError>;
+
+    /// raw value of a runtime setting, see [crate::settings::Settings]
+    async fn setting(&self, key: &str) -> Result<Option<String>, StorageError>;
+
+    async fn set_setting(&self, key: &str, value: &str) -> Result<(), StorageError>;
+
+    async fn upsert_repository_metadata(
+        &self,
+        metadata: &RepositoryMetadata,
+    ) -> Result<(), StorageError>;
+
+    /// metadata of `full_names` when set, of every repository otherwise
+    async fn repository_metadata(
+        &self,
+        full_names: Option<&[String]>,
+    ) -> Result<Vec<RepositoryMetadata>, StorageError>;
+
+    /// returns the new entry's id
+    async fn insert_knowledge_base_entry(
+        &self,
+        repository_full_name: Option<&str>,
+        question: &str,
+        answer: &str,
+        embedding: &[f32],
+    ) -> Result<i32, StorageError>;
+
+    async fn knowledge_base_entries(&self) -> Result<Vec<KnowledgeBaseEntry>, StorageError>;
+
+    /// replaces the entry's content, returns false if there was no entry with this id
+    async fn update_knowledge_base_entry(
+        &self,
+        id: i32,
+        repository_full_name: Option<&str>,
+        question: &str,
+        answer: &str,
+        embedding: &[f32],
+    ) -> Result<bool, StorageError>;
+
+    /// returns false if there was no entry with this id
+    async fn delete_knowledge_base_entry(&self, id: i32) -> Result<bool, StorageError>;
+
+    /// entry closest to `embedding` among the global ones and `repository_full_name`'s
+    async fn closest_knowledge_base_entry(
+        &self,
+        embedding: &[f32],
+        repository_full_name: &str,
+    ) -> Result<Option<KnowledgeBaseMatch>, StorageError>;
+
+    /// replaces the guidance sections previously indexed for the repository, stored with the
+    /// hash of the text they were embedded from
+    async fn replace_guidance_sections(
+        &self,
+        repository_full_name: &str,
+        sections: &[(GuidanceSection, String, Vec<f32>)],
+    ) -> Result<(), StorageError>;
+
+    /// embeddings of the repository's guidance sections by hash of the text they were embedded
+    /// from, see [Storage::replace_guidance_sections]
+    async fn guidance_embeddings(
+        &self,
+        repository_full_name: &str,
+    ) -> Result<HashMap<String, Vec<f32>>, StorageError>;
+
+    async fn closest_guidance_section(
+        &self,
+        embedding: &[f32],
+        repository_full_name: &str,
+    ) -> Result<Option<GuidanceMatch>, StorageError>;
+
+    /// keys are only stored hashed, returns the new key's id
+    async fn insert_api_key(
+        &self,
+        name: &str,
+        key_hash: &str,
+        scopes: &str,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<i32, StorageError>;
+
+    async fn api_key(&self, key_hash: &str) -> Result<Option<ApiKey>, StorageError>;
+
+    async fn api_keys(&self) -> Result<Vec<ApiKey>, StorageError>;
+
+    /// returns false if there was no key with this id
+    async fn delete_api_key(&self, id: i32) -> Result<bool, StorageError>;
+
+    async fn enqueue_comment(
+        &self,
+        source: &Source,
+        repository_full_name: &str,
+        issue_url: &str,
+        body: &str,
+        awaiting_approval: bool,
+        suggestions: bool,
+    ) -> Result<(), StorageError>;
+
+    /// oldest pending comments whose next attempt is due first, drafts awaiting approval left out
+    async fn pending_comments(&self, limit: i64) -> Result<Vec<PendingComment>, StorageError>;
+
+    /// counts a failed attempt at posting the comment and delays the next one by `delay_secs`
+    async fn reschedule_pending_comment(
+        &self,
+        id: i32,
+        delay_secs: i64,
+        error: &str,
+    ) -> Result<(), StorageError>;
+
+    /// drafts awaiting approval that weren't posted to Slack yet, oldest first
+    async fn unannounced_comment_drafts(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<PendingComment>, StorageError>;
+
+    async fn set_comment_draft_announced(
+        &self,
+        id: i32,
+        slack_ts: &str,
+    ) -> Result<(), StorageError>;
+
+    /// queues the draft for posting, returns whether it was still awaiting approval
+    async fn approve_comment_draft(&self, id: i32) -> Result<bool, StorageError>;
+
+    /// returns whether the draft was still awaiting approval
+    async fn discard_comment_draft(&self, id: i32) -> Result<bool, StorageError>;
+
+    /// deletes the drafts left unapproved for `older_than_hours`, returns how many were
+    async fn expire_comment_drafts(&self, older_than_hours: i32) -> Result<u64, StorageError>;
+
+    async fn delete_pending_comment(&self, id: i32) -> Result<(), StorageError>;
+
+    async fn enqueue_slack_message(
+        &self,
+        repository_full_name: &str,
+        payload: &serde_json::Value,
+        error: &str,
+    ) -> Result<(), StorageError>;
+
+    /// oldest `limit` messages whose next attempt is due
+    async fn due_slack_messages(&self, limit: i64)
+        -> Result<Vec<SlackOutboxMessage>, StorageError>;
+
+    /// counts a failed attempt and delays the next one by `delay_secs`
+    async fn reschedule_slack_message(
+        &self,
+        id: i32,
+        delay_secs: i64,
+        error: &str,
+    ) -> Result<(), StorageError>;
+
+    async fn delete_slack_message(&self, id: i32) -> Result<(), StorageError>;
+
+    /// drops the messages queued more than `older_than_minutes` ago, returns how many
+    async fn expire_slack_messages(&self, older_than_minutes: i32) -> Result<u64, StorageError>;
+
+    /// see [crate::slack::Slack::closest_issues]
+    async fn add_to_slack_batch(
+        &self,
+        repository_full_name: &str,
+        payload: &serde_json::Value,
+    ) -> Result<(), StorageError>;
+
+    /// removes and returns the batched notifications of a repository, oldest first
+    async fn take_slack_batch(
+        &self,
+        repository_full_name: &str,
+    ) -> Result<Vec<serde_json::Value>, StorageError>;
+
+    /// repositories with batched notifications, whose batch window is restarted on startup
+    async fn slack_batch_repositories(&self) -> Result<Vec<String>, StorageError>;
+
+    async fn cached_response(&self, url: &str) -> Result<Option<CachedResponse>, StorageError>;
+
+    async fn cache_response(
+        &self,
+        url: &str,
+        response: &CachedResponse,
+    ) -> Result<(), StorageError>;
+
+    /// restarts the age of a cached response GitHub confirmed to be unchanged
+    async fn refresh_cached_response(&self, url: &str) -> Result<(), StorageError>;
+
+    /// drops the responses cached more than `older_than_days` ago, returns how many
+    async fn purge_cached_responses(&self, older_than_days: i32) -> Result<u64, StorageError>;
+
+    /// notifies the instances listening on `channel`, see [Database::listen], doing nothing on
+    /// SQLite whose databases aren't shared between instances
+    async fn notify(&self, channel: &str, payload: &str) -> Result<(), StorageError>;
+
+    async fn comment_backlog(&self) -> Result<CommentBacklog, StorageError>;
+
+    async fn job_backlog(&self) -> Result<Vec<JobBacklog>, StorageError>;
+
+    async fn slack_outbox_backlog(&self) -> Result<SlackOutboxBacklog, StorageError>;
+
+    /// events logged in the last `since_minutes`, grouped by type and outcome
+    async fn event_stats(&self, since_minutes: i32) -> Result<Vec<EventStats>, StorageError>;
+
+    /// `None` when no comment of `fingerprint` was attempted on the discussion, otherwise whether
+    /// it is known to be posted, see [crate::comment_queue::comment_fingerprint]
+    async fn comment_idempotency_key(
+        &self,
+        issue_url: &str,
+        fingerprint: &str,
+    ) -> Result<Option<bool>, StorageError>;
+
+    async fn save_comment_idempotency_key(
+        &self,
+        issue_url: &str,
+        fingerprint: &str,
+        posted: bool,
+    ) -> Result<(), StorageError>;
+
+    /// api url of the comment the bot keeps up to date on a GitHub issue
+    async fn bot_comment(&self, issue_url: &str) -> Result<Option<String>, StorageError>;
+
+    /// points the issue to `comment_url`, keeping `body` in the comment's revisions
+    async fn save_bot_comment(
+        &self,
+        issue_url: &str,
+        comment_url: &str,
+        body: &str,
+    ) -> Result<(), StorageError>;
+
+    /// revisions of the bot's comments on the issue, oldest first
+    async fn bot_comment_revisions(
+        &self,
+        issue_url: &str,
+    ) -> Result<Vec<BotCommentRevision>, StorageError>;
+
+    async fn record_suggestion_targets(
+        &self,
+        issue: &IssueData,
+        targets: &[&ClosestIssue],
+    ) -> Result<(), StorageError>;
+
+    /// issues matched with the given one in the last `window_minutes`, each listed once
+    async fn suggestion_cluster(
+        &self,
+        repository_full_name: &str,
+        number: i32,
+        window_minutes: i32,
+    ) -> Result<Vec<SuggestedIssue>, StorageError>;
+
+    async fn prune_suggestion_targets(&self, older_than_minutes: i32) -> Result<(), StorageError>;
+
+    /// returns whether the issue can be escalated, i.e. it wasn't in the last `cooldown_minutes`
+    async fn claim_escalation(
+        &self,
+        repository_full_name: &str,
+        number: i32,
+        cooldown_minutes: i32,
+    ) -> Result<bool, StorageError>;
+
+    async fn record_suggestions(
+        &self,
+        issue_source_id: i64,
+        suggestions: &[ClosestIssue],
+        run_id: &str,
+        prompt_profile: Option<&str>,
+    ) -> Result<(), StorageError>;
+
+    /// ordered by rank
+    async fn suggestions(&self, issue_source_id: i64) -> Result<Vec<Suggestion>, StorageError>;
+
+    /// replaces the previous resolution of the issue, if any
+    async fn upsert_duplicate_resolution(
+        &self,
+        resolution: &DuplicateResolution,
+    ) -> Result<(), StorageError>;
+
+    /// similarities of the most recently linked issues, under their current embeddings
+    async fn linked_issue_similarities(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<LinkSimilarity>, StorageError>;
+
+    /// replaces the similarities previously sampled under `model`
+    async fn record_link_similarities(
+        &self,
+        model: &str,
+        similarities: &[LinkSimilarity],
+    ) -> Result<(), StorageError>;
+
+    async fn link_similarities(&self, model: &str) -> Result<Vec<LinkSimilarity>, StorageError>;
+
+    async fn repository_cursors(&self) -> Result<Vec<RepositoryCursor>, StorageError>;
+
+    /// moves the cursor of the repository to `processed_at`, never back
+    async fn advance_repository_cursor(
+        &self,
+        repository_full_name: &str,
+        processed_at: DateTime<Utc>,
+    ) -> Result<(), StorageError>;
+
+    /// returns whether the issue wasn't already suppressed, see [crate::suppression]
+    async fn suppress_issue(
+        &self,
+        source_id: i64,
+        suppressed_by: &str,
+    ) -> Result<bool, StorageError>;
+
+    /// returns whether the issue was suppressed
+    async fn unsuppress_issue(&self, source_id: i64) -> Result<bool, StorageError>;
+
+    async fn is_issue_suppressed(&self, source_id: i64) -> Result<bool, StorageError>;
+
+    /// same as [Storage::is_issue_suppressed], for issues only known by their url
+    async fn is_issue_url_suppressed(&self, issue_url: &str) -> Result<bool, StorageError>;
+
+    /// same as [Storage::is_issue_suppressed], for issues only known by their number
+    async fn is_issue_number_suppressed(
+        &self,
+        repository_full_name: &str,
+        number: i32,
+    ) -> Result<bool, StorageError>;
+
+    /// flags the issues and comments of `login`, or deletes them when `purge`, and records the
+    /// request, see [crate::opt_out]
+    async fn opt_out_author(
+        &self,
+        login: &str,
+        requested_through: &str,
+        purge: bool,
+    ) -> Result<OptOutRequest, StorageError>;
+
+    /// most recent first
+    async fn opt_out_requests(&self) -> Result<Vec<OptOutRequest>, StorageError>;
+
+    /// source ids of the issues of other authors `login` commented on, their embeddings have to be
+    /// computed again once the comments are opted out
+    async fn issues_commented_by(&self, login: &str) -> Result<Vec<i64>, StorageError>;
+
+    /// records that the repository was onboarded, again when it already was
+    async fn record_onboarding(
+        &self,
+        repository_full_name: &str,
+        hook_id: Option<i64>,
+        estimated_issues: i64,
+    ) -> Result<OnboardedRepository, StorageError>;
+
+    /// most recently onboarded first
+    async fn onboarded_repositories(&self) -> Result<Vec<OnboardedRepository>, StorageError>;
+
+    async fn expire_closure_proposals(&self, older_than_hours: i32) -> Result<(), StorageError>;
+
+    async fn pending_closure_proposals(&self) -> Result<Vec<ClosureProposal>, StorageError>;
+
+    async fn set_closure_proposal_status(
+        &self,
+        id: i32,
+        status: ClosureProposalStatus,
+    ) -> Result<(), StorageError>;
+
+    async fn insert_event_log_entry(&self, entry: &EventLogEntry) -> Result<(), StorageError>;
+
+    /// `commented` outcome of the events whose comment got posted on the issue of api url
+    /// `issue_url`, found through their run
+    async fn mark_events_commented(&self, issue_url: &str) -> Result<(), StorageError>;
+
+    /// deletes the entries older than `older_than_days`, returns how many were
+    async fn prune_event_log(&self, older_than_days: i32) -> Result<u64, StorageError>;
+
+    /// most recent first
+    async fn event_log(
+        &self,
+        filter: &EventLogFilter,
+        limit: i64,
+    ) -> Result<Vec<EventLogEntry>, StorageError>;
+
+    /// `None` on SQLite, which keeps no such statistics
+    async fn issues_table_health(&self) -> Result<Option<TableHealth>, StorageError>;
+
+    /// `VACUUM (ANALYZE)` of the issues table, cancelled after `timeout` on Postgres
+    async fn vacuum_issues(&self, timeout: Duration) -> Result<(), StorageError>;
+
+    /// names of the vector indexes of the issues table, which vacuuming doesn't shrink unlike
+    /// B-trees, empty on SQLite
+    async fn issues_vector_indexes(&self) -> Result<Vec<String>, StorageError>;
+
+    /// rebuilds an index of the issues table, without blocking writes and cancelled after
+    /// `timeout` on Postgres
+    async fn reindex_issues_index(&self, name: &str, timeout: Duration)
+        -> Result<(), StorageError>;
+
+    /// drops the invalid indexes cancelled concurrent rebuilds of the issues table left behind,
+    /// returns their names
+    async fn drop_invalid_issues_indexes(&self) -> Result<Vec<String>, StorageError>;
+
+    async fn insert_compaction_run(&self, run: &CompactionRun) -> Result<(), StorageError>;
+
+    /// most recent run of `action`
+    async fn last_compaction_run(
+        &self,
+        action: &str,
+    ) -> Result<Option<CompactionRun>, StorageError>;
+}
+
+/// Storage backend selected from the connection string scheme, `sqlite:` or `postgres:`
+#[derive(Clone)]
+pub enum Database {
+    Postgres(PgStorage),
+    Sqlite(SqliteStorage),
+}
+
+impl Database {
+    /// `statement_timeout` only applies to Postgres
+    pub async fn connect(
+        cfg: &DatabaseConfig,
+        statement_timeout: Option<Duration>,
+    ) -> Result<Self, StorageError> {
+        let scheme = cfg
+            .connection_string
+            .split_once(':')
+            .map(|(scheme, _)| scheme)
+            .unwrap_or_default();
+        match scheme {
+            "postgres" | "postgresql" => Ok(Self::Postgres(
+                PgStorage::connect(cfg, statement_timeout).await?,
+            )),
+            "sqlite" => Ok(Self::Sqlite(SqliteStorage::connect(cfg).await?)),
+            scheme => Err(StorageError::UnsupportedScheme(scheme.to_owned())),
+        }
+    }
+
+    /// listener of the notifications sent on `channel` by every instance, `None` on SQLite, see
+    /// [Storage::notify]
+    pub async fn listen(&self, channel: &str) -> Result<Option<PgListener>, StorageError> {
+        match self {
+            Self::Postgres(storage) => Ok(Some(storage.listen(channel).await?)),
+            Self::Sqlite(_) => Ok(None),
+        }
+    }
+
+    /// issues with an id greater than `id`, ordered by id
+    ///
+    /// Read `page_size` at a time instead of through a single cursor, which would hold a
+    /// connection for as long as the issues take to be processed.
+    pub fn issues_after(
+        &self,
+        id: i32,
+        page_size: i64,
+    ) -> impl Stream<Item = Result<StoredIssueId, StorageError>> + '_ {
+        try_stream! {
+            let mut after = id;
+            loop {
+                let page = self.issue_ids_after(after, page_size).await?;
+                let Some(last) = page.last() else {
+                    break;
+                };
+                after = last.id;
+                let is_last_page = (page.len() as i64) < page_size;
+                for issue in page {
+                    yield issue;
+                }
+                if is_last_page {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+macro_rules! delegate {
+    ($self:ident.$method:ident($($arg:expr),*)) => {
+        match $self {
+            Database::Postgres(storage) => storage.$method($($arg),*).await,
+            Database::Sqlite(storage) => storage.$method($($arg),*).await,
+        }
+    };
+}
+
+impl Storage for Database {
+    async fn check(&self) -> Result<(), StorageError> {
+        delegate!(self.check())
+    }
+
+    async fn create_partial_indexes(&self) -> Result<(), StorageError> {
+        delegate!(self.create_partial_indexes())
+    }
+
+    async fn closest_issues(
+        &self,
+        embedding: &[f32],
+        excluded_labels: &[String],
+        repositories: &[String],
+        limit: i64,
+        with_embeddings: bool,
+    ) -> Result<Vec<ClosestIssue>, StorageError> {
+        delegate!(self.closest_issues(
+            embedding,
+            excluded_labels,
+            repositories,
+            limit,
+            with_embeddings
+        ))
+    }
+
+    async fn closest_hot_issues(
+        &self,
+        embedding: &[f32],
+        excluded_labels: &[String],
+        repositories: &[String],
+        limit: i64,
+        cached: &[i64],
+    ) -> Result<Vec<IssueSimilarity>, StorageError> {
+        delegate!(self.closest_hot_issues(embedding, excluded_labels, repositories, limit, cached))
+    }
+
+    async fn hot_issues(&self, source_ids: &[i64]) -> Result<Vec<HotIssue>, StorageError> {
+        delegate!(self.hot_issues(source_ids))
+    }
+
+    async fn issue_by_fingerprint(
+        &self,
+        fingerprint: &str,
+        excluded_labels: &[String],
+        repositories: &[String],
+    ) -> Result<Option<ClosestIssue>, StorageError> {
+        delegate!(self.issue_by_fingerprint(fingerprint, excluded_labels, repositories))
+    }
+
+    async fn set_issue_fingerprint(
+        &self,
+        source_id: i64,
+        fingerprint: Option<&str>,
+    ) -> Result<(), StorageError> {
+        delegate!(self.set_issue_fingerprint(source_id, fingerprint))
+    }
+
+    async fn unfingerprinted_issues(
+        &self,
+        after_id: i32,
+        limit: i64,
+    ) -> Result<Vec<IssueBody>, StorageError> {
+        delegate!(self.unfingerprinted_issues(after_id, limit))
+    }
+
+    async fn set_system_info(&self, source_id: i64, info: &SystemInfo) -> Result<(), StorageError> {
+        delegate!(self.set_system_info(source_id, info))
+    }
+
+    async fn merge_system_info(
+        &self,
+        source_id: i64,
+        info: &SystemInfo,
+    ) -> Result<(), StorageError> {
+        delegate!(self.merge_system_info(source_id, info))
+    }
+
+    async fn set_issue_form(&self, source_id: i64, form: &IssueForm) -> Result<(), StorageError> {
+        delegate!(self.set_issue_form(source_id, form))
+    }
+
+    async fn set_code_context(
+        &self,
+        source_id: i64,
+        code_context: Option<&str>,
+    ) -> Result<(), StorageError> {
+        delegate!(self.set_code_context(source_id, code_context))
+    }
+
+    async fn insert_issue(
+        &self,
+        issue: &IssueData,
+        embedding: &[f32],
+        fingerprint: Option<&str>,
+    ) -> Result<(), StorageError> {
+        delegate!(self.insert_issue(issue, embedding, fingerprint))
+    }
+
+    async fn update_issue(
+        &self,
+        issue: &IssueData,
+        fingerprint: Option<&str>,
+    ) -> Result<(), StorageError> {
+        delegate!(self.update_issue(issue, fingerprint))
+    }
+
+    async fn update_issue_metadata(
+        &self,
+        source_id: i64,
+        labels: &[String],
+        milestone: Option<&str>,
+    ) -> Result<(), StorageError> {
+        delegate!(self.update_issue_metadata(source_id, labels, milestone))
+    }
+
+    async fn delete_issue(&self, source_id: i64) -> Result<(), StorageError> {
+        delegate!(self.delete_issue(source_id))
+    }
+
+    async fn delete_issues(&self, source_ids: &[i64]) -> Result<(), StorageError> {
+        delegate!(self.delete_issues(source_ids))
+    }
+
+    async fn set_issue_closed(&self, source_id: i64, closed: bool) -> Result<(), StorageError> {
+        delegate!(self.set_issue_closed(source_id, closed))
+    }
+
+    async fn issue_id(&self, source_id: i64) -> Result<Option<i32>, StorageError> {
+        delegate!(self.issue_id(source_id))
+    }
+
+    async fn issue_source_id(
+        &self,
+        repository_full_name: &str,
+        number: i32,
+    ) -> Result<Option<i64>, StorageError> {
+        delegate!(self.issue_source_id(repository_full_name, number))
+    }
+
+    async fn stored_issue(&self, source_id: i64) -> Result<Option<StoredIssue>, StorageError> {
+        delegate!(self.stored_issue(source_id))
+    }
+
+    async fn insert_indexed_issue(
+        &self,
+        issue: &IssueWithComments,
+        source: &Source,
+        repository_full_name: &str,
+        embedding: &[f32],
+        fingerprint: Option<&str>,
+    ) -> Result<i32, StorageError> {
+        delegate!(self.insert_indexed_issue(
+            issue,
+            source,
+            repository_full_name,
+            embedding,
+            fingerprint
+        ))
+    }
+
+    async fn insert_indexed_comments(
+        &self,
+        issue_id: i32,
+        issue: &IssueWithComments,
+    ) -> Result<(), StorageError> {
+        delegate!(self.insert_indexed_comments(issue_id, issue))
+    }
+
+    async fn insert_comment(
+        &self,
+        issue_id: i32,
+        comment: &CommentData,
+    ) -> Result<(), StorageError> {
+        delegate!(self.insert_comment(issue_id, comment))
+    }
+
+    async fn update_comment(&self, comment: &CommentData) -> Result<Option<i64>, StorageError> {
+        delegate!(self.update_comment(comment))
+    }
+
+    async fn delete_comment(&self, source_id: i64) -> Result<Option<i64>, StorageError> {
+        delegate!(self.delete_comment(source_id))
+    }
+
+    async fn archived_issue(&self, source_id: i64) -> Result<Option<ArchivedIssue>, StorageError> {
+        delegate!(self.archived_issue(source_id))
+    }
+
+    async fn archive_issue(&self, issue: &ArchivedIssue) -> Result<(), StorageError> {
+        delegate!(self.archive_issue(issue))
+    }
+
+    async fn issue_text(&self, source_id: i64) -> Result<IssueText, StorageError> {
+        delegate!(self.issue_text(source_id))
+    }
+
+    async fn update_issue_embedding(
+        &self,
+        source_id: i64,
+        embedding: &[f32],
+    ) -> Result<(), StorageError> {
+        delegate!(self.update_issue_embedding(source_id, embedding))
+    }
+
+    async fn record_embedding_metadata(
+        &self,
+        source_id: i64,
+        metadata: &EmbeddingMetadata,
+    ) -> Result<(), StorageError> {
+        delegate!(self.record_embedding_metadata(source_id, metadata))
+    }
+
+    async fn embedding_metadata(
+        &self,
+        source_id: i64,
+    ) -> Result<Option<EmbeddingRecord>, StorageError> {
+        delegate!(self.embedding_metadata(source_id))
+    }
+
+    async fn issue_ids_after(
+        &self,
+        id: i32,
+        limit: i64,
+    ) -> Result<Vec<StoredIssueId>, StorageError> {
+        delegate!(self.issue_ids_after(id, limit))
+    }
+
+    async fn count_issues_after(&self, id: i32) -> Result<i64, StorageError> {
+        delegate!(self.count_issues_after(id))
+    }
+
+    async fn export_issues(
+        &self,
+        after_id: i32,
+        limit: i64,
+    ) -> Result<Vec<ExportedIssue>, StorageError> {
+        delegate!(self.export_issues(after_id, limit))
+    }
+
+    async fn stale_issues(
+        &self,
+        repository_full_name: Option<&str>,
+        older_than_days: i64,
+        labels: &[String],
+        state: Option<IssueState>,
+    ) -> Result<Vec<i64>, StorageError> {
+        delegate!(self.stale_issues(repository_full_name, older_than_days, labels, state))
+    }
+
+    async fn exclude_issues(&self, source_ids: &[i64]) -> Result<(), StorageError> {
+        delegate!(self.exclude_issues(source_ids))
+    }
+
+    async fn mark_issue_gone(&self, url: &str) -> Result<bool, StorageError> {
+        delegate!(self.mark_issue_gone(url))
+    }
+
+    async fn move_repository(&self, from: &str, to: &str) -> Result<u64, StorageError> {
+        delegate!(self.move_repository(from, to))
+    }
+
+    async fn set_repository_private(
+        &self,
+        repository_full_name: &str,
+        private: bool,
+    ) -> Result<u64, StorageError> {
+        delegate!(self.set_repository_private(repository_full_name, private))
+    }
+
+    async fn repository_issues(
+        &self,
+        repository_full_name: &str,
+    ) -> Result<Vec<IssueEmbedding>, StorageError> {
+        delegate!(self.repository_issues(repository_full_name))
+    }
+
+    async fn search_issues(
+        &self,
+        embedding: &[f32],
+        repositories: &[String],
+        accelerator: Option<&str>,
+        os: Option<&str>,
+        limit: i64,
+        with_bodies: bool,
+    ) -> Result<Vec<SearchHit>, StorageError> {
+        delegate!(self.search_issues(embedding, repositories, accelerator, os, limit, with_bodies))
+    }
+
+    async fn repository_stats(&self) -> Result<Vec<RepositoryStats>, StorageError> {
+        delegate!(self.repository_stats())
+    }
+
+    async fn recent_suggestions(&self, limit: i64) -> Result<Vec<RecentSuggestion>, StorageError> {
+        delegate!(self.recent_suggestions(limit))
+    }
+
+    async fn insert_issue_links(
+        &self,
+        repository_full_name: &str,
+        links: &[IssueLink],
+    ) -> Result<(), StorageError> {
+        delegate!(self.insert_issue_links(repository_full_name, links))
+    }
+
+    async fn issue_links(
+        &self,
+        repository_full_name: &str,
+        numbers: Option<&[i32]>,
+    ) -> Result<Vec<IssueLink>, StorageError> {
+        delegate!(self.issue_links(repository_full_name, numbers))
+    }
+
+    async fn issue_cohorts(
+        &self,
+        repository_full_name: &str,
+        numbers: &[i32],
+    ) -> Result<Vec<IssueCohort>, StorageError> {
+        delegate!(self.issue_cohorts(repository_full_name, numbers))
+    }
+
+    async fn get_job(
+        &self,
+        job_type: JobType,
+        repository_full_name: Option<&str>,
+    ) -> Result<Option<JobData>, StorageError> {
+        delegate!(self.get_job(job_type, repository_full_name))
+    }
+
+    async fn save_job(
+        &self,
+        job_type: JobType,
+        repository_full_name: Option<&str>,
+        data: &JobData,
+    ) -> Result<(), StorageError> {
+        delegate!(self.save_job(job_type, repository_full_name, data))
+    }
+
+    async fn delete_job(
+        &self,
+        job_type: JobType,
+        repository_full_name: Option<&str>,
+    ) -> Result<(), StorageError> {
+        delegate!(self.delete_job(job_type, repository_full_name))
+    }
+
+    async fn jobs(&self) -> Result<Vec<JobState>, StorageError> {
+        delegate!(self.jobs())
+    }
+
+    async fn set_job_paused(
+        &self,
+        id: i32,
+        paused: bool,
+    ) -> Result<Option<JobState>, StorageError> {
+        delegate!(self.set_job_paused(id, paused))
+    }
+
+    async fn is_job_paused(
+        &self,
+        job_type: JobType,
+        repository_full_name: Option<&str>,
+    ) -> Result<bool, StorageError> {
+        delegate!(self.is_job_paused(job_type, repository_full_name))
+    }
+
+    async fn try_acquire_lock(
+        &self,
+        name: &str,
+        holder: &str,
+        lease: Duration,
+    ) -> Result<bool, StorageError> {
+        delegate!(self.try_acquire_lock(name, holder, lease))
+    }
+
+    async fn renew_lock(
+        &self,
+        name: &str,
+        holder: &str,
+        lease: Duration,
+    ) -> Result<bool, StorageError> {
+        delegate!(self.renew_lock(name, holder, lease))
+    }
+
+    async fn release_lock(&self, name: &str, holder: &str) -> Result<(), StorageError> {
+        delegate!(self.release_lock(name, holder))
+    }
+
+    async fn lock_held(&self, name: &str) -> Result<bool, StorageError> {
+        delegate!(self.lock_held(name))
+    }
+
+    async fn insert_closure_proposal(
+        &self,
+        issue_source_id: i64,
+        issue_url: &str,
+        duplicate_of_html_url: &str,
+        comment_url: &str,
+    ) -> Result<(), StorageError> {
+        delegate!(self.insert_closure_proposal(
+            issue_source_id,
+            issue_url,
+            duplicate_of_html_url,
+            comment_url
+        ))
+    }
+
+    async fn insert_feedback(
+        &self,
+        run_id: &str,
+        vote: Vote,
+    ) -> Result<Option<String>, StorageError> {
+        delegate!(self.insert_feedback(run_id, vote))
+    }
+
+    async fn insert_comment_run(
+        &self,
+        run_id: &str,
+        issue_url: &str,
+        prompt_profile: Option<&str>,
+    ) -> Result<(), StorageError> {
+        delegate!(self.insert_comment_run(run_id, issue_url, prompt_profile))
+    }
+
+    async fn comment_run_exists(&self, run_id: &str) -> Result<bool, StorageError> {
+        delegate!(self.comment_run_exists(run_id))
+    }
+
+    async fn setting(&self, key: &str) -> Result<Option<String>, StorageError> {
+        delegate!(self.setting(key))
+    }
+
+    async fn set_setting(&self, key: &str, value: &str) -> Result<(), StorageError> {
+        delegate!(self.set_setting(key, value))
+    }
+
+    async fn upsert_repository_metadata(
+        &self,
+        metadata: &RepositoryMetadata,
+    ) -> Result<(), StorageError> {
+        delegate!(self.upsert_repository_metadata(metadata))
+    }
+
+    async fn repository_metadata(
+        &self,
+        full_names: Option<&[String]>,
+    ) -> Result<Vec<RepositoryMetadata>, StorageError> {
+        delegate!(self.repository_metadata(full_names))
+    }
+
+    async fn insert_knowledge_base_entry(
+        &self,
+        repository_full_name: Option<&str>,
+        question: &str,
+        answer: &str,
+        embedding: &[f32],
+    ) -> Result<i32, StorageError> {
+        delegate!(self.insert_knowledge_base_entry(
+            repository_full_name,
+            question,
+            answer,
+            embedding
+        ))
+    }
+
+    async fn knowledge_base_entries(&self) -> Result<Vec<KnowledgeBaseEntry>, StorageError> {
+        delegate!(self.knowledge_base_entries())
+    }
+
+    async fn update_knowledge_base_entry(
+        &self,
+        id: i32,
+        repository_full_name: Option<&str>,
+        question: &str,
+        answer: &str,
+        embedding: &[f32],
+    ) -> Result<bool, StorageError> {
+        delegate!(self.update_knowledge_base_entry(
+            id,
+            repository_full_name,
+            question,
+            answer,
+            embedding
+        ))
+    }
+
+    async fn delete_knowledge_base_entry(&self, id: i32) -> Result<bool, StorageError> {
+        delegate!(self.delete_knowledge_base_entry(id))
+    }
+
+    async fn closest_knowledge_base_entry(
+        &self,
+        embedding: &[f32],
+        repository_full_name: &str,
+    ) -> Result<Option<KnowledgeBaseMatch>, StorageError> {
+        delegate!(self.closest_knowledge_base_entry(embedding, repository_full_name))
+    }
+
+    async fn replace_guidance_sections(
+        &self,
+        repository_full_name: &str,
+        sections: &[(GuidanceSection, String, Vec<f32>)],
+    ) -> Result<(), StorageError> {
+        delegate!(self.replace_guidance_sections(repository_full_name, sections))
+    }
+
+    async fn guidance_embeddings(
+        &self,
+        repository_full_name: &str,
+    ) -> Result<HashMap<String, Vec<f32>>, StorageError> {
+        delegate!(self.guidance_embeddings(repository_full_name))
+    }
+
+    async fn closest_guidance_section(
+        &self,
+        embedding: &[f32],
+        repository_full_name: &str,
+    ) -> Result<Option<GuidanceMatch>, StorageError> {
+        delegate!(self.closest_guidance_section(embedding, repository_full_name))
+    }
+
+    async fn insert_api_key(
+        &self,
+        name: &str,
+        key_hash: &str,
+        scopes: &str,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<i32, StorageError> {
+        delegate!(self.insert_api_key(name, key_hash, scopes, expires_at))
+    }
+
+    async fn api_key(&self, key_hash: &str) -> Result<Option<ApiKey>, StorageError> {
+        delegate!(self.api_key(key_hash))
+    }
+
+    async fn api_keys(&self) -> Result<Vec<ApiKey>, StorageError> {
+        delegate!(self.api_keys())
+    }
+
+    async fn delete_api_key(&self, id: i32) -> Result<bool, StorageError> {
+        delegate!(self.delete_api_key(id))
+    }
+
+    async fn enqueue_comment(
+        &self,
+        source: &Source,
+        repository_full_name: &str,
+        issue_url: &str,
+        body: &str,
+        awaiting_approval: bool,
+        suggestions: bool,
+    ) -> Result<(), StorageError> {
+        delegate!(self.enqueue_comment(
+            source,
+            repository_full_name,
+            issue_url,
+            body,
+            awaiting_approval,
+            suggestions
+        ))
+    }
+
+    async fn pending_comments(&self, limit: i64) -> Result<Vec<PendingComment>, StorageError> {
+        delegate!(self.pending_comments(limit))
+    }
+
+    async fn reschedule_pending_comment(
+        &self,
+        id: i32,
+        delay_secs: i64,
+        error: &str,
+    ) -> Result<(), StorageError> {
+        delegate!(self.reschedule_pending_comment(id, delay_secs, error))
+    }
+
+    async fn unannounced_comment_drafts(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<PendingComment>, StorageError> {
+        delegate!(self.unannounced_comment_drafts(limit))
+    }
+
+    async fn set_comment_draft_announced(
+        &self,
+        id: i32,
+        slack_ts: &str,
+    ) -> Result<(), StorageError> {
+        delegate!(self.set_comment_draft_announced(id, slack_ts))
+    }
+
+    async fn approve_comment_draft(&self, id: i32) -> Result<bool, StorageError> {
+        delegate!(self.approve_comment_draft(id))
+    }
+
+    async fn discard_comment_draft(&self, id: i32) -> Result<bool, StorageError> {
+        delegate!(self.discard_comment_draft(id))
+    }
+
+    async fn expire_comment_drafts(&self, older_than_hours: i32) -> Result<u64, StorageError> {
+        delegate!(self.expire_comment_drafts(older_than_hours))
+    }
+
+    async fn delete_pending_comment(&self, id: i32) -> Result<(), StorageError> {
+        delegate!(self.delete_pending_comment(id))
+    }
+
+    async fn enqueue_slack_message(
+        &self,
+        repository_full_name: &str,
+        payload: &serde_json::Value,
+        error: &str,
+    ) -> Result<(), StorageError> {
+        delegate!(self.enqueue_slack_message(repository_full_name, payload, error))
+    }
+
+    async fn due_slack_messages(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<SlackOutboxMessage>, StorageError> {
+        delegate!(self.due_slack_messages(limit))
+    }
+
+    async fn reschedule_slack_message(
+        &self,
+        id: i32,
+        delay_secs: i64,
+        error: &str,
+    ) -> Result<(), StorageError> {
+        delegate!(self.reschedule_slack_message(id, delay_secs, error))
+    }
+
+    async fn delete_slack_message(&self, id: i32) -> Result<(), StorageError> {
+        delegate!(self.delete_slack_message(id))
+    }
+
+    async fn expire_slack_messages(&self, older_than_minutes: i32) -> Result<u64, StorageError> {
+        delegate!(self.expire_slack_messages(older_than_minutes))
+    }
+
+    async fn add_to_slack_batch(
+        &self,
+        repository_full_name: &str,
+        payload: &serde_json::Value,
+    ) -> Result<(), StorageError> {
+        delegate!(self.add_to_slack_batch(repository_full_name, payload))
+    }
+
+    async fn take_slack_batch(
+        &self,
+        repository_full_name: &str,
+    ) -> Result<Vec<serde_json::Value>, StorageError> {
+        delegate!(self.take_slack_batch(repository_full_name))
+    }
+
+    async fn slack_batch_repositories(&self) -> Result<Vec<String>, StorageError> {
+        delegate!(self.slack_batch_repositories())
+    }
+
+    async fn cached_response(&self, url: &str) -> Result<Option<CachedResponse>, StorageError> {
+        delegate!(self.cached_response(url))
+    }
+
+    async fn cache_response(
+        &self,
+        url: &str,
+        response: &CachedResponse,
+    ) -> Result<(), StorageError> {
+        delegate!(self.cache_response(url, response))
+    }
+
+    async fn refresh_cached_response(&self, url: &str) -> Result<(), StorageError> {
+        delegate!(self.refresh_cached_response(url))
+    }
+
+    async fn purge_cached_responses(&self, older_than_days: i32) -> Result<u64, StorageError> {
+        delegate!(self.purge_cached_responses(older_than_days))
+    }
+
+    async fn notify(&self, channel: &str, payload: &str) -> Result<(), StorageError> {
+        delegate!(self.notify(channel, payload))
+    }
+
+    async fn comment_backlog(&self) -> Result<CommentBacklog, StorageError> {
+        delegate!(self.comment_backlog())
+    }
+
+    async fn job_backlog(&self) -> Result<Vec<JobBacklog>, StorageError> {
+        delegate!(self.job_backlog())
+    }
+
+    async fn slack_outbox_backlog(&self) -> Result<SlackOutboxBacklog, StorageError> {
+        delegate!(self.slack_outbox_backlog())
+    }
+
+    async fn event_stats(&self, since_minutes: i32) -> Result<Vec<EventStats>, StorageError> {
+        delegate!(self.event_stats(since_minutes))
+    }
+
+    async fn comment_idempotency_key(
+        &self,
+        issue_url: &str,
+        fingerprint: &str,
+    ) -> Result<Option<bool>, StorageError> {
+        delegate!(self.comment_idempotency_key(issue_url, fingerprint))
+    }
+
+    async fn save_comment_idempotency_key(
+        &self,
+        issue_url: &str,
+        fingerprint: &str,
+        posted: bool,
+    ) -> Result<(), StorageError> {
+        delegate!(self.save_comment_idempotency_key(issue_url, fingerprint, posted))
+    }
+
+    async fn bot_comment(&self, issue_url: &str) -> Result<Option<String>, StorageError> {
+        delegate!(self.bot_comment(issue_url))
+    }
+
+    async fn save_bot_comment(
+        &self,
+        issue_url: &str,
+        comment_url: &str,
+        body: &str,
+    ) -> Result<(), StorageError> {
+        delegate!(self.save_bot_comment(issue_url, comment_url, body))
+    }
+
+    async fn bot_comment_revisions(
+        &self,
+        issue_url: &str,
+    ) -> Result<Vec<BotCommentRevision>, StorageError> {
+        delegate!(self.bot_comment_revisions(issue_url))
+    }
+
+    async fn record_suggestion_targets(
+        &self,
+        issue: &IssueData,
+        targets: &[&ClosestIssue],
+    ) -> Result<(), StorageError> {
+        delegate!(self.record_suggestion_targets(issue, targets))
+    }
+
+    async fn suggestion_cluster(
+        &self,
+        repository_full_name: &str,
+        number: i32,
+        window_minutes: i32,
+    ) -> Result<Vec<SuggestedIssue>, StorageError> {
+        delegate!(self.suggestion_cluster(repository_full_name, number, window_minutes))
+    }
+
+    async fn prune_suggestion_targets(&self, older_than_minutes: i32) -> Result<(), StorageError> {
+        delegate!(self.prune_suggestion_targets(older_than_minutes))
+    }
+
+    async fn claim_escalation(
+        &self,
+        repository_full_name: &str,
+        number: i32,
+        cooldown_minutes: i32,
+    ) -> Result<bool, StorageError> {
+        delegate!(self.claim_escalation(repository_full_name, number, cooldown_minutes))
+    }
+
+    async fn record_suggestions(
+        &self,
+        issue_source_id: i64,
+        suggestions: &[ClosestIssue],
+        run_id: &str,
+        prompt_profile: Option<&str>,
+    ) -> Result<(), StorageError> {
+        delegate!(self.record_suggestions(issue_source_id, suggestions, run_id, prompt_profile))
+    }
+
+    async fn suggestions(&self, issue_source_id: i64) -> Result<Vec<Suggestion>, StorageError> {
+        delegate!(self.suggestions(issue_source_id))
+    }
+
+    async fn upsert_duplicate_resolution(
+        &self,
+        resolution: &DuplicateResolution,
+    ) -> Result<(), StorageError> {
+        delegate!(self.upsert_duplicate_resolution(resolution))
+    }
+
+    async fn linked_issue_similarities(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<LinkSimilarity>, StorageError> {
+        delegate!(self.linked_issue_similarities(limit))
+    }
+
+    async fn record_link_similarities(
+        &self,
+        model: &str,
+        similarities: &[LinkSimilarity],
+    ) -> Result<(), StorageError> {
+        delegate!(self.record_link_similarities(model, similarities))
+    }
+
+    async fn link_similarities(&self, model: &str) -> Result<Vec<LinkSimilarity>, StorageError> {
+        delegate!(self.link_similarities(model))
+    }
+
+    async fn repository_cursors(&self) -> Result<Vec<RepositoryCursor>, StorageError> {
+        delegate!(self.repository_cursors())
+    }
+
+    async fn advance_repository_cursor(
+        &self,
+        repository_full_name: &str,
+        processed_at: DateTime<Utc>,
+    ) -> Result<(), StorageError> {
+        delegate!(self.advance_repository_cursor(repository_full_name, processed_at))
+    }
+
+    async fn suppress_issue(
+        &self,
+        source_id: i64,
+        suppressed_by: &str,
+    ) -> Result<bool, StorageError> {
+        delegate!(self.suppress_issue(source_id, suppressed_by))
+    }
+
+    async fn unsuppress_issue(&self, source_id: i64) -> Result<bool, StorageError> {
+        delegate!(self.unsuppress_issue(source_id))
+    }
+
+    async fn is_issue_suppressed(&self, source_id: i64) -> Result<bool, StorageError> {
+        delegate!(self.is_issue_suppressed(source_id))
+    }
+
+    async fn is_issue_url_suppressed(&self, issue_url: &str) -> Result<bool, StorageError> {
+        delegate!(self.is_issue_url_suppressed(issue_url))
+    }
+
+    async fn is_issue_number_suppressed(
+        &self,
+        repository_full_name: &str,
+        number: i32,
+    ) -> Result<bool, StorageError> {
+        delegate!(self.is_issue_number_suppressed(repository_full_name, number))
+    }
+
+    async fn opt_out_author(
+        &self,
+        login: &str,
+        requested_through: &str,
+        purge: bool,
+    ) -> Result<OptOutRequest, StorageError> {
+        delegate!(self.opt_out_author(login, requested_through, purge))
+    }
+
+    async fn opt_out_requests(&self) -> Result<Vec<OptOutRequest>, StorageError> {
+        delegate!(self.opt_out_requests())
+    }
+
+    async fn issues_commented_by(&self, login: &str) -> Result<Vec<i64>, StorageError> {
+        delegate!(self.issues_commented_by(login))
+    }
+
+    async fn record_onboarding(
+        &self,
+        repository_full_name: &str,
+        hook_id: Option<i64>,
+        estimated_issues: i64,
+    ) -> Result<OnboardedRepository, StorageError> {
+        delegate!(self.record_onboarding(repository_full_name, hook_id, estimated_issues))
+    }
+
+    async fn onboarded_repositories(&self) -> Result<Vec<OnboardedRepository>, StorageError> {
+        delegate!(self.onboarded_repositories())
+    }
+
+    async fn expire_closure_proposals(&self, older_than_hours: i32) -> Result<(), StorageError> {
+        delegate!(self.expire_closure_proposals(older_than_hours))
+    }
+
+    async fn pending_closure_proposals(&self) -> Result<Vec<ClosureProposal>, StorageError> {
+        delegate!(self.pending_closure_proposals())
+    }
+
+    async fn set_closure_proposal_status(
+        &self,
+        id: i32,
+        status: ClosureProposalStatus,
+    ) -> Result<(), StorageError> {
+        delegate!(self.set_closure_proposal_status(id, status))
+    }
+
+    async fn insert_event_log_entry(&self, entry: &EventLogEntry) -> Result<(), StorageError> {
+        delegate!(self.insert_event_log_entry(entry))
+    }
+
+    async fn mark_events_commented(&self, issue_url: &str) -> Result<(), StorageError> {
+        delegate!(self.mark_events_commented(issue_url))
+    }
+
+    async fn prune_event_log(&self, older_than_days: i32) -> Result<u64, StorageError> {
+        delegate!(self.prune_event_log(older_than_days))
+    }
+
+    async fn event_log(
+        &self,
+        filter: &EventLogFilter,
+        limit: i64,
+    ) -> Result<Vec<EventLogEntry>, StorageError> {
+        delegate!(self.event_log(filter, limit))
+    }
+
+    async fn issues_table_health(&self) -> Result<Option<TableHealth>, StorageError> {
+        delegate!(self.issues_table_health())
+    }
+
+    async fn vacuum_issues(&self, timeout: Duration) -> Result<(), StorageError> {
+        delegate!(self.vacuum_issues(timeout))
+    }
+
+    async fn issues_vector_indexes(&self) -> Result<Vec<String>, StorageError> {
+        delegate!(self.issues_vector_indexes())
+    }
+
+    async fn reindex_issues_index(
+        &self,
+        name: &str,
+        timeout: Duration,
+    ) -> Result<(), StorageError> {
+        delegate!(self.reindex_issues_index(name, timeout))
+    }
+
+    async fn drop_invalid_issues_indexes(&self) -> Result<Vec<String>, StorageError> {
+        delegate!(self.drop_invalid_issues_indexes())
+    }
+
+    async fn insert_compaction_run(&self, run: &CompactionRun) -> Result<(), StorageError> {
+        delegate!(self.insert_compaction_run(run))
+    }
+
+    async fn last_compaction_run(
+        &self,
+        action: &str,
+    ) -> Result<Option<CompactionRun>, StorageError> {
+        delegate!(self.last_compaction_run(action))
+    }
+}
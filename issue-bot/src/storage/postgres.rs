@@ -0,0 +1,3486 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use pgvector::Vector;
+use sha2::{Digest, Sha256};
+use sqlx::{
+    postgres::{PgConnectOptions, PgConnection, PgListener, PgPoolOptions, PgRow},
+    types::Json,
+    Executor, FromRow, Pool, Postgres, QueryBuilder,
+};
+use tracing::{info, warn};
+
+use crate::{
+    config::{
+        DatabaseConfig, IssueState, IterativeScan, Quantization, TwoStageSearchConfig,
+        VectorSearchConfig,
+    },
+    embeddings::EmbeddingMetadata,
+    extraction::SystemInfo,
+    github::IssueWithComments,
+    issue_forms::IssueForm,
+    ClosestIssue, CommentData, IssueData, Source, Vote,
+};
+
+use super::{
+    quote_identifier, ApiKey, ArchivedComment, ArchivedIssue, BotCommentRevision, CachedResponse,
+    ClosureProposal, ClosureProposalStatus, CommentBacklog, CompactionRun, DuplicateResolution,
+    EmbeddingRecord, EventLogEntry, EventLogFilter, EventOutcome, EventStats, ExportedIssue,
+    GuidanceMatch, GuidanceSection, HotIssue, IssueBody, IssueCohort, IssueEmbedding, IssueLink,
+    IssueLinkKind, IssueSimilarity, IssueText, JobBacklog, JobData, JobState, JobType,
+    KnowledgeBaseEntry, KnowledgeBaseMatch, LinkSimilarity, OnboardedRepository, OptOutRequest,
+    PendingComment, RecentSuggestion, RepositoryCursor, RepositoryMetadata, RepositoryStats,
+    SearchHit, SlackOutboxBacklog, SlackOutboxMessage, Storage, StorageError, StoredIssue,
+    StoredIssueId, SuggestedIssue, Suggestion, TableHealth, ISSUE_URL_COLUMNS,
+    REPOSITORY_KEYED_TABLES, REPOSITORY_TABLES,
+};
+
+#[derive(Debug)]
+struct Job {
+    data: Json<JobData>,
+}
+
+struct JobRow {
+    id: i32,
+    job_type: String,
+    repository_full_name: Option<String>,
+    data: Json<JobData>,
+    paused: bool,
+}
+
+impl From<JobRow> for JobState {
+    fn from(row: JobRow) -> Self {
+        Self {
+            id: row.id,
+            job_type: row.job_type,
+            repository_full_name: row.repository_full_name,
+            data: row.data.0,
+            paused: row.paused,
+        }
+    }
+}
+
+/// position of a write whose `pg_lsn` couldn't be read, no replica is past it
+const UNKNOWN_LSN: &str = "FFFFFFFF/FFFFFFFF";
+
+/// searches stay on the primary for this long after the replica failed
+const REPLICA_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// see [crate::config::ReadReplicaConfig]
+#[derive(Clone)]
+struct ReadReplica {
+    pool: Pool<Postgres>,
+    /// `pg_lsn` of the primary after this instance's last write of issues
+    written_lsn: Arc<Mutex<Option<String>>>,
+    /// last `written_lsn` the replica was seen past, sparing a round trip per search until the
+    /// next write
+    replayed_lsn: Arc<Mutex<Option<String>>>,
+    /// when the replica last failed, see [REPLICA_RETRY_AFTER]
+    failed_at: Arc<Mutex<Option<Instant>>>,
+}
+
+impl ReadReplica {
+    fn failed_recently(&self) -> bool {
+        self.failed_at
+            .lock()
+            .unwrap()
+            .is_some_and(|at| at.elapsed() < REPLICA_RETRY_AFTER)
+    }
+
+    fn record_failure(&self, err: &sqlx::Error) {
+        warn!(
+            err = err.to_string(),
+            "read replica unreachable, searching the primary"
+        );
+        *self.failed_at.lock().unwrap() = Some(Instant::now());
+    }
+}
+
+/// errors of a database that can't be reached, rather than of the query
+fn is_connection_error(err: &sqlx::Error) -> bool {
+    matches!(
+        err,
+        sqlx::Error::Io(_)
+            | sqlx::Error::Tls(_)
+            | sqlx::Error::PoolTimedOut
+            | sqlx::Error::PoolClosed
+            | sqlx::Error::WorkerCrashed
+    )
+}
+
+/// Postgres + pgvector backend, see `init_db.sql` for the schema
+#[derive(Clone)]
+pub struct PgStorage {
+    pool: Pool<Postgres>,
+    replica: Option<ReadReplica>,
+    vector_search: VectorSearchConfig,
+    /// two stage searches run, see [TwoStageSearchConfig::recall_sample_every]
+    two_stage_searches: Arc<AtomicU64>,
+}
+
+/// `set` statements run on every new connection
+fn session_settings(cfg: &VectorSearchConfig, statement_timeout: Option<Duration>) -> Vec<String> {
+    let mut settings = Vec::new();
+    if let Some(statement_timeout) = statement_timeout {
+        settings.push(format!(
+            "set statement_timeout = {}",
+            statement_timeout.as_millis()
+        ));
+    }
+    if let Some(ef_search) = cfg.ef_search {
+        settings.push(format!("set hnsw.ef_search = {ef_search}"));
+    }
+    if cfg.iterative_scan != IterativeScan::Off {
+        settings.push(format!(
+            "set hnsw.iterative_scan = {}",
+            cfg.iterative_scan.as_str()
+        ));
+    }
+    if let Some(max_scan_tuples) = cfg.max_scan_tuples {
+        settings.push(format!("set hnsw.max_scan_tuples = {max_scan_tuples}"));
+    }
+    if !cfg.partial_indexes.is_empty() {
+        // generic plans can't prove that `repository_full_name = any($n)` matches a partial
+        // index' predicate
+        settings.push("set plan_cache_mode = force_custom_plan".to_owned());
+    }
+    settings
+}
+
+/// name and creation statement of the HNSW index restricted to `repository_full_name`, named
+/// after its hash to stay within Postgres' identifier length limit
+fn partial_index_statement(
+    repository_full_name: &str,
+    quantization: Quantization,
+) -> Result<(String, String), StorageError> {
+    let valid = !repository_full_name.is_empty()
+        && repository_full_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/'));
+    if !valid {
+        return Err(StorageError::InvalidRepositoryName(
+            repository_full_name.to_owned(),
+        ));
+    }
+    let hash = hex::encode(&Sha256::digest(repository_full_name)[..8]);
+    let (name, expression) = match quantization {
+        Quantization::Halfvec => ("hnsw", "embedding halfvec_cosine_ops"),
+        Quantization::Binary => (
+            "binary_hnsw",
+            "(binary_quantize(embedding)::bit(2560)) bit_hamming_ops",
+        ),
+    };
+    let index = format!("issues_embedding_{name}_{hash}_idx");
+    let statement = format!(
+        "create index concurrently {index} on issues using hnsw ({expression}) where repository_full_name = '{repository_full_name}'"
+    );
+    Ok((index, statement))
+}
+
+/// first stage of two stage searches, `$1` being the embedding and `$2` the number of candidates,
+/// followed by `$3` excluded labels and `$4` repositories when `filtered`
+fn prefetch_query(quantization: Quantization, filtered: bool) -> String {
+    let order = match quantization {
+        Quantization::Halfvec => "embedding <=> $1",
+        Quantization::Binary => "binary_quantize(embedding)::bit(2560) <~> binary_quantize($1)",
+    };
+    let filters = if filtered {
+        " and not (labels && $3) and repository_full_name = any($4)"
+    } else {
+        ""
+    };
+    format!(
+        "select source_id from issues where not excluded and not opted_out and not private and gone_at is null{filters} order by {order} LIMIT $2"
+    )
+}
+
+/// deletes the issues and comments of `login` along with every copy kept of them, returning how
+/// many issues and comments were deleted
+async fn purge_author(
+    tx: &mut sqlx::Transaction<'_, Postgres>,
+    login: &str,
+) -> Result<(u64, u64), StorageError> {
+    let issues = sqlx::query!(
+        "select source_id, url, html_url, repository_full_name, number from issues where author = $1",
+        login
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+    let source_ids: Vec<i64> = issues.iter().map(|i| i.source_id).collect();
+    let urls: Vec<String> = issues.iter().map(|i| i.url.clone()).collect();
+    let html_urls: Vec<String> = issues.iter().map(|i| i.html_url.clone()).collect();
+    let repositories: Vec<String> = issues
+        .iter()
+        .map(|i| i.repository_full_name.clone())
+        .collect();
+    let numbers: Vec<i32> = issues.iter().map(|i| i.number).collect();
+    let comments = sqlx::query!(
+        "select source_id, url from comments where author = $1",
+        login
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+    let comment_ids: Vec<i64> = comments.iter().map(|c| c.source_id).collect();
+    let comment_urls: Vec<String> = comments.iter().map(|c| c.url.clone()).collect();
+
+    // the issues' suggestions, and those of other issues pointing to them
+    sqlx::query(
+        r#"delete from suggestions
+           where issue_source_id = any($1)
+              or (repository_full_name, number) in (select * from unnest($2::varchar[], $3::int[]))"#,
+    )
+    .bind(&source_ids)
+    .bind(&repositories)
+    .bind(&numbers)
+    .execute(&mut **tx)
+    .await?;
+    sqlx::query(
+        r#"delete from event_log
+           where source_id = any($1) or source_id = any($2)
+              or (repository_full_name, issue_number) in (select * from unnest($3::varchar[], $4::int[]))"#,
+    )
+    .bind(&source_ids)
+    .bind(&comment_ids)
+    .bind(&repositories)
+    .bind(&numbers)
+    .execute(&mut **tx)
+    .await?;
+    // the bot's comments on the issues, and those listing them
+    sqlx::query(
+        r#"delete from bot_comment_revisions
+           where issue_url = any($1)
+              or exists (select 1 from unnest($2::varchar[]) u where strpos(body, u) > 0)"#,
+    )
+    .bind(&urls)
+    .bind(&html_urls)
+    .execute(&mut **tx)
+    .await?;
+    sqlx::query("delete from bot_comments where issue_url = any($1)")
+        .bind(&urls)
+        .execute(&mut **tx)
+        .await?;
+    sqlx::query(
+        "delete from slack_outbox where exists (select 1 from unnest($1::varchar[]) u where strpos(payload::text, u) > 0)",
+    )
+    .bind(&html_urls)
+    .execute(&mut **tx)
+    .await?;
+    sqlx::query(
+        r#"delete from knowledge_base
+           where exists (select 1 from unnest($1::varchar[]) u where strpos(question || answer, u) > 0)"#,
+    )
+    .bind(&html_urls)
+    .execute(&mut **tx)
+    .await?;
+    // the issues, their comments pages and the author's comments
+    sqlx::query(
+        r#"delete from github_response_cache
+           where url = any($1) or url = any($2)
+              or exists (select 1 from unnest($1::varchar[]) u where starts_with(url, u || '/'))"#,
+    )
+    .bind(&urls)
+    .bind(&comment_urls)
+    .execute(&mut **tx)
+    .await?;
+    sqlx::query!(
+        "delete from archived_issues where data->>'author' = $1",
+        login
+    )
+    .execute(&mut **tx)
+    .await?;
+    sqlx::query!(
+        r#"update archived_issues
+           set data = jsonb_set(data, '{comments}', (
+               select coalesce(jsonb_agg(c), '[]'::jsonb)
+               from jsonb_array_elements(data->'comments') c
+               where c->>'author' is distinct from $1
+           ))
+           where data->'comments' @> jsonb_build_array(jsonb_build_object('author', $1::text))"#,
+        login
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    // comments of other authors go along with the issues
+    let comments = sqlx::query!("delete from comments where author = $1", login)
+        .execute(&mut **tx)
+        .await?
+        .rows_affected();
+    let issues = sqlx::query!("delete from issues where author = $1", login)
+        .execute(&mut **tx)
+        .await?
+        .rows_affected();
+    Ok((issues, comments))
+}
+
+/// columns of [Storage::closest_hot_issues], the content of the issues of the `$cached_param`
+/// array being left out
+fn hot_issue_columns(cached_param: u8) -> String {
+    let mut columns = "source_id".to_owned();
+    for (column, alias) in [
+        ("title", "title"),
+        ("number", "number"),
+        ("html_url", "html_url"),
+        ("labels", "labels"),
+        ("repository_full_name", "repository_full_name"),
+        ("embedding::vector", "embedding"),
+    ] {
+        columns.push_str(&format!(
+            ", case when source_id = any(${cached_param}) then null else {column} end as {alias}"
+        ));
+    }
+    columns
+}
+
+/// Row of [hot_issue_columns], the content being null for the cached issues
+#[derive(FromRow)]
+struct HotIssueRow {
+    source_id: i64,
+    cosine_similarity: f64,
+    title: Option<String>,
+    number: Option<i32>,
+    html_url: Option<String>,
+    labels: Option<Vec<String>>,
+    repository_full_name: Option<String>,
+    embedding: Option<Vector>,
+}
+
+impl From<HotIssueRow> for IssueSimilarity {
+    fn from(row: HotIssueRow) -> Self {
+        let issue = match (
+            row.title,
+            row.number,
+            row.html_url,
+            row.labels,
+            row.repository_full_name,
+            row.embedding,
+        ) {
+            (
+                Some(title),
+                Some(number),
+                Some(html_url),
+                Some(labels),
+                Some(repository_full_name),
+                Some(embedding),
+            ) => Some(HotIssue {
+                source_id: row.source_id,
+                title,
+                number,
+                html_url,
+                labels,
+                repository_full_name,
+                embedding: embedding.to_vec(),
+            }),
+            _ => None,
+        };
+        Self {
+            source_id: row.source_id,
+            cosine_similarity: row.cosine_similarity,
+            issue,
+        }
+    }
+}
+
+/// `$5` closest issues to `$1` by hamming distance, the outer query rescoring them and filtering
+/// out the `$2` excluded labels and the repositories but `$3`
+///
+/// Filtering in the index scan makes it come back short, or Postgres sort the filtered issues
+/// without the binary index, unless iterative scans keep it going past the filtered out issues.
+fn binary_candidates_query(iterative_scan: IterativeScan) -> &'static str {
+    match iterative_scan {
+        IterativeScan::Off => {
+            "select source_id, title, number, html_url, labels, repository_full_name, embedding from issues
+             where not excluded and not opted_out and not private and gone_at is null
+             order by binary_quantize(embedding)::bit(2560) <~> binary_quantize($1) LIMIT $5"
+        }
+        IterativeScan::StrictOrder | IterativeScan::RelaxedOrder => {
+            "select source_id, title, number, html_url, labels, repository_full_name, embedding from issues
+             where not excluded and not opted_out and not private and gone_at is null and not (labels && $2)
+               and repository_full_name = any($3)
+             order by binary_quantize(embedding)::bit(2560) <~> binary_quantize($1) LIMIT $5"
+        }
+    }
+}
+
+/// share of the `exact` closest issues found among the `candidates` of the index scan
+fn recall(exact: &[i64], candidates: &[i64]) -> f64 {
+    if exact.is_empty() {
+        return 1.;
+    }
+    let found = exact.iter().filter(|id| candidates.contains(id)).count();
+    found as f64 / exact.len() as f64
+}
+
+fn record_stage_duration(stage: &'static str, started_at: Instant) {
+    metrics::histogram!("issue_bot_vector_search_stage_seconds", "stage" => stage)
+        .record(started_at.elapsed().as_secs_f64());
+}
+
+fn pool_options(
+    max_connections: u32,
+    cfg: &VectorSearchConfig,
+    statement_timeout: Option<Duration>,
+) -> PgPoolOptions {
+    let settings = session_settings(cfg, statement_timeout);
+    PgPoolOptions::new()
+        .max_connections(max_connections)
+        .after_connect(move |conn: &mut PgConnection, _| {
+            let settings = settings.clone();
+            Box::pin(async move {
+                for setting in settings {
+                    conn.execute(setting.as_str()).await?;
+                }
+                Ok(())
+            })
+        })
+}
+
+impl PgStorage {
+    /// runs a maintenance `statement` cancelled after `timeout`, on a connection of its own as
+    /// neither `VACUUM` nor `REINDEX CONCURRENTLY` can run in a transaction block, which prepared
+    /// statements are wrapped in
+    async fn maintenance(&self, statement: &str, timeout: Duration) -> Result<(), StorageError> {
+        let mut conn = self.pool.acquire().await?;
+        let previous: String = sqlx::query_scalar("select current_setting('statement_timeout')")
+            .fetch_one(&mut *conn)
+            .await?;
+        // 0 would turn the timeout off
+        let millis = timeout.as_millis().max(1).to_string();
+        sqlx::query("select set_config('statement_timeout', $1, false)")
+            .bind(&millis)
+            .execute(&mut *conn)
+            .await?;
+        let res = conn.execute(sqlx::raw_sql(statement)).await;
+        sqlx::query("select set_config('statement_timeout', $1, false)")
+            .bind(&previous)
+            .execute(&mut *conn)
+            .await?;
+        res?;
+        Ok(())
+    }
+
+    pub async fn connect(
+        cfg: &DatabaseConfig,
+        statement_timeout: Option<Duration>,
+    ) -> Result<Self, StorageError> {
+        let opts: PgConnectOptions = cfg.connection_string.parse()?;
+        let pool = pool_options(cfg.max_connections, &cfg.vector_search, statement_timeout)
+            .connect_with(opts)
+            .await?;
+        // connected lazily, an unreachable replica doesn't prevent starting
+        let replica = match &cfg.read_replica {
+            Some(replica_cfg) => {
+                let opts: PgConnectOptions = replica_cfg.connection_string.parse()?;
+                Some(ReadReplica {
+                    pool: pool_options(
+                        replica_cfg.max_connections,
+                        &cfg.vector_search,
+                        statement_timeout,
+                    )
+                    .acquire_timeout(Duration::from_millis(replica_cfg.acquire_timeout_ms))
+                    .connect_lazy_with(opts),
+                    written_lsn: Arc::default(),
+                    replayed_lsn: Arc::default(),
+                    failed_at: Arc::default(),
+                })
+            }
+            None => None,
+        };
+        let storage = Self {
+            pool,
+            replica,
+            vector_search: cfg.vector_search.clone(),
+            two_stage_searches: Arc::default(),
+        };
+        // built in the background, see [Storage::create_partial_indexes]
+        for repository_full_name in &storage.vector_search.partial_indexes {
+            partial_index_statement(repository_full_name, storage.vector_search.quantization)?;
+        }
+        Ok(storage)
+    }
+
+    /// reconnects on its own when its connection is lost, notifications sent meanwhile being lost
+    pub async fn listen(&self, channel: &str) -> Result<PgListener, StorageError> {
+        let mut listener = PgListener::connect_with(&self.pool).await?;
+        listener.listen(channel).await?;
+        Ok(listener)
+    }
+
+    /// pool similarity searches are sent to, the replica only once it has replayed the last
+    /// write of issues, so that a search right after inserting an issue finds it
+    async fn search_pool(&self) -> &Pool<Postgres> {
+        let Some(replica) = &self.replica else {
+            return &self.pool;
+        };
+        let reason = if replica.failed_recently() {
+            "unreachable"
+        } else {
+            let written_lsn = replica
+                .written_lsn
+                .lock()
+                .unwrap()
+                .clone()
+                .unwrap_or_else(|| "0/0".to_owned());
+            if replica.replayed_lsn.lock().unwrap().as_ref() == Some(&written_lsn) {
+                return &replica.pool;
+            }
+            // null when the replica isn't in recovery, i.e. it was promoted
+            let caught_up: Result<Option<bool>, sqlx::Error> =
+                sqlx::query_scalar("select pg_last_wal_replay_lsn() >= $1::pg_lsn")
+                    .bind(&written_lsn)
+                    .fetch_one(&replica.pool)
+                    .await;
+            match caught_up {
+                Ok(Some(true) | None) => {
+                    *replica.replayed_lsn.lock().unwrap() = Some(written_lsn);
+                    return &replica.pool;
+                }
+                Ok(Some(false)) => "lag",
+                Err(err) => {
+                    replica.record_failure(&err);
+                    "unreachable"
+                }
+            }
+        };
+        metrics::counter!("issue_bot_read_replica_fallbacks_total", "reason" => reason)
+            .increment(1);
+        &self.pool
+    }
+
+    /// runs `search` on the [search_pool](Self::search_pool), again on the primary when the
+    /// replica fails to answer
+    async fn search<'a, T, E, F, Fut>(&'a self, search: F) -> Result<T, StorageError>
+    where
+        F: Fn(&'a Pool<Postgres>) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        StorageError: From<E>,
+    {
+        let pool = self.search_pool().await;
+        match (
+            search(pool).await.map_err(StorageError::from),
+            &self.replica,
+        ) {
+            (Err(StorageError::Sqlx(err)), Some(replica))
+                if is_connection_error(&err) && std::ptr::eq(pool, &replica.pool) =>
+            {
+                replica.record_failure(&err);
+                metrics::counter!("issue_bot_read_replica_fallbacks_total", "reason" => "unreachable")
+                    .increment(1);
+                Ok(search(&self.pool).await?)
+            }
+            (res, _) => res,
+        }
+    }
+
+    /// remembers the primary's position after a write of issues searches must see
+    ///
+    /// The write is committed whatever happens here, searches stay on the primary until the
+    /// next write when its position can't be read.
+    async fn track_write(&self) {
+        let Some(replica) = &self.replica else {
+            return;
+        };
+        let lsn = match sqlx::query_scalar("select pg_current_wal_lsn()::text")
+            .fetch_one(&self.pool)
+            .await
+        {
+            Ok(lsn) => lsn,
+            Err(err) => {
+                warn!(
+                    err = err.to_string(),
+                    "failed to read the position of a write, searching the primary"
+                );
+                UNKNOWN_LSN.to_owned()
+            }
+        };
+        *replica.written_lsn.lock().unwrap() = Some(lsn);
+    }
+
+    /// searches `columns` of the closest issues in two stages, see [TwoStageSearchConfig], binding
+    /// `cached` as `$6` for [hot_issue_columns]
+    #[allow(clippy::too_many_arguments)]
+    async fn two_stage_search<T>(
+        &self,
+        pool: &Pool<Postgres>,
+        two_stage: &TwoStageSearchConfig,
+        columns: &str,
+        embedding: &[f32],
+        excluded_labels: &[String],
+        repositories: &[String],
+        limit: i64,
+        cached: Option<&[i64]>,
+    ) -> Result<Vec<T>, StorageError>
+    where
+        T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        let embedding = Vector::from(embedding.to_vec());
+        let started_at = Instant::now();
+        let prefetch = prefetch_query(
+            self.vector_search.quantization,
+            !two_stage.filter_on_rescore,
+        );
+        let mut query = sqlx::query_scalar(&prefetch)
+            .bind(&embedding)
+            .bind(two_stage.prefetch.max(limit));
+        if !two_stage.filter_on_rescore {
+            query = query.bind(excluded_labels).bind(repositories);
+        }
+        let candidates: Vec<i64> = query.fetch_all(pool).await?;
+        record_stage_duration("prefetch", started_at);
+
+        let started_at = Instant::now();
+        let rescore = format!(
+            r#"select {columns}, 1 - (embedding::vector <=> $1) as cosine_similarity from issues
+               where source_id = any($2) and not excluded and not opted_out and not private and gone_at is null
+                 and not (labels && $3) and repository_full_name = any($4)
+               order by cosine_similarity desc LIMIT $5"#
+        );
+        let mut query = sqlx::query_as(&rescore)
+            .bind(&embedding)
+            .bind(&candidates)
+            .bind(excluded_labels)
+            .bind(repositories)
+            .bind(limit);
+        if let Some(cached) = cached {
+            query = query.bind(cached);
+        }
+        let issues = query.fetch_all(pool).await?;
+        record_stage_duration("rescore", started_at);
+
+        let searches = self.two_stage_searches.fetch_add(1, Ordering::Relaxed);
+        if let Some(every) = two_stage.recall_sample_every.filter(|every| *every > 0) {
+            if searches.is_multiple_of(every) {
+                let pool = pool.clone();
+                let excluded_labels = excluded_labels.to_vec();
+                let repositories = repositories.to_vec();
+                tokio::spawn(async move {
+                    // ordering on the cast embeddings bypasses the HNSW index
+                    let exact: Result<Vec<i64>, sqlx::Error> = sqlx::query_scalar(
+                        r#"select source_id from issues
+                           where not excluded and not opted_out and not private and gone_at is null
+                             and not (labels && $2) and repository_full_name = any($3)
+                           order by embedding::vector <=> $1 LIMIT $4"#,
+                    )
+                    .bind(&embedding)
+                    .bind(excluded_labels)
+                    .bind(repositories)
+                    .bind(limit)
+                    .fetch_all(&pool)
+                    .await;
+                    match exact {
+                        Ok(exact) => {
+                            metrics::histogram!("issue_bot_vector_search_recall")
+                                .record(recall(&exact, &candidates));
+                        }
+                        Err(err) => warn!(err = err.to_string(), "failed to measure search recall"),
+                    }
+                });
+            }
+        }
+        Ok(issues)
+    }
+}
+
+impl Storage for PgStorage {
+    async fn check(&self) -> Result<(), StorageError> {
+        let version: Option<String> =
+            sqlx::query_scalar("select extversion from pg_extension where extname = 'vector'")
+                .fetch_optional(&self.pool)
+                .await?;
+        let Some(version) = version else {
+            return Err(StorageError::MissingExtension("vector"));
+        };
+        let mut parts = version
+            .split('.')
+            .map(|p| p.parse::<u32>().unwrap_or_default());
+        let (major, minor) = (
+            parts.next().unwrap_or_default(),
+            parts.next().unwrap_or_default(),
+        );
+        if self.vector_search.iterative_scan != IterativeScan::Off && (major, minor) < (0, 8) {
+            return Err(StorageError::UnsupportedExtensionVersion {
+                extension: "vector",
+                feature: "iterative index scans",
+                found: version,
+                required: "0.8",
+            });
+        }
+        if self.vector_search.quantization == Quantization::Binary {
+            let index: Option<String> = sqlx::query_scalar(
+                "select indexname from pg_indexes where indexname = 'issues_embedding_binary_hnsw_idx'",
+            )
+            .fetch_optional(&self.pool)
+            .await?;
+            if index.is_none() {
+                return Err(StorageError::MissingIndex {
+                    index: "issues_embedding_binary_hnsw_idx",
+                    migration: "binary_quantization.sql",
+                });
+            }
+        }
+        Ok(())
+    }
+
+    async fn create_partial_indexes(&self) -> Result<(), StorageError> {
+        for repository_full_name in &self.vector_search.partial_indexes {
+            let (index, statement) =
+                partial_index_statement(repository_full_name, self.vector_search.quantization)?;
+            // false when a concurrent build failed, leaving an index that is maintained but
+            // never used
+            let valid: Option<bool> = sqlx::query_scalar(
+                r#"select i.indisvalid from pg_index i
+                   join pg_class c on c.oid = i.indexrelid
+                   where c.relname = $1"#,
+            )
+            .bind(&index)
+            .fetch_optional(&self.pool)
+            .await?;
+            match valid {
+                Some(true) => continue,
+                Some(false) => {
+                    warn!(
+                        repository_full_name,
+                        index, "rebuilding invalid partial embedding index"
+                    );
+                    sqlx::raw_sql(&format!("drop index concurrently if exists {index}"))
+                        .execute(&self.pool)
+                        .await?;
+                }
+                None => info!(
+                    repository_full_name,
+                    index, "creating partial embedding index"
+                ),
+            }
+            sqlx::raw_sql(&statement).execute(&self.pool).await?;
+        }
+        Ok(())
+    }
+
+    async fn closest_issues(
+        &self,
+        embedding: &[f32],
+        excluded_labels: &[String],
+        repositories: &[String],
+        limit: i64,
+        with_embeddings: bool,
+    ) -> Result<Vec<ClosestIssue>, StorageError> {
+        // casting every candidate's embedding is only worth it when they are compared
+        let embedding_column = if with_embeddings {
+            ", embedding::vector as embedding"
+        } else {
+            ""
+        };
+        if let Some(two_stage) = &self.vector_search.two_stage {
+            let columns =
+                format!("title, number, html_url, labels, repository_full_name{embedding_column}");
+            return self
+                .search(|pool| {
+                    self.two_stage_search(
+                        pool,
+                        two_stage,
+                        &columns,
+                        embedding,
+                        excluded_labels,
+                        repositories,
+                        limit,
+                        None,
+                    )
+                })
+                .await;
+        }
+        let statement = match self.vector_search.quantization {
+            Quantization::Halfvec => format!("select title, number, html_url, labels, repository_full_name, 1 - (embedding <=> $1) as cosine_similarity{embedding_column} from issues where not excluded and not opted_out and not private and gone_at is null and not (labels && $2) and repository_full_name = any($3) order by embedding <=> $1 LIMIT $4"),
+            // candidates found with the binary quantized index, rescored on the full embeddings,
+            // see [binary_candidates_query]
+            Quantization::Binary => format!(
+                r#"select title, number, html_url, labels, repository_full_name, 1 - (embedding <=> $1) as cosine_similarity{embedding_column}
+                   from ({}) candidates
+                   where not (labels && $2) and repository_full_name = any($3)
+                   order by embedding <=> $1 LIMIT $4"#,
+                binary_candidates_query(self.vector_search.iterative_scan)
+            ),
+        };
+        let embedding = Vector::from(embedding.to_vec());
+        let mut issues: Vec<ClosestIssue> = self
+            .search(|pool| {
+                let mut query = sqlx::query_as(&statement)
+                    .bind(&embedding)
+                    .bind(excluded_labels)
+                    .bind(repositories)
+                    .bind(limit);
+                if self.vector_search.quantization == Quantization::Binary {
+                    query = query.bind(limit * self.vector_search.rescore_factor.max(1));
+                }
+                query.fetch_all(pool)
+            })
+            .await?;
+        if self.vector_search.iterative_scan == IterativeScan::RelaxedOrder {
+            issues.sort_by(|a, b| b.cosine_similarity.total_cmp(&a.cosine_similarity));
+        }
+        Ok(issues)
+    }
+
+    async fn closest_hot_issues(
+        &self,
+        embedding: &[f32],
+        excluded_labels: &[String],
+        repositories: &[String],
+        limit: i64,
+        cached: &[i64],
+    ) -> Result<Vec<IssueSimilarity>, StorageError> {
+        if let Some(two_stage) = &self.vector_search.two_stage {
+            let columns = hot_issue_columns(6);
+            let rows: Vec<HotIssueRow> = self
+                .search(|pool| {
+                    self.two_stage_search(
+                        pool,
+                        two_stage,
+                        &columns,
+                        embedding,
+                        excluded_labels,
+                        repositories,
+                        limit,
+                        Some(cached),
+                    )
+                })
+                .await?;
+            return Ok(rows.into_iter().map(IssueSimilarity::from).collect());
+        }
+        let statement = match self.vector_search.quantization {
+            Quantization::Halfvec => format!("select {}, 1 - (embedding <=> $1) as cosine_similarity from issues where not excluded and not opted_out and not private and gone_at is null and not (labels && $2) and repository_full_name = any($3) order by embedding <=> $1 LIMIT $4", hot_issue_columns(5)),
+            Quantization::Binary => format!(
+                r#"select {}, 1 - (embedding <=> $1) as cosine_similarity
+                   from ({}) candidates
+                   where not (labels && $2) and repository_full_name = any($3)
+                   order by embedding <=> $1 LIMIT $4"#,
+                hot_issue_columns(6),
+                binary_candidates_query(self.vector_search.iterative_scan)
+            ),
+        };
+        let embedding = Vector::from(embedding.to_vec());
+        let rows: Vec<HotIssueRow> = self
+            .search(|pool| {
+                let mut query = sqlx::query_as(&statement)
+                    .bind(&embedding)
+                    .bind(excluded_labels)
+                    .bind(repositories)
+                    .bind(limit);
+                if self.vector_search.quantization == Quantization::Binary {
+                    query = query.bind(limit * self.vector_search.rescore_factor.max(1));
+                }
+                query.bind(cached).fetch_all(pool)
+            })
+            .await?;
+        let mut issues: Vec<IssueSimilarity> =
+            rows.into_iter().map(IssueSimilarity::from).collect();
+        if self.vector_search.iterative_scan == IterativeScan::RelaxedOrder {
+            issues.sort_by(|a, b| b.cosine_similarity.total_cmp(&a.cosine_similarity));
+        }
+        Ok(issues)
+    }
+
+    async fn hot_issues(&self, source_ids: &[i64]) -> Result<Vec<HotIssue>, StorageError> {
+        self.search(|pool| {
+            sqlx::query_as(
+                r#"select source_id, title, number, html_url, labels, repository_full_name, embedding::vector as embedding
+                   from issues where source_id = any($1)"#,
+            )
+            .bind(source_ids)
+            .fetch_all(pool)
+        })
+        .await
+    }
+
+    async fn issue_by_fingerprint(
+        &self,
+        fingerprint: &str,
+        excluded_labels: &[String],
+        repositories: &[String],
+    ) -> Result<Option<ClosestIssue>, StorageError> {
+        let issue = sqlx::query_as(
+            r#"select title, number, html_url, labels, repository_full_name, 1::float8 as cosine_similarity, embedding::vector as embedding
+               from issues
+               where fingerprint = $1 and not excluded and not opted_out and not private and gone_at is null and not (labels && $2)
+                 and repository_full_name = any($3)
+               order by created_at, id limit 1"#,
+        )
+        .bind(fingerprint)
+        .bind(excluded_labels)
+        .bind(repositories)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(issue)
+    }
+
+    async fn set_issue_fingerprint(
+        &self,
+        source_id: i64,
+        fingerprint: Option<&str>,
+    ) -> Result<(), StorageError> {
+        sqlx::query!(
+            "update issues set fingerprint = $1 where source_id = $2",
+            fingerprint,
+            source_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn unfingerprinted_issues(
+        &self,
+        after_id: i32,
+        limit: i64,
+    ) -> Result<Vec<IssueBody>, StorageError> {
+        let issues = sqlx::query_as!(
+            IssueBody,
+            r#"select id, source_id, body from issues
+               where id > $1 and fingerprint is null
+                 and body like '%Traceback (most recent call last)%'
+               order by id
+               limit $2"#,
+            after_id,
+            limit,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(issues)
+    }
+
+    async fn set_system_info(&self, source_id: i64, info: &SystemInfo) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"update issues
+               set package_version = $1, python_version = $2, torch_version = $3, os = $4,
+                   accelerator = $5, gpu = $6
+               where source_id = $7"#,
+            info.package_version,
+            info.python_version,
+            info.torch_version,
+            info.os,
+            info.accelerator,
+            info.gpu,
+            source_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn merge_system_info(
+        &self,
+        source_id: i64,
+        info: &SystemInfo,
+    ) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"update issues
+               set package_version = coalesce($1, package_version),
+                   python_version = coalesce($2, python_version),
+                   torch_version = coalesce($3, torch_version), os = coalesce($4, os),
+                   accelerator = coalesce($5, accelerator), gpu = coalesce($6, gpu)
+               where source_id = $7"#,
+            info.package_version,
+            info.python_version,
+            info.torch_version,
+            info.os,
+            info.accelerator,
+            info.gpu,
+            source_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn set_issue_form(&self, source_id: i64, form: &IssueForm) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"update issues
+               set form_description = $1, form_reproduction = $2, form_expected_behavior = $3,
+                   form_environment = $4
+               where source_id = $5"#,
+            form.description,
+            form.reproduction,
+            form.expected_behavior,
+            form.environment,
+            source_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn set_code_context(
+        &self,
+        source_id: i64,
+        code_context: Option<&str>,
+    ) -> Result<(), StorageError> {
+        sqlx::query!(
+            "update issues set code_context = $1 where source_id = $2",
+            code_context,
+            source_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_issue(
+        &self,
+        issue: &IssueData,
+        embedding: &[f32],
+        fingerprint: Option<&str>,
+    ) -> Result<(), StorageError> {
+        // issues of authors who opted out and of private repositories are flagged right away
+        sqlx::query(
+            r#"insert into issues (source_id, source, title, body, is_pull_request, number, html_url, url, repository_full_name, labels, milestone, embedding, author, fingerprint, opted_out, private)
+               values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, exists (select 1 from opt_out_requests where login = $13), exists (select 1 from private_repositories where repository_full_name = $9))"#,
+        )
+        .bind(issue.source_id)
+        .bind(issue.source.to_string())
+        .bind(&issue.title)
+        .bind(&issue.body)
+        .bind(issue.is_pull_request)
+        .bind(issue.number)
+        .bind(&issue.html_url)
+        .bind(&issue.url)
+        .bind(&issue.repository_full_name)
+        .bind(&issue.labels)
+        .bind(&issue.milestone)
+        .bind(Vector::from(embedding.to_vec()))
+        .bind(&issue.author)
+        .bind(fingerprint)
+        .execute(&self.pool)
+        .await?;
+        self.track_write().await;
+        Ok(())
+    }
+
+    async fn update_issue(
+        &self,
+        issue: &IssueData,
+        fingerprint: Option<&str>,
+    ) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"update issues
+               set title = $1, body = $2, url = $3, labels = $4, milestone = $5, fingerprint = $6,
+                   updated_at = current_timestamp, excluded = false, gone_at = null
+               where source_id = $7"#,
+            issue.title,
+            issue.body,
+            issue.url,
+            issue.labels.as_slice(),
+            issue.milestone,
+            fingerprint,
+            issue.source_id,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn update_issue_metadata(
+        &self,
+        source_id: i64,
+        labels: &[String],
+        milestone: Option<&str>,
+    ) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"update issues
+               set labels = $1, milestone = $2, updated_at = current_timestamp, gone_at = null
+               where source_id = $3"#,
+            labels,
+            milestone,
+            source_id,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_issue(&self, source_id: i64) -> Result<(), StorageError> {
+        sqlx::query!(r#"DELETE FROM issues WHERE source_id = $1"#, source_id)
+            .execute(&self.pool)
+            .await?;
+        self.track_write().await;
+        Ok(())
+    }
+
+    async fn delete_issues(&self, source_ids: &[i64]) -> Result<(), StorageError> {
+        sqlx::query!("delete from issues where source_id = any($1)", source_ids)
+            .execute(&self.pool)
+            .await?;
+        self.track_write().await;
+        Ok(())
+    }
+
+    async fn set_issue_closed(&self, source_id: i64, closed: bool) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"update issues
+               set closed_at = case when $2 then coalesce(closed_at, current_timestamp) end,
+                   excluded = $2 and excluded, gone_at = null
+               where source_id = $1"#,
+            source_id,
+            closed,
+        )
+        .execute(&self.pool)
+        .await?;
+        self.track_write().await;
+        Ok(())
+    }
+
+    async fn issue_id(&self, source_id: i64) -> Result<Option<i32>, StorageError> {
+        let id = sqlx::query_scalar!("select id from issues where source_id = $1", source_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(id)
+    }
+
+    async fn issue_source_id(
+        &self,
+        repository_full_name: &str,
+        number: i32,
+    ) -> Result<Option<i64>, StorageError> {
+        let source_id = sqlx::query_scalar!(
+            "select source_id from issues where repository_full_name = $1 and number = $2",
+            repository_full_name,
+            number,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(source_id)
+    }
+
+    async fn stored_issue(&self, source_id: i64) -> Result<Option<StoredIssue>, StorageError> {
+        let issue = sqlx::query_as!(
+            StoredIssue,
+            "select source, repository_full_name, number, url from issues where source_id = $1",
+            source_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(issue)
+    }
+
+    async fn insert_indexed_issue(
+        &self,
+        issue: &IssueWithComments,
+        source: &Source,
+        repository_full_name: &str,
+        embedding: &[f32],
+        fingerprint: Option<&str>,
+    ) -> Result<i32, StorageError> {
+        let id = sqlx::query_scalar(
+            r#"insert into issues (source_id, source, title, body, is_pull_request, number, html_url, url, repository_full_name, labels, milestone, embedding, author, fingerprint, opted_out, private)
+               values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, exists (select 1 from opt_out_requests where login = $13), exists (select 1 from private_repositories where repository_full_name = $9))
+               returning id"#,
+        )
+        .bind(issue.id)
+        .bind(source.to_string())
+        .bind(&issue.title)
+        .bind(&issue.body)
+        .bind(issue.is_pull_request)
+        .bind(issue.number)
+        .bind(&issue.html_url)
+        .bind(&issue.url)
+        .bind(repository_full_name)
+        .bind(&issue.labels)
+        .bind(&issue.milestone)
+        .bind(Vector::from(embedding.to_vec()))
+        .bind(&issue.author)
+        .bind(fingerprint)
+        .fetch_one(&self.pool)
+        .await?;
+        self.track_write().await;
+        Ok(id)
+    }
+
+    async fn insert_indexed_comments(
+        &self,
+        issue_id: i32,
+        issue: &IssueWithComments,
+    ) -> Result<(), StorageError> {
+        if issue.comments.is_empty() {
+            return Ok(());
+        }
+        let mut qb = QueryBuilder::new(
+            "insert into comments (source_id, body, url, issue_id, is_review, author, opted_out)",
+        );
+        qb.push_values(&issue.comments, |mut b, comment| {
+            b.push_bind(comment.id)
+                .push_bind(&comment.body)
+                .push_bind(&comment.url)
+                .push_bind(issue_id)
+                .push_bind(comment.is_review)
+                .push_bind(comment.author())
+                .push("exists (select 1 from opt_out_requests where login = ")
+                .push_bind_unseparated(comment.author())
+                .push_unseparated(")");
+        });
+        qb.push("on conflict do nothing");
+        qb.build().execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn insert_comment(
+        &self,
+        issue_id: i32,
+        comment: &CommentData,
+    ) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"insert into comments (source_id, body, url, issue_id, is_review, author, opted_out)
+               values ($1, $2, $3, $4, $5, $6, exists (select 1 from opt_out_requests where login = $6::varchar))"#,
+            comment.source_id,
+            comment.body,
+            comment.url,
+            issue_id,
+            comment.is_review,
+            comment.author,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn update_comment(&self, comment: &CommentData) -> Result<Option<i64>, StorageError> {
+        let issue_source_id = sqlx::query_scalar(
+            r#"update comments as c
+               set body = $1, url = $2, updated_at = current_timestamp
+               from issues as i
+               where c.source_id = $3 and i.id = c.issue_id
+               returning i.source_id"#,
+        )
+        .bind(&comment.body)
+        .bind(&comment.url)
+        .bind(comment.source_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(issue_source_id)
+    }
+
+    async fn delete_comment(&self, source_id: i64) -> Result<Option<i64>, StorageError> {
+        let issue_source_id = sqlx::query_scalar(
+            r#"delete from comments as c
+               using issues as i
+               where c.source_id = $1 and i.id = c.issue_id
+               returning i.source_id"#,
+        )
+        .bind(source_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(issue_source_id)
+    }
+
+    async fn archived_issue(&self, source_id: i64) -> Result<Option<ArchivedIssue>, StorageError> {
+        let Some(issue) = sqlx::query!(
+            r#"select id, source, title, body, is_pull_request, number, html_url, url, repository_full_name, labels, milestone, author, embedding::vector as "embedding!: Vector"
+               from issues where source_id = $1"#,
+            source_id,
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        else {
+            return Ok(None);
+        };
+        let comments = sqlx::query_as!(
+            ArchivedComment,
+            "select source_id, body, url, author from comments where issue_id = $1 order by source_id",
+            issue.id,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(Some(ArchivedIssue {
+            source_id,
+            source: issue.source,
+            title: issue.title,
+            body: issue.body,
+            is_pull_request: issue.is_pull_request,
+            number: issue.number,
+            html_url: issue.html_url,
+            url: issue.url,
+            repository_full_name: issue.repository_full_name,
+            labels: issue.labels,
+            milestone: issue.milestone,
+            author: issue.author,
+            comments,
+            embedding: issue.embedding.to_vec(),
+        }))
+    }
+
+    async fn archive_issue(&self, issue: &ArchivedIssue) -> Result<(), StorageError> {
+        sqlx::query(
+            "insert into archived_issues (source_id, repository_full_name, data) values ($1, $2, $3)",
+        )
+        .bind(issue.source_id)
+        .bind(&issue.repository_full_name)
+        .bind(Json(issue))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn issue_text(&self, source_id: i64) -> Result<IssueText, StorageError> {
+        let issue = sqlx::query!(
+            r#"
+                SELECT
+                  i.repository_full_name,
+                  i.labels,
+                  i.title,
+                  i.body,
+                  i.code_context,
+                  (
+                    SELECT JSON_AGG(c.body ORDER BY c.source_id)
+                    FROM comments AS c
+                    WHERE c.issue_id = i.id AND NOT c.opted_out
+                  ) AS comments
+                FROM
+                  issues AS i
+                WHERE
+                  i.source_id = $1;
+            "#,
+            source_id,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        let comments = match issue.comments {
+            Some(comments) => serde_json::from_value(comments)?,
+            None => Vec::new(),
+        };
+        Ok(IssueText {
+            repository_full_name: issue.repository_full_name,
+            labels: issue.labels,
+            title: issue.title,
+            body: issue.body,
+            comments,
+            code_context: issue.code_context,
+        })
+    }
+
+    async fn update_issue_embedding(
+        &self,
+        source_id: i64,
+        embedding: &[f32],
+    ) -> Result<(), StorageError> {
+        sqlx::query(
+            r#"update issues
+               set embedding = $1, updated_at = current_timestamp
+               where source_id = $2"#,
+        )
+        .bind(Vector::from(embedding.to_vec()))
+        .bind(source_id)
+        .execute(&self.pool)
+        .await?;
+        self.track_write().await;
+        Ok(())
+    }
+
+    async fn record_embedding_metadata(
+        &self,
+        source_id: i64,
+        metadata: &EmbeddingMetadata,
+    ) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"insert into embedding_metadata (source_id, model, endpoint, input_chars, input_tokens, truncated, template)
+               values ($1, $2, $3, $4, $5, $6, $7)
+               on conflict (source_id)
+               do update set model = excluded.model, endpoint = excluded.endpoint,
+                             input_chars = excluded.input_chars,
+                             input_tokens = excluded.input_tokens,
+                             truncated = excluded.truncated, template = excluded.template,
+                             embedded_at = current_timestamp"#,
+            source_id,
+            metadata.model,
+            metadata.endpoint,
+            metadata.input_chars,
+            metadata.input_tokens,
+            metadata.truncated,
+            metadata.template,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn embedding_metadata(
+        &self,
+        source_id: i64,
+    ) -> Result<Option<EmbeddingRecord>, StorageError> {
+        let record = sqlx::query_as!(
+            EmbeddingRecord,
+            r#"select source_id, model, endpoint, input_chars, input_tokens, truncated, template,
+                      embedded_at
+               from embedding_metadata where source_id = $1"#,
+            source_id,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(record)
+    }
+
+    async fn issue_ids_after(
+        &self,
+        id: i32,
+        limit: i64,
+    ) -> Result<Vec<StoredIssueId>, StorageError> {
+        let issues = sqlx::query_as!(
+            StoredIssueId,
+            r#"
+                SELECT id, source_id
+                FROM issues
+                WHERE id > $1
+                ORDER BY id
+                LIMIT $2
+            "#,
+            id,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(issues)
+    }
+
+    async fn count_issues_after(&self, id: i32) -> Result<i64, StorageError> {
+        let count = sqlx::query_scalar!(
+            r#"select count(*) as "count!" from issues where id > $1"#,
+            id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(count)
+    }
+
+    async fn export_issues(
+        &self,
+        after_id: i32,
+        limit: i64,
+    ) -> Result<Vec<ExportedIssue>, StorageError> {
+        let issues = sqlx::query_as!(
+            ExportedIssue,
+            r#"select id, source_id, source, repository_full_name, number, title, body, html_url,
+                      is_pull_request, labels, milestone, created_at, updated_at
+               from issues
+               where id > $1 and not excluded and not opted_out and not private and gone_at is null
+               order by id
+               limit $2"#,
+            after_id,
+            limit,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(issues)
+    }
+
+    async fn stale_issues(
+        &self,
+        repository_full_name: Option<&str>,
+        older_than_days: i64,
+        labels: &[String],
+        state: Option<IssueState>,
+    ) -> Result<Vec<i64>, StorageError> {
+        let source_ids = sqlx::query_scalar(
+            r#"select source_id from issues
+               where not excluded
+                 and updated_at < current_timestamp - make_interval(days => $1::int)
+                 and ($2::varchar is null or repository_full_name = $2)
+                 and (cardinality($3::text[]) = 0 or labels && $3)
+                 and ($4::bool is null or (closed_at is not null) = $4)"#,
+        )
+        .bind(older_than_days)
+        .bind(repository_full_name)
+        .bind(labels)
+        .bind(state.map(|state| state == IssueState::Closed))
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(source_ids)
+    }
+
+    async fn exclude_issues(&self, source_ids: &[i64]) -> Result<(), StorageError> {
+        sqlx::query!(
+            "update issues set excluded = true where source_id = any($1)",
+            source_ids
+        )
+        .execute(&self.pool)
+        .await?;
+        self.track_write().await;
+        Ok(())
+    }
+
+    async fn mark_issue_gone(&self, url: &str) -> Result<bool, StorageError> {
+        let mut tx = self.pool.begin().await?;
+        let res = sqlx::query!(
+            "update issues set gone_at = current_timestamp where url = $1 and gone_at is null",
+            url
+        )
+        .execute(&mut *tx)
+        .await?;
+        // the issue and its comments pages
+        sqlx::query!(
+            "delete from github_response_cache where url = $1 or starts_with(url, $1 || '/')",
+            url
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        self.track_write().await;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn move_repository(&self, from: &str, to: &str) -> Result<u64, StorageError> {
+        let (old_path, new_path) = (format!("/{from}/"), format!("/{to}/"));
+        let mut tx = self.pool.begin().await?;
+        let moved = sqlx::query(
+            r#"update issues
+               set repository_full_name = $2, html_url = replace(html_url, $3, $4),
+                   url = replace(url, $3, $4), updated_at = current_timestamp
+               where repository_full_name = $1"#,
+        )
+        .bind(from)
+        .bind(to)
+        .bind(&old_path)
+        .bind(&new_path)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        for table in REPOSITORY_TABLES {
+            sqlx::query(&format!(
+                "update {table} set repository_full_name = $2 where repository_full_name = $1"
+            ))
+            .bind(from)
+            .bind(to)
+            .execute(&mut *tx)
+            .await?;
+        }
+        for (table, column) in REPOSITORY_KEYED_TABLES {
+            sqlx::query(&format!("delete from {table} where {column} = $1"))
+                .bind(to)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query(&format!(
+                "update {table} set {column} = $2 where {column} = $1"
+            ))
+            .bind(from)
+            .bind(to)
+            .execute(&mut *tx)
+            .await?;
+        }
+        sqlx::query(
+            "update suggestion_targets set issue_repository_full_name = $2 where issue_repository_full_name = $1",
+        )
+        .bind(from)
+        .bind(to)
+        .execute(&mut *tx)
+        .await?;
+        for (table, columns) in ISSUE_URL_COLUMNS {
+            let set = columns
+                .iter()
+                .map(|column| format!("{column} = replace({column}, $1, $2)"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let filter = columns
+                .iter()
+                .map(|column| format!("strpos({column}, $1) > 0"))
+                .collect::<Vec<_>>()
+                .join(" or ");
+            sqlx::query(&format!("update {table} set {set} where {filter}"))
+                .bind(&old_path)
+                .bind(&new_path)
+                .execute(&mut *tx)
+                .await?;
+        }
+        sqlx::query("delete from github_response_cache where strpos(url, $1) > 0")
+            .bind(&old_path)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        self.track_write().await;
+        Ok(moved)
+    }
+
+    async fn set_repository_private(
+        &self,
+        repository_full_name: &str,
+        private: bool,
+    ) -> Result<u64, StorageError> {
+        let mut tx = self.pool.begin().await?;
+        if private {
+            sqlx::query!(
+                r#"insert into private_repositories (repository_full_name) values ($1)
+                   on conflict (repository_full_name) do update set updated_at = current_timestamp"#,
+                repository_full_name,
+            )
+            .execute(&mut *tx)
+            .await?;
+        } else {
+            sqlx::query!(
+                "delete from private_repositories where repository_full_name = $1",
+                repository_full_name,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        let res = sqlx::query!(
+            "update issues set private = $1 where repository_full_name = $2",
+            private,
+            repository_full_name,
+        )
+        .execute(&mut *tx)
+        .await?;
+        if private {
+            sqlx::query!(
+                "delete from github_response_cache where strpos(url || '/', $1) > 0",
+                format!("/repos/{repository_full_name}/"),
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(res.rows_affected())
+    }
+
+    async fn repository_issues(
+        &self,
+        repository_full_name: &str,
+    ) -> Result<Vec<IssueEmbedding>, StorageError> {
+        let issues = sqlx::query!(
+            r#"select number, embedding::vector as "embedding!: Vector"
+               from issues
+               where repository_full_name = $1 and not is_pull_request
+               order by number"#,
+            repository_full_name,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(issues
+            .into_iter()
+            .map(|issue| IssueEmbedding {
+                number: issue.number,
+                embedding: issue.embedding.to_vec(),
+            })
+            .collect())
+    }
+
+    async fn search_issues(
+        &self,
+        embedding: &[f32],
+        repositories: &[String],
+        accelerator: Option<&str>,
+        os: Option<&str>,
+        limit: i64,
+        with_bodies: bool,
+    ) -> Result<Vec<SearchHit>, StorageError> {
+        let embedding = Vector::from(embedding.to_vec());
+        self.search(|pool| {
+            sqlx::query_as(
+                r#"select i.id, i.source_id, i.title, i.number, i.html_url, i.labels,
+                          i.repository_full_name, i.is_pull_request,
+                          case when $4 then i.body end as body, i.created_at,
+                          (select count(*) from comments c where c.issue_id = i.id) as comment_count,
+                          1 - (i.embedding <=> $1) as cosine_similarity, i.accelerator, i.os
+                   from issues i
+                   where not i.excluded and not i.opted_out and not i.private and i.gone_at is null
+                     and (cardinality($2::varchar[]) = 0 or i.repository_full_name = any($2))
+                     and ($5::varchar is null or i.accelerator = $5)
+                     and ($6::varchar is null or i.os = $6)
+                   order by i.embedding <=> $1
+                   limit $3"#,
+            )
+            .bind(&embedding)
+            .bind(repositories)
+            .bind(limit)
+            .bind(with_bodies)
+            .bind(accelerator)
+            .bind(os)
+            .fetch_all(pool)
+        })
+        .await
+    }
+
+    async fn repository_stats(&self) -> Result<Vec<RepositoryStats>, StorageError> {
+        let stats = sqlx::query_as(
+            r#"select repository_full_name,
+                      count(*) filter (where not is_pull_request) as issues,
+                      count(*) filter (where is_pull_request) as pull_requests,
+                      count(*) filter (where excluded) as excluded
+               from issues
+               group by repository_full_name
+               order by repository_full_name"#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(stats)
+    }
+
+    async fn recent_suggestions(&self, limit: i64) -> Result<Vec<RecentSuggestion>, StorageError> {
+        let suggestions = sqlx::query_as(
+            r#"select recent.created_at, s.issue_source_id,
+                      i.repository_full_name as issue_repository_full_name, i.number as issue_number,
+                      i.title as issue_title, i.html_url as issue_html_url,
+                      s.repository_full_name, s.number, si.title, si.html_url, s.cosine_similarity
+               from (
+                 select issue_source_id, max(created_at) as created_at from suggestions
+                 group by issue_source_id
+                 order by max(created_at) desc
+                 limit $1
+               ) recent
+               join suggestions s on s.issue_source_id = recent.issue_source_id
+               join issues i on i.source_id = s.issue_source_id
+               join issues si on si.repository_full_name = s.repository_full_name and si.number = s.number
+               order by recent.created_at desc, s.issue_source_id, s.rank"#,
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(suggestions)
+    }
+
+    async fn insert_issue_links(
+        &self,
+        repository_full_name: &str,
+        links: &[IssueLink],
+    ) -> Result<(), StorageError> {
+        if links.is_empty() {
+            return Ok(());
+        }
+        let mut qb = QueryBuilder::new(
+            "insert into issue_links (repository_full_name, number, linked_number, kind)",
+        );
+        qb.push_values(links, |mut b, link| {
+            b.push_bind(repository_full_name)
+                .push_bind(link.number)
+                .push_bind(link.linked_number)
+                .push_bind(link.kind);
+        });
+        qb.push("on conflict do nothing");
+        qb.build().execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn issue_links(
+        &self,
+        repository_full_name: &str,
+        numbers: Option<&[i32]>,
+    ) -> Result<Vec<IssueLink>, StorageError> {
+        let links = sqlx::query_as!(
+            IssueLink,
+            r#"select number, linked_number, kind as "kind: IssueLinkKind"
+               from issue_links
+               where repository_full_name = $1
+                 and ($2::int4[] is null or (number = any($2) and linked_number = any($2)))"#,
+            repository_full_name,
+            numbers as Option<&[i32]>,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(links)
+    }
+
+    async fn issue_cohorts(
+        &self,
+        repository_full_name: &str,
+        numbers: &[i32],
+    ) -> Result<Vec<IssueCohort>, StorageError> {
+        let cohorts = sqlx::query_as!(
+            IssueCohort,
+            r#"select number, package_version, accelerator
+               from issues
+               where repository_full_name = $1 and number = any($2)"#,
+            repository_full_name,
+            numbers,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(cohorts)
+    }
+
+    async fn get_job(
+        &self,
+        job_type: JobType,
+        repository_full_name: Option<&str>,
+    ) -> Result<Option<JobData>, StorageError> {
+        let job = match repository_full_name {
+            Some(repository_full_name) => {
+                sqlx::query_as!(
+                    Job,
+                    r#"select data as "data: Json<JobData>" from jobs where repository_full_name = $1 and job_type = $2"#,
+                    repository_full_name,
+                    job_type as _,
+                )
+                .fetch_optional(&self.pool)
+                .await?
+            }
+            None => {
+                sqlx::query_as!(
+                    Job,
+                    r#"select data as "data: Json<JobData>" from jobs where job_type = $1"#,
+                    job_type as _,
+                )
+                .fetch_optional(&self.pool)
+                .await?
+            }
+        };
+        Ok(job.map(|j| j.data.0))
+    }
+
+    async fn save_job(
+        &self,
+        job_type: JobType,
+        repository_full_name: Option<&str>,
+        data: &JobData,
+    ) -> Result<(), StorageError> {
+        match repository_full_name {
+            Some(repository_full_name) => {
+                sqlx::query(
+                    r#"insert into jobs (data, job_type, repository_full_name)
+                       values ($1, $2, $3)
+                       on conflict (repository_full_name)
+                       do update
+                       set
+                           data = EXCLUDED.data,
+                           updated_at = current_timestamp"#,
+                )
+                .bind(Json(data))
+                .bind(job_type)
+                .bind(repository_full_name)
+                .execute(&self.pool)
+                .await?;
+            }
+            None => {
+                sqlx::query(
+                    r#"insert into jobs (data, job_type)
+                       values ($1, $2)
+                       on conflict (job_type)
+                           where job_type = $2
+                       do update
+                       set
+                           data = EXCLUDED.data,
+                           updated_at = current_timestamp"#,
+                )
+                .bind(Json(data))
+                .bind(job_type)
+                .execute(&self.pool)
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn delete_job(
+        &self,
+        job_type: JobType,
+        repository_full_name: Option<&str>,
+    ) -> Result<(), StorageError> {
+        match repository_full_name {
+            Some(repository_full_name) => {
+                sqlx::query!(
+                    "delete from jobs where repository_full_name = $1",
+                    repository_full_name
+                )
+                .execute(&self.pool)
+                .await?;
+            }
+            None => {
+                sqlx::query!("delete from jobs where job_type = $1", job_type as _)
+                    .execute(&self.pool)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn jobs(&self) -> Result<Vec<JobState>, StorageError> {
+        let jobs = sqlx::query_as!(
+            JobRow,
+            r#"select id, job_type::text as "job_type!", repository_full_name,
+                      data as "data: Json<JobData>", paused
+               from jobs order by id"#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(jobs.into_iter().map(Into::into).collect())
+    }
+
+    async fn set_job_paused(
+        &self,
+        id: i32,
+        paused: bool,
+    ) -> Result<Option<JobState>, StorageError> {
+        let job = sqlx::query_as!(
+            JobRow,
+            r#"update jobs set paused = $1 where id = $2
+               returning id, job_type::text as "job_type!", repository_full_name,
+                         data as "data: Json<JobData>", paused"#,
+            paused,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(job.map(Into::into))
+    }
+
+    async fn is_job_paused(
+        &self,
+        job_type: JobType,
+        repository_full_name: Option<&str>,
+    ) -> Result<bool, StorageError> {
+        let paused = match repository_full_name {
+            Some(repository_full_name) => {
+                sqlx::query_scalar!(
+                    "select paused from jobs where repository_full_name = $1 and job_type = $2",
+                    repository_full_name,
+                    job_type as _,
+                )
+                .fetch_optional(&self.pool)
+                .await?
+            }
+            None => {
+                sqlx::query_scalar!("select paused from jobs where job_type = $1", job_type as _,)
+                    .fetch_optional(&self.pool)
+                    .await?
+            }
+        };
+        Ok(paused.unwrap_or_default())
+    }
+
+    async fn try_acquire_lock(
+        &self,
+        name: &str,
+        holder: &str,
+        lease: Duration,
+    ) -> Result<bool, StorageError> {
+        let res = sqlx::query!(
+            r#"insert into locks (name, holder, expires_at)
+               values ($1, $2, current_timestamp + make_interval(secs => $3))
+               on conflict (name)
+               do update
+               set
+                   holder = EXCLUDED.holder,
+                   expires_at = EXCLUDED.expires_at
+               where locks.expires_at < current_timestamp or locks.holder = EXCLUDED.holder"#,
+            name,
+            holder,
+            lease.as_secs_f64(),
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() == 1)
+    }
+
+    async fn renew_lock(
+        &self,
+        name: &str,
+        holder: &str,
+        lease: Duration,
+    ) -> Result<bool, StorageError> {
+        let res = sqlx::query!(
+            r#"update locks
+               set expires_at = current_timestamp + make_interval(secs => $1)
+               where name = $2 and holder = $3"#,
+            lease.as_secs_f64(),
+            name,
+            holder,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() == 1)
+    }
+
+    async fn release_lock(&self, name: &str, holder: &str) -> Result<(), StorageError> {
+        sqlx::query!(
+            "delete from locks where name = $1 and holder = $2",
+            name,
+            holder
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn lock_held(&self, name: &str) -> Result<bool, StorageError> {
+        let held = sqlx::query_scalar!(
+            r#"select exists(select 1 from locks where name = $1 and expires_at >= current_timestamp) as "held!""#,
+            name
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(held)
+    }
+
+    async fn insert_closure_proposal(
+        &self,
+        issue_source_id: i64,
+        issue_url: &str,
+        duplicate_of_html_url: &str,
+        comment_url: &str,
+    ) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"insert into closure_proposals (issue_source_id, issue_url, duplicate_of_html_url, comment_url)
+               values ($1, $2, $3, $4)"#,
+            issue_source_id,
+            issue_url,
+            duplicate_of_html_url,
+            comment_url,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_feedback(
+        &self,
+        run_id: &str,
+        vote: Vote,
+    ) -> Result<Option<String>, StorageError> {
+        let prompt_profile = sqlx::query_scalar(
+            r#"insert into feedback (run_id, vote, prompt_profile)
+               values ($1, $2, (select prompt_profile from comment_runs where run_id = $1))
+               returning prompt_profile"#,
+        )
+        .bind(run_id)
+        .bind(vote.to_string())
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(prompt_profile)
+    }
+
+    async fn insert_comment_run(
+        &self,
+        run_id: &str,
+        issue_url: &str,
+        prompt_profile: Option<&str>,
+    ) -> Result<(), StorageError> {
+        sqlx::query!(
+            "insert into comment_runs (run_id, issue_url, prompt_profile) values ($1, $2, $3) on conflict do nothing",
+            run_id,
+            issue_url,
+            prompt_profile,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn comment_run_exists(&self, run_id: &str) -> Result<bool, StorageError> {
+        let exists = sqlx::query_scalar!(
+            r#"select exists(select 1 from comment_runs where run_id = $1) as "exists!""#,
+            run_id,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(exists)
+    }
+
+    async fn setting(&self, key: &str) -> Result<Option<String>, StorageError> {
+        let value = sqlx::query_scalar!("select value from settings where key = $1", key)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(value)
+    }
+
+    async fn set_setting(&self, key: &str, value: &str) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"insert into settings (key, value)
+               values ($1, $2)
+               on conflict (key)
+               do update
+               set
+                   value = EXCLUDED.value,
+                   updated_at = current_timestamp"#,
+            key,
+            value,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn upsert_repository_metadata(
+        &self,
+        metadata: &RepositoryMetadata,
+    ) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"insert into repository_metadata (full_name, description, stars, topics)
+               values ($1, $2, $3, $4)
+               on conflict (full_name)
+               do update
+               set
+                   description = EXCLUDED.description,
+                   stars = EXCLUDED.stars,
+                   topics = EXCLUDED.topics,
+                   updated_at = current_timestamp"#,
+            metadata.full_name,
+            metadata.description,
+            metadata.stars,
+            &metadata.topics,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn repository_metadata(
+        &self,
+        full_names: Option<&[String]>,
+    ) -> Result<Vec<RepositoryMetadata>, StorageError> {
+        let metadata = sqlx::query_as!(
+            RepositoryMetadata,
+            r#"select full_name, description, stars, topics from repository_metadata
+               where $1::text[] is null or full_name = any($1)"#,
+            full_names as Option<&[String]>,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(metadata)
+    }
+
+    async fn insert_knowledge_base_entry(
+        &self,
+        repository_full_name: Option<&str>,
+        question: &str,
+        answer: &str,
+        embedding: &[f32],
+    ) -> Result<i32, StorageError> {
+        let id = sqlx::query_scalar(
+            r#"insert into knowledge_base (repository_full_name, question, answer, embedding)
+               values ($1, $2, $3, $4)
+               returning id"#,
+        )
+        .bind(repository_full_name)
+        .bind(question)
+        .bind(answer)
+        .bind(Vector::from(embedding.to_vec()))
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(id)
+    }
+
+    async fn knowledge_base_entries(&self) -> Result<Vec<KnowledgeBaseEntry>, StorageError> {
+        let entries = sqlx::query_as!(
+            KnowledgeBaseEntry,
+            "select id, repository_full_name, question, answer from knowledge_base order by id"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(entries)
+    }
+
+    async fn update_knowledge_base_entry(
+        &self,
+        id: i32,
+        repository_full_name: Option<&str>,
+        question: &str,
+        answer: &str,
+        embedding: &[f32],
+    ) -> Result<bool, StorageError> {
+        let res = sqlx::query(
+            r#"update knowledge_base
+               set repository_full_name = $2, question = $3, answer = $4, embedding = $5
+               where id = $1"#,
+        )
+        .bind(id)
+        .bind(repository_full_name)
+        .bind(question)
+        .bind(answer)
+        .bind(Vector::from(embedding.to_vec()))
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn delete_knowledge_base_entry(&self, id: i32) -> Result<bool, StorageError> {
+        let res = sqlx::query!("delete from knowledge_base where id = $1", id)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn closest_knowledge_base_entry(
+        &self,
+        embedding: &[f32],
+        repository_full_name: &str,
+    ) -> Result<Option<KnowledgeBaseMatch>, StorageError> {
+        let embedding = Vector::from(embedding.to_vec());
+        let row: Option<(i32, Option<String>, String, String, f64)> = self
+            .search(|pool| {
+                sqlx::query_as(
+                    "select id, repository_full_name, question, answer, 1 - (embedding <=> $1) as cosine_similarity from knowledge_base where repository_full_name is null or repository_full_name = $2 order by embedding <=> $1 limit 1",
+                )
+                .bind(&embedding)
+                .bind(repository_full_name)
+                .fetch_optional(pool)
+            })
+            .await?;
+        Ok(row.map(
+            |(id, repository_full_name, question, answer, cosine_similarity)| KnowledgeBaseMatch {
+                entry: KnowledgeBaseEntry {
+                    id,
+                    repository_full_name,
+                    question,
+                    answer,
+                },
+                cosine_similarity,
+            },
+        ))
+    }
+
+    async fn replace_guidance_sections(
+        &self,
+        repository_full_name: &str,
+        sections: &[(GuidanceSection, String, Vec<f32>)],
+    ) -> Result<(), StorageError> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            "delete from guidance_sections where repository_full_name = $1",
+            repository_full_name,
+        )
+        .execute(&mut *tx)
+        .await?;
+        if !sections.is_empty() {
+            let mut qb = QueryBuilder::new(
+                "insert into guidance_sections (repository_full_name, path, heading, anchor, content, content_hash, embedding)",
+            );
+            qb.push_values(sections, |mut b, (section, content_hash, embedding)| {
+                b.push_bind(repository_full_name)
+                    .push_bind(&section.path)
+                    .push_bind(&section.heading)
+                    .push_bind(&section.anchor)
+                    .push_bind(&section.content)
+                    .push_bind(content_hash)
+                    .push_bind(Vector::from(embedding.clone()));
+            });
+            qb.build().execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn guidance_embeddings(
+        &self,
+        repository_full_name: &str,
+    ) -> Result<HashMap<String, Vec<f32>>, StorageError> {
+        let rows = sqlx::query!(
+            r#"select content_hash as "content_hash!", embedding::vector as "embedding!: Vector"
+               from guidance_sections
+               where repository_full_name = $1 and content_hash is not null"#,
+            repository_full_name,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.content_hash, row.embedding.to_vec()))
+            .collect())
+    }
+
+    async fn closest_guidance_section(
+        &self,
+        embedding: &[f32],
+        repository_full_name: &str,
+    ) -> Result<Option<GuidanceMatch>, StorageError> {
+        let embedding = Vector::from(embedding.to_vec());
+        let row: Option<(String, String, String, String, f64)> = self
+            .search(|pool| {
+                sqlx::query_as(
+                    "select path, heading, anchor, content, 1 - (embedding <=> $1) as cosine_similarity from guidance_sections where repository_full_name = $2 order by embedding <=> $1 limit 1",
+                )
+                .bind(&embedding)
+                .bind(repository_full_name)
+                .fetch_optional(pool)
+            })
+            .await?;
+        Ok(row.map(
+            |(path, heading, anchor, content, cosine_similarity)| GuidanceMatch {
+                section: GuidanceSection {
+                    repository_full_name: repository_full_name.to_owned(),
+                    path,
+                    heading,
+                    anchor,
+                    content,
+                },
+                cosine_similarity,
+            },
+        ))
+    }
+
+    async fn insert_api_key(
+        &self,
+        name: &str,
+        key_hash: &str,
+        scopes: &str,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<i32, StorageError> {
+        let id = sqlx::query_scalar!(
+            r#"insert into api_keys (name, key_hash, scopes, expires_at)
+               values ($1, $2, $3, $4)
+               returning id"#,
+            name,
+            key_hash,
+            scopes,
+            expires_at,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(id)
+    }
+
+    async fn api_key(&self, key_hash: &str) -> Result<Option<ApiKey>, StorageError> {
+        let key = sqlx::query_as!(
+            ApiKey,
+            "select id, name, scopes, expires_at, created_at from api_keys where key_hash = $1",
+            key_hash,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(key)
+    }
+
+    async fn api_keys(&self) -> Result<Vec<ApiKey>, StorageError> {
+        let keys = sqlx::query_as!(
+            ApiKey,
+            "select id, name, scopes, expires_at, created_at from api_keys order by id"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(keys)
+    }
+
+    async fn delete_api_key(&self, id: i32) -> Result<bool, StorageError> {
+        let res = sqlx::query!("delete from api_keys where id = $1", id)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn enqueue_comment(
+        &self,
+        source: &Source,
+        repository_full_name: &str,
+        issue_url: &str,
+        body: &str,
+        awaiting_approval: bool,
+        suggestions: bool,
+    ) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"insert into pending_comments (source, repository_full_name, issue_url, body, awaiting_approval, suggestions)
+               values ($1, $2, $3, $4, $5, $6)"#,
+            source.to_string(),
+            repository_full_name,
+            issue_url,
+            body,
+            awaiting_approval,
+            suggestions,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn pending_comments(&self, limit: i64) -> Result<Vec<PendingComment>, StorageError> {
+        let comments = sqlx::query_as!(
+            PendingComment,
+            r#"select id, source, repository_full_name, issue_url, body, suggestions, attempts,
+                      created_at
+               from pending_comments
+               where not awaiting_approval and next_attempt_at <= current_timestamp
+               order by id limit $1"#,
+            limit,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(comments)
+    }
+
+    async fn reschedule_pending_comment(
+        &self,
+        id: i32,
+        delay_secs: i64,
+        error: &str,
+    ) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"update pending_comments
+               set attempts = attempts + 1, last_error = $2,
+                   next_attempt_at = current_timestamp + make_interval(secs => $3)
+               where id = $1"#,
+            id,
+            error,
+            delay_secs as f64,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn unannounced_comment_drafts(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<PendingComment>, StorageError> {
+        let drafts = sqlx::query_as!(
+            PendingComment,
+            r#"select id, source, repository_full_name, issue_url, body, suggestions, attempts,
+                      created_at
+               from pending_comments where awaiting_approval and slack_ts is null
+               order by id limit $1"#,
+            limit,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(drafts)
+    }
+
+    async fn set_comment_draft_announced(
+        &self,
+        id: i32,
+        slack_ts: &str,
+    ) -> Result<(), StorageError> {
+        sqlx::query!(
+            "update pending_comments set slack_ts = $1 where id = $2",
+            slack_ts,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn approve_comment_draft(&self, id: i32) -> Result<bool, StorageError> {
+        let res = sqlx::query!(
+            "update pending_comments set awaiting_approval = false where id = $1 and awaiting_approval",
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn discard_comment_draft(&self, id: i32) -> Result<bool, StorageError> {
+        let res = sqlx::query!(
+            "delete from pending_comments where id = $1 and awaiting_approval",
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn expire_comment_drafts(&self, older_than_hours: i32) -> Result<u64, StorageError> {
+        let res = sqlx::query!(
+            r#"delete from pending_comments
+               where awaiting_approval and created_at < current_timestamp - make_interval(hours => $1)"#,
+            older_than_hours,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected())
+    }
+
+    async fn delete_pending_comment(&self, id: i32) -> Result<(), StorageError> {
+        sqlx::query!("delete from pending_comments where id = $1", id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn enqueue_slack_message(
+        &self,
+        repository_full_name: &str,
+        payload: &serde_json::Value,
+        error: &str,
+    ) -> Result<(), StorageError> {
+        sqlx::query(
+            "insert into slack_outbox (repository_full_name, payload, last_error) values ($1, $2, $3)",
+        )
+        .bind(repository_full_name)
+        .bind(Json(payload))
+        .bind(error)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn due_slack_messages(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<SlackOutboxMessage>, StorageError> {
+        let messages = sqlx::query!(
+            r#"select id, repository_full_name, payload as "payload: Json<serde_json::Value>", attempts
+               from slack_outbox where next_attempt_at <= current_timestamp
+               order by id limit $1"#,
+            limit,
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|row| SlackOutboxMessage {
+            id: row.id,
+            repository_full_name: row.repository_full_name,
+            payload: row.payload.0,
+            attempts: row.attempts,
+        })
+        .collect();
+        Ok(messages)
+    }
+
+    async fn reschedule_slack_message(
+        &self,
+        id: i32,
+        delay_secs: i64,
+        error: &str,
+    ) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"update slack_outbox
+               set attempts = attempts + 1, last_error = $2,
+                   next_attempt_at = current_timestamp + make_interval(secs => $3)
+               where id = $1"#,
+            id,
+            error,
+            delay_secs as f64,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_slack_message(&self, id: i32) -> Result<(), StorageError> {
+        sqlx::query!("delete from slack_outbox where id = $1", id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn expire_slack_messages(&self, older_than_minutes: i32) -> Result<u64, StorageError> {
+        let res = sqlx::query!(
+            r#"delete from slack_outbox
+               where created_at < current_timestamp - make_interval(mins => $1)"#,
+            older_than_minutes,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected())
+    }
+
+    async fn add_to_slack_batch(
+        &self,
+        repository_full_name: &str,
+        payload: &serde_json::Value,
+    ) -> Result<(), StorageError> {
+        sqlx::query("insert into slack_batches (repository_full_name, payload) values ($1, $2)")
+            .bind(repository_full_name)
+            .bind(Json(payload))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn take_slack_batch(
+        &self,
+        repository_full_name: &str,
+    ) -> Result<Vec<serde_json::Value>, StorageError> {
+        let mut rows = sqlx::query!(
+            r#"delete from slack_batches where repository_full_name = $1
+               returning id, payload as "payload: Json<serde_json::Value>""#,
+            repository_full_name,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        rows.sort_by_key(|row| row.id);
+        Ok(rows.into_iter().map(|row| row.payload.0).collect())
+    }
+
+    async fn slack_batch_repositories(&self) -> Result<Vec<String>, StorageError> {
+        let repositories =
+            sqlx::query_scalar!("select distinct repository_full_name from slack_batches")
+                .fetch_all(&self.pool)
+                .await?;
+        Ok(repositories)
+    }
+
+    async fn cached_response(&self, url: &str) -> Result<Option<CachedResponse>, StorageError> {
+        let response = sqlx::query_as!(
+            CachedResponse,
+            "select etag, link, body from github_response_cache where url = $1",
+            url
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(response)
+    }
+
+    async fn cache_response(
+        &self,
+        url: &str,
+        response: &CachedResponse,
+    ) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"insert into github_response_cache (url, etag, link, body)
+               values ($1, $2, $3, $4)
+               on conflict (url)
+               do update set etag = excluded.etag, link = excluded.link, body = excluded.body,
+                             cached_at = current_timestamp"#,
+            url,
+            response.etag,
+            response.link,
+            response.body,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn refresh_cached_response(&self, url: &str) -> Result<(), StorageError> {
+        sqlx::query!(
+            "update github_response_cache set cached_at = current_timestamp where url = $1",
+            url
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn purge_cached_responses(&self, older_than_days: i32) -> Result<u64, StorageError> {
+        let res = sqlx::query!(
+            r#"delete from github_response_cache
+               where cached_at < current_timestamp - make_interval(days => $1)"#,
+            older_than_days,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected())
+    }
+
+    async fn notify(&self, channel: &str, payload: &str) -> Result<(), StorageError> {
+        sqlx::query!("select pg_notify($1, $2)", channel, payload)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn comment_backlog(&self) -> Result<CommentBacklog, StorageError> {
+        let backlog = sqlx::query_as!(
+            CommentBacklog,
+            r#"select count(*) filter (where not awaiting_approval) as "queued!",
+                      count(*) filter (where awaiting_approval) as "awaiting_approval!",
+                      extract(epoch from current_timestamp
+                        - min(created_at) filter (where not awaiting_approval))::float8 as oldest_queued_secs
+               from pending_comments"#
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(backlog)
+    }
+
+    async fn job_backlog(&self) -> Result<Vec<JobBacklog>, StorageError> {
+        let backlog = sqlx::query_as!(
+            JobBacklog,
+            r#"select job_type::text as "job_type!", count(*) as "jobs!",
+                      extract(epoch from current_timestamp - min(updated_at))::float8 as "stalled_secs!"
+               from jobs group by job_type"#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(backlog)
+    }
+
+    async fn slack_outbox_backlog(&self) -> Result<SlackOutboxBacklog, StorageError> {
+        let backlog = sqlx::query_as!(
+            SlackOutboxBacklog,
+            r#"select count(*) as "messages!",
+                      extract(epoch from current_timestamp - min(created_at))::float8 as oldest_secs
+               from slack_outbox"#
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(backlog)
+    }
+
+    async fn event_stats(&self, since_minutes: i32) -> Result<Vec<EventStats>, StorageError> {
+        let stats = sqlx::query_as!(
+            EventStats,
+            r#"select event_type, outcome::text as "outcome!", count(*) as "events!",
+                      avg(total_ms)::float8 as "avg_total_ms!"
+               from event_log
+               where created_at > current_timestamp - make_interval(mins => $1)
+               group by event_type, outcome"#,
+            since_minutes
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(stats)
+    }
+
+    async fn comment_idempotency_key(
+        &self,
+        issue_url: &str,
+        fingerprint: &str,
+    ) -> Result<Option<bool>, StorageError> {
+        let posted = sqlx::query_scalar!(
+            "select posted from comment_idempotency_keys where issue_url = $1 and fingerprint = $2",
+            issue_url,
+            fingerprint
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(posted)
+    }
+
+    async fn save_comment_idempotency_key(
+        &self,
+        issue_url: &str,
+        fingerprint: &str,
+        posted: bool,
+    ) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"insert into comment_idempotency_keys (issue_url, fingerprint, posted)
+               values ($1, $2, $3)
+               on conflict (issue_url, fingerprint)
+               do update set posted = excluded.posted, updated_at = current_timestamp"#,
+            issue_url,
+            fingerprint,
+            posted
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn bot_comment(&self, issue_url: &str) -> Result<Option<String>, StorageError> {
+        let comment_url = sqlx::query_scalar!(
+            "select comment_url from bot_comments where issue_url = $1",
+            issue_url
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(comment_url)
+    }
+
+    async fn save_bot_comment(
+        &self,
+        issue_url: &str,
+        comment_url: &str,
+        body: &str,
+    ) -> Result<(), StorageError> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            r#"insert into bot_comments (issue_url, comment_url)
+               values ($1, $2)
+               on conflict (issue_url)
+               do update set comment_url = excluded.comment_url, updated_at = current_timestamp"#,
+            issue_url,
+            comment_url,
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "insert into bot_comment_revisions (issue_url, comment_url, body) values ($1, $2, $3)",
+            issue_url,
+            comment_url,
+            body,
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn bot_comment_revisions(
+        &self,
+        issue_url: &str,
+    ) -> Result<Vec<BotCommentRevision>, StorageError> {
+        let revisions = sqlx::query_as!(
+            BotCommentRevision,
+            "select comment_url, body, created_at from bot_comment_revisions where issue_url = $1 order by id",
+            issue_url
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(revisions)
+    }
+
+    async fn record_suggestion_targets(
+        &self,
+        issue: &IssueData,
+        targets: &[&ClosestIssue],
+    ) -> Result<(), StorageError> {
+        if targets.is_empty() {
+            return Ok(());
+        }
+        let mut qb = QueryBuilder::new(
+            "insert into suggestion_targets (repository_full_name, number, issue_repository_full_name, issue_number, issue_html_url, issue_title)",
+        );
+        qb.push_values(targets, |mut b, target| {
+            b.push_bind(&target.repository_full_name)
+                .push_bind(target.number)
+                .push_bind(&issue.repository_full_name)
+                .push_bind(issue.number)
+                .push_bind(&issue.html_url)
+                .push_bind(&issue.title);
+        });
+        qb.build().execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn suggestion_cluster(
+        &self,
+        repository_full_name: &str,
+        number: i32,
+        window_minutes: i32,
+    ) -> Result<Vec<SuggestedIssue>, StorageError> {
+        let issues = sqlx::query_as!(
+            SuggestedIssue,
+            r#"select issue_repository_full_name as repository_full_name, issue_number as number,
+                      max(issue_html_url) as "html_url!", max(issue_title) as "title!"
+               from suggestion_targets
+               where repository_full_name = $1 and number = $2
+                 and created_at > current_timestamp - make_interval(mins => $3)
+               group by issue_repository_full_name, issue_number
+               order by issue_repository_full_name, issue_number"#,
+            repository_full_name,
+            number,
+            window_minutes,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(issues)
+    }
+
+    async fn prune_suggestion_targets(&self, older_than_minutes: i32) -> Result<(), StorageError> {
+        sqlx::query!(
+            "delete from suggestion_targets where created_at < current_timestamp - make_interval(mins => $1)",
+            older_than_minutes,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn claim_escalation(
+        &self,
+        repository_full_name: &str,
+        number: i32,
+        cooldown_minutes: i32,
+    ) -> Result<bool, StorageError> {
+        let claimed = sqlx::query_scalar!(
+            r#"insert into escalations (repository_full_name, number)
+               values ($1, $2)
+               on conflict (repository_full_name, number)
+               do update set escalated_at = current_timestamp
+               where escalations.escalated_at < current_timestamp - make_interval(mins => $3)
+               returning number"#,
+            repository_full_name,
+            number,
+            cooldown_minutes,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(claimed.is_some())
+    }
+
+    async fn record_suggestions(
+        &self,
+        issue_source_id: i64,
+        suggestions: &[ClosestIssue],
+        run_id: &str,
+        prompt_profile: Option<&str>,
+    ) -> Result<(), StorageError> {
+        if suggestions.is_empty() {
+            return Ok(());
+        }
+        let mut qb = QueryBuilder::new(
+            "insert into suggestions (issue_source_id, repository_full_name, number, rank, cosine_similarity, run_id, prompt_profile)",
+        );
+        qb.push_values(
+            suggestions.iter().enumerate(),
+            |mut b, (rank, suggestion)| {
+                b.push_bind(issue_source_id)
+                    .push_bind(&suggestion.repository_full_name)
+                    .push_bind(suggestion.number)
+                    .push_bind(rank as i32)
+                    .push_bind(suggestion.cosine_similarity)
+                    .push_bind(run_id)
+                    .push_bind(prompt_profile);
+            },
+        );
+        qb.push(" on conflict (issue_source_id, repository_full_name, number) do nothing");
+        qb.build().execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn suggestions(&self, issue_source_id: i64) -> Result<Vec<Suggestion>, StorageError> {
+        let suggestions = sqlx::query_as!(
+            Suggestion,
+            r#"select repository_full_name, number, rank
+               from suggestions
+               where issue_source_id = $1
+               order by rank"#,
+            issue_source_id,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(suggestions)
+    }
+
+    async fn upsert_duplicate_resolution(
+        &self,
+        resolution: &DuplicateResolution,
+    ) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"insert into duplicate_resolutions (issue_source_id, repository_full_name, number, duplicate_of_number, had_suggestions, suggested_rank)
+               values ($1, $2, $3, $4, $5, $6)
+               on conflict (issue_source_id)
+               do update set duplicate_of_number = excluded.duplicate_of_number,
+                             had_suggestions = excluded.had_suggestions,
+                             suggested_rank = excluded.suggested_rank,
+                             resolved_at = current_timestamp"#,
+            resolution.issue_source_id,
+            resolution.repository_full_name,
+            resolution.number,
+            resolution.duplicate_of_number,
+            resolution.had_suggestions,
+            resolution.suggested_rank,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn linked_issue_similarities(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<LinkSimilarity>, StorageError> {
+        let similarities = sqlx::query_as!(
+            LinkSimilarity,
+            r#"select l.repository_full_name as "repository_full_name!", l.number as "number!",
+                      l.linked_number as "linked_number!",
+                      1 - (a.embedding <=> b.embedding) as "cosine_similarity!"
+               from (select repository_full_name, number, linked_number, max(created_at) as created_at
+                     from issue_links
+                     group by repository_full_name, number, linked_number) l
+               join issues a on a.repository_full_name = l.repository_full_name and a.number = l.number
+               join issues b on b.repository_full_name = l.repository_full_name and b.number = l.linked_number
+               order by l.created_at desc, l.repository_full_name, l.number, l.linked_number
+               limit $1"#,
+            limit,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(similarities)
+    }
+
+    async fn record_link_similarities(
+        &self,
+        model: &str,
+        similarities: &[LinkSimilarity],
+    ) -> Result<(), StorageError> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!("delete from link_similarities where model = $1", model)
+            .execute(&mut *tx)
+            .await?;
+        if !similarities.is_empty() {
+            let mut qb = QueryBuilder::new(
+                "insert into link_similarities (model, repository_full_name, number, linked_number, cosine_similarity)",
+            );
+            qb.push_values(similarities, |mut b, similarity| {
+                b.push_bind(model)
+                    .push_bind(&similarity.repository_full_name)
+                    .push_bind(similarity.number)
+                    .push_bind(similarity.linked_number)
+                    .push_bind(similarity.cosine_similarity);
+            });
+            qb.build().execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn link_similarities(&self, model: &str) -> Result<Vec<LinkSimilarity>, StorageError> {
+        let similarities = sqlx::query_as!(
+            LinkSimilarity,
+            r#"select repository_full_name, number, linked_number, cosine_similarity
+               from link_similarities
+               where model = $1
+               order by repository_full_name, number, linked_number"#,
+            model,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(similarities)
+    }
+
+    async fn repository_cursors(&self) -> Result<Vec<RepositoryCursor>, StorageError> {
+        let cursors = sqlx::query_as!(
+            RepositoryCursor,
+            "select repository_full_name, processed_at from repository_cursors order by repository_full_name",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(cursors)
+    }
+
+    async fn advance_repository_cursor(
+        &self,
+        repository_full_name: &str,
+        processed_at: DateTime<Utc>,
+    ) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"insert into repository_cursors (repository_full_name, processed_at)
+               values ($1, $2)
+               on conflict (repository_full_name)
+               do update
+               set processed_at = greatest(repository_cursors.processed_at, EXCLUDED.processed_at)"#,
+            repository_full_name,
+            processed_at,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn suppress_issue(
+        &self,
+        source_id: i64,
+        suppressed_by: &str,
+    ) -> Result<bool, StorageError> {
+        let inserted = sqlx::query_scalar!(
+            r#"insert into suppressed_issues (source_id, suppressed_by)
+               values ($1, $2)
+               on conflict (source_id) do nothing
+               returning source_id"#,
+            source_id,
+            suppressed_by,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(inserted.is_some())
+    }
+
+    async fn unsuppress_issue(&self, source_id: i64) -> Result<bool, StorageError> {
+        let res = sqlx::query!(
+            "delete from suppressed_issues where source_id = $1",
+            source_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn is_issue_url_suppressed(&self, issue_url: &str) -> Result<bool, StorageError> {
+        let suppressed = sqlx::query_scalar!(
+            r#"select exists(
+                 select 1 from suppressed_issues s
+                 join issues i on i.source_id = s.source_id
+                 where i.url = $1
+               ) as "suppressed!""#,
+            issue_url,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(suppressed)
+    }
+
+    async fn is_issue_suppressed(&self, source_id: i64) -> Result<bool, StorageError> {
+        let suppressed = sqlx::query_scalar!(
+            r#"select exists(select 1 from suppressed_issues where source_id = $1) as "suppressed!""#,
+            source_id,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(suppressed)
+    }
+
+    async fn is_issue_number_suppressed(
+        &self,
+        repository_full_name: &str,
+        number: i32,
+    ) -> Result<bool, StorageError> {
+        let suppressed = sqlx::query_scalar!(
+            r#"select exists(
+                 select 1 from suppressed_issues s
+                 join issues i on i.source_id = s.source_id
+                 where i.repository_full_name = $1 and i.number = $2
+               ) as "suppressed!""#,
+            repository_full_name,
+            number,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(suppressed)
+    }
+
+    async fn opt_out_author(
+        &self,
+        login: &str,
+        requested_through: &str,
+        purge: bool,
+    ) -> Result<OptOutRequest, StorageError> {
+        let mut tx = self.pool.begin().await?;
+        let (issues, comments) = if purge {
+            purge_author(&mut tx, login).await?
+        } else {
+            let issues = sqlx::query!(
+                r#"update issues set opted_out = true, updated_at = current_timestamp
+                   where author = $1 and not opted_out"#,
+                login
+            )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+            let comments = sqlx::query!(
+                "update comments set opted_out = true where author = $1 and not opted_out",
+                login
+            )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+            (issues, comments)
+        };
+        let request = sqlx::query_as!(
+            OptOutRequest,
+            r#"insert into opt_out_requests (login, requested_through, purged, issues, comments)
+               values ($1, $2, $3, $4, $5)
+               returning id, login, requested_through, purged, issues, comments, requested_at"#,
+            login,
+            requested_through,
+            purge,
+            issues as i64,
+            comments as i64,
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        self.track_write().await;
+        Ok(request)
+    }
+
+    async fn opt_out_requests(&self) -> Result<Vec<OptOutRequest>, StorageError> {
+        let requests = sqlx::query_as!(
+            OptOutRequest,
+            r#"select id, login, requested_through, purged, issues, comments, requested_at
+               from opt_out_requests order by id desc"#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(requests)
+    }
+
+    async fn issues_commented_by(&self, login: &str) -> Result<Vec<i64>, StorageError> {
+        let source_ids = sqlx::query_scalar!(
+            r#"select distinct i.source_id from issues i join comments c on c.issue_id = i.id
+               where c.author = $1 and i.author is distinct from $1"#,
+            login
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(source_ids)
+    }
+
+    async fn record_onboarding(
+        &self,
+        repository_full_name: &str,
+        hook_id: Option<i64>,
+        estimated_issues: i64,
+    ) -> Result<OnboardedRepository, StorageError> {
+        let repository = sqlx::query_as!(
+            OnboardedRepository,
+            r#"insert into onboarded_repositories (repository_full_name, hook_id, estimated_issues)
+               values ($1, $2, $3)
+               on conflict (repository_full_name)
+               do update set hook_id = excluded.hook_id, estimated_issues = excluded.estimated_issues,
+                             onboarded_at = current_timestamp
+               returning repository_full_name, hook_id, estimated_issues, onboarded_at"#,
+            repository_full_name,
+            hook_id,
+            estimated_issues
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(repository)
+    }
+
+    async fn onboarded_repositories(&self) -> Result<Vec<OnboardedRepository>, StorageError> {
+        let repositories = sqlx::query_as!(
+            OnboardedRepository,
+            r#"select repository_full_name, hook_id, estimated_issues, onboarded_at
+               from onboarded_repositories order by onboarded_at desc"#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(repositories)
+    }
+
+    async fn expire_closure_proposals(&self, older_than_hours: i32) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"update closure_proposals
+               set status = $1, updated_at = current_timestamp
+               where status = $2 and created_at < current_timestamp - make_interval(hours => $3)"#,
+            ClosureProposalStatus::Expired as _,
+            ClosureProposalStatus::Pending as _,
+            older_than_hours,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn pending_closure_proposals(&self) -> Result<Vec<ClosureProposal>, StorageError> {
+        let proposals = sqlx::query_as!(
+            ClosureProposal,
+            r#"select id, issue_source_id, issue_url, comment_url from closure_proposals where status = $1"#,
+            ClosureProposalStatus::Pending as _,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(proposals)
+    }
+
+    async fn set_closure_proposal_status(
+        &self,
+        id: i32,
+        status: ClosureProposalStatus,
+    ) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"update closure_proposals
+               set status = $1, updated_at = current_timestamp
+               where id = $2"#,
+            status as _,
+            id,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_event_log_entry(&self, entry: &EventLogEntry) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"insert into event_log
+               (event_type, description, source_id, repository_full_name, issue_number, request_id,
+                outcome, error, embed_ms, search_ms, summarize_ms, comment_ms, total_ms, created_at,
+                run_id)
+               values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)"#,
+            entry.event_type,
+            entry.description,
+            entry.source_id,
+            entry.repository_full_name,
+            entry.issue_number,
+            entry.request_id,
+            entry.outcome as _,
+            entry.error,
+            entry.embed_ms,
+            entry.search_ms,
+            entry.summarize_ms,
+            entry.comment_ms,
+            entry.total_ms,
+            entry.created_at,
+            entry.run_id,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn mark_events_commented(&self, issue_url: &str) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"update event_log set outcome = 'commented'
+               where outcome = 'queued'
+                 and run_id in (select run_id from comment_runs where issue_url = $1)"#,
+            issue_url,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn prune_event_log(&self, older_than_days: i32) -> Result<u64, StorageError> {
+        let res = sqlx::query!(
+            "delete from event_log where created_at < current_timestamp - make_interval(days => $1)",
+            older_than_days,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected())
+    }
+
+    async fn event_log(
+        &self,
+        filter: &EventLogFilter,
+        limit: i64,
+    ) -> Result<Vec<EventLogEntry>, StorageError> {
+        let entries = sqlx::query_as!(
+            EventLogEntry,
+            r#"select event_type, description, source_id, repository_full_name, issue_number,
+                      request_id, outcome as "outcome: EventOutcome", run_id, error, embed_ms, search_ms,
+                      summarize_ms, comment_ms, total_ms, created_at
+               from event_log
+               where ($1::varchar is null or repository_full_name = $1)
+                 and ($2::int4 is null or issue_number = $2)
+                 and ($3::int8 is null or source_id = $3)
+                 and ($4::varchar is null or request_id = $4)
+               order by created_at desc
+               limit $5"#,
+            filter.repository_full_name,
+            filter.issue_number,
+            filter.source_id,
+            filter.request_id,
+            limit,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(entries)
+    }
+
+    async fn issues_table_health(&self) -> Result<Option<TableHealth>, StorageError> {
+        let health = sqlx::query_as!(
+            TableHealth,
+            r#"select n_live_tup as "live_tuples!", n_dead_tup as "dead_tuples!",
+                      n_tup_upd - n_tup_hot_upd + n_tup_del as "index_churn!",
+                      pg_table_size(relid) as "table_bytes!",
+                      pg_indexes_size(relid) as "index_bytes!"
+               from pg_stat_user_tables
+               where relname = 'issues'"#
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(health)
+    }
+
+    async fn vacuum_issues(&self, timeout: Duration) -> Result<(), StorageError> {
+        self.maintenance("vacuum (analyze) issues", timeout).await
+    }
+
+    async fn issues_vector_indexes(&self) -> Result<Vec<String>, StorageError> {
+        let names = sqlx::query_scalar!(
+            r#"select indexname as "name!" from pg_indexes
+               where tablename = 'issues' and indexdef ~* 'using (hnsw|ivfflat)'
+               order by indexname"#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(names)
+    }
+
+    async fn reindex_issues_index(
+        &self,
+        name: &str,
+        timeout: Duration,
+    ) -> Result<(), StorageError> {
+        let statement = format!("reindex index concurrently {}", quote_identifier(name));
+        self.maintenance(&statement, timeout).await
+    }
+
+    async fn drop_invalid_issues_indexes(&self) -> Result<Vec<String>, StorageError> {
+        // suffixed `_ccnew` while being built, `_ccold` while being swapped
+        let names = sqlx::query_scalar!(
+            r#"select index.relname as "name!"
+               from pg_index
+               join pg_class index on index.oid = pg_index.indexrelid
+               join pg_class tbl on tbl.oid = pg_index.indrelid
+               where tbl.relname = 'issues'
+                 and not pg_index.indisvalid
+                 and index.relname ~ '_cc(new|old)[0-9]*$'"#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        for name in &names {
+            let statement = format!(
+                "drop index concurrently if exists {}",
+                quote_identifier(name)
+            );
+            sqlx::raw_sql(&statement).execute(&self.pool).await?;
+        }
+        Ok(names)
+    }
+
+    async fn insert_compaction_run(&self, run: &CompactionRun) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"insert into compaction_runs
+                 (action, dead_tuples, index_churn, table_bytes_before, table_bytes_after,
+                  index_bytes_before, index_bytes_after, duration_ms, created_at)
+               values ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
+            run.action,
+            run.dead_tuples,
+            run.index_churn,
+            run.table_bytes_before,
+            run.table_bytes_after,
+            run.index_bytes_before,
+            run.index_bytes_after,
+            run.duration_ms,
+            run.created_at,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn last_compaction_run(
+        &self,
+        action: &str,
+    ) -> Result<Option<CompactionRun>, StorageError> {
+        let run = sqlx::query_as!(
+            CompactionRun,
+            r#"select action, dead_tuples, index_churn, table_bytes_before, table_bytes_after,
+                      index_bytes_before, index_bytes_after, duration_ms, created_at
+               from compaction_runs
+               where action = $1
+               order by created_at desc
+               limit 1"#,
+            action,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(run)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::config::{IterativeScan, Quantization, VectorSearchConfig};
+
+    use super::{partial_index_statement, prefetch_query, recall, session_settings};
+
+    #[test]
+    fn test_scoped_search_settings() {
+        assert!(session_settings(&VectorSearchConfig::default(), None).is_empty());
+        let cfg = VectorSearchConfig {
+            ef_search: Some(100),
+            iterative_scan: IterativeScan::RelaxedOrder,
+            partial_indexes: vec!["huggingface/transformers".to_owned()],
+            ..Default::default()
+        };
+        assert_eq!(
+            session_settings(&cfg, Some(Duration::from_secs(5))),
+            [
+                "set statement_timeout = 5000",
+                "set hnsw.ef_search = 100",
+                "set hnsw.iterative_scan = relaxed_order",
+                "set plan_cache_mode = force_custom_plan",
+            ]
+        );
+
+        let (index, statement) =
+            partial_index_statement("huggingface/transformers", Quantization::Halfvec).unwrap();
+        assert!(statement.starts_with(&format!("create index concurrently {index} on issues")));
+        assert!(statement.contains("where repository_full_name = 'huggingface/transformers'"));
+        assert!(partial_index_statement(
+            "huggingface/x'; drop table issues; --",
+            Quantization::Binary
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_two_stage_search() {
+        assert_eq!(
+            prefetch_query(Quantization::Halfvec, true),
+            "select source_id from issues where not excluded and not opted_out and not private and gone_at is null and not (labels && $3) and repository_full_name = any($4) order by embedding <=> $1 LIMIT $2"
+        );
+        assert!(!prefetch_query(Quantization::Binary, false).contains("$3"));
+
+        assert_eq!(recall(&[1, 2, 3, 4], &[4, 5, 1, 2, 6]), 0.75);
+        assert_eq!(recall(&[], &[1]), 1.);
+    }
+}
@@ -0,0 +1,4216 @@
+use std::{collections::HashMap, str::FromStr, time::Duration};
+
+use chrono::{DateTime, Utc};
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions, SqliteRow},
+    Pool, QueryBuilder, Row, Sqlite,
+};
+
+use crate::{
+    config::{DatabaseConfig, IssueState},
+    embeddings::{cosine_similarity, EmbeddingMetadata},
+    extraction::SystemInfo,
+    github::IssueWithComments,
+    issue_forms::IssueForm,
+    ClosestIssue, CommentData, IssueData, Source, Vote,
+};
+
+use super::{
+    quote_identifier, ApiKey, ArchivedComment, ArchivedIssue, BotCommentRevision, CachedResponse,
+    ClosureProposal, ClosureProposalStatus, CommentBacklog, CompactionRun, DuplicateResolution,
+    EmbeddingRecord, EventLogEntry, EventLogFilter, EventStats, ExportedIssue, GuidanceMatch,
+    GuidanceSection, HotIssue, IssueBody, IssueCohort, IssueEmbedding, IssueLink, IssueSimilarity,
+    IssueText, JobBacklog, JobData, JobState, JobType, KnowledgeBaseEntry, KnowledgeBaseMatch,
+    LinkSimilarity, OnboardedRepository, OptOutRequest, PendingComment, RecentSuggestion,
+    RepositoryCursor, RepositoryMetadata, RepositoryStats, SearchHit, SlackOutboxBacklog,
+    SlackOutboxMessage, Storage, StorageError, StoredIssue, StoredIssueId, SuggestedIssue,
+    Suggestion, TableHealth, ISSUE_URL_COLUMNS, REPOSITORY_KEYED_TABLES, REPOSITORY_TABLES,
+};
+
+/// SQLite counterpart of `init_db.sql`, applied when connecting
+///
+/// Labels and job data are stored as JSON, embeddings as little endian f32 blobs.
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS issues (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  source_id INTEGER NOT NULL UNIQUE,
+  source TEXT NOT NULL,
+  title TEXT NOT NULL,
+  body TEXT NOT NULL,
+  is_pull_request BOOLEAN NOT NULL,
+  number INTEGER NOT NULL,
+  html_url TEXT NOT NULL,
+  url TEXT NOT NULL,
+  repository_full_name TEXT NOT NULL,
+  labels TEXT NOT NULL DEFAULT '[]',
+  milestone TEXT,
+  author TEXT,
+  embedding BLOB NOT NULL,
+  excluded BOOLEAN NOT NULL DEFAULT false,
+  opted_out BOOLEAN NOT NULL DEFAULT false,
+  fingerprint TEXT,
+  private BOOLEAN NOT NULL DEFAULT false,
+  gone_at TEXT,
+  closed_at TEXT,
+  package_version TEXT,
+  python_version TEXT,
+  torch_version TEXT,
+  os TEXT,
+  accelerator TEXT,
+  gpu TEXT,
+  form_description TEXT,
+  form_reproduction TEXT,
+  form_expected_behavior TEXT,
+  form_environment TEXT,
+  code_context TEXT,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS issues_fingerprint_idx ON issues (fingerprint);
+CREATE INDEX IF NOT EXISTS issues_author_idx ON issues (author);
+CREATE INDEX IF NOT EXISTS issues_accelerator_idx ON issues (accelerator);
+
+CREATE TABLE IF NOT EXISTS comments (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  source_id INTEGER NOT NULL UNIQUE,
+  issue_id INTEGER NOT NULL REFERENCES issues(id) ON DELETE CASCADE,
+  body TEXT NOT NULL,
+  url TEXT NOT NULL,
+  author TEXT,
+  opted_out BOOLEAN NOT NULL DEFAULT false,
+  is_review BOOLEAN NOT NULL DEFAULT false,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS comments_author_idx ON comments (author);
+
+CREATE TABLE IF NOT EXISTS archived_issues (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  source_id INTEGER NOT NULL,
+  repository_full_name TEXT NOT NULL,
+  data TEXT NOT NULL,
+  archived_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS closure_proposals (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  issue_source_id INTEGER NOT NULL,
+  issue_url TEXT NOT NULL,
+  duplicate_of_html_url TEXT NOT NULL,
+  comment_url TEXT NOT NULL,
+  status TEXT NOT NULL DEFAULT 'pending',
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS feedback (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  run_id TEXT NOT NULL,
+  vote TEXT NOT NULL,
+  prompt_profile TEXT,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS comment_runs (
+  run_id TEXT PRIMARY KEY,
+  issue_url TEXT NOT NULL,
+  prompt_profile TEXT,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS pending_comments (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  source TEXT NOT NULL,
+  repository_full_name TEXT NOT NULL,
+  issue_url TEXT NOT NULL,
+  body TEXT NOT NULL,
+  awaiting_approval BOOLEAN NOT NULL DEFAULT false,
+  slack_ts TEXT,
+  suggestions BOOLEAN NOT NULL DEFAULT false,
+  attempts INTEGER NOT NULL DEFAULT 0,
+  last_error TEXT,
+  next_attempt_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS issue_links (
+  repository_full_name TEXT NOT NULL,
+  number INTEGER NOT NULL,
+  linked_number INTEGER NOT NULL,
+  kind TEXT NOT NULL,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (repository_full_name, number, linked_number, kind)
+);
+
+CREATE TABLE IF NOT EXISTS repository_metadata (
+  full_name TEXT PRIMARY KEY,
+  description TEXT,
+  stars INTEGER NOT NULL,
+  topics TEXT NOT NULL DEFAULT '[]',
+  updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS settings (
+  key TEXT PRIMARY KEY,
+  value TEXT NOT NULL,
+  updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS knowledge_base (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  repository_full_name TEXT,
+  question TEXT NOT NULL,
+  answer TEXT NOT NULL,
+  embedding BLOB NOT NULL,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS guidance_sections (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  repository_full_name TEXT NOT NULL,
+  path TEXT NOT NULL,
+  heading TEXT NOT NULL,
+  anchor TEXT NOT NULL,
+  content TEXT NOT NULL,
+  content_hash TEXT,
+  embedding BLOB NOT NULL,
+  indexed_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS guidance_sections_repository_idx ON guidance_sections (repository_full_name);
+
+CREATE TABLE IF NOT EXISTS api_keys (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  name TEXT NOT NULL,
+  key_hash TEXT NOT NULL UNIQUE,
+  scopes TEXT NOT NULL,
+  expires_at TEXT,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS locks (
+  name TEXT PRIMARY KEY,
+  holder TEXT NOT NULL,
+  expires_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS jobs (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  job_type TEXT NOT NULL,
+  repository_full_name TEXT UNIQUE,
+  data TEXT NOT NULL,
+  paused BOOLEAN NOT NULL DEFAULT false,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS jobs_type_embeddings_regeneration_idx ON jobs (job_type) WHERE job_type = 'embeddings_regeneration';
+
+CREATE TABLE IF NOT EXISTS event_log (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  event_type TEXT NOT NULL,
+  description TEXT NOT NULL,
+  source_id INTEGER,
+  repository_full_name TEXT,
+  issue_number INTEGER,
+  request_id TEXT NOT NULL,
+  outcome TEXT NOT NULL,
+  run_id TEXT,
+  error TEXT,
+  embed_ms INTEGER,
+  search_ms INTEGER,
+  summarize_ms INTEGER,
+  comment_ms INTEGER,
+  total_ms INTEGER NOT NULL,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS event_log_repository_issue_idx ON event_log (repository_full_name, issue_number);
+CREATE INDEX IF NOT EXISTS event_log_request_id_idx ON event_log (request_id);
+
+CREATE TABLE IF NOT EXISTS suggestion_targets (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  repository_full_name TEXT NOT NULL,
+  number INTEGER NOT NULL,
+  issue_repository_full_name TEXT NOT NULL,
+  issue_number INTEGER NOT NULL,
+  issue_html_url TEXT NOT NULL,
+  issue_title TEXT NOT NULL,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS suggestion_targets_target_idx ON suggestion_targets (repository_full_name, number, created_at);
+
+CREATE TABLE IF NOT EXISTS escalations (
+  repository_full_name TEXT NOT NULL,
+  number INTEGER NOT NULL,
+  escalated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (repository_full_name, number)
+);
+
+CREATE TABLE IF NOT EXISTS suggestions (
+  issue_source_id INTEGER NOT NULL,
+  repository_full_name TEXT NOT NULL,
+  number INTEGER NOT NULL,
+  rank INTEGER NOT NULL,
+  cosine_similarity REAL NOT NULL,
+  run_id TEXT NOT NULL,
+  prompt_profile TEXT,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (issue_source_id, repository_full_name, number)
+);
+
+CREATE TABLE IF NOT EXISTS duplicate_resolutions (
+  issue_source_id INTEGER PRIMARY KEY,
+  repository_full_name TEXT NOT NULL,
+  number INTEGER NOT NULL,
+  duplicate_of_number INTEGER NOT NULL,
+  had_suggestions BOOLEAN NOT NULL,
+  suggested_rank INTEGER,
+  resolved_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS link_similarities (
+  model TEXT NOT NULL,
+  repository_full_name TEXT NOT NULL,
+  number INTEGER NOT NULL,
+  linked_number INTEGER NOT NULL,
+  cosine_similarity REAL NOT NULL,
+  sampled_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (model, repository_full_name, number, linked_number)
+);
+
+CREATE TABLE IF NOT EXISTS repository_cursors (
+  repository_full_name TEXT PRIMARY KEY,
+  processed_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS suppressed_issues (
+  source_id INTEGER PRIMARY KEY,
+  suppressed_by TEXT NOT NULL,
+  suppressed_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS opt_out_requests (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  login TEXT NOT NULL,
+  requested_through TEXT NOT NULL,
+  purged BOOLEAN NOT NULL,
+  issues INTEGER NOT NULL,
+  comments INTEGER NOT NULL,
+  requested_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS opt_out_requests_login_idx ON opt_out_requests (login);
+
+CREATE TABLE IF NOT EXISTS embedding_metadata (
+  source_id INTEGER PRIMARY KEY REFERENCES issues(source_id) ON DELETE CASCADE,
+  model TEXT NOT NULL,
+  endpoint TEXT NOT NULL,
+  input_chars INTEGER NOT NULL,
+  input_tokens INTEGER,
+  truncated BOOLEAN NOT NULL,
+  template TEXT,
+  embedded_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS onboarded_repositories (
+  repository_full_name TEXT PRIMARY KEY,
+  hook_id INTEGER,
+  estimated_issues INTEGER NOT NULL,
+  onboarded_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS comment_idempotency_keys (
+  issue_url TEXT NOT NULL,
+  fingerprint TEXT NOT NULL,
+  posted BOOLEAN NOT NULL,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (issue_url, fingerprint)
+);
+
+CREATE TABLE IF NOT EXISTS slack_outbox (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  repository_full_name TEXT NOT NULL,
+  payload TEXT NOT NULL,
+  attempts INTEGER NOT NULL DEFAULT 0,
+  last_error TEXT,
+  next_attempt_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS slack_batches (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  repository_full_name TEXT NOT NULL,
+  payload TEXT NOT NULL,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS github_response_cache (
+  url TEXT PRIMARY KEY,
+  etag TEXT NOT NULL,
+  link TEXT,
+  body BLOB NOT NULL,
+  cached_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS github_response_cache_cached_at_idx ON github_response_cache (cached_at);
+
+CREATE TABLE IF NOT EXISTS bot_comments (
+  issue_url TEXT PRIMARY KEY,
+  comment_url TEXT NOT NULL,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS bot_comment_revisions (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  issue_url TEXT NOT NULL,
+  comment_url TEXT NOT NULL,
+  body TEXT NOT NULL,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS bot_comment_revisions_issue_url_idx ON bot_comment_revisions (issue_url);
+
+CREATE TABLE IF NOT EXISTS compaction_runs (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  action TEXT NOT NULL,
+  dead_tuples INTEGER NOT NULL,
+  index_churn INTEGER NOT NULL,
+  table_bytes_before INTEGER NOT NULL,
+  table_bytes_after INTEGER NOT NULL,
+  index_bytes_before INTEGER NOT NULL,
+  index_bytes_after INTEGER NOT NULL,
+  duration_ms INTEGER NOT NULL,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS compaction_runs_action_created_at_idx ON compaction_runs (action, created_at);
+
+CREATE TABLE IF NOT EXISTS private_repositories (
+  repository_full_name TEXT PRIMARY KEY,
+  updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+"#;
+
+fn encode_embedding(embedding: &[f32]) -> Vec<u8> {
+    embedding.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn decode_embedding(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect()
+}
+
+fn knowledge_base_entry_from_row(row: &SqliteRow) -> Result<KnowledgeBaseEntry, StorageError> {
+    Ok(KnowledgeBaseEntry {
+        id: row.try_get("id")?,
+        repository_full_name: row.try_get("repository_full_name")?,
+        question: row.try_get("question")?,
+        answer: row.try_get("answer")?,
+    })
+}
+
+fn event_log_entry_from_row(row: &SqliteRow) -> Result<EventLogEntry, StorageError> {
+    Ok(EventLogEntry {
+        event_type: row.try_get("event_type")?,
+        description: row.try_get("description")?,
+        source_id: row.try_get("source_id")?,
+        repository_full_name: row.try_get("repository_full_name")?,
+        issue_number: row.try_get("issue_number")?,
+        request_id: row.try_get("request_id")?,
+        outcome: row.try_get::<String, _>("outcome")?.parse()?,
+        run_id: row.try_get("run_id")?,
+        error: row.try_get("error")?,
+        embed_ms: row.try_get("embed_ms")?,
+        search_ms: row.try_get("search_ms")?,
+        summarize_ms: row.try_get("summarize_ms")?,
+        comment_ms: row.try_get("comment_ms")?,
+        total_ms: row.try_get("total_ms")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+fn api_key_from_row(row: &SqliteRow) -> Result<ApiKey, StorageError> {
+    Ok(ApiKey {
+        id: row.try_get("id")?,
+        name: row.try_get("name")?,
+        scopes: row.try_get("scopes")?,
+        expires_at: row.try_get("expires_at")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+fn opt_out_request_from_row(row: &SqliteRow) -> Result<OptOutRequest, StorageError> {
+    Ok(OptOutRequest {
+        id: row.try_get("id")?,
+        login: row.try_get("login")?,
+        requested_through: row.try_get("requested_through")?,
+        purged: row.try_get("purged")?,
+        issues: row.try_get("issues")?,
+        comments: row.try_get("comments")?,
+        requested_at: row.try_get("requested_at")?,
+    })
+}
+
+fn job_state_from_row(row: &SqliteRow) -> Result<JobState, StorageError> {
+    Ok(JobState {
+        id: row.try_get("id")?,
+        job_type: row.try_get("job_type")?,
+        repository_full_name: row.try_get("repository_full_name")?,
+        data: serde_json::from_str(row.try_get("data")?)?,
+        paused: row.try_get("paused")?,
+    })
+}
+
+fn pending_comment_from_row(row: &SqliteRow) -> Result<PendingComment, StorageError> {
+    Ok(PendingComment {
+        id: row.try_get("id")?,
+        source: row.try_get("source")?,
+        repository_full_name: row.try_get("repository_full_name")?,
+        issue_url: row.try_get("issue_url")?,
+        body: row.try_get("body")?,
+        suggestions: row.try_get("suggestions")?,
+        attempts: row.try_get("attempts")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+/// Single file backend for small deployments, similarity search is a brute-force scan
+#[derive(Clone)]
+pub struct SqliteStorage {
+    pool: Pool<Sqlite>,
+}
+
+impl SqliteStorage {
+    pub async fn connect(cfg: &DatabaseConfig) -> Result<Self, StorageError> {
+        if cfg.read_replica.is_some() {
+            return Err(StorageError::UnsupportedReadReplica);
+        }
+        let opts = SqliteConnectOptions::from_str(&cfg.connection_string)?
+            .create_if_missing(true)
+            .foreign_keys(true);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(cfg.max_connections)
+            .connect_with(opts)
+            .await?;
+        sqlx::raw_sql(SCHEMA).execute(&pool).await?;
+        Ok(Self { pool })
+    }
+}
+
+/// deletes the issues and comments of `login` along with every copy kept of them, returning how
+/// many issues and comments were deleted
+async fn purge_author(
+    tx: &mut sqlx::Transaction<'_, Sqlite>,
+    login: &str,
+) -> Result<(u64, u64), StorageError> {
+    let issues = sqlx::query(
+        "select source_id, url, html_url, repository_full_name, number from issues where author = ?",
+    )
+    .bind(login)
+    .fetch_all(&mut **tx)
+    .await?;
+    for issue in issues {
+        let source_id: i64 = issue.try_get("source_id")?;
+        let url: String = issue.try_get("url")?;
+        let html_url: String = issue.try_get("html_url")?;
+        let repository_full_name: String = issue.try_get("repository_full_name")?;
+        let number: i32 = issue.try_get("number")?;
+        // the issue's suggestions, and those of other issues pointing to it
+        sqlx::query(
+            r#"delete from suggestions
+               where issue_source_id = ? or (repository_full_name = ? and number = ?)"#,
+        )
+        .bind(source_id)
+        .bind(&repository_full_name)
+        .bind(number)
+        .execute(&mut **tx)
+        .await?;
+        sqlx::query(
+            r#"delete from event_log
+               where source_id = ? or (repository_full_name = ? and issue_number = ?)"#,
+        )
+        .bind(source_id)
+        .bind(&repository_full_name)
+        .bind(number)
+        .execute(&mut **tx)
+        .await?;
+        // the bot's comments on the issue, and those listing it
+        sqlx::query("delete from bot_comment_revisions where issue_url = ? or instr(body, ?) > 0")
+            .bind(&url)
+            .bind(&html_url)
+            .execute(&mut **tx)
+            .await?;
+        sqlx::query("delete from bot_comments where issue_url = ?")
+            .bind(&url)
+            .execute(&mut **tx)
+            .await?;
+        sqlx::query("delete from slack_outbox where instr(payload, ?) > 0")
+            .bind(&html_url)
+            .execute(&mut **tx)
+            .await?;
+        sqlx::query("delete from knowledge_base where instr(question || answer, ?) > 0")
+            .bind(&html_url)
+            .execute(&mut **tx)
+            .await?;
+        // the issue and its comments pages
+        sqlx::query("delete from github_response_cache where url = ?1 or url like ?1 || '/%'")
+            .bind(&url)
+            .execute(&mut **tx)
+            .await?;
+    }
+    let comments = sqlx::query("select source_id, url from comments where author = ?")
+        .bind(login)
+        .fetch_all(&mut **tx)
+        .await?;
+    for comment in comments {
+        let source_id: i64 = comment.try_get("source_id")?;
+        let url: String = comment.try_get("url")?;
+        sqlx::query("delete from event_log where source_id = ?")
+            .bind(source_id)
+            .execute(&mut **tx)
+            .await?;
+        sqlx::query("delete from github_response_cache where url = ?")
+            .bind(&url)
+            .execute(&mut **tx)
+            .await?;
+    }
+    sqlx::query("delete from archived_issues where json_extract(data, '$.author') = ?")
+        .bind(login)
+        .execute(&mut **tx)
+        .await?;
+    sqlx::query(
+        r#"update archived_issues
+           set data = json_set(data, '$.comments', (
+               select json_group_array(json(c.value))
+               from json_each(data, '$.comments') c
+               where json_extract(c.value, '$.author') is not ?1
+           ))
+           where exists (
+               select 1 from json_each(data, '$.comments') c
+               where json_extract(c.value, '$.author') = ?1
+           )"#,
+    )
+    .bind(login)
+    .execute(&mut **tx)
+    .await?;
+
+    // comments of other authors go along with the issues
+    let comments = sqlx::query("delete from comments where author = ?")
+        .bind(login)
+        .execute(&mut **tx)
+        .await?
+        .rows_affected();
+    let issues = sqlx::query("delete from issues where author = ?")
+        .bind(login)
+        .execute(&mut **tx)
+        .await?
+        .rows_affected();
+    Ok((issues, comments))
+}
+
+impl Storage for SqliteStorage {
+    async fn check(&self) -> Result<(), StorageError> {
+        sqlx::query("select 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn create_partial_indexes(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    async fn closest_issues(
+        &self,
+        embedding: &[f32],
+        excluded_labels: &[String],
+        repositories: &[String],
+        limit: i64,
+        _with_embeddings: bool,
+    ) -> Result<Vec<ClosestIssue>, StorageError> {
+        // the similarities are computed here, the embeddings are loaded either way
+        let rows = sqlx::query(
+            "select title, number, html_url, labels, repository_full_name, embedding from issues where not excluded and not opted_out and not private and gone_at is null",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let mut issues = Vec::with_capacity(rows.len());
+        for row in rows {
+            let repository_full_name: String = row.try_get("repository_full_name")?;
+            if !repositories.contains(&repository_full_name) {
+                continue;
+            }
+            let labels: Vec<String> = serde_json::from_str(row.try_get("labels")?)?;
+            if labels.iter().any(|l| excluded_labels.contains(l)) {
+                continue;
+            }
+            let issue_embedding = decode_embedding(row.try_get("embedding")?);
+            issues.push(ClosestIssue {
+                title: row.try_get("title")?,
+                number: row.try_get("number")?,
+                html_url: row.try_get("html_url")?,
+                labels,
+                repository_full_name,
+                cosine_similarity: cosine_similarity(embedding, &issue_embedding),
+                boost: 0.,
+                embedding: issue_embedding,
+            });
+        }
+        issues.sort_by(|a, b| b.cosine_similarity.total_cmp(&a.cosine_similarity));
+        issues.truncate(limit.max(0) as usize);
+        Ok(issues)
+    }
+
+    async fn closest_hot_issues(
+        &self,
+        embedding: &[f32],
+        excluded_labels: &[String],
+        repositories: &[String],
+        limit: i64,
+        cached: &[i64],
+    ) -> Result<Vec<IssueSimilarity>, StorageError> {
+        let rows = sqlx::query(
+            "select source_id, labels, repository_full_name, embedding from issues where not excluded and not opted_out and not private and gone_at is null",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let mut issues = Vec::with_capacity(rows.len());
+        for row in rows {
+            let repository_full_name: String = row.try_get("repository_full_name")?;
+            if !repositories.contains(&repository_full_name) {
+                continue;
+            }
+            let labels: Vec<String> = serde_json::from_str(row.try_get("labels")?)?;
+            if labels.iter().any(|l| excluded_labels.contains(l)) {
+                continue;
+            }
+            issues.push(IssueSimilarity {
+                source_id: row.try_get("source_id")?,
+                cosine_similarity: cosine_similarity(
+                    embedding,
+                    &decode_embedding(row.try_get("embedding")?),
+                ),
+                issue: None,
+            });
+        }
+        issues.sort_by(|a, b| b.cosine_similarity.total_cmp(&a.cosine_similarity));
+        issues.truncate(limit.max(0) as usize);
+
+        let missing: Vec<i64> = issues
+            .iter()
+            .map(|issue| issue.source_id)
+            .filter(|source_id| !cached.contains(source_id))
+            .collect();
+        let mut contents: HashMap<i64, HotIssue> = self
+            .hot_issues(&missing)
+            .await?
+            .into_iter()
+            .map(|issue| (issue.source_id, issue))
+            .collect();
+        for issue in &mut issues {
+            issue.issue = contents.remove(&issue.source_id);
+        }
+        Ok(issues)
+    }
+
+    async fn hot_issues(&self, source_ids: &[i64]) -> Result<Vec<HotIssue>, StorageError> {
+        if source_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut query_builder = QueryBuilder::<Sqlite>::new(
+            "select source_id, title, number, html_url, labels, repository_full_name, embedding from issues where source_id in (",
+        );
+        let mut separated = query_builder.separated(", ");
+        for source_id in source_ids {
+            separated.push_bind(source_id);
+        }
+        separated.push_unseparated(")");
+        let rows = query_builder.build().fetch_all(&self.pool).await?;
+        let mut issues = Vec::with_capacity(rows.len());
+        for row in rows {
+            issues.push(HotIssue {
+                source_id: row.try_get("source_id")?,
+                title: row.try_get("title")?,
+                number: row.try_get("number")?,
+                html_url: row.try_get("html_url")?,
+                labels: serde_json::from_str(row.try_get("labels")?)?,
+                repository_full_name: row.try_get("repository_full_name")?,
+                embedding: decode_embedding(row.try_get("embedding")?),
+            });
+        }
+        Ok(issues)
+    }
+
+    async fn issue_by_fingerprint(
+        &self,
+        fingerprint: &str,
+        excluded_labels: &[String],
+        repositories: &[String],
+    ) -> Result<Option<ClosestIssue>, StorageError> {
+        let rows = sqlx::query(
+            "select title, number, html_url, labels, repository_full_name, embedding from issues where fingerprint = ? and not excluded and not opted_out and not private and gone_at is null order by created_at, id",
+        )
+        .bind(fingerprint)
+        .fetch_all(&self.pool)
+        .await?;
+        for row in rows {
+            let repository_full_name: String = row.try_get("repository_full_name")?;
+            if !repositories.contains(&repository_full_name) {
+                continue;
+            }
+            let labels: Vec<String> = serde_json::from_str(row.try_get("labels")?)?;
+            if labels.iter().any(|l| excluded_labels.contains(l)) {
+                continue;
+            }
+            return Ok(Some(ClosestIssue {
+                title: row.try_get("title")?,
+                number: row.try_get("number")?,
+                html_url: row.try_get("html_url")?,
+                labels,
+                repository_full_name,
+                cosine_similarity: 1.,
+                boost: 0.,
+                embedding: decode_embedding(row.try_get("embedding")?),
+            }));
+        }
+        Ok(None)
+    }
+
+    async fn set_issue_fingerprint(
+        &self,
+        source_id: i64,
+        fingerprint: Option<&str>,
+    ) -> Result<(), StorageError> {
+        sqlx::query("update issues set fingerprint = ? where source_id = ?")
+            .bind(fingerprint)
+            .bind(source_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn unfingerprinted_issues(
+        &self,
+        after_id: i32,
+        limit: i64,
+    ) -> Result<Vec<IssueBody>, StorageError> {
+        let issues = sqlx::query_as(
+            r#"select id, source_id, body from issues
+               where id > ? and fingerprint is null
+                 and body like '%Traceback (most recent call last)%'
+               order by id
+               limit ?"#,
+        )
+        .bind(after_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(issues)
+    }
+
+    async fn set_system_info(&self, source_id: i64, info: &SystemInfo) -> Result<(), StorageError> {
+        sqlx::query(
+            r#"update issues
+               set package_version = ?, python_version = ?, torch_version = ?, os = ?,
+                   accelerator = ?, gpu = ?
+               where source_id = ?"#,
+        )
+        .bind(&info.package_version)
+        .bind(&info.python_version)
+        .bind(&info.torch_version)
+        .bind(&info.os)
+        .bind(&info.accelerator)
+        .bind(&info.gpu)
+        .bind(source_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn merge_system_info(
+        &self,
+        source_id: i64,
+        info: &SystemInfo,
+    ) -> Result<(), StorageError> {
+        sqlx::query(
+            r#"update issues
+               set package_version = coalesce(?, package_version),
+                   python_version = coalesce(?, python_version),
+                   torch_version = coalesce(?, torch_version), os = coalesce(?, os),
+                   accelerator = coalesce(?, accelerator), gpu = coalesce(?, gpu)
+               where source_id = ?"#,
+        )
+        .bind(&info.package_version)
+        .bind(&info.python_version)
+        .bind(&info.torch_version)
+        .bind(&info.os)
+        .bind(&info.accelerator)
+        .bind(&info.gpu)
+        .bind(source_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn set_issue_form(&self, source_id: i64, form: &IssueForm) -> Result<(), StorageError> {
+        sqlx::query(
+            r#"update issues
+               set form_description = ?, form_reproduction = ?, form_expected_behavior = ?,
+                   form_environment = ?
+               where source_id = ?"#,
+        )
+        .bind(&form.description)
+        .bind(&form.reproduction)
+        .bind(&form.expected_behavior)
+        .bind(&form.environment)
+        .bind(source_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn set_code_context(
+        &self,
+        source_id: i64,
+        code_context: Option<&str>,
+    ) -> Result<(), StorageError> {
+        sqlx::query("update issues set code_context = ? where source_id = ?")
+            .bind(code_context)
+            .bind(source_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn insert_issue(
+        &self,
+        issue: &IssueData,
+        embedding: &[f32],
+        fingerprint: Option<&str>,
+    ) -> Result<(), StorageError> {
+        // issues of authors who opted out and of private repositories are flagged right away
+        sqlx::query(
+            r#"insert into issues (source_id, source, title, body, is_pull_request, number, html_url, url, repository_full_name, labels, milestone, embedding, author, fingerprint, opted_out, private)
+               values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, exists (select 1 from opt_out_requests where login = ?13), exists (select 1 from private_repositories where repository_full_name = ?9))"#,
+        )
+        .bind(issue.source_id)
+        .bind(issue.source.to_string())
+        .bind(&issue.title)
+        .bind(&issue.body)
+        .bind(issue.is_pull_request)
+        .bind(issue.number)
+        .bind(&issue.html_url)
+        .bind(&issue.url)
+        .bind(&issue.repository_full_name)
+        .bind(serde_json::to_string(&issue.labels)?)
+        .bind(&issue.milestone)
+        .bind(encode_embedding(embedding))
+        .bind(&issue.author)
+        .bind(fingerprint)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn update_issue(
+        &self,
+        issue: &IssueData,
+        fingerprint: Option<&str>,
+    ) -> Result<(), StorageError> {
+        sqlx::query(
+            r#"update issues
+               set title = ?, body = ?, url = ?, labels = ?, milestone = ?, fingerprint = ?,
+                   updated_at = CURRENT_TIMESTAMP, excluded = false, gone_at = null
+               where source_id = ?"#,
+        )
+        .bind(&issue.title)
+        .bind(&issue.body)
+        .bind(&issue.url)
+        .bind(serde_json::to_string(&issue.labels)?)
+        .bind(&issue.milestone)
+        .bind(fingerprint)
+        .bind(issue.source_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn update_issue_metadata(
+        &self,
+        source_id: i64,
+        labels: &[String],
+        milestone: Option<&str>,
+    ) -> Result<(), StorageError> {
+        sqlx::query(
+            r#"update issues
+               set labels = ?, milestone = ?, updated_at = CURRENT_TIMESTAMP, gone_at = null
+               where source_id = ?"#,
+        )
+        .bind(serde_json::to_string(labels)?)
+        .bind(milestone)
+        .bind(source_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_issue(&self, source_id: i64) -> Result<(), StorageError> {
+        sqlx::query("delete from issues where source_id = ?")
+            .bind(source_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_issues(&self, source_ids: &[i64]) -> Result<(), StorageError> {
+        if source_ids.is_empty() {
+            return Ok(());
+        }
+        let mut query_builder =
+            QueryBuilder::<Sqlite>::new("delete from issues where source_id in (");
+        let mut separated = query_builder.separated(", ");
+        for source_id in source_ids {
+            separated.push_bind(source_id);
+        }
+        separated.push_unseparated(")");
+        query_builder.build().execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn set_issue_closed(&self, source_id: i64, closed: bool) -> Result<(), StorageError> {
+        sqlx::query(
+            r#"update issues
+               set closed_at = case when ?2 then coalesce(closed_at, CURRENT_TIMESTAMP) end,
+                   excluded = ?2 and excluded, gone_at = null
+               where source_id = ?1"#,
+        )
+        .bind(source_id)
+        .bind(closed)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn issue_id(&self, source_id: i64) -> Result<Option<i32>, StorageError> {
+        let id = sqlx::query_scalar("select id from issues where source_id = ?")
+            .bind(source_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(id)
+    }
+
+    async fn issue_source_id(
+        &self,
+        repository_full_name: &str,
+        number: i32,
+    ) -> Result<Option<i64>, StorageError> {
+        let source_id = sqlx::query_scalar(
+            "select source_id from issues where repository_full_name = ? and number = ?",
+        )
+        .bind(repository_full_name)
+        .bind(number)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(source_id)
+    }
+
+    async fn stored_issue(&self, source_id: i64) -> Result<Option<StoredIssue>, StorageError> {
+        let issue = sqlx::query_as(
+            "select source, repository_full_name, number, url from issues where source_id = ?",
+        )
+        .bind(source_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(issue)
+    }
+
+    async fn insert_indexed_issue(
+        &self,
+        issue: &IssueWithComments,
+        source: &Source,
+        repository_full_name: &str,
+        embedding: &[f32],
+        fingerprint: Option<&str>,
+    ) -> Result<i32, StorageError> {
+        let id = sqlx::query_scalar(
+            r#"insert into issues (source_id, source, title, body, is_pull_request, number, html_url, url, repository_full_name, labels, milestone, embedding, author, fingerprint, opted_out, private)
+               values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, exists (select 1 from opt_out_requests where login = ?13), exists (select 1 from private_repositories where repository_full_name = ?9))
+               returning id"#,
+        )
+        .bind(issue.id)
+        .bind(source.to_string())
+        .bind(&issue.title)
+        .bind(&issue.body)
+        .bind(issue.is_pull_request)
+        .bind(issue.number)
+        .bind(&issue.html_url)
+        .bind(&issue.url)
+        .bind(repository_full_name)
+        .bind(serde_json::to_string(&issue.labels)?)
+        .bind(&issue.milestone)
+        .bind(encode_embedding(embedding))
+        .bind(&issue.author)
+        .bind(fingerprint)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(id)
+    }
+
+    async fn insert_indexed_comments(
+        &self,
+        issue_id: i32,
+        issue: &IssueWithComments,
+    ) -> Result<(), StorageError> {
+        if issue.comments.is_empty() {
+            return Ok(());
+        }
+        let mut qb = QueryBuilder::new(
+            "insert into comments (source_id, body, url, issue_id, is_review, author, opted_out)",
+        );
+        qb.push_values(&issue.comments, |mut b, comment| {
+            b.push_bind(comment.id)
+                .push_bind(&comment.body)
+                .push_bind(&comment.url)
+                .push_bind(issue_id)
+                .push_bind(comment.is_review)
+                .push_bind(comment.author())
+                .push("exists (select 1 from opt_out_requests where login = ")
+                .push_bind_unseparated(comment.author())
+                .push_unseparated(")");
+        });
+        qb.push("on conflict do nothing");
+        qb.build().execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn insert_comment(
+        &self,
+        issue_id: i32,
+        comment: &CommentData,
+    ) -> Result<(), StorageError> {
+        sqlx::query(
+            r#"insert into comments (source_id, body, url, issue_id, is_review, author, opted_out)
+               values (?, ?, ?, ?, ?, ?, exists (select 1 from opt_out_requests where login = ?))"#,
+        )
+        .bind(comment.source_id)
+        .bind(&comment.body)
+        .bind(&comment.url)
+        .bind(issue_id)
+        .bind(comment.is_review)
+        .bind(&comment.author)
+        .bind(&comment.author)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn update_comment(&self, comment: &CommentData) -> Result<Option<i64>, StorageError> {
+        let issue_source_id = sqlx::query_scalar(
+            r#"update comments
+               set body = ?, url = ?, updated_at = CURRENT_TIMESTAMP
+               where source_id = ?
+               returning (select source_id from issues where id = comments.issue_id)"#,
+        )
+        .bind(&comment.body)
+        .bind(&comment.url)
+        .bind(comment.source_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(issue_source_id)
+    }
+
+    async fn delete_comment(&self, source_id: i64) -> Result<Option<i64>, StorageError> {
+        let issue_source_id = sqlx::query_scalar(
+            r#"delete from comments where source_id = ?
+               returning (select source_id from issues where id = comments.issue_id)"#,
+        )
+        .bind(source_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(issue_source_id)
+    }
+
+    async fn archived_issue(&self, source_id: i64) -> Result<Option<ArchivedIssue>, StorageError> {
+        let Some(issue) = sqlx::query("select * from issues where source_id = ?")
+            .bind(source_id)
+            .fetch_optional(&self.pool)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let issue_id: i32 = issue.try_get("id")?;
+        let comments = sqlx::query(
+            "select source_id, body, url, author from comments where issue_id = ? order by source_id",
+        )
+        .bind(issue_id)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|c| {
+            Ok(ArchivedComment {
+                source_id: c.try_get("source_id")?,
+                body: c.try_get("body")?,
+                url: c.try_get("url")?,
+                author: c.try_get("author")?,
+            })
+        })
+        .collect::<Result<_, StorageError>>()?;
+        Ok(Some(ArchivedIssue {
+            source_id,
+            source: issue.try_get("source")?,
+            title: issue.try_get("title")?,
+            body: issue.try_get("body")?,
+            is_pull_request: issue.try_get("is_pull_request")?,
+            number: issue.try_get("number")?,
+            html_url: issue.try_get("html_url")?,
+            url: issue.try_get("url")?,
+            repository_full_name: issue.try_get("repository_full_name")?,
+            labels: serde_json::from_str(issue.try_get("labels")?)?,
+            milestone: issue.try_get("milestone")?,
+            author: issue.try_get("author")?,
+            comments,
+            embedding: decode_embedding(issue.try_get("embedding")?),
+        }))
+    }
+
+    async fn archive_issue(&self, issue: &ArchivedIssue) -> Result<(), StorageError> {
+        sqlx::query(
+            "insert into archived_issues (source_id, repository_full_name, data) values (?, ?, ?)",
+        )
+        .bind(issue.source_id)
+        .bind(&issue.repository_full_name)
+        .bind(serde_json::to_string(issue)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn issue_text(&self, source_id: i64) -> Result<IssueText, StorageError> {
+        let issue = sqlx::query(
+            "select id, repository_full_name, labels, title, body, code_context from issues where source_id = ?",
+        )
+        .bind(source_id)
+        .fetch_one(&self.pool)
+        .await?;
+        let issue_id: i32 = issue.try_get("id")?;
+        let comments = sqlx::query_scalar(
+            "select body from comments where issue_id = ? and not opted_out order by source_id",
+        )
+        .bind(issue_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(IssueText {
+            repository_full_name: issue.try_get("repository_full_name")?,
+            labels: serde_json::from_str(issue.try_get("labels")?)?,
+            title: issue.try_get("title")?,
+            body: issue.try_get("body")?,
+            comments,
+            code_context: issue.try_get("code_context")?,
+        })
+    }
+
+    async fn update_issue_embedding(
+        &self,
+        source_id: i64,
+        embedding: &[f32],
+    ) -> Result<(), StorageError> {
+        sqlx::query(
+            r#"update issues
+               set embedding = ?, updated_at = CURRENT_TIMESTAMP
+               where source_id = ?"#,
+        )
+        .bind(encode_embedding(embedding))
+        .bind(source_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn record_embedding_metadata(
+        &self,
+        source_id: i64,
+        metadata: &EmbeddingMetadata,
+    ) -> Result<(), StorageError> {
+        sqlx::query(
+            r#"insert into embedding_metadata (source_id, model, endpoint, input_chars, input_tokens, truncated, template)
+               values (?, ?, ?, ?, ?, ?, ?)
+               on conflict (source_id)
+               do update set model = excluded.model, endpoint = excluded.endpoint,
+                             input_chars = excluded.input_chars,
+                             input_tokens = excluded.input_tokens,
+                             truncated = excluded.truncated, template = excluded.template,
+                             embedded_at = CURRENT_TIMESTAMP"#,
+        )
+        .bind(source_id)
+        .bind(&metadata.model)
+        .bind(&metadata.endpoint)
+        .bind(metadata.input_chars)
+        .bind(metadata.input_tokens)
+        .bind(metadata.truncated)
+        .bind(&metadata.template)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn embedding_metadata(
+        &self,
+        source_id: i64,
+    ) -> Result<Option<EmbeddingRecord>, StorageError> {
+        let record = sqlx::query_as(
+            r#"select source_id, model, endpoint, input_chars, input_tokens, truncated, template,
+                      embedded_at
+               from embedding_metadata where source_id = ?"#,
+        )
+        .bind(source_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(record)
+    }
+
+    async fn issue_ids_after(
+        &self,
+        id: i32,
+        limit: i64,
+    ) -> Result<Vec<StoredIssueId>, StorageError> {
+        let rows = sqlx::query("select id, source_id from issues where id > ? order by id limit ?")
+            .bind(id)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+        rows.into_iter()
+            .map(|row| -> Result<StoredIssueId, StorageError> {
+                Ok(StoredIssueId {
+                    id: row.try_get("id")?,
+                    source_id: row.try_get("source_id")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn count_issues_after(&self, id: i32) -> Result<i64, StorageError> {
+        let count = sqlx::query_scalar("select count(*) from issues where id > ?")
+            .bind(id)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(count)
+    }
+
+    async fn export_issues(
+        &self,
+        after_id: i32,
+        limit: i64,
+    ) -> Result<Vec<ExportedIssue>, StorageError> {
+        let rows = sqlx::query(
+            r#"select id, source_id, source, repository_full_name, number, title, body, html_url,
+                      is_pull_request, labels, milestone, created_at, updated_at
+               from issues
+               where id > ? and not excluded and not opted_out and not private and gone_at is null
+               order by id
+               limit ?"#,
+        )
+        .bind(after_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter()
+            .map(|row| -> Result<ExportedIssue, StorageError> {
+                Ok(ExportedIssue {
+                    id: row.try_get("id")?,
+                    source_id: row.try_get("source_id")?,
+                    source: row.try_get("source")?,
+                    repository_full_name: row.try_get("repository_full_name")?,
+                    number: row.try_get("number")?,
+                    title: row.try_get("title")?,
+                    body: row.try_get("body")?,
+                    html_url: row.try_get("html_url")?,
+                    is_pull_request: row.try_get("is_pull_request")?,
+                    labels: serde_json::from_str(row.try_get("labels")?)?,
+                    milestone: row.try_get("milestone")?,
+                    created_at: row.try_get("created_at")?,
+                    updated_at: row.try_get("updated_at")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn stale_issues(
+        &self,
+        repository_full_name: Option<&str>,
+        older_than_days: i64,
+        labels: &[String],
+        state: Option<IssueState>,
+    ) -> Result<Vec<i64>, StorageError> {
+        let rows = sqlx::query(
+            r#"select source_id, labels from issues
+               where not excluded
+                 and updated_at < datetime('now', ?1)
+                 and (?2 is null or repository_full_name = ?2)
+                 and (?3 is null or (closed_at is not null) = ?3)"#,
+        )
+        .bind(format!("-{older_than_days} days"))
+        .bind(repository_full_name)
+        .bind(state.map(|state| state == IssueState::Closed))
+        .fetch_all(&self.pool)
+        .await?;
+        let mut source_ids = Vec::new();
+        for row in rows {
+            let issue_labels: Vec<String> = serde_json::from_str(row.try_get("labels")?)?;
+            if labels.is_empty() || issue_labels.iter().any(|l| labels.contains(l)) {
+                source_ids.push(row.try_get("source_id")?);
+            }
+        }
+        Ok(source_ids)
+    }
+
+    async fn exclude_issues(&self, source_ids: &[i64]) -> Result<(), StorageError> {
+        if source_ids.is_empty() {
+            return Ok(());
+        }
+        let mut query_builder =
+            QueryBuilder::<Sqlite>::new("update issues set excluded = true where source_id in (");
+        let mut separated = query_builder.separated(", ");
+        for source_id in source_ids {
+            separated.push_bind(source_id);
+        }
+        separated.push_unseparated(")");
+        query_builder.build().execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn mark_issue_gone(&self, url: &str) -> Result<bool, StorageError> {
+        let mut tx = self.pool.begin().await?;
+        let res = sqlx::query(
+            "update issues set gone_at = CURRENT_TIMESTAMP where url = ? and gone_at is null",
+        )
+        .bind(url)
+        .execute(&mut *tx)
+        .await?;
+        // the issue and its comments pages
+        sqlx::query("delete from github_response_cache where url = ?1 or url like ?1 || '/%'")
+            .bind(url)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn move_repository(&self, from: &str, to: &str) -> Result<u64, StorageError> {
+        let (old_path, new_path) = (format!("/{from}/"), format!("/{to}/"));
+        let mut tx = self.pool.begin().await?;
+        let moved = sqlx::query(
+            r#"update issues
+               set repository_full_name = ?1, html_url = replace(html_url, ?2, ?3),
+                   url = replace(url, ?2, ?3), updated_at = CURRENT_TIMESTAMP
+               where repository_full_name = ?4"#,
+        )
+        .bind(to)
+        .bind(&old_path)
+        .bind(&new_path)
+        .bind(from)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        for table in REPOSITORY_TABLES {
+            sqlx::query(&format!(
+                "update {table} set repository_full_name = ? where repository_full_name = ?"
+            ))
+            .bind(to)
+            .bind(from)
+            .execute(&mut *tx)
+            .await?;
+        }
+        for (table, column) in REPOSITORY_KEYED_TABLES {
+            sqlx::query(&format!("delete from {table} where {column} = ?"))
+                .bind(to)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query(&format!(
+                "update {table} set {column} = ? where {column} = ?"
+            ))
+            .bind(to)
+            .bind(from)
+            .execute(&mut *tx)
+            .await?;
+        }
+        sqlx::query(
+            "update suggestion_targets set issue_repository_full_name = ? where issue_repository_full_name = ?",
+        )
+        .bind(to)
+        .bind(from)
+        .execute(&mut *tx)
+        .await?;
+        for (table, columns) in ISSUE_URL_COLUMNS {
+            let set = columns
+                .iter()
+                .map(|column| format!("{column} = replace({column}, ?1, ?2)"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let filter = columns
+                .iter()
+                .map(|column| format!("instr({column}, ?1) > 0"))
+                .collect::<Vec<_>>()
+                .join(" or ");
+            sqlx::query(&format!("update {table} set {set} where {filter}"))
+                .bind(&old_path)
+                .bind(&new_path)
+                .execute(&mut *tx)
+                .await?;
+        }
+        sqlx::query("delete from github_response_cache where instr(url, ?) > 0")
+            .bind(&old_path)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(moved)
+    }
+
+    async fn set_repository_private(
+        &self,
+        repository_full_name: &str,
+        private: bool,
+    ) -> Result<u64, StorageError> {
+        let mut tx = self.pool.begin().await?;
+        let statement = if private {
+            r#"insert into private_repositories (repository_full_name) values (?)
+               on conflict (repository_full_name) do update set updated_at = CURRENT_TIMESTAMP"#
+        } else {
+            "delete from private_repositories where repository_full_name = ?"
+        };
+        sqlx::query(statement)
+            .bind(repository_full_name)
+            .execute(&mut *tx)
+            .await?;
+        let res = sqlx::query("update issues set private = ? where repository_full_name = ?")
+            .bind(private)
+            .bind(repository_full_name)
+            .execute(&mut *tx)
+            .await?;
+        if private {
+            sqlx::query("delete from github_response_cache where instr(url || '/', ?) > 0")
+                .bind(format!("/repos/{repository_full_name}/"))
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(res.rows_affected())
+    }
+
+    async fn repository_issues(
+        &self,
+        repository_full_name: &str,
+    ) -> Result<Vec<IssueEmbedding>, StorageError> {
+        let rows = sqlx::query(
+            r#"select number, embedding from issues
+               where repository_full_name = ? and not is_pull_request
+               order by number"#,
+        )
+        .bind(repository_full_name)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.into_iter()
+            .map(|row| -> Result<IssueEmbedding, StorageError> {
+                Ok(IssueEmbedding {
+                    number: row.try_get("number")?,
+                    embedding: decode_embedding(row.try_get("embedding")?),
+                })
+            })
+            .collect()
+    }
+
+    async fn search_issues(
+        &self,
+        embedding: &[f32],
+        repositories: &[String],
+        accelerator: Option<&str>,
+        os: Option<&str>,
+        limit: i64,
+        with_bodies: bool,
+    ) -> Result<Vec<SearchHit>, StorageError> {
+        let rows = sqlx::query(
+            r#"select i.id, i.source_id, i.title, i.number, i.html_url, i.labels,
+                      i.repository_full_name, i.is_pull_request,
+                      case when ? then i.body end as body, i.created_at, i.embedding,
+                      (select count(*) from comments c where c.issue_id = i.id) as comment_count,
+                      i.accelerator, i.os
+               from issues i
+               where not i.excluded and not i.opted_out and not i.private and i.gone_at is null
+                 and (? is null or i.accelerator = ?)
+                 and (? is null or i.os = ?)"#,
+        )
+        .bind(with_bodies)
+        .bind(accelerator)
+        .bind(accelerator)
+        .bind(os)
+        .bind(os)
+        .fetch_all(&self.pool)
+        .await?;
+        let mut hits = Vec::with_capacity(rows.len());
+        for row in rows {
+            let repository_full_name: String = row.try_get("repository_full_name")?;
+            if !repositories.is_empty() && !repositories.contains(&repository_full_name) {
+                continue;
+            }
+            hits.push(SearchHit {
+                id: row.try_get("id")?,
+                source_id: row.try_get("source_id")?,
+                title: row.try_get("title")?,
+                number: row.try_get("number")?,
+                html_url: row.try_get("html_url")?,
+                labels: serde_json::from_str(row.try_get("labels")?)?,
+                repository_full_name,
+                is_pull_request: row.try_get("is_pull_request")?,
+                body: row.try_get("body")?,
+                created_at: row.try_get("created_at")?,
+                comment_count: row.try_get("comment_count")?,
+                cosine_similarity: cosine_similarity(
+                    embedding,
+                    &decode_embedding(row.try_get("embedding")?),
+                ),
+                accelerator: row.try_get("accelerator")?,
+                os: row.try_get("os")?,
+            });
+        }
+        hits.sort_by(|a, b| b.cosine_similarity.total_cmp(&a.cosine_similarity));
+        hits.truncate(limit.max(0) as usize);
+        Ok(hits)
+    }
+
+    async fn repository_stats(&self) -> Result<Vec<RepositoryStats>, StorageError> {
+        let stats = sqlx::query_as(
+            r#"select repository_full_name,
+                      count(*) filter (where not is_pull_request) as issues,
+                      count(*) filter (where is_pull_request) as pull_requests,
+                      count(*) filter (where excluded) as excluded
+               from issues
+               group by repository_full_name
+               order by repository_full_name"#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(stats)
+    }
+
+    async fn recent_suggestions(&self, limit: i64) -> Result<Vec<RecentSuggestion>, StorageError> {
+        let suggestions = sqlx::query_as(
+            r#"select recent.created_at, s.issue_source_id,
+                      i.repository_full_name as issue_repository_full_name, i.number as issue_number,
+                      i.title as issue_title, i.html_url as issue_html_url,
+                      s.repository_full_name, s.number, si.title, si.html_url, s.cosine_similarity
+               from (
+                 select issue_source_id, max(created_at) as created_at from suggestions
+                 group by issue_source_id
+                 order by max(created_at) desc
+                 limit ?
+               ) recent
+               join suggestions s on s.issue_source_id = recent.issue_source_id
+               join issues i on i.source_id = s.issue_source_id
+               join issues si on si.repository_full_name = s.repository_full_name and si.number = s.number
+               order by recent.created_at desc, s.issue_source_id, s.rank"#,
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(suggestions)
+    }
+
+    async fn insert_issue_links(
+        &self,
+        repository_full_name: &str,
+        links: &[IssueLink],
+    ) -> Result<(), StorageError> {
+        if links.is_empty() {
+            return Ok(());
+        }
+        let mut qb = QueryBuilder::new(
+            "insert into issue_links (repository_full_name, number, linked_number, kind)",
+        );
+        qb.push_values(links, |mut b, link| {
+            b.push_bind(repository_full_name)
+                .push_bind(link.number)
+                .push_bind(link.linked_number)
+                .push_bind(link.kind.as_str());
+        });
+        qb.push("on conflict do nothing");
+        qb.build().execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn issue_links(
+        &self,
+        repository_full_name: &str,
+        numbers: Option<&[i32]>,
+    ) -> Result<Vec<IssueLink>, StorageError> {
+        let mut qb = QueryBuilder::<Sqlite>::new(
+            "select number, linked_number, kind from issue_links where repository_full_name = ",
+        );
+        qb.push_bind(repository_full_name);
+        if let Some(numbers) = numbers {
+            if numbers.is_empty() {
+                return Ok(Vec::new());
+            }
+            for column in ["number", "linked_number"] {
+                qb.push(format!(" and {column} in ("));
+                let mut separated = qb.separated(", ");
+                for number in numbers {
+                    separated.push_bind(*number);
+                }
+                separated.push_unseparated(")");
+            }
+        }
+        let rows = qb.build().fetch_all(&self.pool).await?;
+        rows.iter()
+            .map(|row| {
+                Ok(IssueLink {
+                    number: row.try_get("number")?,
+                    linked_number: row.try_get("linked_number")?,
+                    kind: row.try_get::<&str, _>("kind")?.parse()?,
+                })
+            })
+            .collect()
+    }
+
+    async fn issue_cohorts(
+        &self,
+        repository_full_name: &str,
+        numbers: &[i32],
+    ) -> Result<Vec<IssueCohort>, StorageError> {
+        if numbers.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut qb = QueryBuilder::<Sqlite>::new(
+            "select number, package_version, accelerator from issues where repository_full_name = ",
+        );
+        qb.push_bind(repository_full_name);
+        qb.push(" and number in (");
+        let mut separated = qb.separated(", ");
+        for number in numbers {
+            separated.push_bind(*number);
+        }
+        separated.push_unseparated(")");
+        let cohorts = qb.build_query_as().fetch_all(&self.pool).await?;
+        Ok(cohorts)
+    }
+
+    async fn get_job(
+        &self,
+        job_type: JobType,
+        repository_full_name: Option<&str>,
+    ) -> Result<Option<JobData>, StorageError> {
+        let data: Option<String> = match repository_full_name {
+            Some(repository_full_name) => {
+                sqlx::query_scalar(
+                    "select data from jobs where repository_full_name = ? and job_type = ?",
+                )
+                .bind(repository_full_name)
+                .bind(job_type.as_str())
+                .fetch_optional(&self.pool)
+                .await?
+            }
+            None => {
+                sqlx::query_scalar("select data from jobs where job_type = ?")
+                    .bind(job_type.as_str())
+                    .fetch_optional(&self.pool)
+                    .await?
+            }
+        };
+        Ok(data.map(|d| serde_json::from_str(&d)).transpose()?)
+    }
+
+    async fn save_job(
+        &self,
+        job_type: JobType,
+        repository_full_name: Option<&str>,
+        data: &JobData,
+    ) -> Result<(), StorageError> {
+        let data = serde_json::to_string(data)?;
+        match repository_full_name {
+            Some(repository_full_name) => {
+                sqlx::query(
+                    r#"insert into jobs (data, job_type, repository_full_name)
+                       values (?, ?, ?)
+                       on conflict (repository_full_name)
+                       do update
+                       set
+                           data = excluded.data,
+                           updated_at = CURRENT_TIMESTAMP"#,
+                )
+                .bind(data)
+                .bind(job_type.as_str())
+                .bind(repository_full_name)
+                .execute(&self.pool)
+                .await?;
+            }
+            None => {
+                sqlx::query(
+                    r#"insert into jobs (data, job_type)
+                       values (?, ?)
+                       on conflict (job_type)
+                           where job_type = 'embeddings_regeneration'
+                       do update
+                       set
+                           data = excluded.data,
+                           updated_at = CURRENT_TIMESTAMP"#,
+                )
+                .bind(data)
+                .bind(job_type.as_str())
+                .execute(&self.pool)
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn delete_job(
+        &self,
+        job_type: JobType,
+        repository_full_name: Option<&str>,
+    ) -> Result<(), StorageError> {
+        match repository_full_name {
+            Some(repository_full_name) => {
+                sqlx::query("delete from jobs where repository_full_name = ?")
+                    .bind(repository_full_name)
+                    .execute(&self.pool)
+                    .await?;
+            }
+            None => {
+                sqlx::query("delete from jobs where job_type = ?")
+                    .bind(job_type.as_str())
+                    .execute(&self.pool)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn jobs(&self) -> Result<Vec<JobState>, StorageError> {
+        let rows = sqlx::query(
+            "select id, job_type, repository_full_name, data, paused from jobs order by id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(job_state_from_row).collect()
+    }
+
+    async fn set_job_paused(
+        &self,
+        id: i32,
+        paused: bool,
+    ) -> Result<Option<JobState>, StorageError> {
+        let row = sqlx::query(
+            r#"update jobs set paused = ? where id = ?
+               returning id, job_type, repository_full_name, data, paused"#,
+        )
+        .bind(paused)
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+        row.as_ref().map(job_state_from_row).transpose()
+    }
+
+    async fn is_job_paused(
+        &self,
+        job_type: JobType,
+        repository_full_name: Option<&str>,
+    ) -> Result<bool, StorageError> {
+        let paused: Option<bool> = match repository_full_name {
+            Some(repository_full_name) => {
+                sqlx::query_scalar(
+                    "select paused from jobs where repository_full_name = ? and job_type = ?",
+                )
+                .bind(repository_full_name)
+                .bind(job_type.as_str())
+                .fetch_optional(&self.pool)
+                .await?
+            }
+            None => {
+                sqlx::query_scalar("select paused from jobs where job_type = ?")
+                    .bind(job_type.as_str())
+                    .fetch_optional(&self.pool)
+                    .await?
+            }
+        };
+        Ok(paused.unwrap_or_default())
+    }
+
+    async fn try_acquire_lock(
+        &self,
+        name: &str,
+        holder: &str,
+        lease: Duration,
+    ) -> Result<bool, StorageError> {
+        let res = sqlx::query(
+            r#"insert into locks (name, holder, expires_at)
+               values (?, ?, unixepoch() + ?)
+               on conflict (name)
+               do update
+               set
+                   holder = excluded.holder,
+                   expires_at = excluded.expires_at
+               where locks.expires_at < unixepoch() or locks.holder = excluded.holder"#,
+        )
+        .bind(name)
+        .bind(holder)
+        .bind(lease.as_secs() as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() == 1)
+    }
+
+    async fn renew_lock(
+        &self,
+        name: &str,
+        holder: &str,
+        lease: Duration,
+    ) -> Result<bool, StorageError> {
+        let res = sqlx::query(
+            "update locks set expires_at = unixepoch() + ? where name = ? and holder = ?",
+        )
+        .bind(lease.as_secs() as i64)
+        .bind(name)
+        .bind(holder)
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() == 1)
+    }
+
+    async fn release_lock(&self, name: &str, holder: &str) -> Result<(), StorageError> {
+        sqlx::query("delete from locks where name = ? and holder = ?")
+            .bind(name)
+            .bind(holder)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn lock_held(&self, name: &str) -> Result<bool, StorageError> {
+        let held: bool = sqlx::query_scalar(
+            "select exists(select 1 from locks where name = ? and expires_at >= unixepoch())",
+        )
+        .bind(name)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(held)
+    }
+
+    async fn insert_closure_proposal(
+        &self,
+        issue_source_id: i64,
+        issue_url: &str,
+        duplicate_of_html_url: &str,
+        comment_url: &str,
+    ) -> Result<(), StorageError> {
+        sqlx::query(
+            r#"insert into closure_proposals (issue_source_id, issue_url, duplicate_of_html_url, comment_url)
+               values (?, ?, ?, ?)"#,
+        )
+        .bind(issue_source_id)
+        .bind(issue_url)
+        .bind(duplicate_of_html_url)
+        .bind(comment_url)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_feedback(
+        &self,
+        run_id: &str,
+        vote: Vote,
+    ) -> Result<Option<String>, StorageError> {
+        let prompt_profile = sqlx::query_scalar(
+            r#"insert into feedback (run_id, vote, prompt_profile)
+               values (?1, ?2, (select prompt_profile from comment_runs where run_id = ?1))
+               returning prompt_profile"#,
+        )
+        .bind(run_id)
+        .bind(vote.to_string())
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(prompt_profile)
+    }
+
+    async fn insert_comment_run(
+        &self,
+        run_id: &str,
+        issue_url: &str,
+        prompt_profile: Option<&str>,
+    ) -> Result<(), StorageError> {
+        sqlx::query(
+            "insert or ignore into comment_runs (run_id, issue_url, prompt_profile) values (?, ?, ?)",
+        )
+        .bind(run_id)
+        .bind(issue_url)
+        .bind(prompt_profile)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn comment_run_exists(&self, run_id: &str) -> Result<bool, StorageError> {
+        let exists =
+            sqlx::query_scalar("select exists(select 1 from comment_runs where run_id = ?)")
+                .bind(run_id)
+                .fetch_one(&self.pool)
+                .await?;
+        Ok(exists)
+    }
+
+    async fn setting(&self, key: &str) -> Result<Option<String>, StorageError> {
+        let value = sqlx::query_scalar("select value from settings where key = ?")
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(value)
+    }
+
+    async fn set_setting(&self, key: &str, value: &str) -> Result<(), StorageError> {
+        sqlx::query(
+            r#"insert into settings (key, value)
+               values (?, ?)
+               on conflict (key)
+               do update
+               set
+                   value = EXCLUDED.value,
+                   updated_at = CURRENT_TIMESTAMP"#,
+        )
+        .bind(key)
+        .bind(value)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn upsert_repository_metadata(
+        &self,
+        metadata: &RepositoryMetadata,
+    ) -> Result<(), StorageError> {
+        sqlx::query(
+            r#"insert into repository_metadata (full_name, description, stars, topics)
+               values (?, ?, ?, ?)
+               on conflict (full_name)
+               do update
+               set
+                   description = EXCLUDED.description,
+                   stars = EXCLUDED.stars,
+                   topics = EXCLUDED.topics,
+                   updated_at = CURRENT_TIMESTAMP"#,
+        )
+        .bind(&metadata.full_name)
+        .bind(&metadata.description)
+        .bind(metadata.stars)
+        .bind(serde_json::to_string(&metadata.topics)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn repository_metadata(
+        &self,
+        full_names: Option<&[String]>,
+    ) -> Result<Vec<RepositoryMetadata>, StorageError> {
+        let mut qb = QueryBuilder::<Sqlite>::new(
+            "select full_name, description, stars, topics from repository_metadata",
+        );
+        if let Some(full_names) = full_names {
+            if full_names.is_empty() {
+                return Ok(Vec::new());
+            }
+            qb.push(" where full_name in (");
+            let mut separated = qb.separated(", ");
+            for full_name in full_names {
+                separated.push_bind(full_name);
+            }
+            separated.push_unseparated(")");
+        }
+        let rows = qb.build().fetch_all(&self.pool).await?;
+        rows.iter()
+            .map(|row| {
+                Ok(RepositoryMetadata {
+                    full_name: row.try_get("full_name")?,
+                    description: row.try_get("description")?,
+                    stars: row.try_get("stars")?,
+                    topics: serde_json::from_str(row.try_get("topics")?)?,
+                })
+            })
+            .collect()
+    }
+
+    async fn insert_knowledge_base_entry(
+        &self,
+        repository_full_name: Option<&str>,
+        question: &str,
+        answer: &str,
+        embedding: &[f32],
+    ) -> Result<i32, StorageError> {
+        let id = sqlx::query_scalar(
+            r#"insert into knowledge_base (repository_full_name, question, answer, embedding)
+               values (?, ?, ?, ?)
+               returning id"#,
+        )
+        .bind(repository_full_name)
+        .bind(question)
+        .bind(answer)
+        .bind(encode_embedding(embedding))
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(id)
+    }
+
+    async fn knowledge_base_entries(&self) -> Result<Vec<KnowledgeBaseEntry>, StorageError> {
+        let rows = sqlx::query(
+            "select id, repository_full_name, question, answer from knowledge_base order by id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(knowledge_base_entry_from_row).collect()
+    }
+
+    async fn update_knowledge_base_entry(
+        &self,
+        id: i32,
+        repository_full_name: Option<&str>,
+        question: &str,
+        answer: &str,
+        embedding: &[f32],
+    ) -> Result<bool, StorageError> {
+        let res = sqlx::query(
+            r#"update knowledge_base
+               set repository_full_name = ?, question = ?, answer = ?, embedding = ?
+               where id = ?"#,
+        )
+        .bind(repository_full_name)
+        .bind(question)
+        .bind(answer)
+        .bind(encode_embedding(embedding))
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn delete_knowledge_base_entry(&self, id: i32) -> Result<bool, StorageError> {
+        let res = sqlx::query("delete from knowledge_base where id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn closest_knowledge_base_entry(
+        &self,
+        embedding: &[f32],
+        repository_full_name: &str,
+    ) -> Result<Option<KnowledgeBaseMatch>, StorageError> {
+        let rows = sqlx::query(
+            r#"select id, repository_full_name, question, answer, embedding from knowledge_base
+               where repository_full_name is null or repository_full_name = ?"#,
+        )
+        .bind(repository_full_name)
+        .fetch_all(&self.pool)
+        .await?;
+        let mut closest: Option<KnowledgeBaseMatch> = None;
+        for row in rows {
+            let entry_embedding: Vec<u8> = row.try_get("embedding")?;
+            let similarity = cosine_similarity(embedding, &decode_embedding(&entry_embedding));
+            if closest
+                .as_ref()
+                .is_none_or(|c| similarity > c.cosine_similarity)
+            {
+                closest = Some(KnowledgeBaseMatch {
+                    entry: knowledge_base_entry_from_row(&row)?,
+                    cosine_similarity: similarity,
+                });
+            }
+        }
+        Ok(closest)
+    }
+
+    async fn replace_guidance_sections(
+        &self,
+        repository_full_name: &str,
+        sections: &[(GuidanceSection, String, Vec<f32>)],
+    ) -> Result<(), StorageError> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("delete from guidance_sections where repository_full_name = ?")
+            .bind(repository_full_name)
+            .execute(&mut *tx)
+            .await?;
+        if !sections.is_empty() {
+            let mut qb = QueryBuilder::new(
+                "insert into guidance_sections (repository_full_name, path, heading, anchor, content, content_hash, embedding)",
+            );
+            qb.push_values(sections, |mut b, (section, content_hash, embedding)| {
+                b.push_bind(repository_full_name)
+                    .push_bind(&section.path)
+                    .push_bind(&section.heading)
+                    .push_bind(&section.anchor)
+                    .push_bind(&section.content)
+                    .push_bind(content_hash)
+                    .push_bind(encode_embedding(embedding));
+            });
+            qb.build().execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn guidance_embeddings(
+        &self,
+        repository_full_name: &str,
+    ) -> Result<HashMap<String, Vec<f32>>, StorageError> {
+        let rows: Vec<(String, Vec<u8>)> = sqlx::query_as(
+            r#"select content_hash, embedding from guidance_sections
+               where repository_full_name = ? and content_hash is not null"#,
+        )
+        .bind(repository_full_name)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|(content_hash, embedding)| (content_hash, decode_embedding(&embedding)))
+            .collect())
+    }
+
+    async fn closest_guidance_section(
+        &self,
+        embedding: &[f32],
+        repository_full_name: &str,
+    ) -> Result<Option<GuidanceMatch>, StorageError> {
+        let rows = sqlx::query(
+            r#"select path, heading, anchor, content, embedding from guidance_sections
+               where repository_full_name = ?"#,
+        )
+        .bind(repository_full_name)
+        .fetch_all(&self.pool)
+        .await?;
+        let mut closest: Option<GuidanceMatch> = None;
+        for row in rows {
+            let section_embedding: Vec<u8> = row.try_get("embedding")?;
+            let similarity = cosine_similarity(embedding, &decode_embedding(&section_embedding));
+            if closest
+                .as_ref()
+                .is_none_or(|c| similarity > c.cosine_similarity)
+            {
+                closest = Some(GuidanceMatch {
+                    section: GuidanceSection {
+                        repository_full_name: repository_full_name.to_owned(),
+                        path: row.try_get("path")?,
+                        heading: row.try_get("heading")?,
+                        anchor: row.try_get("anchor")?,
+                        content: row.try_get("content")?,
+                    },
+                    cosine_similarity: similarity,
+                });
+            }
+        }
+        Ok(closest)
+    }
+
+    async fn insert_api_key(
+        &self,
+        name: &str,
+        key_hash: &str,
+        scopes: &str,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<i32, StorageError> {
+        let id = sqlx::query_scalar(
+            r#"insert into api_keys (name, key_hash, scopes, expires_at)
+               values (?, ?, ?, ?)
+               returning id"#,
+        )
+        .bind(name)
+        .bind(key_hash)
+        .bind(scopes)
+        .bind(expires_at)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(id)
+    }
+
+    async fn api_key(&self, key_hash: &str) -> Result<Option<ApiKey>, StorageError> {
+        let row = sqlx::query(
+            "select id, name, scopes, expires_at, created_at from api_keys where key_hash = ?",
+        )
+        .bind(key_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+        row.map(|row| api_key_from_row(&row)).transpose()
+    }
+
+    async fn api_keys(&self) -> Result<Vec<ApiKey>, StorageError> {
+        let rows = sqlx::query(
+            "select id, name, scopes, expires_at, created_at from api_keys order by id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(api_key_from_row).collect()
+    }
+
+    async fn delete_api_key(&self, id: i32) -> Result<bool, StorageError> {
+        let res = sqlx::query("delete from api_keys where id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn enqueue_comment(
+        &self,
+        source: &Source,
+        repository_full_name: &str,
+        issue_url: &str,
+        body: &str,
+        awaiting_approval: bool,
+        suggestions: bool,
+    ) -> Result<(), StorageError> {
+        sqlx::query(
+            r#"insert into pending_comments (source, repository_full_name, issue_url, body, awaiting_approval, suggestions)
+               values (?, ?, ?, ?, ?, ?)"#,
+        )
+        .bind(source.to_string())
+        .bind(repository_full_name)
+        .bind(issue_url)
+        .bind(body)
+        .bind(awaiting_approval)
+        .bind(suggestions)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn pending_comments(&self, limit: i64) -> Result<Vec<PendingComment>, StorageError> {
+        let rows = sqlx::query(
+            r#"select id, source, repository_full_name, issue_url, body, suggestions, attempts,
+                      created_at
+               from pending_comments
+               where not awaiting_approval and next_attempt_at <= CURRENT_TIMESTAMP
+               order by id limit ?"#,
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(pending_comment_from_row).collect()
+    }
+
+    async fn reschedule_pending_comment(
+        &self,
+        id: i32,
+        delay_secs: i64,
+        error: &str,
+    ) -> Result<(), StorageError> {
+        sqlx::query(
+            r#"update pending_comments
+               set attempts = attempts + 1, last_error = ?, next_attempt_at = datetime('now', ?)
+               where id = ?"#,
+        )
+        .bind(error)
+        .bind(format!("+{delay_secs} seconds"))
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn unannounced_comment_drafts(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<PendingComment>, StorageError> {
+        let rows = sqlx::query(
+            r#"select id, source, repository_full_name, issue_url, body, suggestions, attempts,
+                      created_at
+               from pending_comments where awaiting_approval and slack_ts is null
+               order by id limit ?"#,
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(pending_comment_from_row).collect()
+    }
+
+    async fn set_comment_draft_announced(
+        &self,
+        id: i32,
+        slack_ts: &str,
+    ) -> Result<(), StorageError> {
+        sqlx::query("update pending_comments set slack_ts = ? where id = ?")
+            .bind(slack_ts)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn approve_comment_draft(&self, id: i32) -> Result<bool, StorageError> {
+        let res = sqlx::query(
+            "update pending_comments set awaiting_approval = false where id = ? and awaiting_approval",
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn discard_comment_draft(&self, id: i32) -> Result<bool, StorageError> {
+        let res = sqlx::query("delete from pending_comments where id = ? and awaiting_approval")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn expire_comment_drafts(&self, older_than_hours: i32) -> Result<u64, StorageError> {
+        let res = sqlx::query(
+            r#"delete from pending_comments
+               where awaiting_approval and created_at < datetime('now', ?)"#,
+        )
+        .bind(format!("-{older_than_hours} hours"))
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected())
+    }
+
+    async fn delete_pending_comment(&self, id: i32) -> Result<(), StorageError> {
+        sqlx::query("delete from pending_comments where id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn enqueue_slack_message(
+        &self,
+        repository_full_name: &str,
+        payload: &serde_json::Value,
+        error: &str,
+    ) -> Result<(), StorageError> {
+        sqlx::query(
+            "insert into slack_outbox (repository_full_name, payload, last_error) values (?, ?, ?)",
+        )
+        .bind(repository_full_name)
+        .bind(serde_json::to_string(payload)?)
+        .bind(error)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn due_slack_messages(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<SlackOutboxMessage>, StorageError> {
+        let rows = sqlx::query(
+            r#"select id, repository_full_name, payload, attempts
+               from slack_outbox where next_attempt_at <= CURRENT_TIMESTAMP
+               order by id limit ?"#,
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(SlackOutboxMessage {
+                    id: row.try_get("id")?,
+                    repository_full_name: row.try_get("repository_full_name")?,
+                    payload: serde_json::from_str(row.try_get("payload")?)?,
+                    attempts: row.try_get("attempts")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn reschedule_slack_message(
+        &self,
+        id: i32,
+        delay_secs: i64,
+        error: &str,
+    ) -> Result<(), StorageError> {
+        sqlx::query(
+            r#"update slack_outbox
+               set attempts = attempts + 1, last_error = ?, next_attempt_at = datetime('now', ?)
+               where id = ?"#,
+        )
+        .bind(error)
+        .bind(format!("+{delay_secs} seconds"))
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_slack_message(&self, id: i32) -> Result<(), StorageError> {
+        sqlx::query("delete from slack_outbox where id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn expire_slack_messages(&self, older_than_minutes: i32) -> Result<u64, StorageError> {
+        let res = sqlx::query("delete from slack_outbox where created_at < datetime('now', ?)")
+            .bind(format!("-{older_than_minutes} minutes"))
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected())
+    }
+
+    async fn add_to_slack_batch(
+        &self,
+        repository_full_name: &str,
+        payload: &serde_json::Value,
+    ) -> Result<(), StorageError> {
+        sqlx::query("insert into slack_batches (repository_full_name, payload) values (?, ?)")
+            .bind(repository_full_name)
+            .bind(serde_json::to_string(payload)?)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn take_slack_batch(
+        &self,
+        repository_full_name: &str,
+    ) -> Result<Vec<serde_json::Value>, StorageError> {
+        let rows = sqlx::query(
+            "delete from slack_batches where repository_full_name = ? returning id, payload",
+        )
+        .bind(repository_full_name)
+        .fetch_all(&self.pool)
+        .await?;
+        let mut payloads = rows
+            .iter()
+            .map(|row| {
+                Ok((
+                    row.try_get::<i64, _>("id")?,
+                    serde_json::from_str(row.try_get("payload")?)?,
+                ))
+            })
+            .collect::<Result<Vec<_>, StorageError>>()?;
+        payloads.sort_by_key(|(id, _)| *id);
+        Ok(payloads.into_iter().map(|(_, payload)| payload).collect())
+    }
+
+    async fn slack_batch_repositories(&self) -> Result<Vec<String>, StorageError> {
+        let repositories =
+            sqlx::query_scalar("select distinct repository_full_name from slack_batches")
+                .fetch_all(&self.pool)
+                .await?;
+        Ok(repositories)
+    }
+
+    async fn cached_response(&self, url: &str) -> Result<Option<CachedResponse>, StorageError> {
+        let row = sqlx::query("select etag, link, body from github_response_cache where url = ?")
+            .bind(url)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.map(|row| {
+            Ok(CachedResponse {
+                etag: row.try_get("etag")?,
+                link: row.try_get("link")?,
+                body: row.try_get("body")?,
+            })
+        })
+        .transpose()
+    }
+
+    async fn cache_response(
+        &self,
+        url: &str,
+        response: &CachedResponse,
+    ) -> Result<(), StorageError> {
+        sqlx::query(
+            r#"insert into github_response_cache (url, etag, link, body)
+               values (?, ?, ?, ?)
+               on conflict (url)
+               do update set etag = excluded.etag, link = excluded.link, body = excluded.body,
+                             cached_at = CURRENT_TIMESTAMP"#,
+        )
+        .bind(url)
+        .bind(&response.etag)
+        .bind(&response.link)
+        .bind(&response.body)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn refresh_cached_response(&self, url: &str) -> Result<(), StorageError> {
+        sqlx::query("update github_response_cache set cached_at = CURRENT_TIMESTAMP where url = ?")
+            .bind(url)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn purge_cached_responses(&self, older_than_days: i32) -> Result<u64, StorageError> {
+        let res =
+            sqlx::query("delete from github_response_cache where cached_at < datetime('now', ?)")
+                .bind(format!("-{older_than_days} days"))
+                .execute(&self.pool)
+                .await?;
+        Ok(res.rows_affected())
+    }
+
+    async fn notify(&self, _channel: &str, _payload: &str) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    async fn comment_backlog(&self) -> Result<CommentBacklog, StorageError> {
+        let backlog = sqlx::query_as(
+            r#"select coalesce(sum(not awaiting_approval), 0) as queued,
+                      coalesce(sum(awaiting_approval), 0) as awaiting_approval,
+                      (julianday('now') - julianday(min(case when not awaiting_approval then created_at end)))
+                        * 86400.0 as oldest_queued_secs
+               from pending_comments"#,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(backlog)
+    }
+
+    async fn job_backlog(&self) -> Result<Vec<JobBacklog>, StorageError> {
+        let backlog = sqlx::query_as(
+            r#"select job_type, count(*) as jobs,
+                      (julianday('now') - julianday(min(updated_at))) * 86400.0 as stalled_secs
+               from jobs group by job_type"#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(backlog)
+    }
+
+    async fn slack_outbox_backlog(&self) -> Result<SlackOutboxBacklog, StorageError> {
+        let backlog = sqlx::query_as(
+            r#"select count(*) as messages,
+                      (julianday('now') - julianday(min(created_at))) * 86400.0 as oldest_secs
+               from slack_outbox"#,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(backlog)
+    }
+
+    async fn event_stats(&self, since_minutes: i32) -> Result<Vec<EventStats>, StorageError> {
+        let stats = sqlx::query_as(
+            r#"select event_type, outcome, count(*) as events, avg(total_ms) as avg_total_ms
+               from event_log
+               where created_at > datetime('now', ?)
+               group by event_type, outcome"#,
+        )
+        .bind(format!("-{since_minutes} minutes"))
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(stats)
+    }
+
+    async fn comment_idempotency_key(
+        &self,
+        issue_url: &str,
+        fingerprint: &str,
+    ) -> Result<Option<bool>, StorageError> {
+        let posted = sqlx::query_scalar(
+            "select posted from comment_idempotency_keys where issue_url = ? and fingerprint = ?",
+        )
+        .bind(issue_url)
+        .bind(fingerprint)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(posted)
+    }
+
+    async fn save_comment_idempotency_key(
+        &self,
+        issue_url: &str,
+        fingerprint: &str,
+        posted: bool,
+    ) -> Result<(), StorageError> {
+        sqlx::query(
+            r#"insert into comment_idempotency_keys (issue_url, fingerprint, posted)
+               values (?, ?, ?)
+               on conflict (issue_url, fingerprint)
+               do update set posted = excluded.posted, updated_at = CURRENT_TIMESTAMP"#,
+        )
+        .bind(issue_url)
+        .bind(fingerprint)
+        .bind(posted)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn bot_comment(&self, issue_url: &str) -> Result<Option<String>, StorageError> {
+        let comment_url =
+            sqlx::query_scalar("select comment_url from bot_comments where issue_url = ?")
+                .bind(issue_url)
+                .fetch_optional(&self.pool)
+                .await?;
+        Ok(comment_url)
+    }
+
+    async fn save_bot_comment(
+        &self,
+        issue_url: &str,
+        comment_url: &str,
+        body: &str,
+    ) -> Result<(), StorageError> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            r#"insert into bot_comments (issue_url, comment_url)
+               values (?, ?)
+               on conflict (issue_url)
+               do update set comment_url = excluded.comment_url, updated_at = CURRENT_TIMESTAMP"#,
+        )
+        .bind(issue_url)
+        .bind(comment_url)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "insert into bot_comment_revisions (issue_url, comment_url, body) values (?, ?, ?)",
+        )
+        .bind(issue_url)
+        .bind(comment_url)
+        .bind(body)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn bot_comment_revisions(
+        &self,
+        issue_url: &str,
+    ) -> Result<Vec<BotCommentRevision>, StorageError> {
+        let revisions = sqlx::query_as(
+            "select comment_url, body, created_at from bot_comment_revisions where issue_url = ? order by id",
+        )
+        .bind(issue_url)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(revisions)
+    }
+
+    async fn record_suggestion_targets(
+        &self,
+        issue: &IssueData,
+        targets: &[&ClosestIssue],
+    ) -> Result<(), StorageError> {
+        if targets.is_empty() {
+            return Ok(());
+        }
+        let mut qb = QueryBuilder::new(
+            "insert into suggestion_targets (repository_full_name, number, issue_repository_full_name, issue_number, issue_html_url, issue_title)",
+        );
+        qb.push_values(targets, |mut b, target| {
+            b.push_bind(&target.repository_full_name)
+                .push_bind(target.number)
+                .push_bind(&issue.repository_full_name)
+                .push_bind(issue.number)
+                .push_bind(&issue.html_url)
+                .push_bind(&issue.title);
+        });
+        qb.build().execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn suggestion_cluster(
+        &self,
+        repository_full_name: &str,
+        number: i32,
+        window_minutes: i32,
+    ) -> Result<Vec<SuggestedIssue>, StorageError> {
+        let issues = sqlx::query_as(
+            r#"select issue_repository_full_name as repository_full_name, issue_number as number,
+                      max(issue_html_url) as html_url, max(issue_title) as title
+               from suggestion_targets
+               where repository_full_name = ? and number = ? and created_at > datetime('now', ?)
+               group by issue_repository_full_name, issue_number
+               order by issue_repository_full_name, issue_number"#,
+        )
+        .bind(repository_full_name)
+        .bind(number)
+        .bind(format!("-{window_minutes} minutes"))
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(issues)
+    }
+
+    async fn prune_suggestion_targets(&self, older_than_minutes: i32) -> Result<(), StorageError> {
+        sqlx::query("delete from suggestion_targets where created_at < datetime('now', ?)")
+            .bind(format!("-{older_than_minutes} minutes"))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn claim_escalation(
+        &self,
+        repository_full_name: &str,
+        number: i32,
+        cooldown_minutes: i32,
+    ) -> Result<bool, StorageError> {
+        let claimed: Option<i32> = sqlx::query_scalar(
+            r#"insert into escalations (repository_full_name, number)
+               values (?, ?)
+               on conflict (repository_full_name, number)
+               do update set escalated_at = CURRENT_TIMESTAMP
+               where escalations.escalated_at < datetime('now', ?)
+               returning number"#,
+        )
+        .bind(repository_full_name)
+        .bind(number)
+        .bind(format!("-{cooldown_minutes} minutes"))
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(claimed.is_some())
+    }
+
+    async fn record_suggestions(
+        &self,
+        issue_source_id: i64,
+        suggestions: &[ClosestIssue],
+        run_id: &str,
+        prompt_profile: Option<&str>,
+    ) -> Result<(), StorageError> {
+        if suggestions.is_empty() {
+            return Ok(());
+        }
+        let mut qb = QueryBuilder::new(
+            "insert into suggestions (issue_source_id, repository_full_name, number, rank, cosine_similarity, run_id, prompt_profile)",
+        );
+        qb.push_values(
+            suggestions.iter().enumerate(),
+            |mut b, (rank, suggestion)| {
+                b.push_bind(issue_source_id)
+                    .push_bind(&suggestion.repository_full_name)
+                    .push_bind(suggestion.number)
+                    .push_bind(rank as i32)
+                    .push_bind(suggestion.cosine_similarity)
+                    .push_bind(run_id)
+                    .push_bind(prompt_profile);
+            },
+        );
+        qb.push(" on conflict (issue_source_id, repository_full_name, number) do nothing");
+        qb.build().execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn suggestions(&self, issue_source_id: i64) -> Result<Vec<Suggestion>, StorageError> {
+        let suggestions = sqlx::query_as(
+            r#"select repository_full_name, number, rank
+               from suggestions
+               where issue_source_id = ?
+               order by rank"#,
+        )
+        .bind(issue_source_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(suggestions)
+    }
+
+    async fn upsert_duplicate_resolution(
+        &self,
+        resolution: &DuplicateResolution,
+    ) -> Result<(), StorageError> {
+        sqlx::query(
+            r#"insert into duplicate_resolutions (issue_source_id, repository_full_name, number, duplicate_of_number, had_suggestions, suggested_rank)
+               values (?, ?, ?, ?, ?, ?)
+               on conflict (issue_source_id)
+               do update set duplicate_of_number = excluded.duplicate_of_number,
+                             had_suggestions = excluded.had_suggestions,
+                             suggested_rank = excluded.suggested_rank,
+                             resolved_at = CURRENT_TIMESTAMP"#,
+        )
+        .bind(resolution.issue_source_id)
+        .bind(&resolution.repository_full_name)
+        .bind(resolution.number)
+        .bind(resolution.duplicate_of_number)
+        .bind(resolution.had_suggestions)
+        .bind(resolution.suggested_rank)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn linked_issue_similarities(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<LinkSimilarity>, StorageError> {
+        let rows = sqlx::query(
+            r#"select l.repository_full_name, l.number, l.linked_number,
+                      a.embedding, b.embedding as linked_embedding
+               from (select repository_full_name, number, linked_number, max(created_at) as created_at
+                     from issue_links
+                     group by repository_full_name, number, linked_number) l
+               join issues a on a.repository_full_name = l.repository_full_name and a.number = l.number
+               join issues b on b.repository_full_name = l.repository_full_name and b.number = l.linked_number
+               order by l.created_at desc, l.repository_full_name, l.number, l.linked_number
+               limit ?"#,
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.into_iter()
+            .map(|row| {
+                Ok(LinkSimilarity {
+                    repository_full_name: row.try_get("repository_full_name")?,
+                    number: row.try_get("number")?,
+                    linked_number: row.try_get("linked_number")?,
+                    cosine_similarity: cosine_similarity(
+                        &decode_embedding(row.try_get("embedding")?),
+                        &decode_embedding(row.try_get("linked_embedding")?),
+                    ),
+                })
+            })
+            .collect()
+    }
+
+    async fn record_link_similarities(
+        &self,
+        model: &str,
+        similarities: &[LinkSimilarity],
+    ) -> Result<(), StorageError> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("delete from link_similarities where model = ?")
+            .bind(model)
+            .execute(&mut *tx)
+            .await?;
+        if !similarities.is_empty() {
+            let mut qb = QueryBuilder::new(
+                "insert into link_similarities (model, repository_full_name, number, linked_number, cosine_similarity)",
+            );
+            qb.push_values(similarities, |mut b, similarity| {
+                b.push_bind(model)
+                    .push_bind(&similarity.repository_full_name)
+                    .push_bind(similarity.number)
+                    .push_bind(similarity.linked_number)
+                    .push_bind(similarity.cosine_similarity);
+            });
+            qb.build().execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn link_similarities(&self, model: &str) -> Result<Vec<LinkSimilarity>, StorageError> {
+        let similarities = sqlx::query_as(
+            r#"select repository_full_name, number, linked_number, cosine_similarity
+               from link_similarities
+               where model = ?
+               order by repository_full_name, number, linked_number"#,
+        )
+        .bind(model)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(similarities)
+    }
+
+    async fn repository_cursors(&self) -> Result<Vec<RepositoryCursor>, StorageError> {
+        let cursors = sqlx::query_as(
+            "select repository_full_name, processed_at from repository_cursors order by repository_full_name",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(cursors)
+    }
+
+    async fn advance_repository_cursor(
+        &self,
+        repository_full_name: &str,
+        processed_at: DateTime<Utc>,
+    ) -> Result<(), StorageError> {
+        sqlx::query(
+            r#"insert into repository_cursors (repository_full_name, processed_at)
+               values (?, ?)
+               on conflict (repository_full_name)
+               do update
+               set processed_at = max(repository_cursors.processed_at, EXCLUDED.processed_at)"#,
+        )
+        .bind(repository_full_name)
+        .bind(processed_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn suppress_issue(
+        &self,
+        source_id: i64,
+        suppressed_by: &str,
+    ) -> Result<bool, StorageError> {
+        let inserted: Option<i64> = sqlx::query_scalar(
+            r#"insert into suppressed_issues (source_id, suppressed_by)
+               values (?, ?)
+               on conflict (source_id) do nothing
+               returning source_id"#,
+        )
+        .bind(source_id)
+        .bind(suppressed_by)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(inserted.is_some())
+    }
+
+    async fn unsuppress_issue(&self, source_id: i64) -> Result<bool, StorageError> {
+        let res = sqlx::query("delete from suppressed_issues where source_id = ?")
+            .bind(source_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn is_issue_url_suppressed(&self, issue_url: &str) -> Result<bool, StorageError> {
+        let suppressed: bool = sqlx::query_scalar(
+            r#"select exists(
+                 select 1 from suppressed_issues s
+                 join issues i on i.source_id = s.source_id
+                 where i.url = ?
+               )"#,
+        )
+        .bind(issue_url)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(suppressed)
+    }
+
+    async fn is_issue_suppressed(&self, source_id: i64) -> Result<bool, StorageError> {
+        let suppressed: bool = sqlx::query_scalar(
+            "select exists(select 1 from suppressed_issues where source_id = ?)",
+        )
+        .bind(source_id)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(suppressed)
+    }
+
+    async fn is_issue_number_suppressed(
+        &self,
+        repository_full_name: &str,
+        number: i32,
+    ) -> Result<bool, StorageError> {
+        let suppressed: bool = sqlx::query_scalar(
+            r#"select exists(
+                 select 1 from suppressed_issues s
+                 join issues i on i.source_id = s.source_id
+                 where i.repository_full_name = ? and i.number = ?
+               )"#,
+        )
+        .bind(repository_full_name)
+        .bind(number)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(suppressed)
+    }
+
+    async fn opt_out_author(
+        &self,
+        login: &str,
+        requested_through: &str,
+        purge: bool,
+    ) -> Result<OptOutRequest, StorageError> {
+        let mut tx = self.pool.begin().await?;
+        let (issues, comments) = if purge {
+            purge_author(&mut tx, login).await?
+        } else {
+            let issues = sqlx::query(
+                r#"update issues set opted_out = true, updated_at = CURRENT_TIMESTAMP
+                   where author = ? and not opted_out"#,
+            )
+            .bind(login)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+            let comments = sqlx::query(
+                "update comments set opted_out = true where author = ? and not opted_out",
+            )
+            .bind(login)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+            (issues, comments)
+        };
+        let row = sqlx::query(
+            r#"insert into opt_out_requests (login, requested_through, purged, issues, comments)
+               values (?, ?, ?, ?, ?)
+               returning id, login, requested_through, purged, issues, comments, requested_at"#,
+        )
+        .bind(login)
+        .bind(requested_through)
+        .bind(purge)
+        .bind(issues as i64)
+        .bind(comments as i64)
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        opt_out_request_from_row(&row)
+    }
+
+    async fn opt_out_requests(&self) -> Result<Vec<OptOutRequest>, StorageError> {
+        let rows = sqlx::query(
+            r#"select id, login, requested_through, purged, issues, comments, requested_at
+               from opt_out_requests order by id desc"#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(opt_out_request_from_row).collect()
+    }
+
+    async fn issues_commented_by(&self, login: &str) -> Result<Vec<i64>, StorageError> {
+        let source_ids = sqlx::query_scalar(
+            r#"select distinct i.source_id from issues i join comments c on c.issue_id = i.id
+               where c.author = ?1 and i.author is not ?1"#,
+        )
+        .bind(login)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(source_ids)
+    }
+
+    async fn record_onboarding(
+        &self,
+        repository_full_name: &str,
+        hook_id: Option<i64>,
+        estimated_issues: i64,
+    ) -> Result<OnboardedRepository, StorageError> {
+        let repository = sqlx::query_as(
+            r#"insert into onboarded_repositories (repository_full_name, hook_id, estimated_issues)
+               values (?, ?, ?)
+               on conflict (repository_full_name)
+               do update set hook_id = excluded.hook_id, estimated_issues = excluded.estimated_issues,
+                             onboarded_at = CURRENT_TIMESTAMP
+               returning repository_full_name, hook_id, estimated_issues, onboarded_at"#,
+        )
+        .bind(repository_full_name)
+        .bind(hook_id)
+        .bind(estimated_issues)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(repository)
+    }
+
+    async fn onboarded_repositories(&self) -> Result<Vec<OnboardedRepository>, StorageError> {
+        let repositories = sqlx::query_as(
+            r#"select repository_full_name, hook_id, estimated_issues, onboarded_at
+               from onboarded_repositories order by onboarded_at desc"#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(repositories)
+    }
+
+    async fn expire_closure_proposals(&self, older_than_hours: i32) -> Result<(), StorageError> {
+        sqlx::query(
+            r#"update closure_proposals
+               set status = ?, updated_at = CURRENT_TIMESTAMP
+               where status = ? and created_at < datetime('now', ?)"#,
+        )
+        .bind(ClosureProposalStatus::Expired.as_str())
+        .bind(ClosureProposalStatus::Pending.as_str())
+        .bind(format!("-{older_than_hours} hours"))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn pending_closure_proposals(&self) -> Result<Vec<ClosureProposal>, StorageError> {
+        let rows = sqlx::query(
+            "select id, issue_source_id, issue_url, comment_url from closure_proposals where status = ?",
+        )
+        .bind(ClosureProposalStatus::Pending.as_str())
+        .fetch_all(&self.pool)
+        .await?;
+        rows.into_iter()
+            .map(|row| -> Result<ClosureProposal, StorageError> {
+                Ok(ClosureProposal {
+                    id: row.try_get("id")?,
+                    issue_source_id: row.try_get("issue_source_id")?,
+                    issue_url: row.try_get("issue_url")?,
+                    comment_url: row.try_get("comment_url")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn set_closure_proposal_status(
+        &self,
+        id: i32,
+        status: ClosureProposalStatus,
+    ) -> Result<(), StorageError> {
+        sqlx::query(
+            r#"update closure_proposals
+               set status = ?, updated_at = CURRENT_TIMESTAMP
+               where id = ?"#,
+        )
+        .bind(status.as_str())
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_event_log_entry(&self, entry: &EventLogEntry) -> Result<(), StorageError> {
+        sqlx::query(
+            r#"insert into event_log
+               (event_type, description, source_id, repository_full_name, issue_number, request_id,
+                outcome, error, embed_ms, search_ms, summarize_ms, comment_ms, total_ms, created_at,
+                run_id)
+               values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+        )
+        .bind(&entry.event_type)
+        .bind(&entry.description)
+        .bind(entry.source_id)
+        .bind(&entry.repository_full_name)
+        .bind(entry.issue_number)
+        .bind(&entry.request_id)
+        .bind(entry.outcome.as_str())
+        .bind(&entry.error)
+        .bind(entry.embed_ms)
+        .bind(entry.search_ms)
+        .bind(entry.summarize_ms)
+        .bind(entry.comment_ms)
+        .bind(entry.total_ms)
+        .bind(entry.created_at)
+        .bind(&entry.run_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn mark_events_commented(&self, issue_url: &str) -> Result<(), StorageError> {
+        sqlx::query(
+            r#"update event_log set outcome = 'commented'
+               where outcome = 'queued'
+                 and run_id in (select run_id from comment_runs where issue_url = ?)"#,
+        )
+        .bind(issue_url)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn prune_event_log(&self, older_than_days: i32) -> Result<u64, StorageError> {
+        let res = sqlx::query("delete from event_log where created_at < datetime('now', ?)")
+            .bind(format!("-{older_than_days} days"))
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected())
+    }
+
+    async fn event_log(
+        &self,
+        filter: &EventLogFilter,
+        limit: i64,
+    ) -> Result<Vec<EventLogEntry>, StorageError> {
+        let mut qb = QueryBuilder::<Sqlite>::new("select * from event_log where 1 = 1");
+        if let Some(repository_full_name) = &filter.repository_full_name {
+            qb.push(" and repository_full_name = ")
+                .push_bind(repository_full_name);
+        }
+        if let Some(issue_number) = filter.issue_number {
+            qb.push(" and issue_number = ").push_bind(issue_number);
+        }
+        if let Some(source_id) = filter.source_id {
+            qb.push(" and source_id = ").push_bind(source_id);
+        }
+        if let Some(request_id) = &filter.request_id {
+            qb.push(" and request_id = ").push_bind(request_id);
+        }
+        qb.push(" order by created_at desc limit ").push_bind(limit);
+        let rows = qb.build().fetch_all(&self.pool).await?;
+        rows.iter().map(event_log_entry_from_row).collect()
+    }
+
+    async fn issues_table_health(&self) -> Result<Option<TableHealth>, StorageError> {
+        Ok(None)
+    }
+
+    async fn vacuum_issues(&self, _timeout: Duration) -> Result<(), StorageError> {
+        // SQLite vacuums whole databases
+        sqlx::query("vacuum").execute(&self.pool).await?;
+        sqlx::query("analyze issues").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn issues_vector_indexes(&self) -> Result<Vec<String>, StorageError> {
+        Ok(Vec::new())
+    }
+
+    async fn reindex_issues_index(
+        &self,
+        name: &str,
+        _timeout: Duration,
+    ) -> Result<(), StorageError> {
+        sqlx::query(&format!("reindex {}", quote_identifier(name)))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn drop_invalid_issues_indexes(&self) -> Result<Vec<String>, StorageError> {
+        Ok(Vec::new())
+    }
+
+    async fn insert_compaction_run(&self, run: &CompactionRun) -> Result<(), StorageError> {
+        sqlx::query(
+            r#"insert into compaction_runs
+                 (action, dead_tuples, index_churn, table_bytes_before, table_bytes_after,
+                  index_bytes_before, index_bytes_after, duration_ms, created_at)
+               values (?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+        )
+        .bind(&run.action)
+        .bind(run.dead_tuples)
+        .bind(run.index_churn)
+        .bind(run.table_bytes_before)
+        .bind(run.table_bytes_after)
+        .bind(run.index_bytes_before)
+        .bind(run.index_bytes_after)
+        .bind(run.duration_ms)
+        .bind(run.created_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn last_compaction_run(
+        &self,
+        action: &str,
+    ) -> Result<Option<CompactionRun>, StorageError> {
+        let run = sqlx::query_as(
+            r#"select action, dead_tuples, index_churn, table_bytes_before, table_bytes_after,
+                      index_bytes_before, index_bytes_after, duration_ms, created_at
+               from compaction_runs
+               where action = ?
+               order by created_at desc
+               limit 1"#,
+        )
+        .bind(action)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(run)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::TryStreamExt;
+
+    use crate::{
+        config::{DatabaseConfig, IssueState, ReadReplicaConfig, VectorSearchConfig},
+        embeddings::{cosine_similarity, EmbeddingMetadata},
+        extraction::SystemInfo,
+        storage::{CachedResponse, GuidanceSection, Storage, StorageError},
+        test_harness::test_db,
+        Action, ClosestIssue, CommentData, IssueData, Source, Vote,
+    };
+
+    use super::{decode_embedding, encode_embedding, SqliteStorage};
+
+    async fn test_storage() -> SqliteStorage {
+        SqliteStorage::connect(&DatabaseConfig {
+            connection_string: "sqlite::memory:".to_owned(),
+            max_connections: 1,
+            read_replica: None,
+            vector_search: VectorSearchConfig::default(),
+        })
+        .await
+        .unwrap()
+    }
+
+    #[test]
+    fn test_embedding_roundtrip_and_similarity() {
+        let embedding = vec![0.5, -1.25, 3.0];
+        assert_eq!(decode_embedding(&encode_embedding(&embedding)), embedding);
+        assert!((cosine_similarity(&embedding, &embedding) - 1.).abs() < 1e-9);
+        assert_eq!(cosine_similarity(&[1., 0.], &[0., 1.]), 0.);
+    }
+
+    #[tokio::test]
+    async fn test_read_replica_unsupported() {
+        let res = SqliteStorage::connect(&DatabaseConfig {
+            connection_string: "sqlite::memory:".to_owned(),
+            max_connections: 1,
+            read_replica: Some(ReadReplicaConfig {
+                acquire_timeout_ms: 500,
+                connection_string: "sqlite::memory:".to_owned(),
+                max_connections: 1,
+            }),
+            vector_search: VectorSearchConfig::default(),
+        })
+        .await;
+        assert!(matches!(res, Err(StorageError::UnsupportedReadReplica)));
+    }
+
+    #[tokio::test]
+    async fn test_streamed_issues() {
+        let db = test_db().await;
+        for number in 1..=5 {
+            let issue = IssueData {
+                source_id: number.into(),
+                action: Action::Created,
+                author: None,
+                labels: vec!["bug".to_owned()],
+                milestone: None,
+                title: format!("issue {number}"),
+                body: String::new(),
+                is_pull_request: false,
+                number,
+                html_url: format!("https://github.com/huggingface/lor-e/issues/{number}"),
+                url: format!("https://api.github.com/repos/huggingface/lor-e/issues/{number}"),
+                repository_full_name: "huggingface/lor-e".to_owned(),
+                source: Source::Github,
+            };
+            db.insert_issue(&issue, &[1., 0.], None).await.unwrap();
+        }
+        db.exclude_issues(&[2]).await.unwrap();
+
+        let after_first: Vec<i64> = db
+            .issues_after(1, 2)
+            .map_ok(|issue| issue.source_id)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(after_first, vec![2, 3, 4, 5]);
+        assert_eq!(db.count_issues_after(1).await.unwrap(), 4);
+
+        let exported: Vec<i32> = db
+            .export_issues(0, 10)
+            .await
+            .unwrap()
+            .iter()
+            .map(|issue| issue.number)
+            .collect();
+        assert_eq!(exported, vec![1, 3, 4, 5]);
+        let first_page = db.export_issues(0, 2).await.unwrap();
+        let next_page: Vec<i32> = db
+            .export_issues(first_page[1].id, 2)
+            .await
+            .unwrap()
+            .iter()
+            .map(|issue| issue.number)
+            .collect();
+        assert_eq!(next_page, vec![4, 5]);
+    }
+
+    #[tokio::test]
+    async fn test_excluded_labels() {
+        let db = test_db().await;
+        for (number, labels) in [(1, vec!["bug"]), (2, vec!["bug", "wontfix"]), (3, vec![])] {
+            let issue = IssueData {
+                source_id: number.into(),
+                action: Action::Created,
+                author: None,
+                labels: labels.into_iter().map(str::to_owned).collect(),
+                milestone: None,
+                title: format!("issue {number}"),
+                body: String::new(),
+                is_pull_request: false,
+                number,
+                html_url: format!("https://github.com/huggingface/lor-e/issues/{number}"),
+                url: format!("https://api.github.com/repos/huggingface/lor-e/issues/{number}"),
+                repository_full_name: "huggingface/lor-e".to_owned(),
+                source: Source::Github,
+            };
+            db.insert_issue(&issue, &[1., 0.], None).await.unwrap();
+        }
+        let repositories = vec!["huggingface/lor-e".to_owned()];
+
+        let mut numbers: Vec<i32> = db
+            .closest_issues(&[1., 0.], &["wontfix".to_owned()], &repositories, 5, false)
+            .await
+            .unwrap()
+            .iter()
+            .map(|ci| ci.number)
+            .collect();
+        numbers.sort();
+        assert_eq!(numbers, vec![1, 3]);
+        let closest = db
+            .closest_issues(&[1., 0.], &[], &repositories, 5, false)
+            .await
+            .unwrap();
+        assert_eq!(closest.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_lock_leases() {
+        let db = test_db().await;
+        let lease = Duration::from_secs(60);
+
+        assert!(db.try_acquire_lock("job", "a", lease).await.unwrap());
+        assert!(!db.try_acquire_lock("job", "b", lease).await.unwrap());
+        // holders can take their own lock again
+        assert!(db.try_acquire_lock("job", "a", lease).await.unwrap());
+        assert!(db.lock_held("job").await.unwrap());
+        assert!(db.renew_lock("job", "a", lease).await.unwrap());
+        assert!(!db.renew_lock("job", "b", lease).await.unwrap());
+
+        // releasing someone else's lock does nothing
+        db.release_lock("job", "b").await.unwrap();
+        assert!(db.lock_held("job").await.unwrap());
+        db.release_lock("job", "a").await.unwrap();
+        assert!(!db.lock_held("job").await.unwrap());
+        assert!(db.try_acquire_lock("job", "b", lease).await.unwrap());
+
+        // an expired lease is taken over, its former holder failing to renew it
+        assert!(db
+            .try_acquire_lock("job", "b", Duration::ZERO)
+            .await
+            .unwrap());
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert!(!db.lock_held("job").await.unwrap());
+        assert!(db.try_acquire_lock("job", "a", lease).await.unwrap());
+        assert!(!db.renew_lock("job", "b", lease).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_author_opt_out() {
+        let db = test_db().await;
+        let issue = |number: i32, author: &str| IssueData {
+            source_id: number.into(),
+            action: Action::Created,
+            author: Some(author.to_owned()),
+            labels: Vec::new(),
+            milestone: None,
+            title: format!("issue {number}"),
+            body: String::new(),
+            is_pull_request: false,
+            number,
+            html_url: format!("https://github.com/huggingface/lor-e/issues/{number}"),
+            url: format!("https://api.github.com/repos/huggingface/lor-e/issues/{number}"),
+            repository_full_name: "huggingface/lor-e".to_owned(),
+            source: Source::Github,
+        };
+        let exported = || async {
+            db.export_issues(0, 10)
+                .await
+                .unwrap()
+                .iter()
+                .map(|issue| issue.number)
+                .collect::<Vec<i32>>()
+        };
+        for (number, author) in [(1, "octocat"), (2, "octocat"), (3, "hubot")] {
+            db.insert_issue(&issue(number, author), &[1., 0.], None)
+                .await
+                .unwrap();
+        }
+
+        let request = db.opt_out_author("octocat", "api", false).await.unwrap();
+        assert_eq!((request.issues, request.purged), (2, false));
+        assert_eq!(exported().await, vec![3]);
+        // issues opened afterwards are flagged
+        db.insert_issue(&issue(4, "octocat"), &[1., 0.], None)
+            .await
+            .unwrap();
+        assert_eq!(exported().await, vec![3]);
+
+        let request = db.opt_out_author("hubot", "comment", true).await.unwrap();
+        assert_eq!((request.issues, request.purged), (1, true));
+        assert!(db.issue_id(3).await.unwrap().is_none());
+        let logins: Vec<String> = db
+            .opt_out_requests()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|request| request.login)
+            .collect();
+        assert_eq!(logins, vec!["hubot", "octocat"]);
+    }
+
+    #[tokio::test]
+    async fn test_author_purge() {
+        let db = test_storage().await;
+        let issue = |number: i32, author: &str| IssueData {
+            source_id: number.into(),
+            action: Action::Created,
+            author: Some(author.to_owned()),
+            labels: Vec::new(),
+            milestone: None,
+            title: format!("issue {number}"),
+            body: String::new(),
+            is_pull_request: false,
+            number,
+            html_url: format!("https://github.com/huggingface/lor-e/issues/{number}"),
+            url: format!("https://api.github.com/repos/huggingface/lor-e/issues/{number}"),
+            repository_full_name: "huggingface/lor-e".to_owned(),
+            source: Source::Github,
+        };
+        for (number, author) in [(1, "octocat"), (2, "hubot")] {
+            db.insert_issue(&issue(number, author), &[1., 0.], None)
+                .await
+                .unwrap();
+        }
+        let issue_id = db.issue_id(1).await.unwrap().unwrap();
+        let comment = CommentData {
+            source_id: 7,
+            action: Action::Created,
+            issue_id: 1,
+            author: Some("hubot".to_owned()),
+            body: "same here".to_owned(),
+            url: "https://api.github.com/repos/huggingface/lor-e/issues/comments/7".to_owned(),
+            is_review: false,
+        };
+        db.insert_comment(issue_id, &comment).await.unwrap();
+        let archived = db.archived_issue(1).await.unwrap().unwrap();
+        db.archive_issue(&archived).await.unwrap();
+        let archived = db.archived_issue(2).await.unwrap().unwrap();
+        db.archive_issue(&archived).await.unwrap();
+        sqlx::query(
+            "insert into github_response_cache (url, etag, body) values (?, 'etag', x'00')",
+        )
+        .bind("https://api.github.com/repos/huggingface/lor-e/issues/2/comments?per_page=100")
+        .execute(&db.pool)
+        .await
+        .unwrap();
+
+        // octocat's issue is re-embedded without hubot's comment
+        assert_eq!(db.issues_commented_by("hubot").await.unwrap(), vec![1]);
+        let request = db.opt_out_author("hubot", "api", true).await.unwrap();
+        assert_eq!((request.issues, request.comments), (1, 1));
+        assert!(db.issue_text(1).await.unwrap().comments.is_empty());
+        let archived: Vec<String> = sqlx::query_scalar("select data from archived_issues")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(archived.len(), 1);
+        assert!(!archived[0].contains("same here"));
+        let cached: i64 = sqlx::query_scalar("select count(*) from github_response_cache")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(cached, 0);
+    }
+
+    #[tokio::test]
+    async fn test_embedding_metadata() {
+        let db = test_db().await;
+        let issue = IssueData {
+            source_id: 1,
+            action: Action::Created,
+            author: None,
+            labels: Vec::new(),
+            milestone: None,
+            title: "issue 1".to_owned(),
+            body: String::new(),
+            is_pull_request: false,
+            number: 1,
+            html_url: "https://github.com/huggingface/lor-e/issues/1".to_owned(),
+            url: "https://api.github.com/repos/huggingface/lor-e/issues/1".to_owned(),
+            repository_full_name: "huggingface/lor-e".to_owned(),
+            source: Source::Github,
+        };
+        db.insert_issue(&issue, &[1., 0.], None).await.unwrap();
+        assert!(db.embedding_metadata(1).await.unwrap().is_none());
+
+        let mut metadata = EmbeddingMetadata {
+            model: "Qwen/Qwen3-Embedding-0.6B".to_owned(),
+            endpoint: "http://localhost:8080".to_owned(),
+            input_chars: 12,
+            input_tokens: None,
+            truncated: false,
+            template: None,
+        };
+        db.record_embedding_metadata(1, &metadata).await.unwrap();
+        metadata.input_tokens = Some(4);
+        metadata.truncated = true;
+        metadata.template = Some("0123456789ab".to_owned());
+        db.record_embedding_metadata(1, &metadata).await.unwrap();
+        let record = db.embedding_metadata(1).await.unwrap().unwrap();
+        assert_eq!((record.input_tokens, record.truncated), (Some(4), true));
+        assert_eq!(record.template.as_deref(), Some("0123456789ab"));
+    }
+
+    #[tokio::test]
+    async fn test_comment_changes_resolve_their_issue() {
+        let db = test_db().await;
+        let issue = IssueData {
+            source_id: 42,
+            action: Action::Created,
+            author: None,
+            labels: Vec::new(),
+            milestone: None,
+            title: "issue 1".to_owned(),
+            body: String::new(),
+            is_pull_request: false,
+            number: 1,
+            html_url: "https://github.com/huggingface/lor-e/issues/1".to_owned(),
+            url: "https://api.github.com/repos/huggingface/lor-e/issues/1".to_owned(),
+            repository_full_name: "huggingface/lor-e".to_owned(),
+            source: Source::Github,
+        };
+        db.insert_issue(&issue, &[1., 0.], None).await.unwrap();
+        let issue_id = db.issue_id(42).await.unwrap().unwrap();
+        let mut comment = CommentData {
+            source_id: 7,
+            action: Action::Created,
+            issue_id: 42,
+            author: None,
+            body: "first".to_owned(),
+            url: "https://api.github.com/repos/huggingface/lor-e/issues/comments/7".to_owned(),
+            is_review: false,
+        };
+        db.insert_comment(issue_id, &comment).await.unwrap();
+
+        comment.body = "edited".to_owned();
+        assert_eq!(db.update_comment(&comment).await.unwrap(), Some(42));
+        comment.source_id = 8;
+        assert_eq!(db.update_comment(&comment).await.unwrap(), None);
+        assert_eq!(db.delete_comment(7).await.unwrap(), Some(42));
+        assert_eq!(db.delete_comment(7).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_rescheduled_pending_comment() {
+        let db = test_db().await;
+        for number in 1..=2 {
+            db.enqueue_comment(
+                &Source::Github,
+                "huggingface/lor-e",
+                &format!("https://api.github.com/repos/huggingface/lor-e/issues/{number}"),
+                "comment",
+                false,
+                false,
+            )
+            .await
+            .unwrap();
+        }
+        let pending = db.pending_comments(10).await.unwrap();
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].attempts, 0);
+
+        db.reschedule_pending_comment(pending[0].id, 60, "timed out")
+            .await
+            .unwrap();
+        let pending_ids: Vec<i32> = db
+            .pending_comments(10)
+            .await
+            .unwrap()
+            .iter()
+            .map(|comment| comment.id)
+            .collect();
+        assert_eq!(pending_ids, vec![pending[1].id]);
+
+        db.reschedule_pending_comment(pending[0].id, 0, "timed out")
+            .await
+            .unwrap();
+        let pending = db.pending_comments(10).await.unwrap();
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn test_code_context() {
+        let db = test_db().await;
+        let issue = IssueData {
+            source_id: 1,
+            action: Action::Created,
+            author: None,
+            labels: Vec::new(),
+            milestone: None,
+            title: "issue 1".to_owned(),
+            body: String::new(),
+            is_pull_request: false,
+            number: 1,
+            html_url: "https://github.com/huggingface/lor-e/issues/1".to_owned(),
+            url: "https://api.github.com/repos/huggingface/lor-e/issues/1".to_owned(),
+            repository_full_name: "huggingface/lor-e".to_owned(),
+            source: Source::Github,
+        };
+        db.insert_issue(&issue, &[1., 0.], None).await.unwrap();
+        assert!(db.issue_text(1).await.unwrap().code_context.is_none());
+
+        let snippets = "\n----\nCode: src/main.rs\nfn main() {}";
+        db.set_code_context(1, Some(snippets)).await.unwrap();
+        let text = db.issue_text(1).await.unwrap();
+        assert_eq!(text.code_context.as_deref(), Some(snippets));
+    }
+
+    #[tokio::test]
+    async fn test_merged_system_info() {
+        let db = test_db().await;
+        let issue = IssueData {
+            source_id: 1,
+            action: Action::Created,
+            author: None,
+            labels: Vec::new(),
+            milestone: None,
+            title: "issue 1".to_owned(),
+            body: String::new(),
+            is_pull_request: false,
+            number: 1,
+            html_url: "https://github.com/huggingface/lor-e/issues/1".to_owned(),
+            url: "https://api.github.com/repos/huggingface/lor-e/issues/1".to_owned(),
+            repository_full_name: "huggingface/lor-e".to_owned(),
+            source: Source::Github,
+        };
+        db.insert_issue(&issue, &[1., 0.], None).await.unwrap();
+        let extracted = SystemInfo {
+            package_version: Some("4.45.0".to_owned()),
+            accelerator: Some("cuda".to_owned()),
+            ..Default::default()
+        };
+        db.set_system_info(1, &extracted).await.unwrap();
+
+        let parsed = SystemInfo {
+            package_version: Some("4.46.0".to_owned()),
+            ..Default::default()
+        };
+        db.merge_system_info(1, &parsed).await.unwrap();
+        let cohorts = db.issue_cohorts("huggingface/lor-e", &[1]).await.unwrap();
+        assert_eq!(cohorts[0].package_version.as_deref(), Some("4.46.0"));
+        assert_eq!(cohorts[0].accelerator.as_deref(), Some("cuda"));
+    }
+
+    #[tokio::test]
+    async fn test_gone_issue() {
+        let db = test_db().await;
+        for number in 1..=2 {
+            let issue = IssueData {
+                source_id: number.into(),
+                action: Action::Created,
+                author: None,
+                labels: Vec::new(),
+                milestone: None,
+                title: format!("issue {number}"),
+                body: String::new(),
+                is_pull_request: false,
+                number,
+                html_url: format!("https://github.com/huggingface/lor-e/issues/{number}"),
+                url: format!("https://api.github.com/repos/huggingface/lor-e/issues/{number}"),
+                repository_full_name: "huggingface/lor-e".to_owned(),
+                source: Source::Github,
+            };
+            db.insert_issue(&issue, &[1., 0.], None).await.unwrap();
+        }
+
+        let exported = || async {
+            db.export_issues(0, 10)
+                .await
+                .unwrap()
+                .iter()
+                .map(|issue| issue.number)
+                .collect::<Vec<i32>>()
+        };
+        let url = "https://api.github.com/repos/huggingface/lor-e/issues/2";
+        let comments_url = format!("{url}/comments?per_page=100");
+        let cached = CachedResponse {
+            etag: "\"abc\"".to_owned(),
+            link: None,
+            body: b"[]".to_vec(),
+        };
+        db.cache_response(&comments_url, &cached).await.unwrap();
+        assert!(db.mark_issue_gone(url).await.unwrap());
+        assert!(!db.mark_issue_gone(url).await.unwrap());
+        assert_eq!(exported().await, vec![1]);
+        assert!(db.cached_response(&comments_url).await.unwrap().is_none());
+
+        // a later webhook brings it back
+        db.set_issue_closed(2, false).await.unwrap();
+        assert_eq!(exported().await, vec![1, 2]);
+        assert!(db.mark_issue_gone(url).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_retention_states() {
+        let storage = test_storage().await;
+        let issue = |number: i32| IssueData {
+            source_id: number.into(),
+            action: Action::Created,
+            author: None,
+            labels: Vec::new(),
+            milestone: None,
+            title: format!("issue {number}"),
+            body: String::new(),
+            is_pull_request: false,
+            number,
+            html_url: format!("https://github.com/huggingface/lor-e/issues/{number}"),
+            url: format!("https://api.github.com/repos/huggingface/lor-e/issues/{number}"),
+            repository_full_name: "huggingface/lor-e".to_owned(),
+            source: Source::Github,
+        };
+        for number in 1..=2 {
+            storage
+                .insert_issue(&issue(number), &[1., 0.], None)
+                .await
+                .unwrap();
+        }
+        storage.set_issue_closed(2, true).await.unwrap();
+        sqlx::query("update issues set updated_at = datetime('now', '-60 days')")
+            .execute(&storage.pool)
+            .await
+            .unwrap();
+
+        let stale = |state| storage.stale_issues(None, 30, &[], state);
+        assert_eq!(stale(None).await.unwrap(), vec![1, 2]);
+        assert_eq!(stale(Some(IssueState::Open)).await.unwrap(), vec![1]);
+        assert_eq!(stale(Some(IssueState::Closed)).await.unwrap(), vec![2]);
+
+        // excluded issues come back once reopened or updated
+        storage.exclude_issues(&[1, 2]).await.unwrap();
+        assert!(stale(None).await.unwrap().is_empty());
+        storage.set_issue_closed(2, false).await.unwrap();
+        storage.update_issue(&issue(1), None).await.unwrap();
+        sqlx::query("update issues set updated_at = datetime('now', '-60 days')")
+            .execute(&storage.pool)
+            .await
+            .unwrap();
+        assert_eq!(stale(Some(IssueState::Open)).await.unwrap(), vec![1, 2]);
+
+        storage.delete_issues(&[1, 2]).await.unwrap();
+        assert!(stale(None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_recent_suggestions() {
+        let storage = test_storage().await;
+        let issue = |number: i32| IssueData {
+            source_id: number.into(),
+            action: Action::Created,
+            author: None,
+            labels: Vec::new(),
+            milestone: None,
+            title: format!("issue {number}"),
+            body: String::new(),
+            is_pull_request: false,
+            number,
+            html_url: format!("https://github.com/huggingface/lor-e/issues/{number}"),
+            url: format!("https://api.github.com/repos/huggingface/lor-e/issues/{number}"),
+            repository_full_name: "huggingface/lor-e".to_owned(),
+            source: Source::Github,
+        };
+        let suggested = |number: i32| ClosestIssue {
+            title: format!("issue {number}"),
+            number,
+            html_url: format!("https://github.com/huggingface/lor-e/issues/{number}"),
+            labels: Vec::new(),
+            repository_full_name: "huggingface/lor-e".to_owned(),
+            cosine_similarity: 0.9,
+            boost: 0.,
+            embedding: Vec::new(),
+        };
+        for number in 1..=4 {
+            storage
+                .insert_issue(&issue(number), &[1., 0.], None)
+                .await
+                .unwrap();
+        }
+        storage
+            .record_suggestions(3, &[suggested(2), suggested(1)], "run-3", None)
+            .await
+            .unwrap();
+        sqlx::query("update suggestions set created_at = datetime('now', '-1 hour')")
+            .execute(&storage.pool)
+            .await
+            .unwrap();
+        storage
+            .record_suggestions(4, &[suggested(3)], "run-4", None)
+            .await
+            .unwrap();
+
+        let recent: Vec<(i32, i32)> = storage
+            .recent_suggestions(10)
+            .await
+            .unwrap()
+            .iter()
+            .map(|s| (s.issue_number, s.number))
+            .collect();
+        assert_eq!(recent, vec![(4, 3), (3, 2), (3, 1)]);
+        let recent = storage.recent_suggestions(1).await.unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].title, "issue 3");
+    }
+
+    #[tokio::test]
+    async fn test_guidance_embeddings() {
+        let storage = test_storage().await;
+        let section = |heading: &str| GuidanceSection {
+            repository_full_name: "huggingface/lor-e".to_owned(),
+            path: "README.md".to_owned(),
+            heading: heading.to_owned(),
+            anchor: heading.to_lowercase(),
+            content: format!("{heading} content"),
+        };
+        storage
+            .replace_guidance_sections(
+                "huggingface/lor-e",
+                &[
+                    (section("Installation"), "hash-1".to_owned(), vec![1., 0.]),
+                    (section("Usage"), "hash-2".to_owned(), vec![0., 1.]),
+                ],
+            )
+            .await
+            .unwrap();
+        sqlx::query("insert into guidance_sections (repository_full_name, path, heading, anchor, content, embedding) values ('huggingface/lor-e', 'CONTRIBUTING.md', 'Legacy', '', 'indexed before hashing', ?)")
+            .bind(encode_embedding(&[0.5, 0.5]))
+            .execute(&storage.pool)
+            .await
+            .unwrap();
+
+        let embeddings = storage
+            .guidance_embeddings("huggingface/lor-e")
+            .await
+            .unwrap();
+        assert_eq!(embeddings.len(), 2);
+        assert_eq!(embeddings["hash-1"], [1., 0.]);
+        assert_eq!(embeddings["hash-2"], [0., 1.]);
+        assert!(storage
+            .guidance_embeddings("huggingface/other")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_feedback_prompt_profile() {
+        let storage = test_storage().await;
+        let url = "https://api.github.com/repos/huggingface/lor-e/issues/1";
+        storage
+            .insert_comment_run("run-a", url, Some("concise"))
+            .await
+            .unwrap();
+        storage
+            .insert_comment_run("run-b", url, None)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            storage.insert_feedback("run-a", Vote::Up).await.unwrap(),
+            Some("concise".to_owned())
+        );
+        assert_eq!(
+            storage.insert_feedback("run-b", Vote::Down).await.unwrap(),
+            None
+        );
+        let profiles: Vec<(String, Option<String>)> =
+            sqlx::query_as("select run_id, prompt_profile from feedback order by id")
+                .fetch_all(&storage.pool)
+                .await
+                .unwrap();
+        assert_eq!(
+            profiles,
+            [
+                ("run-a".to_owned(), Some("concise".to_owned())),
+                ("run-b".to_owned(), None)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_moved_private_repository() {
+        let storage = test_storage().await;
+        let issue = |repository_full_name: &str, number: i32| IssueData {
+            source_id: number.into(),
+            action: Action::Created,
+            author: None,
+            labels: Vec::new(),
+            milestone: None,
+            title: format!("discussion {number}"),
+            body: String::new(),
+            is_pull_request: false,
+            number,
+            html_url: format!("https://huggingface.co/{repository_full_name}/discussions/{number}"),
+            url: format!(
+                "https://huggingface.co/api/models/{repository_full_name}/discussions/{number}"
+            ),
+            repository_full_name: repository_full_name.to_owned(),
+            source: Source::HuggingFace,
+        };
+        storage
+            .insert_issue(&issue("org/old", 1), &[1., 0.], None)
+            .await
+            .unwrap();
+        storage
+            .insert_comment_run("run", &issue("org/old", 1).url, None)
+            .await
+            .unwrap();
+        storage
+            .advance_repository_cursor("org/old", chrono::Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(
+            storage
+                .set_repository_private("org/old", true)
+                .await
+                .unwrap(),
+            1
+        );
+
+        assert_eq!(
+            storage.move_repository("org/old", "org/new").await.unwrap(),
+            1
+        );
+        storage
+            .insert_issue(&issue("org/new", 2), &[1., 0.], None)
+            .await
+            .unwrap();
+        let private: Vec<(i32, String)> = sqlx::query_as(
+            "select number, html_url from issues where private and repository_full_name = 'org/new' order by number",
+        )
+        .fetch_all(&storage.pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            private,
+            vec![
+                (1, "https://huggingface.co/org/new/discussions/1".to_owned()),
+                (2, "https://huggingface.co/org/new/discussions/2".to_owned()),
+            ]
+        );
+        let run_issue_url: String =
+            sqlx::query_scalar("select issue_url from comment_runs where run_id = 'run'")
+                .fetch_one(&storage.pool)
+                .await
+                .unwrap();
+        assert_eq!(run_issue_url, issue("org/new", 1).url);
+        let cursors = storage.repository_cursors().await.unwrap();
+        assert_eq!(cursors.len(), 1);
+        assert_eq!(cursors[0].repository_full_name, "org/new");
+    }
+
+    #[tokio::test]
+    async fn test_comment_drafts() {
+        let storage = test_storage().await;
+        for (number, awaiting_approval) in [(1, true), (2, true), (3, true), (4, false)] {
+            storage
+                .enqueue_comment(
+                    &Source::Github,
+                    "huggingface/lor-e",
+                    &format!("https://api.github.com/repos/huggingface/lor-e/issues/{number}"),
+                    "similar issues",
+                    awaiting_approval,
+                    true,
+                )
+                .await
+                .unwrap();
+        }
+        let drafts = storage.unannounced_comment_drafts(10).await.unwrap();
+        let ids: Vec<i32> = drafts.iter().map(|d| d.id).collect();
+        assert_eq!(ids.len(), 3);
+        storage
+            .set_comment_draft_announced(ids[0], "1700000000.000100")
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.unannounced_comment_drafts(10).await.unwrap().len(),
+            2
+        );
+
+        // only the non draft is posted until approved
+        assert_eq!(storage.pending_comments(10).await.unwrap().len(), 1);
+        assert!(storage.approve_comment_draft(ids[0]).await.unwrap());
+        assert!(!storage.approve_comment_draft(ids[0]).await.unwrap());
+        assert_eq!(storage.pending_comments(10).await.unwrap().len(), 2);
+        // an approved comment can't be discarded anymore
+        assert!(!storage.discard_comment_draft(ids[0]).await.unwrap());
+
+        assert!(storage.discard_comment_draft(ids[1]).await.unwrap());
+        assert!(!storage.approve_comment_draft(ids[1]).await.unwrap());
+
+        sqlx::query(
+            "update pending_comments set created_at = datetime('now', '-2 days') where id = ?",
+        )
+        .bind(ids[2])
+        .execute(&storage.pool)
+        .await
+        .unwrap();
+        assert_eq!(storage.expire_comment_drafts(24).await.unwrap(), 1);
+        assert_eq!(storage.expire_comment_drafts(24).await.unwrap(), 0);
+        assert!(storage
+            .unannounced_comment_drafts(10)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(storage.pending_comments(10).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_unfingerprinted_issues() {
+        let storage = test_storage().await;
+        let issue = |number: i32, body: &str| IssueData {
+            source_id: number.into(),
+            action: Action::Created,
+            author: None,
+            labels: Vec::new(),
+            milestone: None,
+            title: format!("issue {number}"),
+            body: body.to_owned(),
+            is_pull_request: false,
+            number,
+            html_url: format!("https://github.com/huggingface/lor-e/issues/{number}"),
+            url: format!("https://api.github.com/repos/huggingface/lor-e/issues/{number}"),
+            repository_full_name: "huggingface/lor-e".to_owned(),
+            source: Source::Github,
+        };
+        let traceback = "Traceback (most recent call last):\nValueError: boom";
+        storage
+            .insert_issue(&issue(1, traceback), &[1., 0.], None)
+            .await
+            .unwrap();
+        storage
+            .insert_issue(&issue(2, traceback), &[1., 0.], Some("abc"))
+            .await
+            .unwrap();
+        storage
+            .insert_issue(&issue(3, "no traceback"), &[1., 0.], None)
+            .await
+            .unwrap();
+
+        let unfingerprinted = storage.unfingerprinted_issues(0, 10).await.unwrap();
+        assert_eq!(unfingerprinted.len(), 1);
+        assert_eq!(unfingerprinted[0].source_id, 1);
+        assert!(storage
+            .unfingerprinted_issues(unfingerprinted[0].id, 10)
+            .await
+            .unwrap()
+            .is_empty());
+
+        storage
+            .update_issue(&issue(1, traceback), Some("abc"))
+            .await
+            .unwrap();
+        assert!(storage
+            .unfingerprinted_issues(0, 10)
+            .await
+            .unwrap()
+            .is_empty());
+        let duplicate = storage
+            .issue_by_fingerprint("abc", &[], &["huggingface/lor-e".to_owned()])
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(duplicate.number, 1);
+    }
+
+    #[tokio::test]
+    async fn test_issue_unsuppression() {
+        let storage = test_storage().await;
+        let issue = IssueData {
+            source_id: 1,
+            action: Action::Created,
+            author: None,
+            labels: Vec::new(),
+            milestone: None,
+            title: "issue 1".to_owned(),
+            body: "body".to_owned(),
+            is_pull_request: false,
+            number: 1,
+            html_url: "https://github.com/huggingface/lor-e/issues/1".to_owned(),
+            url: "https://api.github.com/repos/huggingface/lor-e/issues/1".to_owned(),
+            repository_full_name: "huggingface/lor-e".to_owned(),
+            source: Source::Github,
+        };
+        storage.insert_issue(&issue, &[1., 0.], None).await.unwrap();
+
+        assert!(storage.suppress_issue(1, "maintainer").await.unwrap());
+        assert!(storage.is_issue_url_suppressed(&issue.url).await.unwrap());
+        assert!(storage.unsuppress_issue(1).await.unwrap());
+        assert!(!storage.unsuppress_issue(1).await.unwrap());
+        assert!(!storage.is_issue_suppressed(1).await.unwrap());
+        assert!(!storage.is_issue_url_suppressed(&issue.url).await.unwrap());
+    }
+}
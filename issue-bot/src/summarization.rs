@@ -1,11 +1,61 @@
+use std::{collections::HashMap, time::Instant};
+
 use reqwest::{
     header::{HeaderMap, HeaderValue, AUTHORIZATION},
     Client,
 };
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::{
+    config::{
+        HttpClientConfig, HttpTarget, RepositoryConfig, SummarizationApiConfig, TimeoutsConfig,
+    },
+    failover::Endpoints,
+    http_client::{client_builder, with_client_certificate, ClientCertificateError},
+    retry::{self, classify_reqwest, Classify, RetryClass},
+    summary_guardrails::{SummaryGuardrails, Violation},
+    IssueData,
+};
+
+/// profile of `summarization_api.system_prompt`
+pub const DEFAULT_PROMPT_PROFILE: &str = "default";
+
+const MAX_SUMMARY_TOKENS: u32 = 100;
 
-use crate::{config::SummarizationApiConfig, APP_USER_AGENT};
+/// enough for the `PASS` or `FAIL` verdict of the critique
+const MAX_CRITIQUE_TOKENS: u32 = 5;
+
+/// replaces the middle of truncated texts
+const TRUNCATION_MARKER: &str = "\n[...]\n";
+
+fn estimate_tokens(text: &str, chars_per_token: f64) -> usize {
+    (text.chars().count() as f64 / chars_per_token).ceil() as usize
+}
+
+/// `text` cut down to about `max_tokens`, `None` when it already fits
+///
+/// The first line, the issue's title, is kept along with as much of the start and end of the rest
+/// as fits, where the description and the latest comments usually are.
+fn truncate_to_tokens(text: &str, max_tokens: usize, chars_per_token: f64) -> Option<String> {
+    let max_chars = (max_tokens as f64 * chars_per_token) as usize;
+    if text.chars().count() <= max_chars {
+        return None;
+    }
+    let (title, rest) = text.split_once('\n').unwrap_or((text, ""));
+    let title: String = title.chars().take(max_chars).collect();
+    let budget =
+        max_chars.saturating_sub(title.chars().count() + 1 + TRUNCATION_MARKER.chars().count());
+    let head: String = rest.chars().take(budget / 2).collect();
+    let tail_chars = budget - budget / 2;
+    let rest_chars = rest.chars().count();
+    let tail: String = rest
+        .chars()
+        .skip(rest_chars.saturating_sub(tail_chars))
+        .collect();
+    Some(format!("{title}\n{head}{TRUNCATION_MARKER}{tail}"))
+}
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Message {
@@ -33,50 +83,327 @@ pub struct ChatCompletionsResponse {
 
 #[derive(Debug, Error)]
 pub enum SummarizationApiError {
+    #[error("client certificate error: {0}")]
+    ClientCertificate(#[from] ClientCertificateError),
     #[error("invalid header value: {0}")]
     InvalidHeaderValue(#[from] reqwest::header::InvalidHeaderValue),
     #[error("reqwest error: {0}")]
     Reqwest(#[from] reqwest::Error),
+    #[error("repository {repository} uses unknown summarization prompt '{profile}'")]
+    UnknownPromptProfile { repository: String, profile: String },
+}
+
+impl Classify for SummarizationApiError {
+    fn retry_class(&self) -> RetryClass {
+        match self {
+            Self::ClientCertificate(_)
+            | Self::InvalidHeaderValue(_)
+            | Self::UnknownPromptProfile { .. } => RetryClass::Fatal,
+            Self::Reqwest(err) => classify_reqwest(err),
+        }
+    }
 }
 
+#[derive(Clone)]
 pub struct SummarizationApi {
+    chars_per_token: f64,
     client: Client,
+    context_window_tokens: usize,
+    endpoints: Endpoints,
+    guardrails: SummaryGuardrails,
     model: String,
+    /// system prompts by profile, `system_prompt` being the default one
+    prompts: HashMap<String, String>,
+    /// profiles by repository full name
+    repository_prompts: HashMap<String, Vec<String>>,
     special_tokens: Vec<String>,
-    system_prompt: String,
-    url: String,
 }
 
 impl SummarizationApi {
-    pub fn new(cfg: SummarizationApiConfig) -> Result<Self, SummarizationApiError> {
+    pub fn new(
+        cfg: SummarizationApiConfig,
+        http_cfg: &HttpClientConfig,
+        timeouts: &TimeoutsConfig,
+        repositories: &HashMap<String, RepositoryConfig>,
+    ) -> Result<Self, SummarizationApiError> {
+        let mut prompts = cfg.prompts;
+        prompts.insert(DEFAULT_PROMPT_PROFILE.to_owned(), cfg.system_prompt);
+        let mut repository_prompts = HashMap::new();
+        for (full_name, repository) in repositories {
+            if repository.summarization_prompts.is_empty() {
+                continue;
+            }
+            if let Some(profile) = repository
+                .summarization_prompts
+                .iter()
+                .find(|profile| !prompts.contains_key(*profile))
+            {
+                return Err(SummarizationApiError::UnknownPromptProfile {
+                    repository: full_name.clone(),
+                    profile: profile.clone(),
+                });
+            }
+            repository_prompts.insert(full_name.clone(), repository.summarization_prompts.clone());
+        }
         let mut headers = HeaderMap::new();
         let mut auth_value = HeaderValue::from_str(&format!("Bearer {}", cfg.auth_token))?;
         auth_value.set_sensitive(true);
         headers.insert(AUTHORIZATION, auth_value);
-        let client = Client::builder()
-            .user_agent(APP_USER_AGENT)
+        let builder = client_builder(http_cfg, timeouts, HttpTarget::Summarization)?;
+        let client = with_client_certificate(builder, cfg.client_certificate.as_ref())?
             .default_headers(headers)
             .build()?;
         Ok(Self {
+            chars_per_token: cfg.chars_per_token,
             client,
+            context_window_tokens: cfg.context_window_tokens,
+            endpoints: Endpoints::new(
+                "summarization_api",
+                cfg.failover,
+                cfg.url,
+                cfg.fallback_urls,
+            ),
+            guardrails: SummaryGuardrails::new(cfg.guardrails),
             model: cfg.model,
+            prompts,
+            repository_prompts,
             special_tokens: cfg.special_tokens_used,
-            system_prompt: cfg.system_prompt,
-            url: cfg.url,
         })
     }
 
-    pub async fn summarize(&self, text: String) -> Result<String, SummarizationApiError> {
-        let chat_completions_url = format!("{}/v1/chat/completions", self.url);
-        let res: ChatCompletionsResponse = self
+    /// prompt profile `issue` is summarized with, always the same for a given issue
+    pub fn prompt_profile(&self, issue: &IssueData) -> &str {
+        match self.repository_prompts.get(&issue.repository_full_name) {
+            Some(profiles) => &profiles[issue.source_id.rem_euclid(profiles.len() as i64) as usize],
+            None => DEFAULT_PROMPT_PROFILE,
+        }
+    }
+
+    /// host of the endpoint requests go to, whose retries are budgeted, see [retry::RetryBudget]
+    pub fn host(&self) -> String {
+        retry::host(self.endpoints.active())
+    }
+
+    /// minimal completion request to validate the url, model and token
+    pub async fn check(&self) -> Result<(), SummarizationApiError> {
+        self.client
+            .post(format!("{}/v1/chat/completions", self.endpoints.primary()))
+            .json(&ChatCompletionsRequest {
+                max_tokens: 1,
+                messages: vec![Message {
+                    role: "user".to_owned(),
+                    content: "ping".to_owned(),
+                }],
+                model: self.model.to_owned(),
+                stream: false,
+            })
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<ChatCompletionsResponse>()
+            .await?;
+        Ok(())
+    }
+
+    fn system_prompt(&self, profile: &str) -> &str {
+        self.prompts
+            .get(profile)
+            .or_else(|| self.prompts.get(DEFAULT_PROMPT_PROFILE))
+            .map(String::as_str)
+            .unwrap_or_default()
+    }
+
+    /// summarizes `text` with the system prompt of `profile`, see [Self::prompt_profile]
+    ///
+    /// Outcomes, durations and summary lengths are reported per profile to compare them. Summaries
+    /// failing the guardrails are replaced by an extract of `text`, see [SummaryGuardrails].
+    pub async fn summarize(
+        &self,
+        text: String,
+        profile: &str,
+    ) -> Result<String, SummarizationApiError> {
+        let start = Instant::now();
+        let res = match self.summarize_with_profile(text.clone(), profile).await {
+            Ok(summary) => Ok(self.guard(summary, &text, profile).await),
+            Err(err) => Err(err),
+        };
+        let outcome = match &res {
+            Ok(summary) if summary.trim().is_empty() => "empty",
+            Ok(_) => "success",
+            Err(_) => "error",
+        };
+        let profile = profile.to_owned();
+        metrics::counter!(
+            "issue_bot_summaries_total",
+            "profile" => profile.clone(),
+            "outcome" => outcome,
+        )
+        .increment(1);
+        metrics::histogram!("issue_bot_summarization_seconds", "profile" => profile.clone())
+            .record(start.elapsed().as_secs_f64());
+        if let Ok(summary) = &res {
+            metrics::histogram!("issue_bot_summary_chars", "profile" => profile)
+                .record(summary.chars().count() as f64);
+        }
+        res
+    }
+
+    /// `summary` when it passes the guardrails, an extract of the summarized `text` otherwise
+    async fn guard(&self, summary: String, text: &str, profile: &str) -> String {
+        if !self.guardrails.enabled() {
+            return summary;
+        }
+        let violation = match self.guardrails.check(&summary, self.system_prompt(profile)) {
+            Ok(()) => match self.critique(text, &summary).await {
+                Ok(true) => return summary,
+                Ok(false) => Violation::Critique,
+                Err(err) => {
+                    warn!(
+                        profile,
+                        err = err.to_string(),
+                        "summary critique failed, keeping summary"
+                    );
+                    return summary;
+                }
+            },
+            Err(violation) => violation,
+        };
+        metrics::counter!(
+            "issue_bot_summary_guardrail_violations_total",
+            "profile" => profile.to_owned(),
+            "violation" => violation.as_str(),
+        )
+        .increment(1);
+        warn!(
+            profile,
+            violation = violation.as_str(),
+            "summary failed guardrails, using an extract of the issue"
+        );
+        self.guardrails.fallback(text)
+    }
+
+    /// whether the model judges `summary` faithful to `text`, always when there's no critique
+    /// prompt
+    async fn critique(&self, text: &str, summary: &str) -> Result<bool, SummarizationApiError> {
+        let Some(critique_prompt) = self.guardrails.critique_prompt() else {
+            return Ok(true);
+        };
+        let max_text_tokens = self.context_window_tokens.saturating_sub(
+            MAX_CRITIQUE_TOKENS as usize
+                + estimate_tokens(critique_prompt, self.chars_per_token)
+                + estimate_tokens(summary, self.chars_per_token),
+        );
+        let text = truncate_to_tokens(text, max_text_tokens, self.chars_per_token)
+            .unwrap_or_else(|| text.to_owned());
+        let (endpoint, url) = self.endpoints.select();
+        let res = self
+            .chat_completions(
+                url,
+                critique_prompt.to_owned(),
+                format!("Issue:\n{text}\n\nSummary:\n{summary}"),
+                MAX_CRITIQUE_TOKENS,
+            )
+            .await;
+        match &res {
+            Ok(_) => self.endpoints.succeeded(endpoint),
+            Err(_) => {
+                self.endpoints.failed(endpoint);
+            }
+        }
+        let verdict = res?
+            .choices
+            .first()
+            .map(|c| c.message.content.trim().to_uppercase())
+            .unwrap_or_default();
+        Ok(!verdict.starts_with("FAIL"))
+    }
+
+    /// retried on the next endpoint when failing over, other retries being left to the caller
+    async fn summarize_with_profile(
+        &self,
+        text: String,
+        profile: &str,
+    ) -> Result<String, SummarizationApiError> {
+        let system_prompt = self.system_prompt(profile).to_owned();
+        let max_text_tokens = self.context_window_tokens.saturating_sub(
+            MAX_SUMMARY_TOKENS as usize + estimate_tokens(&system_prompt, self.chars_per_token),
+        );
+        let text = match truncate_to_tokens(&text, max_text_tokens, self.chars_per_token) {
+            Some(truncated) => {
+                info!(
+                    estimated_tokens = estimate_tokens(&text, self.chars_per_token),
+                    max_text_tokens, "truncating text to summarize"
+                );
+                metrics::counter!("issue_bot_summarization_truncations_total").increment(1);
+                truncated
+            }
+            None => text,
+        };
+        let mut res = self
+            .complete(&system_prompt, text, MAX_SUMMARY_TOKENS)
+            .await?;
+        for token in self.special_tokens.iter() {
+            res = res.replace(&format!("<{token}>"), "");
+            res = res.replace(&format!("</{token}>"), "");
+        }
+        Ok(res)
+    }
+
+    /// completion of `text` with `system_prompt`, retried on the next endpoint when failing over
+    ///
+    /// `text` is truncated to fit the context window along with `max_tokens`.
+    pub async fn complete(
+        &self,
+        system_prompt: &str,
+        text: String,
+        max_tokens: u32,
+    ) -> Result<String, SummarizationApiError> {
+        let max_text_tokens = self.context_window_tokens.saturating_sub(
+            max_tokens as usize + estimate_tokens(system_prompt, self.chars_per_token),
+        );
+        let text = truncate_to_tokens(&text, max_text_tokens, self.chars_per_token).unwrap_or(text);
+        let res = loop {
+            let (endpoint, url) = self.endpoints.select();
+            match self
+                .chat_completions(url, system_prompt.to_owned(), text.clone(), max_tokens)
+                .await
+            {
+                Ok(res) => {
+                    self.endpoints.succeeded(endpoint);
+                    break res;
+                }
+                Err(err) if err.retry_class() == RetryClass::Retryable => {
+                    if !self.endpoints.failed(endpoint) {
+                        return Err(err);
+                    }
+                }
+                Err(err) => return Err(err),
+            }
+        };
+        Ok(res
+            .choices
+            .first()
+            .cloned()
+            .map(|c| c.message.content)
+            .unwrap_or_default())
+    }
+
+    async fn chat_completions(
+        &self,
+        url: &str,
+        system_prompt: String,
+        text: String,
+        max_tokens: u32,
+    ) -> Result<ChatCompletionsResponse, SummarizationApiError> {
+        Ok(self
             .client
-            .post(chat_completions_url)
+            .post(format!("{url}/v1/chat/completions"))
             .json(&ChatCompletionsRequest {
-                max_tokens: 100,
+                max_tokens,
                 messages: vec![
                     Message {
                         role: "system".to_owned(),
-                        content: self.system_prompt.clone(),
+                        content: system_prompt,
                     },
                     Message {
                         role: "user".to_owned(),
@@ -88,18 +415,117 @@ impl SummarizationApi {
             })
             .send()
             .await?
+            .error_for_status()?
             .json()
-            .await?;
-        let mut res = res
-            .choices
-            .first()
-            .cloned()
-            .map(|c| c.message.content)
-            .unwrap_or_default();
-        for token in self.special_tokens.iter() {
-            res = res.replace(&format!("<{token}>"), "");
-            res = res.replace(&format!("</{token}>"), "");
+            .await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        config::{load_config, IssueBotConfig, RepositoryConfig},
+        Action, IssueData, Source,
+    };
+
+    use super::{
+        truncate_to_tokens, SummarizationApi, SummarizationApiError, DEFAULT_PROMPT_PROFILE,
+    };
+
+    fn issue(repository_full_name: &str, source_id: i64) -> IssueData {
+        IssueData {
+            source_id,
+            action: Action::Created,
+            author: None,
+            labels: Vec::new(),
+            milestone: None,
+            title: "Tokenizer crashes on empty input".to_owned(),
+            body: String::new(),
+            is_pull_request: false,
+            number: 1,
+            html_url: format!("https://github.com/{repository_full_name}/issues/1"),
+            url: format!("https://api.github.com/repos/{repository_full_name}/issues/1"),
+            repository_full_name: repository_full_name.to_owned(),
+            source: Source::Github,
         }
-        Ok(res)
+    }
+
+    #[test]
+    fn test_prompt_profile() {
+        let mut config: IssueBotConfig = load_config("ISSUE_BOT_TEST").unwrap();
+        config.summarization_api.prompts = HashMap::from([
+            (
+                "library".to_owned(),
+                "Summarize this library issue".to_owned(),
+            ),
+            ("terse".to_owned(), "Summarize in 5 words".to_owned()),
+        ]);
+        let repository = |prompts: &[&str]| RepositoryConfig {
+            summarization_prompts: prompts.iter().map(|p| (*p).to_owned()).collect(),
+            ..Default::default()
+        };
+        let repositories = HashMap::from([
+            (
+                "huggingface/transformers".to_owned(),
+                repository(&["library"]),
+            ),
+            (
+                "huggingface/tokenizers".to_owned(),
+                repository(&["library", "terse"]),
+            ),
+        ]);
+        let api = SummarizationApi::new(
+            config.summarization_api.clone(),
+            &config.http_client,
+            &config.timeouts,
+            &repositories,
+        )
+        .unwrap();
+        assert_eq!(
+            api.prompt_profile(&issue("huggingface/transformers", 7)),
+            "library"
+        );
+        assert_eq!(
+            api.prompt_profile(&issue("huggingface/lor-e", 7)),
+            DEFAULT_PROMPT_PROFILE
+        );
+        // A/B tested repositories split their issues between the profiles
+        assert_eq!(
+            api.prompt_profile(&issue("huggingface/tokenizers", 8)),
+            "library"
+        );
+        assert_eq!(
+            api.prompt_profile(&issue("huggingface/tokenizers", 9)),
+            "terse"
+        );
+
+        let repositories =
+            HashMap::from([("huggingface/lor-e".to_owned(), repository(&["verbose"]))]);
+        assert!(matches!(
+            SummarizationApi::new(
+                config.summarization_api,
+                &config.http_client,
+                &config.timeouts,
+                &repositories
+            ),
+            Err(SummarizationApiError::UnknownPromptProfile { .. })
+        ));
+    }
+
+    #[test]
+    fn test_truncate_to_tokens() {
+        let text = format!(
+            "# Tokenizer crashes\n{}{}",
+            "a".repeat(100),
+            "z".repeat(100)
+        );
+        assert!(truncate_to_tokens(&text, 100, 3.).is_none());
+
+        let truncated = truncate_to_tokens(&text, 20, 3.).unwrap();
+        assert_eq!(truncated.chars().count(), 60);
+        assert!(truncated.starts_with("# Tokenizer crashes\naaaaaaaaaaaaaaaa\n[...]\n"));
+        assert!(truncated.ends_with('z'));
     }
 }
@@ -0,0 +1,179 @@
+use crate::{config::SummaryGuardrailsConfig, issue_text::COMMENT_SEPARATOR};
+
+/// system prompt lines shorter than this are too generic to tell a leak apart
+const MIN_LEAKED_LINE_CHARS: usize = 40;
+
+/// Reason a generated summary was rejected
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Violation {
+    BannedPhrase,
+    Critique,
+    Empty,
+    PromptLeak,
+    TooLong,
+}
+
+impl Violation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::BannedPhrase => "banned_phrase",
+            Self::Critique => "critique",
+            Self::Empty => "empty",
+            Self::PromptLeak => "prompt_leak",
+            Self::TooLong => "too_long",
+        }
+    }
+}
+
+/// Checks generated summaries before they are sent to Slack or commented, see
+/// [SummaryGuardrailsConfig]
+#[derive(Clone)]
+pub struct SummaryGuardrails {
+    /// lowercased
+    banned_phrases: Vec<String>,
+    cfg: SummaryGuardrailsConfig,
+}
+
+impl SummaryGuardrails {
+    pub fn new(cfg: SummaryGuardrailsConfig) -> Self {
+        Self {
+            banned_phrases: cfg
+                .banned_phrases
+                .iter()
+                .map(|p| p.to_lowercase())
+                .collect(),
+            cfg,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.cfg.enabled
+    }
+
+    pub fn critique_prompt(&self) -> Option<&str> {
+        self.cfg.critique_prompt.as_deref()
+    }
+
+    /// checks that don't involve the model, `system_prompt` being the one `summary` was made with
+    pub fn check(&self, summary: &str, system_prompt: &str) -> Result<(), Violation> {
+        let summary = summary.trim();
+        if summary.is_empty() {
+            return Err(Violation::Empty);
+        }
+        if summary.chars().count() > self.cfg.max_chars {
+            return Err(Violation::TooLong);
+        }
+        let lowercased = summary.to_lowercase();
+        if self
+            .banned_phrases
+            .iter()
+            .any(|phrase| lowercased.contains(phrase.as_str()))
+        {
+            return Err(Violation::BannedPhrase);
+        }
+        if system_prompt
+            .lines()
+            .map(|line| line.trim().trim_start_matches("- "))
+            .filter(|line| line.chars().count() >= MIN_LEAKED_LINE_CHARS)
+            .any(|line| summary.contains(line))
+        {
+            return Err(Violation::PromptLeak);
+        }
+        Ok(())
+    }
+
+    /// first sentences of the description of the issue composed in `text`, its title when the
+    /// description has no prose
+    pub fn fallback(&self, text: &str) -> String {
+        let (title, rest) = text.split_once('\n').unwrap_or((text, ""));
+        let description = rest.split(COMMENT_SEPARATOR).next().unwrap_or_default();
+        let mut in_code_block = false;
+        let mut words = Vec::new();
+        let mut sentences = 0;
+        'lines: for line in description.lines().map(str::trim) {
+            if line.starts_with("```") {
+                in_code_block = !in_code_block;
+                continue;
+            }
+            if in_code_block || line.starts_with('#') {
+                continue;
+            }
+            for word in line.split_whitespace() {
+                words.push(word);
+                if word.ends_with(['.', '!', '?']) {
+                    sentences += 1;
+                    if sentences >= self.cfg.fallback_sentences.max(1) {
+                        break 'lines;
+                    }
+                }
+            }
+        }
+        let summary = if words.is_empty() {
+            title.trim_start_matches('#').trim().to_owned()
+        } else {
+            words.join(" ")
+        };
+        // the quote is where the model writes its description
+        format!(
+            "> {}",
+            summary
+                .chars()
+                .take(self.cfg.max_chars.saturating_sub(2))
+                .collect::<String>()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::SummaryGuardrailsConfig;
+
+    use super::{SummaryGuardrails, Violation};
+
+    #[test]
+    fn test_guardrails() {
+        let guardrails = SummaryGuardrails::new(SummaryGuardrailsConfig {
+            banned_phrases: vec!["As an AI".to_owned()],
+            critique_prompt: None,
+            enabled: true,
+            fallback_sentences: 2,
+            max_chars: 120,
+        });
+        let system_prompt =
+            "You are a helpful assistant.\n  - Short description (under 100 characters):\n";
+
+        assert_eq!(
+            guardrails.check(
+                "*Tags: tokenizers, bug*\n> Crashes on empty input",
+                system_prompt
+            ),
+            Ok(())
+        );
+        assert_eq!(guardrails.check("  ", system_prompt), Err(Violation::Empty));
+        assert_eq!(
+            guardrails.check(&"a".repeat(121), system_prompt),
+            Err(Violation::TooLong)
+        );
+        assert_eq!(
+            guardrails.check("as an ai model, I can't", system_prompt),
+            Err(Violation::BannedPhrase)
+        );
+        assert_eq!(
+            guardrails.check(
+                "> Short description (under 100 characters): crash",
+                system_prompt
+            ),
+            Err(Violation::PromptLeak)
+        );
+
+        let text = "# Tokenizer crashes\nCalling it on an empty string\npanics. See below!\n```\nthread 'main' panicked.\n```\nAny idea?\n----\nComment: same here.";
+        assert_eq!(
+            guardrails.fallback(text),
+            "> Calling it on an empty string panics. See below!"
+        );
+        assert_eq!(
+            guardrails.fallback("# Tokenizer crashes\n```\npanic\n```"),
+            "> Tokenizer crashes"
+        );
+    }
+}
@@ -0,0 +1,197 @@
+use std::{
+    collections::HashSet,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use futures::pin_mut;
+use tokio::{
+    select,
+    time::{interval, sleep},
+};
+use tracing::{error, warn};
+
+use crate::{
+    config::SupervisorConfig,
+    debug::{DebugState, DebugStateSnapshot},
+    shutdown_signal,
+};
+
+/// whether `worker` has been handling the same event since before `now - threshold`, the time it
+/// marked it in flight in [DebugState] serving as its heartbeat
+fn is_stalled(
+    snapshot: &DebugStateSnapshot,
+    worker: &str,
+    threshold: Duration,
+    now: DateTime<Utc>,
+) -> bool {
+    snapshot.in_flight.get(worker).is_some_and(|in_flight| {
+        (now - in_flight.started_at)
+            .to_std()
+            .is_ok_and(|elapsed| elapsed >= threshold)
+    })
+}
+
+/// Runs long-lived workers in their own tasks, restarting them when they panic or stall, and
+/// keeps track of one-off background tasks, see [Supervisor::spawn]
+///
+/// Readiness, i.e. `GET /health`, reports unavailable while a worker is stalled, for the instance
+/// to stop receiving webhooks that would go nowhere, and the systemd watchdog stops being pinged.
+#[derive(Clone)]
+pub struct Supervisor {
+    cfg: SupervisorConfig,
+    debug_state: DebugState,
+    /// workers found stalled by their latest check
+    stalled: Arc<Mutex<HashSet<&'static str>>>,
+}
+
+impl Supervisor {
+    pub fn new(cfg: SupervisorConfig, debug_state: DebugState) -> Self {
+        Self {
+            cfg,
+            debug_state,
+            stalled: Arc::default(),
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.stalled.lock().unwrap().is_empty()
+    }
+
+    fn set_stalled(&self, worker: &'static str, stalled: bool) {
+        let mut workers = self.stalled.lock().unwrap();
+        if stalled {
+            workers.insert(worker);
+        } else {
+            workers.remove(worker);
+        }
+    }
+
+    /// Runs a one-off background task, e.g. a repository indexation, in its own task
+    ///
+    /// It isn't restarted, being resumable through its job instead, but its panic is logged,
+    /// counted and recorded as an error of `task` rather than lost with a detached task, and
+    /// `issue_bot_background_tasks` shows how many are running.
+    pub fn spawn<Fut>(&self, task: &'static str, fut: Fut)
+    where
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let debug_state = self.debug_state.clone();
+        let running = metrics::gauge!("issue_bot_background_tasks", "task" => task);
+        running.increment(1);
+        let handle = tokio::spawn(fut);
+        tokio::spawn(async move {
+            match handle.await {
+                Ok(()) => (),
+                Err(err) if err.is_panic() => {
+                    error!(task, err = err.to_string(), "background task panicked");
+                    metrics::counter!("issue_bot_background_task_panics_total", "task" => task)
+                        .increment(1);
+                    debug_state.record_error(task, format!("background task panicked: {err}"));
+                }
+                Err(err) => warn!(task, err = err.to_string(), "background task cancelled"),
+            }
+            running.decrement(1);
+        });
+    }
+
+    /// runs the worker `start` returns until it ends by itself or on shutdown, starting a new one
+    /// after `restart_backoff_secs` when it panics or stalls
+    ///
+    /// Stalled workers are aborted at their next `.await`, dropping the event they were handling.
+    pub async fn supervise<F, Fut>(&self, worker: &'static str, mut start: F) -> anyhow::Result<()>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let threshold = Duration::from_secs(self.cfg.stall_threshold_secs);
+        let mut ticker = interval(Duration::from_secs(self.cfg.check_interval_secs));
+        let shutdown = shutdown_signal();
+        pin_mut!(shutdown);
+        loop {
+            let mut handle = tokio::spawn(start());
+            let reason = loop {
+                select! {
+                    res = &mut handle => match res {
+                        Ok(()) => return Ok(()),
+                        Err(err) if err.is_panic() => {
+                            error!(worker, err = err.to_string(), "worker panicked, restarting");
+                            break "panic";
+                        }
+                        Err(err) => return Err(anyhow::anyhow!("{worker} worker failed: {err}")),
+                    },
+                    _ = ticker.tick() => {
+                        let stalled = is_stalled(&self.debug_state.snapshot(), worker, threshold, Utc::now());
+                        self.set_stalled(worker, stalled);
+                        if stalled {
+                            error!(
+                                worker,
+                                stall_threshold_secs = self.cfg.stall_threshold_secs,
+                                "worker stalled, restarting"
+                            );
+                            handle.abort();
+                            break "stall";
+                        }
+                    }
+                    _ = &mut shutdown => {
+                        handle.abort();
+                        return Ok(());
+                    }
+                }
+            };
+            metrics::counter!("issue_bot_worker_restarts_total", "worker" => worker, "reason" => reason)
+                .increment(1);
+            self.debug_state
+                .record_error(worker, format!("worker restarted after {reason}"));
+            self.debug_state.clear_in_flight(worker);
+            select! {
+                _ = sleep(Duration::from_secs(self.cfg.restart_backoff_secs)) => {
+                    warn!(worker, "restarting worker");
+                }
+                _ = &mut shutdown => return Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{TimeDelta, Utc};
+
+    use crate::debug::{DebugStateSnapshot, InFlightEvent};
+
+    use super::is_stalled;
+
+    #[test]
+    fn test_is_stalled() {
+        let now = Utc::now();
+        let threshold = Duration::from_secs(600);
+        let mut snapshot = DebugStateSnapshot::default();
+        assert!(!is_stalled(&snapshot, "webhooks", threshold, now));
+
+        snapshot.in_flight.insert(
+            "webhooks".to_owned(),
+            InFlightEvent {
+                event: "issue 42".to_owned(),
+                started_at: now - TimeDelta::seconds(30),
+            },
+        );
+        assert!(!is_stalled(&snapshot, "webhooks", threshold, now));
+        assert!(is_stalled(
+            &snapshot,
+            "webhooks",
+            threshold,
+            now + TimeDelta::seconds(600)
+        ));
+        assert!(!is_stalled(
+            &snapshot,
+            "indexation",
+            threshold,
+            now + TimeDelta::seconds(600)
+        ));
+    }
+}
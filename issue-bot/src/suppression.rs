@@ -0,0 +1,49 @@
+/// Comment command muting the bot on the commented issue, as does `POST /suppress`
+///
+/// Suppressed issues are still indexed and suggested on other issues, but the bot never comments
+/// on them nor mentions them in Slack.
+pub const MUTE_COMMAND: &str = "/lor-e mute";
+
+/// Comment command lifting [MUTE_COMMAND], as does `DELETE /suppress`
+pub const UNMUTE_COMMAND: &str = "/lor-e unmute";
+
+/// whether a line of `body` is the mute command, ignoring surrounding whitespace
+pub fn is_mute_command(body: &str) -> bool {
+    has_command(body, MUTE_COMMAND)
+}
+
+/// whether a line of `body` is the unmute command, ignoring surrounding whitespace
+pub fn is_unmute_command(body: &str) -> bool {
+    has_command(body, UNMUTE_COMMAND)
+}
+
+fn has_command(body: &str, command: &str) -> bool {
+    body.lines()
+        .any(|line| line.trim().eq_ignore_ascii_case(command))
+}
+
+/// whether a GitHub comment's `author_association` allows muting the bot
+pub fn is_maintainer(author_association: &str) -> bool {
+    matches!(author_association, "OWNER" | "MEMBER" | "COLLABORATOR")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_maintainer, is_mute_command, is_unmute_command};
+
+    #[test]
+    fn test_is_mute_command() {
+        assert!(is_mute_command("/lor-e mute"));
+        assert!(is_mute_command(
+            "thanks, not a duplicate\n\n  /lor-e MUTE  "
+        ));
+        assert!(!is_mute_command("please don't /lor-e mute this one"));
+        assert!(!is_mute_command("/lor-e muted"));
+        assert!(!is_mute_command("/lor-e unmute"));
+        assert!(is_unmute_command(" /lor-e unmute"));
+        assert!(!is_unmute_command("/lor-e mute"));
+
+        assert!(is_maintainer("MEMBER"));
+        assert!(!is_maintainer("CONTRIBUTOR"));
+    }
+}
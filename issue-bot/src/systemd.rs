@@ -0,0 +1,108 @@
+use std::{env, time::Duration};
+
+use futures::pin_mut;
+use tokio::{select, time::interval};
+use tracing::{info, warn};
+
+use crate::{shutdown_signal, supervisor::Supervisor};
+
+/// sends `state` to systemd, e.g. `READY=1`, doing nothing when not started by a
+/// `Type=notify` unit, see `man sd_notify`
+pub fn notify(state: &str) {
+    #[cfg(unix)]
+    {
+        let Some(socket) = env::var_os("NOTIFY_SOCKET") else {
+            return;
+        };
+        if let Err(err) = send(&socket, state) {
+            warn!(state, err = err.to_string(), "failed to notify systemd");
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = state;
+}
+
+#[cfg(unix)]
+fn send(socket: &std::ffi::OsStr, state: &str) -> std::io::Result<()> {
+    use std::os::unix::{ffi::OsStrExt, net::UnixDatagram};
+
+    let datagram = UnixDatagram::unbound()?;
+    match socket.as_bytes().strip_prefix(b"@") {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+
+            let addr = SocketAddr::from_abstract_name(name)?;
+            datagram.send_to_addr(state.as_bytes(), &addr)?;
+        }
+        _ => {
+            datagram.send_to(state.as_bytes(), socket)?;
+        }
+    }
+    Ok(())
+}
+
+/// how often systemd expects `WATCHDOG=1`, half of `WATCHDOG_USEC` to leave room for delays
+///
+/// `None` when the watchdog is off or meant for another process, per `WATCHDOG_PID`.
+fn watchdog_interval(usec: Option<&str>, pid: Option<&str>, own_pid: u32) -> Option<Duration> {
+    if pid.is_some_and(|pid| pid.parse::<u32>().ok() != Some(own_pid)) {
+        return None;
+    }
+    let usec: u64 = usec?.parse().ok().filter(|usec| *usec > 0)?;
+    Some(Duration::from_micros(usec / 2))
+}
+
+/// Pings the systemd watchdog while the runtime is responsive and no worker is stalled, see
+/// [Supervisor::is_ready], for hung instances to get restarted
+pub async fn start_watchdog(supervisor: Supervisor) -> anyhow::Result<()> {
+    let usec = env::var("WATCHDOG_USEC").ok();
+    let pid = env::var("WATCHDOG_PID").ok();
+    let Some(period) = watchdog_interval(usec.as_deref(), pid.as_deref(), std::process::id())
+    else {
+        return Ok(());
+    };
+
+    info!(
+        period_ms = period.as_millis() as u64,
+        "starting systemd watchdog"
+    );
+    let mut ticker = interval(period);
+    let shutdown = shutdown_signal();
+    pin_mut!(shutdown);
+    loop {
+        select! {
+            _ = ticker.tick() => {
+                if supervisor.is_ready() {
+                    notify("WATCHDOG=1");
+                } else {
+                    warn!("a worker is stalled, skipping systemd watchdog ping");
+                }
+            }
+            _ = &mut shutdown => break,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::watchdog_interval;
+
+    #[test]
+    fn test_watchdog_interval() {
+        assert_eq!(
+            watchdog_interval(Some("30000000"), None, 42),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(
+            watchdog_interval(Some("30000000"), Some("42"), 42),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(watchdog_interval(Some("30000000"), Some("7"), 42), None);
+        assert_eq!(watchdog_interval(Some("0"), None, 42), None);
+        assert_eq!(watchdog_interval(None, None, 42), None);
+    }
+}
@@ -0,0 +1,767 @@
+//! Mocks of the external services the bot talks to, served by axum on ephemeral local ports, so
+//! that whole flows can be tested without real credentials
+
+use std::{
+    env,
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{Method, StatusCode},
+    middleware::{self, Next},
+    response::Response,
+    routing::{get, post},
+    Json, Router,
+};
+use once_cell::sync::Lazy;
+use serde_json::json;
+use tokio::{net::TcpListener, time::Instant};
+
+use crate::{
+    config::{DatabaseConfig, IssueBotConfig, VectorSearchConfig},
+    storage::Database,
+};
+
+pub use crate::storage::EMBEDDING_DIMENSIONS;
+
+#[derive(Clone, Debug)]
+pub struct RecordedRequest {
+    pub method: Method,
+    pub path: String,
+    pub body: String,
+}
+
+#[derive(Clone, Default)]
+pub struct Recorder(Arc<Mutex<Vec<RecordedRequest>>>);
+
+impl Recorder {
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// first request received on `path`, waiting up to `timeout` for it
+    pub async fn wait_for(&self, method: Method, path: &str, timeout: Duration) -> RecordedRequest {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let request = self
+                .requests()
+                .into_iter()
+                .find(|r| r.method == method && r.path == path);
+            if let Some(request) = request {
+                return request;
+            }
+            if Instant::now() > deadline {
+                panic!(
+                    "no {method} {path} request received, got {:?}",
+                    self.requests()
+                );
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+}
+
+async fn record(State(recorder): State<Recorder>, req: Request, next: Next) -> Response {
+    let (parts, body) = req.into_parts();
+    let bytes = to_bytes(body, usize::MAX).await.unwrap();
+    recorder.0.lock().unwrap().push(RecordedRequest {
+        method: parts.method.clone(),
+        path: parts.uri.path().to_owned(),
+        body: String::from_utf8_lossy(&bytes).into_owned(),
+    });
+    next.run(Request::from_parts(parts, Body::from(bytes)))
+        .await
+}
+
+pub struct MockServer {
+    pub recorder: Recorder,
+    pub url: String,
+}
+
+impl MockServer {
+    /// serves `router` until the end of the test, recording every request it receives
+    pub async fn start(router: Router) -> Self {
+        let recorder = Recorder::default();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let app = router.layer(middleware::from_fn_with_state(recorder.clone(), record));
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        Self { recorder, url }
+    }
+}
+
+/// comments are created, the token has no classic scopes
+pub fn github() -> Router {
+    Router::new()
+        .route("/rate_limit", get(|| async { Json(json!({})) }))
+        .route(
+            "/repos/{owner}/{repo}/issues/{number}/comments",
+            post(|| async {
+                (
+                    StatusCode::CREATED,
+                    Json(json!({ "url": "https://api.github.com/repos/o/r/issues/comments/1" })),
+                )
+            }),
+        )
+}
+
+/// accepts comments on any discussion, served back without comments
+pub fn huggingface() -> Router {
+    Router::new().route(
+        "/{*path}",
+        get(|| async { Json(json!({ "status": "open", "events": [] })) })
+            .post(|| async { Json(json!({})) }),
+    )
+}
+
+/// every text is embedded as the same vector, so that all stored issues are perfect matches
+pub fn embeddings() -> Router {
+    Router::new().route(
+        "/v1/embeddings",
+        post(|| async {
+            Json(json!({ "data": [{ "embedding": vec![1.; EMBEDDING_DIMENSIONS] }] }))
+        }),
+    )
+}
+
+pub fn summarization(summary: &'static str) -> Router {
+    Router::new().route(
+        "/v1/chat/completions",
+        post(move || async move {
+            Json(json!({ "choices": [{ "message": { "content": summary, "role": "assistant" } }] }))
+        }),
+    )
+}
+
+pub fn slack() -> Router {
+    Router::new()
+        .route(
+            "/api/auth.test",
+            post(|| async { Json(json!({ "ok": true })) }),
+        )
+        .route(
+            "/api/chat.postMessage",
+            post(|| async { Json(json!({ "ok": true, "ts": "1700000000.000100" })) }),
+        )
+}
+
+pub struct MockServices {
+    pub embeddings: MockServer,
+    pub github: MockServer,
+    pub huggingface: MockServer,
+    pub slack: MockServer,
+    pub summarization: MockServer,
+}
+
+impl MockServices {
+    pub async fn start() -> Self {
+        Self {
+            embeddings: MockServer::start(embeddings()).await,
+            github: MockServer::start(github()).await,
+            huggingface: MockServer::start(huggingface()).await,
+            slack: MockServer::start(slack()).await,
+            summarization: MockServer::start(summarization("summary of the issue")).await,
+        }
+    }
+
+    /// `ISSUE_BOT_TEST` configuration pointed at the mocks, with comments enabled
+    pub fn config(&self) -> IssueBotConfig {
+        let mut config: IssueBotConfig = crate::config::load_config("ISSUE_BOT_TEST").unwrap();
+        config.embedding_api.url = self.embeddings.url.clone();
+        config.github_api.base_url = self.github.url.clone();
+        config.github_api.comments_enabled = true;
+        config.huggingface_api.base_url = self.huggingface.url.clone();
+        config.huggingface_api.comments_enabled = true;
+        config.slack.auth_test_url = format!("{}/api/auth.test", self.slack.url);
+        config.slack.chat_write_url = format!("{}/api/chat.postMessage", self.slack.url);
+        config.summarization_api.url = self.summarization.url.clone();
+        config
+    }
+}
+
+/// source id no other test, nor earlier run, has used, as the Postgres database of
+/// `TEST_DATABASE_URL` is shared
+pub fn unique_source_id() -> i64 {
+    static NEXT: Lazy<AtomicI64> = Lazy::new(|| {
+        let micros = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time after the epoch")
+            .as_micros() as i64;
+        AtomicI64::new(micros * 1000)
+    });
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Postgres when `TEST_DATABASE_URL` is set, e.g. to the docker-compose one, in memory SQLite
+/// otherwise
+pub async fn test_database() -> Database {
+    match env::var("TEST_DATABASE_URL") {
+        Ok(connection_string) => connect(connection_string).await,
+        Err(_) => test_db().await,
+    }
+}
+
+/// in memory SQLite, for the tests of a single module
+pub async fn test_db() -> Database {
+    connect("sqlite::memory:".to_owned()).await
+}
+
+async fn connect(connection_string: String) -> Database {
+    Database::connect(
+        &DatabaseConfig {
+            connection_string,
+            max_connections: 1,
+            read_replica: None,
+            vector_search: VectorSearchConfig::default(),
+        },
+        None,
+    )
+    .await
+    .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc, time::Duration};
+
+    use axum::{
+        extract::Path,
+        http::{HeaderMap, Method},
+        routing::get,
+        Json, Router,
+    };
+    use nanoid::nanoid;
+    use serde_json::json;
+    use tokio::{
+        sync::{mpsc, Mutex},
+        time::Instant,
+    };
+
+    use crate::{
+        archive::Archive,
+        backlinks::Backlinker,
+        butler::Butler,
+        check_runs::CheckRuns,
+        code_context::CodeContext,
+        comment_queue::{start_comment_queue, CommentQueue},
+        comment_trigger::CommentTrigger,
+        config::{EmailMode, IssueBotConfig, IssueState},
+        debounce::ReembedDebouncer,
+        debug::DebugState,
+        drift::EmbeddingDrift,
+        email::EmailNotifier,
+        embeddings::{inference_endpoints::EmbeddingApi, queue::EmbeddingQueue},
+        escalation::Escalation,
+        event_log::EventLog,
+        events::PipelineEvents,
+        extraction::Extractor,
+        fingerprint::Fingerprints,
+        footer::CommentFooter,
+        github::GithubApi,
+        guidance::Guidance,
+        handle_webhooks,
+        hot_issues::HotIssues,
+        huggingface::HuggingfaceApi,
+        issue_links::IssueLinks,
+        issue_text::IssueTextComposer,
+        knowledge_base::KnowledgeBase,
+        live_config::LiveConfig,
+        locks::Locks,
+        owners::Owners,
+        priority::Priorities,
+        redaction::Redactor,
+        repo_groups::RepoGroups,
+        repo_metadata::RepoMetadata,
+        resolutions::DuplicateResolutions,
+        retry::{RetryBudget, RetryPolicy},
+        settings::Settings,
+        slack::Slack,
+        storage::{Database, Storage},
+        summarization::SummarizationApi,
+        supervisor::Supervisor,
+        Action, EventData, IndexIssueData, IssueData, QueuedEvent, Source, WebhookContext,
+    };
+
+    use super::{
+        github, test_database, unique_source_id, MockServer, MockServices, EMBEDDING_DIMENSIONS,
+    };
+
+    /// repository of a single test, only its issues being suggested
+    fn repository() -> String {
+        format!("huggingface/test-harness-{}", nanoid!(8))
+    }
+
+    fn issue(
+        github_url: &str,
+        repository: &str,
+        source_id: i64,
+        number: i32,
+        title: &str,
+    ) -> IssueData {
+        IssueData {
+            source_id,
+            action: Action::Created,
+            author: None,
+            labels: Vec::new(),
+            milestone: None,
+            title: title.to_owned(),
+            body: "the model fails to load".to_owned(),
+            is_pull_request: false,
+            number,
+            html_url: format!("https://github.com/{repository}/issues/{number}"),
+            url: format!("{github_url}/repos/{repository}/issues/{number}"),
+            repository_full_name: repository.to_owned(),
+            source: Source::Github,
+        }
+    }
+
+    /// [handle_webhooks] and the comment queue running against the mocks, fed through the
+    /// returned sender, along with the context the handlers share
+    async fn spawn_webhooks(
+        config: IssueBotConfig,
+        db: Database,
+    ) -> (mpsc::Sender<QueuedEvent>, WebhookContext) {
+        let debug_state = DebugState::default();
+        let footer = CommentFooter::new(&config.feedback, config.embedding_api.model.clone());
+        let live_config = LiveConfig::new((&config).into());
+        let embedding_api = EmbeddingApi::new(
+            config.embedding_api.clone(),
+            &config.http_client,
+            &config.timeouts,
+            debug_state.clone(),
+            RetryBudget::default(),
+        )
+        .unwrap();
+        let embedding_queue = EmbeddingQueue::new(&config.embedding_api, embedding_api);
+        let github_api = GithubApi::new(
+            config.github_api,
+            &config.http_client,
+            &config.timeouts,
+            live_config.clone(),
+            footer.clone(),
+        )
+        .unwrap();
+        let huggingface_api = HuggingfaceApi::new(
+            config.huggingface_api,
+            &config.http_client,
+            &config.timeouts,
+            live_config.clone(),
+            footer,
+        )
+        .unwrap();
+        let locks = Locks::new(db.clone());
+        let slack = Slack::new(
+            &config.slack,
+            &config.http_client,
+            &config.timeouts,
+            live_config.clone(),
+            None,
+        )
+        .unwrap();
+        let comment_queue = CommentQueue::new(
+            config.comment_queue,
+            db.clone(),
+            github_api.clone(),
+            huggingface_api.clone(),
+            locks.clone(),
+            slack.clone(),
+        );
+        let backlinker = Backlinker::new(
+            config.backlinks,
+            comment_queue.clone(),
+            db.clone(),
+            github_api.clone(),
+            &config.repositories,
+        );
+        let comment_trigger = CommentTrigger::new(
+            config.comment_trigger,
+            comment_queue.clone(),
+            db.clone(),
+            embedding_queue.clone(),
+            github_api.clone(),
+            huggingface_api.clone(),
+            RepoGroups::new(&config.repo_groups).unwrap(),
+            HashMap::new(),
+            Settings::new(live_config.clone(), db.clone()),
+        );
+        let (tx, rx) = mpsc::channel(8);
+        let ctx = WebhookContext {
+            archive: Archive::new(config.archive, db.clone()).unwrap(),
+            backlinker,
+            butler: Butler::new(
+                config.butler,
+                github_api.clone(),
+                db.clone(),
+                debug_state.clone(),
+            ),
+            check_runs: CheckRuns::new(config.check_runs, db.clone(), github_api.clone()),
+            code_context: CodeContext::new(config.code_context, github_api.clone()),
+            comment_queue: comment_queue.clone(),
+            comment_trigger,
+            debouncer: ReembedDebouncer::new(config.reembed),
+            debug_state: debug_state.clone(),
+            diversity_cfg: config.diversity,
+            drift: EmbeddingDrift::new(
+                config.drift,
+                db.clone(),
+                config.embedding_api.model.clone(),
+            ),
+            duplicate_resolutions: DuplicateResolutions::new(db.clone(), github_api.clone()),
+            email: EmailNotifier::new(&config.email).unwrap(),
+            embedding_queue: embedding_queue.clone(),
+            escalation: Escalation::new(config.escalation, db.clone(), slack.clone()),
+            event_log: EventLog::new(config.event_log.clone(), db.clone(), debug_state.clone()),
+            events: PipelineEvents::default(),
+            extractor: Extractor::new(config.extraction, db.clone(), debug_state.clone()),
+            fingerprints: Fingerprints::new(config.fingerprints, db.clone()),
+            github_api: github_api.clone(),
+            guidance: Guidance::new(
+                config.guidance,
+                db.clone(),
+                debug_state.clone(),
+                embedding_queue.clone(),
+                github_api.clone(),
+                locks.clone(),
+            ),
+            hot_issues: HotIssues::new(&config.hot_issues, db.clone()),
+            huggingface_api,
+            issue_links: IssueLinks::new(
+                config.issue_links,
+                db.clone(),
+                debug_state.clone(),
+                github_api.clone(),
+            ),
+            issue_text: IssueTextComposer::new(&config.issue_text),
+            knowledge_base: KnowledgeBase::new(config.knowledge_base, db.clone(), embedding_queue),
+            owners: Owners::new(config.owners, github_api.clone(), HashMap::new()),
+            priorities: Priorities::new(config.priority, slack.clone()).unwrap(),
+            redactor: Redactor::new(&config.redaction).unwrap(),
+            repo_groups: RepoGroups::new(&config.repo_groups).unwrap(),
+            repo_metadata: RepoMetadata::new(
+                config.repo_metadata,
+                db.clone(),
+                debug_state.clone(),
+                github_api,
+                locks.clone(),
+            ),
+            repositories: HashMap::new(),
+            retry_policy: RetryPolicy::new(RetryBudget::default()),
+            settings: Settings::new(live_config.clone(), db.clone()),
+            slack,
+            summarization_api: SummarizationApi::new(
+                config.summarization_api,
+                &config.http_client,
+                &config.timeouts,
+                &HashMap::new(),
+            )
+            .unwrap(),
+            supervisor: Supervisor::new(config.supervisor, debug_state),
+            db,
+            locks,
+        };
+        tokio::spawn(handle_webhooks(Arc::new(Mutex::new(rx)), ctx.clone()));
+        tokio::spawn(start_comment_queue(comment_queue));
+        (tx, ctx)
+    }
+
+    #[tokio::test]
+    async fn test_new_issue_flow() {
+        let mocks = MockServices::start().await;
+        let mut config = mocks.config();
+        config.comment_queue.min_interval_secs = 0;
+        let db = test_database().await;
+        let repository = repository();
+        let older = issue(
+            &mocks.github.url,
+            &repository,
+            unique_source_id(),
+            1,
+            "cannot load model",
+        );
+        db.insert_issue(&older, &vec![1.; EMBEDDING_DIMENSIONS], None)
+            .await
+            .unwrap();
+
+        let (tx, _) = spawn_webhooks(config, db.clone()).await;
+
+        let new_issue = issue(
+            &mocks.github.url,
+            &repository,
+            unique_source_id(),
+            2,
+            "model does not load",
+        );
+        tx.send(QueuedEvent {
+            data: EventData::Issue(new_issue),
+            request_id: "new-issue".to_owned(),
+            cursor_update: None,
+        })
+        .await
+        .unwrap();
+        let mut discussion = issue(
+            &mocks.huggingface.url,
+            &repository,
+            unique_source_id(),
+            3,
+            "loading fails",
+        );
+        discussion.url = "https://huggingface.co/api/models/org/model/discussions/3".to_owned();
+        discussion.source = Source::HuggingFace;
+        tx.send(QueuedEvent {
+            data: EventData::Issue(discussion),
+            request_id: "new-discussion".to_owned(),
+            cursor_update: None,
+        })
+        .await
+        .unwrap();
+
+        let timeout = Duration::from_secs(10);
+        let notification = mocks
+            .slack
+            .recorder
+            .wait_for(Method::POST, "/api/chat.postMessage", timeout)
+            .await;
+        assert!(notification.body.contains("summary of the issue"));
+        let comment = mocks
+            .github
+            .recorder
+            .wait_for(
+                Method::POST,
+                &format!("/repos/{repository}/issues/2/comments"),
+                timeout,
+            )
+            .await;
+        assert!(comment
+            .body
+            .contains(&format!("https://github.com/{repository}/issues/1")));
+        mocks
+            .huggingface
+            .recorder
+            .wait_for(
+                Method::POST,
+                "/api/models/org/model/discussions/3/comment",
+                timeout,
+            )
+            .await;
+    }
+
+    /// pull request served by the GitHub mock in [test_pull_request_edit_flow]
+    async fn edited_pull_request(
+        source_id: i64,
+        headers: HeaderMap,
+        Path((owner, repo, number)): Path<(String, String, i32)>,
+    ) -> Json<serde_json::Value> {
+        let host = headers["host"].to_str().unwrap();
+        let base = format!("http://{host}/repos/{owner}/{repo}");
+        let html_url = format!("https://github.com/{owner}/{repo}/pull/{number}");
+        Json(json!({
+            "body": "loading fails with a sharded checkpoint",
+            "comments_url": format!("{base}/issues/{number}/comments"),
+            "html_url": html_url,
+            "id": source_id,
+            "number": number,
+            "pull_request": { "html_url": html_url, "url": format!("{base}/pulls/{number}") },
+            "title": "fix loading sharded checkpoints",
+            "url": format!("{base}/issues/{number}"),
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_pull_request_edit_flow() {
+        let mut mocks = MockServices::start().await;
+        let source_id = unique_source_id();
+        // an edited pull request, served without comments
+        let router = github()
+            .route(
+                "/repos/{owner}/{repo}/issues/{number}",
+                get(move |headers, path| edited_pull_request(source_id, headers, path)),
+            )
+            .route(
+                "/repos/{owner}/{repo}/issues/{number}/comments",
+                get(|| async { Json(json!([])) }),
+            )
+            .route(
+                "/repos/{owner}/{repo}/pulls/{number}/comments",
+                get(|| async {
+                    (
+                        [
+                            ("x-ratelimit-remaining", "5000"),
+                            ("x-ratelimit-reset", "0"),
+                        ],
+                        Json(json!([])),
+                    )
+                }),
+            );
+        mocks.github = MockServer::start(router).await;
+        let config = mocks.config();
+        let db = test_database().await;
+        let repository = repository();
+        let mut pull_request = issue(&mocks.github.url, &repository, source_id, 4, "draft fix");
+        pull_request.is_pull_request = true;
+        db.insert_issue(&pull_request, &vec![1.; EMBEDDING_DIMENSIONS], None)
+            .await
+            .unwrap();
+
+        let (tx, _) = spawn_webhooks(config, db.clone()).await;
+        tx.send(QueuedEvent {
+            data: EventData::IssueIndexation(IndexIssueData {
+                issue_number: 4,
+                repository_full_name: repository,
+                head_sha: None,
+            }),
+            request_id: "edited-pull-request".to_owned(),
+            cursor_update: None,
+        })
+        .await
+        .unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while db.embedding_metadata(source_id).await.unwrap().is_none() {
+            assert!(
+                Instant::now() < deadline,
+                "pull request embedding not updated"
+            );
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let stored = db.issue_text(source_id).await.unwrap();
+        assert_eq!(stored.title, "fix loading sharded checkpoints");
+        assert_eq!(stored.body, "loading fails with a sharded checkpoint");
+    }
+
+    #[tokio::test]
+    async fn test_hydrated_discussion_flow() {
+        let mut mocks = MockServices::start().await;
+        // a discussion closed since, with a reply the webhook lacks
+        mocks.huggingface = MockServer::start(Router::new().route(
+            "/api/models/org/model/discussions/{number}",
+            get(|| async {
+                Json(json!({
+                    "author": { "_id": "1" },
+                    "status": "closed",
+                    "title": "loading fails",
+                    "events": [
+                        {
+                            "type": "comment",
+                            "id": 1,
+                            "author": { "_id": "1" },
+                            "data": { "latest": { "raw": "the model fails to load" } }
+                        },
+                        {
+                            "type": "comment",
+                            "id": 2,
+                            "author": { "_id": "2" },
+                            "data": { "latest": { "raw": "fixed by upgrading safetensors" } }
+                        },
+                    ]
+                }))
+            }),
+        ))
+        .await;
+        let mut config = mocks.config();
+        config.huggingface_api.hydrate_discussions = true;
+        let db = test_database().await;
+        let repository = repository();
+        let source_id = unique_source_id();
+        let mut discussion = issue(&mocks.huggingface.url, &repository, source_id, 7, "loading");
+        discussion.url = "https://huggingface.co/api/models/org/model/discussions/7".to_owned();
+        discussion.source = Source::HuggingFace;
+        db.insert_issue(&discussion, &vec![1.; EMBEDDING_DIMENSIONS], None)
+            .await
+            .unwrap();
+
+        let (tx, _) = spawn_webhooks(config, db.clone()).await;
+        discussion.action = Action::Edited;
+        tx.send(QueuedEvent {
+            data: EventData::Issue(discussion),
+            request_id: "edited-discussion".to_owned(),
+            cursor_update: None,
+        })
+        .await
+        .unwrap();
+
+        // the status is stored after the comments, and SQLite only tells the discussion was updated
+        // before now once a second went by
+        let closed = || async {
+            db.stale_issues(Some(&repository), 0, &[], Some(IssueState::Closed))
+                .await
+                .unwrap()
+        };
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while closed().await.is_empty() {
+            assert!(Instant::now() < deadline, "discussion status not stored");
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(closed().await, vec![source_id]);
+        let stored = db.issue_text(source_id).await.unwrap();
+        assert_eq!(stored.title, "loading fails");
+        assert_eq!(stored.comments, vec!["fixed by upgrading safetensors"]);
+    }
+
+    #[tokio::test]
+    async fn test_suppressed_issue_flow() {
+        let mocks = MockServices::start().await;
+        let mut config = mocks.config();
+        config.comment_queue.min_interval_secs = 0;
+        config.email.enabled = true;
+        config.email.mode = EmailMode::Daily;
+        config.email.from = "lor-e <lor-e@example.com>".to_owned();
+        config.email.recipients = vec!["maintainers@example.com".to_owned()];
+        config.email.smtp_host = "localhost".to_owned();
+        let db = test_database().await;
+        let repository = repository();
+        let older = issue(
+            &mocks.github.url,
+            &repository,
+            unique_source_id(),
+            1,
+            "cannot load model",
+        );
+        db.insert_issue(&older, &vec![1.; EMBEDDING_DIMENSIONS], None)
+            .await
+            .unwrap();
+        let suppressed = unique_source_id();
+        db.suppress_issue(suppressed, "api").await.unwrap();
+
+        let (tx, ctx) = spawn_webhooks(config, db.clone()).await;
+        for (source_id, number) in [(suppressed, 5), (unique_source_id(), 6)] {
+            tx.send(QueuedEvent {
+                data: EventData::Issue(issue(
+                    &mocks.github.url,
+                    &repository,
+                    source_id,
+                    number,
+                    "model does not load",
+                )),
+                request_id: format!("new-issue-{number}"),
+                cursor_update: None,
+            })
+            .await
+            .unwrap();
+        }
+
+        // events are handled in order, the suppressed issue is done with once the other is
+        // commented on
+        mocks
+            .github
+            .recorder
+            .wait_for(
+                Method::POST,
+                &format!("/repos/{repository}/issues/6/comments"),
+                Duration::from_secs(10),
+            )
+            .await;
+        assert!(!mocks
+            .github
+            .recorder
+            .requests()
+            .iter()
+            .any(|r| r.path == format!("/repos/{repository}/issues/5/comments")));
+        assert_eq!(ctx.email.pending_issues(), 1);
+    }
+}
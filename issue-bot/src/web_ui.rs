@@ -0,0 +1,349 @@
+use std::fmt::Write;
+
+use axum::{
+    extract::{FromRef, FromRequestParts, Query, State},
+    http::{
+        header::{AUTHORIZATION, WWW_AUTHENTICATE},
+        request::Parts,
+        StatusCode,
+    },
+    response::{Html, IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::{
+    api_keys::Scope,
+    config::WebUiConfig,
+    debug::DebugState,
+    embeddings::queue::{EmbeddingQueue, Priority},
+    errors::ApiError,
+    storage::{Database, Storage},
+    AppState,
+};
+
+const SEARCH_LIMIT: i64 = 20;
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// password of a basic `Authorization` header, the user name is ignored
+fn basic_auth_password(header: &str) -> Option<String> {
+    let credentials = BASE64_STANDARD
+        .decode(header.strip_prefix("Basic ")?)
+        .ok()?;
+    let credentials = String::from_utf8(credentials).ok()?;
+    credentials
+        .split_once(':')
+        .map(|(_, password)| password.to_owned())
+}
+
+fn page(title: &str, body: &str) -> Html<String> {
+    Html(format!(
+        r#"<!doctype html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title} - issue bot</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em auto; max-width: 960px; }}
+nav a {{ margin-right: 1em; }}
+table {{ border-collapse: collapse; width: 100%; }}
+td, th {{ border-bottom: 1px solid #ddd; padding: 0.3em; text-align: left; vertical-align: top; }}
+</style>
+</head>
+<body>
+<nav><a href="/ui">Suggestions</a><a href="/ui/search">Search</a><a href="/ui/repositories">Repositories</a><a href="/ui/jobs">Jobs</a></nav>
+<h1>{title}</h1>
+{body}
+</body>
+</html>"#,
+        title = escape(title),
+    ))
+}
+
+fn issue_link(html_url: &str, repository_full_name: &str, number: i32, title: &str) -> String {
+    format!(
+        r#"<a href="{}">{}#{}</a> {}"#,
+        escape(html_url),
+        escape(repository_full_name),
+        number,
+        escape(title)
+    )
+}
+
+fn format_date(at: &DateTime<Utc>) -> String {
+    at.format("%Y-%m-%d %H:%M:%S UTC").to_string()
+}
+
+/// Read-only HTML pages for teammates to inspect the bot from a browser
+#[derive(Clone)]
+pub struct WebUi {
+    cfg: WebUiConfig,
+    db: Database,
+    debug_state: DebugState,
+    embedding_queue: EmbeddingQueue,
+}
+
+impl WebUi {
+    pub fn new(
+        cfg: WebUiConfig,
+        db: Database,
+        debug_state: DebugState,
+        embedding_queue: EmbeddingQueue,
+    ) -> Self {
+        Self {
+            cfg,
+            db,
+            debug_state,
+            embedding_queue,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.cfg.enabled
+    }
+}
+
+/// Requires the admin token, or an API key with the admin scope, as the basic auth password so
+/// that browsers prompt for it
+pub struct UiAuth;
+
+impl<S> FromRequestParts<S> for UiAuth
+where
+    AppState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let state = AppState::from_ref(state);
+        let password = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|header| header.to_str().ok())
+            .and_then(basic_auth_password);
+        if let Some(password) = password {
+            match state.api_keys.authorize(&password, Scope::Admin).await {
+                Ok(true) => return Ok(Self),
+                Ok(false) => (),
+                Err(err) => return Err(ApiError::from(err).into_response()),
+            }
+        }
+        Err((
+            StatusCode::UNAUTHORIZED,
+            [(WWW_AUTHENTICATE, r#"Basic realm="issue-bot""#)],
+        )
+            .into_response())
+    }
+}
+
+pub fn router(enabled: bool) -> Router<AppState> {
+    if !enabled {
+        return Router::new();
+    }
+    Router::new()
+        .route("/ui", get(suggestions))
+        .route("/ui/search", get(search))
+        .route("/ui/repositories", get(repositories))
+        .route("/ui/jobs", get(jobs))
+}
+
+async fn suggestions(_: UiAuth, State(state): State<AppState>) -> Result<Html<String>, ApiError> {
+    let web_ui = &state.web_ui;
+    let rows = web_ui
+        .db
+        .recent_suggestions(web_ui.cfg.recent_suggestions as i64)
+        .await?;
+    if rows.is_empty() {
+        return Ok(page(
+            "Recent suggestions",
+            "<p>No suggestions posted yet.</p>",
+        ));
+    }
+    let mut body = String::from("<table><tr><th>At</th><th>Issue</th><th>Suggested</th></tr>");
+    for suggestions in rows.chunk_by(|a, b| a.issue_source_id == b.issue_source_id) {
+        let issue = &suggestions[0];
+        let suggested = suggestions
+            .iter()
+            .map(|s| {
+                format!(
+                    "{} ({:.3})",
+                    issue_link(&s.html_url, &s.repository_full_name, s.number, &s.title),
+                    s.cosine_similarity
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("<br>");
+        let _ = write!(
+            body,
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+            format_date(&issue.created_at),
+            issue_link(
+                &issue.issue_html_url,
+                &issue.issue_repository_full_name,
+                issue.issue_number,
+                &issue.issue_title
+            ),
+            suggested
+        );
+    }
+    body.push_str("</table>");
+    Ok(page("Recent suggestions", &body))
+}
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    #[serde(default)]
+    q: String,
+    /// searches every repository when unset
+    repository: Option<String>,
+}
+
+async fn search(
+    _: UiAuth,
+    State(state): State<AppState>,
+    Query(query): Query<SearchQuery>,
+) -> Result<Html<String>, ApiError> {
+    let web_ui = &state.web_ui;
+    let repository = query.repository.filter(|r| !r.is_empty());
+    let mut body = format!(
+        r#"<form action="/ui/search"><input name="q" size="60" value="{}"> <input name="repository" placeholder="owner/repository" value="{}"> <button>Search</button></form>"#,
+        escape(&query.q),
+        escape(repository.as_deref().unwrap_or_default())
+    );
+    if query.q.trim().is_empty() {
+        return Ok(page("Search", &body));
+    }
+    let repositories: Vec<String> = repository.into_iter().collect();
+    let embedding = web_ui
+        .embedding_queue
+        .generate_embedding(query.q, Priority::Interactive)
+        .await?;
+    let issues = web_ui
+        .db
+        .search_issues(&embedding, &repositories, None, None, SEARCH_LIMIT, false)
+        .await?;
+    body.push_str("<table><tr><th>Similarity</th><th>Issue</th><th>Labels</th></tr>");
+    for issue in issues {
+        let _ = write!(
+            body,
+            "<tr><td>{:.3}</td><td>{}</td><td>{}</td></tr>",
+            issue.cosine_similarity,
+            issue_link(
+                &issue.html_url,
+                &issue.repository_full_name,
+                issue.number,
+                &issue.title
+            ),
+            escape(&issue.labels.join(", "))
+        );
+    }
+    body.push_str("</table>");
+    Ok(page("Search", &body))
+}
+
+async fn repositories(_: UiAuth, State(state): State<AppState>) -> Result<Html<String>, ApiError> {
+    let stats = state.web_ui.db.repository_stats().await?;
+    let mut body = String::from(
+        "<table><tr><th>Repository</th><th>Issues</th><th>Pull requests</th><th>Excluded</th></tr>",
+    );
+    for s in stats {
+        let _ = write!(
+            body,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape(&s.repository_full_name),
+            s.issues,
+            s.pull_requests,
+            s.excluded
+        );
+    }
+    body.push_str("</table>");
+    Ok(page("Repositories", &body))
+}
+
+async fn jobs(_: UiAuth, State(state): State<AppState>) -> Html<String> {
+    let snapshot = state.web_ui.debug_state.snapshot();
+    let mut body = format!(
+        "<p>Webhook queue: {} / {}</p><h2>Indexations</h2>",
+        state.tx.max_capacity() - state.tx.capacity(),
+        state.tx.max_capacity()
+    );
+    if snapshot.indexations.is_empty() {
+        body.push_str("<p>None running.</p>");
+    } else {
+        body.push_str(
+            "<table><tr><th>Name</th><th>Processed</th><th>Started</th><th>Updated</th></tr>",
+        );
+        for (name, progress) in &snapshot.indexations {
+            let processed = match progress.total {
+                Some(total) => format!("{} / {total}", progress.processed),
+                None => progress.processed.to_string(),
+            };
+            let _ = write!(
+                body,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape(name),
+                processed,
+                format_date(&progress.started_at),
+                format_date(&progress.updated_at)
+            );
+        }
+        body.push_str("</table>");
+    }
+    body.push_str("<h2>In flight</h2><table>");
+    for (worker, event) in &snapshot.in_flight {
+        let _ = write!(
+            body,
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape(worker),
+            escape(&event.event),
+            format_date(&event.started_at)
+        );
+    }
+    body.push_str("</table><h2>Open circuit breakers</h2><table>");
+    for (subsystem, since) in &snapshot.circuit_breakers {
+        let _ = write!(
+            body,
+            "<tr><td>{}</td><td>since {}</td></tr>",
+            escape(subsystem),
+            format_date(since)
+        );
+    }
+    body.push_str("</table><h2>Last errors</h2><table>");
+    for (subsystem, err) in &snapshot.last_errors {
+        let _ = write!(
+            body,
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape(subsystem),
+            escape(&err.message),
+            format_date(&err.at)
+        );
+    }
+    body.push_str("</table>");
+    page("Jobs", &body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::basic_auth_password;
+
+    #[test]
+    fn test_basic_auth_password() {
+        // admin:s3cret:x
+        assert_eq!(
+            basic_auth_password("Basic YWRtaW46czNjcmV0Ong="),
+            Some("s3cret:x".to_owned())
+        );
+        assert_eq!(basic_auth_password("Bearer s3cret"), None);
+        assert_eq!(basic_auth_password("Basic not base64!"), None);
+    }
+}
@@ -0,0 +1,245 @@
+use serde::Serialize;
+use thiserror::Error;
+use tracing::info;
+
+use crate::{
+    github::{
+        GithubApi, GithubApiError, HookDelivery, RepositoryHook, RepositoryHookBody,
+        RepositoryHookBodyConfig,
+    },
+    routes::GITHUB_WEBHOOK_EVENTS,
+};
+
+#[derive(Debug, Error)]
+pub enum WebhookCheckError {
+    #[error("github api error: {0}")]
+    GithubApi(#[from] GithubApiError),
+    #[error("github_api.webhook_url is not set")]
+    MissingUrl,
+}
+
+/// What keeps the webhook of a repository from delivering events to the bot
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "problem")]
+pub enum WebhookProblem {
+    /// no webhook points to the bot's url
+    Missing,
+    Inactive,
+    /// payloads must be sent as JSON
+    ContentType {
+        found: Option<String>,
+    },
+    MissingEvents {
+        events: Vec<String>,
+    },
+    /// unsigned payloads are rejected
+    NoSecret,
+    /// the bot answered the latest delivery with a 401, the webhook signs payloads with another
+    /// secret than `auth_token`
+    RejectedDelivery {
+        delivery_id: u64,
+    },
+}
+
+impl WebhookProblem {
+    /// fixed by setting the webhook's secret to `auth_token`
+    fn needs_secret(&self) -> bool {
+        matches!(
+            self,
+            Self::Missing | Self::NoSecret | Self::RejectedDelivery { .. }
+        )
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct WebhookReport {
+    pub repository_full_name: String,
+    pub hook_id: Option<u64>,
+    pub problems: Vec<WebhookProblem>,
+    /// the webhook was created or updated to fix `problems`
+    pub fixed: bool,
+}
+
+/// Checks that repositories have a webhook sending the events the bot handles to
+/// [crate::config::GithubApiConfig::webhook_url], creating or fixing it on demand
+///
+/// GitHub never returns secrets: a webhook signing payloads with another secret than `auth_token`
+/// is told apart by its latest delivery being rejected. Fixing a webhook only resets its secret when
+/// it is missing, unset or rejected.
+#[derive(Clone)]
+pub struct WebhookChecker {
+    github_api: GithubApi,
+    secret: String,
+    url: Option<String>,
+}
+
+fn same_url(a: &str, b: &str) -> bool {
+    a.trim_end_matches('/') == b.trim_end_matches('/')
+}
+
+fn problems(
+    hook: Option<&RepositoryHook>,
+    latest_delivery: Option<&HookDelivery>,
+) -> Vec<WebhookProblem> {
+    let Some(hook) = hook else {
+        return vec![WebhookProblem::Missing];
+    };
+    let mut problems = Vec::new();
+    if !hook.active {
+        problems.push(WebhookProblem::Inactive);
+    }
+    if hook.config.content_type.as_deref() != Some("json") {
+        problems.push(WebhookProblem::ContentType {
+            found: hook.config.content_type.clone(),
+        });
+    }
+    // `*` subscribes to every event
+    let missing_events: Vec<String> = GITHUB_WEBHOOK_EVENTS
+        .iter()
+        .filter(|event| !hook.events.iter().any(|e| e == *event || e == "*"))
+        .map(|event| event.to_string())
+        .collect();
+    if !missing_events.is_empty() {
+        problems.push(WebhookProblem::MissingEvents {
+            events: missing_events,
+        });
+    }
+    if hook.config.secret.as_deref().is_none_or(str::is_empty) {
+        problems.push(WebhookProblem::NoSecret);
+    } else if let Some(delivery) = latest_delivery.filter(|d| d.status_code == 401) {
+        problems.push(WebhookProblem::RejectedDelivery {
+            delivery_id: delivery.id,
+        });
+    }
+    problems
+}
+
+impl WebhookChecker {
+    pub fn new(github_api: GithubApi, secret: String, url: Option<String>) -> Self {
+        Self {
+            github_api,
+            secret,
+            url,
+        }
+    }
+
+    pub async fn check(
+        &self,
+        repository_full_name: &str,
+        fix: bool,
+    ) -> Result<WebhookReport, WebhookCheckError> {
+        let url = self.url.as_deref().ok_or(WebhookCheckError::MissingUrl)?;
+        let hooks = self
+            .github_api
+            .repository_hooks(repository_full_name)
+            .await?;
+        let hook = hooks
+            .iter()
+            .find(|hook| hook.config.url.as_deref().is_some_and(|u| same_url(u, url)));
+        let deliveries = match hook {
+            Some(hook) => {
+                self.github_api
+                    .hook_deliveries(repository_full_name, hook.id)
+                    .await?
+            }
+            None => Vec::new(),
+        };
+        let problems = problems(hook, deliveries.first());
+        let mut report = WebhookReport {
+            repository_full_name: repository_full_name.to_owned(),
+            hook_id: hook.map(|hook| hook.id),
+            problems,
+            fixed: false,
+        };
+        if !fix || report.problems.is_empty() {
+            return Ok(report);
+        }
+
+        // events the webhook already sends are kept
+        let mut events: Vec<String> = hook.map(|hook| hook.events.clone()).unwrap_or_default();
+        for event in GITHUB_WEBHOOK_EVENTS {
+            if !events.iter().any(|e| e == event || e == "*") {
+                events.push(event.to_owned());
+            }
+        }
+        let body = RepositoryHookBody {
+            name: hook.is_none().then_some("web"),
+            active: true,
+            events,
+            config: RepositoryHookBodyConfig {
+                url,
+                content_type: "json",
+                secret: report
+                    .problems
+                    .iter()
+                    .any(WebhookProblem::needs_secret)
+                    .then_some(self.secret.as_str()),
+            },
+        };
+        let saved = self
+            .github_api
+            .save_repository_hook(repository_full_name, report.hook_id, &body)
+            .await?;
+        info!(
+            repository_full_name,
+            hook_id = saved.id,
+            "fixed repository webhook"
+        );
+        report.hook_id = Some(saved.id);
+        report.fixed = true;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::github::{HookDelivery, RepositoryHook, RepositoryHookConfig};
+
+    use super::{problems, WebhookProblem};
+
+    #[test]
+    fn test_webhook_problems() {
+        assert_eq!(problems(None, None), vec![WebhookProblem::Missing]);
+
+        let mut hook = RepositoryHook {
+            id: 1,
+            active: true,
+            events: vec!["*".to_owned()],
+            config: RepositoryHookConfig {
+                url: Some("https://lor-e.example.com/event/github".to_owned()),
+                content_type: Some("json".to_owned()),
+                secret: Some("********".to_owned()),
+            },
+        };
+        assert!(problems(Some(&hook), None).is_empty());
+        let rejected = HookDelivery {
+            id: 7,
+            status_code: 401,
+        };
+        assert_eq!(
+            problems(Some(&hook), Some(&rejected)),
+            vec![WebhookProblem::RejectedDelivery { delivery_id: 7 }]
+        );
+
+        hook.active = false;
+        hook.events = vec!["issues".to_owned(), "pull_request".to_owned()];
+        hook.config.content_type = Some("form".to_owned());
+        hook.config.secret = None;
+        assert_eq!(
+            problems(Some(&hook), Some(&rejected)),
+            vec![
+                WebhookProblem::Inactive,
+                WebhookProblem::ContentType {
+                    found: Some("form".to_owned())
+                },
+                WebhookProblem::MissingEvents {
+                    events: vec![
+                        "issue_comment".to_owned(),
+                        "pull_request_review_comment".to_owned()
+                    ]
+                },
+                WebhookProblem::NoSecret,
+            ]
+        );
+    }
+}
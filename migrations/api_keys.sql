@@ -0,0 +1,12 @@
+-- Adds the scoped API keys, see `api_keys`.
+-- Only needed for Postgres, SQLite databases get them when connecting, e.g.
+-- `psql -f migrations/api_keys.sql`.
+
+CREATE TABLE api_keys (
+  id SERIAL PRIMARY KEY,
+  name VARCHAR NOT NULL,
+  key_hash VARCHAR NOT NULL UNIQUE,
+  scopes VARCHAR NOT NULL,
+  expires_at timestamp with time zone,
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
@@ -0,0 +1,13 @@
+-- Adds the table deleted issues are copied to before being removed, see `archive`.
+-- Only needed for Postgres, SQLite databases get them when connecting, e.g.
+-- `psql -f migrations/archived_issues.sql`.
+
+CREATE TABLE archived_issues (
+  id SERIAL PRIMARY KEY,
+  source_id BIGINT NOT NULL,
+  repository_full_name VARCHAR NOT NULL,
+  data JSONB NOT NULL,
+  archived_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX archived_issues_source_id_idx ON archived_issues (source_id);
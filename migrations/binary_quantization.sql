@@ -0,0 +1,5 @@
+-- Adds the index used by the `binary` vector search quantization, see `Quantization::Binary`.
+-- Only needed for Postgres databases searched with it, e.g.
+-- `psql -f migrations/binary_quantization.sql`.
+
+CREATE INDEX CONCURRENTLY IF NOT EXISTS issues_embedding_binary_hnsw_idx ON issues USING hnsw ((binary_quantize(embedding)::bit(2560)) bit_hamming_ops);
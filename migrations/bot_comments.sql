@@ -0,0 +1,23 @@
+-- Adds the comments the bot keeps up to date on GitHub issues and their revisions, see
+-- `comment_queue.update_in_place`.
+-- Only needed for Postgres, SQLite databases get them when connecting, e.g.
+-- `psql -f migrations/bot_comments.sql`.
+
+ALTER TABLE pending_comments ADD COLUMN suggestions BOOLEAN NOT NULL DEFAULT false;
+
+CREATE TABLE bot_comments (
+  issue_url VARCHAR PRIMARY KEY,
+  comment_url VARCHAR NOT NULL,
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC'),
+  updated_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE TABLE bot_comment_revisions (
+  id SERIAL PRIMARY KEY,
+  issue_url VARCHAR NOT NULL,
+  comment_url VARCHAR NOT NULL,
+  body TEXT NOT NULL,
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX bot_comment_revisions_issue_url_idx ON bot_comment_revisions (issue_url);
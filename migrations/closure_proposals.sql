@@ -0,0 +1,17 @@
+-- Adds the closure proposals of the butler, see `butler`.
+-- Only needed for Postgres, e.g. `psql -f migrations/closure_proposals.sql`.
+
+CREATE TYPE closure_proposal_status AS ENUM ('pending', 'approved', 'expired');
+
+CREATE TABLE closure_proposals (
+  id SERIAL PRIMARY KEY,
+  issue_source_id BIGINT NOT NULL,
+  issue_url VARCHAR NOT NULL,
+  duplicate_of_html_url VARCHAR NOT NULL,
+  comment_url VARCHAR NOT NULL,
+  status closure_proposal_status NOT NULL DEFAULT 'pending',
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC'),
+  updated_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX closure_proposals_status_idx ON closure_proposals (status);
@@ -0,0 +1,5 @@
+-- Adds the state of comment drafts waiting for an approval in Slack, see `comment_queue.approval`.
+-- Valid for both Postgres and SQLite, e.g. `psql -f migrations/comment_approval.sql`.
+
+ALTER TABLE pending_comments ADD COLUMN awaiting_approval BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE pending_comments ADD COLUMN slack_ts VARCHAR;
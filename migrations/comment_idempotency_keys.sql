@@ -0,0 +1,13 @@
+-- Adds the table of the comments attempted on Hugging Face discussions, see
+-- `comment_queue::comment_fingerprint`.
+-- Only needed for Postgres, SQLite databases get them when connecting, e.g.
+-- `psql -f migrations/comment_idempotency_keys.sql`.
+
+CREATE TABLE comment_idempotency_keys (
+  issue_url VARCHAR NOT NULL,
+  fingerprint VARCHAR NOT NULL,
+  posted BOOLEAN NOT NULL,
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC'),
+  updated_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC'),
+  PRIMARY KEY (issue_url, fingerprint)
+);
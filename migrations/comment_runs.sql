@@ -0,0 +1,10 @@
+-- Adds the runs of the bot's comments, whose feedback links are only accepted for known runs,
+-- see `insert_comment_run`.
+-- Only needed for Postgres, SQLite databases get them when connecting, e.g.
+-- `psql -f migrations/comment_runs.sql`.
+
+CREATE TABLE comment_runs (
+  run_id VARCHAR PRIMARY KEY,
+  issue_url VARCHAR NOT NULL,
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
@@ -0,0 +1,18 @@
+-- Adds the vacuums and reindexes of the issues table, see `compaction`.
+-- Only needed for Postgres, SQLite databases get them when connecting, e.g.
+-- `psql -f migrations/compaction_runs.sql`.
+
+CREATE TABLE compaction_runs (
+  id SERIAL PRIMARY KEY,
+  action VARCHAR NOT NULL,
+  dead_tuples BIGINT NOT NULL,
+  index_churn BIGINT NOT NULL,
+  table_bytes_before BIGINT NOT NULL,
+  table_bytes_after BIGINT NOT NULL,
+  index_bytes_before BIGINT NOT NULL,
+  index_bytes_after BIGINT NOT NULL,
+  duration_ms BIGINT NOT NULL,
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX compaction_runs_action_created_at_idx ON compaction_runs (action, created_at);
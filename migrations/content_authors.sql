@@ -0,0 +1,10 @@
+-- Adds the authors of issues and comments, and the flags leaving the content of authors who opted
+-- out of suggestions, exports and embeddings, see `opt_out`.
+-- Valid for both Postgres and SQLite, e.g. `psql -f migrations/content_authors.sql`.
+
+ALTER TABLE issues ADD COLUMN author VARCHAR;
+ALTER TABLE comments ADD COLUMN author VARCHAR;
+ALTER TABLE issues ADD COLUMN opted_out BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE comments ADD COLUMN opted_out BOOLEAN NOT NULL DEFAULT false;
+CREATE INDEX issues_author_idx ON issues (author);
+CREATE INDEX comments_author_idx ON comments (author);
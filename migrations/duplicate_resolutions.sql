@@ -0,0 +1,26 @@
+-- Adds the tables comparing the bot's suggestions with the duplicates maintainers close issues
+-- as, see `resolutions`.
+-- Only needed for Postgres, SQLite databases get them when connecting, e.g.
+-- `psql -f migrations/duplicate_resolutions.sql`.
+
+CREATE TABLE suggestions (
+  issue_source_id BIGINT NOT NULL,
+  repository_full_name VARCHAR NOT NULL,
+  number INT NOT NULL,
+  rank INT NOT NULL,
+  cosine_similarity DOUBLE PRECISION NOT NULL,
+  run_id VARCHAR NOT NULL,
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC'),
+  PRIMARY KEY (issue_source_id, repository_full_name, number)
+);
+
+CREATE TABLE duplicate_resolutions (
+  issue_source_id BIGINT PRIMARY KEY,
+  repository_full_name VARCHAR NOT NULL,
+  number INT NOT NULL,
+  duplicate_of_number INT NOT NULL,
+  had_suggestions BOOLEAN NOT NULL,
+  -- NULL when the duplicate wasn't among the suggestions
+  suggested_rank INT,
+  resolved_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
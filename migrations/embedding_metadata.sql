@@ -0,0 +1,14 @@
+-- Adds the table of how the stored embeddings were generated, see
+-- `embeddings::EmbeddingMetadata`.
+-- Only needed for Postgres, SQLite databases get them when connecting, e.g.
+-- `psql -f migrations/embedding_metadata.sql`.
+
+CREATE TABLE embedding_metadata (
+  source_id BIGINT PRIMARY KEY REFERENCES issues(source_id) ON DELETE CASCADE,
+  model VARCHAR NOT NULL,
+  endpoint VARCHAR NOT NULL,
+  input_chars INT NOT NULL,
+  input_tokens INT,
+  truncated BOOLEAN NOT NULL,
+  embedded_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
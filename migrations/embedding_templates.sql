@@ -0,0 +1,5 @@
+-- Adds the version of the metadata template issues were embedded with, see
+-- `IssueTextComposer::template_version`.
+-- Valid for both Postgres and SQLite, e.g. `psql -f migrations/embedding_templates.sql`.
+
+ALTER TABLE embedding_metadata ADD COLUMN template VARCHAR;
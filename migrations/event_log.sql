@@ -0,0 +1,27 @@
+-- Adds the per-event processing audit log, see `event_log`.
+-- Only needed for Postgres, SQLite databases get them when connecting, e.g.
+-- `psql -f migrations/event_log.sql`.
+
+CREATE TYPE event_outcome AS ENUM ('commented', 'skipped', 'error', 'handled');
+
+CREATE TABLE event_log (
+  id SERIAL PRIMARY KEY,
+  event_type VARCHAR NOT NULL,
+  description VARCHAR NOT NULL,
+  source_id BIGINT,
+  repository_full_name VARCHAR,
+  issue_number INTEGER,
+  request_id VARCHAR NOT NULL,
+  outcome event_outcome NOT NULL,
+  error TEXT,
+  embed_ms BIGINT,
+  search_ms BIGINT,
+  summarize_ms BIGINT,
+  comment_ms BIGINT,
+  total_ms BIGINT NOT NULL,
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX event_log_repository_issue_idx ON event_log (repository_full_name, issue_number);
+CREATE INDEX event_log_request_id_idx ON event_log (request_id);
+CREATE INDEX event_log_created_at_idx ON event_log (created_at);
@@ -0,0 +1,7 @@
+-- Adds the `queued` outcome of events whose comment wasn't posted yet and the run id linking
+-- them to it, see `Storage::mark_events_commented`.
+-- The type change is only needed for Postgres, the column is valid for both Postgres and SQLite,
+-- e.g. `psql -f migrations/event_log_queued.sql`.
+
+ALTER TYPE event_outcome ADD VALUE IF NOT EXISTS 'queued';
+ALTER TABLE event_log ADD COLUMN run_id varchar;
@@ -0,0 +1,4 @@
+-- Adds the flag leaving issues out of similarity searches, see `retention`.
+-- Valid for both Postgres and SQLite, e.g. `psql -f migrations/excluded_issues.sql`.
+
+ALTER TABLE issues ADD COLUMN excluded BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,12 @@
+-- Adds the votes cast through the feedback links of bot comments, see `feedback`.
+-- Only needed for Postgres, SQLite databases get them when connecting, e.g.
+-- `psql -f migrations/feedback.sql`.
+
+CREATE TABLE feedback (
+  id SERIAL PRIMARY KEY,
+  run_id VARCHAR NOT NULL,
+  vote VARCHAR NOT NULL,
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX feedback_run_id_idx ON feedback (run_id);
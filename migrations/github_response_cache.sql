@@ -0,0 +1,14 @@
+-- Adds the table of the GitHub responses replayed on 304 Not Modified, see
+-- `response_cache`.
+-- Only needed for Postgres, SQLite databases get them when connecting, e.g.
+-- `psql -f migrations/github_response_cache.sql`.
+
+CREATE TABLE github_response_cache (
+  url VARCHAR PRIMARY KEY,
+  etag VARCHAR NOT NULL,
+  link VARCHAR,
+  body BYTEA NOT NULL,
+  cached_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX github_response_cache_cached_at_idx ON github_response_cache (cached_at);
@@ -0,0 +1,5 @@
+-- Adds the time at which an issue was found deleted or transferred away upstream, see
+-- `Storage::mark_issue_gone`.
+-- Valid for both Postgres and SQLite, e.g. `psql -f migrations/gone_issues.sql`.
+
+ALTER TABLE issues ADD COLUMN gone_at timestamp with time zone;
@@ -0,0 +1,5 @@
+-- Adds the hash of the text guidance sections were embedded from, unchanged sections keeping
+-- their embedding when the guidance is re-indexed, see `guidance`.
+-- Valid for both Postgres and SQLite, e.g. `psql -f migrations/guidance_hashes.sql`.
+
+ALTER TABLE guidance_sections ADD COLUMN content_hash VARCHAR;
@@ -0,0 +1,17 @@
+-- Adds the table of the indexed README, CONTRIBUTING and issue templates sections, see
+-- `guidance`.
+-- Only needed for Postgres, SQLite databases get them when connecting, e.g.
+-- `psql -f migrations/guidance_sections.sql`.
+
+CREATE TABLE guidance_sections (
+  id SERIAL PRIMARY KEY,
+  repository_full_name VARCHAR NOT NULL,
+  path VARCHAR NOT NULL,
+  heading TEXT NOT NULL,
+  anchor VARCHAR NOT NULL,
+  content TEXT NOT NULL,
+  embedding halfvec(2560) NOT NULL,
+  indexed_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX guidance_sections_repository_idx ON guidance_sections (repository_full_name);
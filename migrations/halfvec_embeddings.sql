@@ -0,0 +1,16 @@
+-- Converts databases created with full precision `vector(2560)` embeddings to `halfvec(2560)`,
+-- halving the size of the tables and of their indexes, the `binary` vector search quantization
+-- also needs migrations/binary_quantization.sql. Run with
+-- `psql -f migrations/halfvec_embeddings.sql`, the issues table is locked while the embeddings
+-- are converted.
+
+BEGIN;
+
+DROP INDEX IF EXISTS issues_embedding_hnsw_idx;
+
+ALTER TABLE issues ALTER COLUMN embedding TYPE halfvec(2560) USING embedding::halfvec(2560);
+ALTER TABLE knowledge_base ALTER COLUMN embedding TYPE halfvec(2560) USING embedding::halfvec(2560);
+
+CREATE INDEX issues_embedding_hnsw_idx ON issues USING hnsw (embedding halfvec_cosine_ops);
+
+COMMIT;
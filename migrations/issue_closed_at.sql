@@ -0,0 +1,5 @@
+-- Adds when issues were closed, for retention rules restricted to open or closed issues, see
+-- `Storage::set_issue_closed`.
+-- Valid for both Postgres and SQLite, e.g. `psql -f migrations/issue_closed_at.sql`.
+
+ALTER TABLE issues ADD COLUMN closed_at timestamp with time zone;
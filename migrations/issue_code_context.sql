@@ -0,0 +1,5 @@
+-- Adds the snippets of the files issues reference, reused when re-embedding them, see
+-- `Storage::set_code_context`.
+-- Valid for both Postgres and SQLite, e.g. `psql -f migrations/issue_code_context.sql`.
+
+ALTER TABLE issues ADD COLUMN code_context TEXT;
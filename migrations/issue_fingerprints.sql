@@ -0,0 +1,5 @@
+-- Adds the traceback fingerprints of issues, see `fingerprints`.
+-- Valid for both Postgres and SQLite, e.g. `psql -f migrations/issue_fingerprints.sql`.
+
+ALTER TABLE issues ADD COLUMN fingerprint VARCHAR;
+CREATE INDEX issues_fingerprint_idx ON issues (fingerprint);
@@ -0,0 +1,8 @@
+-- Adds the fields parsed from issues created from GitHub issue forms, see
+-- `Storage::set_issue_form`.
+-- Valid for both Postgres and SQLite, e.g. `psql -f migrations/issue_forms.sql`.
+
+ALTER TABLE issues ADD COLUMN form_description TEXT;
+ALTER TABLE issues ADD COLUMN form_reproduction TEXT;
+ALTER TABLE issues ADD COLUMN form_expected_behavior TEXT;
+ALTER TABLE issues ADD COLUMN form_environment TEXT;
@@ -0,0 +1,5 @@
+-- Adds the labels and milestone of issues, see `repositories.<name>.excluded_labels`.
+-- Only needed for Postgres, e.g. `psql -f migrations/issue_labels.sql`.
+
+ALTER TABLE issues ADD COLUMN labels TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE issues ADD COLUMN milestone VARCHAR;
@@ -0,0 +1,14 @@
+-- Adds the links between issues found in their GitHub timeline, see `issue_links`.
+-- Only needed for Postgres, SQLite databases get them when connecting, e.g.
+-- `psql -f migrations/issue_links.sql`.
+
+CREATE TYPE issue_link_kind AS ENUM ('cross_reference', 'fixed_by', 'duplicate');
+
+CREATE TABLE issue_links (
+  repository_full_name VARCHAR NOT NULL,
+  number INTEGER NOT NULL,
+  linked_number INTEGER NOT NULL,
+  kind issue_link_kind NOT NULL,
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC'),
+  PRIMARY KEY (repository_full_name, number, linked_number, kind)
+);
@@ -0,0 +1,11 @@
+-- Adds the environment extracted from the system info section of issues, see
+-- `Storage::set_system_info`.
+-- Valid for both Postgres and SQLite, e.g. `psql -f migrations/issue_system_info.sql`.
+
+ALTER TABLE issues ADD COLUMN package_version VARCHAR;
+ALTER TABLE issues ADD COLUMN python_version VARCHAR;
+ALTER TABLE issues ADD COLUMN torch_version VARCHAR;
+ALTER TABLE issues ADD COLUMN os VARCHAR;
+ALTER TABLE issues ADD COLUMN accelerator VARCHAR;
+ALTER TABLE issues ADD COLUMN gpu VARCHAR;
+CREATE INDEX issues_accelerator_idx ON issues (accelerator);
@@ -0,0 +1,4 @@
+-- Adds the flag holding jobs at their next checkpoint, see `Storage::set_job_paused`.
+-- Valid for both Postgres and SQLite, e.g. `psql -f migrations/job_pause.sql`.
+
+ALTER TABLE jobs ADD COLUMN paused BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,12 @@
+-- Adds the curated knowledge base new issues are answered from, see `knowledge_base`.
+-- Only needed for Postgres, SQLite databases get them when connecting, e.g.
+-- `psql -f migrations/knowledge_base.sql`.
+
+CREATE TABLE knowledge_base (
+  id SERIAL PRIMARY KEY,
+  repository_full_name VARCHAR,
+  question TEXT NOT NULL,
+  answer TEXT NOT NULL,
+  embedding halfvec(2560) NOT NULL,
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
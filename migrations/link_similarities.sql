@@ -0,0 +1,14 @@
+-- Adds the table of the similarities of linked issues sampled per embedding model, see
+-- `drift`.
+-- Only needed for Postgres, SQLite databases get them when connecting, e.g.
+-- `psql -f migrations/link_similarities.sql`.
+
+CREATE TABLE link_similarities (
+  model VARCHAR NOT NULL,
+  repository_full_name VARCHAR NOT NULL,
+  number INT NOT NULL,
+  linked_number INT NOT NULL,
+  cosine_similarity DOUBLE PRECISION NOT NULL,
+  sampled_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC'),
+  PRIMARY KEY (model, repository_full_name, number, linked_number)
+);
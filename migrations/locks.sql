@@ -0,0 +1,9 @@
+-- Adds the lease locks shared by the instances of the bot, see `locks`.
+-- Only needed for Postgres, SQLite databases get them when connecting, e.g.
+-- `psql -f migrations/locks.sql`.
+
+CREATE TABLE locks (
+  name VARCHAR PRIMARY KEY,
+  holder VARCHAR NOT NULL,
+  expires_at timestamp with time zone NOT NULL
+);
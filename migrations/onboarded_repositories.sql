@@ -0,0 +1,11 @@
+-- Adds the table of the repositories onboarded through `POST /onboard`, see
+-- `onboarding`.
+-- Only needed for Postgres, SQLite databases get them when connecting, e.g.
+-- `psql -f migrations/onboarded_repositories.sql`.
+
+CREATE TABLE onboarded_repositories (
+  repository_full_name VARCHAR PRIMARY KEY,
+  hook_id BIGINT,
+  estimated_issues BIGINT NOT NULL,
+  onboarded_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
@@ -0,0 +1,16 @@
+-- Adds the table of the authors who asked to be left out of suggestions and exports, see
+-- `opt_out`.
+-- Only needed for Postgres, SQLite databases get them when connecting, e.g.
+-- `psql -f migrations/opt_out_requests.sql`.
+
+CREATE TABLE opt_out_requests (
+  id SERIAL PRIMARY KEY,
+  login VARCHAR NOT NULL,
+  requested_through VARCHAR NOT NULL,
+  purged BOOLEAN NOT NULL,
+  issues BIGINT NOT NULL,
+  comments BIGINT NOT NULL,
+  requested_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX opt_out_requests_login_idx ON opt_out_requests (login);
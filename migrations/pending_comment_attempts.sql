@@ -0,0 +1,8 @@
+-- Adds the failed attempts at posting queued comments and when to try again, see
+-- `Storage::reschedule_pending_comment`.
+-- Valid for both Postgres and SQLite, e.g. `psql -f migrations/pending_comment_attempts.sql`,
+-- SQLite only taking constant defaults: comments queued with none are due right away.
+
+ALTER TABLE pending_comments ADD COLUMN attempts INT NOT NULL DEFAULT 0;
+ALTER TABLE pending_comments ADD COLUMN last_error TEXT;
+ALTER TABLE pending_comments ADD COLUMN next_attempt_at timestamp with time zone NOT NULL DEFAULT '1970-01-01 00:00:00+00';
@@ -0,0 +1,12 @@
+-- Adds the persisted queue of outbound comments, see `comment_queue`.
+-- Only needed for Postgres, SQLite databases get them when connecting, e.g.
+-- `psql -f migrations/pending_comments.sql`.
+
+CREATE TABLE pending_comments (
+  id SERIAL PRIMARY KEY,
+  source VARCHAR NOT NULL,
+  repository_full_name VARCHAR NOT NULL,
+  issue_url VARCHAR NOT NULL,
+  body TEXT NOT NULL,
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
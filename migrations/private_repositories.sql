@@ -0,0 +1,4 @@
+-- Adds the flag leaving the issues of private Hugging Face repositories out of suggestions and
+-- searches. Valid for both Postgres and SQLite, e.g. `psql -f migrations/private_repositories.sql`.
+
+ALTER TABLE issues ADD COLUMN private BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,7 @@
+-- Adds the prompt profile issues were summarized with to their comment runs, suggestions and
+-- feedback, see `summarization_api.prompts`.
+-- Valid for both Postgres and SQLite, e.g. `psql -f migrations/prompt_profiles.sql`.
+
+ALTER TABLE comment_runs ADD COLUMN prompt_profile VARCHAR;
+ALTER TABLE suggestions ADD COLUMN prompt_profile VARCHAR;
+ALTER TABLE feedback ADD COLUMN prompt_profile VARCHAR;
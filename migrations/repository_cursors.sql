@@ -0,0 +1,9 @@
+-- Adds the table of the last webhook received per GitHub repository, see
+-- `catch_up`.
+-- Only needed for Postgres, SQLite databases get them when connecting, e.g.
+-- `psql -f migrations/repository_cursors.sql`.
+
+CREATE TABLE repository_cursors (
+  repository_full_name VARCHAR PRIMARY KEY,
+  processed_at timestamp with time zone NOT NULL
+);
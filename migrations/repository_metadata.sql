@@ -0,0 +1,11 @@
+-- Adds the metadata of repositories, see `repo_metadata`.
+-- Only needed for Postgres, SQLite databases get them when connecting, e.g.
+-- `psql -f migrations/repository_metadata.sql`.
+
+CREATE TABLE repository_metadata (
+  full_name VARCHAR PRIMARY KEY,
+  description TEXT,
+  stars BIGINT NOT NULL,
+  topics TEXT[] NOT NULL DEFAULT '{}',
+  updated_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
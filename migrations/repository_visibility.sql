@@ -0,0 +1,12 @@
+-- Adds the table of the private Hugging Face repositories, whose new issues are flagged private
+-- when inserted, see `Storage::set_repository_private`.
+-- Only needed for Postgres, SQLite databases get them when connecting, e.g.
+-- `psql -f migrations/repository_visibility.sql`.
+
+CREATE TABLE private_repositories (
+  repository_full_name VARCHAR PRIMARY KEY,
+  updated_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+INSERT INTO private_repositories (repository_full_name)
+  SELECT DISTINCT repository_full_name FROM issues WHERE private;
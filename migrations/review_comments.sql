@@ -0,0 +1,5 @@
+-- Adds the flag telling the comments made on the diff of pull requests apart, see
+-- `ReviewCommentData`.
+-- Valid for both Postgres and SQLite, e.g. `psql -f migrations/review_comments.sql`.
+
+ALTER TABLE comments ADD COLUMN is_review BOOLEAN NOT NULL DEFAULT false;
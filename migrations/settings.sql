@@ -0,0 +1,9 @@
+-- Adds the settings tunable at runtime, see `settings`.
+-- Only needed for Postgres, SQLite databases get them when connecting, e.g.
+-- `psql -f migrations/settings.sql`.
+
+CREATE TABLE settings (
+  key VARCHAR PRIMARY KEY,
+  value VARCHAR NOT NULL,
+  updated_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
@@ -0,0 +1,11 @@
+-- Adds the table of the Slack notifications waiting for their repository's batch window to
+-- end, see `Slack::closest_issues`.
+-- Only needed for Postgres, SQLite databases get them when connecting, e.g.
+-- `psql -f migrations/slack_batches.sql`.
+
+CREATE TABLE slack_batches (
+  id SERIAL PRIMARY KEY,
+  repository_full_name VARCHAR NOT NULL,
+  payload JSONB NOT NULL,
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
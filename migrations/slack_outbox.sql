@@ -0,0 +1,14 @@
+-- Adds the table of the Slack notifications waiting to be delivered again, see
+-- `slack_outbox`.
+-- Only needed for Postgres, SQLite databases get them when connecting, e.g.
+-- `psql -f migrations/slack_outbox.sql`.
+
+CREATE TABLE slack_outbox (
+  id SERIAL PRIMARY KEY,
+  repository_full_name VARCHAR NOT NULL,
+  payload JSONB NOT NULL,
+  attempts INT NOT NULL DEFAULT 0,
+  last_error TEXT,
+  next_attempt_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC'),
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
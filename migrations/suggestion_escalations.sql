@@ -0,0 +1,23 @@
+-- Adds the tables tracking which issues new ones were matched with, see `escalation`.
+-- Only needed for Postgres, SQLite databases get them when connecting, e.g.
+-- `psql -f migrations/suggestion_escalations.sql`.
+
+CREATE TABLE suggestion_targets (
+  id SERIAL PRIMARY KEY,
+  repository_full_name VARCHAR NOT NULL,
+  number INT NOT NULL,
+  issue_repository_full_name VARCHAR NOT NULL,
+  issue_number INT NOT NULL,
+  issue_html_url VARCHAR NOT NULL,
+  issue_title TEXT NOT NULL,
+  created_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX suggestion_targets_target_idx ON suggestion_targets (repository_full_name, number, created_at);
+
+CREATE TABLE escalations (
+  repository_full_name VARCHAR NOT NULL,
+  number INT NOT NULL,
+  escalated_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC'),
+  PRIMARY KEY (repository_full_name, number)
+);
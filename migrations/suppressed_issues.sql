@@ -0,0 +1,10 @@
+-- Adds the table of the issues maintainers asked the bot to leave alone, see
+-- `suppression`.
+-- Only needed for Postgres, SQLite databases get them when connecting, e.g.
+-- `psql -f migrations/suppressed_issues.sql`.
+
+CREATE TABLE suppressed_issues (
+  source_id BIGINT PRIMARY KEY,
+  suppressed_by VARCHAR NOT NULL,
+  suppressed_at timestamp with time zone NOT NULL DEFAULT (current_timestamp AT TIME ZONE 'UTC')
+);